@@ -3,19 +3,22 @@ use crate::actor::Actor;
 use crate::discover::config::{
     load_ton_config, read_ton_config, LiteServer, LiteServerId, TonConfig,
 };
-use futures::{Stream, StreamExt, TryStreamExt};
+use anyhow::anyhow;
+use futures::{stream, Stream, StreamExt};
 use hickory_resolver::error::ResolveError;
 use hickory_resolver::system_conf::read_system_conf;
 use hickory_resolver::TokioAsyncResolver;
 use reqwest::Url;
 use std::collections::HashSet;
 use std::convert::Infallible;
+use std::fmt::Display;
 use std::net::IpAddr;
 use std::path::PathBuf;
 use std::pin::Pin;
+use std::sync::Arc;
 use std::task::{ready, Context, Poll};
 use std::time::Duration;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, watch, Notify};
 use tokio::time::Interval;
 use tokio_stream::wrappers::IntervalStream;
 use tokio_util::sync::{CancellationToken, DropGuard};
@@ -23,38 +26,108 @@ use tower::discover::Change;
 
 pub mod config;
 
+/// Reads the config on every tick of `interval` and on every refresh triggered by `refresh`
 pub fn read_ton_config_from_file_stream(
     path: PathBuf,
     interval: Interval,
+    refresh: &ConfigRefresh,
 ) -> impl Stream<Item = Result<TonConfig, anyhow::Error>> {
-    IntervalStream::new(interval)
+    refresh
+        .ticks(interval)
         .map(move |_| path.clone())
         .then(read_ton_config)
 }
 
+/// Loads the config on every tick of `interval` and on every refresh triggered by `refresh`
 pub fn read_ton_config_from_url_stream(
     url: Url,
     interval: Interval,
+    refresh: &ConfigRefresh,
 ) -> impl Stream<Item = Result<TonConfig, anyhow::Error>> {
-    IntervalStream::new(interval)
+    refresh
+        .ticks(interval)
         .map(move |_| url.clone())
         .then(load_ton_config)
 }
 
+/// Lite servers found by a single refresh of the config, by id
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Refreshed {
+    pub lite_servers: usize,
+    pub inserted: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+/// Triggers refreshes of the config out of schedule and reports their outcomes
+#[derive(Debug, Clone)]
+pub struct ConfigRefresh {
+    notify: Arc<Notify>,
+    outcome: Arc<watch::Sender<Option<Result<Refreshed, String>>>>,
+}
+
+impl Default for ConfigRefresh {
+    fn default() -> Self {
+        Self {
+            notify: Default::default(),
+            outcome: Arc::new(watch::channel(None).0),
+        }
+    }
+}
+
+impl ConfigRefresh {
+    fn ticks(&self, interval: Interval) -> impl Stream<Item = ()> {
+        let triggered = stream::unfold(self.notify.clone(), |notify| async move {
+            notify.notified().await;
+
+            Some(((), notify))
+        });
+
+        stream::select(IntervalStream::new(interval).map(|_| ()), triggered)
+    }
+
+    fn report(&self, outcome: Result<Refreshed, String>) {
+        self.outcome.send_replace(Some(outcome));
+    }
+
+    /// Refreshes the config now and waits for the outcome, the pool is kept as is on failure
+    pub async fn refresh(&self) -> anyhow::Result<Refreshed> {
+        let mut outcome = self.outcome.subscribe();
+        self.notify.notify_one();
+        outcome
+            .changed()
+            .await
+            .map_err(|_| anyhow!("discovery is stopped"))?;
+
+        let outcome = outcome.borrow_and_update().clone();
+        outcome
+            .ok_or_else(|| anyhow!("config is not refreshed"))?
+            .map_err(|e| anyhow!(e))
+    }
+}
+
 pub struct LiteServerDiscoverActor<S> {
     stream: S,
     sender: mpsc::Sender<Change<LiteServerId, TonConfig>>,
+    refresh: ConfigRefresh,
 }
 
 impl<S> LiteServerDiscoverActor<S> {
-    pub fn new(stream: S, sender: mpsc::Sender<Change<LiteServerId, TonConfig>>) -> Self {
-        Self { stream, sender }
+    pub fn new(
+        stream: S,
+        sender: mpsc::Sender<Change<LiteServerId, TonConfig>>,
+        refresh: ConfigRefresh,
+    ) -> Self {
+        Self {
+            stream,
+            sender,
+            refresh,
+        }
     }
 }
 
 impl<S, E> Actor for LiteServerDiscoverActor<S>
 where
-    E: Send,
+    E: Send + Display,
     S: Send + 'static,
     S: Stream<Item = Result<TonConfig, E>>,
 {
@@ -65,15 +138,38 @@ where
         tokio::pin!(stream);
 
         let dns = dns_resolver();
-        let mut liteservers = HashSet::default();
+        let mut liteservers: HashSet<LiteServer> = HashSet::default();
 
-        while let Ok(Some(new_config)) = stream.try_next().await {
+        while let Some(new_config) = stream.next().await {
             tracing::info!("tick service discovery");
 
+            // the running pool is kept until a config with lite servers is read
+            let new_config = match new_config {
+                Ok(config) if !config.liteservers.is_empty() => config,
+                Ok(_) => {
+                    tracing::warn!("config without lite servers, pool is kept");
+                    self.refresh.report(Err("no lite servers".to_owned()));
+
+                    continue;
+                }
+                Err(e) => {
+                    tracing::warn!(error = %e, "failed to read config, pool is kept");
+                    self.refresh.report(Err(format!("{:#}", e)));
+
+                    continue;
+                }
+            };
+
             let mut liteserver_new: HashSet<LiteServer> = HashSet::default();
             for ls in new_config.liteservers.iter() {
                 match apply_dns(dns.clone(), ls.clone()).await {
-                    Err(e) => tracing::error!("dns error: {:?}", e),
+                    Err(e) => {
+                        tracing::error!("dns error: {:?}", e);
+                        // keeps the address resolved before
+                        if let Some(known) = liteservers.iter().find(|known| known.id == ls.id) {
+                            liteserver_new.insert(known.clone());
+                        }
+                    }
                     Ok(ls) => {
                         liteserver_new.insert(ls);
                     }
@@ -110,6 +206,11 @@ where
                     .await;
             }
 
+            self.refresh.report(Ok(Refreshed {
+                lite_servers: liteserver_new.len(),
+                inserted: insert.iter().map(|ls| ls.id()).collect(),
+                removed: remove.iter().map(|ls| ls.id()).collect(),
+            }));
             liteservers.clone_from(&liteserver_new);
         }
     }
@@ -121,13 +222,18 @@ pub struct LiteServerDiscover {
 }
 
 impl LiteServerDiscover {
-    pub fn new<S>(stream: S) -> Self
+    /// Removed lite servers leave routing at once, requests in flight to them are completed
+    pub fn new<S>(stream: S, refresh: ConfigRefresh) -> Self
     where
         LiteServerDiscoverActor<S>: Actor,
     {
         let token = CancellationToken::new();
         let (tx, rx) = mpsc::channel(100);
-        CancellableActor::new(LiteServerDiscoverActor::new(stream, tx), token.clone()).spawn();
+        CancellableActor::new(
+            LiteServerDiscoverActor::new(stream, tx, refresh),
+            token.clone(),
+        )
+        .spawn();
 
         Self {
             receiver: rx,
@@ -170,3 +276,93 @@ async fn apply_dns(
 
     Ok(ls)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn config(keys: &[&str]) -> TonConfig {
+        serde_json::from_value(json!({
+            "@type": "config.global",
+            "liteservers": keys
+                .iter()
+                .map(|key| json!({
+                    "id": { "@type": "pub.ed25519", "key": key },
+                    "ip": 1,
+                    "port": 1
+                }))
+                .collect::<Vec<_>>()
+        }))
+        .unwrap()
+    }
+
+    fn id(key: &str) -> String {
+        format!("pub.ed25519:{}", key)
+    }
+
+    #[tokio::test]
+    async fn failed_reads_keep_the_pool() {
+        let configs: Vec<Result<TonConfig, anyhow::Error>> = vec![
+            Ok(config(&["a", "b"])),
+            Err(anyhow!("unreachable")),
+            Ok(config(&[])),
+            Ok(config(&["b", "c"])),
+        ];
+        let refresh = ConfigRefresh::default();
+        let (tx, mut rx) = mpsc::channel(100);
+
+        LiteServerDiscoverActor::new(stream::iter(configs), tx, refresh.clone())
+            .run()
+            .await;
+
+        let mut changes = Vec::new();
+        while let Some(change) = rx.recv().await {
+            changes.push(match change {
+                Change::Insert(id, _) => format!("+{}", id.key),
+                Change::Remove(id) => format!("-{}", id.key),
+            });
+        }
+        changes[..2].sort();
+        assert_eq!(changes, vec!["+a", "+b", "-a", "+c"]);
+        assert_eq!(
+            *refresh.outcome.borrow(),
+            Some(Ok(Refreshed {
+                lite_servers: 2,
+                inserted: vec![id("c")],
+                removed: vec![id("a")],
+            }))
+        );
+    }
+
+    #[tokio::test]
+    async fn refresh_reads_config_out_of_schedule() {
+        let path = std::env::temp_dir().join(format!("config-{}.json", std::process::id()));
+        tokio::fs::write(&path, config(&["a"]).to_string())
+            .await
+            .unwrap();
+        let refresh = ConfigRefresh::default();
+        let interval = tokio::time::interval_at(
+            tokio::time::Instant::now() + Duration::from_secs(3600),
+            Duration::from_secs(3600),
+        );
+        let _discover = LiteServerDiscover::new(
+            read_ton_config_from_file_stream(path.clone(), interval, &refresh),
+            refresh.clone(),
+        );
+
+        let refreshed = refresh.refresh().await.unwrap();
+        tokio::fs::remove_file(&path).await.unwrap();
+        let failed = refresh.refresh().await;
+
+        assert_eq!(
+            refreshed,
+            Refreshed {
+                lite_servers: 1,
+                inserted: vec![id("a")],
+                removed: vec![],
+            }
+        );
+        assert!(failed.is_err());
+    }
+}
@@ -1,11 +1,13 @@
 use crate::router::route::ToRoute;
-use crate::router::{Routed, Router};
+use crate::router::{RouteObserver, Routed, Router};
 use futures::FutureExt;
 use futures::TryFutureExt;
+use std::borrow::Cow;
 use std::fmt::Debug;
 use std::future::Future;
 use std::hash::Hash;
 use std::pin::Pin;
+use std::sync::Arc;
 use std::task::{Context, Poll};
 use tower::discover::Discover;
 use tower::load::Load;
@@ -29,6 +31,34 @@ where
 
         Balance { router }
     }
+
+    /// See [`Router::set_latency_aware_routing`]
+    pub fn set_latency_aware_routing(mut self, enabled: bool) -> Self {
+        self.router = self.router.set_latency_aware_routing(enabled);
+
+        self
+    }
+
+    /// See [`Router::set_max_lag`]
+    pub fn set_max_lag(mut self, max_lag: Option<i32>) -> Self {
+        self.router = self.router.set_max_lag(max_lag);
+
+        self
+    }
+
+    /// See [`Router::set_network`]
+    pub fn set_network(mut self, network: impl Into<Cow<'static, str>>) -> Self {
+        self.router = self.router.set_network(network);
+
+        self
+    }
+
+    /// See [`Router::set_route_observer`]
+    pub fn set_route_observer(mut self, observer: Option<Arc<dyn RouteObserver>>) -> Self {
+        self.router = self.router.set_route_observer(observer);
+
+        self
+    }
 }
 
 impl<S, R, D> Service<R> for Balance<S, D>
@@ -59,3 +89,126 @@ where
             .boxed()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::router::latency::{LatencyStats, LatencyTracker};
+    use crate::router::route::{BlockCriteria, Route};
+    use crate::router::MIN_LATENCY_SAMPLES;
+    use futures::future::BoxFuture;
+    use std::convert::Infallible;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::time::Instant;
+    use tower::discover::ServiceList;
+
+    struct Latest;
+
+    impl ToRoute for Latest {
+        fn to_route(&self) -> Route {
+            Route::Latest
+        }
+    }
+
+    /// Transport answering every request after `delay`
+    #[derive(Clone)]
+    struct MockTransport {
+        delay: Duration,
+        latency: LatencyTracker,
+        calls: Arc<AtomicUsize>,
+    }
+
+    impl MockTransport {
+        fn new(delay: Duration) -> Self {
+            Self {
+                delay,
+                latency: LatencyTracker::default(),
+                calls: Default::default(),
+            }
+        }
+
+        fn calls(&self) -> usize {
+            self.calls.load(Ordering::Relaxed)
+        }
+    }
+
+    impl Service<Latest> for MockTransport {
+        type Response = ();
+        type Error = Infallible;
+        type Future = BoxFuture<'static, Result<(), Infallible>>;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _: Latest) -> Self::Future {
+            self.calls.fetch_add(1, Ordering::Relaxed);
+            let delay = self.delay;
+            let latency = self.latency.clone();
+
+            async move {
+                let start = Instant::now();
+                tokio::time::sleep(delay).await;
+                latency.record(start.elapsed(), true);
+
+                Ok(())
+            }
+            .boxed()
+        }
+    }
+
+    impl Load for MockTransport {
+        type Metric = usize;
+
+        fn load(&self) -> Self::Metric {
+            0
+        }
+    }
+
+    impl Routed for MockTransport {
+        fn contains(&self, _: &i32, _: &BlockCriteria) -> bool {
+            true
+        }
+        fn contains_not_available(&self, _: &i32, _: &BlockCriteria) -> bool {
+            false
+        }
+        fn last_seqno(&self) -> Option<i32> {
+            Some(1)
+        }
+        fn latency(&self) -> Option<LatencyStats> {
+            self.latency.stats()
+        }
+    }
+
+    async fn send(latency_aware: bool, requests: usize) -> (MockTransport, MockTransport) {
+        let fast = MockTransport::new(Duration::from_millis(5));
+        let slow = MockTransport::new(Duration::from_millis(50));
+        let mut balance = Balance::new(ServiceList::new(vec![fast.clone(), slow.clone()]))
+            .set_latency_aware_routing(latency_aware);
+
+        for _ in 0..requests {
+            (&mut balance).oneshot(Latest).await.unwrap();
+        }
+
+        (fast, slow)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn latency_aware_routing_skews_to_fast_transport() {
+        let (fast, slow) = send(true, 200).await;
+
+        assert_eq!(slow.calls(), MIN_LATENCY_SAMPLES as usize);
+        assert_eq!(fast.calls(), 200 - MIN_LATENCY_SAMPLES as usize);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn load_balancing_ignores_latency() {
+        let (fast, slow) = send(false, 200).await;
+
+        assert_eq!(fast.calls() + slow.calls(), 200);
+        assert!(fast.calls() > 50, "{}", fast.calls());
+        assert!(slow.calls() > 50, "{}", slow.calls());
+    }
+}
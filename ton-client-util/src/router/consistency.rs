@@ -0,0 +1,74 @@
+use std::future::Future;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Arc;
+
+tokio::task_local! {
+    static SESSION: Arc<Session>;
+}
+
+/// Masterchain seqno a client has observed, its requests are routed to upstreams which have
+/// reached it so that a client never reads a state older than the one it has seen, see [`scope`]
+#[derive(Debug, Default)]
+pub struct Session {
+    seqno: AtomicI32,
+}
+
+impl Session {
+    /// The highest masterchain seqno observed, none before the first request
+    pub fn seqno(&self) -> Option<i32> {
+        match self.seqno.load(Ordering::Relaxed) {
+            0 => None,
+            seqno => Some(seqno),
+        }
+    }
+
+    pub fn observe(&self, seqno: i32) {
+        self.seqno.fetch_max(seqno, Ordering::Relaxed);
+    }
+}
+
+/// Requests made by `f` belong to `session`
+pub async fn scope<F: Future>(session: Arc<Session>, f: F) -> F::Output {
+    SESSION.scope(session, f).await
+}
+
+/// [`scope`] for a single poll, e.g. of a stream made on behalf of a request
+pub fn sync_scope<R>(session: Arc<Session>, f: impl FnOnce() -> R) -> R {
+    SESSION.sync_scope(session, f)
+}
+
+/// Session of the request the current task works on
+pub fn current() -> Option<Arc<Session>> {
+    SESSION.try_with(Arc::clone).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn highest_seqno_is_kept() {
+        let session = Arc::new(Session::default());
+        assert_eq!(session.seqno(), None);
+
+        assert!(current().is_none());
+        scope(session.clone(), async {
+            let session = current().unwrap();
+            session.observe(10);
+            session.observe(8);
+        })
+        .await;
+
+        assert_eq!(session.seqno(), Some(10));
+    }
+
+    #[test]
+    fn sync_scope_sees_session() {
+        let session = Arc::new(Session::default());
+
+        sync_scope(session.clone(), || current().unwrap().observe(5));
+
+        assert_eq!(session.seqno(), Some(5));
+        assert!(current().is_none());
+    }
+}
@@ -0,0 +1,65 @@
+use crate::router::route::Route;
+use std::future::Future;
+use tokio::task::futures::TaskLocalFuture;
+
+tokio::task_local! {
+    static HINT: Option<Route>;
+}
+
+/// Requests to the latest block made by `f` are routed by `hint` instead, e.g. to lite servers
+/// which have a given block. Requests of a given block keep their own route
+pub fn scope<F: Future>(hint: Option<Route>, f: F) -> TaskLocalFuture<Option<Route>, F> {
+    HINT.scope(hint, f)
+}
+
+/// [`scope`] for a single call
+pub fn sync_scope<R>(hint: Option<Route>, f: impl FnOnce() -> R) -> R {
+    HINT.sync_scope(hint, f)
+}
+
+/// Hint of the request the current task works on
+pub fn current() -> Option<Route> {
+    HINT.try_with(|hint| *hint).ok().flatten()
+}
+
+/// `route` of a request, or the hint of the current task if it's routed to the latest block
+pub fn apply(route: Route) -> Route {
+    match (route, current()) {
+        (Route::Latest, Some(hint)) => hint,
+        (route, _) => route,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::router::route::BlockCriteria;
+
+    fn block(chain: i32, seqno: i32) -> Route {
+        Route::Block {
+            chain,
+            criteria: BlockCriteria::Seqno {
+                shard: i64::MIN,
+                seqno,
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn latest_is_routed_by_hint() {
+        assert_eq!(apply(Route::Latest), Route::Latest);
+
+        scope(Some(block(-1, 42)), async {
+            assert_eq!(apply(Route::Latest), block(-1, 42));
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn block_keeps_its_route() {
+        scope(Some(block(-1, 42)), async {
+            assert_eq!(apply(block(0, 7)), block(0, 7));
+        })
+        .await;
+    }
+}
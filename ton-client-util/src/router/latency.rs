@@ -0,0 +1,287 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Sub-buckets per power of two, values are kept with ~6% relative error
+const SUB_BUCKET_BITS: u32 = 4;
+const SUB_BUCKETS: usize = 1 << SUB_BUCKET_BITS;
+/// Latencies are tracked in microseconds up to 2^40, about 12 days
+const MAX_MAGNITUDE: u32 = 40;
+const BUCKETS: usize = (MAX_MAGNITUDE as usize - SUB_BUCKET_BITS as usize + 2) * SUB_BUCKETS;
+
+/// Each generation of the rolling window lasts this long, the window covers the last two
+pub const LATENCY_WINDOW_PERIOD: Duration = Duration::from_secs(30);
+
+fn bucket(micros: u64) -> usize {
+    let micros = micros.min((1 << (MAX_MAGNITUDE + 1)) - 1);
+    if micros < SUB_BUCKETS as u64 {
+        return micros as usize;
+    }
+
+    let magnitude = 63 - micros.leading_zeros();
+    let sub_bucket = (micros >> (magnitude - SUB_BUCKET_BITS)) as usize & (SUB_BUCKETS - 1);
+
+    (magnitude - SUB_BUCKET_BITS + 1) as usize * SUB_BUCKETS + sub_bucket
+}
+
+/// The highest value which falls into `index`
+fn highest_equivalent(index: usize) -> u64 {
+    if index < SUB_BUCKETS {
+        return index as u64;
+    }
+
+    let magnitude = (index / SUB_BUCKETS) as u32 + SUB_BUCKET_BITS - 1;
+    let lowest = ((SUB_BUCKETS + index % SUB_BUCKETS) as u64) << (magnitude - SUB_BUCKET_BITS);
+
+    lowest + (1 << (magnitude - SUB_BUCKET_BITS)) - 1
+}
+
+/// Log-linear histogram of latencies, in the manner of HdrHistogram
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    counts: Box<[u64]>,
+    total: u64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            counts: vec![0; BUCKETS].into_boxed_slice(),
+            total: 0,
+        }
+    }
+}
+
+impl Histogram {
+    pub fn record(&mut self, latency: Duration) {
+        self.counts[bucket(latency.as_micros() as u64)] += 1;
+        self.total += 1;
+    }
+
+    pub fn merge(&mut self, other: &Histogram) {
+        for (count, other) in self.counts.iter_mut().zip(other.counts.iter()) {
+            *count += other;
+        }
+        self.total += other.total;
+    }
+
+    pub fn len(&self) -> u64 {
+        self.total
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.total == 0
+    }
+
+    /// Latency below which `quantile` of the samples are, `None` when there are no samples
+    pub fn quantile(&self, quantile: f64) -> Option<Duration> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let rank = ((quantile.clamp(0.0, 1.0) * self.total as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (index, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Some(Duration::from_micros(highest_equivalent(index)));
+            }
+        }
+
+        None
+    }
+
+    fn clear(&mut self) {
+        self.counts.fill(0);
+        self.total = 0;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LatencyStats {
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+    pub requests: u64,
+    pub errors: u64,
+}
+
+impl LatencyStats {
+    pub fn error_rate(&self) -> f64 {
+        if self.requests == 0 {
+            return 0.0;
+        }
+
+        self.errors as f64 / self.requests as f64
+    }
+}
+
+#[derive(Debug, Default)]
+struct Generation {
+    latencies: Histogram,
+    requests: u64,
+    errors: u64,
+}
+
+impl Generation {
+    fn clear(&mut self) {
+        self.latencies.clear();
+        self.requests = 0;
+        self.errors = 0;
+    }
+}
+
+#[derive(Debug)]
+struct Window {
+    period: Duration,
+    started_at: Instant,
+    current: Generation,
+    previous: Generation,
+}
+
+impl Window {
+    fn rotate(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.started_at);
+        if elapsed < self.period {
+            return;
+        }
+
+        if elapsed < self.period * 2 {
+            std::mem::swap(&mut self.current, &mut self.previous);
+        } else {
+            self.previous.clear();
+        }
+        self.current.clear();
+        self.started_at = now;
+    }
+}
+
+/// Latencies and errors of one upstream over a rolling window, shared by its clones
+#[derive(Debug, Clone)]
+pub struct LatencyTracker {
+    window: Arc<Mutex<Window>>,
+}
+
+impl Default for LatencyTracker {
+    fn default() -> Self {
+        Self::new(LATENCY_WINDOW_PERIOD)
+    }
+}
+
+impl LatencyTracker {
+    pub fn new(period: Duration) -> Self {
+        Self {
+            window: Arc::new(Mutex::new(Window {
+                period,
+                started_at: Instant::now(),
+                current: Default::default(),
+                previous: Default::default(),
+            })),
+        }
+    }
+
+    /// Records a request which took `latency`, failed ones count towards the error rate
+    pub fn record(&self, latency: Duration, success: bool) {
+        let mut window = self.window.lock().unwrap();
+        window.rotate(Instant::now());
+
+        window.current.requests += 1;
+        if success {
+            window.current.latencies.record(latency);
+        } else {
+            window.current.errors += 1;
+        }
+    }
+
+    /// Percentiles of successful requests over the window, `None` when nothing was recorded
+    pub fn stats(&self) -> Option<LatencyStats> {
+        let mut window = self.window.lock().unwrap();
+        window.rotate(Instant::now());
+
+        let requests = window.current.requests + window.previous.requests;
+        if requests == 0 {
+            return None;
+        }
+
+        let mut latencies = window.previous.latencies.clone();
+        latencies.merge(&window.current.latencies);
+
+        Some(LatencyStats {
+            p50: latencies.quantile(0.5).unwrap_or_default(),
+            p95: latencies.quantile(0.95).unwrap_or_default(),
+            p99: latencies.quantile(0.99).unwrap_or_default(),
+            requests,
+            errors: window.current.errors + window.previous.errors,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buckets_are_monotonic_and_tight() {
+        let mut last = 0;
+        for micros in (0..100_000).chain([1 << 30, (1 << 40) - 1]) {
+            let index = bucket(micros);
+            assert!(index >= last, "{}", micros);
+            assert!(highest_equivalent(index) >= micros, "{}", micros);
+            assert!(
+                highest_equivalent(index) - micros <= micros / SUB_BUCKETS as u64,
+                "{}",
+                micros
+            );
+            last = index;
+        }
+
+        assert_eq!(bucket(u64::MAX), BUCKETS - 1);
+    }
+
+    #[test]
+    fn quantiles() {
+        let mut histogram = Histogram::default();
+        assert_eq!(histogram.quantile(0.5), None);
+
+        for millis in 1..=100 {
+            histogram.record(Duration::from_millis(millis));
+        }
+
+        let within = |quantile: f64, millis: u64| {
+            let value = histogram.quantile(quantile).unwrap();
+            let expected = Duration::from_millis(millis);
+
+            assert!(value >= expected, "{:?} < {:?}", value, expected);
+            assert!(value <= expected + expected / 16, "{:?}", value);
+        };
+        within(0.5, 50);
+        within(0.95, 95);
+        within(0.99, 99);
+        within(1.0, 100);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn window_forgets_old_generations() {
+        let tracker = LatencyTracker::new(Duration::from_secs(10));
+        assert_eq!(tracker.stats(), None);
+
+        tracker.record(Duration::from_millis(500), true);
+        tracker.record(Duration::from_millis(500), false);
+        tokio::time::advance(Duration::from_secs(11)).await;
+        tracker.record(Duration::from_millis(10), true);
+
+        let stats = tracker.stats().unwrap();
+        assert_eq!(stats.requests, 3);
+        assert_eq!(stats.errors, 1);
+        assert!(stats.p99 >= Duration::from_millis(500));
+
+        tokio::time::advance(Duration::from_secs(10)).await;
+        let stats = tracker.stats().unwrap();
+        assert_eq!(stats.requests, 1);
+        assert_eq!(stats.error_rate(), 0.0);
+        assert!(stats.p99 < Duration::from_millis(11));
+
+        tokio::time::advance(Duration::from_secs(25)).await;
+        assert_eq!(tracker.stats(), None);
+    }
+}
@@ -1,15 +1,23 @@
 pub mod balance;
+pub mod consistency;
+pub mod hint;
+pub mod latency;
 pub mod route;
 pub mod shard_prefix;
 
+use crate::router::consistency::Session;
+use crate::router::latency::LatencyStats;
 use crate::router::route::{BlockCriteria, Error, Route, ToRoute};
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::convert::Infallible;
 use std::fmt::Debug;
 use std::future::{ready, Ready};
 use std::hash::Hash;
 use std::pin::Pin;
+use std::sync::Arc;
 use std::task::{ready, Context, Poll};
+use std::time::Duration;
 use tower::balance::p2c::Balance;
 use tower::discover::{Change, Discover, ServiceList};
 use tower::{BoxError, Service};
@@ -18,6 +26,79 @@ pub trait Routed {
     fn contains(&self, chain: &i32, criteria: &BlockCriteria) -> bool;
     fn contains_not_available(&self, chain: &i32, criteria: &BlockCriteria) -> bool;
     fn last_seqno(&self) -> Option<i32>;
+
+    /// Recent latencies, consumed by latency aware routing
+    fn latency(&self) -> Option<LatencyStats> {
+        None
+    }
+
+    /// Has every block since the first one
+    fn archival(&self) -> bool {
+        false
+    }
+}
+
+/// Told of every request routed, e.g. to count where the traffic goes
+pub trait RouteObserver: Send + Sync {
+    /// `archival` if only archival upstreams were routed to for the block of the request
+    fn observe(&self, route: &Route, archival: bool);
+}
+
+/// Upstreams failing more often are skipped by latency aware routing
+pub const MAX_ERROR_RATE: f64 = 0.5;
+/// Upstreams with fewer requests in the window are tried first to measure them
+const MIN_LATENCY_SAMPLES: u64 = 16;
+
+/// The healthy upstream with the lowest p95, or all of them when none is healthy
+fn fastest<S: Routed>(services: Vec<S>) -> Vec<S> {
+    let fastest = services
+        .iter()
+        .enumerate()
+        .filter_map(|(i, s)| match s.latency() {
+            None => Some((i, Duration::ZERO)),
+            Some(stats) if stats.requests < MIN_LATENCY_SAMPLES => Some((i, Duration::ZERO)),
+            Some(stats) if stats.error_rate() > MAX_ERROR_RATE => None,
+            Some(stats) => Some((i, stats.p95)),
+        })
+        .min_by_key(|(_, p95)| *p95)
+        .map(|(i, _)| i);
+
+    match fastest {
+        Some(i) => services.into_iter().skip(i).take(1).collect(),
+        None => services,
+    }
+}
+
+/// Upstreams at most `max_lag` masterchain blocks behind `max_seqno`, the most recent one of the pool
+fn within_lag<S: Routed>(services: Vec<S>, max_seqno: Option<i32>, max_lag: i32) -> Vec<S> {
+    let Some(max_seqno) = max_seqno else {
+        return services;
+    };
+
+    services
+        .into_iter()
+        .filter(|s| {
+            s.last_seqno()
+                .is_some_and(|seqno| seqno >= max_seqno.saturating_sub(max_lag))
+        })
+        .collect()
+}
+
+/// Upstreams which have reached the masterchain seqno of the session, the session observes the
+/// oldest of them as a response comes from any one
+fn caught_up<S: Routed>(services: Vec<S>, session: &Session) -> Vec<S> {
+    let services: Vec<_> = match session.seqno() {
+        Some(seqno) => services
+            .into_iter()
+            .filter(|s| s.last_seqno().is_some_and(|last| last >= seqno))
+            .collect(),
+        None => services,
+    };
+    if let Some(seqno) = services.iter().filter_map(|s| s.last_seqno()).min() {
+        session.observe(seqno);
+    }
+
+    services
 }
 
 pub struct Router<S, D>
@@ -27,6 +108,10 @@ where
 {
     discover: D,
     services: HashMap<D::Key, S>,
+    latency_aware: bool,
+    max_lag: Option<i32>,
+    observer: Option<Arc<dyn RouteObserver>>,
+    network: Cow<'static, str>,
 }
 
 impl<S, D> Router<S, D>
@@ -45,6 +130,14 @@ where
             "ton_router_delayed_count",
             "Count of delayed requests in router"
         );
+        metrics::describe_counter!(
+            "ton_router_lagging_count",
+            "Count of requests delayed as upstreams having the block lag behind the pool"
+        );
+        metrics::describe_counter!(
+            "ton_router_session_lagging_count",
+            "Count of requests delayed as upstreams lag behind the block their session has observed"
+        );
         metrics::describe_counter!(
             "ton_router_delayed_hit_count",
             "Count of delayed request hits in router"
@@ -57,6 +150,85 @@ where
         Self {
             discover,
             services: Default::default(),
+            latency_aware: false,
+            max_lag: None,
+            observer: None,
+            network: Cow::Borrowed(""),
+        }
+    }
+
+    /// Label of the metrics of the router, empty unless set
+    pub fn set_network(mut self, network: impl Into<Cow<'static, str>>) -> Self {
+        self.network = network.into();
+
+        self
+    }
+
+    pub fn set_route_observer(mut self, observer: Option<Arc<dyn RouteObserver>>) -> Self {
+        self.observer = observer;
+
+        self
+    }
+
+    /// Never route requests of a block to upstreams this many masterchain blocks behind the pool,
+    /// requests of the latest block already go to the most recent upstreams
+    pub fn set_max_lag(mut self, max_lag: Option<i32>) -> Self {
+        self.max_lag = max_lag;
+
+        self
+    }
+
+    /// Send requests to the healthy upstream with the lowest p95 instead of balancing them by load
+    pub fn set_latency_aware_routing(mut self, enabled: bool) -> Self {
+        self.latency_aware = enabled;
+
+        self
+    }
+
+    fn balance<Request>(&self, services: Vec<S>) -> Balance<ServiceList<Vec<S>>, Request>
+    where
+        S: Service<Request, Error: Into<BoxError>> + Routed,
+    {
+        let services = if self.latency_aware {
+            fastest(services)
+        } else {
+            services
+        };
+
+        Balance::new(ServiceList::new(services))
+    }
+
+    fn route(&self, route: Route) -> Result<Vec<S>, Error>
+    where
+        S: Routed + Clone,
+    {
+        match route.choose(self.services.values()) {
+            Ok(services) => match self.max_lag {
+                Some(max_lag) => {
+                    let max_seqno = self.services.values().filter_map(|s| s.last_seqno()).max();
+                    let services = within_lag(services, max_seqno, max_lag);
+                    if services.is_empty() {
+                        metrics::counter!("ton_router_lagging_count", "network" => self.network.clone()).increment(1);
+
+                        Err(Error::RouteNotAvailable)
+                    } else {
+                        Ok(services)
+                    }
+                }
+                None => Ok(services),
+            },
+            Err(Error::RouteUnknown) => {
+                metrics::counter!("ton_router_miss_count", "network" => self.network.clone())
+                    .increment(1);
+
+                Route::Latest.choose(self.services.values())
+            }
+            Err(Error::RouteNotAvailable) => {
+                metrics::counter!("ton_router_delayed_count", "network" => self.network.clone())
+                    .increment(1);
+
+                Err(Error::RouteNotAvailable)
+            }
         }
     }
 
@@ -107,21 +279,155 @@ where
     }
 
     fn call(&mut self, req: &Request) -> Self::Future {
-        ready(match req.to_route().choose(self.services.values()) {
-            Ok(services) => Ok(Balance::new(ServiceList::new(services))),
-            Err(Error::RouteUnknown) => {
-                metrics::counter!("ton_router_miss_count").increment(1);
+        let route = hint::apply(req.to_route());
+        let services = self.route(route).and_then(|services| {
+            let Some(session) = consistency::current() else {
+                return Ok(services);
+            };
 
-                Route::Latest
-                    .choose(self.services.values())
-                    .map(|services| Balance::new(ServiceList::new(services)))
-                    .map_err(Into::into)
-            }
-            Err(Error::RouteNotAvailable) => {
-                metrics::counter!("ton_router_delayed_count").increment(1);
+            let services = caught_up(services, &session);
+            if services.is_empty() {
+                metrics::counter!("ton_router_session_lagging_count", "network" => self.network.clone()).increment(1);
 
-                Err(Error::RouteNotAvailable.into())
+                return Err(Error::RouteNotAvailable);
             }
-        })
+
+            Ok(services)
+        });
+
+        if let (Some(observer), Ok(services)) = (&self.observer, &services) {
+            let archival =
+                matches!(route, Route::Block { .. }) && services.iter().all(|s| s.archival());
+            observer.observe(&route, archival);
+        }
+
+        ready(
+            services
+                .map(|services| self.balance(services))
+                .map_err(Into::into),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    struct Measured(&'static str, Option<LatencyStats>);
+
+    impl Routed for Measured {
+        fn contains(&self, _: &i32, _: &BlockCriteria) -> bool {
+            true
+        }
+        fn contains_not_available(&self, _: &i32, _: &BlockCriteria) -> bool {
+            false
+        }
+        fn last_seqno(&self) -> Option<i32> {
+            None
+        }
+        fn latency(&self) -> Option<LatencyStats> {
+            self.1
+        }
+    }
+
+    fn measured(name: &'static str, p95_millis: u64, errors: u64) -> Measured {
+        Measured(
+            name,
+            Some(LatencyStats {
+                p50: Duration::from_millis(p95_millis / 2),
+                p95: Duration::from_millis(p95_millis),
+                p99: Duration::from_millis(p95_millis * 2),
+                requests: 100,
+                errors,
+            }),
+        )
+    }
+
+    #[test]
+    fn fastest_picks_lowest_p95() {
+        let services = vec![
+            measured("slow", 300, 0),
+            measured("fast", 20, 0),
+            measured("medium", 80, 0),
+        ];
+
+        assert_eq!(fastest(services), vec![measured("fast", 20, 0)]);
+    }
+
+    #[test]
+    fn fastest_skips_failing() {
+        let services = vec![measured("slow", 300, 10), measured("fast", 20, 60)];
+
+        assert_eq!(fastest(services), vec![measured("slow", 300, 10)]);
+    }
+
+    #[test]
+    fn fastest_measures_unknown_first() {
+        let services = vec![measured("fast", 20, 0), Measured("new", None)];
+
+        assert_eq!(fastest(services), vec![Measured("new", None)]);
+    }
+
+    #[test]
+    fn fastest_keeps_all_when_none_is_healthy() {
+        let services = vec![measured("a", 300, 90), measured("b", 20, 60)];
+
+        assert_eq!(fastest(services.clone()), services);
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    struct AtSeqno(&'static str, Option<i32>);
+
+    impl Routed for AtSeqno {
+        fn contains(&self, _: &i32, _: &BlockCriteria) -> bool {
+            true
+        }
+        fn contains_not_available(&self, _: &i32, _: &BlockCriteria) -> bool {
+            false
+        }
+        fn last_seqno(&self) -> Option<i32> {
+            self.1
+        }
+    }
+
+    #[test]
+    fn within_lag_skips_lagging() {
+        let services = vec![
+            AtSeqno("fresh", Some(100)),
+            AtSeqno("behind", Some(95)),
+            AtSeqno("lagging", Some(90)),
+            AtSeqno("syncing", None),
+        ];
+
+        assert_eq!(
+            within_lag(services.clone(), Some(100), 5),
+            vec![AtSeqno("fresh", Some(100)), AtSeqno("behind", Some(95))]
+        );
+        assert_eq!(within_lag(services.clone(), None, 5), services);
+    }
+
+    #[test]
+    fn caught_up_skips_upstreams_behind_session() {
+        let services = vec![
+            AtSeqno("fresh", Some(100)),
+            AtSeqno("behind", Some(95)),
+            AtSeqno("syncing", None),
+        ];
+
+        let session = Session::default();
+        assert_eq!(caught_up(services.clone(), &session), services);
+        assert_eq!(session.seqno(), Some(95));
+
+        session.observe(98);
+        assert_eq!(
+            caught_up(services.clone(), &session),
+            vec![AtSeqno("fresh", Some(100))]
+        );
+        assert_eq!(session.seqno(), Some(100));
+
+        session.observe(101);
+        assert!(caught_up(services, &session).is_empty());
+        assert_eq!(session.seqno(), Some(101));
     }
 }
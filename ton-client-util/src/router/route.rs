@@ -5,7 +5,7 @@ pub trait ToRoute {
     fn to_route(&self) -> Route;
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Route {
     Block { chain: i32, criteria: BlockCriteria },
     Latest,
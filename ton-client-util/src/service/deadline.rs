@@ -0,0 +1,60 @@
+use std::future::Future;
+use std::time::Duration;
+use tokio::time::Instant;
+
+tokio::task_local! {
+    static DEADLINE: Instant;
+}
+
+/// The caller of a request gave up waiting for it, see [`scope`]
+#[derive(Debug, Default, thiserror::Error)]
+#[error("deadline exceeded")]
+pub struct DeadlineExceeded;
+
+/// Requests made by `f` fail with [`DeadlineExceeded`] once `deadline` passes, the earliest deadline wins if nested
+pub async fn scope<F: Future>(deadline: Instant, f: F) -> F::Output {
+    let deadline = current().map_or(deadline, |current| current.min(deadline));
+
+    DEADLINE.scope(deadline, f).await
+}
+
+/// [`scope`] for a single poll, e.g. of a stream made on behalf of a request
+pub fn sync_scope<R>(deadline: Instant, f: impl FnOnce() -> R) -> R {
+    let deadline = current().map_or(deadline, |current| current.min(deadline));
+
+    DEADLINE.sync_scope(deadline, f)
+}
+
+/// Deadline of the request the current task works on
+pub fn current() -> Option<Instant> {
+    DEADLINE.try_with(|deadline| *deadline).ok()
+}
+
+/// Time left until the deadline of the current task, zero if it has passed
+pub fn remaining() -> Option<Duration> {
+    current().map(|deadline| deadline.saturating_duration_since(Instant::now()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn earliest_deadline_wins() {
+        let now = Instant::now();
+
+        assert_eq!(current(), None);
+        scope(now + Duration::from_secs(5), async {
+            assert_eq!(remaining(), Some(Duration::from_secs(5)));
+
+            scope(now + Duration::from_secs(10), async {
+                assert_eq!(current(), Some(now + Duration::from_secs(5)));
+            })
+            .await;
+            sync_scope(now + Duration::from_secs(1), || {
+                assert_eq!(current(), Some(now + Duration::from_secs(1)));
+            });
+        })
+        .await;
+    }
+}
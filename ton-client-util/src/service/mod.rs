@@ -1,2 +1,3 @@
+pub mod deadline;
 pub mod shared;
 pub mod timeout;
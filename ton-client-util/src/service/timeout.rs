@@ -1,3 +1,4 @@
+use crate::service::deadline::{self, DeadlineExceeded};
 use pin_project::pin_project;
 use std::future::Future;
 use std::pin::Pin;
@@ -78,10 +79,15 @@ where
 
     fn call(&mut self, request: Request) -> Self::Future {
         let timeout = request.to_timeout().unwrap_or(self.default_timeout);
-        let response = self.inner.call(request);
+        // the deadline of the caller cuts the timeout short, nothing is sent once it has passed
+        let (timeout, deadline) = match deadline::remaining() {
+            Some(remaining) if remaining <= timeout => (remaining, true),
+            _ => (timeout, false),
+        };
+        let response = (!timeout.is_zero()).then(|| self.inner.call(request));
         let sleep = sleep(timeout);
 
-        ResponseFuture::new(response, sleep)
+        ResponseFuture::new(response, sleep, deadline)
     }
 }
 
@@ -100,14 +106,19 @@ where
 #[pin_project]
 pub struct ResponseFuture<T> {
     #[pin]
-    response: T,
+    response: Option<T>,
     #[pin]
     sleep: Sleep,
+    deadline: bool,
 }
 
 impl<T> ResponseFuture<T> {
-    pub(crate) fn new(response: T, sleep: Sleep) -> Self {
-        ResponseFuture { response, sleep }
+    pub(crate) fn new(response: Option<T>, sleep: Sleep, deadline: bool) -> Self {
+        ResponseFuture {
+            response,
+            sleep,
+            deadline,
+        }
     }
 }
 
@@ -121,13 +132,15 @@ where
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let this = self.project();
 
-        match this.response.poll(cx) {
-            Poll::Ready(v) => return Poll::Ready(v.map_err(Into::into)),
-            Poll::Pending => {}
+        if let Some(response) = this.response.as_pin_mut() {
+            if let Poll::Ready(v) = response.poll(cx) {
+                return Poll::Ready(v.map_err(Into::into));
+            }
         }
 
         match this.sleep.poll(cx) {
             Poll::Pending => Poll::Pending,
+            Poll::Ready(_) if *this.deadline => Poll::Ready(Err(DeadlineExceeded.into())),
             Poll::Ready(_) => Poll::Ready(Err(Elapsed::new().into())),
         }
     }
@@ -0,0 +1,628 @@
+use anyhow::{anyhow, Context};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use num_bigint::{BigInt, BigUint};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::str::FromStr;
+use toner::tlb::bits::ser::pack_with;
+use toner::tlb::de::CellParser;
+use toner::tlb::r#as::Data;
+use toner::tlb::Cell;
+use toner::ton::boc::{BagOfCellsArgs, BoC};
+use toner::ton::MsgAddress;
+use tonlibjson_client::block::{
+    TvmBoxedStackEntry, TvmCell, TvmSlice, TvmStackEntryCell, TvmStackEntrySlice,
+};
+
+use crate::adapters::tuple;
+use crate::decode::{address, reference, uint, var_uint16};
+use crate::TvmBoxedStackEntryExt;
+
+/// Integers of a cell are at most 256 bits long, signed ones 257
+const MAX_UINT_BITS: usize = 256;
+const MAX_INT_BITS: usize = 257;
+
+/// Entry of a get-method result stack
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StackType {
+    /// decimal string, as it may not fit into a JSON number
+    Int,
+    /// -1 is true and 0 is false
+    Bool,
+    /// slice of a std address
+    Address,
+    /// cell or slice as base64 BoC
+    Cell,
+    /// cell or slice parsed field by field
+    Struct {
+        fields: Vec<Field>,
+    },
+    Tuple {
+        items: Vec<Item>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Item {
+    pub name: String,
+    #[serde(flatten)]
+    pub r#type: StackType,
+}
+
+/// Field of a cell, read in order
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FieldType {
+    Uint {
+        bits: usize,
+    },
+    Int {
+        bits: usize,
+    },
+    Bool,
+    /// `VarUInteger 16`, decimal string of nanotons
+    Coins,
+    /// `addr_none` or `addr_std`
+    Address,
+    /// reference parsed field by field
+    Ref {
+        fields: Vec<Field>,
+    },
+    /// reference as base64 BoC
+    Cell,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Field {
+    pub name: String,
+    #[serde(flatten)]
+    pub r#type: FieldType,
+}
+
+/// Result schemas of get-methods by method name
+pub type MethodSchemas = HashMap<String, Vec<Item>>;
+
+#[derive(Deserialize)]
+struct DescriptorFile {
+    contracts: Vec<Descriptor>,
+}
+
+/// Contract matched either by the hash of its code, hex or base64, or by its address
+#[derive(Deserialize)]
+struct Descriptor {
+    code_hash: Option<String>,
+    address: Option<String>,
+    methods: MethodSchemas,
+}
+
+/// Get-method schemas of contracts registered by operators
+#[derive(Debug, Default)]
+pub struct AbiRegistry {
+    by_code_hash: HashMap<[u8; 32], MethodSchemas>,
+    by_address: HashMap<(i32, [u8; 32]), MethodSchemas>,
+}
+
+impl AbiRegistry {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let json = std::fs::read(path).with_context(|| format!("read {}", path.display()))?;
+
+        Self::from_json(&json).with_context(|| format!("ABI descriptors {}", path.display()))
+    }
+
+    /// Every schema is validated, a registry is never built from a malformed one
+    pub fn from_json(json: &[u8]) -> anyhow::Result<Self> {
+        let file: DescriptorFile = serde_json::from_slice(json)?;
+
+        let mut registry = Self::default();
+        for (i, descriptor) in file.contracts.into_iter().enumerate() {
+            let path = format!("contracts[{}]", i);
+            for (method, items) in &descriptor.methods {
+                if method.is_empty() {
+                    return Err(anyhow!("{}: method name is empty", path));
+                }
+                validate_items(&format!("{}.{}", path, method), items)?;
+            }
+
+            let duplicate = match (descriptor.code_hash, descriptor.address) {
+                (Some(code_hash), None) => {
+                    let code_hash = parse_code_hash(&code_hash)
+                        .with_context(|| format!("{}.code_hash", path))?;
+
+                    registry
+                        .by_code_hash
+                        .insert(code_hash, descriptor.methods)
+                        .is_some()
+                }
+                (None, Some(address)) => {
+                    let address = MsgAddress::from_str(&address)
+                        .map_err(|e| anyhow!("{}.address: {}", path, e))?;
+
+                    registry
+                        .by_address
+                        .insert((address.workchain_id, address.address), descriptor.methods)
+                        .is_some()
+                }
+                _ => {
+                    return Err(anyhow!(
+                        "{}: exactly one of code_hash and address is required",
+                        path
+                    ))
+                }
+            };
+            if duplicate {
+                return Err(anyhow!("{}: contract is already described", path));
+            }
+        }
+
+        Ok(registry)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_code_hash.is_empty() && self.by_address.is_empty()
+    }
+
+    /// Whether some schema needs the code hash of a contract to be found
+    pub fn has_code_hashes(&self) -> bool {
+        !self.by_code_hash.is_empty()
+    }
+
+    /// Schema of `method`, the one registered for the address comes before the one of the code
+    pub fn schema(
+        &self,
+        address: &MsgAddress,
+        code_hash: Option<&[u8; 32]>,
+        method: &str,
+    ) -> Option<&[Item]> {
+        self.by_address
+            .get(&(address.workchain_id, address.address))
+            .and_then(|methods| methods.get(method))
+            .or_else(|| {
+                self.by_code_hash
+                    .get(code_hash?)
+                    .and_then(|methods| methods.get(method))
+            })
+            .map(Vec::as_slice)
+    }
+}
+
+fn parse_code_hash(code_hash: &str) -> anyhow::Result<[u8; 32]> {
+    let bytes = match hex::decode(code_hash) {
+        Ok(bytes) => bytes,
+        Err(_) => STANDARD.decode(code_hash)?,
+    };
+
+    bytes
+        .try_into()
+        .map_err(|bytes: Vec<u8>| anyhow!("{} bytes instead of 32", bytes.len()))
+}
+
+fn validate_names<'a>(path: &str, names: impl Iterator<Item = &'a str>) -> anyhow::Result<()> {
+    let mut seen = HashSet::new();
+    for name in names {
+        if name.is_empty() {
+            return Err(anyhow!("{}: name is empty", path));
+        }
+        if !seen.insert(name) {
+            return Err(anyhow!("{}.{}: name is repeated", path, name));
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_items(path: &str, items: &[Item]) -> anyhow::Result<()> {
+    validate_names(path, items.iter().map(|item| item.name.as_str()))?;
+    for item in items {
+        let path = format!("{}.{}", path, item.name);
+        match &item.r#type {
+            StackType::Struct { fields } => validate_fields(&path, fields)?,
+            StackType::Tuple { items } => validate_items(&path, items)?,
+            StackType::Int | StackType::Bool | StackType::Address | StackType::Cell => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_fields(path: &str, fields: &[Field]) -> anyhow::Result<()> {
+    validate_names(path, fields.iter().map(|field| field.name.as_str()))?;
+    for field in fields {
+        let path = format!("{}.{}", path, field.name);
+        match &field.r#type {
+            FieldType::Uint { bits } if !(1..=MAX_UINT_BITS).contains(bits) => {
+                return Err(anyhow!(
+                    "{}: uint bits must be within 1..={}",
+                    path,
+                    MAX_UINT_BITS
+                ))
+            }
+            FieldType::Int { bits } if !(1..=MAX_INT_BITS).contains(bits) => {
+                return Err(anyhow!(
+                    "{}: int bits must be within 1..={}",
+                    path,
+                    MAX_INT_BITS
+                ))
+            }
+            FieldType::Ref { fields } => validate_fields(&path, fields)?,
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Result stack doesn't follow its schema, `path` names the entry or field which doesn't
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("{path}: {reason}")]
+pub struct AbiDecodeError {
+    pub path: String,
+    pub reason: String,
+}
+
+fn mismatch(path: &str, reason: impl Into<String>) -> AbiDecodeError {
+    AbiDecodeError {
+        path: path.to_owned(),
+        reason: reason.into(),
+    }
+}
+
+/// JSON object of the stack entries by their names in the schema
+pub fn decode_stack(items: &[Item], stack: &[TvmBoxedStackEntry]) -> Result<Value, AbiDecodeError> {
+    decode_items("stack", items, stack)
+}
+
+fn decode_items(
+    path: &str,
+    items: &[Item],
+    entries: &[TvmBoxedStackEntry],
+) -> Result<Value, AbiDecodeError> {
+    if items.len() != entries.len() {
+        return Err(mismatch(
+            path,
+            format!("{} entries instead of {}", entries.len(), items.len()),
+        ));
+    }
+
+    items
+        .iter()
+        .zip(entries)
+        .map(|(item, entry)| {
+            let path = format!("{}.{}", path, item.name);
+
+            Ok((item.name.clone(), decode_entry(&path, &item.r#type, entry)?))
+        })
+        .collect::<Result<Map<_, _>, _>>()
+        .map(Value::Object)
+}
+
+fn decode_entry(
+    path: &str,
+    r#type: &StackType,
+    entry: &TvmBoxedStackEntry,
+) -> Result<Value, AbiDecodeError> {
+    let value = match r#type {
+        StackType::Int => {
+            let number = entry
+                .to_number::<BigInt>()
+                .map_err(|_| mismatch(path, "not an int"))?;
+
+            Value::String(number.to_string())
+        }
+        StackType::Bool => match entry.to_number::<i8>() {
+            Ok(-1) => Value::Bool(true),
+            Ok(0) => Value::Bool(false),
+            _ => return Err(mismatch(path, "not a bool")),
+        },
+        StackType::Address => {
+            let address = entry
+                .parse_cell_fully_as::<MsgAddress, Data>()
+                .map_err(|_| mismatch(path, "not an address"))?;
+
+            Value::String(address.to_string())
+        }
+        StackType::Cell => match entry {
+            TvmBoxedStackEntry::TvmStackEntrySlice(TvmStackEntrySlice {
+                slice: TvmSlice { bytes },
+            })
+            | TvmBoxedStackEntry::TvmStackEntryCell(TvmStackEntryCell {
+                cell: TvmCell { bytes },
+            }) => Value::String(bytes.clone()),
+            _ => return Err(mismatch(path, "not a cell")),
+        },
+        StackType::Struct { fields } => {
+            let cell = entry.to_cell().map_err(|_| mismatch(path, "not a cell"))?;
+
+            decode_fields(path, fields, &mut cell.parser())?
+        }
+        StackType::Tuple { items } => {
+            let elements = tuple(entry.clone()).map_err(|_| mismatch(path, "not a tuple"))?;
+
+            decode_items(path, items, &elements)?
+        }
+    };
+
+    Ok(value)
+}
+
+fn decode_fields(
+    path: &str,
+    fields: &[Field],
+    parser: &mut CellParser<'_>,
+) -> Result<Value, AbiDecodeError> {
+    fields
+        .iter()
+        .map(|field| {
+            let path = format!("{}.{}", path, field.name);
+
+            Ok((
+                field.name.clone(),
+                decode_field(&path, &field.r#type, parser)?,
+            ))
+        })
+        .collect::<Result<Map<_, _>, _>>()
+        .map(Value::Object)
+}
+
+fn decode_field(
+    path: &str,
+    r#type: &FieldType,
+    parser: &mut CellParser<'_>,
+) -> Result<Value, AbiDecodeError> {
+    let missing = || mismatch(path, "cell ends before the field");
+
+    let value = match r#type {
+        FieldType::Uint { bits } => {
+            Value::String(big_uint(parser, *bits).ok_or_else(missing)?.to_string())
+        }
+        FieldType::Int { bits } => {
+            let value = BigInt::from(big_uint(parser, *bits).ok_or_else(missing)?);
+            // two's complement
+            let value = if value.bit(*bits as u64 - 1) {
+                value - (BigInt::from(1) << *bits)
+            } else {
+                value
+            };
+
+            Value::String(value.to_string())
+        }
+        FieldType::Bool => Value::Bool(uint(parser, 1).ok_or_else(missing)? == 1),
+        FieldType::Coins => Value::String(var_uint16(parser).ok_or_else(missing)?.to_string()),
+        FieldType::Address => Value::String(
+            address(parser)
+                .ok_or_else(|| mismatch(path, "not an address"))?
+                .to_string(),
+        ),
+        FieldType::Ref { fields } => {
+            let mut reference = reference(parser).ok_or_else(|| mismatch(path, "no reference"))?;
+
+            decode_fields(path, fields, &mut reference)?
+        }
+        FieldType::Cell => {
+            let cell: Cell = reference(parser)
+                .and_then(|mut reference| reference.parse().ok())
+                .ok_or_else(|| mismatch(path, "no reference"))?;
+
+            Value::String(cell_base64(cell).ok_or_else(|| mismatch(path, "cannot pack the cell"))?)
+        }
+    };
+
+    Ok(value)
+}
+
+fn big_uint(parser: &mut CellParser<'_>, bits: usize) -> Option<BigUint> {
+    if parser.bits_left() < bits {
+        return None;
+    }
+
+    (0..bits).try_fold(BigUint::default(), |acc, _| {
+        Some((acc << 1_u8) | BigUint::from(parser.unpack::<bool>().ok()? as u8))
+    })
+}
+
+fn cell_base64(cell: Cell) -> Option<String> {
+    let packed = pack_with(
+        BoC::from_root(cell),
+        BagOfCellsArgs {
+            has_idx: false,
+            has_crc32c: false,
+        },
+    )
+    .ok()?;
+
+    Some(STANDARD.encode(packed.as_raw_slice()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use toner::tlb::r#as::Ref;
+    use tonlibjson_client::block::{TvmStackEntryTuple, TvmTuple};
+
+    fn owner() -> MsgAddress {
+        "EQCjk1hh952vWaE9bRguFkAhDAL5jj3xj9p0uPWrFBq_GEMS"
+            .parse()
+            .unwrap()
+    }
+
+    fn other() -> MsgAddress {
+        "EQBGXZ9ddZeWypx8EkJieHJX75ct0bpkmu0Y4YoYr3NM0Z9e"
+            .parse()
+            .unwrap()
+    }
+
+    fn registry() -> AbiRegistry {
+        AbiRegistry::from_json(
+            json!({
+                "contracts": [{
+                    "code_hash": "84dafa449f98a6987789ba232358072bc0f76dc4524002a5d0918b9a75d2d599",
+                    "methods": {
+                        "get_pool_data": [
+                            { "name": "reserve", "type": "int" },
+                            { "name": "owner", "type": "address" },
+                            { "name": "config", "type": "struct", "fields": [
+                                { "name": "fee", "type": "uint", "bits": 16 },
+                                { "name": "delta", "type": "int", "bits": 8 },
+                                { "name": "paused", "type": "bool" },
+                                { "name": "extra", "type": "ref", "fields": [
+                                    { "name": "admin", "type": "address" },
+                                ] },
+                            ] },
+                            { "name": "pair", "type": "tuple", "items": [
+                                { "name": "left", "type": "address" },
+                                { "name": "nested", "type": "tuple", "items": [
+                                    { "name": "active", "type": "bool" },
+                                ] },
+                            ] },
+                        ],
+                    },
+                }, {
+                    "address": "EQCjk1hh952vWaE9bRguFkAhDAL5jj3xj9p0uPWrFBq_GEMS",
+                    "methods": { "seqno": [{ "name": "seqno", "type": "int" }] },
+                }],
+            })
+            .to_string()
+            .as_bytes(),
+        )
+        .unwrap()
+    }
+
+    fn tuple_entry(elements: Vec<TvmBoxedStackEntry>) -> TvmBoxedStackEntry {
+        TvmBoxedStackEntry::TvmStackEntryTuple(TvmStackEntryTuple {
+            tuple: TvmTuple { elements },
+        })
+    }
+
+    fn code_hash() -> [u8; 32] {
+        parse_code_hash("84dafa449f98a6987789ba232358072bc0f76dc4524002a5d0918b9a75d2d599").unwrap()
+    }
+
+    fn pool_data() -> Vec<TvmBoxedStackEntry> {
+        let mut extra = Cell::builder();
+        extra.pack(owner()).unwrap();
+        let mut config = Cell::builder();
+        config
+            .pack(300_u16)
+            .unwrap()
+            // -3
+            .pack(0xfd_u8)
+            .unwrap()
+            .pack(true)
+            .unwrap()
+            .store_as::<_, Ref>(extra.into_cell())
+            .unwrap();
+
+        vec![
+            TvmBoxedStackEntry::from_number("-1000000000000000000000"),
+            TvmBoxedStackEntry::from_address(&owner()).unwrap(),
+            TvmBoxedStackEntry::from_cell(config.into_cell()).unwrap(),
+            tuple_entry(vec![
+                TvmBoxedStackEntry::from_address(&owner()).unwrap(),
+                tuple_entry(vec![TvmBoxedStackEntry::from_number(-1)]),
+            ]),
+        ]
+    }
+
+    #[test]
+    fn decode_nested_schema() {
+        let registry = registry();
+        let schema = registry
+            .schema(&other(), Some(&code_hash()), "get_pool_data")
+            .unwrap();
+
+        assert_eq!(
+            decode_stack(schema, &pool_data()).unwrap(),
+            json!({
+                "reserve": "-1000000000000000000000",
+                "owner": owner().to_string(),
+                "config": {
+                    "fee": "300",
+                    "delta": "-3",
+                    "paused": true,
+                    "extra": { "admin": owner().to_string() },
+                },
+                "pair": {
+                    "left": owner().to_string(),
+                    "nested": { "active": true },
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn decode_mismatch_names_the_entry() {
+        let registry = registry();
+        let schema = registry
+            .schema(&other(), Some(&code_hash()), "get_pool_data")
+            .unwrap();
+        let mut stack = pool_data();
+        stack[3] = tuple_entry(vec![
+            TvmBoxedStackEntry::from_number(1),
+            tuple_entry(vec![]),
+        ]);
+
+        assert_eq!(
+            decode_stack(schema, &stack).unwrap_err(),
+            mismatch("stack.pair.left", "not an address")
+        );
+        assert_eq!(
+            decode_stack(schema, &stack[..2]).unwrap_err(),
+            mismatch("stack", "2 entries instead of 4")
+        );
+    }
+
+    #[test]
+    fn schema_by_address_comes_first() {
+        let registry = registry();
+
+        assert!(registry.schema(&owner(), None, "seqno").is_some());
+        assert!(registry.schema(&owner(), None, "get_pool_data").is_none());
+        assert!(registry
+            .schema(&owner(), Some(&code_hash()), "get_pool_data")
+            .is_some());
+        assert!(registry.schema(&other(), None, "seqno").is_none());
+    }
+
+    #[test]
+    fn invalid_schemas_are_rejected() {
+        let load = |contracts: Value| {
+            AbiRegistry::from_json(json!({ "contracts": contracts }).to_string().as_bytes())
+                .unwrap_err()
+                .to_string()
+        };
+
+        assert_eq!(
+            load(json!([{ "methods": {} }])),
+            "contracts[0]: exactly one of code_hash and address is required"
+        );
+        assert_eq!(
+            load(json!([{ "address": owner().to_string(), "methods": {
+                "m": [{ "name": "s", "type": "struct", "fields": [
+                    { "name": "x", "type": "uint", "bits": 300 },
+                ] }],
+            } }])),
+            "contracts[0].m.s.x: uint bits must be within 1..=256"
+        );
+        assert_eq!(
+            load(json!([{ "address": owner().to_string(), "methods": {
+                "m": [{ "name": "a", "type": "int" }, { "name": "a", "type": "bool" }],
+            } }])),
+            "contracts[0].m.a: name is repeated"
+        );
+        assert_eq!(
+            load(json!([
+                { "address": owner().to_string(), "methods": {} },
+                { "address": owner().to_string(), "methods": {} },
+            ])),
+            "contracts[1]: contract is already described"
+        );
+        assert!(load(json!([{ "code_hash": "AAAA", "methods": {} }])).contains("code_hash"));
+        assert!(load(json!([{ "address": owner().to_string(), "methods": {
+            "m": [{ "name": "a", "type": "float" }],
+        } }]))
+        .contains("float"));
+    }
+}
@@ -6,15 +6,19 @@ use toner::{
     tlb::bits::ser::pack_with,
     tlb::de::r#as::CellDeserializeAsOwned,
     tlb::de::CellDeserializeOwned,
+    tlb::r#as::Data,
     tlb::ser::r#as::{CellSerializeAs, CellSerializeWrapAsExt},
     tlb::ser::{CellSerialize, CellSerializeExt},
     tlb::{Cell, Error as TlbError},
     ton::boc::{BagOfCellsArgs, BoC},
+    ton::MsgAddress,
 };
 
+use num_bigint::BigUint;
 use tonlibjson_client::block::{
-    TvmBoxedNumber, TvmBoxedStackEntry, TvmCell, TvmNumberDecimal, TvmSlice, TvmStackEntryCell,
-    TvmStackEntryNumber, TvmStackEntrySlice,
+    TvmBoxedNumber, TvmBoxedStackEntry, TvmCell, TvmList, TvmNumberDecimal, TvmSlice,
+    TvmStackEntryCell, TvmStackEntryList, TvmStackEntryNumber, TvmStackEntrySlice,
+    TvmStackEntryTuple, TvmTuple,
 };
 
 use crate::TonContractError;
@@ -63,6 +67,11 @@ pub trait TvmBoxedStackEntryExt: Sized {
     {
         Self::from_cell(value.wrap_as::<As>().to_cell()?)
     }
+    /// Slice with the std address, e.g. the owner argument of `get_wallet_address`
+    #[inline]
+    fn from_address(address: &MsgAddress) -> Result<Self, TonContractError> {
+        Self::store_cell_as::<_, Data>(address)
+    }
 
     fn to_number<T>(&self) -> Result<T, TonContractError>
     where
@@ -132,3 +141,90 @@ impl TvmBoxedStackEntryExt for TvmBoxedStackEntry {
         })
     }
 }
+
+pub(crate) fn tuple(
+    entry: TvmBoxedStackEntry,
+) -> Result<Vec<TvmBoxedStackEntry>, TonContractError> {
+    match entry {
+        TvmBoxedStackEntry::TvmStackEntryTuple(TvmStackEntryTuple {
+            tuple: TvmTuple { elements },
+        }) => Ok(elements),
+        _ => Err(TonContractError::InvalidStack),
+    }
+}
+
+/// Elements of a lisp-style list, tonlib returns it either as a list or as nested pairs
+pub(crate) fn list_elements(
+    mut entry: TvmBoxedStackEntry,
+) -> Result<Vec<TvmBoxedStackEntry>, TonContractError> {
+    let mut elements = Vec::new();
+    loop {
+        match entry {
+            TvmBoxedStackEntry::TvmStackEntryList(TvmStackEntryList {
+                list: TvmList { elements: rest },
+            }) => {
+                elements.extend(rest);
+
+                return Ok(elements);
+            }
+            TvmBoxedStackEntry::TvmStackEntryTuple(_) => {
+                let [head, tail] = tuple(entry)?.try_into()?;
+                elements.push(head);
+                entry = tail;
+            }
+            // null
+            TvmBoxedStackEntry::TvmStackEntryUnsupported(_) => return Ok(elements),
+            _ => return Err(TonContractError::InvalidStack),
+        }
+    }
+}
+
+pub(crate) fn bits256(entry: &TvmBoxedStackEntry) -> Result<[u8; 32], TonContractError> {
+    let bytes = entry.to_number::<BigUint>()?.to_bytes_be();
+    if bytes.len() > 32 {
+        return Err(TonContractError::ParseNumber(format!(
+            "{} bytes don't fit into 256 bits",
+            bytes.len()
+        )));
+    }
+
+    let mut bits = [0; 32];
+    bits[32 - bytes.len()..].copy_from_slice(&bytes);
+
+    Ok(bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn address_slice_of_masterchain_owner() {
+        let owner = MsgAddress::from_str(
+            "-1:3333333333333333333333333333333333333333333333333333333333333333",
+        )
+        .unwrap();
+
+        let cell = TvmBoxedStackEntry::from_address(&owner)
+            .unwrap()
+            .to_cell()
+            .unwrap();
+
+        // addr_std$10 anycast:nothing$0 workchain_id:int8 address:bits256
+        assert_eq!(cell.data.len(), 267);
+        assert!(cell.data[0] && !cell.data[1] && !cell.data[2]);
+        assert!(cell.data[3..11].all());
+        assert_eq!(cell.parse_fully_as::<MsgAddress, Data>().unwrap(), owner);
+    }
+
+    #[test]
+    fn address_slice_round_trip() {
+        let owner =
+            MsgAddress::from_str("EQCD39VS5jcptHL8vMjEXrzGaRcCVYto7HUn4bpAOg8xqB2N").unwrap();
+
+        let entry = TvmBoxedStackEntry::from_address(&owner).unwrap();
+
+        assert!(matches!(entry, TvmBoxedStackEntry::TvmStackEntrySlice(_)));
+        assert_eq!(entry.parse_cell_fully_as::<_, Data>().ok(), Some(owner));
+    }
+}
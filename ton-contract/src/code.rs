@@ -0,0 +1,127 @@
+use toner::tlb::bits::bitvec::order::Msb0;
+use toner::tlb::bits::bitvec::vec::BitVec;
+use toner::tlb::bits::de::BitReaderExt;
+use toner::tlb::bits::r#as::NBits;
+use toner::tlb::de::CellParser;
+use toner::tlb::r#as::{NoArgs, Ref};
+use toner::tlb::{Cell, StringError};
+use toner::ton::hashmap::Hashmap;
+
+/// `SETCP0`
+const SETCP0: u16 = 0xff00;
+/// `DICTPUSHCONST` is `F4A6_ n:(## 10)`, 14 bits of the opcode followed by the key length
+const DICTPUSHCONST: u16 = 0b11_1101_0010_1001;
+
+/// Ids of the methods of contract code, as FunC, Tolk and Fift compile them, i.e.
+/// ```fift
+/// SETCP0
+/// (:methods) 19 DICTPUSHCONST
+/// DICTIGETJMPZ
+/// 11 THROWARG
+/// ```
+/// `recv_internal` is `0`, `recv_external` is `-1` and get-methods are `crc16(name) | 0x10000`.
+/// `None` when the code doesn't start with such a dispatcher, ids are sorted
+pub fn method_ids(code: &Cell) -> Option<Vec<i32>> {
+    parse_method_ids(&mut code.parser()).ok().flatten()
+}
+
+fn parse_method_ids(parser: &mut CellParser<'_>) -> Result<Option<Vec<i32>>, StringError> {
+    if parser.bits_left() < 16 + 24 || parser.unpack::<u16>()? != SETCP0 {
+        return Ok(None);
+    }
+
+    // the last two bits are the high bits of the key length
+    let opcode: u16 = parser.unpack()?;
+    if opcode >> 2 != DICTPUSHCONST {
+        return Ok(None);
+    }
+    let low: u16 = parser.unpack_as::<_, NBits<8>>()?;
+    let n = u32::from((opcode & 0b11) << 8 | low);
+    if n == 0 || n > 32 {
+        return Ok(None);
+    }
+
+    let methods: Cell = parser.parse_as::<_, Ref>()?;
+    // a leaf holds the code of its method right after the label
+    let keys = methods
+        .parser()
+        .parse_as_with::<Vec<(BitVec<u8, Msb0>, ())>, Hashmap<NoArgs<_>, ()>>((n, ()))?;
+
+    let mut ids: Vec<i32> = keys.into_iter().map(|(key, _)| signed(&key)).collect();
+    ids.sort_unstable();
+
+    Ok(Some(ids))
+}
+
+/// Keys of the method dictionary are signed integers
+fn signed(key: &BitVec<u8, Msb0>) -> i32 {
+    let unsigned = key
+        .iter()
+        .fold(0_i64, |acc, bit| acc << 1 | i64::from(*bit));
+    let negative = key.first().is_some_and(|bit| *bit);
+
+    if negative {
+        (unsigned - (1_i64 << key.len())) as i32
+    } else {
+        unsigned as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use toner::tlb::bits::de::unpack_bytes;
+    use toner::tlb::bits::ser::BitWriterExt;
+    use toner::ton::boc::BoC;
+
+    /// Code of wallet v4r2
+    const WALLET_V4R2: &str = include_str!("../tests/fixtures/wallet_v4r2.code");
+
+    fn code(boc: &str) -> Cell {
+        let boc: BoC = unpack_bytes(STANDARD.decode(boc.trim()).unwrap()).unwrap();
+
+        boc.single_root().unwrap().as_ref().clone()
+    }
+
+    #[test]
+    fn wallet_v4r2_method_ids() {
+        let code = code(WALLET_V4R2);
+        assert_eq!(
+            hex::encode(code.hash()),
+            "feb5ff6820e2ff0d9483e7e0d62c817d846789fb4ae580c878866d959dabd5c0"
+        );
+
+        assert_eq!(
+            method_ids(&code),
+            Some(vec![
+                -1,     // recv_external
+                0,      // recv_internal
+                76407,  // is_plugin_installed
+                78748,  // get_public_key
+                81467,  // get_subwallet_id
+                85143,  // seqno
+                107653, // get_plugin_list
+            ])
+        );
+    }
+
+    #[test]
+    fn signed_keys() {
+        let key = |bits: &[bool]| bits.iter().copied().collect::<BitVec<u8, Msb0>>();
+
+        assert_eq!(signed(&key(&[false, true, true])), 3);
+        assert_eq!(signed(&key(&[true, true, true])), -1);
+        assert_eq!(signed(&key(&[true, false, false])), -4);
+    }
+
+    #[test]
+    fn code_without_dispatcher() {
+        let mut builder = Cell::builder();
+        builder.pack(0xff00_u16).unwrap().pack(0x8000_u16).unwrap();
+
+        assert_eq!(method_ids(&builder.into_cell()), None);
+        assert_eq!(method_ids(&Cell::default()), None);
+    }
+}
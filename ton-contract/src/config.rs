@@ -0,0 +1,441 @@
+use async_trait::async_trait;
+use num_bigint::BigUint;
+use std::sync::Arc;
+use toner::tlb::bits::de::BitReaderExt;
+use toner::tlb::de::{CellDeserialize, CellParser, CellParserError};
+use toner::tlb::{Cell, Error as _, StringError};
+use tonlibjson_client::boc::parse_base64_boc;
+use tonlibjson_client::{block::TvmBoxedStackEntry, ton::TonClient};
+
+use crate::{
+    adapters::{bits256, list_elements, tuple},
+    config_address, TonContract, TonContractError, TvmBoxedStackEntryExt,
+};
+
+/// Config param which holds the address of the config contract
+const CONFIG_CONFIG_PARAM: i32 = 0;
+
+/// Proposal to change a config param, as returned by `get_proposal` and `list_proposals`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigProposal {
+    pub hash: [u8; 32],
+    pub expires: u32,
+    pub critical: bool,
+    pub param_id: i32,
+    /// none if the proposal removes the param
+    pub param_value: Option<Arc<Cell>>,
+    /// hash of the value the proposal replaces, none if it replaces any value
+    pub param_hash: Option<[u8; 32]>,
+    /// hash of the validator set the votes were collected from
+    pub vset_id: [u8; 32],
+    /// indices of validators of `vset_id` who voted for the proposal
+    pub voters: Vec<u16>,
+    /// negative once the proposal collected enough votes in the current round
+    pub weight_remaining: i64,
+    pub rounds_remaining: u8,
+    pub losses: u8,
+    pub wins: u8,
+}
+
+impl ConfigProposal {
+    /// `[expires, critical, [param_id, param_val, param_hash], vset_id, voters, weight_remaining,
+    /// rounds_remaining, losses, wins]` where `param_val` is a cell or null, `param_hash` is -1
+    /// if there is none and `voters` is a list of validator indices
+    fn from_stack(hash: [u8; 32], proposal: TvmBoxedStackEntry) -> Result<Self, TonContractError> {
+        let [expires, critical, param, vset_id, voters, weight_remaining, rounds_remaining, losses, wins] =
+            tuple(proposal)?.try_into()?;
+        let [param_id, param_value, param_hash] = tuple(param)?.try_into()?;
+
+        let param_value = match param_value {
+            TvmBoxedStackEntry::TvmStackEntryCell(_)
+            | TvmBoxedStackEntry::TvmStackEntrySlice(_) => Some(param_value.to_cell()?),
+            // null
+            _ => None,
+        };
+        let param_hash = match param_hash.to_number::<i32>() {
+            Ok(-1) => None,
+            _ => Some(bits256(&param_hash)?),
+        };
+
+        Ok(Self {
+            hash,
+            expires: expires.to_number()?,
+            critical: critical.to_number::<i32>()? != 0,
+            param_id: param_id.to_number()?,
+            param_value,
+            param_hash,
+            vset_id: bits256(&vset_id)?,
+            voters: list_elements(voters)?
+                .iter()
+                .map(|voter| voter.to_number())
+                .collect::<Result<_, _>>()?,
+            weight_remaining: weight_remaining.to_number()?,
+            rounds_remaining: rounds_remaining.to_number()?,
+            losses: losses.to_number()?,
+            wins: wins.to_number()?,
+        })
+    }
+}
+
+/// Gas prices and limits of a workchain, `gas_price` is in nanotons per 65536 gas units.
+/// The first `flat_gas_limit` gas units cost `flat_gas_price` altogether
+/// ```tlb
+/// gas_prices#dd gas_price:uint64 gas_limit:uint64 gas_credit:uint64 block_gas_limit:uint64
+///   freeze_due_limit:uint64 delete_due_limit:uint64 = GasLimitsPrices;
+/// gas_prices_ext#de gas_price:uint64 gas_limit:uint64 special_gas_limit:uint64 gas_credit:uint64
+///   block_gas_limit:uint64 freeze_due_limit:uint64 delete_due_limit:uint64 = GasLimitsPrices;
+/// gas_flat_pfx#d1 flat_gas_limit:uint64 flat_gas_price:uint64 other:GasLimitsPrices
+///   = GasLimitsPrices;
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GasPrices {
+    pub flat_gas_limit: u64,
+    pub flat_gas_price: u64,
+    pub gas_price: u64,
+    pub gas_limit: u64,
+    /// `gas_limit` if the param has none
+    pub special_gas_limit: u64,
+    pub gas_credit: u64,
+    pub block_gas_limit: u64,
+    pub freeze_due_limit: u64,
+    pub delete_due_limit: u64,
+}
+
+impl<'de> CellDeserialize<'de> for GasPrices {
+    fn parse(parser: &mut CellParser<'de>) -> Result<Self, CellParserError<'de>> {
+        let mut prices = Self::default();
+        let mut tag: u8 = parser.unpack()?;
+        if tag == 0xd1 {
+            prices.flat_gas_limit = parser.unpack()?;
+            prices.flat_gas_price = parser.unpack()?;
+            tag = parser.unpack()?;
+        }
+        if tag != 0xdd && tag != 0xde {
+            return Err(StringError::custom(format!(
+                "unsupported gas prices tag: {tag:#04x}"
+            )));
+        }
+
+        prices.gas_price = parser.unpack()?;
+        prices.gas_limit = parser.unpack()?;
+        prices.special_gas_limit = match tag {
+            0xde => parser.unpack()?,
+            _ => prices.gas_limit,
+        };
+        prices.gas_credit = parser.unpack()?;
+        prices.block_gas_limit = parser.unpack()?;
+        prices.freeze_due_limit = parser.unpack()?;
+        prices.delete_due_limit = parser.unpack()?;
+
+        Ok(prices)
+    }
+}
+
+/// Gas prices of `workchain` as of the latest block, config param 20 of the masterchain or 21 of the others
+pub async fn gas_prices(client: &TonClient, workchain: i32) -> Result<GasPrices, TonContractError> {
+    let param = match workchain {
+        -1 => 20,
+        _ => 21,
+    };
+    let config = client.get_config_param(param).await?;
+
+    Ok(parse_base64_boc(&config.config.bytes)?.parse_fully()?)
+}
+
+/// Parses the result of `list_proposals`, a list of `[hash, proposal]`
+pub fn parse_proposals(
+    stack: Vec<TvmBoxedStackEntry>,
+) -> Result<Vec<ConfigProposal>, TonContractError> {
+    let [list] = stack.try_into()?;
+
+    list_elements(list)?
+        .into_iter()
+        .map(|entry| {
+            let [hash, proposal] = tuple(entry)?.try_into()?;
+
+            ConfigProposal::from_stack(bits256(&hash)?, proposal)
+        })
+        .collect()
+}
+
+/// Parses the result of `get_proposal`, none if there is no such proposal
+pub fn parse_proposal(
+    hash: [u8; 32],
+    stack: Vec<TvmBoxedStackEntry>,
+) -> Result<Option<ConfigProposal>, TonContractError> {
+    let [proposal] = stack.try_into()?;
+
+    match proposal {
+        TvmBoxedStackEntry::TvmStackEntryTuple(_) => {
+            ConfigProposal::from_stack(hash, proposal).map(Some)
+        }
+        // null
+        _ => Ok(None),
+    }
+}
+
+#[async_trait]
+pub trait ConfigContract {
+    /// Calls `list_proposals` get-method
+    async fn list_proposals(&self) -> Result<Vec<ConfigProposal>, TonContractError>;
+
+    /// Calls `get_proposal` get-method
+    async fn get_proposal(
+        &self,
+        hash: [u8; 32],
+    ) -> Result<Option<ConfigProposal>, TonContractError>;
+}
+
+#[async_trait]
+impl ConfigContract for TonContract {
+    async fn list_proposals(&self) -> Result<Vec<ConfigProposal>, TonContractError> {
+        parse_proposals(self.run_get_method("list_proposals", [].into()).await?)
+    }
+
+    async fn get_proposal(
+        &self,
+        hash: [u8; 32],
+    ) -> Result<Option<ConfigProposal>, TonContractError> {
+        let stack = self
+            .run_get_method(
+                "get_proposal",
+                [TvmBoxedStackEntry::from_number(BigUint::from_bytes_be(
+                    &hash,
+                ))]
+                .into(),
+            )
+            .await?;
+
+        parse_proposal(hash, stack)
+    }
+}
+
+#[async_trait]
+pub trait ConfigProposals {
+    /// Active proposals of the config contract from config param 0
+    async fn config_proposals(&self) -> Result<Vec<ConfigProposal>, TonContractError>;
+
+    async fn config_proposal(
+        &self,
+        hash: [u8; 32],
+    ) -> Result<Option<ConfigProposal>, TonContractError>;
+}
+
+#[async_trait]
+impl ConfigProposals for TonClient {
+    async fn config_proposals(&self) -> Result<Vec<ConfigProposal>, TonContractError> {
+        config_contract(self).await?.list_proposals().await
+    }
+
+    async fn config_proposal(
+        &self,
+        hash: [u8; 32],
+    ) -> Result<Option<ConfigProposal>, TonContractError> {
+        config_contract(self).await?.get_proposal(hash).await
+    }
+}
+
+async fn config_contract(client: &TonClient) -> Result<TonContract, TonContractError> {
+    let address = config_address(client, CONFIG_CONFIG_PARAM).await?;
+
+    Ok(TonContract::new(client.clone(), address))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use toner::tlb::bits::ser::BitWriterExt;
+
+    fn number(n: &str) -> serde_json::Value {
+        json!({"@type": "tvm.stackEntryNumber", "number": {"@type": "tvm.numberDecimal", "number": n}})
+    }
+
+    fn tuple(elements: Vec<serde_json::Value>) -> serde_json::Value {
+        json!({"@type": "tvm.stackEntryTuple", "tuple": {"@type": "tvm.tuple", "elements": elements}})
+    }
+
+    fn list(elements: Vec<serde_json::Value>) -> serde_json::Value {
+        json!({"@type": "tvm.stackEntryList", "list": {"@type": "tvm.list", "elements": elements}})
+    }
+
+    fn null() -> serde_json::Value {
+        json!({"@type": "tvm.stackEntryUnsupported"})
+    }
+
+    #[test]
+    fn gas_prices_of_basechain() {
+        let mut builder = Cell::builder();
+        builder
+            .pack(0xd1u8)
+            .unwrap()
+            .pack(100u64)
+            .unwrap()
+            .pack(40_000u64)
+            .unwrap()
+            .pack(0xdeu8)
+            .unwrap();
+        for value in [
+            26_214_400u64,
+            1_000_000,
+            1_000_000,
+            10_000,
+            10_000_000,
+            100_000_000,
+            1_000_000_000,
+        ] {
+            builder.pack(value).unwrap();
+        }
+
+        let prices: GasPrices = builder.into_cell().parse_fully().unwrap();
+
+        assert_eq!(
+            prices,
+            GasPrices {
+                flat_gas_limit: 100,
+                flat_gas_price: 40_000,
+                gas_price: 26_214_400,
+                gas_limit: 1_000_000,
+                special_gas_limit: 1_000_000,
+                gas_credit: 10_000,
+                block_gas_limit: 10_000_000,
+                freeze_due_limit: 100_000_000,
+                delete_due_limit: 1_000_000_000,
+            }
+        );
+    }
+
+    #[test]
+    fn gas_prices_without_flat_prefix() {
+        let mut builder = Cell::builder();
+        builder.pack(0xddu8).unwrap();
+        for value in [
+            655_360_000u64,
+            1_000_000,
+            10_000,
+            2_500_000,
+            100_000_000,
+            1_000_000_000,
+        ] {
+            builder.pack(value).unwrap();
+        }
+
+        let prices: GasPrices = builder.into_cell().parse_fully().unwrap();
+
+        assert_eq!(prices.gas_price, 655_360_000);
+        assert_eq!(prices.special_gas_limit, prices.gas_limit);
+        assert_eq!(prices.flat_gas_limit, 0);
+
+        let mut builder = Cell::builder();
+        builder.pack(0xccu8).unwrap();
+        assert!(builder.into_cell().parse_fully::<GasPrices>().is_err());
+    }
+
+    fn param_value() -> TvmBoxedStackEntry {
+        let mut builder = Cell::builder();
+        builder.pack(5u32).unwrap();
+
+        TvmBoxedStackEntry::from_cell(builder.into_cell()).unwrap()
+    }
+
+    fn proposal(
+        param_value: serde_json::Value,
+        param_hash: &str,
+        voters: serde_json::Value,
+    ) -> serde_json::Value {
+        tuple(vec![
+            number("1718000000"),
+            number("-1"),
+            tuple(vec![number("13"), param_value, number(param_hash)]),
+            number("255"),
+            voters,
+            number("-12345"),
+            number("2"),
+            number("0"),
+            number("1"),
+        ])
+    }
+
+    fn stack(entries: Vec<serde_json::Value>) -> Vec<TvmBoxedStackEntry> {
+        serde_json::from_value(json!(entries)).unwrap()
+    }
+
+    fn bits(last: u8) -> [u8; 32] {
+        let mut bits = [0; 32];
+        bits[31] = last;
+        bits
+    }
+
+    #[test]
+    fn parse_proposals_list() {
+        let stack = stack(vec![list(vec![
+            tuple(vec![
+                number("1"),
+                proposal(
+                    serde_json::to_value(param_value()).unwrap(),
+                    "-1",
+                    list(vec![number("3"), number("0"), number("17")]),
+                ),
+            ]),
+            tuple(vec![number("2"), proposal(null(), "7", null())]),
+        ])]);
+
+        let proposals = parse_proposals(stack).unwrap();
+
+        assert_eq!(proposals.len(), 2);
+        let first = &proposals[0];
+        assert_eq!(first.hash, bits(1));
+        assert_eq!(first.expires, 1718000000);
+        assert!(first.critical);
+        assert_eq!(first.param_id, 13);
+        assert_eq!(first.param_value, Some(param_value().to_cell().unwrap()));
+        assert_eq!(first.param_hash, None);
+        assert_eq!(first.vset_id, bits(255));
+        assert_eq!(first.voters, [3, 0, 17]);
+        assert_eq!(first.weight_remaining, -12345);
+        assert_eq!(
+            (first.rounds_remaining, first.losses, first.wins),
+            (2, 0, 1)
+        );
+
+        let second = &proposals[1];
+        assert_eq!(second.hash, bits(2));
+        assert_eq!(second.param_value, None);
+        assert_eq!(second.param_hash, Some(bits(7)));
+        assert!(second.voters.is_empty());
+    }
+
+    #[test]
+    fn parse_proposals_nested_pairs() {
+        let entry = |hash| tuple(vec![number(hash), proposal(null(), "-1", null())]);
+        let stack = stack(vec![tuple(vec![
+            entry("1"),
+            tuple(vec![entry("2"), null()]),
+        ])]);
+
+        let hashes: Vec<_> = parse_proposals(stack)
+            .unwrap()
+            .into_iter()
+            .map(|p| p.hash)
+            .collect();
+
+        assert_eq!(hashes, [bits(1), bits(2)]);
+    }
+
+    #[test]
+    fn parse_single_proposal() {
+        let found = parse_proposal(bits(9), stack(vec![proposal(null(), "-1", list(vec![]))]))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(found.hash, bits(9));
+        assert_eq!(parse_proposal(bits(9), stack(vec![null()])).unwrap(), None);
+    }
+
+    #[test]
+    fn parse_invalid_proposals() {
+        assert!(parse_proposals(stack(vec![number("0")])).is_err());
+        assert!(parse_proposals(stack(vec![list(vec![tuple(vec![number("1")])])])).is_err());
+        assert!(parse_proposal(bits(1), stack(vec![tuple(vec![number("1")])])).is_err());
+    }
+}
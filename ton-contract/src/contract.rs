@@ -1,4 +1,6 @@
-use toner::ton::MsgAddress;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use toner::tlb::{bits::de::unpack_bytes, r#as::Data};
+use toner::ton::{boc::BoC, MsgAddress};
 use tonlibjson_client::{
     block::{SmcRunResult, TvmBoxedStackEntry},
     ton::TonClient,
@@ -45,3 +47,20 @@ impl TonContract {
         })
     }
 }
+
+/// Masterchain address stored in a config param as `bits256`
+pub(crate) async fn config_address(
+    client: &TonClient,
+    param: i32,
+) -> Result<MsgAddress, TonContractError> {
+    let config = client.get_config_param(param).await?;
+    let boc: BoC = unpack_bytes(STANDARD.decode(config.config.bytes)?)?;
+    let root = boc
+        .single_root()
+        .ok_or_else(|| anyhow::anyhow!("config param {param} is empty"))?;
+
+    Ok(MsgAddress {
+        workchain_id: -1,
+        address: root.parse_fully_as::<_, Data>()?,
+    })
+}
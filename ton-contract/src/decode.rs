@@ -0,0 +1,524 @@
+use num_bigint::BigUint;
+use toner::tlb::bits::de::BitReaderExt;
+use toner::tlb::bits::r#as::VarInt;
+use toner::tlb::de::CellParser;
+use toner::tlb::r#as::Ref;
+use toner::tlb::Cell;
+use toner::ton::MsgAddress;
+
+pub const JETTON_TRANSFER_OP: u32 = 0x0f8a7ea5;
+pub const JETTON_INTERNAL_TRANSFER_OP: u32 = 0x178d4519;
+pub const JETTON_TRANSFER_NOTIFICATION_OP: u32 = 0x7362d09c;
+pub const JETTON_BURN_OP: u32 = 0x595f07bc;
+pub const NFT_TRANSFER_OP: u32 = 0x5fcc3d14;
+pub const NFT_OWNERSHIP_ASSIGNED_OP: u32 = 0x05138d91;
+pub const NFT_GET_STATIC_DATA_OP: u32 = 0x2fcb26a2;
+
+/// Body has a known op but doesn't follow its scheme
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("malformed {op} body")]
+pub struct DecodeError {
+    pub op: &'static str,
+}
+
+/// TEP-74 operation of a message body, amounts are in minimal units of the jetton
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JettonMessage {
+    /// owner asks their jetton wallet to send jettons to `destination` owner
+    Transfer {
+        query_id: u64,
+        amount: BigUint,
+        destination: MsgAddress,
+        response_destination: MsgAddress,
+        forward_ton_amount: BigUint,
+        comment: Option<String>,
+    },
+    /// jetton wallet of `from` owner credits the receiving jetton wallet
+    InternalTransfer {
+        query_id: u64,
+        amount: BigUint,
+        from: MsgAddress,
+        response_destination: MsgAddress,
+        forward_ton_amount: BigUint,
+        comment: Option<String>,
+    },
+    /// jetton wallet tells its owner about jettons received from `sender` owner
+    TransferNotification {
+        query_id: u64,
+        amount: BigUint,
+        sender: MsgAddress,
+        comment: Option<String>,
+    },
+    Burn {
+        query_id: u64,
+        amount: BigUint,
+        response_destination: MsgAddress,
+    },
+}
+
+/// Decodes the message body if its op is one of the jetton ones,
+/// bodies with another op or malformed ones give none
+pub fn decode_jetton_message(body: &Cell) -> Option<JettonMessage> {
+    // every read is bounds checked first, as the bit reader panics on missing bits
+    let mut parser = body.parser();
+    let op = uint(&mut parser, 32)? as u32;
+    let query_id = uint(&mut parser, 64)?;
+    let amount = var_uint16(&mut parser)?;
+
+    let message = match op {
+        JETTON_TRANSFER_OP => {
+            let destination = address(&mut parser)?;
+            let response_destination = address(&mut parser)?;
+            // custom_payload:(Maybe ^Cell)
+            if uint(&mut parser, 1)? == 1 {
+                reference(&mut parser)?;
+            }
+
+            JettonMessage::Transfer {
+                query_id,
+                amount,
+                destination,
+                response_destination,
+                forward_ton_amount: var_uint16(&mut parser)?,
+                comment: comment(forward_payload(&mut parser)?),
+            }
+        }
+        JETTON_INTERNAL_TRANSFER_OP => JettonMessage::InternalTransfer {
+            query_id,
+            amount,
+            from: address(&mut parser)?,
+            response_destination: address(&mut parser)?,
+            forward_ton_amount: var_uint16(&mut parser)?,
+            comment: comment(forward_payload(&mut parser)?),
+        },
+        JETTON_TRANSFER_NOTIFICATION_OP => JettonMessage::TransferNotification {
+            query_id,
+            amount,
+            sender: address(&mut parser)?,
+            comment: comment(forward_payload(&mut parser)?),
+        },
+        JETTON_BURN_OP => JettonMessage::Burn {
+            query_id,
+            amount,
+            response_destination: address(&mut parser)?,
+        },
+        _ => return None,
+    };
+
+    Some(message)
+}
+
+/// TEP-62 operation of a message body
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NftMessage {
+    /// owner asks the item to pass the ownership to `new_owner`
+    Transfer {
+        query_id: u64,
+        new_owner: MsgAddress,
+        response_destination: MsgAddress,
+        forward_amount: BigUint,
+        forward_payload: Option<Cell>,
+        comment: Option<String>,
+    },
+    /// item tells `new_owner` it got the ownership from `prev_owner`
+    OwnershipAssigned {
+        query_id: u64,
+        prev_owner: MsgAddress,
+        forward_payload: Option<Cell>,
+        comment: Option<String>,
+    },
+    GetStaticData {
+        query_id: u64,
+    },
+}
+
+/// Decodes the message body if its op is one of the NFT ones,
+/// bodies with another op give none and malformed ones an error
+pub fn decode_nft_message(body: &Cell) -> Option<Result<NftMessage, DecodeError>> {
+    let mut parser = body.parser();
+    let op = uint(&mut parser, 32)? as u32;
+    let name = match op {
+        NFT_TRANSFER_OP => "nft transfer",
+        NFT_OWNERSHIP_ASSIGNED_OP => "nft ownership_assigned",
+        NFT_GET_STATIC_DATA_OP => "nft get_static_data",
+        _ => return None,
+    };
+
+    Some(decode_nft_body(op, &mut parser).ok_or(DecodeError { op: name }))
+}
+
+fn decode_nft_body(op: u32, parser: &mut CellParser<'_>) -> Option<NftMessage> {
+    let query_id = uint(parser, 64)?;
+
+    let message = match op {
+        NFT_TRANSFER_OP => {
+            let new_owner = address(parser)?;
+            let response_destination = address(parser)?;
+            // custom_payload:(Maybe ^Cell)
+            if uint(parser, 1)? == 1 {
+                reference(parser)?;
+            }
+            let forward_amount = var_uint16(parser)?;
+            let forward_payload = forward_payload(parser)?;
+
+            NftMessage::Transfer {
+                query_id,
+                new_owner,
+                response_destination,
+                forward_amount,
+                comment: comment(forward_payload.clone()),
+                forward_payload,
+            }
+        }
+        NFT_OWNERSHIP_ASSIGNED_OP => {
+            let prev_owner = address(parser)?;
+            let forward_payload = forward_payload(parser)?;
+
+            NftMessage::OwnershipAssigned {
+                query_id,
+                prev_owner,
+                comment: comment(forward_payload.clone()),
+                forward_payload,
+            }
+        }
+        NFT_GET_STATIC_DATA_OP => NftMessage::GetStaticData { query_id },
+        _ => return None,
+    };
+
+    Some(message)
+}
+
+pub(crate) fn uint(parser: &mut CellParser<'_>, bits: usize) -> Option<u64> {
+    if parser.bits_left() < bits {
+        return None;
+    }
+
+    (0..bits).try_fold(0_u64, |acc, _| {
+        Some((acc << 1) | u64::from(parser.unpack::<bool>().ok()?))
+    })
+}
+
+/// ```tlb
+/// var_uint$_ {n:#} len:(#< n) value:(uint (len * 8)) = VarUInteger n;
+/// ```
+pub(crate) fn var_uint16(parser: &mut CellParser<'_>) -> Option<BigUint> {
+    let len = uint(&mut parser.clone(), 4)? as usize;
+    if parser.bits_left() < 4 + len * 8 {
+        return None;
+    }
+
+    parser.unpack_as::<_, VarInt<4>>().ok()
+}
+
+/// Only `addr_none` and `addr_std` without anycast, the ones jetton wallets use
+pub(crate) fn address(parser: &mut CellParser<'_>) -> Option<MsgAddress> {
+    let bits = match uint(&mut parser.clone(), 2)? {
+        0b00 => 2,
+        0b10 if uint(&mut parser.clone(), 3)? == 0b100 => 3 + 8 + 256,
+        _ => return None,
+    };
+    if parser.bits_left() < bits {
+        return None;
+    }
+
+    parser.unpack().ok()
+}
+
+pub(crate) fn reference<'de>(parser: &mut CellParser<'de>) -> Option<CellParser<'de>> {
+    if parser.no_references_left() {
+        return None;
+    }
+
+    parser.parse_as::<CellParser, Ref>().ok()
+}
+
+/// `forward_payload:(Either Cell ^Cell)`
+fn forward_payload(parser: &mut CellParser<'_>) -> Option<Option<Cell>> {
+    let mut payload = match uint(parser, 1) {
+        // payloads are often cut off entirely
+        None => return Some(None),
+        Some(0) => parser.clone(),
+        Some(_) => reference(parser)?,
+    };
+
+    payload.parse().ok().map(Some)
+}
+
+/// Text comment of a forward payload, none for other payloads
+fn comment(payload: Option<Cell>) -> Option<String> {
+    let payload = payload?;
+    let mut parser = payload.parser();
+    if uint(&mut parser, 32) != Some(0) {
+        return None;
+    }
+
+    snake_text(parser)
+}
+
+/// Text stored in the rest of the cell and its chain of first references
+fn snake_text(mut parser: CellParser<'_>) -> Option<String> {
+    let mut text = Vec::new();
+    loop {
+        if parser.bits_left() % 8 != 0 {
+            return None;
+        }
+        while !parser.no_bits_left() {
+            text.push(uint(&mut parser, 8)? as u8);
+        }
+
+        match parser.references_left() {
+            0 => break,
+            1 => parser = reference(&mut parser)?,
+            _ => return None,
+        }
+    }
+
+    String::from_utf8(text).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use toner::contracts::jetton::{
+        ForwardPayload, ForwardPayloadComment, JettonTransfer, JettonTransferNotification,
+    };
+    use toner::tlb::bits::ser::BitWriterExt;
+    use toner::tlb::ser::CellSerializeExt;
+
+    fn owner() -> MsgAddress {
+        "EQCjk1hh952vWaE9bRguFkAhDAL5jj3xj9p0uPWrFBq_GEMS"
+            .parse()
+            .unwrap()
+    }
+
+    fn wallet() -> MsgAddress {
+        "EQBGXZ9ddZeWypx8EkJieHJX75ct0bpkmu0Y4YoYr3NM0Z9e"
+            .parse()
+            .unwrap()
+    }
+
+    fn transfer() -> Cell {
+        JettonTransfer::<Cell, Cell> {
+            query_id: 42,
+            amount: BigUint::from(1_000_000_000_u64),
+            dst: owner(),
+            response_dst: wallet(),
+            custom_payload: None,
+            forward_ton_amount: BigUint::from(1_u8),
+            forward_payload: ForwardPayload::Comment(ForwardPayloadComment::Text(
+                "invoice 17".to_owned(),
+            )),
+        }
+        .to_cell()
+        .unwrap()
+    }
+
+    #[test]
+    fn decode_transfer_with_comment() {
+        assert_eq!(
+            decode_jetton_message(&transfer()),
+            Some(JettonMessage::Transfer {
+                query_id: 42,
+                amount: BigUint::from(1_000_000_000_u64),
+                destination: owner(),
+                response_destination: wallet(),
+                forward_ton_amount: BigUint::from(1_u8),
+                comment: Some("invoice 17".to_owned()),
+            })
+        );
+    }
+
+    #[test]
+    fn decode_internal_transfer() {
+        let mut payload = Cell::builder();
+        payload.pack(0_u32).unwrap().pack("gm").unwrap();
+        let mut body = Cell::builder();
+        body.pack(JETTON_INTERNAL_TRANSFER_OP)
+            .unwrap()
+            .pack(7_u64)
+            .unwrap()
+            .pack_as::<_, VarInt<4>>(BigUint::from(500_u32))
+            .unwrap()
+            .pack(owner())
+            .unwrap()
+            .pack(wallet())
+            .unwrap()
+            .pack_as::<_, VarInt<4>>(BigUint::from(0_u8))
+            .unwrap()
+            // forward payload in a reference
+            .pack(true)
+            .unwrap()
+            .store_as::<_, Ref>(payload.into_cell())
+            .unwrap();
+
+        assert_eq!(
+            decode_jetton_message(&body.into_cell()),
+            Some(JettonMessage::InternalTransfer {
+                query_id: 7,
+                amount: BigUint::from(500_u32),
+                from: owner(),
+                response_destination: wallet(),
+                forward_ton_amount: BigUint::from(0_u8),
+                comment: Some("gm".to_owned()),
+            })
+        );
+    }
+
+    #[test]
+    fn decode_transfer_notification_without_comment() {
+        let body = JettonTransferNotification::<Cell> {
+            query_id: 1,
+            amount: BigUint::from(3_u8),
+            sender: owner(),
+            forward_payload: ForwardPayload::Data(Cell::default()),
+        }
+        .to_cell()
+        .unwrap();
+
+        assert_eq!(
+            decode_jetton_message(&body),
+            Some(JettonMessage::TransferNotification {
+                query_id: 1,
+                amount: BigUint::from(3_u8),
+                sender: owner(),
+                comment: None,
+            })
+        );
+    }
+
+    #[test]
+    fn decode_burn() {
+        let mut body = Cell::builder();
+        body.pack(JETTON_BURN_OP)
+            .unwrap()
+            .pack(9_u64)
+            .unwrap()
+            .pack_as::<_, VarInt<4>>(BigUint::from(10_u8))
+            .unwrap()
+            .pack(owner())
+            .unwrap()
+            // no custom payload
+            .pack(false)
+            .unwrap();
+
+        assert_eq!(
+            decode_jetton_message(&body.into_cell()),
+            Some(JettonMessage::Burn {
+                query_id: 9,
+                amount: BigUint::from(10_u8),
+                response_destination: owner(),
+            })
+        );
+    }
+
+    #[test]
+    fn decode_other_op() {
+        let mut comment = Cell::builder();
+        comment.pack(0_u32).unwrap().pack("hello").unwrap();
+        let mut truncated = Cell::builder();
+        truncated
+            .pack(JETTON_TRANSFER_OP)
+            .unwrap()
+            .pack(1_u64)
+            .unwrap();
+
+        assert_eq!(decode_jetton_message(&comment.into_cell()), None);
+        assert_eq!(decode_jetton_message(&truncated.into_cell()), None);
+        assert_eq!(decode_jetton_message(&Cell::default()), None);
+    }
+
+    fn nft_transfer() -> Cell {
+        let mut body = Cell::builder();
+        body.pack(NFT_TRANSFER_OP)
+            .unwrap()
+            .pack(5_u64)
+            .unwrap()
+            .pack(owner())
+            .unwrap()
+            .pack(wallet())
+            .unwrap()
+            // no custom payload
+            .pack(false)
+            .unwrap()
+            .pack_as::<_, VarInt<4>>(BigUint::from(1_u8))
+            .unwrap()
+            // inline forward payload
+            .pack(false)
+            .unwrap()
+            .pack(0_u32)
+            .unwrap()
+            .pack("gift")
+            .unwrap();
+
+        body.into_cell()
+    }
+
+    #[test]
+    fn decode_nft_transfer() {
+        let mut payload = Cell::builder();
+        payload.pack(0_u32).unwrap().pack("gift").unwrap();
+
+        assert_eq!(
+            decode_nft_message(&nft_transfer()),
+            Some(Ok(NftMessage::Transfer {
+                query_id: 5,
+                new_owner: owner(),
+                response_destination: wallet(),
+                forward_amount: BigUint::from(1_u8),
+                forward_payload: Some(payload.into_cell()),
+                comment: Some("gift".to_owned()),
+            }))
+        );
+    }
+
+    #[test]
+    fn decode_nft_ownership_assigned() {
+        let mut body = Cell::builder();
+        body.pack(NFT_OWNERSHIP_ASSIGNED_OP)
+            .unwrap()
+            .pack(5_u64)
+            .unwrap()
+            .pack(owner())
+            .unwrap();
+
+        assert_eq!(
+            decode_nft_message(&body.into_cell()),
+            Some(Ok(NftMessage::OwnershipAssigned {
+                query_id: 5,
+                prev_owner: owner(),
+                forward_payload: None,
+                comment: None,
+            }))
+        );
+    }
+
+    #[test]
+    fn decode_nft_malformed() {
+        let body = nft_transfer();
+
+        // cuts between the op and the forward payload, a missing payload is allowed
+        for len in 32..body.data.len() - (1 + 32 + 4 * 8) {
+            let mut truncated = Cell::builder();
+            truncated.pack(&body.data[..len]).unwrap();
+
+            assert_eq!(
+                decode_nft_message(&truncated.into_cell()),
+                Some(Err(DecodeError { op: "nft transfer" }))
+            );
+        }
+        assert_eq!(decode_nft_message(&transfer()), None);
+    }
+
+    #[test]
+    fn decode_truncated_transfer() {
+        let body = transfer();
+
+        for len in 0..body.data.len() {
+            let mut truncated = Cell::builder();
+            truncated.pack(&body.data[..len]).unwrap();
+
+            let decoded = decode_jetton_message(&truncated.into_cell());
+            assert!(decoded.is_none() || matches!(decoded, Some(JettonMessage::Transfer { .. })));
+        }
+    }
+}
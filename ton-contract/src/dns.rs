@@ -0,0 +1,287 @@
+use async_trait::async_trait;
+use core::str::FromStr;
+use num_bigint::BigUint;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use toner::{
+    tlb::bits::de::BitReaderExt,
+    tlb::bits::ser::BitWriterExt,
+    tlb::de::{CellDeserialize, CellParser, CellParserError},
+    tlb::{Cell, Error as _},
+    ton::MsgAddress,
+};
+use tonlibjson_client::{block::TvmBoxedStackEntry, ton::TonClient};
+
+use crate::{config_address, TonContract, TonContractError, TvmBoxedStackEntryExt};
+
+/// Hops allowed between resolvers before giving up on a domain
+const MAX_RESOLVE_STEPS: usize = 16;
+
+/// Config param which holds the address of the root DNS contract
+const DNS_ROOT_CONFIG_PARAM: i32 = 4;
+
+const DNS_SMC_ADDRESS_TAG: u16 = 0x9fd3;
+const DNS_NEXT_RESOLVER_TAG: u16 = 0xba93;
+const DNS_ADNL_ADDRESS_TAG: u16 = 0xad01;
+const DNS_STORAGE_ADDRESS_TAG: u16 = 0x7473;
+
+/// Record categories from [TEP-81](https://github.com/ton-blockchain/TEPs/blob/master/text/0081-dns-standard.md)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DnsCategory {
+    Wallet,
+    Site,
+    Storage,
+    NextResolver,
+}
+
+impl DnsCategory {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Wallet => "wallet",
+            Self::Site => "site",
+            Self::Storage => "storage",
+            Self::NextResolver => "dns_next_resolver",
+        }
+    }
+
+    /// Category id is `sha256` of the category name
+    pub fn id(&self) -> [u8; 32] {
+        Sha256::digest(self.name()).into()
+    }
+}
+
+impl FromStr for DnsCategory {
+    type Err = TonContractError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "wallet" => Ok(Self::Wallet),
+            "site" => Ok(Self::Site),
+            "storage" => Ok(Self::Storage),
+            "dns_next_resolver" => Ok(Self::NextResolver),
+            _ => Err(TonContractError::Dns(format!("unsupported category: {s}"))),
+        }
+    }
+}
+
+/// ```tlb
+/// dns_smc_address#9fd3 smc_addr:MsgAddressInt flags:(## 8) { flags <= 1 }
+///   cap_list:flags . 0?SmcCapList = DNSRecord;
+/// dns_next_resolver#ba93 resolver:MsgAddressInt = DNSRecord;
+/// dns_adnl_address#ad01 adnl_addr:bits256 flags:(## 8) { flags <= 1 }
+///   proto_list:flags . 0?ProtoList = DNSRecord;
+/// dns_storage_address#7473 bag_id:bits256 = DNSRecord;
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DnsRecord {
+    Wallet(MsgAddress),
+    NextResolver(MsgAddress),
+    Site([u8; 32]),
+    Storage([u8; 32]),
+}
+
+impl<'de> CellDeserialize<'de> for DnsRecord {
+    fn parse(parser: &mut CellParser<'de>) -> Result<Self, CellParserError<'de>> {
+        // capability and protocol lists are not exposed, so the rest of the cell is ignored
+        match parser.unpack::<u16>()? {
+            DNS_SMC_ADDRESS_TAG => Ok(Self::Wallet(parser.unpack()?)),
+            DNS_NEXT_RESOLVER_TAG => Ok(Self::NextResolver(parser.unpack()?)),
+            DNS_ADNL_ADDRESS_TAG => Ok(Self::Site(parser.unpack()?)),
+            DNS_STORAGE_ADDRESS_TAG => Ok(Self::Storage(parser.unpack()?)),
+            tag => Err(toner::tlb::StringError::custom(format!(
+                "unsupported dns record tag: {tag:#06x}"
+            ))),
+        }
+    }
+}
+
+/// Encodes domain into the internal representation: labels in reverse order, each terminated by `\0`,
+/// i.e. `alice.ton` becomes `ton\0alice\0`
+pub fn encode_domain(domain: &str) -> Result<Vec<u8>, TonContractError> {
+    let domain = domain.trim_end_matches('.').to_lowercase();
+    if domain.is_empty() {
+        return Err(TonContractError::Dns("empty domain".to_owned()));
+    }
+
+    let mut bytes = Vec::with_capacity(domain.len() + 1);
+    for label in domain.rsplit('.') {
+        if label.is_empty() || label.bytes().any(|b| b == 0) {
+            return Err(TonContractError::Dns(format!("invalid domain: {domain}")));
+        }
+        bytes.extend_from_slice(label.as_bytes());
+        bytes.push(0);
+    }
+
+    Ok(bytes)
+}
+
+#[async_trait]
+pub trait DnsContract {
+    /// Calls `dnsresolve` get-method, returns number of resolved bits and the record cell if any
+    async fn dnsresolve(
+        &self,
+        subdomain: &[u8],
+        category: DnsCategory,
+    ) -> Result<(usize, Option<Arc<Cell>>), TonContractError>;
+}
+
+#[async_trait]
+impl DnsContract for TonContract {
+    async fn dnsresolve(
+        &self,
+        subdomain: &[u8],
+        category: DnsCategory,
+    ) -> Result<(usize, Option<Arc<Cell>>), TonContractError> {
+        let mut builder = Cell::builder();
+        builder.pack(subdomain)?;
+
+        let [resolved_bits, record] = self
+            .run_get_method(
+                "dnsresolve",
+                [
+                    TvmBoxedStackEntry::from_cell(builder.into_cell())?,
+                    TvmBoxedStackEntry::from_number(BigUint::from_bytes_be(&category.id())),
+                ]
+                .into(),
+            )
+            .await?
+            .try_into()?;
+
+        // null is returned as a non-cell stack entry
+        let record = match record {
+            TvmBoxedStackEntry::TvmStackEntryCell(_)
+            | TvmBoxedStackEntry::TvmStackEntrySlice(_) => Some(record.to_cell()?),
+            _ => None,
+        };
+
+        Ok((resolved_bits.to_number()?, record))
+    }
+}
+
+#[async_trait]
+pub trait DnsResolver {
+    /// Resolves `domain` recursively starting from the root DNS contract, `None` if there is no such record
+    async fn dns_resolve(
+        &self,
+        domain: &str,
+        category: DnsCategory,
+    ) -> Result<Option<DnsRecord>, TonContractError>;
+}
+
+#[async_trait]
+impl DnsResolver for TonClient {
+    async fn dns_resolve(
+        &self,
+        domain: &str,
+        category: DnsCategory,
+    ) -> Result<Option<DnsRecord>, TonContractError> {
+        let mut name = encode_domain(domain)?;
+        let mut resolver = dns_root_address(self).await?;
+
+        for _ in 0..MAX_RESOLVE_STEPS {
+            let (resolved_bits, record) = TonContract::new(self.clone(), resolver)
+                .dnsresolve(&name, category)
+                .await?;
+
+            if resolved_bits == 0 {
+                return Ok(None);
+            }
+            if resolved_bits % 8 != 0 || resolved_bits > name.len() * 8 {
+                return Err(TonContractError::Dns(format!(
+                    "invalid number of resolved bits: {resolved_bits}"
+                )));
+            }
+            let Some(record) = record else {
+                return Ok(None);
+            };
+            let record: DnsRecord = record.parser().parse()?;
+
+            let rest = &name[resolved_bits / 8..];
+            match record {
+                // fully resolved, but the record lives in the next resolver
+                DnsRecord::NextResolver(next)
+                    if rest.is_empty() && category != DnsCategory::NextResolver =>
+                {
+                    resolver = next;
+                    name = vec![0];
+                }
+                record if rest.is_empty() => return Ok(Some(record)),
+                DnsRecord::NextResolver(next) => {
+                    resolver = next;
+                    name = rest.to_vec();
+                }
+                _ => {
+                    return Err(TonContractError::Dns(
+                        "partially resolved domain without next resolver".to_owned(),
+                    ))
+                }
+            }
+        }
+
+        Err(TonContractError::Dns(format!(
+            "too many resolution steps for {domain}"
+        )))
+    }
+}
+
+async fn dns_root_address(client: &TonClient) -> Result<MsgAddress, TonContractError> {
+    // _ dns_root_addr:bits256 = ConfigParam 4;
+    config_address(client, DNS_ROOT_CONFIG_PARAM).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_domain_reverses_labels() {
+        assert_eq!(encode_domain("alice.ton").unwrap(), b"ton\0alice\0");
+        assert_eq!(
+            encode_domain("Sub.Alice.ton.").unwrap(),
+            b"ton\0alice\0sub\0"
+        );
+    }
+
+    #[test]
+    fn encode_domain_rejects_empty_labels() {
+        assert!(encode_domain("").is_err());
+        assert!(encode_domain("alice..ton").is_err());
+    }
+
+    #[test]
+    fn category_id_is_sha256_of_name() {
+        assert_eq!(
+            hex::encode(DnsCategory::Wallet.id()),
+            "e8d44050873dba865aa7c170ab4cce64d90839a34dcfd6cf71d14e0205443b1b"
+        );
+    }
+
+    #[test]
+    fn parse_wallet_record() {
+        let address: MsgAddress = "EQBGXZ9ddZeWypx8EkJieHJX75ct0bpkmu0Y4YoYr3NM0Z9e"
+            .parse()
+            .unwrap();
+        let mut builder = Cell::builder();
+        builder
+            .pack(DNS_SMC_ADDRESS_TAG)
+            .unwrap()
+            .pack(address)
+            .unwrap()
+            .pack(0_u8)
+            .unwrap();
+
+        let record: DnsRecord = builder.into_cell().parser().parse().unwrap();
+
+        assert_eq!(record, DnsRecord::Wallet(address));
+    }
+
+    #[test]
+    fn parse_unknown_record() {
+        let mut builder = Cell::builder();
+        builder.pack(0x1eda_u16).unwrap();
+
+        let record = builder.into_cell().parser().parse::<DnsRecord>();
+
+        assert!(record.is_err());
+    }
+}
@@ -0,0 +1,296 @@
+use async_trait::async_trait;
+use futures::try_join;
+use num_bigint::BigUint;
+use toner::ton::MsgAddress;
+use tonlibjson_client::{block::TvmBoxedStackEntry, ton::TonClient};
+
+use crate::{
+    adapters::{bits256, list_elements, tuple},
+    config_address, TonContract, TonContractError, TvmBoxedStackEntryExt,
+};
+
+/// Config param which holds the address of the elector contract
+const ELECTOR_CONFIG_PARAM: i32 = 1;
+
+/// Current election as returned by `participant_list_extended`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ElectionData {
+    /// `elect_at` of the active election, 0 when there is none
+    pub election_id: u64,
+    pub elect_close: u64,
+    pub min_stake: BigUint,
+    pub total_stake: BigUint,
+    pub participants: Vec<ElectionParticipant>,
+    pub failed: bool,
+    pub finished: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ElectionParticipant {
+    pub pubkey: [u8; 32],
+    pub stake: BigUint,
+    /// in 1/65536 units
+    pub max_factor: u32,
+    /// masterchain wallet which made the stake
+    pub wallet: MsgAddress,
+    pub adnl_addr: [u8; 32],
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stake {
+    /// stake in the active election, 0 if the wallet doesn't take part
+    pub stake: BigUint,
+    /// frozen stakes and bonuses which can be recovered right now
+    pub returned_stake: BigUint,
+}
+
+impl TryFrom<Vec<TvmBoxedStackEntry>> for ElectionData {
+    type Error = TonContractError;
+
+    /// `(elect_at, elect_close, min_stake, total_stake, l, failed, finished)`
+    /// where `l` is a list of `[pubkey, [stake, max_factor, wallet, adnl_addr]]`
+    fn try_from(stack: Vec<TvmBoxedStackEntry>) -> Result<Self, Self::Error> {
+        let [elect_at, elect_close, min_stake, total_stake, list, failed, finished] =
+            stack.try_into()?;
+
+        let participants = list_elements(list)?
+            .into_iter()
+            .map(|participant| {
+                let [pubkey, data] = tuple(participant)?.try_into()?;
+                let [stake, max_factor, wallet, adnl_addr] = tuple(data)?.try_into()?;
+
+                Ok(ElectionParticipant {
+                    pubkey: bits256(&pubkey)?,
+                    stake: stake.to_number()?,
+                    max_factor: max_factor.to_number()?,
+                    wallet: MsgAddress {
+                        workchain_id: -1,
+                        address: bits256(&wallet)?,
+                    },
+                    adnl_addr: bits256(&adnl_addr)?,
+                })
+            })
+            .collect::<Result<_, TonContractError>>()?;
+
+        Ok(Self {
+            election_id: elect_at.to_number()?,
+            elect_close: elect_close.to_number()?,
+            min_stake: min_stake.to_number()?,
+            total_stake: total_stake.to_number()?,
+            participants,
+            failed: failed.to_number::<i32>()? != 0,
+            finished: finished.to_number::<i32>()? != 0,
+        })
+    }
+}
+
+#[async_trait]
+pub trait ElectorContract {
+    /// Calls `participant_list_extended` get-method
+    async fn participant_list_extended(&self) -> Result<ElectionData, TonContractError>;
+
+    /// Calls `compute_returned_stake` get-method, `wallet` has to be a masterchain address
+    async fn compute_returned_stake(
+        &self,
+        wallet: &MsgAddress,
+    ) -> Result<BigUint, TonContractError>;
+}
+
+#[async_trait]
+impl ElectorContract for TonContract {
+    async fn participant_list_extended(&self) -> Result<ElectionData, TonContractError> {
+        self.run_get_method("participant_list_extended", [].into())
+            .await?
+            .try_into()
+    }
+
+    async fn compute_returned_stake(
+        &self,
+        wallet: &MsgAddress,
+    ) -> Result<BigUint, TonContractError> {
+        let [returned_stake] = self
+            .run_get_method(
+                "compute_returned_stake",
+                [TvmBoxedStackEntry::from_number(BigUint::from_bytes_be(
+                    &wallet.address,
+                ))]
+                .into(),
+            )
+            .await?
+            .try_into()?;
+
+        returned_stake.to_number()
+    }
+}
+
+#[async_trait]
+pub trait Elector {
+    /// Election data of the elector from config param 1
+    async fn election_data(&self) -> Result<ElectionData, TonContractError>;
+
+    /// Stake of `wallet` in the active election and the funds it can recover
+    async fn stake(&self, wallet: &MsgAddress) -> Result<Stake, TonContractError>;
+}
+
+#[async_trait]
+impl Elector for TonClient {
+    async fn election_data(&self) -> Result<ElectionData, TonContractError> {
+        elector(self).await?.participant_list_extended().await
+    }
+
+    async fn stake(&self, wallet: &MsgAddress) -> Result<Stake, TonContractError> {
+        let elector = elector(self).await?;
+        let (election, returned_stake) = try_join!(
+            elector.participant_list_extended(),
+            elector.compute_returned_stake(wallet)
+        )?;
+
+        let stake = election
+            .participants
+            .into_iter()
+            .find(|participant| participant.wallet == *wallet)
+            .map(|participant| participant.stake)
+            .unwrap_or_default();
+
+        Ok(Stake {
+            stake,
+            returned_stake,
+        })
+    }
+}
+
+async fn elector(client: &TonClient) -> Result<TonContract, TonContractError> {
+    let address = config_address(client, ELECTOR_CONFIG_PARAM).await?;
+
+    Ok(TonContract::new(client.clone(), address))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn number(n: &str) -> serde_json::Value {
+        json!({"@type": "tvm.stackEntryNumber", "number": {"@type": "tvm.numberDecimal", "number": n}})
+    }
+
+    fn tuple(elements: Vec<serde_json::Value>) -> serde_json::Value {
+        json!({"@type": "tvm.stackEntryTuple", "tuple": {"@type": "tvm.tuple", "elements": elements}})
+    }
+
+    fn participant(pubkey: &str, stake: &str, wallet: &str) -> serde_json::Value {
+        tuple(vec![
+            number(pubkey),
+            tuple(vec![
+                number(stake),
+                number("196608"),
+                number(wallet),
+                number("1"),
+            ]),
+        ])
+    }
+
+    fn stack(list: serde_json::Value) -> Vec<TvmBoxedStackEntry> {
+        serde_json::from_value(json!([
+            number("1700000000"),
+            number("1699990000"),
+            number("300000000000000"),
+            number("1500000000000000"),
+            list,
+            number("0"),
+            number("-1"),
+        ]))
+        .unwrap()
+    }
+
+    #[test]
+    fn parse_election_data_list() {
+        let list = json!({"@type": "tvm.stackEntryList", "list": {"@type": "tvm.list", "elements": [
+            participant(
+                "72585508153698218530463839426049123349046390453089316397449926587012838236190",
+                "1000000000000000",
+                "255"
+            ),
+            participant("2", "500000000000000", "256"),
+        ]}});
+
+        let election = ElectionData::try_from(stack(list)).unwrap();
+
+        assert_eq!(election.election_id, 1700000000);
+        assert_eq!(election.total_stake, BigUint::from(1500000000000000_u64));
+        assert!(!election.failed);
+        assert!(election.finished);
+        assert_eq!(election.participants.len(), 2);
+        assert_eq!(
+            election.participants[0],
+            ElectionParticipant {
+                pubkey: hex::decode(
+                    "a079f114f1d778a30658eb60b996d2b8a1f53a419ef2e89dd74abb8c123aa01e"
+                )
+                .unwrap()
+                .try_into()
+                .unwrap(),
+                stake: BigUint::from(1000000000000000_u64),
+                max_factor: 196608,
+                wallet: MsgAddress {
+                    workchain_id: -1,
+                    address: {
+                        let mut address = [0; 32];
+                        address[31] = 0xff;
+                        address
+                    },
+                },
+                adnl_addr: {
+                    let mut adnl_addr = [0; 32];
+                    adnl_addr[31] = 1;
+                    adnl_addr
+                },
+            }
+        );
+        assert_eq!(election.participants[1].wallet.address[30..], [1, 0]);
+    }
+
+    #[test]
+    fn parse_election_data_nested_pairs() {
+        let list = tuple(vec![
+            participant("1", "10", "1"),
+            tuple(vec![
+                participant("2", "20", "2"),
+                json!({"@type": "tvm.stackEntryUnsupported"}),
+            ]),
+        ]);
+
+        let election = ElectionData::try_from(stack(list)).unwrap();
+
+        assert_eq!(
+            election
+                .participants
+                .iter()
+                .map(|p| p.stake.clone())
+                .collect::<Vec<_>>(),
+            [BigUint::from(10_u8), BigUint::from(20_u8)]
+        );
+    }
+
+    #[test]
+    fn parse_election_data_without_election() {
+        let empty =
+            json!({"@type": "tvm.stackEntryList", "list": {"@type": "tvm.list", "elements": []}});
+        let mut stack = stack(empty);
+        stack[0] = TvmBoxedStackEntry::from_number(0);
+
+        let election = ElectionData::try_from(stack).unwrap();
+
+        assert_eq!(election.election_id, 0);
+        assert!(election.participants.is_empty());
+    }
+
+    #[test]
+    fn parse_election_data_invalid_stack() {
+        let mut stack = stack(number("0"));
+
+        assert!(ElectionData::try_from(stack.clone()).is_err());
+        stack.pop();
+        assert!(ElectionData::try_from(stack).is_err());
+    }
+}
@@ -2,6 +2,8 @@ use thiserror::Error as ThisError;
 use toner::tlb::StringError as TlbError;
 use tonlibjson_client::block::TvmBoxedStackEntry;
 
+use crate::wallet::HighloadBatchError;
+
 #[derive(Debug, ThisError)]
 pub enum TonContractError {
     #[error("contract failed with exit code: {0}")]
@@ -14,6 +16,10 @@ pub enum TonContractError {
     Base64(#[from] base64::DecodeError),
     #[error("cannot parse number: {0}")]
     ParseNumber(String),
+    #[error("dns: {0}")]
+    Dns(String),
+    #[error("highload batch: {0}")]
+    HighloadBatch(#[from] HighloadBatchError),
     #[error(transparent)]
     Client(#[from] anyhow::Error),
 }
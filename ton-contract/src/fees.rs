@@ -0,0 +1,350 @@
+use toner::tlb::bits::bitvec::{order::Msb0, vec::BitVec};
+use toner::tlb::bits::de::BitReaderExt;
+use toner::tlb::de::{CellDeserialize, CellParser, CellParserError};
+use toner::tlb::r#as::NoArgs;
+use toner::tlb::{Error as _, StringError};
+use toner::ton::hashmap::Hashmap;
+use tonlibjson_client::boc::parse_base64_boc;
+use tonlibjson_client::ton::TonClient;
+
+use crate::config::{gas_prices, GasPrices};
+use crate::TonContractError;
+
+/// Prices of config params are per 65536 units
+const PRICE_SHIFT: u32 = 16;
+
+/// Storage prices in nanotons per 65536 bits or cells a second, since `utime_since`
+/// ```tlb
+/// storage_prices#cc utime_since:uint32 bit_price_ps:uint64 cell_price_ps:uint64
+///   mc_bit_price_ps:uint64 mc_cell_price_ps:uint64 = StoragePrices;
+/// _ (Hashmap 32 StoragePrices) = ConfigParam 18;
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StoragePrices {
+    pub utime_since: u32,
+    pub bit_price_ps: u64,
+    pub cell_price_ps: u64,
+    pub mc_bit_price_ps: u64,
+    pub mc_cell_price_ps: u64,
+}
+
+impl<'de> CellDeserialize<'de> for StoragePrices {
+    fn parse(parser: &mut CellParser<'de>) -> Result<Self, CellParserError<'de>> {
+        let tag: u8 = parser.unpack()?;
+        if tag != 0xcc {
+            return Err(StringError::custom(format!(
+                "unsupported storage prices tag: {tag:#04x}"
+            )));
+        }
+
+        Ok(Self {
+            utime_since: parser.unpack()?,
+            bit_price_ps: parser.unpack()?,
+            cell_price_ps: parser.unpack()?,
+            mc_bit_price_ps: parser.unpack()?,
+            mc_cell_price_ps: parser.unpack()?,
+        })
+    }
+}
+
+/// Forward prices in nanotons, `bit_price` and `cell_price` per 65536 bits or cells,
+/// the fractions are of 65536
+/// ```tlb
+/// msg_forward_prices#ea lump_price:uint64 bit_price:uint64 cell_price:uint64
+///   ihr_price_factor:uint32 first_frac:uint16 next_frac:uint16 = MsgForwardPrices;
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MsgForwardPrices {
+    pub lump_price: u64,
+    pub bit_price: u64,
+    pub cell_price: u64,
+    pub ihr_price_factor: u32,
+    /// part of the forward fee the sender pays in its action phase
+    pub first_frac: u16,
+    pub next_frac: u16,
+}
+
+impl<'de> CellDeserialize<'de> for MsgForwardPrices {
+    fn parse(parser: &mut CellParser<'de>) -> Result<Self, CellParserError<'de>> {
+        let tag: u8 = parser.unpack()?;
+        if tag != 0xea {
+            return Err(StringError::custom(format!(
+                "unsupported forward prices tag: {tag:#04x}"
+            )));
+        }
+
+        Ok(Self {
+            lump_price: parser.unpack()?,
+            bit_price: parser.unpack()?,
+            cell_price: parser.unpack()?,
+            ihr_price_factor: parser.unpack()?,
+            first_frac: parser.unpack()?,
+            next_frac: parser.unpack()?,
+        })
+    }
+}
+
+/// Config params fees of a workchain are computed from, 18 and 20 or 21 and 24 or 25
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeeConfig {
+    pub workchain: i32,
+    /// the latest storage prices, they apply to the whole duration charged
+    pub storage: StoragePrices,
+    pub gas: GasPrices,
+    pub forward: MsgForwardPrices,
+}
+
+impl FeeConfig {
+    /// Params of `workchain` as of the latest block
+    pub async fn fetch(client: &TonClient, workchain: i32) -> Result<Self, TonContractError> {
+        let storage = latest_storage_prices(&client.get_config_param(18).await?.config.bytes)?;
+        let forward = client
+            .get_config_param(match workchain {
+                -1 => 24,
+                _ => 25,
+            })
+            .await?;
+
+        Ok(Self {
+            workchain,
+            storage,
+            gas: gas_prices(client, workchain).await?,
+            forward: parse_base64_boc(&forward.config.bytes)?.parse_fully()?,
+        })
+    }
+}
+
+/// Storage prices of config param 18 with the latest `utime_since`
+pub fn latest_storage_prices(param: &str) -> Result<StoragePrices, TonContractError> {
+    let root = parse_base64_boc(param)?;
+
+    root.parser()
+        .parse_as_with::<Vec<(BitVec<u8, Msb0>, StoragePrices)>, Hashmap<NoArgs<_>, ()>>((32, ()))?
+        .into_iter()
+        .map(|(_, prices)| prices)
+        .max_by_key(|prices| prices.utime_since)
+        .ok_or_else(|| anyhow::anyhow!("config param 18 is empty").into())
+}
+
+/// What fees are computed for, bits and cells of a message don't count its root cell
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FeeInput {
+    pub msg_bits: u64,
+    pub msg_cells: u64,
+    /// storage used by the account
+    pub storage_bits: u64,
+    pub storage_cells: u64,
+    /// seconds of storage charged
+    pub duration: u64,
+    pub gas_used: u64,
+}
+
+/// Fees in nanotons
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Fees {
+    /// total forward fee of the message
+    pub fwd_fee: u64,
+    /// part of `fwd_fee` the sender pays in its action phase, the rest is left in the message
+    pub action_fee: u64,
+    pub ihr_fee: u64,
+    pub storage_fee: u64,
+    pub gas_fee: u64,
+}
+
+/// `ceil(value / 65536)`
+fn shift_ceil(value: u128) -> u128 {
+    (value + (1 << PRICE_SHIFT) - 1) >> PRICE_SHIFT
+}
+
+fn saturate(value: u128) -> u64 {
+    u64::try_from(value).unwrap_or(u64::MAX)
+}
+
+/// Fees as validators charge them, without running the contract:
+/// `fwd_fee = lump_price + ceil((bit_price * bits + cell_price * cells) / 2^16)`,
+/// `storage_fee = ceil((bit_price_ps * bits + cell_price_ps * cells) * duration / 2^16)` and
+/// `gas_fee = flat_gas_price + ceil(gas_price * (gas_used - flat_gas_limit) / 2^16)`
+pub fn compute_fees(config: &FeeConfig, input: &FeeInput) -> Fees {
+    let forward = &config.forward;
+    let fwd_fee = forward.lump_price as u128
+        + shift_ceil(
+            forward.bit_price as u128 * input.msg_bits as u128
+                + forward.cell_price as u128 * input.msg_cells as u128,
+        );
+
+    let (bit_price, cell_price) = match config.workchain {
+        -1 => (
+            config.storage.mc_bit_price_ps,
+            config.storage.mc_cell_price_ps,
+        ),
+        _ => (config.storage.bit_price_ps, config.storage.cell_price_ps),
+    };
+    let storage_fee = shift_ceil(
+        (bit_price as u128 * input.storage_bits as u128
+            + cell_price as u128 * input.storage_cells as u128)
+            * input.duration as u128,
+    );
+
+    Fees {
+        fwd_fee: saturate(fwd_fee),
+        action_fee: saturate((fwd_fee * forward.first_frac as u128) >> PRICE_SHIFT),
+        ihr_fee: saturate((fwd_fee * forward.ihr_price_factor as u128) >> PRICE_SHIFT),
+        storage_fee: saturate(storage_fee),
+        gas_fee: gas_fee(&config.gas, input.gas_used),
+    }
+}
+
+/// Fee of `gas_used` gas units, the first `flat_gas_limit` units cost `flat_gas_price` altogether
+pub fn gas_fee(prices: &GasPrices, gas_used: u64) -> u64 {
+    let metered = gas_used.saturating_sub(prices.flat_gas_limit) as u128;
+
+    saturate(prices.flat_gas_price as u128 + shift_ceil(prices.gas_price as u128 * metered))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use toner::tlb::bits::ser::pack_with;
+    use toner::tlb::Cell;
+    use toner::ton::boc::{BagOfCellsArgs, BoC};
+
+    /// Basechain prices of mainnet since global version 4
+    fn basechain() -> FeeConfig {
+        FeeConfig {
+            workchain: 0,
+            storage: StoragePrices {
+                utime_since: 0,
+                bit_price_ps: 1,
+                cell_price_ps: 500,
+                mc_bit_price_ps: 1000,
+                mc_cell_price_ps: 500000,
+            },
+            gas: GasPrices {
+                flat_gas_limit: 100,
+                flat_gas_price: 40000,
+                gas_price: 26214400,
+                gas_limit: 1000000,
+                special_gas_limit: 1000000,
+                gas_credit: 10000,
+                block_gas_limit: 10000000,
+                freeze_due_limit: 100000000,
+                delete_due_limit: 1000000000,
+            },
+            forward: MsgForwardPrices {
+                lump_price: 400000,
+                bit_price: 26214400,
+                cell_price: 2621440000,
+                ihr_price_factor: 98304,
+                first_frac: 21845,
+                next_frac: 21845,
+            },
+        }
+    }
+
+    fn within(actual: u64, expected: u64, tolerance: u64) -> bool {
+        actual.abs_diff(expected) <= tolerance
+    }
+
+    #[test]
+    fn transfer_without_body() {
+        let fees = compute_fees(&basechain(), &FeeInput::default());
+
+        // as tonlib and explorers report a transfer without body: the message carries
+        // fwd_fee 266669 and the sender pays 133331 of the lump price in its action phase
+        assert_eq!(fees.fwd_fee, 400000);
+        assert!(within(fees.action_fee, 133331, 1));
+        assert!(within(fees.fwd_fee - fees.action_fee, 266669, 1));
+        assert_eq!(fees.storage_fee, 0);
+    }
+
+    #[test]
+    fn message_with_body_cells() {
+        let fees = compute_fees(
+            &basechain(),
+            &FeeInput {
+                msg_bits: 1023,
+                msg_cells: 1,
+                ..Default::default()
+            },
+        );
+
+        // 400 nanotons a bit and 40000 a cell
+        assert_eq!(fees.fwd_fee, 400000 + 1023 * 400 + 40000);
+    }
+
+    #[test]
+    fn gas_is_flat_up_to_flat_limit() {
+        let gas = basechain().gas;
+
+        assert_eq!(gas_fee(&gas, 0), 40000);
+        assert_eq!(gas_fee(&gas, 100), 40000);
+        assert_eq!(gas_fee(&gas, 1000), 40000 + 900 * 400);
+        assert_eq!(gas_fee(&gas, 2994), 1197600);
+    }
+
+    #[test]
+    fn storage_fee_of_a_year() {
+        let year = 365 * 24 * 3600;
+        let input = FeeInput {
+            storage_bits: 1000,
+            storage_cells: 3,
+            duration: year,
+            ..Default::default()
+        };
+
+        let base = compute_fees(&basechain(), &input);
+        let master = compute_fees(
+            &FeeConfig {
+                workchain: -1,
+                ..basechain()
+            },
+            &input,
+        );
+
+        assert_eq!(base.storage_fee, ((1000 + 3 * 500) * year).div_ceil(65536));
+        assert_eq!(
+            master.storage_fee,
+            ((1000 * 1000 + 3 * 500000) * year).div_ceil(65536)
+        );
+    }
+
+    fn base64(cell: Cell) -> String {
+        let packed = pack_with(
+            BoC::from_root(cell),
+            BagOfCellsArgs {
+                has_idx: false,
+                has_crc32c: false,
+            },
+        )
+        .unwrap();
+
+        STANDARD.encode(packed.as_raw_slice())
+    }
+
+    #[test]
+    fn parse_forward_prices() {
+        let mut cell = Cell::builder();
+        cell.pack(0xea_u8)
+            .unwrap()
+            .pack(400000_u64)
+            .unwrap()
+            .pack(26214400_u64)
+            .unwrap()
+            .pack(2621440000_u64)
+            .unwrap()
+            .pack(98304_u32)
+            .unwrap()
+            .pack(21845_u16)
+            .unwrap()
+            .pack(21845_u16)
+            .unwrap();
+
+        let prices: MsgForwardPrices = parse_base64_boc(&base64(cell.into_cell()))
+            .unwrap()
+            .parse_fully()
+            .unwrap();
+
+        assert_eq!(prices, basechain().forward);
+    }
+}
@@ -0,0 +1,230 @@
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use futures::future::join_all;
+use toner::tlb::bits::de::unpack_bytes;
+use toner::ton::{boc::BoC, MsgAddress};
+use tonlibjson_client::ton::TonClient;
+
+use crate::TonContractError;
+
+/// Exit code of a get-method the contract doesn't have
+pub(crate) const METHOD_NOT_FOUND: i32 = 11;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ContractInterface {
+    Wallet,
+    WalletV3R2,
+    WalletV4R2,
+    WalletV5R1,
+    JettonMaster,
+    JettonWallet,
+    NftCollection,
+    NftItem,
+    DnsResolver,
+    Multisig,
+}
+
+impl ContractInterface {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Wallet => "wallet",
+            Self::WalletV3R2 => "wallet_v3r2",
+            Self::WalletV4R2 => "wallet_v4r2",
+            Self::WalletV5R1 => "wallet_v5r1",
+            Self::JettonMaster => "jetton_master",
+            Self::JettonWallet => "jetton_wallet",
+            Self::NftCollection => "nft_collection",
+            Self::NftItem => "nft_item",
+            Self::DnsResolver => "dns_resolver",
+            Self::Multisig => "multisig",
+        }
+    }
+}
+
+/// Interfaces of well-known contracts by hex hash of their code cell
+pub const KNOWN_CODE_HASHES: &[(&str, &[ContractInterface])] = &[
+    (
+        "84dafa449f98a6987789ba232358072bc0f76dc4524002a5d0918b9a75d2d599",
+        &[ContractInterface::Wallet, ContractInterface::WalletV3R2],
+    ),
+    (
+        "feb5ff6820e2ff0d9483e7e0d62c817d846789fb4ae580c878866d959dabd5c0",
+        &[ContractInterface::Wallet, ContractInterface::WalletV4R2],
+    ),
+    (
+        "20834b7b72b112147e1b2fb457b84e74d1a30f04f737d4f62a668e9552d2b72f",
+        &[ContractInterface::Wallet, ContractInterface::WalletV5R1],
+    ),
+];
+
+/// Get-methods probed when the code is unknown, a contract has the interface if it has the method
+pub const INTERFACE_PROBES: &[(&str, ContractInterface)] = &[
+    ("seqno", ContractInterface::Wallet),
+    ("get_subwallet_id", ContractInterface::Wallet),
+    ("get_jetton_data", ContractInterface::JettonMaster),
+    ("get_wallet_data", ContractInterface::JettonWallet),
+    ("get_collection_data", ContractInterface::NftCollection),
+    ("get_nft_data", ContractInterface::NftItem),
+    ("dnsresolve", ContractInterface::DnsResolver),
+    ("get_n_k", ContractInterface::Multisig),
+    ("get_multisig_data", ContractInterface::Multisig),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContractInterfaces {
+    /// none for accounts without code
+    pub code_hash: Option<[u8; 32]>,
+    /// whether the interfaces come from [`KNOWN_CODE_HASHES`] rather than from probes
+    pub known_code: bool,
+    pub interfaces: Vec<ContractInterface>,
+}
+
+pub fn known_interfaces(code_hash: &[u8; 32]) -> Option<&'static [ContractInterface]> {
+    let code_hash = hex::encode(code_hash);
+
+    KNOWN_CODE_HASHES
+        .iter()
+        .find(|(hash, _)| *hash == code_hash)
+        .map(|(_, interfaces)| *interfaces)
+}
+
+/// Interfaces of the probes which found their method, `exit_code` is none if the probe failed
+fn probed_interfaces(
+    probes: impl IntoIterator<Item = (ContractInterface, Option<i32>)>,
+) -> Vec<ContractInterface> {
+    let mut interfaces = Vec::new();
+    for (interface, exit_code) in probes {
+        // any other exit code means the method is there, e.g. `dnsresolve` fails without arguments
+        let found = exit_code.is_some_and(|code| code != METHOD_NOT_FOUND);
+        if found && !interfaces.contains(&interface) {
+            interfaces.push(interface);
+        }
+    }
+
+    interfaces
+}
+
+#[async_trait]
+pub trait InterfaceDetector {
+    /// Interfaces of the contract at `address` by its code hash, or by probing get-methods
+    async fn contract_interfaces(
+        &self,
+        address: &MsgAddress,
+    ) -> Result<ContractInterfaces, TonContractError>;
+}
+
+#[async_trait]
+impl InterfaceDetector for TonClient {
+    async fn contract_interfaces(
+        &self,
+        address: &MsgAddress,
+    ) -> Result<ContractInterfaces, TonContractError> {
+        let address = address.to_base64_std();
+        let state = self.raw_get_account_state(&address).await?;
+        if state.code.is_empty() {
+            return Ok(ContractInterfaces {
+                code_hash: None,
+                known_code: false,
+                interfaces: vec![],
+            });
+        }
+
+        let code: BoC = unpack_bytes(STANDARD.decode(&state.code)?)?;
+        let code_hash = code
+            .single_root()
+            .ok_or_else(|| anyhow::anyhow!("code of {address} is empty"))?
+            .hash();
+
+        if let Some(interfaces) = known_interfaces(&code_hash) {
+            return Ok(ContractInterfaces {
+                code_hash: Some(code_hash),
+                known_code: true,
+                interfaces: interfaces.to_vec(),
+            });
+        }
+
+        let probes = join_all(INTERFACE_PROBES.iter().map(|(method, interface)| {
+            let address = address.clone();
+
+            async move {
+                let exit_code = self
+                    .run_get_method(address, method.to_string(), vec![])
+                    .await
+                    .ok()
+                    .map(|result| result.exit_code);
+
+                (*interface, exit_code)
+            }
+        }))
+        .await;
+
+        Ok(ContractInterfaces {
+            code_hash: Some(code_hash),
+            known_code: false,
+            interfaces: probed_interfaces(probes),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use toner::contracts::wallet::{v4r2::V4R2, v5r1::V5R1, WalletVersion};
+
+    // single cell code of wallet v3r2
+    const WALLET_V3R2_CODE: &str = "te6cckEBAQEAcQAA3v8AIN0gggFMl7ohggEznLqxn3Gw7UTQ0x/THzHXC//jBOCk8mCDCNcYINMf0x/TH/gjE7vyY+1E0NMf0x/T/9FRMrryoVFEuvKiBPkBVBBV+RDyo/gAkyDXSpbTB9QC+wDo0QGkyMsfyx/L/8ntVBC9ba0=";
+
+    #[test]
+    fn wallet_v3r2_code_is_known() {
+        let code: BoC = unpack_bytes(STANDARD.decode(WALLET_V3R2_CODE).unwrap()).unwrap();
+        let hash = code.single_root().unwrap().hash();
+
+        assert_eq!(
+            known_interfaces(&hash).unwrap(),
+            [ContractInterface::Wallet, ContractInterface::WalletV3R2]
+        );
+    }
+
+    #[test]
+    fn wallet_v4r2_and_v5r1_code_is_known() {
+        assert_eq!(
+            known_interfaces(&V4R2::code().hash()).unwrap(),
+            [ContractInterface::Wallet, ContractInterface::WalletV4R2]
+        );
+        assert_eq!(
+            known_interfaces(&V5R1::code().hash()).unwrap(),
+            [ContractInterface::Wallet, ContractInterface::WalletV5R1]
+        );
+        assert!(known_interfaces(&[0; 32]).is_none());
+    }
+
+    #[test]
+    fn jetton_minter_is_probed() {
+        let probes = INTERFACE_PROBES.iter().map(|(method, interface)| {
+            let exit_code = match *method {
+                "get_jetton_data" => Some(0),
+                // lite server failed to run the method
+                "get_wallet_data" => None,
+                _ => Some(METHOD_NOT_FOUND),
+            };
+
+            (*interface, exit_code)
+        });
+
+        assert_eq!(probed_interfaces(probes), [ContractInterface::JettonMaster]);
+    }
+
+    #[test]
+    fn probed_interfaces_are_unique() {
+        let probes = [
+            (ContractInterface::Wallet, Some(0)),
+            (ContractInterface::Wallet, Some(0)),
+            (ContractInterface::DnsResolver, Some(2)),
+        ];
+
+        assert_eq!(
+            probed_interfaces(probes),
+            [ContractInterface::Wallet, ContractInterface::DnsResolver]
+        );
+    }
+}
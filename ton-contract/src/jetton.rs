@@ -2,6 +2,7 @@ use crate::{TonContract, TonContractError, TvmBoxedStackEntryExt};
 use async_trait::async_trait;
 use num_bigint::BigUint;
 use toner::{tlb::r#as::Data, ton::MsgAddress};
+use tonlibjson_client::{block::TvmBoxedStackEntry, ton::TonClient};
 
 pub struct JettonWalletData {
     pub balance: BigUint,
@@ -30,3 +31,59 @@ impl JettonWalletContract for TonContract {
         })
     }
 }
+
+#[async_trait]
+pub trait JettonMasterContract {
+    /// Calls `get_wallet_address` get-method
+    async fn get_wallet_address(&self, owner: &MsgAddress) -> Result<MsgAddress, TonContractError>;
+}
+
+#[async_trait]
+impl JettonMasterContract for TonContract {
+    async fn get_wallet_address(&self, owner: &MsgAddress) -> Result<MsgAddress, TonContractError> {
+        let [address] = self
+            .run_get_method(
+                "get_wallet_address",
+                [TvmBoxedStackEntry::from_address(owner)?].into(),
+            )
+            .await?
+            .try_into()?;
+
+        address.parse_cell_fully_as::<_, Data>()
+    }
+}
+
+pub struct JettonWalletAddress {
+    pub address: MsgAddress,
+    /// whether the wallet has code, it's deployed by the first transfer to the owner
+    pub deployed: bool,
+}
+
+#[async_trait]
+pub trait JettonWallets {
+    /// Jetton wallet of `owner` for the jetton `master`
+    async fn jetton_wallet_address(
+        &self,
+        master: &MsgAddress,
+        owner: &MsgAddress,
+    ) -> Result<JettonWalletAddress, TonContractError>;
+}
+
+#[async_trait]
+impl JettonWallets for TonClient {
+    async fn jetton_wallet_address(
+        &self,
+        master: &MsgAddress,
+        owner: &MsgAddress,
+    ) -> Result<JettonWalletAddress, TonContractError> {
+        let address = TonContract::new(self.clone(), *master)
+            .get_wallet_address(owner)
+            .await?;
+        let state = self.raw_get_account_state(&address.to_base64_std()).await?;
+
+        Ok(JettonWalletAddress {
+            address,
+            deployed: !state.code.is_empty(),
+        })
+    }
+}
@@ -4,5 +4,14 @@ mod error;
 
 pub use self::{adapters::*, contract::*, error::*};
 
+pub mod abi;
+pub mod code;
+pub mod config;
+pub mod decode;
+pub mod dns;
+pub mod elector;
+pub mod fees;
+pub mod interfaces;
 pub mod jetton;
+pub mod multisig;
 pub mod wallet;
@@ -0,0 +1,526 @@
+use async_trait::async_trait;
+use num_bigint::{BigInt, BigUint};
+use toner::tlb::bits::bitvec::{order::Msb0, vec::BitVec};
+use toner::tlb::bits::de::BitReaderExt;
+use toner::tlb::r#as::{Data, NoArgs, Ref};
+use toner::tlb::Cell;
+use toner::ton::hashmap::Hashmap;
+use toner::ton::message::{CommonMsgInfo, Message};
+use toner::ton::MsgAddress;
+use tonlibjson_client::{block::TvmBoxedStackEntry, ton::TonClient};
+
+use crate::{TonContract, TonContractError, TvmBoxedStackEntryExt};
+
+/// `send_message#f1381e5b mode:uint8 message:^(MessageRelaxed Any) = Action`
+pub const MULTISIG_SEND_MESSAGE_ACTION: u32 = 0xf1381e5b;
+/// `update_multisig_params#1d0cfbd3 threshold:uint8 signers:^(Hashmap 8 MsgAddressInt)
+/// proposers:(HashmapE 8 MsgAddressInt) = Action`
+pub const MULTISIG_UPDATE_PARAMS_ACTION: u32 = 0x1d0cfbd3;
+
+/// Key length of the dictionaries of signers, proposers and actions
+const INDEX_BITS: u32 = 8;
+
+/// Stack of a get-method, parsed if it follows the scheme of multisig v2
+#[derive(Debug, Clone)]
+pub enum Decoded<T> {
+    Parsed(T),
+    /// the contract is another multisig version, the stack is kept as is
+    Raw(Vec<TvmBoxedStackEntry>),
+}
+
+/// Multisig v2 as returned by `get_multisig_data`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultisigData {
+    /// none if orders may have any seqno
+    pub next_order_seqno: Option<BigUint>,
+    pub threshold: u32,
+    /// by index
+    pub signers: Vec<MsgAddress>,
+    pub proposers: Vec<MsgAddress>,
+}
+
+/// Order of a multisig v2 as returned by `get_order_data`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultisigOrder {
+    pub multisig: MsgAddress,
+    pub order_seqno: BigUint,
+    /// none until the order is initialized by the multisig
+    pub threshold: Option<u32>,
+    pub executed: bool,
+    pub signers: Vec<MsgAddress>,
+    /// bit `i` is set once the signer `i` approved
+    pub approvals_mask: BigUint,
+    pub approvals_num: u32,
+    pub expiration_date: u64,
+    /// by index, empty until the order is initialized
+    pub actions: Vec<MultisigAction>,
+}
+
+impl MultisigOrder {
+    /// Indexes of the signers which approved the order
+    pub fn approved_by(&self) -> Vec<u32> {
+        (0..self.signers.len() as u64)
+            .filter(|i| self.approvals_mask.bit(*i))
+            .map(|i| i as u32)
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MultisigAction {
+    SendMessage {
+        mode: u8,
+        destination: MsgAddress,
+        value: BigUint,
+        bounce: bool,
+        /// decode it with [`crate::decode`]
+        body: Cell,
+    },
+    UpdateParams {
+        threshold: u32,
+        signers: Vec<MsgAddress>,
+        proposers: Vec<MsgAddress>,
+    },
+    /// an action of another op, or a message which isn't an internal one
+    Unknown(Cell),
+}
+
+impl TryFrom<Vec<TvmBoxedStackEntry>> for MultisigData {
+    type Error = TonContractError;
+
+    /// `(next_order_seqno, threshold, signers, proposers)`
+    fn try_from(stack: Vec<TvmBoxedStackEntry>) -> Result<Self, Self::Error> {
+        let [next_order_seqno, threshold, signers, proposers] = stack.try_into()?;
+        let next_order_seqno = next_order_seqno.to_number::<BigInt>()?;
+
+        Ok(Self {
+            // -1 if arbitrary seqnos are allowed
+            next_order_seqno: next_order_seqno.to_biguint(),
+            threshold: threshold.to_number()?,
+            signers: addresses(&signers)?,
+            proposers: addresses(&proposers)?,
+        })
+    }
+}
+
+impl TryFrom<Vec<TvmBoxedStackEntry>> for MultisigOrder {
+    type Error = TonContractError;
+
+    /// `(multisig, order_seqno, threshold, executed, signers, approvals_mask,
+    /// approvals_num, expiration_date, order)`
+    fn try_from(stack: Vec<TvmBoxedStackEntry>) -> Result<Self, Self::Error> {
+        let [multisig, order_seqno, threshold, executed, signers, approvals_mask, approvals_num, expiration_date, order] =
+            stack.try_into()?;
+
+        let actions = match null(&order) {
+            true => Vec::new(),
+            false => action_dict(&*order.to_cell()?)?,
+        };
+
+        Ok(Self {
+            multisig: multisig.parse_cell_fully_as::<_, Data>()?,
+            order_seqno: order_seqno.to_number()?,
+            threshold: match null(&threshold) {
+                true => None,
+                false => Some(threshold.to_number()?),
+            },
+            executed: !null(&executed) && executed.to_number::<i32>()? != 0,
+            signers: addresses(&signers)?,
+            approvals_mask: approvals_mask.to_number()?,
+            approvals_num: approvals_num.to_number()?,
+            expiration_date: expiration_date.to_number()?,
+            actions,
+        })
+    }
+}
+
+fn null(entry: &TvmBoxedStackEntry) -> bool {
+    matches!(entry, TvmBoxedStackEntry::TvmStackEntryUnsupported(_))
+}
+
+/// Addresses of a `Hashmap 8 MsgAddressInt` by index
+fn address_dict(root: &Cell) -> Result<Vec<MsgAddress>, TonContractError> {
+    let entries =
+        root.parser()
+            .parse_as_with::<Vec<(BitVec<u8, Msb0>, MsgAddress)>, Hashmap<NoArgs<_, Data>, ()>>(
+                (INDEX_BITS, ()),
+            )?;
+
+    Ok(entries.into_iter().map(|(_, address)| address).collect())
+}
+
+/// Actions of a `Hashmap 8 ^Action` by index
+fn action_dict(root: &Cell) -> Result<Vec<MultisigAction>, TonContractError> {
+    let entries = root
+        .parser()
+        .parse_as_with::<Vec<(BitVec<u8, Msb0>, Cell)>, Hashmap<NoArgs<_, Ref>, ()>>((
+            INDEX_BITS,
+            (),
+        ))?;
+
+    entries
+        .iter()
+        .map(|(_, action)| parse_action(action))
+        .collect()
+}
+
+/// Null is an empty `HashmapE`
+fn addresses(entry: &TvmBoxedStackEntry) -> Result<Vec<MsgAddress>, TonContractError> {
+    if null(entry) {
+        return Ok(Vec::new());
+    }
+
+    address_dict(&*entry.to_cell()?)
+}
+
+fn parse_action(cell: &Cell) -> Result<MultisigAction, TonContractError> {
+    let mut parser = cell.parser();
+    let op: u32 = parser.unpack()?;
+
+    match op {
+        MULTISIG_SEND_MESSAGE_ACTION => {
+            let mode: u8 = parser.unpack()?;
+            let message: Message<Cell> = parser.parse_as::<_, Ref>()?;
+            let CommonMsgInfo::Internal(info) = message.info else {
+                return Ok(MultisigAction::Unknown(cell.clone()));
+            };
+
+            Ok(MultisigAction::SendMessage {
+                mode,
+                destination: info.dst,
+                value: info.value.grams,
+                bounce: info.bounce,
+                body: message.body,
+            })
+        }
+        MULTISIG_UPDATE_PARAMS_ACTION => {
+            let threshold: u8 = parser.unpack()?;
+            let signers: Cell = parser.parse_as::<_, Ref>()?;
+            let proposers: Option<Cell> = parser.parse_as::<_, Option<Ref>>()?;
+
+            Ok(MultisigAction::UpdateParams {
+                threshold: threshold.into(),
+                signers: address_dict(&signers)?,
+                proposers: proposers
+                    .map(|proposers| address_dict(&proposers))
+                    .transpose()?
+                    .unwrap_or_default(),
+            })
+        }
+        _ => Ok(MultisigAction::Unknown(cell.clone())),
+    }
+}
+
+fn decoded<T>(stack: Vec<TvmBoxedStackEntry>) -> Decoded<T>
+where
+    T: TryFrom<Vec<TvmBoxedStackEntry>, Error = TonContractError>,
+{
+    // the bit reader panics on cells shorter than the scheme of another version expects
+    match std::panic::catch_unwind(|| T::try_from(stack.clone())) {
+        Ok(Ok(parsed)) => Decoded::Parsed(parsed),
+        _ => Decoded::Raw(stack),
+    }
+}
+
+#[async_trait]
+pub trait MultisigContract {
+    /// Calls `get_multisig_data` get-method
+    async fn get_multisig_data(&self) -> Result<Decoded<MultisigData>, TonContractError>;
+
+    /// Calls `get_order_data` get-method of an order contract
+    async fn get_order_data(&self) -> Result<Decoded<MultisigOrder>, TonContractError>;
+}
+
+#[async_trait]
+impl MultisigContract for TonContract {
+    async fn get_multisig_data(&self) -> Result<Decoded<MultisigData>, TonContractError> {
+        let stack = self.run_get_method("get_multisig_data", [].into()).await?;
+
+        Ok(decoded(stack))
+    }
+
+    async fn get_order_data(&self) -> Result<Decoded<MultisigOrder>, TonContractError> {
+        let stack = self.run_get_method("get_order_data", [].into()).await?;
+
+        Ok(decoded(stack))
+    }
+}
+
+#[async_trait]
+pub trait Multisigs {
+    /// Signers, proposers and threshold of the multisig at `address`
+    async fn multisig(
+        &self,
+        address: &MsgAddress,
+    ) -> Result<Decoded<MultisigData>, TonContractError>;
+
+    /// Approvals and actions of the order contract at `address`
+    async fn multisig_order(
+        &self,
+        address: &MsgAddress,
+    ) -> Result<Decoded<MultisigOrder>, TonContractError>;
+}
+
+#[async_trait]
+impl Multisigs for TonClient {
+    async fn multisig(
+        &self,
+        address: &MsgAddress,
+    ) -> Result<Decoded<MultisigData>, TonContractError> {
+        TonContract::new(self.clone(), *address)
+            .get_multisig_data()
+            .await
+    }
+
+    async fn multisig_order(
+        &self,
+        address: &MsgAddress,
+    ) -> Result<Decoded<MultisigOrder>, TonContractError> {
+        TonContract::new(self.clone(), *address)
+            .get_order_data()
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::{decode_jetton_message, JettonMessage};
+    use serde_json::json;
+    use toner::contracts::jetton::{ForwardPayload, JettonTransfer};
+    use toner::tlb::bits::r#as::NBits;
+    use toner::tlb::bits::ser::BitWriterExt;
+    use toner::tlb::ser::{CellBuilder, CellSerializeExt};
+    use toner::ton::message::InternalMsgInfo;
+
+    fn signer(i: u8) -> MsgAddress {
+        MsgAddress {
+            workchain_id: 0,
+            address: [i; 32],
+        }
+    }
+
+    fn multisig() -> MsgAddress {
+        "EQCjk1hh952vWaE9bRguFkAhDAL5jj3xj9p0uPWrFBq_GEMS"
+            .parse()
+            .unwrap()
+    }
+
+    /// `Hashmap 8 X` of the keys 0 and 1
+    fn dict(values: [CellBuilder; 2]) -> Cell {
+        let mut root = Cell::builder();
+        // hml_same$11 v:0 n:7, the keys share 7 leading zeros
+        root.pack_as::<_, NBits<2>>(0b11_u8)
+            .unwrap()
+            .pack(false)
+            .unwrap()
+            .pack_as::<_, NBits<4>>(7_u8)
+            .unwrap();
+        for value in values {
+            let mut leaf = Cell::builder();
+            // hml_short$0 with an empty label
+            leaf.pack(false).unwrap().pack(false).unwrap();
+            leaf.store(value.into_cell()).unwrap();
+            root.store_as::<_, Ref>(leaf.into_cell()).unwrap();
+        }
+
+        root.into_cell()
+    }
+
+    fn address(address: MsgAddress) -> CellBuilder {
+        let mut builder = Cell::builder();
+        builder.pack(address).unwrap();
+
+        builder
+    }
+
+    fn reference(value: CellBuilder) -> CellBuilder {
+        let mut builder = Cell::builder();
+        builder.store_as::<_, Ref>(value.into_cell()).unwrap();
+
+        builder
+    }
+
+    fn cell(cell: Cell) -> TvmBoxedStackEntry {
+        TvmBoxedStackEntry::from_cell(cell).unwrap()
+    }
+
+    fn null() -> TvmBoxedStackEntry {
+        serde_json::from_value(json!({"@type": "tvm.stackEntryUnsupported"})).unwrap()
+    }
+
+    fn jetton_transfer() -> Cell {
+        JettonTransfer::<Cell, Cell> {
+            query_id: 1,
+            amount: BigUint::from(100_u8),
+            dst: signer(2),
+            response_dst: multisig(),
+            custom_payload: None,
+            forward_ton_amount: BigUint::from(0_u8),
+            forward_payload: ForwardPayload::Data(Cell::default()),
+        }
+        .to_cell()
+        .unwrap()
+    }
+
+    fn send_message_action() -> CellBuilder {
+        let message = Message::<Cell> {
+            info: CommonMsgInfo::Internal(InternalMsgInfo::transfer(
+                signer(9),
+                BigUint::from(50_000_000_u32),
+                true,
+            )),
+            init: None,
+            body: jetton_transfer(),
+        }
+        .to_cell()
+        .unwrap();
+        let mut action = Cell::builder();
+        action
+            .pack(MULTISIG_SEND_MESSAGE_ACTION)
+            .unwrap()
+            .pack(3_u8)
+            .unwrap()
+            .store_as::<_, Ref>(message)
+            .unwrap();
+
+        action
+    }
+
+    fn update_params_action() -> CellBuilder {
+        let mut action = Cell::builder();
+        action
+            .pack(MULTISIG_UPDATE_PARAMS_ACTION)
+            .unwrap()
+            .pack(1_u8)
+            .unwrap()
+            .store_as::<_, Ref>(dict([address(signer(0)), address(signer(5))]))
+            .unwrap()
+            // no proposers
+            .pack(false)
+            .unwrap();
+
+        action
+    }
+
+    #[test]
+    fn parse_multisig_data() {
+        let stack = vec![
+            TvmBoxedStackEntry::from_number(4),
+            TvmBoxedStackEntry::from_number(2),
+            cell(dict([address(signer(0)), address(signer(1))])),
+            null(),
+        ];
+
+        assert_eq!(
+            MultisigData::try_from(stack).unwrap(),
+            MultisigData {
+                next_order_seqno: Some(BigUint::from(4_u8)),
+                threshold: 2,
+                signers: vec![signer(0), signer(1)],
+                proposers: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn parse_multisig_data_with_arbitrary_seqno() {
+        let stack = vec![
+            TvmBoxedStackEntry::from_number(-1),
+            TvmBoxedStackEntry::from_number(1),
+            cell(dict([address(signer(0)), address(signer(1))])),
+            cell(dict([address(signer(2)), address(signer(3))])),
+        ];
+
+        let data = MultisigData::try_from(stack).unwrap();
+
+        assert_eq!(data.next_order_seqno, None);
+        assert_eq!(data.proposers, [signer(2), signer(3)]);
+    }
+
+    #[test]
+    fn parse_order_with_actions() {
+        let stack = vec![
+            TvmBoxedStackEntry::from_address(&multisig()).unwrap(),
+            TvmBoxedStackEntry::from_number(4),
+            TvmBoxedStackEntry::from_number(2),
+            TvmBoxedStackEntry::from_number(0),
+            cell(dict([address(signer(0)), address(signer(1))])),
+            TvmBoxedStackEntry::from_number(0b10),
+            TvmBoxedStackEntry::from_number(1),
+            TvmBoxedStackEntry::from_number(1700000000),
+            cell(dict([
+                reference(send_message_action()),
+                reference(update_params_action()),
+            ])),
+        ];
+
+        let order = MultisigOrder::try_from(stack).unwrap();
+
+        assert_eq!(order.multisig, multisig());
+        assert_eq!(order.threshold, Some(2));
+        assert!(!order.executed);
+        assert_eq!(order.approved_by(), [1]);
+        assert_eq!(order.expiration_date, 1700000000);
+        let MultisigAction::SendMessage {
+            mode,
+            destination,
+            value,
+            bounce,
+            body,
+        } = &order.actions[0]
+        else {
+            panic!("expected a message, got {:?}", order.actions[0]);
+        };
+        assert_eq!(
+            (*mode, *destination, value.clone(), *bounce),
+            (3, signer(9), BigUint::from(50_000_000_u32), true)
+        );
+        assert!(matches!(
+            decode_jetton_message(body),
+            Some(JettonMessage::Transfer { query_id: 1, .. })
+        ));
+        assert_eq!(
+            order.actions[1],
+            MultisigAction::UpdateParams {
+                threshold: 1,
+                signers: vec![signer(0), signer(5)],
+                proposers: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn uninitialized_order_has_no_actions() {
+        let stack = vec![
+            TvmBoxedStackEntry::from_address(&multisig()).unwrap(),
+            TvmBoxedStackEntry::from_number(4),
+            null(),
+            null(),
+            null(),
+            TvmBoxedStackEntry::from_number(0),
+            TvmBoxedStackEntry::from_number(0),
+            TvmBoxedStackEntry::from_number(0),
+            null(),
+        ];
+
+        let order = MultisigOrder::try_from(stack).unwrap();
+
+        assert_eq!(order.threshold, None);
+        assert!(order.actions.is_empty());
+        assert!(order.approved_by().is_empty());
+    }
+
+    #[test]
+    fn stack_of_unknown_version_is_raw() {
+        let stack = vec![
+            TvmBoxedStackEntry::from_number(4),
+            TvmBoxedStackEntry::from_number(2),
+        ];
+
+        assert!(matches!(
+            decoded::<MultisigData>(stack),
+            Decoded::Raw(stack) if stack.len() == 2
+        ));
+    }
+}
@@ -1,10 +1,26 @@
 use async_trait::async_trait;
+use num_bigint::BigUint;
+use toner::tlb::bits::r#as::{NBits, VarInt};
+use toner::tlb::r#as::Ref;
+use toner::tlb::Cell;
+use toner::ton::MsgAddress;
+use tonlibjson_client::ton::TonClient;
 
-use crate::{adapters::TvmBoxedStackEntryExt, TonContract, TonContractError};
+use crate::interfaces::{ContractInterface, InterfaceDetector, METHOD_NOT_FOUND};
+use crate::{
+    adapters::{bits256, list_elements, tuple, TvmBoxedStackEntryExt},
+    TonContract, TonContractError,
+};
 
 #[async_trait]
 pub trait WalletContract {
     async fn seqno(&self) -> Result<u32, TonContractError>;
+
+    /// None if the wallet has no `get_subwallet_id`, e.g. wallet v2
+    async fn subwallet_id(&self) -> Result<Option<u32>, TonContractError>;
+
+    /// Plugins installed into a wallet v4
+    async fn plugin_list(&self) -> Result<Vec<MsgAddress>, TonContractError>;
 }
 
 #[async_trait]
@@ -13,4 +29,521 @@ impl WalletContract for TonContract {
         let [seqno] = self.run_get_method("seqno", [].into()).await?.try_into()?;
         seqno.to_number()
     }
+
+    async fn subwallet_id(&self) -> Result<Option<u32>, TonContractError> {
+        match self.run_get_method("get_subwallet_id", [].into()).await {
+            Ok(stack) => {
+                let [subwallet_id] = stack.try_into()?;
+
+                Ok(Some(subwallet_id.to_number()?))
+            }
+            Err(TonContractError::Contract(METHOD_NOT_FOUND)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn plugin_list(&self) -> Result<Vec<MsgAddress>, TonContractError> {
+        let [list] = self
+            .run_get_method("get_plugin_list", [].into())
+            .await?
+            .try_into()?;
+
+        list_elements(list)?
+            .into_iter()
+            .map(|plugin| {
+                let [workchain_id, address] = tuple(plugin)?.try_into()?;
+
+                Ok(MsgAddress {
+                    workchain_id: workchain_id.to_number()?,
+                    address: bits256(&address)?,
+                })
+            })
+            .collect()
+    }
+}
+
+pub struct WalletSeqno {
+    /// 0 for wallets which aren't deployed yet, the seqno of their first message
+    pub seqno: u32,
+    pub subwallet_id: Option<u32>,
+    /// the most specific wallet interface, none for wallets which aren't deployed yet
+    pub wallet_type: Option<ContractInterface>,
+    pub deployed: bool,
+}
+
+/// The versioned wallet interface over the generic one
+fn wallet_type(interfaces: &[ContractInterface]) -> Option<ContractInterface> {
+    interfaces
+        .iter()
+        .copied()
+        .filter(|interface| {
+            matches!(
+                interface,
+                ContractInterface::Wallet
+                    | ContractInterface::WalletV3R2
+                    | ContractInterface::WalletV4R2
+                    | ContractInterface::WalletV5R1
+            )
+        })
+        .max_by_key(|interface| *interface != ContractInterface::Wallet)
+}
+
+#[async_trait]
+pub trait Wallets {
+    /// Seqno and subwallet id of the wallet at `address` to sign its next message,
+    /// fails with [`TonContractError::Contract`] if the contract is not a wallet
+    async fn wallet_seqno(&self, address: &MsgAddress) -> Result<WalletSeqno, TonContractError>;
+}
+
+#[async_trait]
+impl Wallets for TonClient {
+    async fn wallet_seqno(&self, address: &MsgAddress) -> Result<WalletSeqno, TonContractError> {
+        let detected = self.contract_interfaces(address).await?;
+        if detected.code_hash.is_none() {
+            return Ok(WalletSeqno {
+                seqno: 0,
+                subwallet_id: None,
+                wallet_type: None,
+                deployed: false,
+            });
+        }
+        let wallet_type = wallet_type(&detected.interfaces)
+            .ok_or(TonContractError::Contract(METHOD_NOT_FOUND))?;
+
+        let contract = TonContract::new(self.clone(), *address);
+        let (seqno, subwallet_id) = futures::try_join!(contract.seqno(), contract.subwallet_id())?;
+
+        Ok(WalletSeqno {
+            seqno,
+            subwallet_id,
+            wallet_type: Some(wallet_type),
+            deployed: true,
+        })
+    }
+}
+
+/// `internal_transfer#ae42e5a4 query_id:uint64 actions:^(OutList n) = InternalMsgBody n;`,
+/// the message a highload wallet v3 sends to itself to send the transfers of a batch
+pub const HIGHLOAD_V3_INTERNAL_TRANSFER: u32 = 0xae42e5a4;
+/// `action_send_msg#0ec3c86d mode:(## 8) out_msg:^(MessageRelaxed Any) = OutAction;`
+pub const ACTION_SEND_MSG: u32 = 0x0ec3c86d;
+/// Transfers of an internal transfer, the out action list holds 255 actions
+pub const HIGHLOAD_V3_MAX_TRANSFERS: usize = 254;
+/// `timeout:uint22`
+pub const HIGHLOAD_V3_MAX_TIMEOUT: u32 = (1 << 22) - 1;
+/// The wallet rejects the last bit of a shift
+const HIGHLOAD_V3_MAX_BIT_NUMBER: u16 = 1022;
+const HIGHLOAD_V3_MAX_SHIFT: u16 = (1 << 13) - 1;
+/// Mode of the internal transfer, it carries the whole balance for the transfers to spend
+const SEND_MODE_CARRY_ALL_BALANCE: u8 = 128;
+
+/// Query id of a highload wallet v3, `shift:uint13 bit_number:uint10`. The wallet keeps the
+/// bits of processed queries of each shift until `timeout` passes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HighloadQueryId {
+    pub shift: u16,
+    pub bit_number: u16,
+}
+
+impl HighloadQueryId {
+    pub fn from_query_id(query_id: u32) -> Self {
+        Self {
+            shift: (query_id >> 10) as u16 & HIGHLOAD_V3_MAX_SHIFT,
+            bit_number: query_id as u16 & 0x3ff,
+        }
+    }
+
+    pub fn query_id(&self) -> u32 {
+        (self.shift as u32) << 10 | self.bit_number as u32
+    }
+
+    /// The next query id, none once every bit of every shift is used
+    pub fn next(&self) -> Option<Self> {
+        if self.bit_number < HIGHLOAD_V3_MAX_BIT_NUMBER {
+            return Some(Self {
+                shift: self.shift,
+                bit_number: self.bit_number + 1,
+            });
+        }
+        if self.shift < HIGHLOAD_V3_MAX_SHIFT {
+            return Some(Self {
+                shift: self.shift + 1,
+                bit_number: 0,
+            });
+        }
+
+        None
+    }
+}
+
+/// Internal message of a batch, `mode` is its send mode
+#[derive(Debug, Clone)]
+pub struct Transfer {
+    pub destination: MsgAddress,
+    pub value: BigUint,
+    pub bounce: bool,
+    pub mode: u8,
+    pub body: Option<Cell>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum HighloadBatchError {
+    #[error("batch is empty")]
+    Empty,
+    #[error("batch has {0} transfers, at most {HIGHLOAD_V3_MAX_TRANSFERS} fit")]
+    TooManyTransfers(usize),
+    #[error("transfers send {total} nanotons, the wallet has {balance}")]
+    InsufficientBalance { total: BigUint, balance: BigUint },
+    #[error("query id bit number {0} is over {HIGHLOAD_V3_MAX_BIT_NUMBER}")]
+    BitNumber(u16),
+    #[error("query id shift {0} is over {HIGHLOAD_V3_MAX_SHIFT}")]
+    Shift(u16),
+    #[error("timeout {0} is over {HIGHLOAD_V3_MAX_TIMEOUT}")]
+    Timeout(u32),
+}
+
+fn check_highload_v3_batch(
+    transfers: &[Transfer],
+    query_id: HighloadQueryId,
+    timeout: u32,
+) -> Result<(), HighloadBatchError> {
+    if transfers.is_empty() {
+        return Err(HighloadBatchError::Empty);
+    }
+    if transfers.len() > HIGHLOAD_V3_MAX_TRANSFERS {
+        return Err(HighloadBatchError::TooManyTransfers(transfers.len()));
+    }
+    if query_id.bit_number > HIGHLOAD_V3_MAX_BIT_NUMBER {
+        return Err(HighloadBatchError::BitNumber(query_id.bit_number));
+    }
+    if query_id.shift > HIGHLOAD_V3_MAX_SHIFT {
+        return Err(HighloadBatchError::Shift(query_id.shift));
+    }
+    if timeout > HIGHLOAD_V3_MAX_TIMEOUT {
+        return Err(HighloadBatchError::Timeout(timeout));
+    }
+
+    Ok(())
+}
+
+/// Checks the batch against the constraints of the wallet, the values sent may not exceed
+/// `balance`, fees aside
+pub fn validate_highload_v3_batch(
+    transfers: &[Transfer],
+    query_id: HighloadQueryId,
+    timeout: u32,
+    balance: &BigUint,
+) -> Result<(), HighloadBatchError> {
+    check_highload_v3_batch(transfers, query_id, timeout)?;
+
+    let total: BigUint = transfers.iter().map(|transfer| &transfer.value).sum();
+    if &total > balance {
+        return Err(HighloadBatchError::InsufficientBalance {
+            total,
+            balance: balance.clone(),
+        });
+    }
+
+    Ok(())
+}
+
+/// `MessageRelaxed` of an internal message with `ihr_disabled`, no source and no state init.
+/// The body is kept in the root cell if it fits, as the reference tooling does
+fn message_relaxed(
+    destination: &MsgAddress,
+    value: &BigUint,
+    bounce: bool,
+    body: &Cell,
+) -> Result<Cell, TonContractError> {
+    let value_bytes = value.bits().div_ceil(8) as usize;
+    // info flags, addr_none, addr_std, grams, no extra currencies, ihr and forward fees,
+    // created_lt, created_at and no state init
+    let header_bits = 4 + 2 + 267 + 4 + 8 * value_bytes + 1 + 4 + 4 + 64 + 32 + 1;
+    let inline = body.data.len() < 1023 - header_bits && body.references.len() <= 4;
+
+    let mut builder = Cell::builder();
+    builder
+        // int_msg_info$0 ihr_disabled bounce bounced
+        .pack(false)?
+        .pack(true)?
+        .pack(bounce)?
+        .pack(false)?
+        .pack_as::<_, NBits<2>>(0_u8)?
+        .pack(destination)?
+        .pack_as::<_, VarInt<4>>(value.clone())?
+        .pack(false)?
+        .pack_as::<_, VarInt<4>>(BigUint::ZERO)?
+        .pack_as::<_, VarInt<4>>(BigUint::ZERO)?
+        .pack(0_u64)?
+        .pack(0_u32)?
+        .pack(false)?
+        .pack(!inline)?;
+    if inline {
+        builder.store(body)?;
+    } else {
+        builder.store_as::<_, Ref>(body)?;
+    }
+
+    Ok(builder.into_cell())
+}
+
+/// `OutList` of the transfers, the last one at the root
+fn out_list(transfers: &[Transfer]) -> Result<Cell, TonContractError> {
+    transfers
+        .iter()
+        .try_fold(Cell::default(), |prev, transfer| {
+            let body = transfer.body.clone().unwrap_or_default();
+            let message = message_relaxed(
+                &transfer.destination,
+                &transfer.value,
+                transfer.bounce,
+                &body,
+            )?;
+
+            let mut builder = Cell::builder();
+            builder
+                .store_as::<_, Ref>(prev)?
+                .pack(ACTION_SEND_MSG)?
+                .pack(transfer.mode)?
+                .store_as::<_, Ref>(message)?;
+
+            Ok(builder.into_cell())
+        })
+}
+
+/// Unsigned `msg_inner` of an external message of a highload wallet v3 at `wallet` sending the
+/// transfers at once: `subwallet_id:uint32 message_to_send:^Cell send_mode:uint8
+/// query_id:uint23 created_at:uint64 timeout:uint22`. The message to send is an internal
+/// transfer to the wallet itself, the external one is `signature:bits512 ^msg_inner` with
+/// the signature of the hash of `msg_inner`
+pub fn build_highload_v3_batch(
+    wallet: &MsgAddress,
+    subwallet_id: u32,
+    transfers: &[Transfer],
+    query_id: HighloadQueryId,
+    created_at: u64,
+    timeout: u32,
+) -> Result<Cell, TonContractError> {
+    check_highload_v3_batch(transfers, query_id, timeout)?;
+
+    let mut internal_transfer = Cell::builder();
+    internal_transfer
+        .pack(HIGHLOAD_V3_INTERNAL_TRANSFER)?
+        .pack(query_id.query_id() as u64)?
+        .store_as::<_, Ref>(out_list(transfers)?)?;
+    let message = message_relaxed(wallet, &BigUint::ZERO, true, &internal_transfer.into_cell())?;
+
+    let mut inner = Cell::builder();
+    inner
+        .pack(subwallet_id)?
+        .store_as::<_, Ref>(message)?
+        .pack(SEND_MODE_CARRY_ALL_BALANCE)?
+        .pack_as::<_, NBits<23>>(query_id.query_id())?
+        .pack(created_at)?
+        .pack_as::<_, NBits<22>>(timeout)?;
+
+    Ok(inner.into_cell())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::{address, reference, uint, var_uint16};
+    use toner::tlb::de::CellParser;
+
+    #[test]
+    fn versioned_wallet_type_is_preferred() {
+        assert_eq!(
+            wallet_type(&[ContractInterface::Wallet, ContractInterface::WalletV4R2]),
+            Some(ContractInterface::WalletV4R2)
+        );
+        assert_eq!(
+            wallet_type(&[ContractInterface::Wallet, ContractInterface::DnsResolver]),
+            Some(ContractInterface::Wallet)
+        );
+        assert_eq!(wallet_type(&[ContractInterface::JettonMaster]), None);
+    }
+
+    fn wallet() -> MsgAddress {
+        "EQCjk1hh952vWaE9bRguFkAhDAL5jj3xj9p0uPWrFBq_GEMS"
+            .parse()
+            .unwrap()
+    }
+
+    fn transfer(value: u32, body: Option<Cell>) -> Transfer {
+        Transfer {
+            destination: "EQBGXZ9ddZeWypx8EkJieHJX75ct0bpkmu0Y4YoYr3NM0Z9e"
+                .parse()
+                .unwrap(),
+            value: BigUint::from(value),
+            bounce: false,
+            mode: 3,
+            body,
+        }
+    }
+
+    const QUERY_ID: HighloadQueryId = HighloadQueryId {
+        shift: 5,
+        bit_number: 7,
+    };
+
+    #[test]
+    fn query_id_packs_shift_and_bit_number() {
+        assert_eq!(QUERY_ID.query_id(), 5 << 10 | 7);
+        assert_eq!(HighloadQueryId::from_query_id(5 << 10 | 7), QUERY_ID);
+        assert_eq!(
+            HighloadQueryId {
+                shift: 5,
+                bit_number: 1022
+            }
+            .next(),
+            Some(HighloadQueryId {
+                shift: 6,
+                bit_number: 0
+            })
+        );
+        assert_eq!(
+            HighloadQueryId {
+                shift: 8191,
+                bit_number: 1022
+            }
+            .next(),
+            None
+        );
+    }
+
+    /// Fields of an internal message up to its body
+    fn message_header(parser: &mut CellParser<'_>) -> (bool, MsgAddress, BigUint) {
+        // int_msg_info$0 ihr_disabled bounce bounced
+        let flags = uint(parser, 4).unwrap();
+        assert_eq!(flags & 0b1101, 0b0100);
+        let bounce = flags & 0b0010 != 0;
+        assert_eq!(uint(parser, 2), Some(0));
+        let destination = address(parser).unwrap();
+        let value = var_uint16(parser).unwrap();
+        // extra currencies, ihr and forward fees, created_lt, created_at, state init
+        assert_eq!(uint(parser, 1 + 4 + 4), Some(0));
+        assert_eq!(uint(parser, 64), Some(0));
+        assert_eq!(uint(parser, 32 + 1), Some(0));
+
+        (bounce, destination, value)
+    }
+
+    #[test]
+    fn batch_layout() {
+        let transfers = vec![
+            transfer(1, None),
+            transfer(
+                2,
+                Some(Cell::builder().pack(0xdead_u16).unwrap().into_cell()),
+            ),
+        ];
+
+        let inner =
+            build_highload_v3_batch(&wallet(), 0x10ad, &transfers, QUERY_ID, 1700000000, 3600)
+                .unwrap();
+
+        let mut parser = inner.parser();
+        assert_eq!(uint(&mut parser, 32), Some(0x10ad));
+        let mut message = reference(&mut parser).unwrap();
+        assert_eq!(uint(&mut parser, 8), Some(128));
+        assert_eq!(uint(&mut parser, 23), Some(QUERY_ID.query_id() as u64));
+        assert_eq!(uint(&mut parser, 64), Some(1700000000));
+        assert_eq!(uint(&mut parser, 22), Some(3600));
+        assert!(parser.no_bits_left());
+
+        // the internal transfer goes to the wallet itself, its body inline
+        let (_, destination, value) = message_header(&mut message);
+        assert_eq!(destination, wallet());
+        assert_eq!(value, BigUint::ZERO);
+        assert_eq!(uint(&mut message, 1), Some(0));
+        assert_eq!(
+            uint(&mut message, 32),
+            Some(HIGHLOAD_V3_INTERNAL_TRANSFER as u64)
+        );
+        assert_eq!(uint(&mut message, 64), Some(QUERY_ID.query_id() as u64));
+        let mut actions = reference(&mut message).unwrap();
+
+        // the last transfer is at the root of the out list
+        for (i, expected) in transfers.iter().enumerate().rev() {
+            let prev = reference(&mut actions).unwrap();
+            assert_eq!(uint(&mut actions, 32), Some(ACTION_SEND_MSG as u64));
+            assert_eq!(uint(&mut actions, 8), Some(3));
+            let mut out = reference(&mut actions).unwrap();
+            assert!(actions.no_bits_left());
+
+            let (_, destination, value) = message_header(&mut out);
+            assert_eq!(destination, expected.destination);
+            assert_eq!(value, BigUint::from(i + 1));
+            assert_eq!(uint(&mut out, 1), Some(0));
+            if expected.body.is_some() {
+                assert_eq!(uint(&mut out, 16), Some(0xdead));
+            }
+            assert!(out.no_bits_left());
+
+            actions = prev;
+        }
+        assert!(actions.no_bits_left() && actions.no_references_left());
+    }
+
+    #[test]
+    fn large_body_is_referenced() {
+        let mut body = Cell::builder();
+        for _ in 0..100 {
+            body.pack(0xff_u8).unwrap();
+        }
+        let body = body.into_cell();
+
+        let message = message_relaxed(&wallet(), &BigUint::from(1_u8), true, &body).unwrap();
+
+        let mut parser = message.parser();
+        let (bounce, _, _) = message_header(&mut parser);
+        assert!(bounce);
+        assert_eq!(uint(&mut parser, 1), Some(1));
+        assert_eq!(
+            reference(&mut parser).unwrap().parse::<Cell>().unwrap(),
+            body
+        );
+    }
+
+    #[test]
+    fn batch_is_validated() {
+        let balance = BigUint::from(10_u8);
+        let check = |transfers: &[Transfer], query_id, timeout| {
+            validate_highload_v3_batch(transfers, query_id, timeout, &balance)
+        };
+
+        assert_eq!(check(&[], QUERY_ID, 60), Err(HighloadBatchError::Empty));
+        let many: Vec<_> = (0..255).map(|_| transfer(0, None)).collect();
+        assert_eq!(
+            check(&many, QUERY_ID, 60),
+            Err(HighloadBatchError::TooManyTransfers(255))
+        );
+        assert_eq!(
+            check(&[transfer(6, None), transfer(5, None)], QUERY_ID, 60),
+            Err(HighloadBatchError::InsufficientBalance {
+                total: BigUint::from(11_u8),
+                balance: balance.clone(),
+            })
+        );
+        assert_eq!(
+            check(
+                &[transfer(1, None)],
+                HighloadQueryId {
+                    shift: 0,
+                    bit_number: 1023
+                },
+                60
+            ),
+            Err(HighloadBatchError::BitNumber(1023))
+        );
+        assert_eq!(
+            check(&[transfer(1, None)], QUERY_ID, 1 << 22),
+            Err(HighloadBatchError::Timeout(1 << 22))
+        );
+        assert_eq!(
+            check(&[transfer(5, None), transfer(5, None)], QUERY_ID, 60),
+            Ok(())
+        );
+    }
 }
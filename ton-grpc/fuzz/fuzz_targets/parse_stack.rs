@@ -0,0 +1,19 @@
+//! The stack of RunGetMethod, the JSON a client sends is parsed within the default limits
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use ton_grpc::json::{self, JsonLimits};
+use tonlibjson_client::block::TvmBoxedStackEntry;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(stack) = std::str::from_utf8(data) else {
+        return;
+    };
+
+    let limits = JsonLimits::default();
+    let _ = json::from_str::<Vec<TvmBoxedStackEntry>>("stack", stack, &limits);
+    // whatever passes the limits is safe to parse into any shape
+    if json::check(stack, &limits).is_ok() {
+        let _ = serde_json::from_str::<serde_json::Value>(stack);
+    }
+});
@@ -1,27 +1,124 @@
 #![allow(clippy::blocks_in_conditions)]
 
-use crate::helpers::{extend_block_id, extend_from_tx_id, extend_to_tx_id};
+use crate::account_state::{fetch_extended_account_state, special_flags};
+use crate::account_stats::fetch_account_stats;
+use crate::cache::{block_etag, no_store, transaction_etag, CachePolicy, Freshness};
+use crate::canonical;
+use crate::code::{code_hash, fetch_contract_code};
+use crate::cursor::{AccountTxCursor, Cursors, ExportCursor};
+use crate::export::ExportJobs;
+use crate::helpers::{
+    extend_block_id, extend_from_tx_id, extend_to_tx_id, take_utime_range, utime_bounds,
+    within_utime_range,
+};
+use crate::json::{self, JsonLimits};
+use crate::limits::{
+    ParamLimits, ResponseSizeLimits, ACCOUNT_STATS_EXACT_COUNT_LIMIT,
+    ACCOUNT_TRANSACTIONS_PAGE_LIMIT, FETCH_EXPORT_CHUNK_LIMIT, MESSAGE_TRACE_MAX_DEPTH,
+    WAIT_FOR_TRANSACTION_TIMEOUT_MS,
+};
+use crate::prefetch::HotAddresses;
+use crate::recent::{Confirmation, RecentMessages};
+use crate::stale::StaleStates;
+use crate::summary::account_summary;
 use crate::ton::account_service_server::AccountService as BaseAccountService;
 use crate::ton::get_account_state_response::AccountState;
+use crate::ton::get_account_states_response;
+use crate::ton::get_account_states_response::result::Result as AccountStatesResult;
 use crate::ton::get_account_transactions_request::Order;
-use crate::ton::{get_account_state_request, get_shard_account_cell_request};
+use crate::ton::run_get_methods_response::result::Result as GetMethodResult;
+use crate::ton::{
+    account_state_delta, run_get_methods_response, AccountStateDelta, AccountStateProofs,
+    AccountStats, AccountSummary, AsOf, ConfigProposal, DnsResolveRequest, DnsResolveResponse,
+    ExportStatus, ExtendedAccountState, FetchExportChunkRequest, FetchExportChunkResponse,
+    GetAccountStateRequest, GetAccountStateResponse, GetAccountStatesRequest,
+    GetAccountStatesResponse, GetAccountStatsRequest, GetAccountSummaryRequest,
+    GetAccountTransactionsPageRequest, GetAccountTransactionsPageResponse,
+    GetAccountTransactionsRequest, GetConfigProposalRequest, GetConfigProposalsRequest,
+    GetConfigProposalsResponse, GetConsistentSnapshotRequest, GetConsistentSnapshotResponse,
+    GetContractCodeRequest, GetContractCodeResponse, GetContractInterfacesRequest,
+    GetContractInterfacesResponse, GetElectionDataRequest, GetElectionDataResponse,
+    GetExportStatusRequest, GetExtendedAccountStateRequest, GetJettonWalletAddressRequest,
+    GetJettonWalletAddressResponse, GetMessageTraceRequest, GetMultisigInfoRequest,
+    GetMultisigOrderRequest, GetShardAccountCellRequest, GetShardAccountCellResponse,
+    GetStakeRequest, GetStakeResponse, GetWalletSeqnoRequest, GetWalletSeqnoResponse, MessageTrace,
+    MultisigInfo, MultisigOrder, PartialTransactionId, RunGetMethodRequest, RunGetMethodResponse,
+    RunGetMethodsRequest, RunGetMethodsResponse, StartTransactionExportRequest, Transaction,
+    WaitForTransactionRequest, WaitForTransactionResponse, WatchAccountStateRequest,
+};
 use crate::ton::{
-    GetAccountStateRequest, GetAccountStateResponse, GetAccountTransactionsRequest,
-    GetShardAccountCellRequest, GetShardAccountCellResponse, Transaction,
+    dns_resolve_request, get_account_state_request, get_shard_account_cell_request,
+    wait_for_transaction_request, wait_for_transaction_response,
 };
+use crate::trace::message_trace;
+use crate::watch::AccountWatchers;
 use anyhow::Result;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
 use derive_new::new;
-use futures::{try_join, Stream, StreamExt, TryFutureExt, TryStreamExt};
+use futures::{try_join, Stream, StreamExt, TryStreamExt};
+use std::collections::{HashMap, HashSet};
 use std::pin::Pin;
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_stream::wrappers::WatchStream;
+use ton_contract::abi::{decode_stack, AbiRegistry};
+use ton_contract::config::ConfigProposals;
+use ton_contract::dns::{encode_domain, DnsResolver};
+use ton_contract::elector::Elector;
+use ton_contract::interfaces::InterfaceDetector;
+use ton_contract::jetton::JettonWallets;
+use ton_contract::multisig::{Decoded, Multisigs};
+use ton_contract::wallet::Wallets;
+use ton_contract::TonContractError;
+use toner::ton::MsgAddress;
 use tonic::{async_trait, Request, Response, Status};
 use tonlibjson_client::address::AccountAddressData;
-use tonlibjson_client::block::{RawFullAccountState, TonBlockIdExt, TvmCell};
-use tonlibjson_client::ton::TonClient;
+use tonlibjson_client::block::{
+    InternalTransactionId, RawFullAccountState, RawTransaction, SmcRunResult, TonBlockIdExt,
+    TvmBoxedStackEntry, TvmCell,
+};
+use tonlibjson_client::breaker::is_circuit_open;
+use tonlibjson_client::ton::{
+    AccountStatus, GetMethodCall, MessageRef, TonClient, WaitForTransaction, RUN_GET_METHODS_LIMIT,
+};
+use tonlibjson_client::transport::LiteServerTransport;
+use uuid::Uuid;
+
+/// Max number of accounts of a single `GetConsistentSnapshot`
+const CONSISTENT_SNAPSHOT_MAX_ACCOUNTS: usize = 1000;
+const CONSISTENT_SNAPSHOT_CONCURRENCY: usize = 16;
+/// Max number of accounts of a single `GetAccountStates`
+const ACCOUNT_STATES_MAX_ACCOUNTS: usize = 256;
 
 #[derive(new)]
 pub struct AccountService {
     client: TonClient,
+    #[new(default)]
+    proofs: Option<Arc<dyn LiteServerTransport>>,
+    #[new(default)]
+    response_size_limits: ResponseSizeLimits,
+    #[new(default)]
+    param_limits: ParamLimits,
+    #[new(default)]
+    json_limits: JsonLimits,
+    #[new(default)]
+    watchers: Option<Arc<AccountWatchers>>,
+    #[new(default)]
+    cache_policy: CachePolicy,
+    #[new(default)]
+    export_jobs: Option<Arc<ExportJobs>>,
+    #[new(default)]
+    cursors: Cursors,
+    #[new(default)]
+    stale_states: Option<Arc<StaleStates>>,
+    #[new(default)]
+    abi: Option<Arc<AbiRegistry>>,
+    #[new(default)]
+    recent: Option<Arc<RecentMessages>>,
+    #[new(default)]
+    hot_addresses: Option<Arc<HotAddresses>>,
 }
 
 #[async_trait]
@@ -31,31 +128,196 @@ impl BaseAccountService for AccountService {
         &self,
         request: Request<GetAccountStateRequest>,
     ) -> std::result::Result<Response<GetAccountStateResponse>, Status> {
+        let (metadata, _, msg) = request.into_parts();
+
+        if let Some(hot) = &self.hot_addresses {
+            if msg.criteria.is_none() && !msg.include_proofs && !msg.include_as_of {
+                hot.touch(&msg.account_address);
+                if let Some((etag, response)) = hot.state(&msg.account_address) {
+                    return self
+                        .cache_policy
+                        .respond(&metadata, etag, Freshness::Latest, || response);
+                }
+            }
+        }
+
+        let freshness = match &msg.criteria {
+            Some(get_account_state_request::Criteria::BlockId(block_id)) => self
+                .cache_policy
+                .block_freshness(&self.client, block_id)
+                .await
+                .map_err(|e| Status::internal(e.to_string()))?,
+            Some(get_account_state_request::Criteria::TransactionId(_)) => Freshness::Final,
+            Some(get_account_state_request::Criteria::AtLeastBlockId(_)) | None => {
+                Freshness::Latest
+            }
+        };
+        let state = match self.fetch_account_state(&msg).await {
+            Ok(state) => state,
+            Err(e) => {
+                return match self.stale_state(&msg, &e) {
+                    Some(response) => Ok(no_store(Response::new(response))),
+                    None => Err(Status::internal(e.to_string())),
+                }
+            }
+        };
+        let proofs = if msg.include_proofs {
+            Some(
+                self.fetch_account_state_proofs(&msg.account_address, &state.block_id)
+                    .await?,
+            )
+        } else {
+            None
+        };
+
+        let as_of = if msg.include_as_of {
+            let account = AccountAddressData::from_str(&msg.account_address)
+                .map_err(|e| Status::invalid_argument(e.to_string()))?;
+            Some(
+                self.client
+                    .read_context(state.block_id.clone(), &account)
+                    .await
+                    .map_err(|e| Status::internal(e.to_string()))?,
+            )
+        } else {
+            None
+        };
+
+        let (etag, mut response) = state_response(&self.client, msg.account_address, state).await?;
+        response.proofs = proofs;
+        if let Some(stale_states) = &self.stale_states {
+            if msg.criteria.is_none() && !msg.include_proofs {
+                stale_states.insert(response.account_address.clone(), response.clone());
+            }
+        }
+        response.as_of = as_of.map(Into::into);
+
+        self.cache_policy
+            .respond(&metadata, etag, freshness, || response)
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn get_consistent_snapshot(
+        &self,
+        request: Request<GetConsistentSnapshotRequest>,
+    ) -> Result<Response<GetConsistentSnapshotResponse>, Status> {
         let msg = request.into_inner();
+        if msg.account_addresses.len() > CONSISTENT_SNAPSHOT_MAX_ACCOUNTS {
+            return Err(Status::invalid_argument(format!(
+                "account_addresses must not exceed {}",
+                CONSISTENT_SNAPSHOT_MAX_ACCOUNTS
+            )));
+        }
 
-        let address = AccountAddressData::from_str(&msg.account_address)
-            .map_err(|e| Status::internal(e.to_string()))?;
+        let pinned = match msg.block_id {
+            Some(block_id) if block_id.workchain != -1 => {
+                return Err(Status::invalid_argument(
+                    "block_id must be a masterchain block",
+                ))
+            }
+            Some(block_id) => self.client.with_block(
+                extend_block_id(&self.client, &block_id)
+                    .await
+                    .map_err(|e| Status::internal(e.to_string()))?,
+            ),
+            None => self
+                .client
+                .with_last_block()
+                .await
+                .map_err(|e| Status::internal(e.to_string()))?,
+        };
 
-        let state = self
-            .fetch_account_state(&msg)
-            .map_err(|e| Status::internal(e.to_string()))
+        let account_states = futures::stream::iter(msg.account_addresses)
+            .map(|address| {
+                let pinned = pinned.clone();
+                async move {
+                    let state = pinned
+                        .raw_get_account_state(&address)
+                        .await
+                        .map_err(|e| Status::internal(e.to_string()))?;
+
+                    account_state_response(address, state)
+                }
+            })
+            .buffered(CONSISTENT_SNAPSHOT_CONCURRENCY)
+            .try_collect()
             .await?;
 
-        let block_id = state.block_id.clone();
-        let balance = state.balance.unwrap_or_default();
-        let last_transaction_id = state
-            .last_transaction_id
-            .clone()
-            .map(|t| (&address, t).into());
-        let state: AccountState = state.into();
-        let block_id = block_id.into();
+        Ok(Response::new(GetConsistentSnapshotResponse {
+            block_id: Some(pinned.block_id().clone().into()),
+            account_states,
+        }))
+    }
 
-        Ok(Response::new(GetAccountStateResponse {
-            balance,
-            account_address: msg.account_address,
-            block_id: Some(block_id),
-            last_transaction_id,
-            account_state: Some(state),
+    #[tracing::instrument(skip_all, err)]
+    async fn get_account_states(
+        &self,
+        request: Request<GetAccountStatesRequest>,
+    ) -> Result<Response<GetAccountStatesResponse>, Status> {
+        let msg = request.into_inner();
+        if msg.account_addresses.len() > ACCOUNT_STATES_MAX_ACCOUNTS {
+            return Err(Status::invalid_argument(format!(
+                "account_addresses must not exceed {}",
+                ACCOUNT_STATES_MAX_ACCOUNTS
+            )));
+        }
+
+        let pinned = if msg.pin_block {
+            Some(
+                self.client
+                    .with_last_block()
+                    .await
+                    .map_err(|e| Status::internal(e.to_string()))?,
+            )
+        } else {
+            None
+        };
+
+        let (raw_addresses, unique) = dedup_addresses(&msg.account_addresses);
+        let states: HashMap<_, _> = futures::stream::iter(unique)
+            .map(|(raw_address, address)| {
+                let client = self.client.clone();
+                let pinned = pinned.clone();
+                async move {
+                    let state = match &pinned {
+                        Some(pinned) => pinned.raw_get_account_state(&address).await,
+                        None => client.raw_get_account_state(&address).await,
+                    };
+                    let state = state.map_err(|e| e.to_string()).and_then(|state| {
+                        account_state_response(address, state).map_err(|e| e.message().to_owned())
+                    });
+
+                    (raw_address, state)
+                }
+            })
+            .buffer_unordered(CONSISTENT_SNAPSHOT_CONCURRENCY)
+            .collect()
+            .await;
+
+        let results = msg
+            .account_addresses
+            .into_iter()
+            .zip(raw_addresses)
+            .map(|(account_address, raw_address)| {
+                let result = match raw_address.as_ref().map(|raw_address| &states[raw_address]) {
+                    Ok(Ok(state)) => AccountStatesResult::AccountState(GetAccountStateResponse {
+                        account_address: account_address.clone(),
+                        ..state.clone()
+                    }),
+                    Ok(Err(e)) | Err(e) => AccountStatesResult::Error(e.clone()),
+                };
+
+                get_account_states_response::Result {
+                    account_address,
+                    raw_address: raw_address.unwrap_or_default(),
+                    result: Some(result),
+                }
+            })
+            .collect();
+
+        Ok(Response::new(GetAccountStatesResponse {
+            block_id: pinned.map(|pinned| pinned.block_id().clone().into()),
+            results,
         }))
     }
 
@@ -97,23 +359,57 @@ impl BaseAccountService for AccountService {
         let address = AccountAddressData::from_str(&msg.account_address)
             .map_err(|e| Status::internal(e.to_string()))?;
 
-        let (from_tx, to_tx) = try_join!(
+        let (mut from_tx, mut to_tx, utime_bounds) = try_join!(
             extend_from_tx_id(&client, &msg.account_address, msg.from.clone()),
-            extend_to_tx_id(&client, &msg.account_address, msg.to.clone())
+            extend_to_tx_id(&client, &msg.account_address, msg.to.clone()),
+            utime_bounds(
+                &client,
+                &msg.account_address,
+                msg.start_utime,
+                msg.end_utime
+            )
         )
         .map_err(|e: anyhow::Error| Status::internal(e.to_string()))?;
 
+        // explicit bounds win, the utime filter below still applies to them
+        let Some((utime_from, utime_to)) = utime_bounds else {
+            return Ok(Response::new(futures::stream::empty().boxed()));
+        };
+        if msg.from.is_none() {
+            from_tx = utime_from;
+        }
+        if msg.to.is_none() {
+            to_tx = utime_to;
+        }
+
+        let (start_utime, end_utime) = (msg.start_utime, msg.end_utime);
         let stream = match msg.order() {
             Order::Unordered => client
                 .get_account_tx_range_unordered(&msg.account_address, (from_tx, to_tx))
                 .await
                 .map_err(|e: anyhow::Error| Status::internal(e.to_string()))?
+                .try_filter(move |tx| {
+                    futures::future::ready(within_utime_range(tx, start_utime, end_utime))
+                })
                 .boxed(),
-            Order::FromNewToOld => client
-                .get_account_tx_range(&msg.account_address, (from_tx, to_tx))
-                .boxed(),
+            Order::FromNewToOld => take_utime_range(
+                client.get_account_tx_range(&msg.account_address, (from_tx, to_tx)),
+                start_utime,
+                end_utime,
+            )
+            .boxed(),
         }
-        .map_ok(move |t| (&address, t).into())
+        .and_then(move |t| {
+            let tx = Transaction::try_from((&address, t)).map(|tx| {
+                if msg.decode_messages {
+                    tx.decode_messages()
+                } else {
+                    tx
+                }
+            });
+
+            futures::future::ready(tx)
+        })
         .map_err(|e: anyhow::Error| {
             tracing::error!(error = %e, "get_account_transactions failed");
             Status::internal(e.to_string())
@@ -122,9 +418,802 @@ impl BaseAccountService for AccountService {
 
         Ok(Response::new(stream))
     }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn get_account_transactions_page(
+        &self,
+        request: Request<GetAccountTransactionsPageRequest>,
+    ) -> std::result::Result<Response<GetAccountTransactionsPageResponse>, Status> {
+        let msg = request.into_inner();
+
+        let address = AccountAddressData::from_str(&msg.account_address)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let limit =
+            self.param_limits
+                .apply(ACCOUNT_TRANSACTIONS_PAGE_LIMIT, "limit", msg.limit.into())?
+                as usize;
+        let max_size = self
+            .response_size_limits
+            .for_method("GetAccountTransactionsPage");
+
+        let from = match (msg.cursor.as_str(), msg.from) {
+            ("", from) => from.map(Into::into),
+            (cursor, None) => Some(
+                self.cursors
+                    .decode::<AccountTxCursor>(cursor)?
+                    .transaction_id(&address)?,
+            ),
+            (_, Some(_)) => {
+                return Err(Status::invalid_argument(
+                    "either from or cursor is expected",
+                ))
+            }
+        };
+
+        let prefetched = match &self.hot_addresses {
+            Some(hot) if from.is_none() => {
+                hot.touch(&msg.account_address);
+                hot.transactions(&msg.account_address, limit)
+            }
+            _ => None,
+        };
+        let mut stream = match prefetched {
+            Some(transactions) => futures::stream::iter(transactions.into_iter().map(Ok)).boxed(),
+            None => self
+                .client
+                .get_account_tx_stream_from(&msg.account_address, from)
+                .boxed(),
+        };
+
+        // size is tracked while draining, so a heavy account never gets collected in full
+        let mut response = GetAccountTransactionsPageResponse::default();
+        let mut size = 0;
+        while let Some(tx) = stream
+            .try_next()
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+        {
+            let mut tx = Transaction::try_from((&address, tx))
+                .map_err(|e| Status::internal(e.to_string()))?;
+            if msg.decode_messages {
+                tx = tx.decode_messages();
+            }
+            let tx_size = prost::encoding::message::encoded_len(1, &tx);
+
+            // the first transaction always goes, otherwise the cursor would never move
+            if response.transactions.len() == limit
+                || (!response.transactions.is_empty() && size + tx_size > max_size)
+            {
+                let id = tx.id.map(|id| PartialTransactionId {
+                    hash: id.hash,
+                    lt: id.lt,
+                });
+                if let Some(id) = &id {
+                    let cursor = AccountTxCursor::new(&address, &id.clone().into())
+                        .map_err(|e| Status::internal(e.to_string()))?;
+                    response.next_cursor = self.cursors.encode(&cursor);
+                }
+
+                response.incomplete = true;
+                response.next = id;
+                break;
+            }
+
+            size += tx_size;
+            response.transactions.push(tx);
+        }
+
+        Ok(Response::new(response))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn wait_for_transaction(
+        &self,
+        request: Request<WaitForTransactionRequest>,
+    ) -> Result<Response<WaitForTransactionResponse>, Status> {
+        let msg = request.into_inner();
+
+        let address = AccountAddressData::from_str(&msg.account_address)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let message = match msg.message {
+            Some(wait_for_transaction_request::Message::MessageHash(hash)) => {
+                MessageRef::Hash(hash)
+            }
+            Some(wait_for_transaction_request::Message::Body(body)) => MessageRef::Body {
+                source: body.source,
+                body_hash: body.body_hash,
+            },
+            None => return Err(Status::invalid_argument("message is required")),
+        };
+        let timeout = self.param_limits.apply(
+            WAIT_FOR_TRANSACTION_TIMEOUT_MS,
+            "timeout_ms",
+            msg.timeout_ms,
+        )?;
+        let timeout = Duration::from_millis(timeout as u64);
+
+        let response = match self
+            .client
+            .wait_for_transaction(&msg.account_address, &message, msg.after_lt, timeout)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+        {
+            WaitForTransaction::Found {
+                transaction,
+                bounced,
+            } => {
+                self.confirm_recent(&message, &transaction);
+
+                let transaction = Transaction::try_from((&address, *transaction))
+                    .map_err(|e| Status::internal(e.to_string()))?;
+
+                WaitForTransactionResponse {
+                    result: Some(wait_for_transaction_response::Result::Transaction(
+                        transaction,
+                    )),
+                    bounced,
+                }
+            }
+            WaitForTransaction::Timeout { last_checked_lt } => WaitForTransactionResponse {
+                result: Some(wait_for_transaction_response::Result::LastCheckedLt(
+                    last_checked_lt,
+                )),
+                bounced: false,
+            },
+        };
+
+        Ok(Response::new(response))
+    }
+
+    type WatchAccountStateStream =
+        Pin<Box<dyn Stream<Item = Result<AccountStateDelta, Status>> + Send + 'static>>;
+
+    #[tracing::instrument(skip_all, err)]
+    async fn watch_account_state(
+        &self,
+        request: Request<WatchAccountStateRequest>,
+    ) -> Result<Response<Self::WatchAccountStateStream>, Status> {
+        let msg = request.into_inner();
+
+        let watchers = self
+            .watchers
+            .as_ref()
+            .ok_or_else(|| Status::unimplemented("account watching is not enabled"))?;
+        let address = AccountAddressData::from_str(&msg.account_address)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let stream = WatchStream::new(watchers.subscribe(&address.to_raw_string()))
+            .filter_map(move |delta| {
+                futures::future::ready(delta.map(|delta| Ok((&address, delta).into())))
+            })
+            .boxed();
+
+        Ok(Response::new(stream))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn dns_resolve(
+        &self,
+        request: Request<DnsResolveRequest>,
+    ) -> Result<Response<DnsResolveResponse>, Status> {
+        let msg = request.into_inner();
+        // bad input is told apart from resolver failures before any request
+        let category = dns_resolve_request::Category::try_from(msg.category)
+            .map_err(|_| Status::invalid_argument(format!("unknown category: {}", msg.category)))?;
+        encode_domain(&msg.domain).map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let record = self
+            .client
+            .dns_resolve(&msg.domain, category.into())
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .ok_or_else(|| Status::not_found(format!("{} not found", msg.domain)))?;
+
+        Ok(Response::new(DnsResolveResponse {
+            domain: msg.domain,
+            record: Some(record.into()),
+        }))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn get_election_data(
+        &self,
+        _request: Request<GetElectionDataRequest>,
+    ) -> Result<Response<GetElectionDataResponse>, Status> {
+        let election = self
+            .client
+            .election_data()
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        Ok(Response::new(election.into()))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn get_stake(
+        &self,
+        request: Request<GetStakeRequest>,
+    ) -> Result<Response<GetStakeResponse>, Status> {
+        let msg = request.into_inner();
+        let wallet = MsgAddress::from_str(&msg.account_address)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        if wallet.workchain_id != -1 {
+            return Err(Status::invalid_argument(
+                "validator wallet must be in the masterchain",
+            ));
+        }
+
+        let stake = self
+            .client
+            .stake(&wallet)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        Ok(Response::new(GetStakeResponse {
+            account_address: msg.account_address,
+            stake: stake.stake.to_string(),
+            returned_stake: stake.returned_stake.to_string(),
+        }))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn get_contract_interfaces(
+        &self,
+        request: Request<GetContractInterfacesRequest>,
+    ) -> Result<Response<GetContractInterfacesResponse>, Status> {
+        let msg = request.into_inner();
+        let address = MsgAddress::from_str(&msg.account_address)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let detected = self
+            .client
+            .contract_interfaces(&address)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        Ok(Response::new(GetContractInterfacesResponse {
+            account_address: msg.account_address,
+            code_hash: detected.code_hash.map(|hash| STANDARD.encode(hash)),
+            known_code: detected.known_code,
+            interfaces: detected
+                .interfaces
+                .iter()
+                .map(|interface| interface.name().to_owned())
+                .collect(),
+        }))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn get_contract_code(
+        &self,
+        request: Request<GetContractCodeRequest>,
+    ) -> Result<Response<GetContractCodeResponse>, Status> {
+        let msg = request.into_inner();
+        let code = fetch_contract_code(&self.client, &msg.account_address).await?;
+
+        Ok(Response::new(code))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn get_jetton_wallet_address(
+        &self,
+        request: Request<GetJettonWalletAddressRequest>,
+    ) -> Result<Response<GetJettonWalletAddressResponse>, Status> {
+        let msg = request.into_inner();
+        let master = MsgAddress::from_str(&msg.jetton_master)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let owner = MsgAddress::from_str(&msg.owner)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let wallet = self
+            .client
+            .jetton_wallet_address(&master, &owner)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        Ok(Response::new(GetJettonWalletAddressResponse {
+            jetton_master: msg.jetton_master,
+            owner: msg.owner,
+            wallet_address: wallet.address.to_string(),
+            deployed: wallet.deployed,
+        }))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn get_wallet_seqno(
+        &self,
+        request: Request<GetWalletSeqnoRequest>,
+    ) -> Result<Response<GetWalletSeqnoResponse>, Status> {
+        let msg = request.into_inner();
+        let address = MsgAddress::from_str(&msg.account_address)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let wallet = match self.client.wallet_seqno(&address).await {
+            Ok(wallet) => wallet,
+            Err(TonContractError::Contract(exit_code)) => {
+                return Err(Status::failed_precondition(format!(
+                    "{} is not a wallet, exit code {}",
+                    msg.account_address, exit_code
+                )))
+            }
+            Err(e) => return Err(Status::internal(e.to_string())),
+        };
+
+        Ok(Response::new(GetWalletSeqnoResponse {
+            account_address: msg.account_address,
+            seqno: wallet.seqno,
+            subwallet_id: wallet.subwallet_id,
+            wallet_type: wallet
+                .wallet_type
+                .map(|interface| interface.name().to_owned())
+                .unwrap_or_default(),
+            deployed: wallet.deployed,
+        }))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn get_multisig_info(
+        &self,
+        request: Request<GetMultisigInfoRequest>,
+    ) -> Result<Response<MultisigInfo>, Status> {
+        let msg = request.into_inner();
+        let address = MsgAddress::from_str(&msg.account_address)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let info = match self.client.multisig(&address).await {
+            Ok(Decoded::Parsed(data)) => MultisigInfo::from(data),
+            Ok(Decoded::Raw(stack)) => MultisigInfo {
+                raw_stack: Some(raw_stack(&stack)?),
+                ..Default::default()
+            },
+            Err(e) => return Err(multisig_error(&msg.account_address, e)),
+        };
+
+        Ok(Response::new(MultisigInfo {
+            account_address: msg.account_address,
+            ..info
+        }))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn get_multisig_order(
+        &self,
+        request: Request<GetMultisigOrderRequest>,
+    ) -> Result<Response<MultisigOrder>, Status> {
+        let msg = request.into_inner();
+        let address = MsgAddress::from_str(&msg.order_address)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let order = match self.client.multisig_order(&address).await {
+            Ok(Decoded::Parsed(order)) => MultisigOrder::from(order),
+            Ok(Decoded::Raw(stack)) => MultisigOrder {
+                raw_stack: Some(raw_stack(&stack)?),
+                ..Default::default()
+            },
+            Err(e) => return Err(multisig_error(&msg.order_address, e)),
+        };
+
+        Ok(Response::new(MultisigOrder {
+            order_address: msg.order_address,
+            ..order
+        }))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn get_message_trace(
+        &self,
+        request: Request<GetMessageTraceRequest>,
+    ) -> Result<Response<MessageTrace>, Status> {
+        let msg = request.into_inner();
+        let max_depth =
+            self.param_limits
+                .apply(MESSAGE_TRACE_MAX_DEPTH, "max_depth", msg.max_depth.into())?
+                as u32;
+
+        let trace = message_trace(
+            &self.client,
+            &msg.account_address,
+            InternalTransactionId {
+                lt: msg.lt,
+                hash: msg.hash,
+            },
+            max_depth,
+        )
+        .await?;
+
+        Ok(Response::new(trace))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn get_account_summary(
+        &self,
+        request: Request<GetAccountSummaryRequest>,
+    ) -> Result<Response<AccountSummary>, Status> {
+        let msg = request.into_inner();
+        let summary = account_summary(&self.client, &msg.account_address).await?;
+
+        Ok(Response::new(summary))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn get_account_stats(
+        &self,
+        request: Request<GetAccountStatsRequest>,
+    ) -> Result<Response<AccountStats>, Status> {
+        let msg = request.into_inner();
+        let exact_limit = self.param_limits.apply(
+            ACCOUNT_STATS_EXACT_COUNT_LIMIT,
+            "exact_count_limit",
+            msg.exact_count_limit.into(),
+        )? as usize;
+        let stats = fetch_account_stats(&self.client, &msg.account_address, exact_limit).await?;
+
+        Ok(Response::new(stats))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn get_extended_account_state(
+        &self,
+        request: Request<GetExtendedAccountStateRequest>,
+    ) -> Result<Response<ExtendedAccountState>, Status> {
+        let msg = request.into_inner();
+        let state = fetch_extended_account_state(&self.client, &msg.account_address).await?;
+
+        Ok(Response::new(state))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn get_config_proposals(
+        &self,
+        _request: Request<GetConfigProposalsRequest>,
+    ) -> Result<Response<GetConfigProposalsResponse>, Status> {
+        let proposals = self
+            .client
+            .config_proposals()
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        Ok(Response::new(GetConfigProposalsResponse {
+            proposals: proposals.into_iter().map(Into::into).collect(),
+        }))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn get_config_proposal(
+        &self,
+        request: Request<GetConfigProposalRequest>,
+    ) -> Result<Response<ConfigProposal>, Status> {
+        let msg = request.into_inner();
+        let hash: [u8; 32] = hex::decode(&msg.hash)
+            .ok()
+            .and_then(|hash| hash.try_into().ok())
+            .ok_or_else(|| Status::invalid_argument("hash must be 32 hex encoded bytes"))?;
+
+        let proposal = self
+            .client
+            .config_proposal(hash)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .ok_or_else(|| Status::not_found(format!("config proposal {} not found", msg.hash)))?;
+
+        Ok(Response::new(proposal.into()))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn start_transaction_export(
+        &self,
+        request: Request<StartTransactionExportRequest>,
+    ) -> Result<Response<ExportStatus>, Status> {
+        let msg = request.into_inner();
+        let job = self
+            .export_jobs()?
+            .start(msg.account_address, msg.to_lt, msg.decode_messages)?;
+
+        Ok(no_store(Response::new(job.status())))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn get_export_status(
+        &self,
+        request: Request<GetExportStatusRequest>,
+    ) -> Result<Response<ExportStatus>, Status> {
+        let job = self.export_jobs()?.get(&request.into_inner().job_id)?;
+
+        Ok(no_store(Response::new(job.status())))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn fetch_export_chunk(
+        &self,
+        request: Request<FetchExportChunkRequest>,
+    ) -> Result<Response<FetchExportChunkResponse>, Status> {
+        let msg = request.into_inner();
+        let limit =
+            self.param_limits
+                .apply(FETCH_EXPORT_CHUNK_LIMIT, "limit", msg.limit.into())? as usize;
+        let job_id = Uuid::parse_str(&msg.job_id)
+            .map_err(|_| Status::not_found(format!("export job {} not found", msg.job_id)))?;
+        let offset = if msg.cursor.is_empty() {
+            msg.offset
+        } else {
+            let cursor = self.cursors.decode::<ExportCursor>(&msg.cursor)?;
+            if cursor.job_id != job_id {
+                return Err(Status::invalid_argument("cursor belongs to another job"));
+            }
+
+            cursor.offset
+        };
+        let job = self.export_jobs()?.get(&msg.job_id)?;
+
+        // status first, so that a completed status guarantees the chunk is the last one
+        let status = job.status();
+        let (transactions, next_offset) = job.chunk(offset as usize, limit);
+
+        Ok(no_store(Response::new(FetchExportChunkResponse {
+            transactions,
+            next_offset: next_offset as u64,
+            status: Some(status),
+            next_cursor: self.cursors.encode(&ExportCursor {
+                job_id,
+                offset: next_offset as u64,
+            }),
+        })))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn run_get_method(
+        &self,
+        request: Request<RunGetMethodRequest>,
+    ) -> Result<Response<RunGetMethodResponse>, Status> {
+        let msg = request.into_inner();
+        let address = MsgAddress::from_str(&msg.account_address)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let stack: Vec<TvmBoxedStackEntry> = if msg.stack.is_empty() {
+            vec![]
+        } else {
+            json::from_str("stack", &msg.stack, &self.json_limits)?
+        };
+
+        // pinned to the last block, so the reported blocks are the ones the method ran at
+        let (result, as_of) = if msg.include_as_of {
+            let (result, context) = self
+                .client
+                .with_last_block()
+                .await
+                .map_err(|e| Status::internal(e.to_string()))?
+                .run_get_method_with_context(msg.account_address.clone(), msg.method.clone(), stack)
+                .await
+                .map_err(|e| Status::internal(e.to_string()))?;
+
+            (result, Some(context.into()))
+        } else {
+            let result = self
+                .client
+                .run_get_method(msg.account_address.clone(), msg.method.clone(), stack)
+                .await
+                .map_err(|e| Status::internal(e.to_string()))?;
+
+            (result, None)
+        };
+
+        Ok(Response::new(
+            self.get_method_response(msg.account_address, &address, &msg.method, result, as_of)
+                .await?,
+        ))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn run_get_methods(
+        &self,
+        request: Request<RunGetMethodsRequest>,
+    ) -> Result<Response<RunGetMethodsResponse>, Status> {
+        let msg = request.into_inner();
+        if msg.calls.len() > RUN_GET_METHODS_LIMIT {
+            return Err(Status::invalid_argument(format!(
+                "calls must not exceed {}",
+                RUN_GET_METHODS_LIMIT
+            )));
+        }
+
+        // a malformed call fails alone, like a call the contract fails
+        let parsed: Vec<Result<(MsgAddress, GetMethodCall), String>> = msg
+            .calls
+            .into_iter()
+            .map(|call| {
+                let address =
+                    MsgAddress::from_str(&call.account_address).map_err(|e| e.to_string())?;
+                let stack = if call.stack.is_empty() {
+                    vec![]
+                } else {
+                    json::from_str("stack", &call.stack, &self.json_limits)
+                        .map_err(|e| e.message().to_owned())?
+                };
+
+                Ok((
+                    address,
+                    GetMethodCall {
+                        address: call.account_address,
+                        method: call.method,
+                        stack,
+                    },
+                ))
+            })
+            .collect();
+
+        let calls = parsed
+            .iter()
+            .filter_map(|call| call.as_ref().ok())
+            .map(|(_, call)| call.clone())
+            .collect();
+        let mut ran = self
+            .client
+            .run_get_methods(calls)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .into_iter();
+
+        let results = parsed
+            .into_iter()
+            .map(|call| {
+                call.and_then(|call| {
+                    let result = ran.next().expect("a result per call");
+
+                    result
+                        .map(|result| (call, result))
+                        .map_err(|e| e.to_string())
+                })
+            })
+            .collect::<Vec<_>>();
+        let results = futures::stream::iter(results)
+            .map(|result| async move {
+                let result = match result {
+                    Ok(((address, call), result)) => self
+                        .get_method_response(call.address, &address, &call.method, result, None)
+                        .await
+                        .map_err(|e| e.message().to_owned()),
+                    Err(e) => Err(e),
+                };
+
+                run_get_methods_response::Result {
+                    result: Some(match result {
+                        Ok(response) => GetMethodResult::Response(response),
+                        Err(e) => GetMethodResult::Error(e),
+                    }),
+                }
+            })
+            .buffered(CONSISTENT_SNAPSHOT_CONCURRENCY)
+            .collect::<Vec<_>>()
+            .await;
+
+        Ok(Response::new(RunGetMethodsResponse { results }))
+    }
 }
 
 impl AccountService {
+    pub fn set_cache_policy(mut self, cache_policy: CachePolicy) -> Self {
+        self.cache_policy = cache_policy;
+        self
+    }
+
+    pub fn set_response_size_limits(mut self, limits: ResponseSizeLimits) -> Self {
+        self.response_size_limits = limits;
+        self
+    }
+
+    pub fn set_param_limits(mut self, limits: ParamLimits) -> Self {
+        self.param_limits = limits;
+        self
+    }
+
+    pub fn set_json_limits(mut self, limits: JsonLimits) -> Self {
+        self.json_limits = limits;
+        self
+    }
+
+    pub fn set_account_watchers(mut self, watchers: Arc<AccountWatchers>) -> Self {
+        self.watchers = Some(watchers);
+        self
+    }
+
+    pub fn set_cursors(mut self, cursors: Cursors) -> Self {
+        self.cursors = cursors;
+        self
+    }
+
+    pub fn set_export_jobs(mut self, export_jobs: Arc<ExportJobs>) -> Self {
+        self.export_jobs = Some(export_jobs);
+        self
+    }
+
+    /// Decodes results of RunGetMethod by the schemas of the registry
+    pub fn set_abi(mut self, abi: Arc<AbiRegistry>) -> Self {
+        self.abi = Some(abi);
+        self
+    }
+
+    /// Confirms recent messages whose transactions WaitForTransaction finds
+    pub fn set_recent_messages(mut self, recent: Arc<RecentMessages>) -> Self {
+        self.recent = Some(recent);
+        self
+    }
+
+    fn confirm_recent(&self, message: &MessageRef, transaction: &RawTransaction) {
+        let Some(recent) = &self.recent else {
+            return;
+        };
+        let hash = match message {
+            MessageRef::Hash(hash) => Some(hash.as_str()),
+            MessageRef::Body { .. } => transaction.in_msg.as_ref().and_then(|msg| msg.hash()),
+        };
+
+        if let Some(hash) = hash {
+            recent.confirm(
+                hash,
+                Confirmation {
+                    lt: transaction.transaction_id.lt,
+                    transaction_hash: transaction.transaction_id.hash.clone(),
+                    utime: transaction.utime,
+                },
+            );
+        }
+    }
+
+    /// Keeps the latest account states, they are served while the circuit of reads is open
+    pub fn set_stale_states(mut self, stale_states: Arc<StaleStates>) -> Self {
+        self.stale_states = Some(stale_states);
+        self
+    }
+
+    /// Serves the latest states and transactions of hot addresses from their prefetched copies
+    pub fn set_hot_addresses(mut self, hot_addresses: Arc<HotAddresses>) -> Self {
+        self.hot_addresses = Some(hot_addresses);
+        self
+    }
+
+    /// Cached state of the latest account state request which failed as the circuit is open
+    fn stale_state(
+        &self,
+        msg: &GetAccountStateRequest,
+        error: &anyhow::Error,
+    ) -> Option<GetAccountStateResponse> {
+        if msg.criteria.is_some() || msg.include_proofs || !is_circuit_open(error) {
+            return None;
+        }
+
+        self.stale_states.as_ref()?.get(&msg.account_address)
+    }
+
+    fn export_jobs(&self) -> std::result::Result<&ExportJobs, Status> {
+        self.export_jobs
+            .as_deref()
+            .ok_or_else(|| Status::unimplemented("transaction export is not enabled"))
+    }
+
+    /// Lite server used to serve `include_proofs`, tonlibjson doesn't expose proofs. The proofs
+    /// are passed through as the lite server returns them, they aren't checked against the block
+    #[cfg(feature = "liteserver")]
+    pub fn set_proofs(mut self, proofs: Arc<dyn LiteServerTransport>) -> Self {
+        self.proofs = Some(proofs);
+        self
+    }
+
+    async fn fetch_account_state_proofs(
+        &self,
+        address: &str,
+        block_id: &TonBlockIdExt,
+    ) -> std::result::Result<AccountStateProofs, Status> {
+        let proofs = self
+            .proofs
+            .as_ref()
+            .ok_or_else(|| Status::unimplemented("account state proofs are not enabled"))?;
+
+        proofs
+            .get_account_state_proofs(address, block_id)
+            .await
+            .map(Into::into)
+            .map_err(|e| Status::internal(e.to_string()))
+    }
+
     async fn fetch_account_state(
         &self,
         msg: &GetAccountStateRequest,
@@ -218,22 +1307,190 @@ impl AccountService {
 
         Ok((block_id, cell))
     }
+
+    /// Response of a get method, its stack is decoded if an `--abi-file` schema describes the method
+    async fn get_method_response(
+        &self,
+        account_address: String,
+        address: &MsgAddress,
+        method: &str,
+        result: SmcRunResult,
+        as_of: Option<AsOf>,
+    ) -> Result<RunGetMethodResponse, Status> {
+        let schema = match &self.abi {
+            Some(abi) => {
+                let code_hash = match abi.schema(address, None, method) {
+                    None if abi.has_code_hashes() => {
+                        let state = self
+                            .client
+                            .raw_get_account_state(&account_address)
+                            .await
+                            .map_err(|e| Status::internal(e.to_string()))?;
+
+                        code_hash(&state).map_err(|e| Status::internal(e.to_string()))?
+                    }
+                    _ => None,
+                };
+
+                abi.schema(address, code_hash.as_ref(), method)
+            }
+            None => None,
+        };
+        // a mismatch is reported alongside the raw stack rather than failing the call
+        let (decoded, decode_error) = match schema.map(|schema| decode_stack(schema, &result.stack))
+        {
+            Some(Ok(decoded)) => (Some(decoded.to_string()), None),
+            Some(Err(e)) => (None, Some(e.to_string())),
+            None => (None, None),
+        };
+
+        Ok(RunGetMethodResponse {
+            account_address,
+            exit_code: result.exit_code,
+            gas_used: result.gas_used,
+            raw_stack: raw_stack(&result.stack)?,
+            decoded,
+            decode_error,
+            as_of,
+        })
+    }
+}
+
+/// Raw address of every position, and each distinct account once along with its first requested form
+#[allow(clippy::type_complexity)]
+fn dedup_addresses(
+    addresses: &[String],
+) -> (
+    Vec<std::result::Result<String, String>>,
+    Vec<(String, String)>,
+) {
+    let mut unique = Vec::new();
+    let mut seen = HashSet::new();
+    let raw_addresses = addresses
+        .iter()
+        .map(|address| {
+            let raw_address = AccountAddressData::from_str(address)
+                .map_err(|e| e.to_string())?
+                .to_raw_string();
+            if seen.insert(raw_address.clone()) {
+                unique.push((raw_address.clone(), address.clone()));
+            }
+
+            Ok(raw_address)
+        })
+        .collect();
+
+    (raw_addresses, unique)
+}
+
+fn account_state_response(
+    account_address: String,
+    state: RawFullAccountState,
+) -> Result<GetAccountStateResponse, Status> {
+    let address = AccountAddressData::from_str(&account_address)
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+    let extra_currencies = state
+        .extra_currencies()
+        .map_err(|e| Status::internal(e.to_string()))?;
+    let last_transaction_id = state
+        .last_transaction_id
+        .clone()
+        .map(|t| (&address, t).into());
+    let status = account_state_delta::Status::from(AccountStatus::from(&state));
+
+    Ok(GetAccountStateResponse {
+        account_address,
+        block_id: Some(state.block_id.clone().into()),
+        balance: state.balance.unwrap_or_default(),
+        last_transaction_id,
+        extra_currencies,
+        account_state: Some(state.into()),
+        proofs: None,
+        status: status.into(),
+        stale: false,
+        as_of_seqno: None,
+        as_of: None,
+    })
+}
+
+/// Response of an account state with the special flags of an active account, and its etag
+pub(crate) async fn state_response(
+    client: &TonClient,
+    account_address: String,
+    state: RawFullAccountState,
+) -> Result<(String, GetAccountStateResponse), Status> {
+    let etag = match &state.last_transaction_id {
+        Some(tx_id) => transaction_etag(tx_id),
+        None => block_etag(&state.block_id),
+    };
+    let block_id = state.block_id.clone();
+
+    let mut response = account_state_response(account_address, state)?;
+    if let Some(AccountState::Active(active)) = &mut response.account_state {
+        active.special = special_flags(client, &response.account_address, &block_id).await?;
+    }
+
+    Ok((etag, response))
+}
+
+/// Canonical JSON, the same stack always gives the same string
+fn raw_stack(stack: &[TvmBoxedStackEntry]) -> Result<String, Status> {
+    canonical::to_string(stack).map_err(|e| Status::internal(e.to_string()))
+}
+
+/// Get-methods fail with an exit code on contracts of other kinds
+fn multisig_error(address: &str, error: TonContractError) -> Status {
+    match error {
+        TonContractError::Contract(exit_code) => Status::failed_precondition(format!(
+            "{} is not a multisig contract, exit code {}",
+            address, exit_code
+        )),
+        e => Status::internal(e.to_string()),
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::account::AccountService;
+    use crate::account::{dedup_addresses, AccountService};
+    use crate::limits::ResponseSizeLimits;
     use crate::ton::account_service_server::AccountService as BaseAccountService;
     use crate::ton::get_account_transactions_request::bound;
     use crate::ton::{
-        get_account_transactions_request, GetAccountStateRequest, GetAccountTransactionsRequest,
+        get_account_transactions_request, GetAccountStateRequest,
+        GetAccountTransactionsPageRequest, GetAccountTransactionsRequest,
         GetShardAccountCellRequest, PartialTransactionId,
     };
     use futures::StreamExt;
+    use std::sync::Arc;
     use tonic::Request;
+    use tonlibjson_client::fake::{client_builder, wait_followed, Scenario};
     use tonlibjson_client::ton::TonClientBuilder;
     use tracing_test::traced_test;
 
+    /// Any address, fake lite servers keep transactions of every account
+    const FAKE_ADDRESS: &str = "EQCjk1hh952vWaE9bRguFkAhDAL5jj3xj9p0uPWrFBq_GEMS";
+
+    #[test]
+    fn dedup_addresses_keeps_positions() {
+        let addresses = [
+            "EQCkgtq1pKJh4Zpif_z4RR2aYmespuImTw15amEacGX-k6Zj",
+            "invalid",
+            "0:a482dab5a4a261e19a627ffcf8451d9a6267aca6e2264f0d796a611a7065fe93",
+            "EQCkgtq1pKJh4Zpif_z4RR2aYmespuImTw15amEacGX-k6Zj",
+        ]
+        .map(ToOwned::to_owned);
+
+        let (raw_addresses, unique) = dedup_addresses(&addresses);
+
+        let raw = "0:a482dab5a4a261e19a627ffcf8451d9a6267aca6e2264f0d796a611a7065fe93";
+        assert_eq!(raw_addresses[0].as_deref(), Ok(raw));
+        assert!(raw_addresses[1].is_err());
+        assert_eq!(raw_addresses[2].as_deref(), Ok(raw));
+        assert_eq!(raw_addresses[3].as_deref(), Ok(raw));
+        assert_eq!(unique, [(raw.to_owned(), addresses[0].clone())]);
+    }
+
     #[tokio::test]
     #[traced_test]
     #[ignore]
@@ -260,6 +1517,9 @@ mod tests {
                     hash: "JatZ7mIBIfBpCNHHHQkpIc1+72RrzSiM8xvqlqRAbmc=".to_string(),
                 })),
             }),
+            decode_messages: false,
+            start_utime: None,
+            end_utime: None,
         });
 
         let resp = svc.get_account_transactions(req).await.unwrap();
@@ -281,6 +1541,8 @@ mod tests {
         let req = Request::new(GetAccountStateRequest {
             account_address: "EQCaatdRleXHdMCc3ONQsZklcF32jyCiJhHyN3YEKxPXMhsF".to_string(),
             criteria: None,
+            include_proofs: false,
+            include_as_of: false,
         });
 
         let resp = svc.get_account_state(req).await;
@@ -308,4 +1570,78 @@ mod tests {
         tracing::info!(resp = ?resp);
         assert!(resp.is_ok())
     }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn get_account_transactions_page_cut_by_response_size() {
+        let scenario = Arc::new(Scenario::new(900_000, 1_000_000));
+        for _ in 0..3 {
+            scenario.add_transaction(FAKE_ADDRESS, 999_000).unwrap();
+        }
+        let mut client = client_builder(&scenario, 1).build().unwrap();
+        client.ready().await.unwrap();
+        wait_followed(&client, &scenario).await.unwrap();
+        let svc =
+            AccountService::new(client).set_response_size_limits(ResponseSizeLimits::new(1, []));
+        let req = Request::new(GetAccountTransactionsPageRequest {
+            account_address: FAKE_ADDRESS.to_string(),
+            from: None,
+            limit: 10,
+            decode_messages: false,
+            cursor: String::new(),
+        });
+
+        let resp = svc
+            .get_account_transactions_page(req)
+            .await
+            .unwrap()
+            .into_inner();
+
+        assert_eq!(resp.transactions.len(), 1);
+        assert!(resp.incomplete);
+        assert!(resp.next.is_some());
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn get_account_state_proofs_without_lite_server() {
+        let scenario = Arc::new(Scenario::new(900_000, 1_000_000));
+        scenario.set_balance(FAKE_ADDRESS, 42).unwrap();
+        let mut client = client_builder(&scenario, 1).build().unwrap();
+        client.ready().await.unwrap();
+        wait_followed(&client, &scenario).await.unwrap();
+        let req = Request::new(GetAccountStateRequest {
+            account_address: FAKE_ADDRESS.to_string(),
+            criteria: None,
+            include_proofs: true,
+            include_as_of: false,
+        });
+
+        let status = AccountService::new(client)
+            .get_account_state(req)
+            .await
+            .unwrap_err();
+
+        assert_eq!(status.code(), tonic::Code::Unimplemented);
+    }
+
+    #[tokio::test]
+    async fn get_account_transactions_page_rejects_malformed_address() {
+        let scenario = Arc::new(Scenario::new(900_000, 1_000_000));
+        let client = client_builder(&scenario, 1).build().unwrap();
+        let req = Request::new(GetAccountTransactionsPageRequest {
+            account_address: "invalid".to_string(),
+            from: None,
+            limit: 10,
+            decode_messages: false,
+            cursor: String::new(),
+        });
+
+        let status = AccountService::new(client)
+            .get_account_transactions_page(req)
+            .await
+            .unwrap_err();
+
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    }
 }
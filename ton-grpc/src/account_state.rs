@@ -0,0 +1,309 @@
+use crate::ton::extended_account_state::AccountState as ExtendedState;
+use crate::ton::wallet_account_state::Type as WalletType;
+use crate::ton::{
+    account_state_delta, ActiveAccountState, ExtendedAccountState, FrozenAccountState, TickTock,
+    UninitializedAccountState, WalletAccountState,
+};
+use std::str::FromStr;
+use ton_contract::wallet::WalletContract;
+use ton_contract::TonContract;
+use toner::ton::MsgAddress;
+use tonic::Status;
+use tonlibjson_client::account_cell::{parse_shard_account, Special};
+use tonlibjson_client::address::AccountAddressData;
+use tonlibjson_client::block::{
+    FullAccountState, ParsedAccountState, RawFullAccountState, TonBlockIdExt,
+};
+use tonlibjson_client::ton::TonClient;
+
+impl From<Special> for TickTock {
+    fn from(value: Special) -> Self {
+        Self {
+            tick: value.tick,
+            tock: value.tock,
+        }
+    }
+}
+
+/// Tick-tock flags of an account at `block_id`, only masterchain accounts may be special
+pub async fn special_flags(
+    client: &TonClient,
+    address: &str,
+    block_id: &TonBlockIdExt,
+) -> Result<Option<TickTock>, Status> {
+    let account = AccountAddressData::from_str(address)
+        .map_err(|e| Status::invalid_argument(e.to_string()))?;
+    if account.chain_id != -1 {
+        return Ok(None);
+    }
+
+    let cell = client
+        .get_shard_account_cell_on_block(address, block_id.clone())
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+    let state = parse_shard_account(&cell.bytes).map_err(|e| Status::internal(e.to_string()))?;
+
+    Ok(state.and_then(|state| state.special).map(Into::into))
+}
+
+/// `code`, `data` and `frozen_hash` of a contract tonlib doesn't parse
+fn raw_state(code: String, data: String, frozen_hash: String) -> ExtendedState {
+    if !code.is_empty() {
+        ExtendedState::Raw(ActiveAccountState {
+            code,
+            data,
+            special: None,
+        })
+    } else if !frozen_hash.is_empty() {
+        ExtendedState::Frozen(FrozenAccountState { frozen_hash })
+    } else {
+        ExtendedState::Uninitialized(UninitializedAccountState {})
+    }
+}
+
+fn wallet(r#type: WalletType, wallet_id: i64, seqno: Option<i32>) -> ExtendedState {
+    ExtendedState::Wallet(WalletAccountState {
+        r#type: r#type.into(),
+        wallet_id,
+        seqno,
+        plugins: Vec::new(),
+    })
+}
+
+/// Types the loose union of `getAccountState`. Contracts tonlib returns no code and data of,
+/// DNS, payment channels and account states unknown to it, fall back to `raw` of the same block.
+pub fn extended_account_state(
+    account_address: &str,
+    state: FullAccountState,
+    raw: Option<RawFullAccountState>,
+) -> Result<ExtendedAccountState, Status> {
+    let address = AccountAddressData::from_str(account_address)
+        .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+    let account_state = match state.account_state {
+        ParsedAccountState::WalletV3(s) => wallet(WalletType::V3, s.wallet_id, Some(s.seqno)),
+        ParsedAccountState::WalletV4(s) => wallet(WalletType::V4, s.wallet_id, Some(s.seqno)),
+        ParsedAccountState::WalletHighloadV1(s) => {
+            wallet(WalletType::HighloadV1, s.wallet_id, Some(s.seqno))
+        }
+        ParsedAccountState::WalletHighloadV2(s) => {
+            wallet(WalletType::HighloadV2, s.wallet_id, None)
+        }
+        ParsedAccountState::RestrictedWallet(s) => {
+            wallet(WalletType::Restricted, s.wallet_id, Some(s.seqno))
+        }
+        ParsedAccountState::RawAccount(s) => raw_state(s.code, s.data, s.frozen_hash),
+        ParsedAccountState::Uninitialized(s) => {
+            raw_state(String::new(), String::new(), s.frozen_hash)
+        }
+        ParsedAccountState::Dns(_)
+        | ParsedAccountState::PaymentChannel(_)
+        | ParsedAccountState::Raw(_) => {
+            let raw =
+                raw.ok_or_else(|| Status::internal("raw state of the contract is missing"))?;
+
+            raw_state(raw.code, raw.data, raw.frozen_hash)
+        }
+    };
+
+    let status = match account_state {
+        ExtendedState::Wallet(_) | ExtendedState::Raw(_) => account_state_delta::Status::Active,
+        ExtendedState::Frozen(_) => account_state_delta::Status::Frozen,
+        ExtendedState::Uninitialized(_) => account_state_delta::Status::Uninit,
+    };
+    let last_transaction_id =
+        (state.last_transaction_id.lt != 0).then(|| (&address, state.last_transaction_id).into());
+
+    Ok(ExtendedAccountState {
+        account_address: account_address.to_owned(),
+        block_id: Some(state.block_id.into()),
+        balance: state.balance,
+        last_transaction_id,
+        status: status.into(),
+        account_state: Some(account_state),
+    })
+}
+
+/// [`extended_account_state`] of the latest block, with tick-tock flags of special accounts
+/// and plugins of v4 wallets
+pub async fn fetch_extended_account_state(
+    client: &TonClient,
+    address: &str,
+) -> Result<ExtendedAccountState, Status> {
+    let state = client
+        .get_account_state(address)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+    let block_id = state.block_id.clone();
+
+    let raw = match state.account_state {
+        ParsedAccountState::Dns(_)
+        | ParsedAccountState::PaymentChannel(_)
+        | ParsedAccountState::Raw(_) => Some(
+            client
+                .raw_get_account_state_on_block(address, block_id.clone())
+                .await
+                .map_err(|e| Status::internal(e.to_string()))?,
+        ),
+        _ => None,
+    };
+
+    let mut extended = extended_account_state(address, state, raw)?;
+    match &mut extended.account_state {
+        Some(ExtendedState::Raw(active)) => {
+            active.special = special_flags(client, address, &block_id).await?;
+        }
+        Some(ExtendedState::Wallet(wallet)) if wallet.r#type() == WalletType::V4 => {
+            wallet.plugins = plugins(client, address).await?;
+        }
+        _ => {}
+    }
+
+    Ok(extended)
+}
+
+async fn plugins(client: &TonClient, address: &str) -> Result<Vec<String>, Status> {
+    let address =
+        MsgAddress::from_str(address).map_err(|e| Status::invalid_argument(e.to_string()))?;
+    let plugins = TonContract::new(client.clone(), address)
+        .plugin_list()
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+    Ok(plugins.iter().map(ToString::to_string).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ELECTOR: &str = "-1:3333333333333333333333333333333333333333333333333333333333333333";
+
+    fn fixture(name: &str) -> FullAccountState {
+        let path = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/accounts")
+            .join(name);
+
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn wallet_v4_is_parsed() {
+        let address = "EQBGXZ9ddZeWypx8EkJieHJX75ct0bpkmu0Y4YoYr3NM0Z9e";
+        let state = extended_account_state(address, fixture("wallet_v4.json"), None).unwrap();
+
+        assert_eq!(state.status(), account_state_delta::Status::Active);
+        assert_eq!(state.balance, 1_250_000_000);
+        assert_eq!(
+            state.account_state,
+            Some(ExtendedState::Wallet(WalletAccountState {
+                r#type: WalletType::V4.into(),
+                wallet_id: 698983191,
+                seqno: Some(57),
+                plugins: vec![],
+            }))
+        );
+        assert_eq!(state.last_transaction_id.unwrap().lt, 47_000_000_000_003);
+    }
+
+    #[test]
+    fn wallets_are_parsed() {
+        let address = "0:83dfd552e63729b472fcbcc8c45ebcc6691702558b68ec7527e1ba403a0f31a8";
+        let cases = [
+            ("wallet_v3.json", WalletType::V3, 698983191, Some(12)),
+            (
+                "wallet_highload_v1.json",
+                WalletType::HighloadV1,
+                0,
+                Some(3),
+            ),
+            (
+                "wallet_highload_v2.json",
+                WalletType::HighloadV2,
+                698983191,
+                None,
+            ),
+        ];
+
+        for (name, r#type, wallet_id, seqno) in cases {
+            let state = extended_account_state(address, fixture(name), None).unwrap();
+
+            assert_eq!(
+                state.status(),
+                account_state_delta::Status::Active,
+                "{}",
+                name
+            );
+            assert_eq!(
+                state.account_state,
+                Some(ExtendedState::Wallet(WalletAccountState {
+                    r#type: r#type.into(),
+                    wallet_id,
+                    seqno,
+                    plugins: vec![],
+                })),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_account_state_needs_raw_state() {
+        let address = "0:894e485dd56db0ced915369fddb0a069bf6488a145a8232cc8ee642b6d2ae3fc";
+        let state = fixture("wallet_v5.json");
+        assert!(matches!(state.account_state, ParsedAccountState::Raw(_)));
+
+        assert!(extended_account_state(address, state, None).is_err());
+    }
+
+    #[test]
+    fn frozen_account_has_frozen_hash() {
+        let address = "0:4e3d1eb4a6c55a46ad5c8d36a9b1cf5a2d4fd2b0ad6c7e2bf3d0c3a2b1e0f9d8";
+        let state = extended_account_state(address, fixture("frozen.json"), None).unwrap();
+
+        assert_eq!(state.status(), account_state_delta::Status::Frozen);
+        assert_eq!(
+            state.account_state,
+            Some(ExtendedState::Frozen(FrozenAccountState {
+                frozen_hash: "qN4s3hYGNaxQ8fMXPDRDrA0xp2GfKbAOS2sKVW3qWbY=".to_owned(),
+            }))
+        );
+    }
+
+    #[test]
+    fn elector_falls_back_to_raw() {
+        let state = extended_account_state(ELECTOR, fixture("elector.json"), None).unwrap();
+
+        assert_eq!(state.status(), account_state_delta::Status::Active);
+        let Some(ExtendedState::Raw(active)) = state.account_state else {
+            panic!("{:?}", state.account_state);
+        };
+        assert!(!active.code.is_empty());
+        assert!(!active.data.is_empty());
+    }
+
+    #[test]
+    fn dns_needs_raw_state() {
+        let mut state = fixture("wallet_v4.json");
+        state.account_state =
+            serde_json::from_str(r#"{"@type": "dns.accountState", "wallet_id": "0"}"#).unwrap();
+
+        assert!(extended_account_state(ELECTOR, state.clone(), None).is_err());
+
+        let raw: RawFullAccountState = serde_json::from_value(serde_json::json!({
+            "@type": "raw.fullAccountState",
+            "balance": "1",
+            "code": "te6cckEBAQEAAgAAAEysuc0=",
+            "data": "te6cckEBAQEAAgAAAEysuc0=",
+            "last_transaction_id": {"@type": "internal.transactionId", "lt": "0", "hash": "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA="},
+            "block_id": {"@type": "ton.blockIdExt", "workchain": -1, "shard": "-9223372036854775808", "seqno": 1, "root_hash": "", "file_hash": ""},
+            "frozen_hash": "",
+            "sync_utime": "0",
+        }))
+        .unwrap();
+        let state = extended_account_state(ELECTOR, state, Some(raw)).unwrap();
+
+        assert!(matches!(state.account_state, Some(ExtendedState::Raw(_))));
+    }
+}
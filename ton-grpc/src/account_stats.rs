@@ -0,0 +1,434 @@
+use crate::ton::{account_stats, AccountStats, TransactionId};
+use std::future::Future;
+use std::str::FromStr;
+use tonic::Status;
+use tonlibjson_client::address::AccountAddressData;
+use tonlibjson_client::block::{InternalTransactionId, RawTransactions};
+use tonlibjson_client::ton::TonClient;
+
+/// Pages walked back from the first transaction found by the search, an account rarely has
+/// more transactions within a single masterchain block
+pub const FIRST_TX_MAX_PAGES: usize = 4;
+
+/// A transaction at either end of the history
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryEnd {
+    pub id: InternalTransactionId,
+    pub utime: i64,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct HistoryStats {
+    pub first: Option<HistoryEnd>,
+    pub last: Option<HistoryEnd>,
+    pub transactions: u64,
+    /// the count is extrapolated from the density of the walked parts of history
+    pub approximate: bool,
+}
+
+/// Transactions walked back from a transaction, the newest first
+#[derive(Debug, Default)]
+struct Walk {
+    txs: Vec<HistoryEnd>,
+    /// the next transaction to fetch, none once the start of history is reached
+    next: Option<InternalTransactionId>,
+}
+
+async fn walk<F, Fut>(
+    from: InternalTransactionId,
+    max_pages: usize,
+    page: &mut F,
+) -> anyhow::Result<Walk>
+where
+    F: FnMut(InternalTransactionId) -> Fut,
+    Fut: Future<Output = anyhow::Result<RawTransactions>>,
+{
+    let mut walk = Walk {
+        txs: Vec::new(),
+        next: Some(from),
+    };
+
+    for _ in 0..max_pages {
+        let Some(next) = walk.next.take() else {
+            break;
+        };
+        let txs = page(next).await?;
+        let empty = txs.transactions.is_empty();
+
+        walk.txs
+            .extend(txs.transactions.into_iter().map(|tx| HistoryEnd {
+                id: tx.transaction_id,
+                utime: tx.utime,
+            }));
+        walk.next = txs.previous_transaction_id.filter(|_| !empty);
+    }
+
+    Ok(walk)
+}
+
+/// Earliest masterchain block in `1..=last_seqno` at which the account has a transaction,
+/// `last_tx_at` tells the last transaction of the account as of a block. Returns the
+/// transaction of that block, `last` if the account has none in earlier ones.
+async fn search_first_block<F, Fut>(
+    last: InternalTransactionId,
+    last_seqno: i32,
+    mut last_tx_at: F,
+) -> anyhow::Result<InternalTransactionId>
+where
+    F: FnMut(i32) -> Fut,
+    Fut: Future<Output = anyhow::Result<Option<InternalTransactionId>>>,
+{
+    let (mut lo, mut hi, mut found) = (1, last_seqno.max(1), last);
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        match last_tx_at(mid).await? {
+            Some(tx) => (hi, found) = (mid, tx),
+            None => lo = mid + 1,
+        }
+    }
+
+    Ok(found)
+}
+
+/// Count of transactions between `first` and `last` from the mean lt gap of the walked
+/// parts, `walked` of them are known
+fn extrapolate(walked: &[&[HistoryEnd]], first: i64, last: i64) -> u64 {
+    let (span, gaps) =
+        walked
+            .iter()
+            .filter(|txs| txs.len() > 1)
+            .fold((0, 0), |(span, gaps), txs| {
+                let span_of = txs[0].id.lt - txs[txs.len() - 1].id.lt;
+
+                (span + span_of, gaps + txs.len() as i64 - 1)
+            });
+    let known = walked.iter().map(|txs| txs.len() as u64).sum::<u64>();
+    if span <= 0 {
+        return known + 1;
+    }
+
+    let estimate = 1 + ((last - first) as f64 * gaps as f64 / span as f64).round() as u64;
+
+    // parts of history weren't walked, so there is at least one more transaction
+    estimate.max(known + 1)
+}
+
+/// First and last transactions of an account and its count of transactions, found without
+/// walking history longer than `exact_limit`, see [`history_stats`]
+pub async fn fetch_account_stats(
+    client: &TonClient,
+    address: &str,
+    exact_limit: usize,
+) -> Result<AccountStats, Status> {
+    let account = AccountAddressData::from_str(address)
+        .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+    let (state, last_block) = futures::try_join!(client.raw_get_account_state(address), async {
+        Ok(client.get_masterchain_info().await?.last)
+    })
+    .map_err(|e| Status::internal(e.to_string()))?;
+
+    let stats = history_stats(
+        state.last_transaction_id,
+        last_block.seqno,
+        exact_limit,
+        |next| async move { client.raw_get_transactions(address, &next).await },
+        |seqno| {
+            let last_block = &last_block;
+
+            async move {
+                let block = client
+                    .look_up_block_by_seqno(last_block.workchain, last_block.shard, seqno)
+                    .await?;
+                let state = client
+                    .raw_get_account_state_on_block(address, block)
+                    .await?;
+
+                Ok(state.last_transaction_id)
+            }
+        },
+    )
+    .await
+    .map_err(|e| Status::internal(e.to_string()))?;
+
+    let count = match stats.approximate {
+        true => account_stats::Count::ApproximateCount(stats.transactions),
+        false => account_stats::Count::TransactionCount(stats.transactions),
+    };
+
+    Ok(AccountStats {
+        account_address: address.to_owned(),
+        first_utime: stats.first.as_ref().map(|end| end.utime),
+        first_transaction_id: stats
+            .first
+            .map(|end| TransactionId::from((&account, end.id))),
+        last_utime: stats.last.as_ref().map(|end| end.utime),
+        last_transaction_id: stats
+            .last
+            .map(|end| TransactionId::from((&account, end.id))),
+        count: Some(count),
+    })
+}
+
+/// First and last transactions of an account and its count of transactions.
+///
+/// History is walked back from `last` up to `exact_limit` transactions, the count is exact
+/// if it starts within them. Otherwise the earliest masterchain block up to `last_seqno`
+/// at which the account has a transaction is binary searched with `last_tx_at` and history
+/// is walked back from its transaction to the start. The count is then extrapolated from the
+/// mean lt gap of both walks, it is exact for evenly spread transactions and is off
+/// by the ratio of the mean gap in the unwalked part to the walked one otherwise.
+///
+/// Requests: `exact_limit / page size` pages and, for longer history, up to
+/// `log2(last_seqno)` searched blocks and [`FIRST_TX_MAX_PAGES`] more pages.
+pub async fn history_stats<P, PFut, B, BFut>(
+    last: Option<InternalTransactionId>,
+    last_seqno: i32,
+    exact_limit: usize,
+    mut page: P,
+    last_tx_at: B,
+) -> anyhow::Result<HistoryStats>
+where
+    P: FnMut(InternalTransactionId) -> PFut,
+    PFut: Future<Output = anyhow::Result<RawTransactions>>,
+    B: FnMut(i32) -> BFut,
+    BFut: Future<Output = anyhow::Result<Option<InternalTransactionId>>>,
+{
+    let Some(last) = last else {
+        return Ok(HistoryStats::default());
+    };
+
+    let mut recent = Walk {
+        next: Some(last.clone()),
+        ..Default::default()
+    };
+    while recent.txs.len() < exact_limit {
+        let Some(next) = recent.next.take() else {
+            break;
+        };
+        let walked = walk(next, 1, &mut page).await?;
+        recent.txs.extend(walked.txs);
+        recent.next = walked.next;
+    }
+
+    let Some(next) = recent.next else {
+        return Ok(HistoryStats {
+            first: recent.txs.last().cloned(),
+            last: recent.txs.first().cloned(),
+            transactions: recent.txs.len() as u64,
+            approximate: false,
+        });
+    };
+
+    let found = search_first_block(last, last_seqno, last_tx_at).await?;
+    // the block is within the walked history, the walk just goes on
+    let joined = found.lt >= next.lt;
+    let early = walk(
+        if joined { next } else { found },
+        FIRST_TX_MAX_PAGES,
+        &mut page,
+    )
+    .await?;
+
+    let first = early.txs.last().or(recent.txs.last()).cloned();
+    let last = recent.txs.first().cloned();
+    let known = (recent.txs.len() + early.txs.len()) as u64;
+    if joined && early.next.is_none() {
+        return Ok(HistoryStats {
+            first,
+            last,
+            transactions: known,
+            approximate: false,
+        });
+    }
+
+    let transactions = match (&first, &last) {
+        (Some(first), Some(last)) => {
+            extrapolate(&[&recent.txs, &early.txs], first.id.lt, last.id.lt)
+        }
+        _ => known,
+    };
+
+    Ok(HistoryStats {
+        first,
+        last,
+        transactions,
+        approximate: true,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const PAGE_SIZE: usize = 16;
+    /// lt range of a masterchain block
+    const BLOCK_LT: i64 = 1_000;
+
+    /// Transactions at the given lts, ascending, with utime of lt / 10
+    struct History {
+        lts: Vec<i64>,
+        pages: AtomicUsize,
+        blocks: AtomicUsize,
+    }
+
+    impl History {
+        /// `count` transactions `gap(i)` apart, from lt 10 * BLOCK_LT on
+        fn with_gaps(count: usize, gap: impl Fn(usize) -> i64) -> Self {
+            let lts = (0..count)
+                .scan(10 * BLOCK_LT, |lt, i| {
+                    *lt += gap(i);
+
+                    Some(*lt)
+                })
+                .collect();
+
+            Self {
+                lts,
+                pages: AtomicUsize::new(0),
+                blocks: AtomicUsize::new(0),
+            }
+        }
+
+        fn id(&self, i: usize) -> InternalTransactionId {
+            InternalTransactionId {
+                lt: self.lts[i],
+                hash: format!("hash-{}", self.lts[i]),
+            }
+        }
+
+        fn last_seqno(&self) -> i32 {
+            (self.lts[self.lts.len() - 1] / BLOCK_LT + 1) as i32
+        }
+
+        fn page(&self, from: &InternalTransactionId) -> RawTransactions {
+            self.pages.fetch_add(1, Ordering::Relaxed);
+            let end = self.lts.binary_search(&from.lt).unwrap();
+            let start = (end + 1).saturating_sub(PAGE_SIZE);
+
+            serde_json::from_value(json!({
+                "@type": "raw.transactions",
+                "transactions": (start..=end).rev().map(|i| json!({
+                    "@type": "raw.transaction",
+                    "address": {"@type": "accountAddress", "account_address": "0:0000000000000000000000000000000000000000000000000000000000000001"},
+                    "utime": (self.lts[i] / 10).to_string(),
+                    "data": "",
+                    "transaction_id": self.id(i),
+                    "fee": "0",
+                    "storage_fee": "0",
+                    "other_fee": "0",
+                    "in_msg": null,
+                    "out_msgs": [],
+                })).collect::<Vec<_>>(),
+                "previous_transaction_id": match start {
+                    0 => InternalTransactionId::default(),
+                    start => self.id(start - 1),
+                },
+            }))
+            .unwrap()
+        }
+
+        fn last_tx_at(&self, seqno: i32) -> Option<InternalTransactionId> {
+            self.blocks.fetch_add(1, Ordering::Relaxed);
+            let end = self.lts.partition_point(|lt| *lt < seqno as i64 * BLOCK_LT);
+
+            end.checked_sub(1).map(|i| self.id(i))
+        }
+
+        async fn stats(&self, exact_limit: usize) -> HistoryStats {
+            history_stats(
+                Some(self.id(self.lts.len() - 1)),
+                self.last_seqno(),
+                exact_limit,
+                |next| async move { Ok(self.page(&next)) },
+                |seqno| async move { Ok(self.last_tx_at(seqno)) },
+            )
+            .await
+            .unwrap()
+        }
+    }
+
+    fn end(history: &History, i: usize) -> Option<HistoryEnd> {
+        Some(HistoryEnd {
+            id: history.id(i),
+            utime: history.lts[i] / 10,
+        })
+    }
+
+    #[tokio::test]
+    async fn short_history_is_exact() {
+        let history = History::with_gaps(40, |_| 7);
+
+        let stats = history.stats(64).await;
+
+        assert_eq!(
+            stats,
+            HistoryStats {
+                first: end(&history, 0),
+                last: end(&history, 39),
+                transactions: 40,
+                approximate: false,
+            }
+        );
+        assert_eq!(history.pages.load(Ordering::Relaxed), 3);
+        assert_eq!(history.blocks.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn long_even_history_is_extrapolated() {
+        let history = History::with_gaps(100_000, |_| 100);
+
+        let stats = history.stats(64).await;
+
+        assert_eq!(stats.first, end(&history, 0));
+        assert_eq!(stats.last, end(&history, 99_999));
+        assert_eq!(stats.transactions, 100_000);
+        assert!(stats.approximate);
+        // 4 pages of recent history, the search and a page back from the first block found
+        assert_eq!(history.pages.load(Ordering::Relaxed), 5);
+        let max_blocks = (history.last_seqno() as f64).log2().ceil() as usize;
+        assert!(history.blocks.load(Ordering::Relaxed) <= max_blocks);
+    }
+
+    #[tokio::test]
+    async fn uneven_history_is_within_bounds() {
+        // gaps of 20..=80 in a repeating pattern, the mean gap of both walks is close to 50
+        let history = History::with_gaps(50_000, |i| 20 + (i as i64 * 37) % 61);
+
+        let stats = history.stats(256).await;
+
+        assert_eq!(stats.first, end(&history, 0));
+        let error = (stats.transactions as f64 - 50_000.0).abs() / 50_000.0;
+        assert!(error < 0.05, "{} transactions", stats.transactions);
+        assert!(stats.approximate);
+    }
+
+    #[tokio::test]
+    async fn history_slightly_over_limit_is_exact() {
+        // the first block found is within the walked history, so the walk goes on
+        let history = History::with_gaps(70, |_| 10);
+
+        let stats = history.stats(64).await;
+
+        assert_eq!(stats.first, end(&history, 0));
+        assert_eq!(stats.transactions, 70);
+        assert!(!stats.approximate);
+    }
+
+    #[tokio::test]
+    async fn account_without_transactions() {
+        let stats = history_stats(
+            None,
+            10,
+            64,
+            |_| async { unreachable!() },
+            |_| async { unreachable!() },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(stats, HistoryStats::default());
+    }
+}
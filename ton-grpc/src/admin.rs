@@ -0,0 +1,669 @@
+use crate::chain_stats::ChainStats;
+use crate::export::ExportJobs;
+use crate::export_file::{seqnos, Source};
+use crate::journal::SendJournal;
+use crate::maintenance::Maintenance;
+use crate::memory::MemoryBudget;
+use crate::quota::{reset_times, ApiKey, Quotas};
+use crate::rejected::{Rejected, RejectedMessages};
+use crate::ton::admin_service_server::AdminService as BaseAdminService;
+use crate::ton::lite_server_status::Health;
+use crate::ton::start_file_export_request::Source as RequestSource;
+use crate::ton::{
+    ChainStats as ChainStatsResponse, ChainStatsRequest, CompareAccountStateRequest,
+    CompareAccountStateResponse, ComponentMemoryUsage, ExportStatus, GetLiteServersRequest,
+    GetLiteServersResponse, GetMaintenanceModeRequest, GetRejectedMessageRequest, KeyUsage,
+    KeyUsageRequest, ListPendingMessagesRequest, ListPendingMessagesResponse,
+    ListRejectedMessagesRequest, ListRejectedMessagesResponse, LiteServerAccountState,
+    LiteServerStatus, MaintenanceMode, MemoryUsage, MemoryUsageRequest, PendingMessage,
+    PingLiteServerRequest, PingLiteServerResponse, RefreshConfigRequest, RefreshConfigResponse,
+    RejectedMessage, ReplayMessageRequest, SendResponse, SetMaintenanceModeRequest,
+    StartFileExportRequest, TrafficStat, TrafficStats, TrafficStatsRequest,
+};
+use derive_new::new;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tonic::{async_trait, Request, Response, Status};
+use tonlibjson_client::address::AccountAddressData;
+use tonlibjson_client::pool;
+use tonlibjson_client::ton::TonClient;
+use tonlibjson_client::traffic::TrafficKey;
+
+/// Metadata key of the admin key, required by [`admin_key_interceptor`]
+pub const ADMIN_KEY_HEADER: &str = "x-admin-key";
+
+/// Rejects requests without the admin key, lets every request through if there is no key
+pub fn admin_key_interceptor(
+    admin_key: Option<String>,
+) -> impl Fn(Request<()>) -> Result<Request<()>, Status> + Clone {
+    move |request| {
+        let Some(admin_key) = &admin_key else {
+            return Ok(request);
+        };
+
+        match request.metadata().get(ADMIN_KEY_HEADER) {
+            Some(key) if key.as_bytes() == admin_key.as_bytes() => Ok(request),
+            Some(_) => Err(Status::permission_denied("invalid admin key")),
+            None => Err(Status::unauthenticated(format!(
+                "{} is required",
+                ADMIN_KEY_HEADER
+            ))),
+        }
+    }
+}
+
+#[derive(new)]
+pub struct AdminService {
+    quotas: Option<Arc<Quotas>>,
+    default_network: String,
+    clients: HashMap<String, TonClient>,
+    #[new(default)]
+    journals: HashMap<String, Arc<SendJournal>>,
+    #[new(default)]
+    rejected: HashMap<String, Arc<RejectedMessages>>,
+    #[new(default)]
+    export_jobs: HashMap<String, Arc<ExportJobs>>,
+    #[new(default)]
+    memory: Option<Arc<MemoryBudget>>,
+    #[new(default)]
+    maintenance: Option<Arc<Maintenance>>,
+    #[new(default)]
+    chain_stats: HashMap<String, Arc<ChainStats>>,
+}
+
+impl AdminService {
+    /// Send journals by network
+    pub fn set_journals(mut self, journals: HashMap<String, Arc<SendJournal>>) -> Self {
+        self.journals = journals;
+        self
+    }
+
+    /// Rejected messages by network
+    pub fn set_rejected_messages(
+        mut self,
+        rejected: HashMap<String, Arc<RejectedMessages>>,
+    ) -> Self {
+        self.rejected = rejected;
+        self
+    }
+
+    /// Export jobs by network, shared with AccountService which reports their status
+    pub fn set_export_jobs(mut self, export_jobs: HashMap<String, Arc<ExportJobs>>) -> Self {
+        self.export_jobs = export_jobs;
+        self
+    }
+
+    /// Memory budget of caches and buffers, none if they are unbounded
+    pub fn set_memory_budget(mut self, memory: Option<Arc<MemoryBudget>>) -> Self {
+        self.memory = memory;
+        self
+    }
+
+    pub fn set_maintenance(mut self, maintenance: Arc<Maintenance>) -> Self {
+        self.maintenance = Some(maintenance);
+        self
+    }
+
+    /// Chain stats by network
+    pub fn set_chain_stats(mut self, chain_stats: HashMap<String, Arc<ChainStats>>) -> Self {
+        self.chain_stats = chain_stats;
+        self
+    }
+
+    fn chain_stats(&self, network: &str) -> Result<&ChainStats, Status> {
+        let network = if network.is_empty() {
+            &self.default_network
+        } else {
+            network
+        };
+
+        self.chain_stats
+            .get(network)
+            .map(Arc::as_ref)
+            .ok_or_else(|| Status::unimplemented("chain stats are not enabled"))
+    }
+
+    fn maintenance(&self) -> Result<&Maintenance, Status> {
+        self.maintenance
+            .as_deref()
+            .ok_or_else(|| Status::unimplemented("maintenance mode is not enabled"))
+    }
+
+    fn export_jobs(&self, network: &str) -> Result<&ExportJobs, Status> {
+        let network = if network.is_empty() {
+            &self.default_network
+        } else {
+            network
+        };
+
+        self.export_jobs
+            .get(network)
+            .map(Arc::as_ref)
+            .ok_or_else(|| Status::unimplemented("transaction export is not enabled"))
+    }
+
+    fn rejected(&self, network: &str) -> Result<&RejectedMessages, Status> {
+        let network = if network.is_empty() {
+            &self.default_network
+        } else {
+            network
+        };
+
+        self.rejected
+            .get(network)
+            .map(Arc::as_ref)
+            .ok_or_else(|| Status::unimplemented("rejected messages are not kept"))
+    }
+
+    fn journal(&self, network: &str) -> Result<&Arc<SendJournal>, Status> {
+        let network = if network.is_empty() {
+            &self.default_network
+        } else {
+            network
+        };
+
+        self.journals
+            .get(network)
+            .ok_or_else(|| Status::unimplemented("send journal is not enabled"))
+    }
+
+    fn quotas(&self) -> Result<&Quotas, Status> {
+        self.quotas
+            .as_deref()
+            .ok_or_else(|| Status::failed_precondition("api keys are not required"))
+    }
+
+    fn key(&self, request: Request<KeyUsageRequest>) -> Result<&ApiKey, Status> {
+        let name = request.into_inner().name;
+
+        self.quotas()?
+            .key_by_name(&name)
+            .ok_or_else(|| Status::not_found(format!("api key {} not found", name)))
+    }
+
+    fn key_usage(&self, key: &ApiKey) -> Result<KeyUsage, Status> {
+        let quotas = self.quotas()?;
+        let now = SystemTime::now();
+        let usage = quotas.usage(key, now);
+        let (daily_reset_at, monthly_reset_at) = reset_times(now);
+
+        Ok(KeyUsage {
+            name: key.name.clone(),
+            daily: usage.daily,
+            daily_quota: key.daily,
+            daily_reset_at,
+            monthly: usage.monthly,
+            monthly_quota: key.monthly,
+            monthly_reset_at,
+        })
+    }
+
+    fn client(&self, network: &str) -> Result<&TonClient, Status> {
+        let network = if network.is_empty() {
+            &self.default_network
+        } else {
+            network
+        };
+
+        self.clients
+            .get(network)
+            .ok_or_else(|| Status::not_found(format!("network {} is not configured", network)))
+    }
+}
+
+fn rejected_message(rejected: Rejected) -> RejectedMessage {
+    RejectedMessage {
+        hash: rejected.hash,
+        boc: rejected.boc,
+        error: rejected.error,
+        rejected_at: rejected.rejected_at,
+        api_key: rejected.api_key,
+    }
+}
+
+fn traffic_stats(since: SystemTime, requests: Vec<(TrafficKey, u64)>) -> TrafficStats {
+    TrafficStats {
+        since: since
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or_default(),
+        stats: requests
+            .into_iter()
+            .map(|(key, requests)| TrafficStat {
+                workchain: key.workchain,
+                shard: key.shard,
+                archival: key.archival,
+                requests,
+            })
+            .collect(),
+    }
+}
+
+fn lite_server_status(network: &str, status: pool::LiteServerStatus) -> LiteServerStatus {
+    let health = match status.health {
+        pool::Health::Syncing => Health::Syncing,
+        pool::Health::Healthy => Health::Healthy,
+        pool::Health::Unhealthy => Health::Unhealthy,
+        pool::Health::Dead => Health::Dead,
+    };
+    let millis = |d: std::time::Duration| d.as_millis() as u64;
+
+    LiteServerStatus {
+        network: network.to_owned(),
+        id: status.id,
+        health: health.into(),
+        archival: status.archival,
+        first_seqno: status.first_seqno,
+        last_seqno: status.last_seqno,
+        last_ping_ms: status.last_ping.map(millis),
+        consecutive_failures: status.consecutive_failures,
+        inflight: status.inflight,
+        p50_ms: status.latency.map(|l| millis(l.p50)),
+        p95_ms: status.latency.map(|l| millis(l.p95)),
+        p99_ms: status.latency.map(|l| millis(l.p99)),
+        error_rate: status.latency.map(|l| l.error_rate()).unwrap_or_default(),
+        idle_ms: millis(status.idle),
+        last_probe_ms: status.last_probe.map(millis),
+    }
+}
+
+fn lite_server_account_state(
+    address: &AccountAddressData,
+    state: pool::LiteServerAccountState,
+) -> LiteServerAccountState {
+    match state.state {
+        Ok(account_state) => LiteServerAccountState {
+            id: state.id,
+            block_id: Some(account_state.block_id.into()),
+            balance: account_state.balance.unwrap_or_default(),
+            last_transaction_id: account_state
+                .last_transaction_id
+                .map(|tx_id| (address, tx_id).into()),
+            error: None,
+            diverged: state.diverged,
+        },
+        Err(error) => LiteServerAccountState {
+            id: state.id,
+            error: Some(error),
+            diverged: state.diverged,
+            ..Default::default()
+        },
+    }
+}
+
+#[async_trait]
+impl BaseAdminService for AdminService {
+    async fn get_key_usage(
+        &self,
+        request: Request<KeyUsageRequest>,
+    ) -> Result<Response<KeyUsage>, Status> {
+        let key = self.key(request)?;
+
+        Ok(Response::new(self.key_usage(key)?))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn reset_key_usage(
+        &self,
+        request: Request<KeyUsageRequest>,
+    ) -> Result<Response<KeyUsage>, Status> {
+        let key = self.key(request)?;
+        self.quotas()?.reset(key);
+        tracing::info!(key = key.name, "usage reset");
+
+        Ok(Response::new(self.key_usage(key)?))
+    }
+
+    async fn get_lite_servers(
+        &self,
+        _: Request<GetLiteServersRequest>,
+    ) -> Result<Response<GetLiteServersResponse>, Status> {
+        let mut networks: Vec<_> = self.clients.iter().collect();
+        networks.sort_unstable_by_key(|(network, _)| *network);
+
+        let lite_servers = networks
+            .into_iter()
+            .flat_map(|(network, client)| {
+                client
+                    .lite_servers()
+                    .into_iter()
+                    .map(|status| lite_server_status(network, status))
+            })
+            .collect();
+
+        Ok(Response::new(GetLiteServersResponse { lite_servers }))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn ping_lite_server(
+        &self,
+        request: Request<PingLiteServerRequest>,
+    ) -> Result<Response<PingLiteServerResponse>, Status> {
+        let request = request.into_inner();
+        let latency = self
+            .client(&request.network)?
+            .ping_lite_server(&request.id)
+            .await
+            .map_err(|e| Status::unavailable(e.to_string()))?;
+
+        Ok(Response::new(PingLiteServerResponse {
+            latency_ms: latency.as_millis() as u64,
+        }))
+    }
+
+    async fn compare_account_state(
+        &self,
+        request: Request<CompareAccountStateRequest>,
+    ) -> Result<Response<CompareAccountStateResponse>, Status> {
+        let request = request.into_inner();
+        let address = AccountAddressData::from_str(&request.account_address)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let comparison = self
+            .client(&request.network)?
+            .compare_account_state(&request.account_address, request.max_lag)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        Ok(Response::new(CompareAccountStateResponse {
+            diverged: comparison.diverged(),
+            max_seqno: comparison.max_seqno,
+            lite_servers: comparison
+                .lite_servers
+                .into_iter()
+                .map(|state| lite_server_account_state(&address, state))
+                .collect(),
+        }))
+    }
+
+    async fn list_pending_messages(
+        &self,
+        request: Request<ListPendingMessagesRequest>,
+    ) -> Result<Response<ListPendingMessagesResponse>, Status> {
+        let messages = self
+            .journal(&request.get_ref().network)?
+            .pending()
+            .into_iter()
+            .map(|accepted| PendingMessage {
+                hash: accepted.hash,
+                boc: accepted.boc,
+                accepted_at: accepted.accepted_at,
+                api_key: accepted.api_key,
+            })
+            .collect();
+
+        Ok(Response::new(ListPendingMessagesResponse { messages }))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn list_rejected_messages(
+        &self,
+        request: Request<ListRejectedMessagesRequest>,
+    ) -> Result<Response<ListRejectedMessagesResponse>, Status> {
+        let messages = self
+            .rejected(&request.get_ref().network)?
+            .list()
+            .into_iter()
+            .map(|rejected| RejectedMessage {
+                boc: String::new(),
+                ..rejected_message(rejected)
+            })
+            .collect();
+
+        Ok(Response::new(ListRejectedMessagesResponse { messages }))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn get_rejected_message(
+        &self,
+        request: Request<GetRejectedMessageRequest>,
+    ) -> Result<Response<RejectedMessage>, Status> {
+        let request = request.into_inner();
+        let rejected = self
+            .rejected(&request.network)?
+            .get(&request.hash)
+            .ok_or_else(|| {
+                Status::not_found(format!("rejected message {} not found", request.hash))
+            })?;
+
+        Ok(Response::new(rejected_message(rejected)))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn replay_message(
+        &self,
+        request: Request<ReplayMessageRequest>,
+    ) -> Result<Response<SendResponse>, Status> {
+        let request = request.into_inner();
+        let journal = self.journal(&request.network)?.clone();
+        let client = self.client(&request.network)?;
+        let accepted = journal.get_pending(&request.hash).ok_or_else(|| {
+            Status::not_found(format!("pending message {} not found", request.hash))
+        })?;
+
+        let hash = client.send_message_returning_hash(&accepted.boc).await;
+        let error = hash.as_ref().err().map(|e| e.to_string());
+        tokio::task::spawn_blocking(move || journal.relayed(&accepted.hash, error))
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .map_err(|e| Status::internal(e.to_string()))?;
+        let hash = hash.map_err(|e| Status::unavailable(e.to_string()))?;
+        tracing::info!(hash, "pending message replayed");
+
+        Ok(Response::new(SendResponse {
+            hash,
+            duplicate: false,
+        }))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn start_file_export(
+        &self,
+        request: Request<StartFileExportRequest>,
+    ) -> Result<Response<ExportStatus>, Status> {
+        let request = request.into_inner();
+        let source = match request.source {
+            Some(RequestSource::AccountAddress(address)) => Source::Account(
+                AccountAddressData::from_str(&address)
+                    .map_err(|e| Status::invalid_argument(e.to_string()))?,
+            ),
+            Some(RequestSource::Seqnos(range)) => Source::Seqnos(
+                seqnos(range.from_seqno, range.to_seqno)
+                    .map_err(|e| Status::invalid_argument(e.to_string()))?,
+            ),
+            None => return Err(Status::invalid_argument("source is required")),
+        };
+
+        let job = self
+            .export_jobs(&request.network)?
+            .start_file(source, request.gzip)?;
+
+        Ok(Response::new(job.status()))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn get_traffic_stats(
+        &self,
+        request: Request<TrafficStatsRequest>,
+    ) -> Result<Response<TrafficStats>, Status> {
+        let (since, requests) = self
+            .client(&request.get_ref().network)?
+            .traffic_stats()
+            .snapshot();
+
+        Ok(Response::new(traffic_stats(since, requests)))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn reset_traffic_stats(
+        &self,
+        request: Request<TrafficStatsRequest>,
+    ) -> Result<Response<TrafficStats>, Status> {
+        let (since, requests) = self
+            .client(&request.get_ref().network)?
+            .traffic_stats()
+            .reset();
+        tracing::info!(network = request.get_ref().network, "traffic stats reset");
+
+        Ok(Response::new(traffic_stats(since, requests)))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn refresh_config(
+        &self,
+        request: Request<RefreshConfigRequest>,
+    ) -> Result<Response<RefreshConfigResponse>, Status> {
+        let refreshed = self
+            .client(&request.get_ref().network)?
+            .refresh_config()
+            .await
+            .map_err(|e| Status::unavailable(e.to_string()))?;
+        tracing::info!(
+            lite_servers = refreshed.lite_servers,
+            inserted = refreshed.inserted.len(),
+            removed = refreshed.removed.len(),
+            "config refreshed"
+        );
+
+        Ok(Response::new(RefreshConfigResponse {
+            lite_servers: refreshed.lite_servers as u32,
+            inserted: refreshed.inserted,
+            removed: refreshed.removed,
+        }))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn get_memory_usage(
+        &self,
+        _: Request<MemoryUsageRequest>,
+    ) -> Result<Response<MemoryUsage>, Status> {
+        let memory = self
+            .memory
+            .as_ref()
+            .ok_or_else(|| Status::unimplemented("memory budget is not enabled"))?;
+        let components: Vec<_> = memory
+            .usage()
+            .into_iter()
+            .map(|usage| ComponentMemoryUsage {
+                name: usage.name,
+                tier: usage.tier.to_string(),
+                bytes: usage.bytes as u64,
+            })
+            .collect();
+
+        Ok(Response::new(MemoryUsage {
+            cap: memory.cap() as u64,
+            total: components.iter().map(|c| c.bytes).sum(),
+            components,
+        }))
+    }
+
+    async fn get_maintenance_mode(
+        &self,
+        _: Request<GetMaintenanceModeRequest>,
+    ) -> Result<Response<MaintenanceMode>, Status> {
+        let maintenance = self.maintenance()?;
+
+        Ok(Response::new(MaintenanceMode {
+            on: maintenance.is_on(),
+            in_flight: maintenance.in_flight(),
+        }))
+    }
+
+    async fn set_maintenance_mode(
+        &self,
+        request: Request<SetMaintenanceModeRequest>,
+    ) -> Result<Response<MaintenanceMode>, Status> {
+        let maintenance = self.maintenance()?;
+        maintenance.set(request.into_inner().on);
+
+        Ok(Response::new(MaintenanceMode {
+            on: maintenance.is_on(),
+            in_flight: maintenance.in_flight(),
+        }))
+    }
+
+    async fn get_chain_stats(
+        &self,
+        request: Request<ChainStatsRequest>,
+    ) -> Result<Response<ChainStatsResponse>, Status> {
+        let request = request.into_inner();
+        let stats = self.chain_stats(&request.network)?;
+        let window = (request.window > 0).then(|| Duration::from_secs(request.window.into()));
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|e| Status::internal(e.to_string()))?
+            .as_secs();
+
+        Ok(Response::new(stats.window(window, now as i64)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tonic::Code;
+    use tonlibjson_client::pool::LatencyStats;
+
+    #[test]
+    fn admin_key_is_required() {
+        let check = admin_key_interceptor(Some("secret".to_owned()));
+        let request = |key: Option<&str>| {
+            let mut request = Request::new(());
+            if let Some(key) = key {
+                request
+                    .metadata_mut()
+                    .insert(ADMIN_KEY_HEADER, key.parse().unwrap());
+            }
+
+            request
+        };
+
+        assert!(check(request(Some("secret"))).is_ok());
+        assert_eq!(
+            check(request(Some("other"))).unwrap_err().code(),
+            Code::PermissionDenied
+        );
+        assert_eq!(
+            check(request(None)).unwrap_err().code(),
+            Code::Unauthenticated
+        );
+        assert!(admin_key_interceptor(None)(request(None)).is_ok());
+    }
+
+    #[test]
+    fn lite_server_status_is_serialized() {
+        let status = lite_server_status(
+            "mainnet",
+            pool::LiteServerStatus {
+                id: "pub.ed25519:key".to_owned(),
+                health: pool::Health::Unhealthy,
+                archival: true,
+                first_seqno: Some(1),
+                last_seqno: Some(100),
+                last_ping: Some(Duration::from_millis(12)),
+                consecutive_failures: 3,
+                inflight: 2,
+                latency: Some(LatencyStats {
+                    p50: Duration::from_millis(10),
+                    p95: Duration::from_millis(20),
+                    p99: Duration::from_millis(30),
+                    requests: 4,
+                    errors: 3,
+                }),
+                idle: Duration::from_secs(2),
+                last_probe: Some(Duration::from_millis(15)),
+            },
+        );
+
+        assert_eq!(status.network, "mainnet");
+        assert_eq!(status.health(), Health::Unhealthy);
+        assert_eq!(status.last_ping_ms, Some(12));
+        assert_eq!(status.p95_ms, Some(20));
+        assert_eq!(status.error_rate, 0.75);
+        assert_eq!(status.idle_ms, 2000);
+        assert_eq!(status.last_probe_ms, Some(15));
+    }
+}
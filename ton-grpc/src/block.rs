@@ -1,22 +1,164 @@
 #![allow(clippy::blocks_in_conditions)]
 
-use crate::helpers::{extend_block_id, extend_get_block_header};
+use crate::cache::{block_etag, CachePolicy, Freshness};
+use crate::fees::{FeeConfigs, FeeStats};
+use crate::helpers::{extend_block_id, extend_get_block_header, fetch_each};
+use crate::limits::{
+    ParamLimits, ResponseSizeLimits, FEE_STATS_BLOCKS, FULL_TRANSACTIONS_COUNT,
+    MASTERCHAIN_INFO_TIMEOUT_MS, SHARD_HIERARCHY_BLOCKS,
+};
+use crate::masterchain::MasterchainWatcher;
+use crate::shards::{fetch_shards, ShardHistory};
 use crate::ton::block_service_server::BlockService as BaseBlockService;
+use crate::ton::full_transaction::Result as FullTransactionResult;
 use crate::ton::get_transaction_ids_request::Order;
 use crate::ton::{
-    AccountAddress, BlockId, BlockIdExt, BlocksHeader, GetLastBlockRequest, GetShardsResponse,
-    GetTransactionIdsRequest, GetTransactionsRequest, Transaction, TransactionId,
+    AccountAddress, BlockId, BlockIdExt, BlockProof, BlocksHeader, ComputeFeesRequest,
+    ComputeFeesResponse, FindMasterchainBlockByUtimeRequest, FollowMasterchainRequest,
+    FullTransaction, GetBlockDataResponse, GetBlockProofRequest, GetFeeStatsRequest,
+    GetFeeStatsResponse, GetFullTransactionsRequest, GetLastBlockRequest,
+    GetMasterchainInfoRequest, GetOutMsgQueueSizesRequest, GetOutMsgQueueSizesResponse,
+    GetShardHierarchyRequest, GetShardHierarchyResponse, GetShardsRequest, GetShardsResponse,
+    GetTransactionIdsRequest, GetTransactionsRequest, MasterchainBlockEvent, MasterchainInfo,
+    Transaction, TransactionId,
 };
-use anyhow::Context;
+use anyhow::{anyhow, Context};
 use derive_new::new;
 use futures::stream::BoxStream;
 use futures::{StreamExt, TryStreamExt};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_stream::wrappers::WatchStream;
+use tonic::metadata::MetadataValue;
 use tonic::{async_trait, Request, Response, Status};
+use tonlibjson_client::block::{
+    BlocksAccountTransactionId, BlocksShortTxId, InternalTransactionId, TonBlockIdExt,
+};
+use tonlibjson_client::proof::ProofError;
+use tonlibjson_client::read_context::ReadContext;
+use tonlibjson_client::reorg::{follow_tips, DEFAULT_REORG_WINDOW};
 use tonlibjson_client::ton::TonClient;
+use tonlibjson_client::transport::LiteServerTransport;
+use tonlibjson_client::verify::VerificationError;
+
+/// Metadata key set to `true` if transactions may be missing from the response
+pub const INCOMPLETE_HEADER: &str = "x-ton-incomplete";
+/// Metadata key of why the response may be incomplete
+pub const WARNING_HEADER: &str = "x-ton-warning";
 
 #[derive(new)]
 pub struct BlockService {
     client: TonClient,
+    #[new(default)]
+    block_data: Option<Arc<dyn LiteServerTransport>>,
+    #[new(default)]
+    response_size_limits: ResponseSizeLimits,
+    #[new(default)]
+    cache_policy: CachePolicy,
+    #[new(default)]
+    param_limits: ParamLimits,
+    #[new(default)]
+    fee_stats: Arc<FeeStats>,
+    #[new(default)]
+    fee_configs: Arc<FeeConfigs>,
+    #[new(default)]
+    masterchain: MasterchainWatcher,
+    #[new(default)]
+    shard_history: Arc<ShardHistory>,
+}
+
+/// Transactions fetched at a time by `GetFullTransactions`
+const FULL_TRANSACTIONS_CONCURRENCY: usize = 16;
+
+impl BlockService {
+    async fn get_checked_transaction_ids(
+        &self,
+        block_id: &TonBlockIdExt,
+    ) -> Result<Response<BoxStream<'static, Result<TransactionId, Status>>>, Status> {
+        let checked = self
+            .client
+            .get_block_tx_ids_checked(block_id)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        let stream = futures::stream::iter(
+            checked
+                .transactions
+                .into_iter()
+                .map(|tx| Ok(TransactionId::from(tx))),
+        )
+        .boxed();
+        let mut response = Response::new(stream);
+        if let Some(warning) = checked.warning {
+            response
+                .metadata_mut()
+                .insert(INCOMPLETE_HEADER, MetadataValue::from_static("true"));
+            if let Ok(warning) = MetadataValue::try_from(warning) {
+                response.metadata_mut().insert(WARNING_HEADER, warning);
+            }
+        }
+
+        Ok(response)
+    }
+
+    pub fn set_cache_policy(mut self, cache_policy: CachePolicy) -> Self {
+        self.cache_policy = cache_policy;
+        self
+    }
+
+    async fn freshness(&self, block_id: &BlockId) -> Result<Freshness, Status> {
+        self.cache_policy
+            .block_freshness(&self.client, block_id)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))
+    }
+
+    pub fn set_response_size_limits(mut self, limits: ResponseSizeLimits) -> Self {
+        self.response_size_limits = limits;
+        self
+    }
+
+    pub fn set_param_limits(mut self, limits: ParamLimits) -> Self {
+        self.param_limits = limits;
+        self
+    }
+
+    /// Shares the masterchain watcher of long polls, e.g. with the chain stats sampler
+    pub fn set_masterchain_watcher(mut self, masterchain: MasterchainWatcher) -> Self {
+        self.masterchain = masterchain;
+        self
+    }
+
+    /// Shares the fee stats cache, e.g. with the memory budget
+    pub fn set_fee_stats(mut self, fee_stats: Arc<FeeStats>) -> Self {
+        self.fee_stats = fee_stats;
+        self
+    }
+
+    pub fn set_fee_configs(mut self, fee_configs: Arc<FeeConfigs>) -> Self {
+        self.fee_configs = fee_configs;
+        self
+    }
+
+    pub fn set_shard_history(mut self, shard_history: Arc<ShardHistory>) -> Self {
+        self.shard_history = shard_history;
+        self
+    }
+
+    /// Lite server used to serve `GetBlockData`, tonlibjson doesn't expose block data
+    #[cfg(feature = "liteserver")]
+    pub fn set_block_data(mut self, block_data: Arc<dyn LiteServerTransport>) -> Self {
+        self.block_data = Some(block_data);
+        self
+    }
+}
+
+/// DATA_LOSS if the block failed verification, see --verify-blocks, INTERNAL otherwise
+fn block_status(e: anyhow::Error) -> Status {
+    match e.downcast_ref::<VerificationError>() {
+        Some(_) => Status::data_loss(e.to_string()),
+        None => Status::internal(e.to_string()),
+    }
 }
 
 #[async_trait]
@@ -24,7 +166,7 @@ impl BaseBlockService for BlockService {
     #[tracing::instrument(skip_all, err)]
     async fn get_last_block(
         &self,
-        _request: Request<GetLastBlockRequest>,
+        request: Request<GetLastBlockRequest>,
     ) -> Result<Response<BlockIdExt>, Status> {
         let block = self
             .client
@@ -33,16 +175,25 @@ impl BaseBlockService for BlockService {
             .map_err(|e: anyhow::Error| Status::internal(e.to_string()))?
             .last;
 
-        Ok(Response::new(block.into()))
+        self.cache_policy.respond(
+            request.metadata(),
+            block_etag(&block),
+            Freshness::Latest,
+            || block.into(),
+        )
     }
 
     #[tracing::instrument(skip_all, err)]
     async fn get_block(&self, request: Request<BlockId>) -> Result<Response<BlockIdExt>, Status> {
-        let block_id = extend_block_id(&self.client, &request.into_inner())
+        let freshness = self.freshness(request.get_ref()).await?;
+        let block_id = extend_block_id(&self.client, request.get_ref())
             .await
-            .map_err(|e: anyhow::Error| Status::internal(e.to_string()))?;
+            .map_err(block_status)?;
 
-        Ok(Response::new(block_id.into()))
+        self.cache_policy
+            .respond(request.metadata(), block_etag(&block_id), freshness, || {
+                block_id.into()
+            })
     }
 
     #[tracing::instrument(skip_all, err)]
@@ -50,31 +201,67 @@ impl BaseBlockService for BlockService {
         &self,
         request: Request<BlockId>,
     ) -> Result<Response<BlocksHeader>, Status> {
-        let block_header = extend_get_block_header(&self.client, &request.into_inner())
+        let freshness = self.freshness(request.get_ref()).await?;
+        let block_header = extend_get_block_header(&self.client, request.get_ref())
             .await
-            .map_err(|e: anyhow::Error| Status::internal(e.to_string()))?;
+            .map_err(block_status)?;
 
-        Ok(Response::new(block_header.into()))
+        self.cache_policy.respond(
+            request.metadata(),
+            block_etag(&block_header.id),
+            freshness,
+            || block_header.into(),
+        )
     }
 
     #[tracing::instrument(skip_all, err)]
     async fn get_shards(
         &self,
-        request: Request<BlockId>,
+        request: Request<GetShardsRequest>,
     ) -> Result<Response<GetShardsResponse>, Status> {
-        let block_id = extend_block_id(&self.client, &request.into_inner())
+        let msg = request.get_ref();
+        let block_id = BlockId::from(msg);
+        let freshness = self.freshness(&block_id).await?;
+        let block_id = extend_block_id(&self.client, &block_id)
             .await
-            .map_err(|e: anyhow::Error| Status::internal(e.to_string()))?;
+            .map_err(block_status)?;
+        let etag = block_etag(&block_id);
+        let as_of = msg
+            .include_as_of
+            .then(|| ReadContext::masterchain(block_id.clone()).into());
 
         let shards = self
             .client
             .get_shards_by_block_id(block_id)
             .await
             .map_err(|e: anyhow::Error| Status::internal(e.to_string()))?;
+        let results = fetch_shards(shards, msg.strict, |shard| {
+            let client = self.client.clone();
 
-        Ok(Response::new(GetShardsResponse {
-            shards: shards.into_iter().map(|i| i.into()).collect(),
-        }))
+            async move { client.get_block_header_by_id(&shard).await }
+        })
+        .await
+        .map_err(|e| Status::internal(format!("{e:#}")))?;
+
+        // a shard which failed may be there on retry
+        let complete = results.is_complete();
+        let (etag, freshness) = if complete {
+            (etag, freshness)
+        } else {
+            (format!("W/{etag}"), Freshness::Latest)
+        };
+
+        self.cache_policy
+            .respond(request.metadata(), etag, freshness, || GetShardsResponse {
+                shards: results
+                    .fetched
+                    .into_iter()
+                    .map(|(shard, _)| shard.into())
+                    .collect(),
+                errors: results.errors,
+                complete,
+                as_of,
+            })
     }
 
     type GetTransactionIdsStream = BoxStream<'static, Result<TransactionId, Status>>;
@@ -92,19 +279,45 @@ impl BaseBlockService for BlockService {
             .context("block id is required")
             .map_err(|e| Status::internal(e.to_string()))?;
 
-        let chain_id = block_id.workchain;
+        let after = msg
+            .after
+            .map(BlocksAccountTransactionId::try_from)
+            .transpose()
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        if after.is_some() && order == Order::Unordered {
+            return Err(Status::invalid_argument(
+                "after requires the ASC or DESC order",
+            ));
+        }
+
         let block_id = extend_block_id(&self.client, &block_id)
             .await
             .map_err(|e| Status::internal(e.to_string()))?;
 
+        if msg.check_complete {
+            if order != Order::Unordered {
+                return Err(Status::invalid_argument(
+                    "check_complete requires the UNORDERED order",
+                ));
+            }
+
+            return self.get_checked_transaction_ids(&block_id).await;
+        }
+
         let stream = match order {
             Order::Unordered => self.client.get_block_tx_stream_unordered(&block_id).boxed(),
-            Order::Asc => self.client.get_block_tx_id_stream(&block_id, false).boxed(),
-            Order::Desc => self.client.get_block_tx_id_stream(&block_id, true).boxed(),
+            Order::Asc => self
+                .client
+                .get_block_tx_id_stream_after(&block_id, false, after)
+                .boxed(),
+            Order::Desc => self
+                .client
+                .get_block_tx_id_stream_after(&block_id, true, after)
+                .boxed(),
         };
 
         let stream = stream
-            .map_ok(move |t| (chain_id, t).into())
+            .map_ok(TransactionId::from)
             .map_err(|e| Status::internal(e.to_string()))
             .boxed();
 
@@ -141,19 +354,22 @@ impl BaseBlockService for BlockService {
         &self,
         request: Request<GetTransactionsRequest>,
     ) -> Result<Response<Self::GetTransactionsStream>, Status> {
-        let msg = request.into_inner();
+        let (metadata, _, msg) = request.into_parts();
 
         // TODO[akostylev0]
         let _order = msg.order();
+        let decode_messages = msg.decode_messages;
         let block_id = msg
             .block_id
             .context("block id is required")
             .map_err(|e| Status::internal(e.to_string()))?;
 
         let chain_id = block_id.workchain;
+        let freshness = self.freshness(&block_id).await?;
         let block_id = extend_block_id(&self.client, &block_id)
             .await
             .map_err(|e| Status::internal(e.to_string()))?;
+        let etag = block_etag(&block_id);
 
         let stream = self.client.get_block_tx_stream(&block_id, false).boxed();
 
@@ -162,9 +378,352 @@ impl BaseBlockService for BlockService {
                 Ok(tx) => (chain_id, tx).try_into(),
                 Err(e) => Err(e),
             })
+            .map_ok(move |tx: Transaction| {
+                if decode_messages {
+                    tx.decode_messages()
+                } else {
+                    tx
+                }
+            })
+            .map_err(|e| Status::internal(e.to_string()))
+            .boxed();
+
+        self.cache_policy
+            .respond(&metadata, etag, freshness, || stream)
+    }
+
+    type GetFullTransactionsStream = BoxStream<'static, Result<FullTransaction, Status>>;
+
+    #[tracing::instrument(skip_all, err)]
+    async fn get_full_transactions(
+        &self,
+        request: Request<GetFullTransactionsRequest>,
+    ) -> Result<Response<Self::GetFullTransactionsStream>, Status> {
+        let msg = request.into_inner();
+        let order = msg.order();
+        let decode_messages = msg.decode_messages;
+        let count =
+            self.param_limits
+                .apply(FULL_TRANSACTIONS_COUNT, "count", msg.count.into())? as usize;
+        let block_id = msg
+            .block_id
+            .context("block id is required")
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        let chain_id = block_id.workchain;
+        let block_id = extend_block_id(&self.client, &block_id)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        let ids = match order {
+            Order::Unordered => self.client.get_block_tx_stream_unordered(&block_id).boxed(),
+            Order::Asc => self.client.get_block_tx_id_stream(&block_id, false).boxed(),
+            Order::Desc => self.client.get_block_tx_id_stream(&block_id, true).boxed(),
+        };
+
+        let client = self.client.clone();
+        let fetch = move |id: BlocksShortTxId| {
+            let client = client.clone();
+
+            async move {
+                let address = id.address().to_string();
+                let tx_id = InternalTransactionId {
+                    lt: id.lt,
+                    hash: id.hash,
+                };
+
+                // the page starts at the transaction itself
+                client
+                    .raw_get_transactions(&address, &tx_id)
+                    .await?
+                    .transactions
+                    .into_iter()
+                    .find(|tx| tx.transaction_id == tx_id)
+                    .ok_or_else(|| anyhow!("transaction {} not found", tx_id.hash))
+            }
+        };
+
+        let stream = fetch_each(ids, count, FULL_TRANSACTIONS_CONCURRENCY, fetch)
+            .map_ok(move |(id, tx)| {
+                let result = tx
+                    .and_then(|tx| (chain_id, tx).try_into())
+                    .map(|tx: Transaction| {
+                        if decode_messages {
+                            tx.decode_messages()
+                        } else {
+                            tx
+                        }
+                    });
+
+                FullTransaction {
+                    id: Some(id.into()),
+                    result: Some(match result {
+                        Ok(tx) => FullTransactionResult::Transaction(tx),
+                        Err(e) => FullTransactionResult::Error(e.to_string()),
+                    }),
+                }
+            })
             .map_err(|e| Status::internal(e.to_string()))
             .boxed();
 
         Ok(Response::new(stream))
     }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn get_out_msg_queue_sizes(
+        &self,
+        _request: Request<GetOutMsgQueueSizesRequest>,
+    ) -> Result<Response<GetOutMsgQueueSizesResponse>, Status> {
+        let sizes = self
+            .client
+            .get_out_msg_queue_sizes()
+            .await
+            .map_err(|e: anyhow::Error| Status::internal(e.to_string()))?;
+
+        Ok(Response::new(sizes.into()))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn get_block_data(
+        &self,
+        request: Request<BlockId>,
+    ) -> Result<Response<GetBlockDataResponse>, Status> {
+        let block_data = self
+            .block_data
+            .as_ref()
+            .ok_or_else(|| Status::unimplemented("block data is not enabled"))?;
+
+        let freshness = self.freshness(request.get_ref()).await?;
+        let block_id = extend_block_id(&self.client, request.get_ref())
+            .await
+            .map_err(block_status)?;
+
+        let data = block_data
+            .get_block_data(&block_id)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        let max_size = self.response_size_limits.for_method("GetBlockData");
+        if data.len() > max_size {
+            return Err(Status::resource_exhausted(format!(
+                "block data of {} bytes exceeds the response size limit of {} bytes",
+                data.len(),
+                max_size
+            )));
+        }
+
+        self.cache_policy
+            .respond(request.metadata(), block_etag(&block_id), freshness, || {
+                GetBlockDataResponse {
+                    id: Some(block_id.into()),
+                    data,
+                }
+            })
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn find_masterchain_block_by_utime(
+        &self,
+        request: Request<FindMasterchainBlockByUtimeRequest>,
+    ) -> Result<Response<BlocksHeader>, Status> {
+        let utime = request.get_ref().utime;
+        let header = self
+            .client
+            .find_masterchain_block_by_utime(utime)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .ok_or_else(|| {
+                Status::not_found(format!("no masterchain block generated before {}", utime))
+            })?;
+
+        Ok(Response::new(header.into()))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn get_fee_stats(
+        &self,
+        request: Request<GetFeeStatsRequest>,
+    ) -> Result<Response<GetFeeStatsResponse>, Status> {
+        let blocks =
+            self.param_limits
+                .apply(FEE_STATS_BLOCKS, "blocks", request.get_ref().blocks.into())?
+                as u32;
+
+        let stats = self
+            .fee_stats
+            .get(&self.client, blocks)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        Ok(Response::new(stats.as_ref().clone()))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn compute_fees(
+        &self,
+        request: Request<ComputeFeesRequest>,
+    ) -> Result<Response<ComputeFeesResponse>, Status> {
+        let fees = self
+            .fee_configs
+            .compute(&self.client, request.get_ref())
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        Ok(Response::new(fees))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn get_masterchain_info(
+        &self,
+        request: Request<GetMasterchainInfoRequest>,
+    ) -> Result<Response<MasterchainInfo>, Status> {
+        let msg = request.into_inner();
+        let timeout =
+            self.param_limits
+                .apply(MASTERCHAIN_INFO_TIMEOUT_MS, "timeout_ms", msg.timeout_ms)?;
+
+        let waited = self
+            .masterchain
+            .wait_for_seqno(
+                &self.client,
+                msg.wait_seqno.unwrap_or(i32::MIN),
+                Duration::from_millis(timeout as u64),
+            )
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        Ok(Response::new(MasterchainInfo {
+            last: Some(waited.info.last.into()),
+            state_root_hash: waited.info.state_root_hash,
+            init: Some(waited.info.init.into()),
+            timed_out: waited.timed_out,
+        }))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn get_shard_hierarchy(
+        &self,
+        request: Request<GetShardHierarchyRequest>,
+    ) -> Result<Response<GetShardHierarchyResponse>, Status> {
+        let msg = request.into_inner();
+        let limit = self
+            .param_limits
+            .get(SHARD_HIERARCHY_BLOCKS)
+            .ok_or_else(|| Status::internal(format!("no limit of {}", SHARD_HIERARCHY_BLOCKS)))?;
+        let to_seqno = match msg.to_seqno {
+            Some(to_seqno) if to_seqno < msg.from_seqno => {
+                return Err(Status::invalid_argument(
+                    "to_seqno must not be less than from_seqno",
+                ))
+            }
+            Some(to_seqno) => {
+                limit.apply("blocks", i64::from(to_seqno - msg.from_seqno) + 1)?;
+
+                to_seqno
+            }
+            None => msg.from_seqno + limit.default as i32 - 1,
+        };
+
+        let hierarchy = self
+            .shard_history
+            .hierarchy(&self.client, msg.from_seqno, to_seqno)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        Ok(Response::new(hierarchy))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn get_block_proof(
+        &self,
+        request: Request<GetBlockProofRequest>,
+    ) -> Result<Response<BlockProof>, Status> {
+        let msg = request.into_inner();
+        let block_id = msg
+            .block
+            .ok_or_else(|| Status::invalid_argument("block is required"))?;
+        let trusted = msg
+            .prove_from
+            .ok_or_else(|| Status::invalid_argument("prove_from is required"))?
+            .into();
+
+        let block = extend_block_id(&self.client, &block_id)
+            .await
+            .map_err(|e: anyhow::Error| Status::internal(e.to_string()))?;
+        let proof = self
+            .client
+            .get_block_proof(&block, &trusted)
+            .await
+            .map_err(|e| match e {
+                ProofError::Unavailable(_) => Status::failed_precondition(e.to_string()),
+                ProofError::Invalid(_) => Status::data_loss(e.to_string()),
+            })?;
+
+        Ok(Response::new((block, proof).into()))
+    }
+
+    type FollowMasterchainStream = BoxStream<'static, Result<MasterchainBlockEvent, Status>>;
+
+    #[tracing::instrument(skip_all, err)]
+    async fn follow_masterchain(
+        &self,
+        _: Request<FollowMasterchainRequest>,
+    ) -> Result<Response<Self::FollowMasterchainStream>, Status> {
+        let tips = WatchStream::new(self.masterchain.subscribe(&self.client))
+            .filter_map(|info| async move { info.map(|info| Ok(info.last)) });
+        let transport: Arc<dyn LiteServerTransport> = Arc::new(self.client.clone());
+
+        let stream = follow_tips(transport, tips, DEFAULT_REORG_WINDOW)
+            .map_ok(MasterchainBlockEvent::from)
+            .map_err(|e| Status::internal(e.to_string()))
+            .boxed();
+
+        Ok(Response::new(stream))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tonic::Code;
+    use tonlibjson_client::fake::{block_id, client_builder, wait_followed, Scenario};
+
+    async fn block_header(scenario: Arc<Scenario>) -> Result<Response<BlocksHeader>, Status> {
+        let mut client = client_builder(&scenario, 1)
+            .set_verify_blocks(true)
+            .build()
+            .unwrap();
+        client.ready().await.unwrap();
+        wait_followed(&client, &scenario).await.unwrap();
+        let block = block_id(-1, scenario.last_seqno() - 1);
+
+        BlockService::new(client)
+            .get_block_header(Request::new(BlockId {
+                workchain: block.workchain,
+                shard: block.shard,
+                seqno: block.seqno,
+                root_hash: Some(block.root_hash),
+                file_hash: Some(block.file_hash),
+            }))
+            .await
+    }
+
+    #[tokio::test]
+    async fn signed_block_is_verified() {
+        let scenario = Arc::new(Scenario::new(900_000, 1_000_000));
+
+        let header = block_header(scenario).await.unwrap().into_inner();
+
+        assert_eq!(header.id.unwrap().seqno, 999_999);
+    }
+
+    #[tokio::test]
+    async fn forged_block_is_data_loss() {
+        let scenario = Arc::new(Scenario::new(900_000, 1_000_000));
+        scenario.forge_signatures();
+
+        let status = block_header(scenario).await.unwrap_err();
+
+        assert_eq!(status.code(), Code::DataLoss);
+    }
 }
@@ -0,0 +1,232 @@
+use crate::ton::BlockId;
+use std::time::Duration;
+use tonic::metadata::{MetadataMap, MetadataValue};
+use tonic::{Code, Response, Status};
+use tonlibjson_client::block::{InternalTransactionId, TonBlockIdExt};
+use tonlibjson_client::ton::TonClient;
+
+const CACHE_CONTROL: &str = "cache-control";
+const ETAG: &str = "etag";
+const IF_NONE_MATCH: &str = "if-none-match";
+
+/// `Cache-Control` and `ETag` of idempotent responses, so that a cache in front of the proxy can serve them
+#[derive(Debug, Clone, Copy)]
+pub struct CachePolicy {
+    /// max-age of data which never changes, e.g. headers of final blocks
+    pub final_max_age: Duration,
+    /// max-age of the latest data, e.g. account states and the last block
+    pub latest_max_age: Duration,
+    /// masterchain blocks this far behind the last one are final when requested by seqno
+    pub final_after_seqnos: i32,
+}
+
+impl Default for CachePolicy {
+    fn default() -> Self {
+        Self {
+            final_max_age: Duration::from_secs(24 * 60 * 60),
+            latest_max_age: Duration::from_secs(5),
+            final_after_seqnos: 16,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Freshness {
+    Final,
+    Latest,
+}
+
+impl CachePolicy {
+    fn cache_control(&self, freshness: Freshness) -> String {
+        let max_age = match freshness {
+            Freshness::Final => self.final_max_age,
+            Freshness::Latest => self.latest_max_age,
+        };
+
+        format!("public, max-age={}", max_age.as_secs())
+    }
+
+    /// Blocks requested by root hash are final, the ones requested by seqno only once
+    /// they are old masterchain blocks, as shard blocks can't be compared with `last_seqno`
+    pub fn freshness(&self, block_id: &BlockId, last_seqno: Option<i32>) -> Freshness {
+        match last_seqno {
+            _ if block_id.root_hash.is_some() => Freshness::Final,
+            Some(last_seqno)
+                if block_id.workchain == -1
+                    && block_id.seqno <= last_seqno.saturating_sub(self.final_after_seqnos) =>
+            {
+                Freshness::Final
+            }
+            _ => Freshness::Latest,
+        }
+    }
+
+    /// Same as [`CachePolicy::freshness`], looks the last masterchain seqno up when it matters
+    pub async fn block_freshness(
+        &self,
+        client: &TonClient,
+        block_id: &BlockId,
+    ) -> anyhow::Result<Freshness> {
+        let last_seqno = if block_id.root_hash.is_none() && block_id.workchain == -1 {
+            Some(client.get_masterchain_info().await?.last.seqno)
+        } else {
+            None
+        };
+
+        Ok(self.freshness(block_id, last_seqno))
+    }
+
+    /// Response with caching metadata, `body` is only built when the client doesn't have it already:
+    /// gRPC has no 304, so a matching `if-none-match` gets `FAILED_PRECONDITION` with the same metadata
+    pub fn respond<T>(
+        &self,
+        request: &MetadataMap,
+        etag: String,
+        freshness: Freshness,
+        body: impl FnOnce() -> T,
+    ) -> Result<Response<T>, Status> {
+        let mut metadata = MetadataMap::new();
+        if let Ok(value) = MetadataValue::try_from(self.cache_control(freshness)) {
+            metadata.insert(CACHE_CONTROL, value);
+        }
+        if let Ok(value) = MetadataValue::try_from(&etag) {
+            metadata.insert(ETAG, value);
+        }
+
+        if matches_etag(request, &etag) {
+            return Err(Status::with_metadata(
+                Code::FailedPrecondition,
+                "not modified",
+                metadata,
+            ));
+        }
+
+        let mut response = Response::new(body());
+        *response.metadata_mut() = metadata;
+
+        Ok(response)
+    }
+}
+
+/// Responses which must never be cached, e.g. of sent messages
+pub fn no_store<T>(mut response: Response<T>) -> Response<T> {
+    response
+        .metadata_mut()
+        .insert(CACHE_CONTROL, MetadataValue::from_static("no-store"));
+
+    response
+}
+
+pub fn block_etag(block_id: &TonBlockIdExt) -> String {
+    format!("\"{}\"", block_id.root_hash)
+}
+
+pub fn transaction_etag(tx_id: &InternalTransactionId) -> String {
+    format!("\"{}:{}\"", tx_id.lt, tx_id.hash)
+}
+
+fn matches_etag(request: &MetadataMap, etag: &str) -> bool {
+    let Some(Ok(if_none_match)) = request.get(IF_NONE_MATCH).map(|v| v.to_str()) else {
+        return false;
+    };
+
+    if_none_match.split(',').map(str::trim).any(|candidate| {
+        candidate == "*" || candidate.trim_start_matches("W/") == etag.trim_start_matches("W/")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block_id(workchain: i32, seqno: i32, root_hash: Option<&str>) -> BlockId {
+        BlockId {
+            workchain,
+            shard: i64::MIN,
+            seqno,
+            root_hash: root_hash.map(ToOwned::to_owned),
+            file_hash: None,
+        }
+    }
+
+    fn if_none_match(value: &'static str) -> MetadataMap {
+        let mut metadata = MetadataMap::new();
+        metadata.insert(IF_NONE_MATCH, MetadataValue::from_static(value));
+
+        metadata
+    }
+
+    #[test]
+    fn block_freshness() {
+        let policy = CachePolicy::default();
+
+        assert_eq!(
+            policy.freshness(&block_id(0, 100, Some("hash")), None),
+            Freshness::Final
+        );
+        assert_eq!(
+            policy.freshness(&block_id(-1, 100, None), Some(116)),
+            Freshness::Final
+        );
+        assert_eq!(
+            policy.freshness(&block_id(-1, 101, None), Some(116)),
+            Freshness::Latest
+        );
+        assert_eq!(
+            policy.freshness(&block_id(0, 100, None), None),
+            Freshness::Latest
+        );
+    }
+
+    #[test]
+    fn respond_sets_caching_metadata() {
+        let response = CachePolicy::default()
+            .respond(
+                &MetadataMap::new(),
+                "\"abc\"".to_owned(),
+                Freshness::Final,
+                || 1,
+            )
+            .unwrap();
+
+        assert_eq!(
+            response.metadata().get(CACHE_CONTROL).unwrap(),
+            "public, max-age=86400"
+        );
+        assert_eq!(response.metadata().get(ETAG).unwrap(), "\"abc\"");
+        assert_eq!(response.into_inner(), 1);
+    }
+
+    #[test]
+    fn respond_not_modified_skips_body() {
+        for value in ["\"abc\"", "\"xyz\", W/\"abc\"", "*"] {
+            let status = CachePolicy::default()
+                .respond::<i32>(
+                    &if_none_match(value),
+                    "\"abc\"".to_owned(),
+                    Freshness::Latest,
+                    || unreachable!(),
+                )
+                .unwrap_err();
+
+            assert_eq!(status.code(), Code::FailedPrecondition);
+            assert_eq!(status.metadata().get(ETAG).unwrap(), "\"abc\"");
+        }
+
+        assert!(CachePolicy::default()
+            .respond(
+                &if_none_match("\"xyz\""),
+                "\"abc\"".to_owned(),
+                Freshness::Latest,
+                || 1
+            )
+            .is_ok());
+    }
+
+    #[test]
+    fn no_store_response() {
+        let response = no_store(Response::new(()));
+
+        assert_eq!(response.metadata().get(CACHE_CONTROL).unwrap(), "no-store");
+    }
+}
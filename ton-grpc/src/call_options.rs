@@ -0,0 +1,209 @@
+use crate::methods::normalize_method;
+use crate::quota::quoted_method;
+use anyhow::anyhow;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tonic::body::BoxBody;
+use tonic::codegen::http::{HeaderMap, Request, Response};
+use tonic::codegen::{Body, Bytes, Service};
+use tonic::Status;
+use tonlibjson_client::call_options::{self, CallOptions};
+use tower::{Layer, ServiceExt};
+
+/// Timeout and retries of the lite server calls of methods, e.g. no retries for `SendMessage`
+/// so that a failed send is reported at once, the client settings apply to other methods
+#[derive(Debug, Clone, Default)]
+pub struct MethodCallOptions {
+    methods: HashMap<String, CallOptions>,
+}
+
+impl MethodCallOptions {
+    pub fn set_timeout(mut self, method: &str, timeout: Duration) -> Self {
+        let options = self.methods.entry(normalize_method(method)).or_default();
+        *options = options.set_timeout(timeout);
+
+        self
+    }
+
+    pub fn set_max_retries(mut self, method: &str, max_retries: u32) -> Self {
+        let options = self.methods.entry(normalize_method(method)).or_default();
+        *options = options.set_max_retries(max_retries);
+
+        self
+    }
+
+    pub fn get(&self, method: &str) -> Option<CallOptions> {
+        self.methods.get(&normalize_method(method)).copied()
+    }
+}
+
+/// Parses `Method=duration`, e.g. `GetAccountState=5s`
+pub fn parse_method_timeout(s: &str) -> anyhow::Result<(String, Duration)> {
+    let (method, timeout) = s
+        .split_once('=')
+        .ok_or_else(|| anyhow!("expected Method=duration, got {}", s))?;
+
+    Ok((method.to_owned(), humantime::parse_duration(timeout)?))
+}
+
+/// Parses `Method=retries`, e.g. `SendMessage=0`
+pub fn parse_method_retries(s: &str) -> anyhow::Result<(String, u32)> {
+    let (method, retries) = s
+        .split_once('=')
+        .ok_or_else(|| anyhow!("expected Method=retries, got {}", s))?;
+
+    Ok((method.to_owned(), retries.parse()?))
+}
+
+#[derive(Clone)]
+pub struct CallOptionsLayer {
+    options: Arc<MethodCallOptions>,
+}
+
+impl CallOptionsLayer {
+    pub fn new(options: Arc<MethodCallOptions>) -> Self {
+        Self { options }
+    }
+}
+
+impl<S> Layer<S> for CallOptionsLayer {
+    type Service = CallOptionsService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        CallOptionsService {
+            inner,
+            options: self.options.clone(),
+        }
+    }
+}
+
+/// Runs a handler and its response stream with the [`CallOptions`] of its method
+#[derive(Clone)]
+pub struct CallOptionsService<S> {
+    inner: S,
+    options: Arc<MethodCallOptions>,
+}
+
+impl<S, B> Service<Request<B>> for CallOptionsService<S>
+where
+    S: Service<Request<B>, Response = Response<BoxBody>> + Clone + Send + 'static,
+    S::Error: Send + 'static,
+    S::Future: Send,
+    B: Send + 'static,
+{
+    type Response = Response<BoxBody>;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        let Some(options) = quoted_method(req.uri().path()).and_then(|m| self.options.get(m))
+        else {
+            return self.inner.clone().oneshot(req).boxed();
+        };
+
+        let response = call_options::scope(options, self.inner.clone().oneshot(req));
+        async move {
+            let response = response.await?;
+
+            Ok(response.map(|inner| OptionsBody { inner, options }.boxed_unsync()))
+        }
+        .boxed()
+    }
+}
+
+struct OptionsBody {
+    inner: BoxBody,
+    options: CallOptions,
+}
+
+impl Body for OptionsBody {
+    type Data = Bytes;
+    type Error = Status;
+
+    fn poll_data(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let this = self.get_mut();
+        let inner = &mut this.inner;
+
+        call_options::sync_scope(this.options, || Pin::new(inner).poll_data(cx))
+    }
+
+    fn poll_trailers(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        Pin::new(&mut self.inner).poll_trailers(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use tower::service_fn;
+
+    fn request(path: &str) -> Request<()> {
+        Request::builder().uri(path).body(()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn handlers_run_with_options_of_their_method() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let handler = {
+            let seen = seen.clone();
+            service_fn(move |_: Request<()>| {
+                seen.lock().unwrap().push(call_options::current());
+
+                async { Ok::<_, Status>(Response::new(tonic::body::empty_body())) }
+            })
+        };
+        let options =
+            MethodCallOptions::default().set_max_retries("/ton.MessageService/SendMessage", 0);
+        let mut service = CallOptionsLayer::new(Arc::new(options)).layer(handler);
+
+        service
+            .call(request("/ton.MessageService/SendMessage"))
+            .await
+            .unwrap();
+        service
+            .call(request("/ton.AccountService/GetAccountState"))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            *seen.lock().unwrap(),
+            [
+                CallOptions::default().set_max_retries(0),
+                CallOptions::default()
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_flags() {
+        assert_eq!(
+            parse_method_timeout("GetAccountState=5s").unwrap(),
+            ("GetAccountState".to_owned(), Duration::from_secs(5))
+        );
+        assert_eq!(
+            parse_method_retries("SendMessage=0").unwrap(),
+            ("SendMessage".to_owned(), 0)
+        );
+        assert!(parse_method_retries("SendMessage").is_err());
+    }
+}
@@ -0,0 +1,98 @@
+use serde::ser::{SerializeMap, SerializeSeq};
+use serde::{Serialize, Serializer};
+use serde_json::Value;
+
+/// JSON of `value` with object keys sorted bytewise and no whitespace, the same value always
+/// gives the same bytes whatever the order of its maps
+pub fn to_vec<T: Serialize + ?Sized>(value: &T) -> serde_json::Result<Vec<u8>> {
+    let value = serde_json::to_value(value)?;
+
+    serde_json::to_vec(&Sorted(&value))
+}
+
+pub fn to_string<T: Serialize + ?Sized>(value: &T) -> serde_json::Result<String> {
+    let bytes = to_vec(value)?;
+
+    Ok(String::from_utf8(bytes).expect("serde_json writes UTF-8"))
+}
+
+struct Sorted<'a>(&'a Value);
+
+impl Serialize for Sorted<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0 {
+            Value::Object(object) => {
+                let mut entries: Vec<_> = object.iter().collect();
+                entries.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+
+                let mut map = serializer.serialize_map(Some(entries.len()))?;
+                for (key, value) in entries {
+                    map.serialize_entry(key, &Sorted(value))?;
+                }
+                map.end()
+            }
+            Value::Array(array) => {
+                let mut seq = serializer.serialize_seq(Some(array.len()))?;
+                for value in array {
+                    seq.serialize_element(&Sorted(value))?;
+                }
+                seq.end()
+            }
+            value => value.serialize(serializer),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    #[derive(Serialize)]
+    struct Fixture {
+        zeta: u8,
+        alpha: HashMap<String, Value>,
+    }
+
+    fn fixture() -> Fixture {
+        let alpha = (0..32)
+            .map(|i| {
+                (
+                    format!("key{}", i),
+                    json!({"b": [i, {"d": 1, "c": 2}], "a": null}),
+                )
+            })
+            .collect();
+
+        Fixture { zeta: 1, alpha }
+    }
+
+    #[test]
+    fn keys_are_sorted_without_whitespace() {
+        let value = json!({"b": 1, "a": [{"y": true, "x": "s p"}], "B": 2.5});
+
+        assert_eq!(
+            to_string(&value).unwrap(),
+            r#"{"B":2.5,"a":[{"x":"s p","y":true}],"b":1}"#
+        );
+    }
+
+    #[test]
+    fn typed_structs_are_sorted() {
+        let json = to_string(&fixture()).unwrap();
+
+        assert!(json.starts_with(r#"{"alpha":{"key0":{"a":null,"b":[0,{"c":2,"d":1}]},"key1":"#));
+        assert!(json.ends_with(r#""zeta":1}"#));
+    }
+
+    #[test]
+    fn output_is_byte_stable() {
+        // maps of each fixture iterate in their own random order
+        let first = to_vec(&fixture()).unwrap();
+
+        for _ in 0..16 {
+            assert_eq!(to_vec(&fixture()).unwrap(), first);
+        }
+    }
+}
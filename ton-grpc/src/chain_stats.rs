@@ -0,0 +1,305 @@
+use crate::maintenance::Pause;
+use crate::masterchain::MasterchainWatcher;
+use crate::ton::{ChainBlockSample, ChainStats as ChainStatsResponse};
+use futures::future::{ready, try_join_all};
+use futures::{try_join, TryStreamExt};
+use std::collections::VecDeque;
+use std::iter;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tonlibjson_client::block::TonBlockIdExt;
+use tonlibjson_client::ton::TonClient;
+
+/// Settings of [`sample_chain`]
+#[derive(Debug, Clone)]
+pub struct ChainStatsPolicy {
+    /// masterchain blocks kept for GetChainStats
+    pub history: usize,
+    /// counts transactions of the masterchain block and of the shard blocks it commits,
+    /// a few lite server requests per block
+    pub transactions: bool,
+    /// transactions are counted in one of every this many blocks
+    pub transactions_every: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockSample {
+    pub seqno: i32,
+    pub gen_utime: i64,
+    /// seconds from the generation of the block until it was seen
+    pub lag: i64,
+    pub shards: usize,
+    pub transactions: Option<u64>,
+}
+
+impl From<&BlockSample> for ChainBlockSample {
+    fn from(value: &BlockSample) -> Self {
+        Self {
+            seqno: value.seqno,
+            gen_utime: value.gen_utime,
+            lag: value.lag,
+            shards: value.shards as u32,
+            transactions: value.transactions,
+        }
+    }
+}
+
+/// Recent masterchain blocks as seen by this proxy, the oldest are dropped past the history
+pub struct ChainStats {
+    policy: ChainStatsPolicy,
+    samples: Mutex<VecDeque<BlockSample>>,
+}
+
+impl ChainStats {
+    pub fn new(policy: ChainStatsPolicy) -> Self {
+        metrics::describe_counter!(
+            "ton_grpc_chain_blocks_total",
+            "Masterchain blocks committed since the start"
+        );
+        metrics::describe_gauge!("ton_grpc_chain_seqno", "Last masterchain block seen");
+        metrics::describe_gauge!(
+            "ton_grpc_chain_block_lag_seconds",
+            "Seconds from the generation of the last masterchain block until it was seen"
+        );
+        metrics::describe_gauge!(
+            "ton_grpc_chain_shards",
+            "Shards of the last masterchain block"
+        );
+        metrics::describe_histogram!(
+            "ton_grpc_chain_block_transactions",
+            "Transactions of a masterchain block and the shard blocks it commits"
+        );
+
+        Self {
+            policy,
+            samples: Default::default(),
+        }
+    }
+
+    fn counts_transactions(&self, seqno: i32) -> bool {
+        self.policy.transactions && seqno as u32 % self.policy.transactions_every.max(1) == 0
+    }
+
+    async fn sample(
+        &self,
+        client: &TonClient,
+        block: &TonBlockIdExt,
+    ) -> anyhow::Result<BlockSample> {
+        let (header, shards) = try_join!(
+            client.get_block_header_by_id(block),
+            client.get_shards_by_block_id(block.clone())
+        )?;
+        let transactions = if self.counts_transactions(block.seqno) {
+            Some(count_transactions(client, block, &shards).await?)
+        } else {
+            None
+        };
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+
+        Ok(BlockSample {
+            seqno: block.seqno,
+            gen_utime: header.gen_utime,
+            lag: now - header.gen_utime,
+            shards: shards.len(),
+            transactions,
+        })
+    }
+
+    fn record(&self, network: &str, sample: BlockSample) {
+        metrics::gauge!("ton_grpc_chain_seqno", "network" => network.to_owned())
+            .set(sample.seqno as f64);
+        metrics::gauge!("ton_grpc_chain_block_lag_seconds", "network" => network.to_owned())
+            .set(sample.lag as f64);
+        metrics::gauge!("ton_grpc_chain_shards", "network" => network.to_owned())
+            .set(sample.shards as f64);
+        if let Some(transactions) = sample.transactions {
+            metrics::histogram!("ton_grpc_chain_block_transactions", "network" => network.to_owned())
+                .record(transactions as f64);
+        }
+
+        let mut samples = self.samples.lock().unwrap();
+        samples.push_back(sample);
+        while samples.len() > self.policy.history {
+            samples.pop_front();
+        }
+    }
+
+    /// Stats of the blocks generated within `window` before `now`, of the whole history if none
+    pub fn window(&self, window: Option<Duration>, now: i64) -> ChainStatsResponse {
+        let samples: Vec<_> = self
+            .samples
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|sample| {
+                window.map_or(true, |window| {
+                    sample.gen_utime >= now - window.as_secs() as i64
+                })
+            })
+            .cloned()
+            .collect();
+
+        summary(&samples)
+    }
+}
+
+fn summary(samples: &[BlockSample]) -> ChainStatsResponse {
+    let (Some(first), Some(last)) = (samples.first(), samples.last()) else {
+        return ChainStatsResponse::default();
+    };
+
+    let span = last.gen_utime - first.gen_utime;
+    let blocks_per_minute = if span > 0 {
+        (last.seqno - first.seqno) as f64 * 60.0 / span as f64
+    } else {
+        0.0
+    };
+    let counted: Vec<_> = samples
+        .iter()
+        .filter_map(|sample| sample.transactions)
+        .collect();
+
+    ChainStatsResponse {
+        blocks: last.seqno - first.seqno + 1,
+        blocks_per_minute,
+        mean_lag: samples.iter().map(|sample| sample.lag as f64).sum::<f64>()
+            / samples.len() as f64,
+        max_lag: samples
+            .iter()
+            .map(|sample| sample.lag)
+            .max()
+            .unwrap_or_default(),
+        transactions_per_block: (!counted.is_empty())
+            .then(|| counted.iter().sum::<u64>() as f64 / counted.len() as f64),
+        samples: samples.iter().map(Into::into).collect(),
+    }
+}
+
+/// Transactions of `block` and of its `shards`
+async fn count_transactions(
+    client: &TonClient,
+    block: &TonBlockIdExt,
+    shards: &[TonBlockIdExt],
+) -> anyhow::Result<u64> {
+    let counts = try_join_all(iter::once(block).chain(shards).map(|block| {
+        client
+            .get_block_tx_id_stream(block, false)
+            .try_fold(0, |count, _| ready(Ok(count + 1)))
+    }))
+    .await?;
+
+    Ok(counts.into_iter().sum())
+}
+
+/// Samples every new masterchain block the shared watcher sees, not while `pause` holds
+pub async fn sample_chain(
+    client: TonClient,
+    network: String,
+    masterchain: MasterchainWatcher,
+    stats: Arc<ChainStats>,
+    mut pause: Pause,
+) {
+    let mut receiver = masterchain.subscribe(&client);
+    let mut last_seqno: Option<i32> = None;
+    loop {
+        if receiver.changed().await.is_err() {
+            return;
+        }
+        pause.resumed().await;
+        let Some(block) = receiver
+            .borrow_and_update()
+            .as_ref()
+            .map(|info| info.last.clone())
+        else {
+            continue;
+        };
+        if last_seqno.is_some_and(|seqno| block.seqno <= seqno) {
+            continue;
+        }
+
+        // blocks between the ones seen are counted though not sampled
+        let committed = last_seqno.map_or(1, |seqno| block.seqno - seqno);
+        last_seqno = Some(block.seqno);
+        metrics::counter!("ton_grpc_chain_blocks_total", "network" => network.clone())
+            .increment(committed as u64);
+
+        match stats.sample(&client, &block).await {
+            Ok(sample) => stats.record(&network, sample),
+            Err(e) => {
+                tracing::warn!(network, seqno = block.seqno, error = ?e, "chain stats sample failed")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(history: usize) -> ChainStats {
+        ChainStats::new(ChainStatsPolicy {
+            history,
+            transactions: true,
+            transactions_every: 2,
+        })
+    }
+
+    fn sample(seqno: i32, gen_utime: i64, transactions: Option<u64>) -> BlockSample {
+        BlockSample {
+            seqno,
+            gen_utime,
+            lag: seqno as i64 % 3,
+            shards: 4,
+            transactions,
+        }
+    }
+
+    #[test]
+    fn oldest_samples_are_dropped() {
+        let stats = stats(2);
+        for seqno in 1..=3 {
+            stats.record("mainnet", sample(seqno, seqno as i64 * 5, None));
+        }
+
+        let window = stats.window(None, 100);
+        assert_eq!(window.samples.len(), 2);
+        assert_eq!(window.samples[0].seqno, 2);
+    }
+
+    #[test]
+    fn window_summary() {
+        let stats = stats(100);
+        // a block every 5 seconds, 12 a minute, the one at seqno 4 wasn't seen
+        for seqno in [1, 2, 3, 5, 6, 7] {
+            let transactions = (seqno % 2 == 0).then_some(seqno as u64 * 10);
+            stats.record(
+                "mainnet",
+                sample(seqno, 1000 + seqno as i64 * 5, transactions),
+            );
+        }
+
+        let window = stats.window(Some(Duration::from_secs(20)), 1035);
+        assert_eq!(
+            window.samples.iter().map(|s| s.seqno).collect::<Vec<_>>(),
+            [3, 5, 6, 7]
+        );
+        assert_eq!(window.blocks, 5);
+        assert_eq!(window.blocks_per_minute, 12.0);
+        assert_eq!(window.max_lag, 2);
+        assert_eq!(window.mean_lag, 0.75);
+        assert_eq!(window.transactions_per_block, Some(60.0));
+
+        assert_eq!(
+            stats.window(Some(Duration::from_secs(1)), 2000),
+            Default::default()
+        );
+    }
+
+    #[test]
+    fn transactions_are_counted_in_sampled_blocks() {
+        let stats = stats(1);
+
+        assert!(stats.counts_transactions(10));
+        assert!(!stats.counts_transactions(11));
+    }
+}
@@ -0,0 +1,244 @@
+use serde::Serialize;
+use std::future::Future;
+use std::time::{Duration, Instant};
+use tonlibjson_client::ton::{load_ton_config, TonClient, TonClientBuilder, TonConfig};
+use tonlibjson_client::zero_state::ZeroState;
+use url::Url;
+
+/// The elector, it exists on every network
+pub const CHECK_ADDRESS: &str =
+    "-1:3333333333333333333333333333333333333333333333333333333333333333";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Pass,
+    Fail,
+    /// an earlier step failed
+    Skipped,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Step {
+    pub name: &'static str,
+    pub outcome: Outcome,
+    /// what was found, or the error
+    pub detail: String,
+    pub elapsed_ms: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub network: String,
+    pub steps: Vec<Step>,
+}
+
+impl Report {
+    fn new(network: String) -> Self {
+        Self {
+            network,
+            steps: Vec::new(),
+        }
+    }
+
+    pub fn passed(&self) -> bool {
+        self.steps.iter().all(|step| step.outcome == Outcome::Pass)
+    }
+
+    /// Runs `step` within `timeout` unless an earlier one failed
+    async fn step<T, F>(
+        &mut self,
+        name: &'static str,
+        timeout: Duration,
+        step: F,
+        detail: impl FnOnce(&T) -> String,
+    ) -> Option<T>
+    where
+        F: Future<Output = anyhow::Result<T>>,
+    {
+        if !self.passed() {
+            self.steps.push(Step {
+                name,
+                outcome: Outcome::Skipped,
+                detail: String::new(),
+                elapsed_ms: 0,
+            });
+
+            return None;
+        }
+
+        let started_at = Instant::now();
+        let result = match tokio::time::timeout(timeout, step).await {
+            Ok(result) => result,
+            Err(_) => Err(anyhow::anyhow!("timed out after {:?}", timeout)),
+        };
+        let (outcome, detail, value) = match result {
+            Ok(value) => (Outcome::Pass, detail(&value), Some(value)),
+            Err(e) => (Outcome::Fail, format!("{:#}", e), None),
+        };
+        self.steps.push(Step {
+            name,
+            outcome,
+            detail,
+            elapsed_ms: started_at.elapsed().as_millis() as u64,
+        });
+
+        value
+    }
+}
+
+/// Checks a network end to end with a client built by `builder` from the loaded config,
+/// the client is dropped with all its lite server connections before it returns
+pub async fn run(
+    network: String,
+    config_url: Url,
+    builder: impl FnOnce(TonConfig) -> TonClientBuilder,
+    zero_state: Option<ZeroState>,
+    timeout: Duration,
+) -> Report {
+    let mut report = Report::new(network);
+
+    let config = report
+        .step(
+            "config",
+            timeout,
+            async {
+                let config = load_ton_config(config_url).await?;
+                anyhow::ensure!(!config.liteservers.is_empty(), "no lite servers");
+
+                Ok(config)
+            },
+            |config| format!("{} lite servers", config.liteservers.len()),
+        )
+        .await;
+
+    let client = report
+        .step(
+            "client",
+            timeout,
+            async {
+                let config = config.ok_or_else(|| anyhow::anyhow!("no config"))?;
+                let mut client = builder(config).build()?;
+                client.ready().await?;
+
+                Ok(client)
+            },
+            |_: &TonClient| "ready".to_owned(),
+        )
+        .await;
+
+    let info = report
+        .step(
+            "masterchain_info",
+            timeout,
+            async {
+                let info = client_of(&client)?.get_masterchain_info().await?;
+                if let Some(zero_state) = &zero_state {
+                    zero_state.check(&info)?;
+                }
+
+                Ok(info)
+            },
+            |info| format!("last seqno {}", info.last.seqno),
+        )
+        .await;
+
+    report
+        .step(
+            "block_header",
+            timeout,
+            async {
+                let last = info
+                    .as_ref()
+                    .map(|info| &info.last)
+                    .ok_or_else(|| anyhow::anyhow!("no masterchain info"))?;
+
+                client_of(&client)?.get_block_header_by_id(last).await
+            },
+            |header| format!("utime {}", header.gen_utime),
+        )
+        .await;
+
+    report
+        .step(
+            "account_state",
+            timeout,
+            async {
+                client_of(&client)?
+                    .raw_get_account_state(CHECK_ADDRESS)
+                    .await
+            },
+            |state| format!("{} at seqno {}", CHECK_ADDRESS, state.block_id.seqno),
+        )
+        .await;
+
+    drop(client);
+
+    report
+}
+
+fn client_of(client: &Option<TonClient>) -> anyhow::Result<&TonClient> {
+    client.as_ref().ok_or_else(|| anyhow::anyhow!("no client"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn steps_after_failure_are_skipped() {
+        let mut report = Report::new("mainnet".to_owned());
+        let timeout = Duration::from_secs(1);
+
+        let first = report
+            .step("first", timeout, async { Ok(1) }, |v| v.to_string())
+            .await;
+        let second = report
+            .step(
+                "second",
+                timeout,
+                async { Err(anyhow::anyhow!("broken")) },
+                |v: &i32| v.to_string(),
+            )
+            .await;
+        let third = report
+            .step("third", timeout, async { Ok(3) }, |v| v.to_string())
+            .await;
+
+        assert_eq!(first, Some(1));
+        assert_eq!(second, None);
+        assert_eq!(third, None);
+        assert!(!report.passed());
+        let outcomes: Vec<_> = report.steps.iter().map(|s| (s.name, s.outcome)).collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                ("first", Outcome::Pass),
+                ("second", Outcome::Fail),
+                ("third", Outcome::Skipped)
+            ]
+        );
+        assert_eq!(report.steps[1].detail, "broken");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn step_times_out() {
+        let mut report = Report::new("mainnet".to_owned());
+
+        let value: Option<()> = report
+            .step(
+                "slow",
+                Duration::from_secs(1),
+                async {
+                    tokio::time::sleep(Duration::from_secs(10)).await;
+
+                    Ok(())
+                },
+                |_| String::new(),
+            )
+            .await;
+
+        assert_eq!(value, None);
+        assert_eq!(report.steps[0].outcome, Outcome::Fail);
+    }
+}
@@ -0,0 +1,100 @@
+use crate::ton::GetContractCodeResponse;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use ton_contract::code::method_ids;
+use toner::tlb::bits::de::unpack_bytes;
+use toner::tlb::Cell;
+use toner::ton::boc::BoC;
+use tonic::Status;
+use tonlibjson_client::block::RawFullAccountState;
+use tonlibjson_client::ton::TonClient;
+
+/// Root cell of a base64 BoC, none if it's empty
+fn root(boc: &str) -> anyhow::Result<Option<Cell>> {
+    if boc.is_empty() {
+        return Ok(None);
+    }
+
+    let boc: BoC = unpack_bytes(STANDARD.decode(boc)?)?;
+
+    Ok(boc.single_root().map(|root| root.as_ref().clone()))
+}
+
+/// Code and data of `state` with their hashes, method ids are parsed from the code
+pub fn contract_code(
+    account_address: &str,
+    state: RawFullAccountState,
+) -> anyhow::Result<GetContractCodeResponse> {
+    let code = root(&state.code)?;
+    let data = root(&state.data)?;
+
+    Ok(GetContractCodeResponse {
+        account_address: account_address.to_owned(),
+        block_id: Some(state.block_id.into()),
+        code_hash: code.as_ref().map(|code| STANDARD.encode(code.hash())),
+        data_hash: data.as_ref().map(|data| STANDARD.encode(data.hash())),
+        method_ids: code.as_ref().and_then(method_ids).unwrap_or_default(),
+        code: state.code,
+        data: state.data,
+    })
+}
+
+/// Hash of the code cell of `state`, none for accounts without code
+pub fn code_hash(state: &RawFullAccountState) -> anyhow::Result<Option<[u8; 32]>> {
+    Ok(root(&state.code)?.map(|code| code.hash()))
+}
+
+pub async fn fetch_contract_code(
+    client: &TonClient,
+    address: &str,
+) -> Result<GetContractCodeResponse, Status> {
+    let state = client
+        .raw_get_account_state(address)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+    contract_code(address, state).map_err(|e| Status::internal(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const EMPTY_CELL: &str = "te6cckEBAQEAAgAAAEysuc0=";
+
+    fn state(code: &str, data: &str) -> RawFullAccountState {
+        serde_json::from_value(json!({
+            "@type": "raw.fullAccountState",
+            "balance": "1",
+            "code": code,
+            "data": data,
+            "last_transaction_id": {"@type": "internal.transactionId", "lt": "0", "hash": "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA="},
+            "block_id": {"@type": "ton.blockIdExt", "workchain": -1, "shard": "-9223372036854775808", "seqno": 1, "root_hash": "", "file_hash": ""},
+            "frozen_hash": "",
+            "sync_utime": "0",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn code_and_data_are_hashed() {
+        let response = contract_code("address", state(EMPTY_CELL, EMPTY_CELL)).unwrap();
+
+        // representation hash of an empty cell
+        let empty_hash = "lqKW0iTyhcZ77pPDD4owkVfw2qNdxbh+QQt4YwoJz8c=";
+        assert_eq!(response.code, EMPTY_CELL);
+        assert_eq!(response.code_hash.as_deref(), Some(empty_hash));
+        assert_eq!(response.data_hash.as_deref(), Some(empty_hash));
+        assert!(response.method_ids.is_empty());
+    }
+
+    #[test]
+    fn uninitialized_account_has_no_hashes() {
+        let response = contract_code("address", state("", "")).unwrap();
+
+        assert_eq!(response.code_hash, None);
+        assert_eq!(response.data_hash, None);
+        assert!(response.code.is_empty());
+    }
+}
@@ -0,0 +1,400 @@
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use tonic::Status;
+use tonlibjson_client::address::AccountAddressData;
+use tonlibjson_client::block::InternalTransactionId;
+use uuid::Uuid;
+
+const CURSOR_VERSION: u8 = 1;
+/// Truncated HMAC-SHA256, forging one still takes 2^128 attempts
+const CURSOR_MAC_LEN: usize = 16;
+
+/// Position of a paginated method, encoded into an opaque cursor
+pub trait Cursor: Sized {
+    /// Distinguishes positions of different methods, so that a cursor only fits its own
+    const KIND: u8;
+
+    fn write(&self, buf: &mut Vec<u8>);
+
+    fn read(payload: &[u8]) -> Option<Self>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountTxCursor {
+    pub address_hash: [u8; 32],
+    pub lt: i64,
+    pub hash: [u8; 32],
+}
+
+impl AccountTxCursor {
+    pub fn new(address: &AccountAddressData, id: &InternalTransactionId) -> anyhow::Result<Self> {
+        let hash = STANDARD.decode(&id.hash)?;
+
+        Ok(Self {
+            address_hash: address.bytes,
+            lt: id.lt,
+            hash: hash
+                .try_into()
+                .map_err(|_| anyhow::anyhow!("transaction hash must be 32 bytes"))?,
+        })
+    }
+
+    /// Transaction of `address` the cursor points to
+    pub fn transaction_id(
+        &self,
+        address: &AccountAddressData,
+    ) -> Result<InternalTransactionId, Status> {
+        if self.address_hash != address.bytes {
+            return Err(Status::invalid_argument(
+                "cursor belongs to another account",
+            ));
+        }
+
+        Ok(InternalTransactionId {
+            lt: self.lt,
+            hash: STANDARD.encode(self.hash),
+        })
+    }
+}
+
+impl Cursor for AccountTxCursor {
+    const KIND: u8 = 1;
+
+    fn write(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.address_hash);
+        buf.extend_from_slice(&self.lt.to_be_bytes());
+        buf.extend_from_slice(&self.hash);
+    }
+
+    fn read(payload: &[u8]) -> Option<Self> {
+        let (address_hash, rest) = payload.split_first_chunk::<32>()?;
+        let (lt, rest) = rest.split_first_chunk::<8>()?;
+        let hash: &[u8; 32] = rest.try_into().ok()?;
+
+        Some(Self {
+            address_hash: *address_hash,
+            lt: i64::from_be_bytes(*lt),
+            hash: *hash,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockCursor {
+    pub workchain: i32,
+    pub shard: i64,
+    pub seqno: i32,
+}
+
+impl Cursor for BlockCursor {
+    const KIND: u8 = 2;
+
+    fn write(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.workchain.to_be_bytes());
+        buf.extend_from_slice(&self.shard.to_be_bytes());
+        buf.extend_from_slice(&self.seqno.to_be_bytes());
+    }
+
+    fn read(payload: &[u8]) -> Option<Self> {
+        let (workchain, rest) = payload.split_first_chunk::<4>()?;
+        let (shard, rest) = rest.split_first_chunk::<8>()?;
+        let seqno: &[u8; 4] = rest.try_into().ok()?;
+
+        Some(Self {
+            workchain: i32::from_be_bytes(*workchain),
+            shard: i64::from_be_bytes(*shard),
+            seqno: i32::from_be_bytes(*seqno),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportCursor {
+    pub job_id: Uuid,
+    pub offset: u64,
+}
+
+impl Cursor for ExportCursor {
+    const KIND: u8 = 3;
+
+    fn write(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(self.job_id.as_bytes());
+        buf.extend_from_slice(&self.offset.to_be_bytes());
+    }
+
+    fn read(payload: &[u8]) -> Option<Self> {
+        let (job_id, rest) = payload.split_first_chunk::<16>()?;
+        let offset: &[u8; 8] = rest.try_into().ok()?;
+
+        Some(Self {
+            job_id: Uuid::from_bytes(*job_id),
+            offset: u64::from_be_bytes(*offset),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum CursorError {
+    #[error("cursor is malformed")]
+    Malformed,
+    #[error("cursor version {0} is not supported")]
+    Version(u8),
+    #[error("cursor is not signed by this server")]
+    Tampered,
+    #[error("cursor belongs to another method")]
+    Kind,
+}
+
+impl From<CursorError> for Status {
+    fn from(e: CursorError) -> Self {
+        Status::invalid_argument(e.to_string())
+    }
+}
+
+/// Signs cursors as base64url of `version | kind | position | mac`
+#[derive(Clone)]
+pub struct Cursors {
+    mac: Hmac<Sha256>,
+}
+
+impl Cursors {
+    pub fn new(secret: &[u8]) -> Self {
+        Self {
+            mac: Hmac::new_from_slice(secret).expect("hmac accepts keys of any length"),
+        }
+    }
+
+    fn sign(&self, data: &[u8]) -> [u8; CURSOR_MAC_LEN] {
+        let mut mac = self.mac.clone();
+        mac.update(data);
+
+        mac.finalize().into_bytes()[..CURSOR_MAC_LEN]
+            .try_into()
+            .unwrap()
+    }
+
+    pub fn encode<C: Cursor>(&self, cursor: &C) -> String {
+        let mut buf = vec![CURSOR_VERSION, C::KIND];
+        cursor.write(&mut buf);
+        let mac = self.sign(&buf);
+        buf.extend_from_slice(&mac);
+
+        URL_SAFE_NO_PAD.encode(buf)
+    }
+
+    pub fn decode<C: Cursor>(&self, cursor: &str) -> Result<C, CursorError> {
+        let buf = URL_SAFE_NO_PAD
+            .decode(cursor)
+            .map_err(|_| CursorError::Malformed)?;
+        if buf.len() < 2 + CURSOR_MAC_LEN {
+            return Err(CursorError::Malformed);
+        }
+        if buf[0] != CURSOR_VERSION {
+            return Err(CursorError::Version(buf[0]));
+        }
+
+        let (data, mac) = buf.split_at(buf.len() - CURSOR_MAC_LEN);
+        let mut expected = self.mac.clone();
+        expected.update(data);
+        expected
+            .verify_truncated_left(mac)
+            .map_err(|_| CursorError::Tampered)?;
+
+        if data[1] != C::KIND {
+            return Err(CursorError::Kind);
+        }
+
+        C::read(&data[2..]).ok_or(CursorError::Malformed)
+    }
+}
+
+/// Random secret, cursors don't survive a restart
+impl Default for Cursors {
+    fn default() -> Self {
+        let secret = [*Uuid::new_v4().as_bytes(), *Uuid::new_v4().as_bytes()].concat();
+
+        Self::new(&secret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account_tx() -> AccountTxCursor {
+        AccountTxCursor {
+            address_hash: [7; 32],
+            lt: 48_000_000_000_001,
+            hash: [9; 32],
+        }
+    }
+
+    /// xorshift, enough to spread malformed inputs without extra dependencies
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let cursors = Cursors::new(b"secret");
+
+        let cursor = cursors.encode(&account_tx());
+        assert_eq!(cursors.decode(&cursor), Ok(account_tx()));
+
+        let block = BlockCursor {
+            workchain: -1,
+            shard: i64::MIN,
+            seqno: 42,
+        };
+        assert_eq!(cursors.decode(&cursors.encode(&block)), Ok(block));
+
+        let export = ExportCursor {
+            job_id: Uuid::new_v4(),
+            offset: 1000,
+        };
+        assert_eq!(cursors.decode(&cursors.encode(&export)), Ok(export));
+    }
+
+    #[test]
+    fn cursor_is_url_safe() {
+        let cursor = Cursors::new(b"secret").encode(&account_tx());
+
+        assert!(cursor
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+    }
+
+    #[test]
+    fn foreign_secret_is_rejected() {
+        let cursor = Cursors::new(b"secret").encode(&account_tx());
+
+        assert_eq!(
+            Cursors::new(b"other").decode::<AccountTxCursor>(&cursor),
+            Err(CursorError::Tampered)
+        );
+    }
+
+    #[test]
+    fn other_kind_is_rejected() {
+        let cursors = Cursors::new(b"secret");
+        let cursor = cursors.encode(&account_tx());
+
+        assert_eq!(
+            cursors.decode::<BlockCursor>(&cursor),
+            Err(CursorError::Kind)
+        );
+    }
+
+    #[test]
+    fn foreign_version_is_rejected() {
+        let cursors = Cursors::new(b"secret");
+        let mut buf = URL_SAFE_NO_PAD
+            .decode(cursors.encode(&account_tx()))
+            .unwrap();
+        buf[0] = 2;
+
+        assert_eq!(
+            cursors.decode::<AccountTxCursor>(&URL_SAFE_NO_PAD.encode(buf)),
+            Err(CursorError::Version(2))
+        );
+    }
+
+    #[test]
+    fn every_flipped_bit_is_rejected() {
+        let cursors = Cursors::new(b"secret");
+        let buf = URL_SAFE_NO_PAD
+            .decode(cursors.encode(&account_tx()))
+            .unwrap();
+
+        for bit in 0..buf.len() * 8 {
+            let mut tampered = buf.clone();
+            tampered[bit / 8] ^= 1 << (bit % 8);
+
+            assert!(
+                cursors
+                    .decode::<AccountTxCursor>(&URL_SAFE_NO_PAD.encode(tampered))
+                    .is_err(),
+                "bit {}",
+                bit
+            );
+        }
+    }
+
+    #[test]
+    fn fuzz_random_input() {
+        let cursors = Cursors::new(b"secret");
+        let valid = URL_SAFE_NO_PAD
+            .decode(cursors.encode(&account_tx()))
+            .unwrap();
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+
+        for _ in 0..20_000 {
+            let input = match rng.below(4) {
+                // arbitrary strings, mostly not base64 at all
+                0 => (0..rng.below(128))
+                    .map(|_| char::from_u32(rng.below(0x250) as u32).unwrap_or('?'))
+                    .collect(),
+                // arbitrary bytes of any length
+                1 => URL_SAFE_NO_PAD.encode(
+                    (0..rng.below(128))
+                        .map(|_| rng.next() as u8)
+                        .collect::<Vec<_>>(),
+                ),
+                // a valid cursor cut or extended
+                2 => {
+                    let mut buf = valid.clone();
+                    buf.resize(rng.below(valid.len() * 2), rng.next() as u8);
+                    if buf == valid {
+                        continue;
+                    }
+                    URL_SAFE_NO_PAD.encode(buf)
+                }
+                // a valid cursor with random bytes replaced
+                _ => {
+                    let mut buf = valid.clone();
+                    for _ in 0..=rng.below(4) {
+                        let i = rng.below(buf.len());
+                        buf[i] = rng.next() as u8;
+                    }
+                    if buf == valid {
+                        continue;
+                    }
+                    URL_SAFE_NO_PAD.encode(buf)
+                }
+            };
+
+            assert!(
+                cursors.decode::<AccountTxCursor>(&input).is_err(),
+                "{:?}",
+                input
+            );
+            assert!(cursors.decode::<BlockCursor>(&input).is_err());
+        }
+    }
+
+    #[test]
+    fn payload_of_wrong_length_is_malformed() {
+        let cursors = Cursors::new(b"secret");
+        let mut buf = vec![CURSOR_VERSION, AccountTxCursor::KIND, 1, 2, 3];
+        let mac = cursors.sign(&buf);
+        buf.extend_from_slice(&mac);
+
+        assert_eq!(
+            cursors.decode::<AccountTxCursor>(&URL_SAFE_NO_PAD.encode(buf)),
+            Err(CursorError::Malformed)
+        );
+    }
+}
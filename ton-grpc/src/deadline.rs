@@ -0,0 +1,320 @@
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::time::{sleep_until, Instant, Sleep};
+use tonic::body::BoxBody;
+use tonic::codegen::http::{HeaderMap, Request, Response};
+use tonic::codegen::{Body, Bytes, Service};
+use tonic::Status;
+use tonlibjson_client::deadline;
+use tower::{Layer, ServiceExt};
+
+/// Milliseconds a client waits for the response, for clients which don't set `grpc-timeout`
+pub const REQUEST_TIMEOUT_HEADER: &str = "x-request-timeout-ms";
+/// Messages streamed before the deadline, sent along with `DEADLINE_EXCEEDED`
+pub const COMPLETED_HEADER: &str = "x-completed-messages";
+
+const GRPC_TIMEOUT_HEADER: &str = "grpc-timeout";
+
+/// `grpc-timeout` as of the gRPC over HTTP/2 spec, e.g. `100m` or `5S`
+fn parse_grpc_timeout(value: &str) -> Option<Duration> {
+    if value.len() < 2 || value.len() > 9 {
+        return None;
+    }
+    let (value, unit) = value.split_at(value.len() - 1);
+    let value: u64 = value.parse().ok()?;
+
+    Some(match unit {
+        "H" => Duration::from_secs(value * 60 * 60),
+        "M" => Duration::from_secs(value * 60),
+        "S" => Duration::from_secs(value),
+        "m" => Duration::from_millis(value),
+        "u" => Duration::from_micros(value),
+        "n" => Duration::from_nanos(value),
+        _ => return None,
+    })
+}
+
+/// The shortest of the timeouts a client has sent
+fn request_timeout(headers: &HeaderMap) -> Option<Duration> {
+    let header = |name| headers.get(name).and_then(|v| v.to_str().ok());
+
+    let grpc = header(GRPC_TIMEOUT_HEADER).and_then(parse_grpc_timeout);
+    let http = header(REQUEST_TIMEOUT_HEADER)
+        .and_then(|v| v.parse().ok())
+        .map(Duration::from_millis);
+
+    grpc.into_iter().chain(http).min()
+}
+
+fn exceeded(completed: u64) -> Status {
+    let mut status =
+        Status::deadline_exceeded(format!("deadline exceeded after {} messages", completed));
+    status
+        .metadata_mut()
+        .insert(COMPLETED_HEADER, completed.into());
+
+    status
+}
+
+fn is_failed(headers: &HeaderMap) -> bool {
+    headers
+        .get("grpc-status")
+        .is_some_and(|status| status != "0")
+}
+
+#[derive(Clone, Default)]
+pub struct DeadlineLayer;
+
+impl<S> Layer<S> for DeadlineLayer {
+    type Service = DeadlineService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        DeadlineService { inner }
+    }
+}
+
+/// Makes lite server requests of a handler and of its response stream fail once the client
+/// has given up, the stream is cut with `DEADLINE_EXCEEDED` and the count of sent messages
+#[derive(Clone)]
+pub struct DeadlineService<S> {
+    inner: S,
+}
+
+impl<S, B> Service<Request<B>> for DeadlineService<S>
+where
+    S: Service<Request<B>, Response = Response<BoxBody>> + Clone + Send + 'static,
+    S::Error: Send + 'static,
+    S::Future: Send,
+    B: Send + 'static,
+{
+    type Response = Response<BoxBody>;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        let Some(timeout) = request_timeout(req.headers()) else {
+            return self.inner.clone().oneshot(req).boxed();
+        };
+
+        let deadline = Instant::now() + timeout;
+        let response = deadline::scope(deadline, self.inner.clone().oneshot(req));
+        async move {
+            let Ok(response) = tokio::time::timeout_at(deadline, response).await else {
+                return Ok(exceeded(0).to_http());
+            };
+            let response = response?;
+            // the handler has failed on a request cut by the deadline
+            if is_failed(response.headers()) && Instant::now() >= deadline {
+                return Ok(exceeded(0).to_http());
+            }
+
+            Ok(response.map(|body| DeadlineBody::new(body, deadline).boxed_unsync()))
+        }
+        .boxed()
+    }
+}
+
+/// Counts gRPC messages, each one is prefixed with a compression flag and its length
+#[derive(Debug, Default)]
+struct Messages {
+    prefix: Vec<u8>,
+    remaining: usize,
+    count: u64,
+}
+
+impl Messages {
+    fn feed(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            if self.remaining > 0 {
+                let n = self.remaining.min(data.len());
+                self.remaining -= n;
+                data = &data[n..];
+                if self.remaining == 0 {
+                    self.count += 1;
+                }
+
+                continue;
+            }
+
+            let n = (5 - self.prefix.len()).min(data.len());
+            self.prefix.extend_from_slice(&data[..n]);
+            data = &data[n..];
+            if self.prefix.len() == 5 {
+                let len = u32::from_be_bytes([
+                    self.prefix[1],
+                    self.prefix[2],
+                    self.prefix[3],
+                    self.prefix[4],
+                ]);
+                self.prefix.clear();
+                match len {
+                    0 => self.count += 1,
+                    len => self.remaining = len as usize,
+                }
+            }
+        }
+    }
+}
+
+struct DeadlineBody {
+    inner: BoxBody,
+    deadline: Instant,
+    sleep: Pin<Box<Sleep>>,
+    expired: bool,
+    messages: Messages,
+}
+
+impl DeadlineBody {
+    fn new(inner: BoxBody, deadline: Instant) -> Self {
+        Self {
+            inner,
+            deadline,
+            sleep: Box::pin(sleep_until(deadline)),
+            expired: false,
+            messages: Messages::default(),
+        }
+    }
+
+    fn exceeded_trailers(&self) -> Result<HeaderMap, Status> {
+        let mut trailers = HeaderMap::new();
+        exceeded(self.messages.count).add_header(&mut trailers)?;
+
+        Ok(trailers)
+    }
+}
+
+impl Body for DeadlineBody {
+    type Data = Bytes;
+    type Error = Status;
+
+    fn poll_data(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let this = self.get_mut();
+        if this.expired || this.sleep.as_mut().poll(cx).is_ready() {
+            // the stream is dropped along with its outstanding requests
+            this.expired = true;
+
+            return Poll::Ready(None);
+        }
+
+        let inner = &mut this.inner;
+        let poll = deadline::sync_scope(this.deadline, || Pin::new(inner).poll_data(cx));
+        if let Poll::Ready(Some(Ok(data))) = &poll {
+            this.messages.feed(data);
+        }
+
+        poll
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        let this = self.get_mut();
+        if this.expired {
+            return Poll::Ready(this.exceeded_trailers().map(Some));
+        }
+
+        let inner = &mut this.inner;
+        match deadline::sync_scope(this.deadline, || Pin::new(inner).poll_trailers(cx)) {
+            Poll::Ready(Ok(Some(trailers)))
+                if is_failed(&trailers) && Instant::now() >= this.deadline =>
+            {
+                Poll::Ready(this.exceeded_trailers().map(Some))
+            }
+            poll => poll,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream::BoxStream;
+    use futures::StreamExt;
+    use tonic::codegen::http::HeaderValue;
+
+    struct StreamBody(BoxStream<'static, Result<Bytes, Status>>);
+
+    impl Body for StreamBody {
+        type Data = Bytes;
+        type Error = Status;
+
+        fn poll_data(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+        ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+            self.0.poll_next_unpin(cx)
+        }
+
+        fn poll_trailers(
+            self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+        ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+            Poll::Ready(Ok(None))
+        }
+    }
+
+    #[test]
+    fn timeouts_of_headers() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(request_timeout(&headers), None);
+
+        headers.insert(REQUEST_TIMEOUT_HEADER, HeaderValue::from_static("1500"));
+        assert_eq!(request_timeout(&headers), Some(Duration::from_millis(1500)));
+
+        headers.insert(GRPC_TIMEOUT_HEADER, HeaderValue::from_static("1S"));
+        assert_eq!(request_timeout(&headers), Some(Duration::from_secs(1)));
+
+        assert_eq!(parse_grpc_timeout("2M"), Some(Duration::from_secs(120)));
+        assert_eq!(parse_grpc_timeout("100m"), Some(Duration::from_millis(100)));
+        assert_eq!(parse_grpc_timeout("123456789S"), None);
+        assert_eq!(parse_grpc_timeout("5x"), None);
+        assert_eq!(parse_grpc_timeout("S"), None);
+    }
+
+    #[test]
+    fn messages_are_counted_across_chunks() {
+        let mut messages = Messages::default();
+
+        messages.feed(&[0, 0, 0, 0, 2, 1, 2, 0, 0]);
+        assert_eq!(messages.count, 1);
+        messages.feed(&[0, 0, 1]);
+        assert_eq!(messages.count, 1);
+        messages.feed(&[9, 0, 0, 0, 0, 0]);
+        assert_eq!(messages.count, 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stream_is_cut_at_deadline() {
+        let deadline = Instant::now() + Duration::from_secs(1);
+        let chunks = futures::stream::iter(0..3u8)
+            .then(move |i| async move {
+                tokio::time::sleep(Duration::from_millis(600)).await;
+                assert_eq!(deadline::current(), Some(deadline));
+
+                Ok::<_, Status>(Bytes::from(vec![0, 0, 0, 0, 1, i]))
+            })
+            .boxed();
+        let inner = StreamBody(chunks).boxed_unsync();
+        let mut body = DeadlineBody::new(inner, deadline);
+
+        assert!(body.data().await.is_some());
+        assert!(body.data().await.is_none());
+
+        let trailers = body.trailers().await.unwrap().unwrap();
+        let status = Status::from_header_map(&trailers).unwrap();
+        assert_eq!(status.code(), tonic::Code::DeadlineExceeded);
+        assert_eq!(trailers.get(COMPLETED_HEADER).unwrap(), "1");
+    }
+}
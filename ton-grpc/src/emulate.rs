@@ -0,0 +1,205 @@
+use crate::ton::{EmulateResponse, EmulatedMessage};
+use anyhow::anyhow;
+use futures::try_join;
+use num_bigint::BigUint;
+use serde::Deserialize;
+use std::time::{SystemTime, UNIX_EPOCH};
+use toner::tlb::bits::bitvec::order::Msb0;
+use toner::tlb::bits::bitvec::vec::BitVec;
+use toner::tlb::bits::de::{BitReader, BitReaderExt};
+use toner::tlb::bits::r#as::NBits;
+use toner::tlb::de::CellParser;
+use toner::tlb::r#as::{NoArgs, Ref};
+use toner::tlb::Cell;
+use toner::ton::currency::CurrencyCollection;
+use toner::ton::hashmap::HashmapE;
+use toner::ton::message::{CommonMsgInfo, Message};
+use tonic::Status;
+use tonlibjson_client::boc::{parse_base64_boc, to_base64_boc};
+use tonlibjson_client::ton::TonClient;
+use tonlibjson_client::transaction::{parse_description, ComputePhase};
+
+/// Runs the external message `body`, a base64 BoC, against the current state of its destination
+/// with the transaction emulator. Nothing is broadcast.
+pub async fn emulate_message(client: &TonClient, body: &str) -> Result<EmulateResponse, Status> {
+    let destination =
+        external_destination(body).map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+    let (config, shard_account) = try_join!(
+        client.get_config_all(),
+        client.get_shard_account_cell(&destination)
+    )
+    .map_err(|e| Status::internal(e.to_string()))?;
+
+    let body = body.to_owned();
+    let output = tokio::task::spawn_blocking(move || {
+        let emulator = tonlibjson_sys::TransactionEmulator::new(&config.config.bytes, 0)?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        emulator.set_unixtime(now as u32);
+
+        emulator.emulate(&shard_account.bytes, &body)
+    })
+    .await
+    .map_err(|e| Status::internal(e.to_string()))?
+    .map_err(|e| Status::internal(e.to_string()))?;
+
+    let result: EmulatorOutput =
+        serde_json::from_str(&output).map_err(|e| Status::internal(e.to_string()))?;
+
+    emulation(result).map_err(|e| Status::internal(e.to_string()))
+}
+
+/// Destination of the external inbound message `body`, a base64 BoC
+pub(crate) fn external_destination(body: &str) -> anyhow::Result<String> {
+    let root = parse_base64_boc(body)?;
+    let message: Message = root.parse_fully()?;
+
+    match message.info {
+        CommonMsgInfo::ExternalIn(info) => Ok(info.dst.to_base64_std()),
+        _ => Err(anyhow!("not an external inbound message")),
+    }
+}
+
+/// Output of `transaction_emulator_emulate_transaction`
+#[derive(Debug, Deserialize)]
+struct EmulatorOutput {
+    success: bool,
+    error: Option<String>,
+    #[serde(default)]
+    external_not_accepted: bool,
+    vm_exit_code: Option<i32>,
+    transaction: Option<String>,
+}
+
+fn emulation(output: EmulatorOutput) -> anyhow::Result<EmulateResponse> {
+    if !output.success {
+        if !output.external_not_accepted {
+            return Err(anyhow!(output
+                .error
+                .unwrap_or_else(|| "emulation failed".to_owned())));
+        }
+
+        return Ok(EmulateResponse {
+            accepted: false,
+            compute_exit_code: output.vm_exit_code,
+            error: output.error,
+            ..Default::default()
+        });
+    }
+
+    let transaction = output
+        .transaction
+        .ok_or_else(|| anyhow!("emulator returned no transaction"))?;
+    let root = parse_base64_boc(&transaction)?;
+
+    let (out_msgs, total_fees) = parse_transaction(&mut root.parser())?;
+    let description =
+        parse_description(&root)?.ok_or_else(|| anyhow!("not an ordinary transaction"))?;
+    let (compute_exit_code, compute_success, gas_used) = match description.compute {
+        Some(ComputePhase::Vm {
+            success,
+            exit_code,
+            gas_used,
+            ..
+        }) => (Some(exit_code), success, gas_used),
+        _ => (None, false, 0),
+    };
+
+    Ok(EmulateResponse {
+        accepted: true,
+        compute_exit_code,
+        compute_success,
+        gas_used,
+        action_result_code: description.action.map(|action| action.result_code),
+        action_success: description.action.is_some_and(|action| action.success),
+        aborted: description.aborted,
+        total_fees: total_fees.to_string(),
+        out_msgs: out_msgs
+            .iter()
+            .map(emulated_message)
+            .collect::<anyhow::Result<_>>()?,
+        error: None,
+        hash: None,
+    })
+}
+
+/// ```tlb
+/// transaction$0111 account_addr:bits256 lt:uint64 prev_trans_hash:bits256 prev_trans_lt:uint64
+///   now:uint32 outmsg_cnt:uint15 orig_status:AccountStatus end_status:AccountStatus
+///   ^[ in_msg:(Maybe ^(Message Any)) out_msgs:(HashmapE 15 ^(Message Any)) ]
+///   total_fees:CurrencyCollection state_update:^(HASH_UPDATE Account)
+///   description:^TransactionDescr = Transaction;
+/// ```
+fn parse_transaction(parser: &mut CellParser<'_>) -> anyhow::Result<(Vec<Cell>, BigUint)> {
+    let tag: u8 = parser.unpack_as::<_, NBits<4>>()?;
+    if tag != 0b0111 {
+        return Err(anyhow!("unsupported transaction tag: {tag:#b}"));
+    }
+    // account_addr, lt, prev_trans_hash, prev_trans_lt, now, outmsg_cnt and statuses
+    parser.skip(256 + 64 + 256 + 64 + 32 + 15 + 2 + 2)?;
+
+    let messages: Cell = parser.parse_as::<_, Ref>()?;
+    let mut messages = messages.parser();
+    let _in_msg: Option<Cell> = messages.parse_as::<_, Option<Ref>>()?;
+    let mut out_msgs = messages
+        .parse_as_with::<Vec<(BitVec<u8, Msb0>, Cell)>, HashmapE<Ref<NoArgs<_>>, ()>>((15, ()))?;
+    out_msgs.sort_by(|(lhs, _), (rhs, _)| lhs.cmp(rhs));
+
+    let total_fees: CurrencyCollection = parser.parse()?;
+
+    Ok((
+        out_msgs.into_iter().map(|(_, msg)| msg).collect(),
+        total_fees.grams,
+    ))
+}
+
+fn emulated_message(cell: &Cell) -> anyhow::Result<EmulatedMessage> {
+    let message: Message = cell.parse_fully()?;
+    let (destination, value, bounce) = match message.info {
+        CommonMsgInfo::Internal(info) => (info.dst.to_string(), info.value.grams, info.bounce),
+        // logs and events
+        _ => (String::new(), BigUint::ZERO, false),
+    };
+
+    Ok(EmulatedMessage {
+        destination,
+        value: value.to_string(),
+        bounce,
+        boc: to_base64_boc(cell.clone())?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn not_accepted_external_message() {
+        let output = serde_json::from_str(
+            r#"{"success":false,"error":"External message not accepted by smart contract","external_not_accepted":true,"vm_exit_code":33,"vm_log":"","elapsed_time":0.001}"#,
+        )
+        .unwrap();
+
+        let emulation = emulation(output).unwrap();
+
+        assert!(!emulation.accepted);
+        assert_eq!(emulation.compute_exit_code, Some(33));
+        assert!(emulation.error.is_some());
+        assert!(emulation.out_msgs.is_empty());
+    }
+
+    #[test]
+    fn emulator_failure_is_error() {
+        let output =
+            serde_json::from_str(r#"{"success":false,"error":"Can't deserialize message"}"#)
+                .unwrap();
+
+        assert!(emulation(output).is_err());
+    }
+
+    #[test]
+    fn only_external_messages_are_emulated() {
+        // empty cell
+        assert!(external_destination("te6cckEBAQEAAgAAAEysuc0=").is_err());
+    }
+}
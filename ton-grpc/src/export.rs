@@ -0,0 +1,668 @@
+use crate::export_file::{extension, rows, CsvWriter, Row, Source};
+use crate::memory::Tracked;
+use crate::ton::export_status::State;
+use crate::ton::{ExportStatus, Transaction};
+use anyhow::{bail, Result};
+use futures::TryStreamExt;
+use prost::Message;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tonic::Status;
+use tonlibjson_client::address::AccountAddressData;
+use tonlibjson_client::block::{InternalTransactionId, RawTransactions};
+use tonlibjson_client::ton::TonClient;
+use uuid::Uuid;
+
+/// Consecutive failed pages after which a job gives up, its position is kept between retries
+const MAX_CONSECUTIVE_FAILURES: u32 = 10;
+const RETRY_FIRST_DELAY: Duration = Duration::from_secs(1);
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(30);
+/// Rows queued for the writer of a file export, fetching waits for it beyond that
+const FILE_QUEUE_ROWS: usize = 256;
+
+/// Transaction history exports driven in the background, bounded in count and size
+pub struct ExportJobs {
+    client: TonClient,
+    max_jobs: usize,
+    max_transactions: usize,
+    ttl: Duration,
+    dir: Option<PathBuf>,
+    jobs: Mutex<HashMap<String, Arc<Job>>>,
+}
+
+impl ExportJobs {
+    pub fn new(client: TonClient, max_jobs: usize, max_transactions: usize, ttl: Duration) -> Self {
+        Self {
+            client,
+            max_jobs,
+            max_transactions,
+            ttl,
+            dir: None,
+            jobs: Default::default(),
+        }
+    }
+
+    /// Directory of files written by [`Self::start_file`], file exports are disabled without it
+    pub fn set_dir(mut self, dir: PathBuf) -> Self {
+        self.dir = Some(dir);
+        self
+    }
+
+    /// Starts exporting transactions of `address` from the newest one down to `to_lt` exclusive
+    pub fn start(
+        &self,
+        address: String,
+        to_lt: Option<i64>,
+        decode_messages: bool,
+    ) -> Result<Arc<Job>, Status> {
+        let account = AccountAddressData::from_str(&address)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let job = self.insert(Progress::default())?;
+
+        tracing::info!(job_id = job.id, address, "transaction export started");
+        tokio::spawn(run(
+            self.client.clone(),
+            job.clone(),
+            Export {
+                address,
+                account,
+                to_lt,
+                decode_messages,
+                max_transactions: self.max_transactions,
+            },
+        ));
+
+        Ok(job)
+    }
+
+    /// Starts writing transactions of `source` to a CSV file, transactions aren't kept in memory
+    pub fn start_file(&self, source: Source, gzip: bool) -> Result<Arc<Job>, Status> {
+        let dir = self
+            .dir
+            .as_ref()
+            .ok_or_else(|| Status::unimplemented("file exports are not enabled"))?;
+
+        let id = Uuid::new_v4().simple().to_string();
+        let path = dir.join(format!("{}.{}", id, extension(gzip)));
+        let job = self.insert_with_id(
+            id,
+            Progress {
+                file: Some(FileProgress {
+                    path: path.clone(),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        )?;
+
+        tracing::info!(job_id = job.id, ?source, path = %path.display(), "file export started");
+        tokio::spawn(run_file(
+            rows(self.client.clone(), source),
+            job.clone(),
+            path,
+            gzip,
+        ));
+
+        Ok(job)
+    }
+
+    fn insert(&self, progress: Progress) -> Result<Arc<Job>, Status> {
+        self.insert_with_id(Uuid::new_v4().simple().to_string(), progress)
+    }
+
+    fn insert_with_id(&self, id: String, progress: Progress) -> Result<Arc<Job>, Status> {
+        let mut jobs = self.jobs.lock().unwrap();
+        sweep(&mut jobs, Instant::now());
+        if jobs.len() >= self.max_jobs {
+            return Err(Status::resource_exhausted(format!(
+                "no more than {} export jobs at a time",
+                self.max_jobs
+            )));
+        }
+
+        let job = Arc::new(Job {
+            id,
+            expires_at: Instant::now() + self.ttl,
+            cancelled: AtomicBool::new(false),
+            progress: Mutex::new(progress),
+        });
+        jobs.insert(job.id.clone(), job.clone());
+
+        Ok(job)
+    }
+
+    pub fn get(&self, job_id: &str) -> Result<Arc<Job>, Status> {
+        let mut jobs = self.jobs.lock().unwrap();
+        sweep(&mut jobs, Instant::now());
+
+        jobs.get(job_id)
+            .cloned()
+            .ok_or_else(|| Status::not_found(format!("export job {} not found", job_id)))
+    }
+}
+
+impl Tracked for ExportJobs {
+    /// Transactions kept by every job times the size of its first one
+    fn usage(&self) -> usize {
+        let jobs = self.jobs.lock().unwrap();
+
+        jobs.values().map(|job| job.usage()).sum()
+    }
+
+    /// Fails the oldest jobs and drops their transactions, file exports aren't kept in memory
+    fn shrink(&self, bytes: usize) -> usize {
+        let mut jobs: Vec<_> = self.jobs.lock().unwrap().values().cloned().collect();
+        jobs.sort_by_key(|job| job.expires_at);
+
+        let mut freed = 0;
+        for job in jobs {
+            if freed >= bytes {
+                break;
+            }
+
+            let usage = job.usage();
+            if usage == 0 {
+                continue;
+            }
+            job.cancelled.store(true, Ordering::Relaxed);
+            let mut progress = job.progress.lock().unwrap();
+            progress.transactions = vec![];
+            progress.state = State::Failed;
+            progress.error = Some("evicted as the memory budget is exceeded".to_owned());
+            tracing::warn!(job_id = job.id, usage, "transaction export evicted");
+            freed += usage;
+        }
+
+        freed
+    }
+}
+
+/// Drops expired jobs, their background tasks stop at the next page
+fn sweep(jobs: &mut HashMap<String, Arc<Job>>, now: Instant) {
+    jobs.retain(|_, job| {
+        let alive = job.expires_at > now;
+        if !alive {
+            job.cancelled.store(true, Ordering::Relaxed);
+        }
+
+        alive
+    });
+}
+
+struct Export {
+    address: String,
+    account: AccountAddressData,
+    to_lt: Option<i64>,
+    decode_messages: bool,
+    max_transactions: usize,
+}
+
+pub struct Job {
+    id: String,
+    expires_at: Instant,
+    cancelled: AtomicBool,
+    progress: Mutex<Progress>,
+}
+
+impl Job {
+    pub fn status(&self) -> ExportStatus {
+        let progress = self.progress.lock().unwrap();
+        let (transactions, last_lt) = match &progress.file {
+            Some(file) => (file.rows, file.last_lt),
+            None => (
+                progress.transactions.len() as u64,
+                progress
+                    .transactions
+                    .last()
+                    .and_then(|tx| tx.id.as_ref())
+                    .map_or(0, |id| id.lt),
+            ),
+        };
+
+        ExportStatus {
+            job_id: self.id.clone(),
+            state: progress.state as i32,
+            transactions,
+            last_lt,
+            file: progress
+                .file
+                .as_ref()
+                .filter(|_| progress.state == State::Completed)
+                .map(|file| file.path.display().to_string()),
+            truncated: progress.truncated,
+            error: progress.error.clone(),
+            retries: progress.retries,
+            expires_in: self
+                .expires_at
+                .saturating_duration_since(Instant::now())
+                .as_secs(),
+        }
+    }
+
+    fn usage(&self) -> usize {
+        let progress = self.progress.lock().unwrap();
+
+        progress.transactions.len()
+            * progress
+                .transactions
+                .first()
+                .map_or(0, |tx| tx.encoded_len())
+    }
+
+    /// Up to `limit` transactions from `offset` and the offset of the next chunk
+    pub fn chunk(&self, offset: usize, limit: usize) -> (Vec<Transaction>, usize) {
+        let progress = self.progress.lock().unwrap();
+        let transactions: Vec<_> = progress
+            .transactions
+            .iter()
+            .skip(offset)
+            .take(limit)
+            .cloned()
+            .collect();
+        let next_offset = offset + transactions.len();
+
+        (transactions, next_offset)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Progress {
+    state: State,
+    transactions: Vec<Transaction>,
+    /// transaction the next page starts from, the last one of the account if none yet
+    next: Option<InternalTransactionId>,
+    truncated: bool,
+    error: Option<String>,
+    retries: u32,
+    /// set for jobs writing to a file, their transactions aren't kept
+    file: Option<FileProgress>,
+}
+
+#[derive(Debug, Default)]
+struct FileProgress {
+    path: PathBuf,
+    rows: u64,
+    last_lt: i64,
+}
+
+impl Progress {
+    /// Appends a page, the export completes at `to_lt`, at the first transaction of the account or when full
+    fn push(
+        &mut self,
+        page: Vec<Transaction>,
+        previous: Option<InternalTransactionId>,
+        to_lt: Option<i64>,
+        max_transactions: usize,
+    ) {
+        for tx in page {
+            let lt = tx.id.as_ref().map_or(0, |id| id.lt);
+            if to_lt.is_some_and(|to_lt| lt <= to_lt) {
+                self.state = State::Completed;
+                return;
+            }
+            if self.transactions.len() == max_transactions {
+                self.truncated = true;
+                self.state = State::Completed;
+                return;
+            }
+
+            self.transactions.push(tx);
+        }
+
+        self.next = previous;
+        if self.next.is_none() {
+            self.state = State::Completed;
+        }
+    }
+}
+
+async fn run(client: TonClient, job: Arc<Job>, export: Export) {
+    let mut failures = 0;
+    loop {
+        if job.cancelled.load(Ordering::Relaxed) {
+            tracing::info!(job_id = job.id, "transaction export expired");
+            return;
+        }
+
+        let next = {
+            let progress = job.progress.lock().unwrap();
+            if progress.state != State::Running {
+                tracing::info!(
+                    job_id = job.id,
+                    transactions = progress.transactions.len(),
+                    "transaction export finished"
+                );
+                return;
+            }
+
+            progress.next.clone()
+        };
+
+        let page = fetch_page(&client, &export.address, next)
+            .await
+            .and_then(|page| {
+                let (transactions, previous) = match page {
+                    Some(page) => (page.transactions, page.previous_transaction_id),
+                    None => (vec![], None),
+                };
+                let transactions = transactions
+                    .into_iter()
+                    .map(|tx| {
+                        let tx = Transaction::try_from((&export.account, tx))?;
+                        Ok(if export.decode_messages {
+                            tx.decode_messages()
+                        } else {
+                            tx
+                        })
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+
+                Ok((transactions, previous))
+            });
+
+        match page {
+            Ok((transactions, previous)) => {
+                failures = 0;
+
+                let mut progress = job.progress.lock().unwrap();
+                if progress.state == State::Running {
+                    progress.push(
+                        transactions,
+                        previous,
+                        export.to_lt,
+                        export.max_transactions,
+                    );
+                }
+            }
+            Err(e) => {
+                failures += 1;
+                tracing::warn!(job_id = job.id, error = ?e, failures, "transaction export page failed");
+
+                {
+                    let mut progress = job.progress.lock().unwrap();
+                    progress.retries += 1;
+                    if failures >= MAX_CONSECUTIVE_FAILURES {
+                        progress.state = State::Failed;
+                        progress.error = Some(e.to_string());
+                        continue;
+                    }
+                }
+
+                let delay = RETRY_FIRST_DELAY
+                    .saturating_mul(1 << (failures - 1))
+                    .min(RETRY_MAX_DELAY);
+                tokio::time::sleep(delay).await;
+            }
+        }
+    }
+}
+
+/// Writes rows to `<path>.partial` and renames it to `path` once complete, the partial file is
+/// removed if the export fails or expires
+async fn run_file(
+    rows: impl futures::Stream<Item = Result<Row>> + Unpin,
+    job: Arc<Job>,
+    path: PathBuf,
+    gzip: bool,
+) {
+    let mut partial = path.clone().into_os_string();
+    partial.push(".partial");
+    let partial = PathBuf::from(partial);
+    let result = match write_file(rows, job.clone(), &partial, gzip).await {
+        Ok(()) => tokio::fs::rename(&partial, &path).await.map_err(Into::into),
+        Err(e) => Err(e),
+    };
+
+    if let Err(e) = &result {
+        tracing::warn!(job_id = job.id, error = ?e, "file export failed");
+        if let Err(e) = tokio::fs::remove_file(&partial).await {
+            tracing::warn!(job_id = job.id, error = ?e, "partial export file is not removed");
+        }
+    }
+
+    let mut progress = job.progress.lock().unwrap();
+    match result {
+        Ok(()) => {
+            tracing::info!(job_id = job.id, path = %path.display(), "file export finished");
+            progress.state = State::Completed;
+        }
+        Err(e) => {
+            progress.state = State::Failed;
+            progress.error = Some(e.to_string());
+        }
+    }
+}
+
+/// Feeds rows to a blocking writer of the file, the rows queued are written even if the
+/// export fails midway
+async fn write_file(
+    mut rows: impl futures::Stream<Item = Result<Row>> + Unpin,
+    job: Arc<Job>,
+    path: &Path,
+    gzip: bool,
+) -> Result<()> {
+    let (sender, receiver) = mpsc::channel(FILE_QUEUE_ROWS);
+    let writer = tokio::task::spawn_blocking({
+        let job = job.clone();
+        let path = path.to_owned();
+
+        move || write_rows(receiver, &job, &path, gzip)
+    });
+
+    let sent = async {
+        while let Some(row) = rows.try_next().await? {
+            if job.cancelled.load(Ordering::Relaxed) {
+                bail!("export expired");
+            }
+            if sender.send(row).await.is_err() {
+                // the writer failed, its error is returned below
+                break;
+            }
+        }
+
+        Ok(())
+    }
+    .await;
+    drop(sender);
+
+    let written = writer.await?;
+
+    sent.and(written)
+}
+
+fn write_rows(mut rows: mpsc::Receiver<Row>, job: &Job, path: &Path, gzip: bool) -> Result<()> {
+    let mut writer = CsvWriter::new(BufWriter::new(File::create(path)?), gzip)?;
+    while let Some(row) = rows.blocking_recv() {
+        writer.write(&row)?;
+        let mut progress = job.progress.lock().unwrap();
+        if let Some(file) = progress.file.as_mut() {
+            file.rows += 1;
+            file.last_lt = row.1.id.as_ref().map_or(0, |id| id.lt);
+        }
+    }
+    writer
+        .finish()?
+        .into_inner()
+        .map_err(|e| e.into_error())?
+        .sync_all()?;
+
+    Ok(())
+}
+
+/// Page starting at `next`, none if the account has no transactions
+async fn fetch_page(
+    client: &TonClient,
+    address: &str,
+    next: Option<InternalTransactionId>,
+) -> Result<Option<RawTransactions>> {
+    let next = match next {
+        Some(next) => next,
+        None => match client
+            .raw_get_account_state(address)
+            .await?
+            .last_transaction_id
+        {
+            Some(last) => last,
+            None => return Ok(None),
+        },
+    };
+
+    Ok(Some(client.raw_get_transactions(address, &next).await?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ton::TransactionId;
+
+    fn tx(lt: i64) -> Transaction {
+        Transaction {
+            id: Some(TransactionId {
+                lt,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn tx_id(lt: i64) -> InternalTransactionId {
+        InternalTransactionId {
+            lt,
+            hash: "hash".to_owned(),
+        }
+    }
+
+    fn lts(progress: &Progress) -> Vec<i64> {
+        progress
+            .transactions
+            .iter()
+            .map(|tx| tx.id.as_ref().unwrap().lt)
+            .collect()
+    }
+
+    #[test]
+    fn push_follows_previous_transaction() {
+        let mut progress = Progress::default();
+
+        progress.push(vec![tx(5), tx(4)], Some(tx_id(3)), None, 10);
+        assert_eq!(progress.state, State::Running);
+        assert_eq!(progress.next.as_ref().unwrap().lt, 3);
+
+        progress.push(vec![tx(3)], None, None, 10);
+        assert_eq!(progress.state, State::Completed);
+        assert_eq!(lts(&progress), [5, 4, 3]);
+        assert!(!progress.truncated);
+    }
+
+    #[test]
+    fn push_stops_at_to_lt() {
+        let mut progress = Progress::default();
+
+        progress.push(vec![tx(5), tx(4), tx(3)], Some(tx_id(2)), Some(4), 10);
+
+        assert_eq!(progress.state, State::Completed);
+        assert_eq!(lts(&progress), [5]);
+    }
+
+    #[test]
+    fn push_truncates_at_max_transactions() {
+        let mut progress = Progress::default();
+
+        progress.push(vec![tx(5), tx(4), tx(3)], Some(tx_id(2)), None, 2);
+
+        assert_eq!(progress.state, State::Completed);
+        assert!(progress.truncated);
+        assert_eq!(lts(&progress), [5, 4]);
+    }
+
+    #[test]
+    fn chunks_and_expiry() {
+        let job = Arc::new(Job {
+            id: "job".to_owned(),
+            expires_at: Instant::now() + Duration::from_secs(60),
+            cancelled: AtomicBool::new(false),
+            progress: Mutex::new(Progress {
+                transactions: (1..=5).rev().map(tx).collect(),
+                ..Default::default()
+            }),
+        });
+
+        let (chunk, next_offset) = job.chunk(3, 10);
+        assert_eq!(chunk.len(), 2);
+        assert_eq!(next_offset, 5);
+        assert_eq!(job.chunk(next_offset, 10), (vec![], 5));
+        assert_eq!(job.status().last_lt, 1);
+
+        let mut jobs = HashMap::from([("job".to_owned(), job.clone())]);
+        sweep(&mut jobs, Instant::now());
+        assert_eq!(jobs.len(), 1);
+        sweep(&mut jobs, Instant::now() + Duration::from_secs(61));
+        assert!(jobs.is_empty());
+        assert!(job.cancelled.load(Ordering::Relaxed));
+    }
+    fn file_job(path: &Path) -> Arc<Job> {
+        Arc::new(Job {
+            id: "job".to_owned(),
+            expires_at: Instant::now() + Duration::from_secs(60),
+            cancelled: AtomicBool::new(false),
+            progress: Mutex::new(Progress {
+                file: Some(FileProgress {
+                    path: path.to_owned(),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+        })
+    }
+
+    #[tokio::test]
+    async fn file_is_renamed_once_written() {
+        let dir = std::env::temp_dir().join(format!("export-{}", Uuid::new_v4()));
+        std::fs::create_dir(&dir).unwrap();
+        let path = dir.join("job.csv");
+        let job = file_job(&path);
+
+        let rows = futures::stream::iter((1..=3).map(|lt| Ok((None, tx(lt)))));
+        run_file(rows, job.clone(), path.clone(), false).await;
+
+        let status = job.status();
+        assert_eq!(status.state, State::Completed as i32);
+        assert_eq!(status.transactions, 3);
+        assert_eq!(status.last_lt, 3);
+        assert_eq!(status.file, Some(path.display().to_string()));
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 4);
+        assert!(!dir.join("job.csv.partial").exists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn partial_file_is_removed_on_failure() {
+        let dir = std::env::temp_dir().join(format!("export-{}", Uuid::new_v4()));
+        std::fs::create_dir(&dir).unwrap();
+        let path = dir.join("job.csv.gz");
+        let job = file_job(&path);
+
+        let rows = futures::stream::iter(vec![
+            Ok((None, tx(1))),
+            Err(anyhow::anyhow!("lite server is gone")),
+        ]);
+        run_file(rows, job.clone(), path.clone(), true).await;
+
+        let status = job.status();
+        assert_eq!(status.state, State::Failed as i32);
+        assert_eq!(status.transactions, 1);
+        assert_eq!(status.file, None);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,413 @@
+//! Transactions written to CSV files for bulk analysis, see `StartFileExportRequest` for the
+//! columns. Rows are streamed to the file as pages of transactions arrive, memory doesn't grow
+//! with the size of the export.
+
+use crate::ton::Transaction;
+use anyhow::{bail, Result};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use futures::stream::BoxStream;
+use futures::{stream, Future, StreamExt, TryStreamExt};
+use std::collections::HashSet;
+use std::io::{self, Write};
+use std::ops::RangeInclusive;
+use tonlibjson_client::address::AccountAddressData;
+use tonlibjson_client::block::TonBlockIdExt;
+use tonlibjson_client::ton::TonClient;
+
+pub const COLUMNS: [&str; 12] = [
+    "block",
+    "lt",
+    "hash",
+    "utime",
+    "account",
+    "in_msg_source",
+    "in_msg_value",
+    "out_msgs",
+    "out_msgs_value",
+    "fee",
+    "storage_fee",
+    "other_fee",
+];
+
+const MASTERCHAIN: i32 = -1;
+const MASTERCHAIN_SHARD: i64 = i64::MIN;
+
+#[derive(Debug, Clone)]
+pub enum Source {
+    Account(AccountAddressData),
+    Seqnos(RangeInclusive<i32>),
+}
+
+pub fn extension(gzip: bool) -> &'static str {
+    if gzip {
+        "csv.gz"
+    } else {
+        "csv"
+    }
+}
+
+/// Transaction along with its block, which is unknown for transactions of an account
+pub type Row = (Option<TonBlockIdExt>, Transaction);
+
+pub fn rows(client: TonClient, source: Source) -> BoxStream<'static, Result<Row>> {
+    match source {
+        Source::Account(account) => {
+            let address = account.to_string();
+
+            client
+                .get_account_tx_stream(&address)
+                .and_then(move |tx| {
+                    futures::future::ready(
+                        Transaction::try_from((&account, tx)).map(|tx| (None, tx)),
+                    )
+                })
+                .boxed()
+        }
+        Source::Seqnos(seqnos) => stream::iter(seqnos)
+            .then({
+                let client = client.clone();
+
+                move |seqno| {
+                    let client = client.clone();
+
+                    async move { committed_blocks(&client, seqno).await }
+                }
+            })
+            .map_ok(move |blocks| {
+                let client = client.clone();
+
+                stream::iter(blocks)
+                    .map(move |block| {
+                        client
+                            .get_block_tx_stream(&block, false)
+                            .and_then(move |tx| {
+                                let block = block.clone();
+
+                                async move {
+                                    let tx = Transaction::try_from((block.workchain, tx))?;
+
+                                    Ok((Some(block), tx))
+                                }
+                            })
+                    })
+                    .flatten()
+            })
+            .try_flatten()
+            .boxed(),
+    }
+}
+
+/// The masterchain block and the shard blocks it commits, i.e. the ones new since the previous one
+async fn committed_blocks(client: &TonClient, seqno: i32) -> Result<Vec<TonBlockIdExt>> {
+    let master = client
+        .look_up_block_by_seqno(MASTERCHAIN, MASTERCHAIN_SHARD, seqno)
+        .await?;
+    let shards = client.get_shards_by_block_id(master.clone()).await?;
+    let previous = if seqno > 1 {
+        client.get_shards(seqno - 1).await?.shards
+    } else {
+        Vec::new()
+    };
+
+    let mut blocks = vec![master];
+    blocks.extend(
+        new_shard_blocks(shards, &previous, |block| {
+            let client = client.clone();
+            let block = block.clone();
+
+            async move { Ok(client.get_block_header_by_id(&block).await?.prev_blocks) }
+        })
+        .await?,
+    );
+
+    Ok(blocks)
+}
+
+/// Shard blocks from `shards` back to `previous` exclusive, following previous blocks across
+/// splits and merges. Shard block seqnos grow along the chain, so a block which is not above
+/// every previous one of its workchain is already committed.
+async fn new_shard_blocks<F, Fut>(
+    shards: Vec<TonBlockIdExt>,
+    previous: &[TonBlockIdExt],
+    mut prev_blocks: F,
+) -> Result<Vec<TonBlockIdExt>>
+where
+    F: FnMut(&TonBlockIdExt) -> Fut,
+    Fut: Future<Output = Result<Vec<TonBlockIdExt>>>,
+{
+    let mut seen = HashSet::new();
+    let mut blocks = Vec::new();
+    let mut pending = shards;
+    while let Some(block) = pending.pop() {
+        let committed = previous
+            .iter()
+            .filter(|prev| prev.workchain == block.workchain)
+            .map(|prev| prev.seqno)
+            .min()
+            .is_some_and(|min| block.seqno <= min);
+        if committed || previous.contains(&block) || !seen.insert(block.clone()) {
+            continue;
+        }
+
+        pending.extend(prev_blocks(&block).await?);
+        blocks.push(block);
+    }
+    blocks.sort_unstable_by_key(|block| (block.workchain, block.shard as u64, block.seqno));
+
+    Ok(blocks)
+}
+
+enum Output<W: Write> {
+    Plain(W),
+    Gzip(GzEncoder<W>),
+}
+
+/// Writes rows as CSV, fields are quoted when they need to
+pub struct CsvWriter<W: Write> {
+    output: Output<W>,
+}
+
+impl<W: Write> CsvWriter<W> {
+    pub fn new(output: W, gzip: bool) -> io::Result<Self> {
+        let output = if gzip {
+            Output::Gzip(GzEncoder::new(output, Compression::default()))
+        } else {
+            Output::Plain(output)
+        };
+        let mut writer = Self { output };
+        writer.write_record(COLUMNS.iter().map(|column| column.to_string()))?;
+
+        Ok(writer)
+    }
+
+    pub fn write(&mut self, (block, tx): &Row) -> io::Result<()> {
+        self.write_record(fields(block.as_ref(), tx))
+    }
+
+    /// Flushes the rows and completes the gzip stream, the file is incomplete without it
+    pub fn finish(self) -> io::Result<W> {
+        match self.output {
+            Output::Plain(mut output) => {
+                output.flush()?;
+
+                Ok(output)
+            }
+            Output::Gzip(output) => output.finish(),
+        }
+    }
+
+    fn write_record(&mut self, fields: impl IntoIterator<Item = String>) -> io::Result<()> {
+        let line = fields
+            .into_iter()
+            .map(|field| escape(&field))
+            .collect::<Vec<_>>()
+            .join(",");
+
+        match &mut self.output {
+            Output::Plain(output) => writeln!(output, "{}", line),
+            Output::Gzip(output) => writeln!(output, "{}", line),
+        }
+    }
+}
+
+fn escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+fn fields(block: Option<&TonBlockIdExt>, tx: &Transaction) -> Vec<String> {
+    let id = tx.id.clone().unwrap_or_default();
+    let (in_msg_source, in_msg_value) = match &tx.in_msg {
+        Some(msg) => match &msg.source {
+            Some(source) if !source.is_empty() => (source.clone(), msg.value.to_string()),
+            _ => (String::new(), String::new()),
+        },
+        None => (String::new(), String::new()),
+    };
+
+    vec![
+        block.map_or_else(String::new, |block| {
+            format!("{}:{:016x}:{}", block.workchain, block.shard, block.seqno)
+        }),
+        id.lt.to_string(),
+        id.hash,
+        tx.utime.to_string(),
+        id.account_address,
+        in_msg_source,
+        in_msg_value,
+        tx.out_msgs.len().to_string(),
+        tx.out_msgs
+            .iter()
+            .map(|msg| msg.value)
+            .sum::<i64>()
+            .to_string(),
+        tx.fee.to_string(),
+        tx.storage_fee.to_string(),
+        tx.other_fee.to_string(),
+    ]
+}
+
+/// Checks a range of masterchain seqnos of a request
+pub fn seqnos(from_seqno: i32, to_seqno: i32) -> Result<RangeInclusive<i32>> {
+    if from_seqno <= 0 || to_seqno < from_seqno {
+        bail!("invalid seqno range {}..={}", from_seqno, to_seqno);
+    }
+
+    Ok(from_seqno..=to_seqno)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ton::{Message, TransactionId};
+    use flate2::read::GzDecoder;
+    use std::collections::HashMap;
+    use std::io::Read;
+
+    fn block(workchain: i32, shard: u64, seqno: i32) -> TonBlockIdExt {
+        TonBlockIdExt {
+            workchain,
+            shard: shard as i64,
+            seqno,
+            root_hash: String::new(),
+            file_hash: String::new(),
+        }
+    }
+
+    fn tx() -> Transaction {
+        Transaction {
+            id: Some(TransactionId {
+                account_address: "EQCjk1hh952vWaE9bRguFkAhDAL5jj3xj9p0uPWrFBq_GEMS".to_owned(),
+                hash: "hash".to_owned(),
+                lt: 42,
+            }),
+            utime: 1700000000,
+            fee: 10,
+            storage_fee: 3,
+            other_fee: 7,
+            in_msg: Some(Message {
+                source: Some("sender".to_owned()),
+                value: 1000,
+                ..Default::default()
+            }),
+            out_msgs: vec![
+                Message {
+                    value: 400,
+                    ..Default::default()
+                },
+                Message {
+                    value: 500,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        }
+    }
+
+    fn csv(rows: &[Row], gzip: bool) -> String {
+        let mut writer = CsvWriter::new(Vec::new(), gzip).unwrap();
+        for row in rows {
+            writer.write(row).unwrap();
+        }
+        let bytes = writer.finish().unwrap();
+
+        if gzip {
+            let mut csv = String::new();
+            GzDecoder::new(&bytes[..]).read_to_string(&mut csv).unwrap();
+
+            csv
+        } else {
+            String::from_utf8(bytes).unwrap()
+        }
+    }
+
+    #[test]
+    fn rows_are_written() {
+        let rows = vec![
+            (Some(block(0, 0x8000000000000000, 7)), tx()),
+            (None, Transaction::default()),
+        ];
+
+        let csv = csv(&rows, false);
+
+        assert_eq!(
+            csv.lines().collect::<Vec<_>>(),
+            vec![
+                "block,lt,hash,utime,account,in_msg_source,in_msg_value,out_msgs,out_msgs_value,fee,storage_fee,other_fee",
+                "0:8000000000000000:7,42,hash,1700000000,EQCjk1hh952vWaE9bRguFkAhDAL5jj3xj9p0uPWrFBq_GEMS,sender,1000,2,900,10,3,7",
+                ",0,,0,,,,0,0,0,0,0",
+            ]
+        );
+    }
+
+    #[test]
+    fn gzip_output() {
+        let rows = vec![(None, tx())];
+
+        assert_eq!(csv(&rows, true), csv(&rows, false));
+    }
+
+    #[test]
+    fn fields_are_escaped() {
+        assert_eq!(escape("plain"), "plain");
+        assert_eq!(escape("a,b"), "\"a,b\"");
+        assert_eq!(escape("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn seqno_ranges() {
+        assert_eq!(seqnos(1, 3).unwrap(), 1..=3);
+        assert!(seqnos(0, 3).is_err());
+        assert!(seqnos(5, 3).is_err());
+    }
+
+    #[tokio::test]
+    async fn new_shard_blocks_stop_at_previous_ones() {
+        // shard 8 at 10 split into 4 and c at 11, then each of them produced one more block
+        let prev = HashMap::from([
+            (
+                block(0, 0x4000000000000000, 12),
+                vec![block(0, 0x4000000000000000, 11)],
+            ),
+            (
+                block(0, 0x4000000000000000, 11),
+                vec![block(0, 0x8000000000000000, 10)],
+            ),
+            (
+                block(0, 0xc000000000000000, 12),
+                vec![block(0, 0xc000000000000000, 11)],
+            ),
+            (
+                block(0, 0xc000000000000000, 11),
+                vec![block(0, 0x8000000000000000, 10)],
+            ),
+        ]);
+        let shards = vec![
+            block(0, 0x4000000000000000, 12),
+            block(0, 0xc000000000000000, 12),
+        ];
+        let previous = vec![block(0, 0x8000000000000000, 10)];
+
+        let blocks = new_shard_blocks(shards, &previous, |block| {
+            let prev = prev.get(block).cloned().unwrap_or_default();
+
+            async move { Ok(prev) }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(
+            blocks,
+            vec![
+                block(0, 0x4000000000000000, 11),
+                block(0, 0x4000000000000000, 12),
+                block(0, 0xc000000000000000, 11),
+                block(0, 0xc000000000000000, 12),
+            ]
+        );
+    }
+}
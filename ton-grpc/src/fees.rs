@@ -0,0 +1,266 @@
+use crate::memory::{EntrySize, Tracked};
+use crate::ton::get_fee_stats_response::{GasPrices, Percentiles, Workchain};
+use crate::ton::{ComputeFeesRequest, ComputeFeesResponse, GetFeeStatsResponse};
+use futures::{stream, StreamExt, TryStreamExt};
+use prost::Message;
+use quick_cache::sync::Cache;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+use ton_contract::config::{self, gas_prices};
+use ton_contract::fees::{compute_fees, FeeConfig, FeeInput};
+use tonlibjson_client::block::{RawTransaction, TonBlockIdExt};
+use tonlibjson_client::boc::parse_base64_boc;
+use tonlibjson_client::ton::TonClient;
+use tonlibjson_client::transaction::{parse_description, ComputePhase};
+
+/// Blocks whose transactions are fetched at a time
+const FEE_STATS_CONCURRENCY: usize = 8;
+
+/// Fees of transactions of the last masterchain blocks and of their shard blocks,
+/// sampled once per last masterchain block and count of blocks
+pub struct FeeStats {
+    samples: Cache<(i32, u32), Arc<GetFeeStatsResponse>>,
+    entry_size: EntrySize,
+}
+
+impl Default for FeeStats {
+    fn default() -> Self {
+        Self {
+            samples: Cache::new(16),
+            entry_size: Default::default(),
+        }
+    }
+}
+
+impl FeeStats {
+    pub async fn get(
+        &self,
+        client: &TonClient,
+        blocks: u32,
+    ) -> anyhow::Result<Arc<GetFeeStatsResponse>> {
+        let last = client.get_masterchain_info().await?.last;
+
+        // concurrent callers wait for the sample of the first one
+        self.samples
+            .get_or_insert_async(&(last.seqno, blocks), async {
+                let response = sample(client, &last, blocks).await?;
+                self.entry_size.sample(|| response.encoded_len());
+
+                anyhow::Ok(Arc::new(response))
+            })
+            .await
+    }
+}
+
+/// Samples are dropped as a whole, the cache has no order to drop the oldest ones by
+impl Tracked for FeeStats {
+    fn usage(&self) -> usize {
+        self.samples.len() * self.entry_size.get()
+    }
+
+    fn shrink(&self, _: usize) -> usize {
+        let freed = self.usage();
+        self.samples.clear();
+
+        freed
+    }
+}
+
+/// Fee config params by the last key block, as only key blocks change them, and workchain
+pub struct FeeConfigs {
+    configs: Cache<(i32, i32), Arc<FeeConfig>>,
+}
+
+impl Default for FeeConfigs {
+    fn default() -> Self {
+        Self {
+            configs: Cache::new(16),
+        }
+    }
+}
+
+impl FeeConfigs {
+    /// Fees of the request by the params as of the last key block, no contract is run
+    pub async fn compute(
+        &self,
+        client: &TonClient,
+        request: &ComputeFeesRequest,
+    ) -> anyhow::Result<ComputeFeesResponse> {
+        let last = client.get_masterchain_info().await?.last;
+        let header = client.get_block_header_by_id(&last).await?;
+        let key_block_seqno = match header.is_key_block {
+            true => last.seqno,
+            false => header.prev_key_block_seqno,
+        };
+
+        let config = self
+            .configs
+            .get_or_insert_async(&(key_block_seqno, request.workchain), async {
+                anyhow::Ok(Arc::new(FeeConfig::fetch(client, request.workchain).await?))
+            })
+            .await?;
+        let fees = compute_fees(
+            &config,
+            &FeeInput {
+                msg_bits: request.msg_bits,
+                msg_cells: request.msg_cells,
+                storage_bits: request.storage_bits,
+                storage_cells: request.storage_cells,
+                duration: request.duration,
+                gas_used: request.gas_used,
+            },
+        );
+
+        Ok(ComputeFeesResponse {
+            key_block_seqno,
+            fwd_fee: fees.fwd_fee,
+            action_fee: fees.action_fee,
+            ihr_fee: fees.ihr_fee,
+            storage_fee: fees.storage_fee,
+            gas_fee: fees.gas_fee,
+            gas_prices: Some(config.gas.into()),
+        })
+    }
+}
+
+/// Configs are dropped as a whole, the cache has no order to drop the oldest ones by
+impl Tracked for FeeConfigs {
+    fn usage(&self) -> usize {
+        self.configs.len() * std::mem::size_of::<FeeConfig>()
+    }
+
+    fn shrink(&self, _: usize) -> usize {
+        let freed = self.usage();
+        self.configs.clear();
+
+        freed
+    }
+}
+
+async fn sample(
+    client: &TonClient,
+    last: &TonBlockIdExt,
+    blocks: u32,
+) -> anyhow::Result<GetFeeStatsResponse> {
+    let first = (last.seqno - blocks as i32 + 1).max(1);
+    let mut sampled = Vec::new();
+    for seqno in first..=last.seqno {
+        let block = match seqno == last.seqno {
+            true => last.clone(),
+            false => {
+                client
+                    .look_up_block_by_seqno(last.workchain, last.shard, seqno)
+                    .await?
+            }
+        };
+        // a shard block is listed again by masterchain blocks until the shard moves on
+        sampled.extend(client.get_shards_by_block_id(block.clone()).await?);
+        sampled.push(block);
+    }
+    let mut seen = HashSet::new();
+    sampled.retain(|block| seen.insert((block.workchain, block.shard, block.seqno)));
+
+    let transactions: Vec<(i32, Vec<RawTransaction>)> = stream::iter(sampled)
+        .map(|block| async move {
+            let txs = client
+                .get_block_tx_stream(&block, false)
+                .try_collect()
+                .await?;
+
+            anyhow::Ok((block.workchain, txs))
+        })
+        .buffer_unordered(FEE_STATS_CONCURRENCY)
+        .try_collect()
+        .await?;
+
+    let mut fees: BTreeMap<i32, (Vec<i64>, Vec<i64>)> = BTreeMap::new();
+    for (workchain, txs) in transactions {
+        let (total_fees, gas_fees) = fees.entry(workchain).or_default();
+        for tx in txs {
+            total_fees.push(tx.fee);
+            if let Some(gas) = gas_fees_of(&tx) {
+                gas_fees.push(gas);
+            }
+        }
+    }
+
+    let mut workchains = Vec::with_capacity(fees.len());
+    for (workchain, (total_fees, gas_fees)) in fees {
+        workchains.push(Workchain {
+            workchain,
+            transactions: total_fees.len() as u64,
+            total_fees: Some(percentiles(total_fees)),
+            gas_fees: Some(percentiles(gas_fees)),
+            gas_prices: Some(gas_prices(client, workchain).await?.into()),
+        });
+    }
+
+    Ok(GetFeeStatsResponse {
+        block: Some(last.clone().into()),
+        blocks: (last.seqno - first + 1) as u32,
+        workchains,
+    })
+}
+
+/// Gas fees of the compute phase, none for transactions without one
+fn gas_fees_of(tx: &RawTransaction) -> Option<i64> {
+    let root = parse_base64_boc(&tx.data).ok()?;
+
+    match parse_description(&root).ok()??.compute? {
+        ComputePhase::Vm { gas_fees, .. } => gas_fees.try_into().ok(),
+        ComputePhase::Skipped(_) => Some(0),
+    }
+}
+
+/// Nearest-rank percentiles, zeros without values
+fn percentiles(mut values: Vec<i64>) -> Percentiles {
+    values.sort_unstable();
+    let rank = |p: usize| match values.len() {
+        0 => 0,
+        n => values[(p * n).div_ceil(100).max(1) - 1],
+    };
+
+    Percentiles {
+        p25: rank(25),
+        p50: rank(50),
+        p90: rank(90),
+    }
+}
+
+impl From<config::GasPrices> for GasPrices {
+    fn from(value: config::GasPrices) -> Self {
+        Self {
+            gas_price: value.gas_price,
+            gas_limit: value.gas_limit,
+            gas_credit: value.gas_credit,
+            flat_gas_limit: value.flat_gas_limit,
+            flat_gas_price: value.flat_gas_price,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nearest_rank_percentiles() {
+        assert_eq!(percentiles(vec![]), Percentiles::default());
+        assert_eq!(
+            percentiles(vec![7]),
+            Percentiles {
+                p25: 7,
+                p50: 7,
+                p90: 7
+            }
+        );
+        assert_eq!(
+            percentiles((1..=10).rev().collect()),
+            Percentiles {
+                p25: 3,
+                p50: 5,
+                p90: 9
+            }
+        );
+    }
+}
@@ -2,11 +2,37 @@ use crate::ton;
 use crate::ton::get_account_transactions_request::bound::Bound::{BlockId, TransactionId};
 use crate::ton::get_account_transactions_request::bound::Type;
 use anyhow::{anyhow, Result};
+use futures::{FutureExt, Stream, StreamExt, TryStreamExt};
+use std::future::Future;
 use std::ops::Bound;
 use std::ops::Bound::{Excluded, Included};
+use std::time::{SystemTime, UNIX_EPOCH};
 use tonlibjson_client::block;
-use tonlibjson_client::block::InternalTransactionId;
+use tonlibjson_client::block::{InternalTransactionId, RawTransaction};
 use tonlibjson_client::ton::TonClient;
+use tonlibjson_client::utime::find_masterchain_block_by_utime;
+
+/// Shard blocks reach the masterchain within this many seconds after their transactions
+const SHARD_COMMIT_MARGIN: i64 = 60;
+
+/// Fetches an item for each of the first `count` ids, `concurrency` at a time and in the
+/// order of `ids`. Failed fetches stay next to their ids, failures of `ids` are passed on.
+pub fn fetch_each<I, T, S, F, Fut>(
+    ids: S,
+    count: usize,
+    concurrency: usize,
+    fetch: F,
+) -> impl Stream<Item = Result<(I, Result<T>)>>
+where
+    I: Clone,
+    S: Stream<Item = Result<I>>,
+    F: Fn(I) -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    ids.take(count)
+        .map_ok(move |id| fetch(id.clone()).map(|result| Ok((id, result))))
+        .try_buffered(concurrency)
+}
 
 #[tracing::instrument(skip_all, err)]
 pub async fn extend_block_id(
@@ -146,3 +172,236 @@ pub async fn extend_to_tx_id(
         }
     })
 }
+
+/// Bounds around transactions of `address` generated within `start_utime..=end_utime`,
+/// from account states at masterchain blocks found by utime. None if there can't be any.
+#[tracing::instrument(skip_all, err)]
+pub async fn utime_bounds(
+    client: &TonClient,
+    address: &str,
+    start_utime: Option<i64>,
+    end_utime: Option<i64>,
+) -> Result<Option<(Bound<InternalTransactionId>, Bound<InternalTransactionId>)>> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+
+    let from = match end_utime {
+        Some(end_utime) if end_utime.saturating_add(SHARD_COMMIT_MARGIN) < now => {
+            let Some(block) =
+                find_masterchain_block_by_utime(client, end_utime + SHARD_COMMIT_MARGIN).await?
+            else {
+                return Ok(None);
+            };
+            let state = client
+                .raw_get_account_state_on_block(address, block.id)
+                .await?;
+            let Some(tx_id) = state.last_transaction_id else {
+                return Ok(None);
+            };
+
+            Included(tx_id)
+        }
+        _ => Bound::Unbounded,
+    };
+
+    let to = match start_utime {
+        Some(start_utime) => {
+            match find_masterchain_block_by_utime(client, start_utime.saturating_sub(1)).await? {
+                Some(block) => client
+                    .raw_get_account_state_on_block(address, block.id)
+                    .await?
+                    .last_transaction_id
+                    .map_or(Bound::Unbounded, Excluded),
+                None => Bound::Unbounded,
+            }
+        }
+        None => Bound::Unbounded,
+    };
+
+    Ok(Some((from, to)))
+}
+
+/// Transactions of a stream from new to old generated within `start_utime..=end_utime`,
+/// the stream ends at the first one older than `start_utime`
+pub fn take_utime_range(
+    stream: impl Stream<Item = Result<RawTransaction>>,
+    start_utime: Option<i64>,
+    end_utime: Option<i64>,
+) -> impl Stream<Item = Result<RawTransaction>> {
+    stream
+        .try_skip_while(move |tx| {
+            std::future::ready(Ok(end_utime.is_some_and(|end| tx.utime > end)))
+        })
+        .try_take_while(move |tx| {
+            std::future::ready(Ok(start_utime.map_or(true, |start| tx.utime >= start)))
+        })
+}
+
+pub fn within_utime_range(
+    tx: &RawTransaction,
+    start_utime: Option<i64>,
+    end_utime: Option<i64>,
+) -> bool {
+    start_utime.map_or(true, |start| tx.utime >= start)
+        && end_utime.map_or(true, |end| tx.utime <= end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{stream, StreamExt};
+    use serde_json::json;
+
+    fn tx(lt: i64, utime: i64) -> RawTransaction {
+        serde_json::from_value(json!({
+            "@type": "raw.transaction",
+            "address": {"@type": "accountAddress", "account_address": "0:0000000000000000000000000000000000000000000000000000000000000001"},
+            "utime": utime.to_string(),
+            "data": "",
+            "transaction_id": {"@type": "internal.transactionId", "lt": lt.to_string(), "hash": ""},
+            "fee": "0",
+            "storage_fee": "0",
+            "other_fee": "0",
+            "in_msg": null,
+            "out_msgs": [],
+        }))
+        .unwrap()
+    }
+
+    /// history from new to old, two transactions at each boundary second
+    fn history() -> Vec<RawTransaction> {
+        vec![
+            tx(9, 300),
+            tx(8, 200),
+            tx(7, 200),
+            tx(6, 150),
+            tx(5, 100),
+            tx(4, 100),
+            tx(3, 99),
+            tx(2, 50),
+        ]
+    }
+
+    async fn lts(start_utime: Option<i64>, end_utime: Option<i64>) -> Vec<i64> {
+        take_utime_range(
+            stream::iter(history().into_iter().map(Ok)),
+            start_utime,
+            end_utime,
+        )
+        .map(|tx| tx.unwrap().transaction_id.lt)
+        .collect()
+        .await
+    }
+
+    #[tokio::test]
+    async fn boundaries_are_included() {
+        assert_eq!(lts(Some(100), Some(200)).await, [8, 7, 6, 5, 4]);
+        assert_eq!(lts(Some(200), Some(200)).await, [8, 7]);
+    }
+
+    #[tokio::test]
+    async fn open_ranges() {
+        assert_eq!(lts(None, Some(99)).await, [3, 2]);
+        assert_eq!(lts(Some(201), None).await, [9]);
+        assert_eq!(lts(None, None).await.len(), history().len());
+    }
+
+    #[tokio::test]
+    async fn range_between_transactions() {
+        assert!(lts(Some(101), Some(149)).await.is_empty());
+    }
+
+    #[test]
+    fn unordered_filter() {
+        let within: Vec<_> = history()
+            .iter()
+            .filter(|tx| within_utime_range(tx, Some(100), Some(200)))
+            .map(|tx| tx.transaction_id.lt)
+            .collect();
+
+        assert_eq!(within, [8, 7, 6, 5, 4]);
+    }
+
+    /// Fetches of odd ids fail, later ids finish first
+    async fn fetch_odd_fails(id: u64) -> Result<u64> {
+        tokio::time::sleep(std::time::Duration::from_millis(20 - id)).await;
+        if id % 2 == 1 {
+            Err(anyhow!("{} not found", id))
+        } else {
+            Ok(id * 10)
+        }
+    }
+
+    fn fetched(items: Vec<Result<(u64, Result<u64>)>>) -> Vec<(u64, Option<u64>)> {
+        items
+            .into_iter()
+            .map(|item| {
+                let (id, result) = item.unwrap();
+                (id, result.ok())
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn fetch_each_keeps_order_and_failures() {
+        let ids = stream::iter((0..6).map(Ok));
+
+        let items = fetch_each(ids, 10, 3, fetch_odd_fails).collect().await;
+
+        assert_eq!(
+            fetched(items),
+            [
+                (0, Some(0)),
+                (1, None),
+                (2, Some(20)),
+                (3, None),
+                (4, Some(40)),
+                (5, None)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn fetch_each_is_bounded() {
+        let in_flight = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let max_in_flight = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let fetches = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let ids = stream::iter((0..10).map(Ok));
+
+        let items: Vec<_> = fetch_each(ids, 7, 2, |id: u64| {
+            let (in_flight, max_in_flight, fetches) =
+                (in_flight.clone(), max_in_flight.clone(), fetches.clone());
+
+            async move {
+                use std::sync::atomic::Ordering::SeqCst;
+                fetches.fetch_add(1, SeqCst);
+                max_in_flight.fetch_max(in_flight.fetch_add(1, SeqCst) + 1, SeqCst);
+                tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+                in_flight.fetch_sub(1, SeqCst);
+
+                Ok(id)
+            }
+        })
+        .collect()
+        .await;
+
+        use std::sync::atomic::Ordering::SeqCst;
+        assert_eq!(items.len(), 7);
+        assert_eq!(fetches.load(SeqCst), 7);
+        assert_eq!(max_in_flight.load(SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn fetch_each_passes_id_failures() {
+        let ids = stream::iter([Ok(0), Ok(2), Err(anyhow!("block is gone")), Ok(4)]);
+
+        let items: Vec<_> = fetch_each(ids, 10, 4, fetch_odd_fails).collect().await;
+
+        let errors: Vec<_> = items
+            .iter()
+            .filter_map(|item| item.as_ref().err())
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(items.len(), 4);
+        assert_eq!(errors, ["block is gone"]);
+    }
+}
@@ -0,0 +1,236 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Message accepted by `SendMessage`, written before it is relayed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Accepted {
+    pub hash: String,
+    pub boc: String,
+    /// unix time
+    pub accepted_at: i64,
+    /// name of the api key, not the key itself
+    pub api_key: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Entry {
+    Accepted(Accepted),
+    Relayed { hash: String, error: Option<String> },
+}
+
+struct Log {
+    file: File,
+    size: u64,
+    /// accepted messages without an outcome, by hash
+    pending: HashMap<String, Accepted>,
+}
+
+/// Append-only JSON lines journal of sent messages, each entry is fsync'd before it returns.
+/// Once the file would exceed `max_size` it's rotated to `<path>.1`, replacing the previous one,
+/// and pending messages are written again to the new file, so both files stay bounded.
+pub struct SendJournal {
+    path: PathBuf,
+    max_size: u64,
+    log: Mutex<Log>,
+}
+
+impl SendJournal {
+    pub fn open(path: PathBuf, max_size: u64) -> anyhow::Result<Self> {
+        let mut pending = HashMap::new();
+        for path in [rotated(&path), path.clone()] {
+            read_entries(&path, &mut pending)?;
+        }
+        if !pending.is_empty() {
+            tracing::warn!(
+                pending = pending.len(),
+                "sent messages with unknown outcome in the journal"
+            );
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+
+        Ok(Self {
+            path,
+            max_size,
+            log: Mutex::new(Log {
+                file,
+                size,
+                pending,
+            }),
+        })
+    }
+
+    pub fn accept(&self, accepted: Accepted) -> anyhow::Result<()> {
+        let mut log = self.log.lock().unwrap();
+        self.append(&mut log, &Entry::Accepted(accepted.clone()))?;
+        log.pending.insert(accepted.hash.clone(), accepted);
+
+        Ok(())
+    }
+
+    pub fn relayed(&self, hash: &str, error: Option<String>) -> anyhow::Result<()> {
+        let mut log = self.log.lock().unwrap();
+        log.pending.remove(hash);
+
+        self.append(
+            &mut log,
+            &Entry::Relayed {
+                hash: hash.to_owned(),
+                error,
+            },
+        )
+    }
+
+    /// Accepted messages without an outcome, the oldest first
+    pub fn pending(&self) -> Vec<Accepted> {
+        let mut pending: Vec<_> = self.log.lock().unwrap().pending.values().cloned().collect();
+        pending.sort_by(|lhs, rhs| lhs.accepted_at.cmp(&rhs.accepted_at));
+
+        pending
+    }
+
+    pub fn get_pending(&self, hash: &str) -> Option<Accepted> {
+        self.log.lock().unwrap().pending.get(hash).cloned()
+    }
+
+    fn append(&self, log: &mut Log, entry: &Entry) -> anyhow::Result<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+
+        if log.size > 0 && log.size + line.len() as u64 > self.max_size {
+            self.rotate(log)?;
+        }
+
+        log.file.write_all(&line)?;
+        log.file.sync_data()?;
+        log.size += line.len() as u64;
+
+        Ok(())
+    }
+
+    fn rotate(&self, log: &mut Log) -> anyhow::Result<()> {
+        std::fs::rename(&self.path, rotated(&self.path))?;
+        log.file = OpenOptions::new()
+            .create(true)
+            .truncate(true)
+            .write(true)
+            .open(&self.path)?;
+        log.size = 0;
+
+        let mut pending: Vec<_> = log.pending.values().cloned().collect();
+        pending.sort_by(|lhs, rhs| lhs.accepted_at.cmp(&rhs.accepted_at));
+        for accepted in pending {
+            let mut line = serde_json::to_vec(&Entry::Accepted(accepted))?;
+            line.push(b'\n');
+            log.file.write_all(&line)?;
+            log.size += line.len() as u64;
+        }
+        log.file.sync_data()?;
+        tracing::info!(path = ?self.path, "send journal rotated");
+
+        Ok(())
+    }
+}
+
+fn rotated(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".1");
+
+    PathBuf::from(name)
+}
+
+/// A line cut by a crash is skipped
+fn read_entries(path: &Path, pending: &mut HashMap<String, Accepted>) -> anyhow::Result<()> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+
+    for line in BufReader::new(file).lines() {
+        match serde_json::from_str(&line?) {
+            Ok(Entry::Accepted(accepted)) => {
+                pending.insert(accepted.hash.clone(), accepted);
+            }
+            Ok(Entry::Relayed { hash, .. }) => {
+                pending.remove(&hash);
+            }
+            Err(e) => tracing::warn!(error = ?e, ?path, "skipped broken send journal entry"),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("journal-{}-{}.jsonl", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(rotated(&path));
+
+        path
+    }
+
+    fn accepted(hash: &str, accepted_at: i64) -> Accepted {
+        Accepted {
+            hash: hash.to_owned(),
+            boc: "te6cckEBAQEAAgAAAEysuc0=".to_owned(),
+            accepted_at,
+            api_key: Some("acme".to_owned()),
+        }
+    }
+
+    #[test]
+    fn pending_messages_survive_restart() {
+        let path = path("restart");
+        let journal = SendJournal::open(path.clone(), 1024 * 1024).unwrap();
+        journal.accept(accepted("a", 1)).unwrap();
+        journal.accept(accepted("b", 2)).unwrap();
+        journal.relayed("a", None).unwrap();
+        drop(journal);
+
+        // a crash in the middle of a write
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"{\"type\":\"acc").unwrap();
+        drop(file);
+
+        let journal = SendJournal::open(path.clone(), 1024 * 1024).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(journal.pending(), vec![accepted("b", 2)]);
+        assert_eq!(journal.get_pending("b"), Some(accepted("b", 2)));
+        assert_eq!(journal.get_pending("a"), None);
+    }
+
+    #[test]
+    fn rotation_keeps_pending_messages() {
+        let path = path("rotation");
+        let journal = SendJournal::open(path.clone(), 512).unwrap();
+        journal.accept(accepted("pending", 0)).unwrap();
+        for i in 1..100 {
+            let hash = format!("hash-{}", i);
+            journal.accept(accepted(&hash, i)).unwrap();
+            journal.relayed(&hash, Some("timeout".to_owned())).unwrap();
+        }
+        drop(journal);
+
+        assert!(std::fs::metadata(&path).unwrap().len() <= 512);
+        assert!(std::fs::metadata(rotated(&path)).unwrap().len() <= 512);
+
+        let journal = SendJournal::open(path.clone(), 512).unwrap();
+        std::fs::remove_file(rotated(&path)).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(journal.pending(), vec![accepted("pending", 0)]);
+    }
+}
@@ -0,0 +1,180 @@
+use serde::de::DeserializeOwned;
+use thiserror::Error;
+use tonic::Status;
+
+/// Bounds of JSON passed in request parameters, e.g. the stack of RunGetMethod,
+/// checked before it's deserialized
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JsonLimits {
+    /// bytes of the whole parameter
+    pub max_size: usize,
+    /// arrays and objects nested in one another
+    pub max_depth: usize,
+    /// elements of an array
+    pub max_array_len: usize,
+    /// bytes of a string as written, escapes included, e.g. a boc or a hash
+    pub max_string_len: usize,
+}
+
+impl Default for JsonLimits {
+    fn default() -> Self {
+        Self {
+            max_size: 1024 * 1024,
+            max_depth: 32,
+            max_array_len: 1024,
+            max_string_len: 256 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum LimitError {
+    #[error("size exceeds max_size of {0} bytes")]
+    Size(usize),
+    #[error("nesting exceeds max_depth of {0}")]
+    Depth(usize),
+    #[error("array exceeds max_array_len of {0} elements")]
+    ArrayLength(usize),
+    #[error("string exceeds max_string_len of {0} bytes")]
+    StringLength(usize),
+}
+
+/// Scans `json` against `limits` without building it, so that serde never recurses into
+/// a payload over them. Malformed JSON is let through for the parser to reject.
+pub fn check(json: &str, limits: &JsonLimits) -> Result<(), LimitError> {
+    if json.len() > limits.max_size {
+        return Err(LimitError::Size(limits.max_size));
+    }
+
+    // commas of each open array, none for objects
+    let mut open: Vec<Option<usize>> = Vec::new();
+    // bytes of the string being scanned
+    let mut string: Option<usize> = None;
+    let mut escaped = false;
+    for &byte in json.as_bytes() {
+        if let Some(len) = &mut string {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => {
+                    string = None;
+                    continue;
+                }
+                _ => {}
+            }
+            *len += 1;
+            if *len > limits.max_string_len {
+                return Err(LimitError::StringLength(limits.max_string_len));
+            }
+
+            continue;
+        }
+
+        match byte {
+            b'"' => string = Some(0),
+            b'[' | b'{' => {
+                open.push((byte == b'[').then_some(0));
+                if open.len() > limits.max_depth {
+                    return Err(LimitError::Depth(limits.max_depth));
+                }
+            }
+            b']' | b'}' => {
+                open.pop();
+            }
+            b',' => {
+                if let Some(Some(commas)) = open.last_mut() {
+                    *commas += 1;
+                    if *commas >= limits.max_array_len {
+                        return Err(LimitError::ArrayLength(limits.max_array_len));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Deserializes the request parameter `param` within `limits`, both a violated limit
+/// and malformed JSON are `INVALID_ARGUMENT` naming the parameter
+pub fn from_str<T: DeserializeOwned>(
+    param: &str,
+    json: &str,
+    limits: &JsonLimits,
+) -> Result<T, Status> {
+    check(json, limits).map_err(|e| Status::invalid_argument(format!("{}: {}", param, e)))?;
+
+    serde_json::from_str(json).map_err(|e| Status::invalid_argument(format!("{}: {}", param, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tonic::Code;
+
+    fn limits() -> JsonLimits {
+        JsonLimits {
+            max_size: 64,
+            max_depth: 3,
+            max_array_len: 3,
+            max_string_len: 8,
+        }
+    }
+
+    #[test]
+    fn within_limits() {
+        assert_eq!(check(r#"[{"a": [1, 2, 3]}]"#, &limits()), Ok(()));
+        assert_eq!(
+            check(r#"{"a": 1, "b": 2, "c": 3, "d": 4}"#, &limits()),
+            Ok(())
+        );
+        assert_eq!(check(r#"["12345678"]"#, &limits()), Ok(()));
+        assert_eq!(check("[]", &limits()), Ok(()));
+    }
+
+    #[test]
+    fn violated_limit_is_named() {
+        assert_eq!(
+            check(&format!("[{}]", " ".repeat(64)), &limits()),
+            Err(LimitError::Size(64))
+        );
+        assert_eq!(
+            check(r#"[[{"a": [1, 2, 3]}]]"#, &limits()),
+            Err(LimitError::Depth(3))
+        );
+        assert_eq!(
+            check("[1, 2, 3, 4]", &limits()),
+            Err(LimitError::ArrayLength(3))
+        );
+        assert_eq!(
+            check(r#"["123456789"]"#, &limits()),
+            Err(LimitError::StringLength(8))
+        );
+
+        let error = from_str::<serde_json::Value>("stack", "[[[[]]]]", &limits()).unwrap_err();
+        assert_eq!(error.code(), Code::InvalidArgument);
+        assert_eq!(error.message(), "stack: nesting exceeds max_depth of 3");
+    }
+
+    #[test]
+    fn brackets_in_strings_are_not_nesting() {
+        assert_eq!(check(r#"["[[[[", "]]]]"]"#, &limits()), Ok(()));
+        assert_eq!(check(r#"["\"[[[["]"#, &limits()), Ok(()));
+        assert_eq!(check(r#"["a,b,c,d"]"#, &limits()), Ok(()));
+    }
+
+    #[test]
+    fn deep_nesting_is_rejected_without_recursion() {
+        let json = "[".repeat(2 * 1024 * 1024);
+
+        assert_eq!(
+            check(&json, &JsonLimits::default()),
+            Err(LimitError::Size(1024 * 1024))
+        );
+        assert_eq!(
+            check(&json[..100_000], &JsonLimits::default()),
+            Err(LimitError::Depth(32))
+        );
+    }
+}
@@ -0,0 +1,49 @@
+pub mod account;
+pub mod account_state;
+pub mod account_stats;
+pub mod admin;
+pub mod block;
+pub mod cache;
+pub mod call_options;
+pub mod canonical;
+pub mod chain_stats;
+pub mod check;
+pub mod code;
+pub mod cursor;
+pub mod deadline;
+pub mod emulate;
+pub mod export;
+pub mod export_file;
+pub mod fees;
+pub mod helpers;
+pub mod journal;
+pub mod json;
+pub mod limits;
+pub mod listen;
+pub mod maintenance;
+pub mod masterchain;
+pub mod memory;
+pub mod message;
+pub mod methods;
+pub mod network;
+#[cfg(feature = "otel")]
+pub mod otel;
+pub mod panic;
+pub mod peers;
+pub mod prefetch;
+pub mod quota;
+pub mod recent;
+pub mod rejected;
+pub mod replica;
+pub mod send_queue;
+pub mod server;
+pub mod session;
+pub mod shards;
+pub mod stale;
+pub mod summary;
+pub mod tls;
+#[allow(clippy::enum_variant_names, clippy::large_enum_variant)]
+pub mod ton;
+pub mod trace;
+pub mod watch;
+pub mod webhook;
@@ -0,0 +1,296 @@
+use anyhow::anyhow;
+use std::collections::HashMap;
+use tonic::Status;
+
+pub const ACCOUNT_TRANSACTIONS_PAGE_LIMIT: &str = "GetAccountTransactionsPage.limit";
+pub const WAIT_FOR_TRANSACTION_TIMEOUT_MS: &str = "WaitForTransaction.timeout_ms";
+pub const FETCH_EXPORT_CHUNK_LIMIT: &str = "FetchExportChunk.limit";
+pub const MESSAGE_TRACE_MAX_DEPTH: &str = "GetMessageTrace.max_depth";
+pub const FULL_TRANSACTIONS_COUNT: &str = "GetFullTransactions.count";
+pub const FEE_STATS_BLOCKS: &str = "GetFeeStats.blocks";
+pub const ACCOUNT_STATS_EXACT_COUNT_LIMIT: &str = "GetAccountStats.exact_count_limit";
+pub const MASTERCHAIN_INFO_TIMEOUT_MS: &str = "GetMasterchainInfo.timeout_ms";
+pub const SHARD_HIERARCHY_BLOCKS: &str = "GetShardHierarchy.blocks";
+
+/// Max encoded size of a response, the global one applies to methods without their own limit
+#[derive(Debug, Clone)]
+pub struct ResponseSizeLimits {
+    global: usize,
+    per_method: HashMap<String, usize>,
+}
+
+impl ResponseSizeLimits {
+    pub fn new(global: usize, per_method: impl IntoIterator<Item = (String, usize)>) -> Self {
+        Self {
+            global,
+            per_method: per_method.into_iter().collect(),
+        }
+    }
+
+    pub fn global(&self) -> usize {
+        self.global
+    }
+
+    pub fn for_method(&self, method: &str) -> usize {
+        self.per_method.get(method).copied().unwrap_or(self.global)
+    }
+}
+
+impl Default for ResponseSizeLimits {
+    fn default() -> Self {
+        Self::new(16 * 1024 * 1024, [])
+    }
+}
+
+/// Parses `Method=bytes`
+pub fn parse_method_limit(s: &str) -> anyhow::Result<(String, usize)> {
+    let (method, limit) = s
+        .split_once('=')
+        .ok_or_else(|| anyhow!("expected Method=bytes, got {}", s))?;
+
+    Ok((method.to_owned(), limit.parse()?))
+}
+
+/// Default and max of a numeric request parameter, zero stands for a missing value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParamLimit {
+    pub default: i64,
+    pub max: i64,
+}
+
+impl ParamLimit {
+    pub fn apply(&self, param: &str, value: i64) -> Result<i64, Status> {
+        match value {
+            0 => Ok(self.default),
+            v if v < 0 => Err(Status::invalid_argument(format!(
+                "{} must not be negative",
+                param
+            ))),
+            v if v > self.max => Err(Status::invalid_argument(format!(
+                "{} must not exceed {}",
+                param, self.max
+            ))),
+            v => Ok(v),
+        }
+    }
+}
+
+/// Limits of request parameters keyed by `Method.param`
+#[derive(Debug, Clone)]
+pub struct ParamLimits(HashMap<&'static str, ParamLimit>);
+
+impl ParamLimits {
+    pub fn with_overrides(
+        overrides: impl IntoIterator<Item = (String, ParamLimit)>,
+    ) -> anyhow::Result<Self> {
+        let mut limits = Self::default();
+        for (key, limit) in overrides {
+            if limit.default <= 0 || limit.default > limit.max {
+                return Err(anyhow!("{}: default must be in 1..={}", key, limit.max));
+            }
+
+            let entry = limits
+                .0
+                .iter_mut()
+                .find(|(k, _)| **k == key)
+                .ok_or_else(|| anyhow!("unknown parameter {}", key))?;
+            *entry.1 = limit;
+        }
+
+        Ok(limits)
+    }
+
+    pub fn get(&self, key: &str) -> Option<ParamLimit> {
+        self.0.get(key).copied()
+    }
+
+    /// [`ParamLimit::apply`] of the limit of `key`, a key without a limit is a bug of the server
+    pub fn apply(&self, key: &str, param: &str, value: i64) -> Result<i64, Status> {
+        self.get(key)
+            .ok_or_else(|| Status::internal(format!("no limit of {}", key)))?
+            .apply(param, value)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, ParamLimit)> + '_ {
+        self.0.iter().map(|(k, v)| (*k, *v))
+    }
+}
+
+impl Default for ParamLimits {
+    fn default() -> Self {
+        Self(HashMap::from([
+            (
+                ACCOUNT_TRANSACTIONS_PAGE_LIMIT,
+                ParamLimit {
+                    default: 10,
+                    max: 100,
+                },
+            ),
+            (
+                FETCH_EXPORT_CHUNK_LIMIT,
+                ParamLimit {
+                    default: 100,
+                    max: 1000,
+                },
+            ),
+            (
+                FULL_TRANSACTIONS_COUNT,
+                ParamLimit {
+                    default: 256,
+                    max: 4096,
+                },
+            ),
+            (
+                FEE_STATS_BLOCKS,
+                ParamLimit {
+                    default: 4,
+                    max: 16,
+                },
+            ),
+            (
+                ACCOUNT_STATS_EXACT_COUNT_LIMIT,
+                ParamLimit {
+                    default: 256,
+                    max: 4096,
+                },
+            ),
+            (
+                MESSAGE_TRACE_MAX_DEPTH,
+                ParamLimit {
+                    default: 4,
+                    max: 16,
+                },
+            ),
+            (
+                SHARD_HIERARCHY_BLOCKS,
+                ParamLimit {
+                    default: 100,
+                    max: 1000,
+                },
+            ),
+            (
+                MASTERCHAIN_INFO_TIMEOUT_MS,
+                ParamLimit {
+                    default: 25_000,
+                    max: 25_000,
+                },
+            ),
+            (
+                WAIT_FOR_TRANSACTION_TIMEOUT_MS,
+                ParamLimit {
+                    default: 10_000,
+                    max: 25_000,
+                },
+            ),
+        ]))
+    }
+}
+
+/// Parses `Method.param=default:max`
+pub fn parse_param_limit(s: &str) -> anyhow::Result<(String, ParamLimit)> {
+    let (key, limit) = s
+        .split_once('=')
+        .ok_or_else(|| anyhow!("expected Method.param=default:max, got {}", s))?;
+    let (default, max) = limit
+        .split_once(':')
+        .ok_or_else(|| anyhow!("expected Method.param=default:max, got {}", s))?;
+
+    Ok((
+        key.to_owned(),
+        ParamLimit {
+            default: default.parse()?,
+            max: max.parse()?,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn method_limit_overrides_global() {
+        let limits = ResponseSizeLimits::new(100, [("GetAccountTransactionsPage".to_owned(), 10)]);
+
+        assert_eq!(limits.for_method("GetAccountTransactionsPage"), 10);
+        assert_eq!(limits.for_method("GetAccountState"), 100);
+    }
+
+    #[test]
+    fn parse_method_limit_valid() {
+        assert_eq!(
+            parse_method_limit("GetAccountTransactionsPage=1024").unwrap(),
+            ("GetAccountTransactionsPage".to_owned(), 1024)
+        );
+        assert!(parse_method_limit("GetAccountTransactionsPage").is_err());
+        assert!(parse_method_limit("GetAccountTransactionsPage=big").is_err());
+    }
+
+    #[test]
+    fn param_limit_boundaries() {
+        let limits = ParamLimits::default();
+        let page = limits.get(ACCOUNT_TRANSACTIONS_PAGE_LIMIT).unwrap();
+        let wait = limits.get(WAIT_FOR_TRANSACTION_TIMEOUT_MS).unwrap();
+
+        assert_eq!(page.apply("limit", 0).unwrap(), 10);
+        assert_eq!(page.apply("limit", 1).unwrap(), 1);
+        assert_eq!(page.apply("limit", 100).unwrap(), 100);
+        assert!(page.apply("limit", 101).is_err());
+        assert!(page.apply("limit", -1).is_err());
+
+        assert_eq!(wait.apply("timeout_ms", 0).unwrap(), 10_000);
+        assert_eq!(wait.apply("timeout_ms", 25_000).unwrap(), 25_000);
+        assert!(wait.apply("timeout_ms", 25_001).is_err());
+        assert!(wait.apply("timeout_ms", -1).is_err());
+    }
+
+    #[test]
+    fn param_limit_error_names_max() {
+        let error = ParamLimits::default()
+            .apply(ACCOUNT_TRANSACTIONS_PAGE_LIMIT, "limit", 65535)
+            .unwrap_err();
+
+        assert_eq!(error.code(), tonic::Code::InvalidArgument);
+        assert_eq!(error.message(), "limit must not exceed 100");
+    }
+
+    #[test]
+    fn param_limits_overrides() {
+        let limits = ParamLimits::with_overrides([parse_param_limit(
+            "GetAccountTransactionsPage.limit=20:1000",
+        )
+        .unwrap()])
+        .unwrap();
+
+        assert_eq!(
+            limits.get(ACCOUNT_TRANSACTIONS_PAGE_LIMIT),
+            Some(ParamLimit {
+                default: 20,
+                max: 1000
+            })
+        );
+        assert!(
+            ParamLimits::with_overrides([parse_param_limit("Unknown.limit=1:2").unwrap()]).is_err()
+        );
+        assert!(ParamLimits::with_overrides([parse_param_limit(
+            "GetAccountTransactionsPage.limit=3:2"
+        )
+        .unwrap()])
+        .is_err());
+        assert!(parse_param_limit("GetAccountTransactionsPage.limit=3").is_err());
+    }
+
+    #[test]
+    fn unknown_param_limit() {
+        let limits = ParamLimits::default();
+
+        assert_eq!(limits.get("Unknown.limit"), None);
+        assert_eq!(
+            limits
+                .apply("Unknown.limit", "limit", 1)
+                .unwrap_err()
+                .code(),
+            tonic::Code::Internal
+        );
+    }
+}
@@ -0,0 +1,170 @@
+use crate::tls::TlsIncoming;
+use anyhow::{anyhow, bail};
+use std::fmt::{Display, Formatter};
+use std::fs::Permissions;
+use std::net::SocketAddr;
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tokio::net::UnixListener;
+
+const UNIX_SCHEME: &str = "unix://";
+
+/// Endpoint the server listens on, `host:port` or `unix:///path/to.sock`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Listen {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+impl FromStr for Listen {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix(UNIX_SCHEME) {
+            Some(path) if path.starts_with('/') => Ok(Listen::Unix(PathBuf::from(path))),
+            Some(_) => Err(anyhow!("unix socket path must be absolute: {}", s)),
+            None => Ok(Listen::Tcp(s.parse()?)),
+        }
+    }
+}
+
+impl Display for Listen {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Listen::Tcp(addr) => write!(f, "{}", addr),
+            Listen::Unix(path) => write!(f, "{}{}", UNIX_SCHEME, path.display()),
+        }
+    }
+}
+
+/// Listener bound for the server, plain TCP ones are bound by tonic itself
+pub enum Bound {
+    Tcp(SocketAddr),
+    Tls(TlsIncoming),
+    Unix(UnixListener),
+}
+
+/// Permissions of a socket file in octal, e.g. 660
+pub fn parse_mode(s: &str) -> anyhow::Result<u32> {
+    let mode = u32::from_str_radix(s.trim_start_matches("0o"), 8)?;
+    if mode > 0o777 {
+        bail!("mode {} is out of range", s);
+    }
+
+    Ok(mode)
+}
+
+/// Socket file of a bound unix listener, removed on drop
+#[derive(Debug)]
+pub struct SocketFile(PathBuf);
+
+impl Drop for SocketFile {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.0) {
+            tracing::warn!(path = ?self.0, error = ?e, "failed to remove socket");
+        }
+    }
+}
+
+/// Binds a unix socket at `path` with `mode` permissions. A socket left by a previous
+/// run is replaced, a socket something still listens on or any other file is an error.
+pub fn bind_unix(path: &Path, mode: u32) -> anyhow::Result<(UnixListener, SocketFile)> {
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            bail!("{} exists and isn't a socket", path.display());
+        }
+        if std::os::unix::net::UnixStream::connect(path).is_ok() {
+            bail!("{} is in use", path.display());
+        }
+
+        tracing::info!(path = ?path, "removing stale socket");
+        std::fs::remove_file(path)?;
+    }
+
+    let listener = UnixListener::bind(path)?;
+    let file = SocketFile(path.to_owned());
+    std::fs::set_permissions(path, Permissions::from_mode(mode))?;
+
+    Ok((listener, file))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn socket_path() -> PathBuf {
+        std::env::temp_dir().join(format!("ton-grpc-{}.sock", Uuid::new_v4()))
+    }
+
+    #[test]
+    fn parse_listen() {
+        assert_eq!(
+            "0.0.0.0:50052".parse::<Listen>().unwrap(),
+            Listen::Tcp("0.0.0.0:50052".parse().unwrap())
+        );
+        assert_eq!(
+            "unix:///run/ton-rpc.sock".parse::<Listen>().unwrap(),
+            Listen::Unix(PathBuf::from("/run/ton-rpc.sock"))
+        );
+        assert_eq!(
+            Listen::Unix(PathBuf::from("/run/ton-rpc.sock")).to_string(),
+            "unix:///run/ton-rpc.sock"
+        );
+        assert!("unix://ton-rpc.sock".parse::<Listen>().is_err());
+        assert!("localhost".parse::<Listen>().is_err());
+    }
+
+    #[test]
+    fn parse_octal_mode() {
+        assert_eq!(parse_mode("660").unwrap(), 0o660);
+        assert_eq!(parse_mode("0o600").unwrap(), 0o600);
+        assert!(parse_mode("980").is_err());
+        assert!(parse_mode("1777").is_err());
+    }
+
+    #[tokio::test]
+    async fn socket_is_removed_on_drop() {
+        let path = socket_path();
+
+        let (listener, file) = bind_unix(&path, 0o600).unwrap();
+        let metadata = std::fs::metadata(&path).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+
+        drop(listener);
+        drop(file);
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn stale_socket_is_replaced() {
+        let path = socket_path();
+        let stale = std::os::unix::net::UnixListener::bind(&path).unwrap();
+        drop(stale);
+
+        let (_listener, _file) = bind_unix(&path, 0o660).unwrap();
+
+        tokio::net::UnixStream::connect(&path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn socket_in_use_is_error() {
+        let path = socket_path();
+        let (_listener, _file) = bind_unix(&path, 0o660).unwrap();
+
+        assert!(bind_unix(&path, 0o660).is_err());
+        assert!(path.exists());
+    }
+
+    #[tokio::test]
+    async fn regular_file_is_kept() {
+        let path = socket_path();
+        std::fs::write(&path, "data").unwrap();
+
+        assert!(bind_unix(&path, 0o660).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "data");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,32 +1,96 @@
-mod account;
-mod block;
-mod helpers;
-mod message;
-#[allow(clippy::enum_variant_names)]
-mod ton;
-
-use crate::account::AccountService;
-use crate::block::BlockService;
-use crate::message::MessageService;
-use crate::ton::account_service_server::AccountServiceServer;
-use crate::ton::block_service_server::BlockServiceServer;
-use crate::ton::message_service_server::MessageServiceServer;
+use anyhow::anyhow;
 use clap::Parser;
+use futures::future::try_join_all;
 use metrics_exporter_prometheus::PrometheusBuilder;
+use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Duration;
-use tonic::codec::CompressionEncoding::Gzip;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio_stream::wrappers::UnixListenerStream;
+use ton_contract::abi::AbiRegistry;
+use ton_grpc::account::AccountService;
+use ton_grpc::admin::{admin_key_interceptor, AdminService};
+use ton_grpc::block::BlockService;
+use ton_grpc::cache::CachePolicy;
+use ton_grpc::call_options::{parse_method_retries, parse_method_timeout, MethodCallOptions};
+use ton_grpc::chain_stats::{sample_chain, ChainStats, ChainStatsPolicy};
+use ton_grpc::check;
+use ton_grpc::cursor::Cursors;
+use ton_grpc::export::ExportJobs;
+use ton_grpc::fees::{FeeConfigs, FeeStats};
+use ton_grpc::journal::SendJournal;
+use ton_grpc::json::JsonLimits;
+use ton_grpc::limits::{
+    parse_method_limit, parse_param_limit, ParamLimit, ParamLimits, ResponseSizeLimits,
+};
+use ton_grpc::listen::{bind_unix, parse_mode, Bound, Listen};
+use ton_grpc::maintenance::{report_maintenance, Maintenance};
+use ton_grpc::masterchain::MasterchainWatcher;
+use ton_grpc::memory::{enforce_budget, ClientCaches, MemoryBudget, Tier};
+use ton_grpc::message::{MessageService, SentMessages};
+use ton_grpc::methods::MethodPolicy;
+use ton_grpc::network::{expected_zero_state, parse_network, parse_zero_state};
+#[cfg(feature = "otel")]
+use ton_grpc::otel;
+use ton_grpc::peers::{check_peers, PeerCheckPolicy};
+use ton_grpc::prefetch::{prefetch, HotAddresses, PrefetchPolicy};
+use ton_grpc::quota::{load_api_keys, parse_method_cost, MemoryUsageStore};
+use ton_grpc::recent::{RecentMessages, RecentMessagesPolicy};
+use ton_grpc::rejected::RejectedMessages;
+use ton_grpc::replica::{Upstream, UpstreamPolicy};
+use ton_grpc::send_queue::SendQueues;
+use ton_grpc::server::{NetworkServices, ServerBuilder};
+use ton_grpc::session::Sessions;
+use ton_grpc::shards::ShardHistory;
+use ton_grpc::stale::{report_degraded, StaleStates};
+use ton_grpc::tls::{ReloadableTls, TlsConnectInfo, TlsFiles};
+use ton_grpc::ton::account_service_server::AccountServiceServer;
+use ton_grpc::ton::admin_service_server::AdminServiceServer;
+use ton_grpc::ton::block_service_server::BlockServiceServer;
+use ton_grpc::ton::message_service_server::MessageServiceServer;
+use ton_grpc::watch::AccountWatchers;
+use ton_grpc::webhook::{DeliveryPolicy, WebhookService, Webhooks};
+use tonic::server::NamedService;
 use tonic::transport::Server;
+use tonic_health::ServingStatus;
+use tonlibjson_client::breaker::BreakerPolicy;
+use tonlibjson_client::keepalive::KeepalivePolicy;
+use tonlibjson_client::replica::{BlockData, Replica};
+use tonlibjson_client::send::SendRetryPolicy;
+use tonlibjson_client::shadow::{Shadow, ShadowPolicy, SHADOWED_METHODS};
 use tonlibjson_client::ton::TonClientBuilder;
+use tonlibjson_client::transport::Backend;
+use tonlibjson_client::zero_state::ZeroState;
 use tracing_subscriber::fmt::format::FmtSpan;
-use tracing_subscriber::EnvFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
 use url::Url;
+use uuid::Uuid;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
+    /// `host:port` or `unix:///path/to.sock`, repeat to listen on several endpoints
     #[clap(long, default_value = "0.0.0.0:50052")]
-    listen: SocketAddr,
+    listen: Vec<Listen>,
+    /// Permissions of unix sockets in octal
+    #[clap(long, value_parser = parse_mode, default_value = "660")]
+    listen_mode: u32,
+    /// PEM certificate chain, TCP listeners serve TLS instead of plain HTTP/2 when set
+    #[clap(long, requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+    /// PEM private key of --tls-cert
+    #[clap(long, requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+    /// PEM CA certificates, only clients presenting a certificate signed by them are accepted
+    #[clap(long, requires = "tls_cert")]
+    tls_client_ca: Option<PathBuf>,
+    /// How often TLS files are checked for changes, SIGHUP reloads them right away
+    #[clap(long, value_parser = humantime::parse_duration, default_value = "60s")]
+    tls_reload_interval: Duration,
     #[clap(long, value_parser = humantime::parse_duration, default_value = "30s")]
     timeout: Duration,
     #[clap(long, value_parser = humantime::parse_duration, default_value = "300s")]
@@ -40,6 +104,21 @@ struct Args {
     #[clap(long, default_value = "65535")]
     initial_stream_window_size: u32,
 
+    /// Exports traces to this OTLP gRPC collector, e.g. http://localhost:4317,
+    /// traces of requests with a W3C traceparent header are continued
+    #[cfg(feature = "otel")]
+    #[clap(long)]
+    otlp_endpoint: Option<Url>,
+    /// Spans exported over OTLP, in the syntax of RUST_LOG
+    #[cfg(feature = "otel")]
+    #[clap(long, default_value = "info,tonlibjson_client=debug")]
+    otlp_filter: String,
+
+    /// Checks the default network end to end without serving anything, prints a JSON report
+    /// and exits with a non-zero code if any step fails
+    #[clap(long)]
+    check: bool,
+
     #[clap(long)]
     enable_metrics: bool,
     #[clap(long, default_value = "0.0.0.0:9000")]
@@ -47,6 +126,24 @@ struct Args {
 
     #[clap(long, value_parser = Url::parse, default_value_t = tonlibjson_client::ton::default_ton_config_url())]
     ton_config_url: Url,
+    /// How often the config is read again to add and remove lite servers,
+    /// see AdminService.RefreshConfig to read it at once
+    #[clap(long, default_value = "60s", value_parser = humantime::parse_duration)]
+    ton_config_refresh_interval: Duration,
+    /// Serves a named network, e.g. testnet=https://ton.org/testnet-global.config.json,
+    /// the default network is served from --ton-config-url if none is given
+    #[clap(long, value_parser = parse_network)]
+    network: Vec<(String, Url)>,
+    /// Network of requests without the x-ton-network header
+    #[clap(long, default_value = "mainnet")]
+    default_network: String,
+    /// Zero state a network must start from as name=root_hash:file_hash,
+    /// mainnet and testnet are known
+    #[clap(long, value_parser = parse_zero_state)]
+    zero_state: Vec<(String, ZeroState)>,
+    /// Skips the zero state check, for private networks
+    #[clap(long)]
+    allow_custom_network: bool,
     #[clap(long, value_parser = humantime::parse_duration, default_value = "10s")]
     ton_timeout: Duration,
     #[clap(long, value_parser = humantime::parse_duration, default_value = "10s")]
@@ -60,20 +157,356 @@ struct Args {
     #[clap(long, value_parser = humantime::parse_duration, default_value = "4096ms")]
     retry_max_delay: Duration,
 
+    /// Consecutive upstream failures which open the circuit of reads
+    #[clap(long, default_value_t = 20)]
+    breaker_failures: u32,
+    /// How long the circuit of reads stays open until a probe is let through
+    #[clap(long, value_parser = humantime::parse_duration, default_value = "5s")]
+    breaker_open_for: Duration,
+    /// Consecutive upstream failures which open the circuit of sent messages
+    #[clap(long, default_value_t = 5)]
+    send_breaker_failures: u32,
+    /// How long the circuit of sent messages stays open until a probe is let through
+    #[clap(long, value_parser = humantime::parse_duration, default_value = "10s")]
+    send_breaker_open_for: Duration,
+    /// Sends of a message on connectivity errors including the first one,
+    /// rejected messages aren't sent again
+    #[clap(long, default_value_t = 3)]
+    send_attempts: u32,
+
     #[clap(long, value_parser = humantime::parse_duration, default_value = "70ms")]
     ewma_default_rtt: Duration,
     #[clap(long, value_parser = humantime::parse_duration, default_value = "1ms")]
     ewma_decay: Duration,
+    /// Route requests to the healthy lite server with the lowest p95 latency
+    #[clap(long)]
+    latency_aware_routing: bool,
+    /// Never route requests of a block to lite servers this many masterchain blocks behind the pool
+    #[clap(long)]
+    max_seqno_lag: Option<i32>,
+    /// Probes connections to lite servers without a response for this long, a lite server
+    /// missing a probe isn't routed to and its connection is rebuilt
+    #[clap(long, value_parser = humantime::parse_duration)]
+    keepalive_idle: Option<Duration>,
+    /// How long a keepalive probe waits for the response
+    #[clap(long, value_parser = humantime::parse_duration, default_value = "5s")]
+    keepalive_timeout: Duration,
+    /// Rejects requests to workchains and doesn't track their shards
+    #[clap(long)]
+    masterchain_only: bool,
+
+    #[clap(long)]
+    verify_blocks: bool,
+
+    /// Records tonlib requests and responses of each network to <dir>/<network>.jsonl
+    #[clap(long)]
+    record_fixtures_dir: Option<PathBuf>,
+
+    #[clap(long, default_value_t = 16 * 1024 * 1024)]
+    max_response_size: usize,
+    /// Overrides --max-response-size for a single method, e.g. GetAccountTransactionsPage=4194304
+    #[clap(long, value_parser = parse_method_limit)]
+    method_max_response_size: Vec<(String, usize)>,
+    /// Timeout of the lite server calls of a method, their retries included,
+    /// e.g. GetAccountState=5s, overrides the client timeout
+    #[clap(long, value_parser = parse_method_timeout)]
+    method_call_timeout: Vec<(String, Duration)>,
+    /// Retries of the lite server calls of a method, e.g. SendMessage=0 to report a failed send
+    /// at once, the retry budget still applies
+    #[clap(long, value_parser = parse_method_retries)]
+    method_max_retries: Vec<(String, u32)>,
+
+    /// Overrides default and max of a request parameter, e.g. GetAccountTransactionsPage.limit=10:100
+    #[clap(long, value_parser = parse_param_limit)]
+    param_limit: Vec<(String, ParamLimit)>,
+    /// Bytes of a JSON request parameter, e.g. the stack of RunGetMethod
+    #[clap(long, default_value_t = 1024 * 1024)]
+    json_max_size: usize,
+    /// Arrays and objects nested in one another in a JSON request parameter
+    #[clap(long, default_value_t = 32)]
+    json_max_depth: usize,
+    #[clap(long, default_value_t = 1024)]
+    json_max_array_len: usize,
+    /// Bytes of a string in a JSON request parameter, e.g. a boc
+    #[clap(long, default_value_t = 256 * 1024)]
+    json_max_string_len: usize,
+
+    /// Deduplicates messages sent again within this window, disabled if missing
+    #[clap(long, value_parser = humantime::parse_duration)]
+    send_dedup_ttl: Option<Duration>,
+    #[clap(long, default_value_t = 100_000)]
+    send_dedup_capacity: usize,
+    /// Relays messages to the same wallet one at a time so they don't race for its seqno
+    #[clap(long)]
+    send_serialize: bool,
+    /// Messages which may wait behind the one relayed to a wallet
+    #[clap(long, default_value_t = 16)]
+    send_queue_depth: usize,
+    #[clap(long, value_parser = humantime::parse_duration, default_value = "30s")]
+    send_queue_wait: Duration,
+    /// Wallets which may have messages queued at once
+    #[clap(long, default_value_t = 10_000)]
+    send_queue_wallets: usize,
+    /// Journals sent messages of each network to <dir>/<network>.jsonl before they are relayed,
+    /// AdminService lists the ones with an unknown outcome after a crash
+    #[clap(long)]
+    send_journal_dir: Option<PathBuf>,
+    /// Size of a journal file until it's rotated to <network>.jsonl.1
+    #[clap(long, default_value_t = 64 * 1024 * 1024)]
+    send_journal_max_size: u64,
+    /// Keeps messages rejected by the lite server up to this many bytes per network for
+    /// AdminService, off if missing since they may be private
+    #[clap(long)]
+    rejected_messages_bytes: Option<usize>,
+    #[clap(long, value_parser = humantime::parse_duration, default_value = "24h")]
+    rejected_messages_retention: Duration,
+    /// Keeps this many messages sent per network for GetRecentMessages and WatchRecentMessages,
+    /// off if missing
+    #[clap(long)]
+    recent_messages: Option<usize>,
+    /// Leaves destinations out of recent messages
+    #[clap(long)]
+    recent_messages_redact_destination: bool,
+    /// Salt of the anonymous ids of api keys in recent messages, random on each start if missing
+    #[clap(long)]
+    recent_messages_salt: Option<String>,
+    /// Bytes the caches of the client, stale states, fee stats, shard history, sessions, export
+    /// jobs, sent, recent and rejected messages, account watchers and webhooks of every network
+    /// are kept under, caches are shrunk first, unbounded if missing. Watchers and registered
+    /// webhooks are accounted but never dropped, cursors are bounded by their own capacity only
+    #[clap(long)]
+    memory_budget: Option<usize>,
+    #[clap(long, value_parser = humantime::parse_duration, default_value = "1s")]
+    memory_check_interval: Duration,
+    /// Sent as retry-after along with requests rejected in maintenance mode,
+    /// which is toggled by SIGUSR2 or AdminService.SetMaintenanceMode
+    #[clap(long, value_parser = humantime::parse_duration, default_value = "30s")]
+    maintenance_retry_after: Duration,
+    /// Samples every masterchain block for lag and shard metrics and AdminService.GetChainStats
+    #[clap(long)]
+    chain_stats: bool,
+    /// Masterchain blocks kept for GetChainStats
+    #[clap(long, default_value_t = 720)]
+    chain_stats_history: usize,
+    /// Counts transactions of sampled blocks, a few lite server requests per block
+    #[clap(long)]
+    chain_stats_transactions: bool,
+    /// Counts transactions in one of every this many blocks
+    #[clap(long, default_value_t = 1)]
+    chain_stats_transactions_every: u32,
+
+    /// Refreshes the latest state and transactions of this address on every masterchain block
+    /// while it's requested, so that requests for it are served without lite servers
+    #[clap(long)]
+    hot_address: Vec<String>,
+    /// Time a refresh of hot addresses may take once a new block is seen
+    #[clap(long, value_parser = humantime::parse_duration, default_value = "1s")]
+    hot_refresh_deadline: Duration,
+    /// Lite server requests of a refresh of hot addresses, the most requested go first
+    #[clap(long, default_value_t = 64)]
+    hot_max_requests_per_block: usize,
+    /// Latest transactions kept per hot address
+    #[clap(long, default_value_t = 16)]
+    hot_transactions: usize,
+    /// Requests of a hot address count half as much after this long
+    #[clap(long, value_parser = humantime::parse_duration, default_value = "10m")]
+    hot_half_life: Duration,
+    /// Hot addresses requested less than this, decay included, are no longer refreshed
+    #[clap(long, default_value_t = 0.1)]
+    hot_min_score: f64,
+
+    /// While the circuit of reads is open, serves latest account states fetched within this bound,
+    /// marked as stale, and reports SERVING for the <network>.degraded health service
+    #[clap(long, value_parser = humantime::parse_duration)]
+    serve_stale_for: Option<Duration>,
+    #[clap(long, default_value_t = 100_000)]
+    stale_capacity: usize,
+
+    /// Cache-Control max-age of final blocks and of data read at them
+    #[clap(long, value_parser = humantime::parse_duration, default_value = "1d")]
+    cache_final_max_age: Duration,
+    /// Cache-Control max-age of the last block and the latest account states
+    #[clap(long, value_parser = humantime::parse_duration, default_value = "5s")]
+    cache_latest_max_age: Duration,
+    /// Masterchain blocks requested by seqno are final once this far behind the last one
+    #[clap(long, default_value_t = 16)]
+    cache_final_after_seqnos: i32,
+
+    /// Requires an x-api-key from this JSON file, e.g. {"<key>": {"name": "acme", "daily": 100000, "monthly": 2000000}},
+    /// quotas are counted in method costs
+    #[clap(long)]
+    api_keys: Option<PathBuf>,
+    /// Overrides the quota cost of a method, e.g. GetTransactions=10
+    #[clap(long, value_parser = parse_method_cost)]
+    method_cost: Vec<(String, u64)>,
+    /// Keeps usage of api keys across restarts in this JSON file
+    #[clap(long)]
+    usage_snapshot: Option<PathBuf>,
+    #[clap(long, value_parser = humantime::parse_duration, default_value = "60s")]
+    usage_snapshot_interval: Duration,
+    /// Enables only these methods, e.g. GetAccountState, any method which isn't denied is enabled if missing
+    #[clap(long)]
+    allow_method: Vec<String>,
+    /// Disables a method, e.g. GetFullTransactions, api keys unlock it with "methods": {"allow": [...]}
+    #[clap(long)]
+    deny_method: Vec<String>,
+    /// Serves AdminService, usage of keys is available with --api-keys
+    #[clap(long)]
+    admin_listen: Option<SocketAddr>,
+    /// Requires this key in the x-admin-key of AdminService requests
+    #[clap(long)]
+    admin_key: Option<String>,
+
+    /// Max number of transaction exports kept at a time, running or finished
+    #[clap(long, default_value_t = 16)]
+    export_max_jobs: usize,
+    /// Max number of transactions collected by a single export
+    #[clap(long, default_value_t = 500_000)]
+    export_max_transactions: usize,
+    /// How long an export and its transactions are kept after it started
+    #[clap(long, value_parser = humantime::parse_duration, default_value = "1h")]
+    export_ttl: Duration,
+    /// Enables StartFileExport, exported files are written to <dir>/<network>
+    #[clap(long)]
+    export_dir: Option<PathBuf>,
+
+    /// Poll interval of accounts watched by WatchAccountState
+    #[clap(long, default_value = "1s", value_parser = humantime::parse_duration)]
+    watch_account_interval: Duration,
+
+    /// Sibling instances of this proxy polled for their masterchain tips, an alarm is raised
+    /// while the tip of this one lags theirs
+    #[clap(long)]
+    peer: Vec<Url>,
+    /// x-api-key sent to the peers
+    #[clap(long)]
+    peer_api_key: Option<String>,
+    #[clap(long, value_parser = humantime::parse_duration, default_value = "10s")]
+    peer_check_interval: Duration,
+    #[clap(long, value_parser = humantime::parse_duration, default_value = "5s")]
+    peer_timeout: Duration,
+    /// Masterchain blocks this instance may be behind the highest tip of the peers
+    #[clap(long, default_value_t = 5)]
+    peer_max_lag: i32,
+    /// How long the tip lags the peers beyond --peer-max-lag until the alarm is raised
+    #[clap(long, value_parser = humantime::parse_duration, default_value = "60s")]
+    peer_lag_grace: Duration,
+
+    /// JSON file of get-method result schemas by contract code hash or address, RunGetMethod
+    /// decodes results of the methods it describes
+    #[clap(long)]
+    abi_file: Option<PathBuf>,
+
+    /// Signs pagination cursors, a random one is used if missing so cursors don't survive restarts
+    #[clap(long)]
+    cursor_secret: Option<String>,
+
+    /// Routes reads of a client sending x-ton-session to lite servers which have reached the
+    /// masterchain block its previous requests have seen, keeping this many sessions
+    #[clap(long)]
+    session_capacity: Option<usize>,
+
+    /// Serves WebhookService, registered webhooks are kept in <dir>/<network>.json
+    #[clap(long)]
+    webhook_dir: Option<PathBuf>,
+    /// Signs webhook payloads with HMAC-SHA256 in the x-ton-grpc-signature header
+    #[clap(long)]
+    webhook_secret: Option<String>,
+    /// Max number of webhooks of a network
+    #[clap(long, default_value_t = 1000)]
+    webhook_max: usize,
+    /// Attempts of a delivery before it's dead-lettered
+    #[clap(long, default_value_t = 8)]
+    webhook_max_attempts: u32,
+    #[clap(long, value_parser = humantime::parse_duration, default_value = "1s")]
+    webhook_retry_first_delay: Duration,
+    #[clap(long, value_parser = humantime::parse_duration, default_value = "5m")]
+    webhook_retry_max_delay: Duration,
+    #[clap(long, value_parser = humantime::parse_duration, default_value = "10s")]
+    webhook_timeout: Duration,
+
+    /// Runs a sample of reads on this backend too and logs where it disagrees with the serving
+    /// one, `tonlibjson` or `liteserver`. Sends are never shadowed
+    #[clap(long)]
+    shadow_backend: Option<Backend>,
+    /// Lite servers of --shadow-backend, those of the network by default, requires a single network
+    #[clap(long, requires = "shadow_backend")]
+    shadow_config_url: Option<Url>,
+    /// Share of reads shadowed, from 0 to 1
+    #[clap(long, default_value_t = 0.01)]
+    shadow_sample_rate: f64,
+    /// tonlib method to shadow, e.g. blocks.getTransactions, repeat for several, all if none
+    #[clap(long)]
+    shadow_method: Vec<String>,
+
+    /// Reads block headers, shard lists and block transactions from this instance of ton-grpc
+    /// instead of lite servers, account states and sends still go to lite servers
+    #[clap(long)]
+    replica_of: Option<Url>,
+    /// Block data read from --replica-of: headers, shards or transactions, all of them if none
+    #[clap(long, value_delimiter = ',', requires = "replica_of")]
+    replica_data: Vec<BlockData>,
+    /// x-api-key sent to --replica-of
+    #[clap(long)]
+    replica_api_key: Option<String>,
+    #[clap(long, value_parser = humantime::parse_duration, default_value = "10s")]
+    replica_timeout: Duration,
+
+    #[cfg(feature = "liteserver")]
+    #[clap(long)]
+    account_state_proofs: bool,
+    /// Serves GetBlockData through a lite server
+    #[cfg(feature = "liteserver")]
+    #[clap(long)]
+    block_data: bool,
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let args = Args::parse();
 
-    tracing_subscriber::fmt()
-        .with_env_filter(EnvFilter::from_default_env())
-        .with_span_events(FmtSpan::CLOSE)
-        .init();
+    let registry = tracing_subscriber::registry().with(
+        tracing_subscriber::fmt::layer()
+            .with_span_events(FmtSpan::CLOSE)
+            .with_filter(EnvFilter::from_default_env()),
+    );
+    #[cfg(feature = "otel")]
+    let registry = registry.with(
+        args.otlp_endpoint
+            .as_ref()
+            .map(|endpoint| otel::layer(endpoint, &args.otlp_filter))
+            .transpose()?,
+    );
+    registry.init();
+
+    if args.check {
+        let network = args.default_network.clone();
+        let config_url = args
+            .network
+            .iter()
+            .find(|(name, _)| name == &network)
+            .map(|(_, url)| url.clone())
+            .unwrap_or_else(|| args.ton_config_url.clone());
+        let zero_state = (!args.allow_custom_network)
+            .then(|| expected_zero_state(&network, &args.zero_state, args.network.is_empty()))
+            .transpose()?;
+
+        let report = check::run(
+            network,
+            config_url,
+            |config| configure_client(&args, TonClientBuilder::from_config(config)),
+            zero_state,
+            args.timeout,
+        )
+        .await;
+        println!("{}", serde_json::to_string_pretty(&report)?);
+
+        return if report.passed() {
+            Ok(())
+        } else {
+            Err(anyhow!("check failed"))
+        };
+    }
 
     if args.enable_metrics {
         PrometheusBuilder::new()
@@ -84,39 +517,526 @@ async fn main() -> anyhow::Result<()> {
         tracing::info!("Listening metrics on {:?}", &args.metrics_listen);
     }
 
-    tracing::info!("TON Config URL: {}", &args.ton_config_url);
-
-    let mut client =
-        TonClientBuilder::from_config_url(args.ton_config_url, Duration::from_secs(60))
-            .set_timeout(args.ton_timeout)
-            .set_retry_budget_ttl(args.retry_budget_ttl)
-            .set_retry_min_per_sec(args.retry_min_rps)
-            .set_retry_percent(args.retry_withdraw_percent)
-            .set_retry_first_delay(args.retry_first_delay)
-            .set_retry_max_delay(args.retry_max_delay)
-            .set_ewma_default_rtt(args.ewma_default_rtt)
-            .set_ewma_decay(args.ewma_decay)
+    let networks = if args.network.is_empty() {
+        vec![(args.default_network.clone(), args.ton_config_url.clone())]
+    } else {
+        args.network.clone()
+    };
+
+    if args.shadow_config_url.is_some() && networks.len() > 1 {
+        return Err(anyhow!("--shadow-config-url requires a single network"));
+    }
+    if !(0.0..=1.0).contains(&args.shadow_sample_rate) {
+        return Err(anyhow!("--shadow-sample-rate must be from 0 to 1"));
+    }
+    if let Some(method) = args
+        .shadow_method
+        .iter()
+        .find(|m| !SHADOWED_METHODS.contains(&m.as_str()))
+    {
+        return Err(anyhow!(
+            "{} can't be shadowed, only {}",
+            method,
+            SHADOWED_METHODS.join(", ")
+        ));
+    }
+
+    let cursors = match &args.cursor_secret {
+        Some(secret) => Cursors::new(secret.as_bytes()),
+        None => {
+            tracing::warn!("no --cursor-secret, cursors are valid until restart");
+
+            Cursors::default()
+        }
+    };
+
+    let abi = match &args.abi_file {
+        Some(path) => {
+            let abi = AbiRegistry::load(path)?;
+            tracing::info!(path = %path.display(), "ABI descriptors loaded");
+
+            Some(Arc::new(abi))
+        }
+        None => None,
+    };
+
+    let param_limits = ParamLimits::with_overrides(args.param_limit.clone())?;
+    for (key, limit) in param_limits.iter() {
+        tracing::info!(
+            key,
+            default = limit.default,
+            max = limit.max,
+            "request parameter limit"
+        );
+    }
+
+    let call_options = args.method_call_timeout.iter().fold(
+        MethodCallOptions::default(),
+        |options, (method, timeout)| options.set_timeout(method, *timeout),
+    );
+    let call_options = args
+        .method_max_retries
+        .iter()
+        .fold(call_options, |options, (method, retries)| {
+            options.set_max_retries(method, *retries)
+        });
+
+    let mut builder = ServerBuilder::new(args.default_network.clone())
+        .set_method_policy(MethodPolicy::new(&args.allow_method, &args.deny_method))
+        .set_response_size_limits(ResponseSizeLimits::new(
+            args.max_response_size,
+            args.method_max_response_size.clone(),
+        ))
+        .set_param_limits(param_limits)
+        .set_json_limits(JsonLimits {
+            max_size: args.json_max_size,
+            max_depth: args.json_max_depth,
+            max_array_len: args.json_max_array_len,
+            max_string_len: args.json_max_string_len,
+        })
+        .set_cache_policy(CachePolicy {
+            final_max_age: args.cache_final_max_age,
+            latest_max_age: args.cache_latest_max_age,
+            final_after_seqnos: args.cache_final_after_seqnos,
+        })
+        .set_cursors(cursors)
+        .set_call_options(call_options)
+        .set_method_costs(args.method_cost.clone());
+    let sessions = args
+        .session_capacity
+        .map(|capacity| Arc::new(Sessions::new(capacity)));
+    if let Some(sessions) = &sessions {
+        builder = builder.set_sessions(sessions.clone());
+    }
+
+    let usage_store = Arc::new(match &args.usage_snapshot {
+        Some(path) => MemoryUsageStore::with_snapshot(path.clone())?,
+        None => MemoryUsageStore::default(),
+    });
+    if let Some(path) = &args.api_keys {
+        let keys = load_api_keys(path)?;
+        tracing::info!(keys = keys.len(), "api keys are required");
+
+        builder = builder
+            .set_api_keys(keys)
+            .set_usage_store(usage_store.clone());
+    }
+    if args.api_keys.is_some() && args.usage_snapshot.is_some() {
+        let usage_store = usage_store.clone();
+        let mut interval = tokio::time::interval(args.usage_snapshot_interval);
+        tokio::spawn(async move {
+            loop {
+                interval.tick().await;
+                if let Err(e) = usage_store.snapshot() {
+                    tracing::error!(error = ?e, "failed to snapshot key usage");
+                }
+            }
+        });
+    }
+
+    let (mut health_reporter, health_server) = tonic_health::server::health_reporter();
+
+    let mut clients = HashMap::new();
+    let mut journals = HashMap::new();
+    let mut rejected_messages = HashMap::new();
+    let recent_messages_salt = args
+        .recent_messages_salt
+        .clone()
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+    let mut export_jobs = HashMap::new();
+    let mut chain_stats = HashMap::new();
+    let memory = args
+        .memory_budget
+        .map(|cap| Arc::new(MemoryBudget::new(cap)));
+    if let (Some(memory), Some(sessions)) = (&memory, &sessions) {
+        memory.register("sessions", Tier::Cache, sessions.clone());
+    }
+    let maintenance = Arc::new(Maintenance::new(args.maintenance_retry_after));
+    // the server as a whole, the readiness probe checks it
+    let mut health_services = vec![String::new()];
+    for (network, ton_config_url) in networks {
+        tracing::info!(network, "TON Config URL: {}", &ton_config_url);
+
+        let builder_of_client = TonClientBuilder::from_config_url(
+            ton_config_url.clone(),
+            args.ton_config_refresh_interval,
+        );
+        let builder_of_client = match &args.record_fixtures_dir {
+            Some(dir) => {
+                builder_of_client.set_record_fixture(dir.join(format!("{}.jsonl", network)))
+            }
+            None => builder_of_client,
+        };
+        let builder_of_client = match &args.shadow_backend {
+            Some(backend) => {
+                let shadow_config_url = args
+                    .shadow_config_url
+                    .clone()
+                    .unwrap_or_else(|| ton_config_url.clone());
+                tracing::info!(network, ?backend, %shadow_config_url, "shadowing reads");
+
+                let shadow = TonClientBuilder::from_config_url(
+                    shadow_config_url,
+                    args.ton_config_refresh_interval,
+                )
+                .set_timeout(args.ton_timeout)
+                .set_backend(backend.clone())
+                .build_transport()?;
+
+                builder_of_client.set_shadow(Shadow::new(
+                    shadow,
+                    ShadowPolicy {
+                        sample_rate: args.shadow_sample_rate,
+                        methods: args.shadow_method.clone(),
+                    },
+                ))
+            }
+            None => builder_of_client,
+        };
+        let builder_of_client = match &args.replica_of {
+            Some(url) => {
+                tracing::info!(network, %url, data = ?args.replica_data, "replica of upstream");
+                let upstream = Upstream::new(
+                    network.clone(),
+                    UpstreamPolicy {
+                        url: url.clone(),
+                        api_key: args.replica_api_key.clone(),
+                        timeout: args.replica_timeout,
+                    },
+                )?;
+
+                builder_of_client
+                    .set_replica(Replica::new(Arc::new(upstream), args.replica_data.clone()))
+            }
+            None => builder_of_client,
+        };
+        let mut client = configure_client(&args, builder_of_client)
+            .set_network(network.clone())
             .build()?;
 
-    client.ready().await?;
-    tracing::info!("Ton Client is ready");
+        client.ready().await?;
+        let info = client.get_masterchain_info().await?;
+        if !args.allow_custom_network {
+            expected_zero_state(&network, &args.zero_state, args.network.is_empty())?
+                .check(&info)
+                .map_err(|e| anyhow!("network {}: {}", network, e))?;
+        }
+        tracing::info!(network, "Ton Client is ready");
+        clients.insert(network.clone(), client.clone());
+        if let Some(memory) = &memory {
+            memory.register(
+                format!("{}.client_caches", network),
+                Tier::Cache,
+                Arc::new(ClientCaches(client.clone())),
+            );
+        }
+
+        let mut services = NetworkServices::new(client.clone());
+        let masterchain = MasterchainWatcher::default();
+        services.zero_state = Some(ZeroState::of(&info));
+
+        let watchers = Arc::new(
+            AccountWatchers::new(client.clone(), args.watch_account_interval)
+                .set_pause(maintenance.pause()),
+        );
+        if let Some(memory) = &memory {
+            memory.register(
+                format!("{}.account_watchers", network),
+                Tier::Buffer,
+                watchers.clone(),
+            );
+        }
+        if let Some(dir) = &args.webhook_dir {
+            std::fs::create_dir_all(dir)?;
+            let webhooks = Webhooks::new(
+                client.clone(),
+                watchers.clone(),
+                DeliveryPolicy {
+                    secret: args.webhook_secret.clone(),
+                    max_attempts: args.webhook_max_attempts,
+                    first_delay: args.webhook_retry_first_delay,
+                    max_delay: args.webhook_retry_max_delay,
+                    timeout: args.webhook_timeout,
+                },
+                args.webhook_max,
+                Some(dir.join(format!("{}.json", network))),
+            )?;
+            if let Some(memory) = &memory {
+                memory.register(
+                    format!("{}.webhooks", network),
+                    Tier::Buffer,
+                    webhooks.clone(),
+                );
+            }
+            services.webhooks = Some(WebhookService::new(webhooks));
+        }
+
+        let mut jobs = ExportJobs::new(
+            client.clone(),
+            args.export_max_jobs,
+            args.export_max_transactions,
+            args.export_ttl,
+        );
+        if let Some(dir) = &args.export_dir {
+            let dir = dir.join(&network);
+            std::fs::create_dir_all(&dir)?;
+            jobs = jobs.set_dir(dir);
+        }
+        let jobs = Arc::new(jobs);
+        export_jobs.insert(network.clone(), jobs.clone());
+        if let Some(memory) = &memory {
+            memory.register(
+                format!("{}.export_jobs", network),
+                Tier::Buffer,
+                jobs.clone(),
+            );
+        }
+        let account_service = AccountService::new(client.clone())
+            .set_account_watchers(watchers)
+            .set_export_jobs(jobs);
+        let account_service = match args.serve_stale_for {
+            Some(max_staleness) => {
+                tokio::spawn(report_degraded(
+                    client.clone(),
+                    network.clone(),
+                    health_reporter.clone(),
+                ));
+
+                let states = Arc::new(StaleStates::new(max_staleness, args.stale_capacity));
+                if let Some(memory) = &memory {
+                    memory.register(
+                        format!("{}.stale_states", network),
+                        Tier::Cache,
+                        states.clone(),
+                    );
+                }
+
+                account_service.set_stale_states(states)
+            }
+            None => account_service,
+        };
+        let account_service = match &abi {
+            Some(abi) => account_service.set_abi(abi.clone()),
+            None => account_service,
+        };
+        let account_service = if args.hot_address.is_empty() {
+            account_service
+        } else {
+            let hot = Arc::new(HotAddresses::new(PrefetchPolicy {
+                addresses: args.hot_address.clone(),
+                deadline: args.hot_refresh_deadline,
+                max_requests_per_block: args.hot_max_requests_per_block,
+                transactions: args.hot_transactions,
+                half_life: args.hot_half_life,
+                min_score: args.hot_min_score,
+            })?);
+            tokio::spawn(prefetch(
+                client.clone(),
+                masterchain.clone(),
+                hot.clone(),
+                maintenance.pause(),
+            ));
 
-    let reflection = tonic_reflection::server::Builder::configure()
-        .register_encoded_file_descriptor_set(tonic_health::pb::FILE_DESCRIPTOR_SET)
-        .register_encoded_file_descriptor_set(ton::FILE_DESCRIPTOR_SET)
+            account_service.set_hot_addresses(hot)
+        };
+        let recent = args.recent_messages.map(|capacity| {
+            Arc::new(RecentMessages::new(RecentMessagesPolicy {
+                capacity,
+                redact_destination: args.recent_messages_redact_destination,
+                salt: recent_messages_salt.clone(),
+            }))
+        });
+        if let (Some(memory), Some(recent)) = (&memory, &recent) {
+            memory.register(
+                format!("{}.recent_messages", network),
+                Tier::Buffer,
+                recent.clone(),
+            );
+        }
+        let account_service = match &recent {
+            Some(recent) => account_service.set_recent_messages(recent.clone()),
+            None => account_service,
+        };
+        if !args.peer.is_empty() {
+            tokio::spawn(check_peers(
+                client.clone(),
+                network.clone(),
+                PeerCheckPolicy {
+                    peers: args.peer.clone(),
+                    api_key: args.peer_api_key.clone(),
+                    interval: args.peer_check_interval,
+                    timeout: args.peer_timeout,
+                    max_lag: args.peer_max_lag,
+                    grace: args.peer_lag_grace,
+                },
+                health_reporter.clone(),
+            ));
+        }
+        #[cfg(feature = "liteserver")]
+        let lite_server = if args.account_state_proofs || args.block_data {
+            Some(
+                TonClientBuilder::from_config_url(ton_config_url, args.ton_config_refresh_interval)
+                    .set_timeout(args.ton_timeout)
+                    .set_backend(tonlibjson_client::transport::Backend::LiteServer)
+                    .build_transport()?,
+            )
+        } else {
+            None
+        };
+        #[cfg(feature = "liteserver")]
+        let account_service = match &lite_server {
+            Some(lite_server) if args.account_state_proofs => {
+                account_service.set_proofs(lite_server.clone())
+            }
+            _ => account_service,
+        };
+        services.account = account_service;
+        #[cfg(feature = "liteserver")]
+        if let Some(lite_server) = lite_server.filter(|_| args.block_data) {
+            services.block = BlockService::new(client.clone()).set_block_data(lite_server);
+        }
+        let fee_stats = Arc::new(FeeStats::default());
+        let fee_configs = Arc::new(FeeConfigs::default());
+        let shard_history = Arc::new(ShardHistory::default());
+        if let Some(memory) = &memory {
+            memory.register(
+                format!("{}.fee_stats", network),
+                Tier::Cache,
+                fee_stats.clone(),
+            );
+            memory.register(
+                format!("{}.fee_configs", network),
+                Tier::Cache,
+                fee_configs.clone(),
+            );
+            memory.register(
+                format!("{}.shard_history", network),
+                Tier::Cache,
+                shard_history.clone(),
+            );
+        }
+        services.block = services
+            .block
+            .set_masterchain_watcher(masterchain.clone())
+            .set_fee_stats(fee_stats)
+            .set_fee_configs(fee_configs)
+            .set_shard_history(shard_history);
+        if args.chain_stats {
+            let stats = Arc::new(ChainStats::new(ChainStatsPolicy {
+                history: args.chain_stats_history,
+                transactions: args.chain_stats_transactions,
+                transactions_every: args.chain_stats_transactions_every,
+            }));
+            chain_stats.insert(network.clone(), stats.clone());
+            tokio::spawn(sample_chain(
+                client.clone(),
+                network.clone(),
+                masterchain,
+                stats,
+                maintenance.pause(),
+            ));
+        }
+
+        let message_service = MessageService::new(client);
+        let message_service = match args.send_dedup_ttl {
+            Some(ttl) => {
+                let sent = Arc::new(SentMessages::new(ttl, args.send_dedup_capacity));
+                if let Some(memory) = &memory {
+                    memory.register(
+                        format!("{}.sent_messages", network),
+                        Tier::Buffer,
+                        sent.clone(),
+                    );
+                }
+
+                message_service.set_sent_messages(sent)
+            }
+            None => message_service,
+        };
+        let message_service = match args.send_serialize {
+            true => message_service.set_send_queues(SendQueues::new(
+                args.send_queue_depth,
+                args.send_queue_wait,
+                args.send_queue_wallets,
+            )),
+            false => message_service,
+        };
+        let message_service = match args.rejected_messages_bytes {
+            Some(max_bytes) => {
+                let rejected = Arc::new(RejectedMessages::new(
+                    max_bytes,
+                    args.rejected_messages_retention,
+                ));
+                rejected_messages.insert(network.clone(), rejected.clone());
+                if let Some(memory) = &memory {
+                    memory.register(
+                        format!("{}.rejected_messages", network),
+                        Tier::Buffer,
+                        rejected.clone(),
+                    );
+                }
+
+                message_service.set_rejected_messages(rejected)
+            }
+            None => message_service,
+        };
+        let message_service = match recent {
+            Some(recent) => message_service.set_recent_messages(recent),
+            None => message_service,
+        };
+        services.message = match &args.send_journal_dir {
+            Some(dir) => {
+                std::fs::create_dir_all(dir)?;
+                let journal = Arc::new(SendJournal::open(
+                    dir.join(format!("{}.jsonl", network)),
+                    args.send_journal_max_size,
+                )?);
+                journals.insert(network.clone(), journal.clone());
+
+                message_service.set_journal(journal)
+            }
+            None => message_service,
+        };
+
+        health_reporter
+            .set_service_status(&network, ServingStatus::Serving)
+            .await;
+        health_services.push(network.clone());
+
+        builder = builder.add_network(network, services);
+    }
+
+    let api = builder
+        .add_service(health_server)
+        .set_maintenance(maintenance.clone())
         .build()?;
 
-    let account_service = AccountServiceServer::new(AccountService::new(client.clone()))
-        .accept_compressed(Gzip)
-        .send_compressed(Gzip);
-    let block_service = BlockServiceServer::new(BlockService::new(client.clone()))
-        .accept_compressed(Gzip)
-        .send_compressed(Gzip);
-    let message_service = MessageServiceServer::new(MessageService::new(client))
-        .accept_compressed(Gzip)
-        .send_compressed(Gzip);
+    if let Some(memory) = &memory {
+        tokio::spawn(enforce_budget(memory.clone(), args.memory_check_interval));
+    }
+
+    if let Some(admin_listen) = args.admin_listen {
+        if args.admin_key.is_none() {
+            tracing::warn!("no --admin-key, admin is served to anyone who reaches it");
+        }
+
+        tracing::info!("Listening admin on {:?}", &admin_listen);
+        tokio::spawn(
+            Server::builder()
+                .add_service(AdminServiceServer::with_interceptor(
+                    AdminService::new(api.quotas(), args.default_network.clone(), clients)
+                        .set_journals(journals)
+                        .set_rejected_messages(rejected_messages)
+                        .set_export_jobs(export_jobs)
+                        .set_memory_budget(memory)
+                        .set_maintenance(maintenance.clone())
+                        .set_chain_stats(chain_stats),
+                    admin_key_interceptor(args.admin_key.clone()),
+                ))
+                .serve(admin_listen),
+        );
+    }
 
-    let (mut health_reporter, health_server) = tonic_health::server::health_reporter();
     health_reporter
         .set_serving::<AccountServiceServer<AccountService>>()
         .await;
@@ -126,25 +1046,166 @@ async fn main() -> anyhow::Result<()> {
     health_reporter
         .set_serving::<MessageServiceServer<MessageService>>()
         .await;
+    health_services.extend(
+        [
+            AccountServiceServer::<AccountService>::NAME,
+            BlockServiceServer::<BlockService>::NAME,
+            MessageServiceServer::<MessageService>::NAME,
+        ]
+        .map(String::from),
+    );
+    tokio::spawn(report_maintenance(
+        maintenance.clone(),
+        health_reporter.clone(),
+        health_services,
+    ));
+    // registered up front, so a failure stops the start instead of a detached task
+    let mut maintenance_signal = signal(SignalKind::user_defined2())
+        .map_err(|e| anyhow!("cannot listen to SIGUSR2: {}", e))?;
+    tokio::spawn(async move {
+        while maintenance_signal.recv().await.is_some() {
+            maintenance.toggle();
+        }
+    });
 
-    tracing::info!("Listening on {:?}", &args.listen);
-
-    Server::builder()
+    let server = Server::builder()
         .timeout(args.timeout)
         .tcp_keepalive(args.tcp_keepalive.into())
         .http2_keepalive_interval(args.http2_keepalive_interval.into())
         .http2_keepalive_timeout(args.http2_keepalive_timeout.into())
         .initial_connection_window_size(args.initial_connection_window_size)
-        .initial_stream_window_size(args.initial_stream_window_size)
-        .add_service(reflection)
-        .add_service(health_server)
-        .add_service(account_service)
-        .add_service(block_service)
-        .add_service(message_service)
-        .serve_with_shutdown(args.listen, async move {
-            tokio::signal::ctrl_c().await.unwrap();
+        .initial_stream_window_size(args.initial_stream_window_size);
+    #[cfg(feature = "otel")]
+    let trace_requests = args.tls_client_ca.is_some() || args.otlp_endpoint.is_some();
+    #[cfg(not(feature = "otel"))]
+    let trace_requests = args.tls_client_ca.is_some();
+    let server = if trace_requests {
+        server.trace_fn(|request| {
+            let info = request.extensions().get::<TlsConnectInfo>();
+
+            let span = tracing::info_span!(
+                "request",
+                path = request.uri().path(),
+                client_cn = info.and_then(|info| info.client_cn.as_deref()),
+                remote_addr = ?info.and_then(|info| info.remote_addr),
+            );
+            #[cfg(feature = "otel")]
+            otel::set_parent(&span, request.headers());
+
+            span
         })
-        .await?;
+    } else {
+        server
+    };
+
+    let tls = match (&args.tls_cert, &args.tls_key) {
+        (Some(cert), Some(key)) => {
+            let tls = ReloadableTls::new(TlsFiles {
+                cert: cert.clone(),
+                key: key.clone(),
+                client_ca: args.tls_client_ca.clone(),
+            })?;
+            tls.spawn_reloader(args.tls_reload_interval)?;
+
+            Some(tls)
+        }
+        _ => None,
+    };
+
+    let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(());
+    tokio::spawn(async move {
+        tokio::signal::ctrl_c().await.unwrap();
+        let _ = shutdown_tx.send(());
+    });
+
+    // sockets are bound upfront so that a busy path fails before serving anything,
+    // their files are removed when the server is stopped
+    let mut socket_files = Vec::new();
+    let mut listeners = Vec::new();
+    for listen in &args.listen {
+        let bound = match (listen, &tls) {
+            (Listen::Tcp(addr), None) => Bound::Tcp(*addr),
+            (Listen::Tcp(addr), Some(tls)) => {
+                Bound::Tls(tls.incoming(tokio::net::TcpListener::bind(addr).await?))
+            }
+            (Listen::Unix(path), _) => {
+                let (listener, file) = bind_unix(path, args.listen_mode)?;
+                socket_files.push(file);
+
+                Bound::Unix(listener)
+            }
+        };
+        listeners.push((listen, bound));
+    }
+
+    try_join_all(listeners.into_iter().map(|(listen, bound)| {
+        tracing::info!("Listening on {}", listen);
+
+        let router = api.router(server.clone());
+        let mut shutdown_rx = shutdown_rx.clone();
+        let shutdown = async move {
+            let _ = shutdown_rx.changed().await;
+        };
+
+        async move {
+            match bound {
+                Bound::Tcp(addr) => router.serve_with_shutdown(addr, shutdown).await,
+                Bound::Tls(incoming) => {
+                    router
+                        .serve_with_incoming_shutdown(incoming, shutdown)
+                        .await
+                }
+                Bound::Unix(listener) => {
+                    router
+                        .serve_with_incoming_shutdown(UnixListenerStream::new(listener), shutdown)
+                        .await
+                }
+            }
+        }
+    }))
+    .await?;
+    drop(socket_files);
+
+    usage_store.snapshot()?;
+    #[cfg(feature = "otel")]
+    tokio::task::spawn_blocking(otel::shutdown).await?;
 
     Ok(())
 }
+
+/// Settings of tonlib clients shared by serving and --check
+fn configure_client(args: &Args, builder: TonClientBuilder) -> TonClientBuilder {
+    let builder = match args.keepalive_idle {
+        Some(idle) => builder.set_keepalive(KeepalivePolicy {
+            idle,
+            timeout: args.keepalive_timeout,
+        }),
+        None => builder,
+    };
+
+    builder
+        .set_timeout(args.ton_timeout)
+        .set_retry_budget_ttl(args.retry_budget_ttl)
+        .set_retry_min_per_sec(args.retry_min_rps)
+        .set_retry_percent(args.retry_withdraw_percent)
+        .set_retry_first_delay(args.retry_first_delay)
+        .set_retry_max_delay(args.retry_max_delay)
+        .set_read_breaker(BreakerPolicy {
+            failure_threshold: args.breaker_failures,
+            open_for: args.breaker_open_for,
+        })
+        .set_send_breaker(BreakerPolicy {
+            failure_threshold: args.send_breaker_failures,
+            open_for: args.send_breaker_open_for,
+        })
+        .set_send_retry(SendRetryPolicy {
+            attempts: args.send_attempts,
+            ..Default::default()
+        })
+        .set_ewma_default_rtt(args.ewma_default_rtt)
+        .set_ewma_decay(args.ewma_decay)
+        .set_latency_aware_routing(args.latency_aware_routing)
+        .set_masterchain_only(args.masterchain_only)
+        .set_max_seqno_lag(args.max_seqno_lag)
+        .set_verify_blocks(args.verify_blocks)
+}
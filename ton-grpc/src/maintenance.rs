@@ -0,0 +1,316 @@
+use crate::quota::quoted_method;
+use futures::future::{pending, BoxFuture};
+use futures::FutureExt;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::sync::watch;
+use tonic::body::BoxBody;
+use tonic::codegen::http::{HeaderMap, Request, Response};
+use tonic::codegen::{Body, Bytes, Service};
+use tonic::Status;
+use tonic_health::server::HealthReporter;
+use tonic_health::ServingStatus;
+use tower::{Layer, ServiceExt};
+
+/// Seconds a client should wait before retrying a request rejected for maintenance
+pub const RETRY_AFTER_HEADER: &str = "retry-after";
+
+/// Drain of the instance, e.g. before it leaves the load balancer: new API requests are
+/// rejected with `UNAVAILABLE` while the ones in flight and open streams go on, background
+/// tasks holding a [`Pause`] wait until it's turned off
+pub struct Maintenance {
+    on: watch::Sender<bool>,
+    retry_after: Duration,
+    in_flight: AtomicU64,
+}
+
+impl Maintenance {
+    pub fn new(retry_after: Duration) -> Self {
+        metrics::describe_gauge!(
+            "ton_grpc_maintenance",
+            "Whether new requests are rejected to drain the instance"
+        );
+        metrics::describe_gauge!(
+            "ton_grpc_in_flight_requests",
+            "Requests and streams being served"
+        );
+        metrics::gauge!("ton_grpc_maintenance").set(0);
+
+        Self {
+            on: watch::channel(false).0,
+            retry_after,
+            in_flight: AtomicU64::new(0),
+        }
+    }
+
+    pub fn is_on(&self) -> bool {
+        *self.on.borrow()
+    }
+
+    /// Turns the mode on or off, returns whether it was on
+    pub fn set(&self, on: bool) -> bool {
+        let was_on = self.on.send_replace(on);
+        if was_on != on {
+            tracing::warn!(on, in_flight = self.in_flight(), "maintenance mode");
+            metrics::gauge!("ton_grpc_maintenance").set(on as u8);
+        }
+
+        was_on
+    }
+
+    /// Flips the mode, returns whether it's on now
+    pub fn toggle(&self) -> bool {
+        let on = !self.is_on();
+        self.set(on);
+
+        on
+    }
+
+    /// Requests and streams being served, zero once the instance is drained
+    pub fn in_flight(&self) -> u64 {
+        self.in_flight.load(Ordering::Relaxed)
+    }
+
+    pub fn pause(&self) -> Pause {
+        Pause(Some(self.on.subscribe()))
+    }
+
+    fn rejected(&self) -> Status {
+        let mut status = Status::unavailable("maintenance");
+        status
+            .metadata_mut()
+            .insert(RETRY_AFTER_HEADER, self.retry_after.as_secs().into());
+
+        status
+    }
+}
+
+/// Lets a background task wait out maintenance, the default one never pauses
+#[derive(Clone, Default)]
+pub struct Pause(Option<watch::Receiver<bool>>);
+
+impl Pause {
+    /// Resolves once maintenance is on
+    pub async fn paused(&mut self) {
+        if let Some(on) = &mut self.0 {
+            if on.wait_for(|on| *on).await.is_ok() {
+                return;
+            }
+        }
+
+        pending().await
+    }
+
+    /// Resolves at once unless maintenance is on, then once it's off
+    pub async fn resumed(&mut self) {
+        if let Some(on) = &mut self.0 {
+            let _ = on.wait_for(|on| !*on).await;
+        }
+    }
+}
+
+/// Reports `services` as not serving while maintenance is on, `""` is the server as a whole
+/// the readiness probe checks
+pub async fn report_maintenance(
+    maintenance: Arc<Maintenance>,
+    mut reporter: HealthReporter,
+    services: Vec<String>,
+) {
+    let mut on = maintenance.on.subscribe();
+    loop {
+        let status = if *on.borrow_and_update() {
+            ServingStatus::NotServing
+        } else {
+            ServingStatus::Serving
+        };
+        for service in &services {
+            reporter.set_service_status(service, status).await;
+        }
+
+        if on.changed().await.is_err() {
+            return;
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct MaintenanceLayer {
+    maintenance: Arc<Maintenance>,
+}
+
+impl MaintenanceLayer {
+    pub fn new(maintenance: Arc<Maintenance>) -> Self {
+        Self { maintenance }
+    }
+}
+
+impl<S> Layer<S> for MaintenanceLayer {
+    type Service = MaintenanceService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        MaintenanceService {
+            inner,
+            maintenance: self.maintenance.clone(),
+        }
+    }
+}
+
+/// Rejects API requests while maintenance is on and counts the ones in flight until their
+/// response is sent, health and reflection are always served
+#[derive(Clone)]
+pub struct MaintenanceService<S> {
+    inner: S,
+    maintenance: Arc<Maintenance>,
+}
+
+impl<S, B> Service<Request<B>> for MaintenanceService<S>
+where
+    S: Service<Request<B>, Response = Response<BoxBody>> + Clone + Send + 'static,
+    S::Error: Send + 'static,
+    S::Future: Send,
+    B: Send + 'static,
+{
+    type Response = Response<BoxBody>;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        if quoted_method(req.uri().path()).is_none() {
+            return self.inner.clone().oneshot(req).boxed();
+        }
+        if self.maintenance.is_on() {
+            let status = self.maintenance.rejected();
+
+            return async move { Ok(status.to_http()) }.boxed();
+        }
+
+        let guard = InFlight::new(self.maintenance.clone());
+        let response = self.inner.clone().oneshot(req);
+        async move {
+            let response = response.await?;
+
+            Ok(response.map(|inner| {
+                InFlightBody {
+                    inner,
+                    _guard: guard,
+                }
+                .boxed_unsync()
+            }))
+        }
+        .boxed()
+    }
+}
+
+struct InFlight(Arc<Maintenance>);
+
+impl InFlight {
+    fn new(maintenance: Arc<Maintenance>) -> Self {
+        let count = maintenance.in_flight.fetch_add(1, Ordering::Relaxed) + 1;
+        metrics::gauge!("ton_grpc_in_flight_requests").set(count as f64);
+
+        Self(maintenance)
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        let count = self.0.in_flight.fetch_sub(1, Ordering::Relaxed) - 1;
+        metrics::gauge!("ton_grpc_in_flight_requests").set(count as f64);
+    }
+}
+
+/// Response body counted as in flight until it's dropped
+struct InFlightBody {
+    inner: BoxBody,
+    _guard: InFlight,
+}
+
+impl Body for InFlightBody {
+    type Data = Bytes;
+    type Error = Status;
+
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        Pin::new(&mut self.inner).poll_data(cx)
+    }
+
+    fn poll_trailers(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        Pin::new(&mut self.inner).poll_trailers(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tonic::Code;
+    use tower::service_fn;
+
+    async fn ok(_: Request<()>) -> Result<Response<BoxBody>, Status> {
+        Ok(Response::new(tonic::body::empty_body()))
+    }
+
+    fn request(path: &str) -> Request<()> {
+        Request::builder().uri(path).body(()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn new_requests_are_rejected() {
+        let maintenance = Arc::new(Maintenance::new(Duration::from_secs(30)));
+        let mut service = MaintenanceLayer::new(maintenance.clone()).layer(service_fn(ok));
+
+        let in_flight = service
+            .call(request("/ton.AccountService/GetAccountState"))
+            .await
+            .unwrap();
+        assert_eq!(maintenance.in_flight(), 1);
+
+        assert!(!maintenance.set(true));
+        let response = service
+            .call(request("/ton.AccountService/GetAccountState"))
+            .await
+            .unwrap();
+        let status = Status::from_header_map(response.headers()).unwrap();
+        assert_eq!(status.code(), Code::Unavailable);
+        assert_eq!(status.metadata().get(RETRY_AFTER_HEADER).unwrap(), "30");
+        let health = service
+            .call(request("/grpc.health.v1.Health/Check"))
+            .await
+            .unwrap();
+        assert!(Status::from_header_map(health.headers()).is_none());
+
+        drop(in_flight);
+        assert_eq!(maintenance.in_flight(), 0);
+    }
+
+    #[tokio::test]
+    async fn tasks_pause_until_it_is_off() {
+        let maintenance = Maintenance::new(Duration::from_secs(30));
+        let mut pause = maintenance.pause();
+        pause.resumed().await;
+
+        assert!(maintenance.toggle());
+        pause.paused().await;
+        let resumed = tokio::spawn(async move { pause.resumed().await });
+        tokio::task::yield_now().await;
+        assert!(!resumed.is_finished());
+
+        assert!(!maintenance.toggle());
+        resumed.await.unwrap();
+    }
+}
@@ -0,0 +1,233 @@
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::watch;
+use tonlibjson_client::block::BlocksMasterchainInfo;
+use tonlibjson_client::ton::TonClient;
+
+/// How often the shared watcher asks for the last masterchain block
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+type Watcher = Arc<Mutex<Option<Arc<watch::Sender<Option<BlocksMasterchainInfo>>>>>>;
+
+/// Masterchain info watcher shared by all long-polling requests,
+/// it stops once its last waiter is gone
+#[derive(Clone, Default)]
+pub struct MasterchainWatcher {
+    watcher: Watcher,
+}
+
+/// Info to respond a long poll with, the latest one known if the seqno wasn't reached in time
+#[derive(Debug, PartialEq)]
+pub struct Waited {
+    pub info: BlocksMasterchainInfo,
+    pub timed_out: bool,
+}
+
+impl MasterchainWatcher {
+    /// Responds right away if the last block is already at `seqno`, otherwise waits for it up
+    /// to `timeout`
+    pub async fn wait_for_seqno(
+        &self,
+        client: &TonClient,
+        seqno: i32,
+        timeout: Duration,
+    ) -> anyhow::Result<Waited> {
+        let info = client.get_masterchain_info().await?;
+        if info.last.seqno >= seqno {
+            return Ok(Waited {
+                info,
+                timed_out: false,
+            });
+        }
+
+        Ok(wait_for(self.subscribe(client), info, seqno, timeout).await)
+    }
+
+    /// Masterchain info kept up to date while the receiver is held, none until the first poll
+    pub fn subscribe(&self, client: &TonClient) -> watch::Receiver<Option<BlocksMasterchainInfo>> {
+        let client = client.clone();
+
+        subscribe_with(&self.watcher, POLL_INTERVAL, move || {
+            let client = client.clone();
+
+            async move { client.get_masterchain_info().await }.boxed()
+        })
+    }
+}
+
+async fn wait_for(
+    mut receiver: watch::Receiver<Option<BlocksMasterchainInfo>>,
+    current: BlocksMasterchainInfo,
+    seqno: i32,
+    timeout: Duration,
+) -> Waited {
+    let reached = receiver.wait_for(|info| info.as_ref().is_some_and(|i| i.last.seqno >= seqno));
+    if let Ok(Ok(info)) = tokio::time::timeout(timeout, reached).await {
+        if let Some(info) = info.clone() {
+            return Waited {
+                info,
+                timed_out: false,
+            };
+        }
+    }
+
+    // the watcher may have seen newer blocks than the first request did
+    let info = receiver
+        .borrow()
+        .clone()
+        .filter(|info| info.last.seqno > current.last.seqno)
+        .unwrap_or(current);
+
+    Waited {
+        info,
+        timed_out: true,
+    }
+}
+
+fn subscribe_with<F>(
+    watcher: &Watcher,
+    poll_interval: Duration,
+    poll: F,
+) -> watch::Receiver<Option<BlocksMasterchainInfo>>
+where
+    F: Fn() -> BoxFuture<'static, anyhow::Result<BlocksMasterchainInfo>> + Send + 'static,
+{
+    let mut guard = watcher.lock().unwrap();
+    if let Some(sender) = guard.as_ref() {
+        return sender.subscribe();
+    }
+
+    let (sender, receiver) = watch::channel(None);
+    let sender = Arc::new(sender);
+    *guard = Some(sender.clone());
+
+    tokio::spawn(run(watcher.clone(), sender, poll_interval, poll));
+
+    receiver
+}
+
+async fn run<F>(
+    watcher: Watcher,
+    sender: Arc<watch::Sender<Option<BlocksMasterchainInfo>>>,
+    poll_interval: Duration,
+    poll: F,
+) where
+    F: Fn() -> BoxFuture<'static, anyhow::Result<BlocksMasterchainInfo>>,
+{
+    let mut interval = tokio::time::interval(poll_interval);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            _ = sender.closed() => {
+                // a waiter may have joined while the watcher was stopping
+                let mut watcher = watcher.lock().unwrap();
+                if sender.receiver_count() == 0 {
+                    *watcher = None;
+
+                    return;
+                }
+            }
+            _ = interval.tick() => match poll().await {
+                Ok(info) => {
+                    sender.send_if_modified(|last| {
+                        let modified = last.as_ref() != Some(&info);
+                        *last = Some(info);
+
+                        modified
+                    });
+                }
+                Err(e) => tracing::warn!(error = ?e, "masterchain watcher failed"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicI32, Ordering};
+    use tonlibjson_client::block::TonBlockIdExt;
+
+    fn info(seqno: i32) -> BlocksMasterchainInfo {
+        let block = TonBlockIdExt::new(-1, i64::MIN, seqno, String::new(), String::new());
+
+        BlocksMasterchainInfo {
+            last: block.clone(),
+            state_root_hash: String::new(),
+            init: block,
+        }
+    }
+
+    fn chain(
+        seqno: Arc<AtomicI32>,
+    ) -> impl Fn() -> BoxFuture<'static, anyhow::Result<BlocksMasterchainInfo>> {
+        move || {
+            let seqno = seqno.fetch_add(1, Ordering::SeqCst);
+
+            async move { Ok(info(seqno)) }.boxed()
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn responds_once_seqno_is_reached() {
+        let watcher = Watcher::default();
+        let receiver = subscribe_with(&watcher, POLL_INTERVAL, chain(Arc::new(AtomicI32::new(10))));
+
+        let waited = wait_for(receiver, info(9), 12, Duration::from_secs(25)).await;
+
+        assert_eq!(
+            waited,
+            Waited {
+                info: info(12),
+                timed_out: false
+            }
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn timeout_responds_with_latest_info() {
+        let watcher = Watcher::default();
+        let receiver = subscribe_with(&watcher, POLL_INTERVAL, chain(Arc::new(AtomicI32::new(10))));
+
+        let waited = wait_for(receiver, info(9), 100, Duration::from_millis(2500)).await;
+
+        assert_eq!(
+            waited,
+            Waited {
+                info: info(12),
+                timed_out: true
+            }
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn waiters_share_watcher() {
+        let watcher = Watcher::default();
+        let polls = Arc::new(AtomicI32::new(10));
+        let first = subscribe_with(&watcher, POLL_INTERVAL, chain(polls.clone()));
+        let second = subscribe_with(&watcher, POLL_INTERVAL, chain(polls.clone()));
+
+        let (first, second) = tokio::join!(
+            wait_for(first, info(9), 11, Duration::from_secs(25)),
+            wait_for(second, info(9), 11, Duration::from_secs(25)),
+        );
+
+        assert_eq!(first.info, info(11));
+        assert_eq!(second.info, info(11));
+        assert_eq!(polls.load(Ordering::SeqCst), 12);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn last_waiter_stops_watcher() {
+        let watcher = Watcher::default();
+        let receiver = subscribe_with(&watcher, POLL_INTERVAL, chain(Arc::new(AtomicI32::new(10))));
+
+        drop(receiver);
+        while watcher.lock().unwrap().is_some() {
+            tokio::task::yield_now().await;
+        }
+    }
+}
@@ -0,0 +1,272 @@
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::MissedTickBehavior;
+use tonlibjson_client::ton::TonClient;
+
+/// Usage share of the cap above which components are shrunk
+const HIGH_WATERMARK: f64 = 0.9;
+/// Usage share of the cap components are shrunk down to
+const LOW_WATERMARK: f64 = 0.8;
+/// Entries measured by [`EntrySize`], one of every this many
+const SAMPLE_EVERY: usize = 64;
+
+/// Order of eviction, caches are shrunk before buffers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Tier {
+    Cache,
+    Buffer,
+}
+
+impl Display for Tier {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Tier::Cache => f.write_str("cache"),
+            Tier::Buffer => f.write_str("buffer"),
+        }
+    }
+}
+
+/// A bounded structure accounted by [`MemoryBudget`]
+pub trait Tracked: Send + Sync {
+    /// Approximate bytes kept
+    fn usage(&self) -> usize;
+
+    /// Drops the oldest entries worth at least `bytes` if there are that many, returns the bytes dropped
+    fn shrink(&self, bytes: usize) -> usize;
+}
+
+/// Approximate size of an entry, measured on a sample of inserts
+#[derive(Debug, Default)]
+pub struct EntrySize {
+    inserts: AtomicUsize,
+    bytes: AtomicUsize,
+}
+
+impl EntrySize {
+    /// Measures the entry with `size` if it's sampled
+    pub fn sample(&self, size: impl FnOnce() -> usize) {
+        if self.inserts.fetch_add(1, Ordering::Relaxed) % SAMPLE_EVERY == 0 {
+            self.bytes.store(size(), Ordering::Relaxed);
+        }
+    }
+
+    pub fn get(&self) -> usize {
+        self.bytes.load(Ordering::Relaxed)
+    }
+}
+
+/// Block header, validator set and out msg queue caches of a client
+pub struct ClientCaches(pub TonClient);
+
+impl Tracked for ClientCaches {
+    fn usage(&self) -> usize {
+        self.0.cache_usage()
+    }
+
+    fn shrink(&self, bytes: usize) -> usize {
+        self.0.shrink_caches(bytes)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Usage {
+    pub name: String,
+    pub tier: Tier,
+    pub bytes: usize,
+}
+
+struct Component {
+    name: String,
+    tier: Tier,
+    tracked: Arc<dyn Tracked>,
+}
+
+/// Global cap on the memory of caches and buffers, components over it are shrunk
+/// in proportion to their usage, see [`enforce_budget`]
+pub struct MemoryBudget {
+    cap: usize,
+    components: Mutex<Vec<Component>>,
+}
+
+impl MemoryBudget {
+    pub fn new(cap: usize) -> Self {
+        metrics::describe_gauge!(
+            "ton_grpc_memory_budget_bytes",
+            "Bytes caches and buffers are kept under"
+        );
+        metrics::describe_gauge!(
+            "ton_grpc_memory_bytes",
+            "Approximate bytes kept by a cache or a buffer"
+        );
+        metrics::describe_counter!(
+            "ton_grpc_memory_evicted_bytes_total",
+            "Approximate bytes dropped from a cache or a buffer to keep under the budget"
+        );
+        metrics::gauge!("ton_grpc_memory_budget_bytes").set(cap as f64);
+
+        Self {
+            cap,
+            components: Default::default(),
+        }
+    }
+
+    pub fn register(&self, name: impl Into<String>, tier: Tier, tracked: Arc<dyn Tracked>) {
+        self.components.lock().unwrap().push(Component {
+            name: name.into(),
+            tier,
+            tracked,
+        });
+    }
+
+    pub fn cap(&self) -> usize {
+        self.cap
+    }
+
+    pub fn usage(&self) -> Vec<Usage> {
+        self.components
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|component| Usage {
+                name: component.name.clone(),
+                tier: component.tier,
+                bytes: component.tracked.usage(),
+            })
+            .collect()
+    }
+
+    /// Shrinks components down to the low watermark once usage is above the high one,
+    /// caches first and buffers only if that's not enough, returns the bytes dropped
+    pub fn enforce(&self) -> usize {
+        let usage = self.usage();
+        for component in &usage {
+            metrics::gauge!("ton_grpc_memory_bytes", "component" => component.name.clone())
+                .set(component.bytes as f64);
+        }
+
+        let total: usize = usage.iter().map(|component| component.bytes).sum();
+        if total as f64 <= self.cap as f64 * HIGH_WATERMARK {
+            return 0;
+        }
+
+        let mut excess = total - (self.cap as f64 * LOW_WATERMARK) as usize;
+        let mut evicted = 0;
+        let components = self.components.lock().unwrap();
+        for tier in [Tier::Cache, Tier::Buffer] {
+            let tier_total: usize = usage
+                .iter()
+                .filter(|component| component.tier == tier)
+                .map(|component| component.bytes)
+                .sum();
+            if excess == 0 || tier_total == 0 {
+                continue;
+            }
+
+            let to_free = excess.min(tier_total);
+            for (component, usage) in components.iter().zip(&usage) {
+                if component.tier != tier || usage.bytes == 0 {
+                    continue;
+                }
+
+                let share = (to_free as u128 * usage.bytes as u128).div_ceil(tier_total as u128);
+                let freed = component.tracked.shrink(share as usize);
+                tracing::warn!(
+                    component = component.name,
+                    usage = usage.bytes,
+                    freed,
+                    "memory budget exceeded"
+                );
+                metrics::counter!("ton_grpc_memory_evicted_bytes_total", "component" => component.name.clone())
+                    .increment(freed as u64);
+                excess = excess.saturating_sub(freed);
+                evicted += freed;
+            }
+        }
+
+        evicted
+    }
+}
+
+/// Checks the budget every `interval`
+pub async fn enforce_budget(budget: Arc<MemoryBudget>, interval: Duration) {
+    let mut interval = tokio::time::interval(interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    loop {
+        interval.tick().await;
+        budget.enforce();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Entries of 100 bytes each
+    struct Entries(Mutex<usize>);
+
+    impl Entries {
+        fn new(count: usize) -> Arc<Self> {
+            Arc::new(Self(Mutex::new(count)))
+        }
+
+        fn count(&self) -> usize {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    impl Tracked for Entries {
+        fn usage(&self) -> usize {
+            self.count() * 100
+        }
+
+        fn shrink(&self, bytes: usize) -> usize {
+            let mut count = self.0.lock().unwrap();
+            let dropped = bytes.div_ceil(100).min(*count);
+            *count -= dropped;
+
+            dropped * 100
+        }
+    }
+
+    #[test]
+    fn nothing_is_evicted_under_high_watermark() {
+        let budget = MemoryBudget::new(10_000);
+        let cache = Entries::new(90);
+        budget.register("cache", Tier::Cache, cache.clone());
+
+        assert_eq!(budget.enforce(), 0);
+        assert_eq!(cache.count(), 90);
+    }
+
+    #[test]
+    fn caches_shrink_first_in_proportion() {
+        let budget = MemoryBudget::new(10_000);
+        let small = Entries::new(20);
+        let large = Entries::new(60);
+        let buffer = Entries::new(15);
+        budget.register("small", Tier::Cache, small.clone());
+        budget.register("large", Tier::Cache, large.clone());
+        budget.register("buffer", Tier::Buffer, buffer.clone());
+
+        assert_eq!(budget.enforce(), 1_600);
+        assert_eq!(small.count(), 16);
+        assert_eq!(large.count(), 48);
+        assert_eq!(buffer.count(), 15);
+        assert_eq!(budget.usage().iter().map(|u| u.bytes).sum::<usize>(), 7_900);
+    }
+
+    #[test]
+    fn buffers_shrink_once_caches_are_empty() {
+        let budget = MemoryBudget::new(10_000);
+        let cache = Entries::new(10);
+        let buffer = Entries::new(90);
+        budget.register("cache", Tier::Cache, cache.clone());
+        budget.register("buffer", Tier::Buffer, buffer.clone());
+
+        assert_eq!(budget.enforce(), 2_000);
+        assert_eq!(cache.count(), 0);
+        assert_eq!(buffer.count(), 80);
+    }
+}
@@ -1,14 +1,168 @@
 #![allow(clippy::blocks_in_conditions)]
 
+use crate::cache::no_store;
+use crate::emulate::{emulate_message, external_destination};
+use crate::journal::{Accepted, SendJournal};
+use crate::memory::{EntrySize, Tracked};
+use crate::quota::ApiKey;
+use crate::recent::RecentMessages;
+use crate::rejected::RejectedMessages;
+use crate::send_queue::SendQueues;
 use crate::ton::message_service_server::MessageService as BaseMessageService;
-use crate::ton::{SendRequest, SendResponse};
+use crate::ton::{
+    EmulateRequest, EmulateResponse, GetRecentMessagesRequest, GetRecentMessagesResponse,
+    ParseAccountStateRequest, ParseBocRequest, ParseBocResponse, ParsedAccount, RecentMessage,
+    SendRequest, SendResponse, WatchRecentMessagesRequest,
+};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
 use derive_new::new;
+use futures::{Stream, StreamExt};
+use quick_cache::sync::{Cache, DefaultLifecycle, PlaceholderGuard};
+use quick_cache::{DefaultHashBuilder, UnitWeighter};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use tokio_stream::wrappers::BroadcastStream;
 use tonic::{async_trait, Request, Response, Status};
+use tonlibjson_client::account_cell::parse_account_state;
+use tonlibjson_client::boc::{cell_tree, parse_base64_boc};
+use tonlibjson_client::breaker::is_circuit_open;
 use tonlibjson_client::ton::TonClient;
 
 #[derive(new)]
 pub struct MessageService {
     client: TonClient,
+    #[new(default)]
+    sent: Option<Arc<SentMessages>>,
+    #[new(default)]
+    journal: Option<Arc<SendJournal>>,
+    #[new(default)]
+    rejected: Option<Arc<RejectedMessages>>,
+    #[new(default)]
+    queues: Option<Arc<SendQueues>>,
+    #[new(default)]
+    recent: Option<Arc<RecentMessages>>,
+}
+
+impl MessageService {
+    pub fn set_sent_messages(mut self, sent: Arc<SentMessages>) -> Self {
+        self.sent = Some(sent);
+        self
+    }
+
+    /// Journals messages before they are relayed and their outcome after
+    pub fn set_journal(mut self, journal: Arc<SendJournal>) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    /// Keeps messages the lite server rejects for AdminService
+    pub fn set_rejected_messages(mut self, rejected: Arc<RejectedMessages>) -> Self {
+        self.rejected = Some(rejected);
+        self
+    }
+
+    /// Relays messages to the same wallet one at a time
+    pub fn set_send_queues(mut self, queues: SendQueues) -> Self {
+        self.queues = Some(Arc::new(queues));
+        self
+    }
+
+    /// Feeds sent messages to GetRecentMessages and WatchRecentMessages
+    pub fn set_recent_messages(mut self, recent: Arc<RecentMessages>) -> Self {
+        self.recent = Some(recent);
+        self
+    }
+
+    fn recent(&self) -> Result<&RecentMessages, Status> {
+        self.recent
+            .as_deref()
+            .ok_or_else(|| Status::unimplemented("recent messages are not enabled"))
+    }
+
+    fn record_recent(&self, hash: &str, body: &str, api_key: Option<&str>) {
+        let Some(recent) = &self.recent else {
+            return;
+        };
+        let size = STANDARD.decode(body).map_or(0, |boc| boc.len());
+
+        recent.record(
+            hash.to_owned(),
+            external_destination(body).ok(),
+            size,
+            api_key,
+        );
+    }
+
+    fn record_rejected(&self, body: &str, error: &anyhow::Error, api_key: Option<String>) {
+        let Some(rejected) = &self.rejected else {
+            return;
+        };
+        // the circuit rejects messages without asking a lite server, neither are bocs
+        // which can't be parsed worth keeping
+        if is_circuit_open(error) {
+            return;
+        }
+        let Ok(hash) = message_hash(body) else {
+            return;
+        };
+
+        rejected.record(hash, body.to_owned(), error.to_string(), api_key);
+    }
+
+    /// Hash of the journaled message, the request fails if it can't be journaled
+    async fn journal_accepted(
+        &self,
+        body: &str,
+        api_key: Option<String>,
+    ) -> Result<Option<String>, Status> {
+        let Some(journal) = self.journal.clone() else {
+            return Ok(None);
+        };
+
+        let hash = message_hash(body).map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let accepted = Accepted {
+            hash: hash.clone(),
+            boc: body.to_owned(),
+            accepted_at: unix_time(SystemTime::now()),
+            api_key,
+        };
+        tokio::task::spawn_blocking(move || journal.accept(accepted))
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|result| result)
+            .map_err(|e| Status::internal(format!("failed to journal the message: {}", e)))?;
+
+        Ok(Some(hash))
+    }
+
+    async fn journal_relayed(&self, hash: Option<String>, error: Option<String>) {
+        let (Some(journal), Some(hash)) = (self.journal.clone(), hash) else {
+            return;
+        };
+
+        if let Err(e) = tokio::task::spawn_blocking(move || journal.relayed(&hash, error))
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|result| result)
+        {
+            tracing::error!(error = ?e, "failed to journal the outcome of a message");
+        }
+    }
+}
+
+fn duplicate(hash: String) -> Response<SendResponse> {
+    no_store(Response::new(SendResponse {
+        hash,
+        duplicate: true,
+    }))
+}
+
+fn unix_time(time: SystemTime) -> i64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
 }
 
 #[async_trait]
@@ -18,14 +172,315 @@ impl BaseMessageService for MessageService {
         &self,
         request: Request<SendRequest>,
     ) -> Result<Response<SendResponse>, Status> {
+        let api_key = request
+            .extensions()
+            .get::<ApiKey>()
+            .map(|key| key.name.clone());
         let msg = request.into_inner();
 
-        let hash = self
-            .client
-            .send_message_returning_hash(&msg.body)
+        let key = match (&self.sent, msg.idempotency_key) {
+            (None, _) => None,
+            (Some(_), Some(key)) => Some(key),
+            (Some(_), None) => {
+                Some(message_hash(&msg.body).map_err(|e| Status::invalid_argument(e.to_string()))?)
+            }
+        };
+
+        if let (Some(sent), Some(key)) = (&self.sent, &key) {
+            if let Some(hash) = sent.get(key) {
+                return Ok(duplicate(hash));
+            }
+        }
+
+        let _turn = match &self.queues {
+            Some(queues) => {
+                let wallet = external_destination(&msg.body)
+                    .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+                Some(queues.acquire(&wallet).await?)
+            }
+            None => None,
+        };
+        // a duplicate which arrived meanwhile or waited for the turn finds the hash here,
+        // or waits until the one being relayed is sent
+        let reservation = match (&self.sent, &key) {
+            (Some(sent), Some(key)) => match sent.reserve(key).await {
+                Ok(hash) => return Ok(duplicate(hash)),
+                Err(reservation) => Some(reservation),
+            },
+            _ => None,
+        };
+
+        let journaled = self.journal_accepted(&msg.body, api_key.clone()).await?;
+        let sent = self.client.send_message_with_retry(&msg.body).await;
+        if let Err(e) = &sent {
+            self.record_rejected(&msg.body, e, api_key.clone());
+        }
+        self.journal_relayed(
+            journaled,
+            sent.as_ref().err().map(|e: &anyhow::Error| e.to_string()),
+        )
+        .await;
+
+        let sent = sent.map_err(|e| match is_circuit_open(&e) {
+            true => Status::unavailable(e.to_string()),
+            false => Status::internal(e.to_string()),
+        })?;
+
+        if let Some(reservation) = reservation {
+            reservation.sent(sent.hash.clone());
+        }
+        self.record_recent(&sent.hash, &msg.body, api_key.as_deref());
+
+        Ok(no_store(Response::new(SendResponse {
+            hash: sent.hash,
+            duplicate: sent.duplicate,
+        })))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn emulate_message(
+        &self,
+        request: Request<EmulateRequest>,
+    ) -> Result<Response<EmulateResponse>, Status> {
+        let (metadata, extensions, msg) = request.into_parts();
+
+        let mut emulation = emulate_message(&self.client, &msg.body).await?;
+        if msg.then_send && emulation.accepted && !emulation.aborted {
+            // the send is made on behalf of the same caller, e.g. counted to its api key
+            let sent = self
+                .send_message(Request::from_parts(
+                    metadata,
+                    extensions,
+                    SendRequest {
+                        body: msg.body,
+                        idempotency_key: None,
+                    },
+                ))
+                .await?;
+
+            emulation.hash = Some(sent.into_inner().hash);
+        }
+
+        Ok(no_store(Response::new(emulation)))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn parse_boc(
+        &self,
+        request: Request<ParseBocRequest>,
+    ) -> Result<Response<ParseBocResponse>, Status> {
+        let root = parse_base64_boc(&request.into_inner().boc)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        Ok(Response::new(cell_tree(&root).into()))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn parse_account_state(
+        &self,
+        request: Request<ParseAccountStateRequest>,
+    ) -> Result<Response<ParsedAccount>, Status> {
+        let state = parse_account_state(&request.into_inner().boc)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        Ok(Response::new(state.into()))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn get_recent_messages(
+        &self,
+        request: Request<GetRecentMessagesRequest>,
+    ) -> Result<Response<GetRecentMessagesResponse>, Status> {
+        let limit = match request.into_inner().limit {
+            0 => usize::MAX,
+            limit => limit as usize,
+        };
+        let messages = self.recent()?.list(limit);
+
+        Ok(no_store(Response::new(GetRecentMessagesResponse {
+            messages: messages.into_iter().map(Into::into).collect(),
+        })))
+    }
+
+    type WatchRecentMessagesStream =
+        Pin<Box<dyn Stream<Item = Result<RecentMessage, Status>> + Send + 'static>>;
+
+    #[tracing::instrument(skip_all, err)]
+    async fn watch_recent_messages(
+        &self,
+        _: Request<WatchRecentMessagesRequest>,
+    ) -> Result<Response<Self::WatchRecentMessagesStream>, Status> {
+        // a subscriber which falls behind misses updates rather than holding the feed back
+        let stream = BroadcastStream::new(self.recent()?.subscribe())
+            .filter_map(|message| futures::future::ready(message.ok().map(|m| Ok(m.into()))))
+            .boxed();
+
+        Ok(Response::new(stream))
+    }
+}
+
+/// Recently sent messages by idempotency key, bounded by capacity and expired by ttl
+pub struct SentMessages {
+    ttl: Duration,
+    cache: Cache<String, (Instant, String)>,
+    entry_size: EntrySize,
+}
+
+/// Length of a base64 message hash
+const HASH_LEN: usize = 44;
+
+/// Key of a message being relayed, requests with the same key wait for it, the next of them
+/// relays the message itself if it's dropped without being sent
+struct Reservation<'a>(
+    PlaceholderGuard<
+        'a,
+        String,
+        (Instant, String),
+        UnitWeighter,
+        DefaultHashBuilder,
+        DefaultLifecycle<String, (Instant, String)>,
+    >,
+);
+
+impl Reservation<'_> {
+    fn sent(self, hash: String) {
+        let _ = self.0.insert((Instant::now(), hash));
+    }
+}
+
+impl SentMessages {
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            ttl,
+            cache: Cache::new(capacity),
+            entry_size: Default::default(),
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<String> {
+        let hash = self.cache.get(key).and_then(|(sent_at, hash)| {
+            if sent_at.elapsed() > self.ttl {
+                self.cache.remove(key);
+
+                return None;
+            }
+
+            Some(hash)
+        });
+        tracing::debug!(cache = "sent_messages", hit = hash.is_some());
+
+        hash
+    }
+
+    /// Hash of the message sent with `key` within ttl, otherwise the key is reserved
+    /// for the caller to send it, waits while another request holds the key
+    async fn reserve(&self, key: &str) -> Result<String, Reservation<'_>> {
+        loop {
+            match self.cache.get_value_or_guard_async(key).await {
+                Ok((sent_at, _)) if sent_at.elapsed() > self.ttl => {
+                    self.cache.remove(key);
+                }
+                Ok((_, hash)) => return Ok(hash),
+                Err(guard) => {
+                    self.entry_size.sample(|| {
+                        std::mem::size_of::<(String, (Instant, String))>() + key.len() + HASH_LEN
+                    });
+
+                    return Err(Reservation(guard));
+                }
+            }
+        }
+    }
+}
+
+/// Messages are forgotten as a whole, the cache has no order to drop the oldest ones by,
+/// so a message resent with a forgotten key is relayed again
+impl Tracked for SentMessages {
+    fn usage(&self) -> usize {
+        self.cache.len() * self.entry_size.get()
+    }
+
+    fn shrink(&self, _: usize) -> usize {
+        let freed = self.usage();
+        self.cache.clear();
+
+        freed
+    }
+}
+
+fn message_hash(body: &str) -> anyhow::Result<String> {
+    Ok(STANDARD.encode(parse_base64_boc(body)?.hash()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn sent_messages_expire() {
+        let sent = SentMessages::new(Duration::ZERO, 16);
+        sent.reserve("key")
+            .await
+            .unwrap_err()
+            .sent("hash".to_owned());
+        std::thread::sleep(Duration::from_millis(1));
+
+        assert_eq!(sent.get("key"), None);
+        assert!(sent.reserve("key").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn sent_messages_within_ttl() {
+        let sent = SentMessages::new(Duration::from_secs(600), 16);
+        sent.reserve("key")
             .await
-            .map_err(|e| Status::internal(e.to_string()))?;
+            .unwrap_err()
+            .sent("hash".to_owned());
+
+        assert_eq!(sent.get("key"), Some("hash".to_owned()));
+        assert_eq!(sent.reserve("key").await.ok(), Some("hash".to_owned()));
+        assert_eq!(sent.get("other"), None);
+    }
+
+    #[tokio::test]
+    async fn duplicates_wait_for_the_reservation() {
+        let sent = Arc::new(SentMessages::new(Duration::from_secs(600), 16));
+        let reservation = sent.reserve("key").await.unwrap_err();
+
+        let waiting = {
+            let sent = sent.clone();
+            tokio::spawn(async move { sent.reserve("key").await.ok() })
+        };
+        tokio::task::yield_now().await;
+        assert!(!waiting.is_finished());
+
+        reservation.sent("hash".to_owned());
+        assert_eq!(waiting.await.unwrap(), Some("hash".to_owned()));
+    }
+
+    #[tokio::test]
+    async fn failed_send_passes_the_reservation_on() {
+        let sent = Arc::new(SentMessages::new(Duration::from_secs(600), 16));
+        let reservation = sent.reserve("key").await.unwrap_err();
+
+        let waiting = {
+            let sent = sent.clone();
+            tokio::spawn(async move { sent.reserve("key").await.is_err() })
+        };
+        tokio::task::yield_now().await;
+
+        drop(reservation);
+        assert!(waiting.await.unwrap());
+        assert_eq!(sent.get("key"), None);
+    }
+
+    #[test]
+    fn message_hash_of_boc() {
+        // empty cell
+        let hash = message_hash("te6cckEBAQEAAgAAAEysuc0=").unwrap();
 
-        Ok(Response::new(SendResponse { hash }))
+        assert_eq!(hash, "lqKW0iTyhcZ77pPDD4owkVfw2qNdxbh+QQt4YwoJz8c=");
+        assert!(message_hash("not a boc").is_err());
     }
 }
@@ -0,0 +1,310 @@
+use crate::quota::{quoted_method, ApiKey, Quotas, API_KEY_HEADER};
+use crate::ton::method_service_server::MethodService as BaseMethodService;
+use crate::ton::{ListMethodsRequest, ListMethodsResponse, MethodInfo};
+use futures::future::{ready, Either, Ready};
+use prost::Message;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tonic::body::BoxBody;
+use tonic::codegen::http::{Request, Response};
+use tonic::codegen::Service;
+use tonic::{async_trait, Status};
+use tower::util::Oneshot;
+use tower::{Layer, ServiceExt};
+
+/// Methods a key unlocks or loses on top of the server policy, e.g. {"allow": ["GetFullTransactions"]}
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct MethodOverrides {
+    #[serde(default)]
+    pub allow: Vec<String>,
+    #[serde(default)]
+    pub deny: Vec<String>,
+}
+
+impl MethodOverrides {
+    fn allows(&self, method: &str) -> Option<bool> {
+        let matches = |m: &String| normalize_method(m) == method;
+
+        if self.deny.iter().any(matches) {
+            Some(false)
+        } else if self.allow.iter().any(matches) {
+            Some(true)
+        } else {
+            None
+        }
+    }
+}
+
+/// `GetAccountState`, `getAccountState` and `/ton.AccountService/GetAccountState` are the same method
+pub fn normalize_method(method: &str) -> String {
+    method
+        .rsplit(['/', '.'])
+        .next()
+        .unwrap_or(method)
+        .to_ascii_lowercase()
+}
+
+/// Methods enabled on the server, keys override it with [`MethodOverrides`]
+#[derive(Debug, Clone, Default)]
+pub struct MethodPolicy {
+    allow: Option<HashSet<String>>,
+    deny: HashSet<String>,
+}
+
+impl MethodPolicy {
+    /// Every method which isn't denied is enabled if `allow` is empty
+    pub fn new(allow: &[String], deny: &[String]) -> Self {
+        let normalize = |methods: &[String]| methods.iter().map(|m| normalize_method(m)).collect();
+
+        Self {
+            allow: (!allow.is_empty()).then(|| normalize(allow)),
+            deny: normalize(deny),
+        }
+    }
+
+    pub fn is_enabled(&self, method: &str, key: Option<&ApiKey>) -> bool {
+        let method = normalize_method(method);
+        if let Some(allowed) = key.and_then(|key| key.methods.allows(&method)) {
+            return allowed;
+        }
+
+        !self.deny.contains(&method)
+            && self
+                .allow
+                .as_ref()
+                .map_or(true, |allow| allow.contains(&method))
+    }
+
+    pub fn check(&self, method: &str, key: Option<&ApiKey>) -> Result<(), Status> {
+        if self.is_enabled(method, key) {
+            return Ok(());
+        }
+
+        let hint = match key {
+            Some(key) => format!("api key {} doesn't unlock it", key.name),
+            None => "an api key may unlock it".to_owned(),
+        };
+
+        Err(Status::unimplemented(format!(
+            "method {} is disabled, {}",
+            method, hint
+        )))
+    }
+}
+
+/// Api key of a request, if keys are required and it is a known one
+fn request_key<'a>(quotas: Option<&'a Quotas>, key: Option<&str>) -> Option<&'a ApiKey> {
+    quotas?.key(key?)
+}
+
+#[derive(Clone)]
+pub struct MethodPolicyLayer {
+    policy: Arc<MethodPolicy>,
+    quotas: Option<Arc<Quotas>>,
+}
+
+impl MethodPolicyLayer {
+    pub fn new(policy: Arc<MethodPolicy>, quotas: Option<Arc<Quotas>>) -> Self {
+        Self { policy, quotas }
+    }
+}
+
+impl<S> Layer<S> for MethodPolicyLayer {
+    type Service = MethodPolicyService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        MethodPolicyService {
+            inner,
+            policy: self.policy.clone(),
+            quotas: self.quotas.clone(),
+        }
+    }
+}
+
+/// Rejects requests of methods disabled for their key with `UNIMPLEMENTED`
+#[derive(Clone)]
+pub struct MethodPolicyService<S> {
+    inner: S,
+    policy: Arc<MethodPolicy>,
+    quotas: Option<Arc<Quotas>>,
+}
+
+impl<S, B> Service<Request<B>> for MethodPolicyService<S>
+where
+    S: Service<Request<B>, Response = Response<BoxBody>> + Clone,
+{
+    type Response = Response<BoxBody>;
+    type Error = S::Error;
+    type Future = Either<Oneshot<S, Request<B>>, Ready<Result<Response<BoxBody>, S::Error>>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        if let Some(method) = quoted_method(req.uri().path()) {
+            let key = req
+                .headers()
+                .get(API_KEY_HEADER)
+                .and_then(|v| v.to_str().ok());
+            let key = request_key(self.quotas.as_deref(), key);
+            if let Err(status) = self.policy.check(method, key) {
+                return Either::Right(ready(Ok(status.to_http())));
+            }
+        }
+
+        Either::Left(self.inner.clone().oneshot(req))
+    }
+}
+
+/// Lists methods of the served services, as enabled for the key of the request
+pub struct MethodService {
+    policy: Arc<MethodPolicy>,
+    quotas: Option<Arc<Quotas>>,
+    methods: Vec<(String, String)>,
+}
+
+impl MethodService {
+    /// `services` are full names of the served services, e.g. `ton.AccountService`
+    pub fn new(
+        policy: Arc<MethodPolicy>,
+        quotas: Option<Arc<Quotas>>,
+        services: &[&str],
+    ) -> anyhow::Result<Self> {
+        let descriptors = prost_types::FileDescriptorSet::decode(crate::ton::FILE_DESCRIPTOR_SET)?;
+
+        let mut methods = Vec::new();
+        for file in descriptors.file {
+            let package = file.package().to_owned();
+            for service in file.service {
+                let name = format!("{}.{}", package, service.name());
+                if !services.contains(&name.as_str()) {
+                    continue;
+                }
+
+                for method in service.method {
+                    methods.push((name.clone(), method.name().to_owned()));
+                }
+            }
+        }
+
+        Ok(Self {
+            policy,
+            quotas,
+            methods,
+        })
+    }
+}
+
+#[async_trait]
+impl BaseMethodService for MethodService {
+    async fn list_methods(
+        &self,
+        request: tonic::Request<ListMethodsRequest>,
+    ) -> Result<tonic::Response<ListMethodsResponse>, Status> {
+        let key = request
+            .metadata()
+            .get(API_KEY_HEADER)
+            .and_then(|v| v.to_str().ok());
+        let key = request_key(self.quotas.as_deref(), key);
+
+        let methods = self
+            .methods
+            .iter()
+            .map(|(service, name)| MethodInfo {
+                service: service.clone(),
+                name: name.clone(),
+                enabled: self.policy.is_enabled(name, key),
+            })
+            .collect();
+
+        Ok(tonic::Response::new(ListMethodsResponse { methods }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tonic::Code;
+
+    fn archival() -> ApiKey {
+        ApiKey {
+            name: "archival".to_owned(),
+            daily: None,
+            monthly: None,
+            methods: MethodOverrides {
+                allow: vec!["getFullTransactions".to_owned()],
+                deny: vec!["SendMessage".to_owned()],
+            },
+        }
+    }
+
+    #[test]
+    fn method_names_are_normalized() {
+        assert_eq!(normalize_method("GetAccountState"), "getaccountstate");
+        assert_eq!(normalize_method("getAccountState"), "getaccountstate");
+        assert_eq!(
+            normalize_method("/ton.AccountService/GetAccountState"),
+            "getaccountstate"
+        );
+        assert_eq!(
+            normalize_method("ton.AccountService.GetAccountState"),
+            "getaccountstate"
+        );
+    }
+
+    #[test]
+    fn deny_list() {
+        let policy = MethodPolicy::new(&[], &["GetFullTransactions".to_owned()]);
+
+        assert!(policy.is_enabled("GetAccountState", None));
+        assert!(!policy.is_enabled("getFullTransactions", None));
+        assert!(policy.is_enabled("GetFullTransactions", Some(&archival())));
+    }
+
+    #[test]
+    fn allow_list() {
+        let policy = MethodPolicy::new(
+            &["GetAccountState".to_owned(), "SendMessage".to_owned()],
+            &[],
+        );
+
+        assert!(policy.is_enabled("GetAccountState", None));
+        assert!(!policy.is_enabled("GetTransactions", None));
+        assert!(!policy.is_enabled("SendMessage", Some(&archival())));
+        assert!(policy.is_enabled("GetFullTransactions", Some(&archival())));
+    }
+
+    #[test]
+    fn disabled_method_is_unimplemented_with_hint() {
+        let policy = MethodPolicy::new(&[], &["GetFullTransactions".to_owned()]);
+
+        let status = policy.check("GetFullTransactions", None).unwrap_err();
+
+        assert_eq!(status.code(), Code::Unimplemented);
+        assert!(status.message().contains("api key"), "{}", status.message());
+    }
+
+    #[tokio::test]
+    async fn list_methods_of_served_services() {
+        let policy = Arc::new(MethodPolicy::new(&[], &["SendMessage".to_owned()]));
+        let service = MethodService::new(policy, None, &["ton.MessageService"]).unwrap();
+
+        let methods = service
+            .list_methods(tonic::Request::new(ListMethodsRequest {}))
+            .await
+            .unwrap()
+            .into_inner()
+            .methods;
+
+        assert!(methods.iter().all(|m| m.service == "ton.MessageService"));
+        assert!(methods
+            .iter()
+            .any(|m| m.name == "SendMessage" && !m.enabled));
+        assert!(methods
+            .iter()
+            .any(|m| m.name == "EmulateMessage" && m.enabled));
+    }
+}
@@ -0,0 +1,308 @@
+use anyhow::anyhow;
+use futures::future::{ready, BoxFuture};
+use futures::{FutureExt, TryFutureExt};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tonic::body::BoxBody;
+use tonic::codegen::http::{HeaderValue, Request, Response};
+use tonic::codegen::Service;
+use tonic::server::NamedService;
+use tonic::Status;
+use tonlibjson_client::zero_state::ZeroState;
+use tower::ServiceExt;
+use url::Url;
+
+/// Metadata key naming the network of a request, the default network serves requests without it.
+/// Responses carry the tag of the network which served them under the same key
+pub const NETWORK_HEADER: &str = "x-ton-network";
+
+/// Routes requests to the service of the network named by [`NETWORK_HEADER`], by its name or
+/// its tag, see [`ZeroState::tag`]. Any other value is refused before the method runs, so a
+/// request asserting one network never reaches another
+#[derive(Clone)]
+pub struct NetworkRouter<S> {
+    default: String,
+    services: Arc<HashMap<String, S>>,
+    /// tags and zero state root hashes of networks
+    aliases: Arc<HashMap<String, String>>,
+    tags: Arc<HashMap<String, HeaderValue>>,
+}
+
+impl<S> NetworkRouter<S> {
+    pub fn new(default: String, services: HashMap<String, S>) -> Self {
+        Self {
+            default,
+            services: Arc::new(services),
+            aliases: Default::default(),
+            tags: Default::default(),
+        }
+    }
+
+    /// Zero states of networks, a network is tagged by its name without one
+    pub fn set_zero_states(mut self, zero_states: &HashMap<String, ZeroState>) -> Self {
+        let mut aliases = HashMap::new();
+        let mut tags = HashMap::new();
+        for (network, zero_state) in zero_states {
+            let tag = zero_state.tag();
+            aliases.insert(zero_state.root_hash.clone(), network.clone());
+            aliases.insert(tag.clone(), network.clone());
+            if let Ok(tag) = HeaderValue::from_str(&tag) {
+                tags.insert(network.clone(), tag);
+            }
+        }
+        self.aliases = Arc::new(aliases);
+        self.tags = Arc::new(tags);
+
+        self
+    }
+
+    fn tag(&self, network: &str) -> Option<HeaderValue> {
+        self.tags
+            .get(network)
+            .cloned()
+            .or_else(|| HeaderValue::from_str(network).ok())
+    }
+}
+
+impl<S: NamedService> NamedService for NetworkRouter<S> {
+    const NAME: &'static str = S::NAME;
+}
+
+impl<S, B> Service<Request<B>> for NetworkRouter<S>
+where
+    S: Service<Request<B>, Response = Response<BoxBody>, Error = Infallible>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send,
+    B: Send + 'static,
+{
+    type Response = Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<'static, Result<Response<BoxBody>, Infallible>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        let network = match req.headers().get(NETWORK_HEADER).map(|v| v.to_str()) {
+            None => self.default.as_str(),
+            Some(Ok(network)) if self.services.contains_key(network) => network,
+            Some(Ok(network)) => self.aliases.get(network).map_or(network, String::as_str),
+            Some(Err(_)) => {
+                return ready(Ok(Status::invalid_argument(format!(
+                    "{} must be ascii",
+                    NETWORK_HEADER
+                ))
+                .to_http()))
+                .boxed()
+            }
+        };
+
+        let Some(service) = self.services.get(network) else {
+            return ready(Ok(Status::failed_precondition(format!(
+                "network mismatch: {} is not served here",
+                network
+            ))
+            .to_http()))
+            .boxed();
+        };
+
+        let tag = self.tag(network);
+        service
+            .clone()
+            .oneshot(req)
+            .map_ok(move |mut response| {
+                if let Some(tag) = tag {
+                    response.headers_mut().insert(NETWORK_HEADER, tag);
+                }
+
+                response
+            })
+            .boxed()
+    }
+}
+
+/// Parses `name=config_url`
+pub fn parse_network(s: &str) -> anyhow::Result<(String, Url)> {
+    let (name, url) = s
+        .split_once('=')
+        .ok_or_else(|| anyhow!("expected name=config_url, got {}", s))?;
+
+    Ok((name.to_owned(), Url::parse(url)?))
+}
+
+/// Parses `name=root_hash:file_hash`
+pub fn parse_zero_state(s: &str) -> anyhow::Result<(String, ZeroState)> {
+    let (name, zero_state) = s
+        .split_once('=')
+        .ok_or_else(|| anyhow!("expected name=root_hash:file_hash, got {}", s))?;
+
+    Ok((name.to_owned(), zero_state.parse()?))
+}
+
+/// Zero state `network` must start from: a configured one, a well-known one by the name,
+/// or the one of the default config when it's the only network served
+pub fn expected_zero_state(
+    network: &str,
+    configured: &[(String, ZeroState)],
+    default_only: bool,
+) -> anyhow::Result<ZeroState> {
+    if let Some((_, zero_state)) = configured.iter().find(|(name, _)| name == network) {
+        return Ok(zero_state.clone());
+    }
+    if default_only {
+        return Ok(ZeroState::default_network());
+    }
+
+    ZeroState::known(network).ok_or_else(|| {
+        anyhow!(
+            "zero state of network {} is unknown, set it with --zero-state or pass --allow-custom-network",
+            network
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tonic::body::empty_body;
+
+    #[derive(Clone)]
+    struct Named(&'static str);
+
+    impl Service<Request<()>> for Named {
+        type Response = Response<BoxBody>;
+        type Error = Infallible;
+        type Future = BoxFuture<'static, Result<Response<BoxBody>, Infallible>>;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _: Request<()>) -> Self::Future {
+            let name = self.0;
+
+            async move {
+                Ok(Response::builder()
+                    .header("network", name)
+                    .body(empty_body())
+                    .unwrap())
+            }
+            .boxed()
+        }
+    }
+
+    fn router() -> NetworkRouter<Named> {
+        NetworkRouter::new(
+            "mainnet".to_owned(),
+            HashMap::from([
+                ("mainnet".to_owned(), Named("mainnet")),
+                ("testnet".to_owned(), Named("testnet")),
+                ("private".to_owned(), Named("private")),
+            ]),
+        )
+        .set_zero_states(&HashMap::from([
+            ("mainnet".to_owned(), ZeroState::mainnet()),
+            (
+                "private".to_owned(),
+                ZeroState {
+                    root_hash: "root".to_owned(),
+                    file_hash: "file".to_owned(),
+                },
+            ),
+        ]))
+    }
+
+    async fn call(network: Option<&str>) -> Response<BoxBody> {
+        let mut req = Request::builder();
+        if let Some(network) = network {
+            req = req.header(NETWORK_HEADER, network);
+        }
+
+        router().oneshot(req.body(()).unwrap()).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn routes_by_header() {
+        assert_eq!(call(None).await.headers()["network"], "mainnet");
+        assert_eq!(call(Some("testnet")).await.headers()["network"], "testnet");
+    }
+
+    #[tokio::test]
+    async fn responses_are_tagged() {
+        assert_eq!(call(None).await.headers()[NETWORK_HEADER], "mainnet");
+        assert_eq!(
+            call(Some("testnet")).await.headers()[NETWORK_HEADER],
+            "testnet"
+        );
+        assert_eq!(
+            call(Some("private")).await.headers()[NETWORK_HEADER],
+            "root"
+        );
+    }
+
+    #[tokio::test]
+    async fn routes_by_tag() {
+        assert_eq!(call(Some("root")).await.headers()["network"], "private");
+        assert_eq!(
+            call(Some(&ZeroState::mainnet().root_hash)).await.headers()["network"],
+            "mainnet"
+        );
+    }
+
+    #[tokio::test]
+    async fn mismatch_is_refused() {
+        let response = call(Some("devnet")).await;
+
+        assert_eq!(response.headers()["grpc-status"], "9");
+        assert!(response.headers().get("network").is_none());
+        assert!(response.headers().get(NETWORK_HEADER).is_none());
+    }
+
+    #[test]
+    fn parse_network_valid() {
+        let (name, url) =
+            parse_network("testnet=https://ton.org/testnet-global.config.json").unwrap();
+
+        assert_eq!(name, "testnet");
+        assert_eq!(url.as_str(), "https://ton.org/testnet-global.config.json");
+        assert!(parse_network("testnet").is_err());
+    }
+
+    #[test]
+    fn zero_state_of_network() {
+        let private = ZeroState {
+            root_hash: "root".to_owned(),
+            file_hash: "file".to_owned(),
+        };
+        let configured = vec![("private".to_owned(), private.clone())];
+
+        assert_eq!(
+            expected_zero_state("private", &configured, false).unwrap(),
+            private
+        );
+        assert_eq!(
+            expected_zero_state("testnet", &configured, false).unwrap(),
+            ZeroState::testnet()
+        );
+        assert_eq!(
+            expected_zero_state("mainnet", &[], true).unwrap(),
+            ZeroState::default_network()
+        );
+        assert!(expected_zero_state("staging", &configured, false).is_err());
+    }
+
+    #[test]
+    fn parse_zero_state_valid() {
+        let (name, zero_state) = parse_zero_state("private=root:file").unwrap();
+
+        assert_eq!(name, "private");
+        assert_eq!(zero_state.root_hash, "root");
+        assert_eq!(zero_state.file_hash, "file");
+        assert!(parse_zero_state("private=root").is_err());
+    }
+}
@@ -0,0 +1,244 @@
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use std::any::Any;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tonic::body::BoxBody;
+use tonic::codegen::http::{HeaderMap, Request, Response};
+use tonic::codegen::{Body, Bytes, Service};
+use tonic::Status;
+use tower::{Layer, ServiceExt};
+use uuid::Uuid;
+
+/// Id of a request, sent back along with `INTERNAL` if its handler panics
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// The id sent by the client, a new one if it has sent none
+fn request_id(headers: &HeaderMap) -> String {
+    headers
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map_or_else(|| Uuid::new_v4().to_string(), ToOwned::to_owned)
+}
+
+fn message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown")
+}
+
+/// Logs the payload and makes the status sent instead, the payload stays on the server
+fn panicked(request_id: &str, payload: Box<dyn Any + Send>) -> Status {
+    tracing::error!(request_id, panic = message(&*payload), "handler panicked");
+    metrics::counter!("ton_grpc_panics_total").increment(1);
+
+    let mut status = Status::internal("internal panic");
+    if let Ok(request_id) = request_id.parse() {
+        status.metadata_mut().insert(REQUEST_ID_HEADER, request_id);
+    }
+
+    status
+}
+
+#[derive(Clone, Default)]
+pub struct CatchPanicLayer;
+
+impl<S> Layer<S> for CatchPanicLayer {
+    type Service = CatchPanicService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        CatchPanicService { inner }
+    }
+}
+
+/// Turns a panic of a handler or of its response stream into `INTERNAL` rather than a reset
+/// of the connection
+#[derive(Clone)]
+pub struct CatchPanicService<S> {
+    inner: S,
+}
+
+impl<S, B> Service<Request<B>> for CatchPanicService<S>
+where
+    S: Service<Request<B>, Response = Response<BoxBody>> + Clone + Send + 'static,
+    S::Error: Send + 'static,
+    S::Future: Send,
+    B: Send + 'static,
+{
+    type Response = Response<BoxBody>;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        let request_id = request_id(req.headers());
+        let inner = self.inner.clone();
+
+        async move {
+            // a panic of `call` itself is caught along with one of the future
+            let response = AssertUnwindSafe(async move { inner.oneshot(req).await })
+                .catch_unwind()
+                .await;
+
+            match response {
+                Ok(response) => {
+                    Ok(response?.map(|body| CatchPanicBody::new(body, request_id).boxed_unsync()))
+                }
+                Err(payload) => Ok(panicked(&request_id, payload).to_http()),
+            }
+        }
+        .boxed()
+    }
+}
+
+struct CatchPanicBody {
+    inner: BoxBody,
+    request_id: String,
+    panicked: Option<Status>,
+}
+
+impl CatchPanicBody {
+    fn new(inner: BoxBody, request_id: String) -> Self {
+        Self {
+            inner,
+            request_id,
+            panicked: None,
+        }
+    }
+}
+
+impl Body for CatchPanicBody {
+    type Data = Bytes;
+    type Error = Status;
+
+    fn poll_data(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let this = self.get_mut();
+        if this.panicked.is_some() {
+            return Poll::Ready(None);
+        }
+
+        let inner = &mut this.inner;
+        match catch_unwind(AssertUnwindSafe(|| Pin::new(inner).poll_data(cx))) {
+            Ok(poll) => poll,
+            Err(payload) => {
+                // the stream ends here, the status is sent in the trailers
+                this.panicked = Some(panicked(&this.request_id, payload));
+
+                Poll::Ready(None)
+            }
+        }
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        let this = self.get_mut();
+        if this.panicked.is_none() {
+            let inner = &mut this.inner;
+            match catch_unwind(AssertUnwindSafe(|| Pin::new(inner).poll_trailers(cx))) {
+                Ok(poll) => return poll,
+                Err(payload) => this.panicked = Some(panicked(&this.request_id, payload)),
+            }
+        }
+
+        let mut trailers = HeaderMap::new();
+        if let Some(status) = &this.panicked {
+            status.add_header(&mut trailers)?;
+        }
+
+        Poll::Ready(Ok(Some(trailers)))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.panicked.is_none() && self.inner.is_end_stream()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream::BoxStream;
+    use futures::StreamExt;
+    use std::convert::Infallible;
+
+    struct StreamBody(BoxStream<'static, Result<Bytes, Status>>);
+
+    impl Body for StreamBody {
+        type Data = Bytes;
+        type Error = Status;
+
+        fn poll_data(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+        ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+            self.0.poll_next_unpin(cx)
+        }
+
+        fn poll_trailers(
+            self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+        ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+            Poll::Ready(Ok(None))
+        }
+    }
+
+    fn status_of(headers: &HeaderMap) -> Status {
+        Status::from_header_map(headers).expect("status is sent")
+    }
+
+    #[tokio::test]
+    async fn handler_panic_is_internal() {
+        let service = tower::service_fn(|_: Request<()>| async {
+            if true {
+                panic!("secret state");
+            }
+
+            Ok::<Response<BoxBody>, Infallible>(Response::new(tonic::body::empty_body()))
+        });
+        let request = Request::builder()
+            .header(REQUEST_ID_HEADER, "42")
+            .body(())
+            .unwrap();
+
+        let response = CatchPanicLayer
+            .layer(service)
+            .oneshot(request)
+            .await
+            .unwrap();
+
+        let status = status_of(response.headers());
+        assert_eq!(status.code(), tonic::Code::Internal);
+        assert_eq!(status.message(), "internal panic");
+        assert_eq!(status.metadata().get(REQUEST_ID_HEADER).unwrap(), "42");
+    }
+
+    #[tokio::test]
+    async fn stream_panic_ends_with_internal() {
+        let chunks = futures::stream::iter(0..3u8)
+            .map(|i| {
+                assert!(i < 1, "secret state");
+
+                Ok::<_, Status>(Bytes::from(vec![0, 0, 0, 0, 1, i]))
+            })
+            .boxed();
+        let inner = StreamBody(chunks).boxed_unsync();
+        let mut body = CatchPanicBody::new(inner, "42".to_owned());
+
+        assert!(body.data().await.is_some());
+        assert!(body.data().await.is_none());
+
+        let status = status_of(&body.trailers().await.unwrap().unwrap());
+        assert_eq!(status.code(), tonic::Code::Internal);
+        assert!(!status.message().contains("secret"));
+    }
+}
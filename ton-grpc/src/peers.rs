@@ -0,0 +1,243 @@
+use crate::network::NETWORK_HEADER;
+use crate::quota::API_KEY_HEADER;
+use crate::ton::block_service_client::BlockServiceClient;
+use crate::ton::GetMasterchainInfoRequest;
+use futures::future::join_all;
+use std::time::{Duration, Instant};
+use tokio::time::MissedTickBehavior;
+use tonic::transport::{Channel, Endpoint};
+use tonic::Request;
+use tonic_health::server::HealthReporter;
+use tonic_health::ServingStatus;
+use tonlibjson_client::ton::TonClient;
+use url::Url;
+
+/// Sibling instances of this proxy whose masterchain tips are compared with the local one
+#[derive(Debug, Clone)]
+pub struct PeerCheckPolicy {
+    pub peers: Vec<Url>,
+    pub api_key: Option<String>,
+    pub interval: Duration,
+    pub timeout: Duration,
+    /// masterchain blocks the local tip may be behind the highest tip of the peers
+    pub max_lag: i32,
+    /// how long the lag is over `max_lag` until the alarm is raised
+    pub grace: Duration,
+}
+
+/// Alarm of the local tip lagging the peers, raised once the lag is over `max_lag` for
+/// `grace` and cleared once it is back within half of `max_lag`.
+/// No peer tip at all clears it, an unreachable peer is no evidence of the local one being stale
+#[derive(Debug)]
+pub struct Divergence {
+    max_lag: i32,
+    grace: Duration,
+    lagging_since: Option<Instant>,
+    raised: bool,
+}
+
+impl Divergence {
+    pub fn new(max_lag: i32, grace: Duration) -> Self {
+        Self {
+            max_lag,
+            grace,
+            lagging_since: None,
+            raised: false,
+        }
+    }
+
+    pub fn is_raised(&self) -> bool {
+        self.raised
+    }
+
+    /// Whether the alarm is raised after comparing `local` with the highest tip of the peers
+    pub fn observe(&mut self, local: i32, peers: Option<i32>, now: Instant) -> bool {
+        let Some(lag) = peers.map(|peers| peers.saturating_sub(local)) else {
+            self.lagging_since = None;
+            self.raised = false;
+
+            return false;
+        };
+
+        if lag > self.max_lag {
+            let since = *self.lagging_since.get_or_insert(now);
+            if now.duration_since(since) >= self.grace {
+                self.raised = true;
+            }
+        } else {
+            self.lagging_since = None;
+            if lag <= self.max_lag / 2 {
+                self.raised = false;
+            }
+        }
+
+        self.raised
+    }
+}
+
+/// Polls the peers for their masterchain tips and keeps the `<network>.diverged` health
+/// service SERVING while the alarm of [`Divergence`] is raised
+pub async fn check_peers(
+    client: TonClient,
+    network: String,
+    policy: PeerCheckPolicy,
+    mut reporter: HealthReporter,
+) {
+    metrics::describe_gauge!(
+        "ton_grpc_peer_lag",
+        "Masterchain blocks the local tip is behind the highest tip of the peers"
+    );
+    metrics::describe_gauge!(
+        "ton_grpc_peer_diverged",
+        "Whether the local tip lags the peers beyond the threshold for the grace period"
+    );
+
+    let service = format!("{}.diverged", network);
+    reporter
+        .set_service_status(&service, ServingStatus::NotServing)
+        .await;
+
+    let peers: Vec<_> = policy
+        .peers
+        .iter()
+        .filter_map(|url| match Endpoint::from_shared(url.to_string()) {
+            Ok(endpoint) => Some((
+                url.clone(),
+                BlockServiceClient::new(endpoint.timeout(policy.timeout).connect_lazy()),
+            )),
+            Err(e) => {
+                tracing::error!(network, %url, error = ?e, "invalid peer");
+
+                None
+            }
+        })
+        .collect();
+
+    let mut divergence = Divergence::new(policy.max_lag, policy.grace);
+    let mut interval = tokio::time::interval(policy.interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    loop {
+        interval.tick().await;
+        let local = match client.get_masterchain_info().await {
+            Ok(info) => info.last.seqno,
+            Err(e) => {
+                tracing::warn!(network, error = ?e, "no local tip to compare with peers");
+
+                continue;
+            }
+        };
+
+        let tips =
+            join_all(peers.iter().map(|(url, peer)| {
+                peer_tip(url, peer.clone(), &network, policy.api_key.as_deref())
+            }))
+            .await;
+        let highest = tips.into_iter().flatten().max();
+        if let Some(highest) = highest {
+            metrics::gauge!("ton_grpc_peer_lag", "network" => network.clone())
+                .set(highest.saturating_sub(local) as f64);
+        }
+
+        let was_raised = divergence.is_raised();
+        let raised = divergence.observe(local, highest, Instant::now());
+        if raised == was_raised {
+            continue;
+        }
+
+        if raised {
+            tracing::warn!(network, local, ?highest, "tip lags the peers");
+        } else {
+            tracing::info!(network, local, ?highest, "tip caught up with the peers");
+        }
+        metrics::gauge!("ton_grpc_peer_diverged", "network" => network.clone()).set(raised as u8);
+        let status = if raised {
+            ServingStatus::Serving
+        } else {
+            ServingStatus::NotServing
+        };
+        reporter.set_service_status(&service, status).await;
+    }
+}
+
+async fn peer_tip(
+    url: &Url,
+    mut peer: BlockServiceClient<Channel>,
+    network: &str,
+    api_key: Option<&str>,
+) -> Option<i32> {
+    let mut request = Request::new(GetMasterchainInfoRequest::default());
+    request
+        .metadata_mut()
+        .insert(NETWORK_HEADER, network.parse().ok()?);
+    if let Some(api_key) = api_key {
+        request
+            .metadata_mut()
+            .insert(API_KEY_HEADER, api_key.parse().ok()?);
+    }
+
+    match peer.get_masterchain_info(request).await {
+        Ok(response) => response.into_inner().last.map(|block| block.seqno),
+        Err(e) => {
+            tracing::debug!(network, %url, error = ?e, "peer is unreachable");
+
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GRACE: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn alarm_is_raised_after_grace() {
+        let mut divergence = Divergence::new(10, GRACE);
+        let start = Instant::now();
+
+        assert!(!divergence.observe(100, Some(120), start));
+        assert!(!divergence.observe(100, Some(120), start + GRACE / 2));
+        assert!(divergence.observe(100, Some(120), start + GRACE));
+    }
+
+    #[test]
+    fn short_lag_resets_grace() {
+        let mut divergence = Divergence::new(10, GRACE);
+        let start = Instant::now();
+
+        assert!(!divergence.observe(100, Some(120), start));
+        assert!(!divergence.observe(115, Some(120), start + GRACE / 2));
+        assert!(!divergence.observe(100, Some(120), start + GRACE));
+        assert!(divergence.observe(100, Some(120), start + GRACE * 2));
+    }
+
+    #[test]
+    fn alarm_clears_within_half_of_max_lag() {
+        let mut divergence = Divergence::new(10, GRACE);
+        let start = Instant::now();
+        divergence.observe(100, Some(120), start);
+        divergence.observe(100, Some(120), start + GRACE);
+
+        assert!(divergence.observe(112, Some(120), start + GRACE * 2));
+        assert!(!divergence.observe(116, Some(120), start + GRACE * 3));
+    }
+
+    #[test]
+    fn no_peers_fail_open() {
+        let mut divergence = Divergence::new(10, GRACE);
+        let start = Instant::now();
+        divergence.observe(100, Some(120), start);
+        divergence.observe(100, Some(120), start + GRACE);
+
+        assert!(!divergence.observe(100, None, start + GRACE * 2));
+        assert!(!divergence.observe(100, Some(120), start + GRACE * 3));
+    }
+
+    #[test]
+    fn ahead_of_peers_is_not_lagging() {
+        let mut divergence = Divergence::new(10, Duration::ZERO);
+
+        assert!(!divergence.observe(120, Some(100), Instant::now()));
+    }
+}
@@ -0,0 +1,399 @@
+use crate::account::state_response;
+use crate::maintenance::Pause;
+use crate::masterchain::MasterchainWatcher;
+use crate::ton::GetAccountStateResponse;
+use futures::future::join_all;
+use futures::{StreamExt, TryStreamExt};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tonlibjson_client::address::AccountAddressData;
+use tonlibjson_client::block::{RawTransaction, TonBlockIdExt};
+use tonlibjson_client::ton::TonClient;
+
+/// Transactions of a single `raw.getTransactionsV2`, a lite server request each
+const TRANSACTIONS_PER_REQUEST: usize = 16;
+
+#[derive(Debug, Clone)]
+pub struct PrefetchPolicy {
+    /// addresses refreshed on every masterchain block while they're requested
+    pub addresses: Vec<String>,
+    /// time a refresh may take once a new block is seen, the addresses not refreshed by then
+    /// aren't served until the next block
+    pub deadline: Duration,
+    /// lite server requests of a refresh, the most requested addresses go first
+    pub max_requests_per_block: usize,
+    /// latest transactions kept per address
+    pub transactions: usize,
+    /// requests of an address count half as much after this long
+    pub half_life: Duration,
+    /// addresses requested less than this, decay included, aren't refreshed.
+    /// Configured addresses start as requested once
+    pub min_score: f64,
+}
+
+impl PrefetchPolicy {
+    fn requests_per_address(&self) -> usize {
+        1 + self.transactions.div_ceil(TRANSACTIONS_PER_REQUEST)
+    }
+}
+
+/// Requests of an address decaying exponentially since `at`
+#[derive(Debug, Clone, Copy)]
+struct Score {
+    value: f64,
+    at: Instant,
+}
+
+impl Score {
+    fn decayed(&self, now: Instant, half_life: Duration) -> f64 {
+        let elapsed = now.saturating_duration_since(self.at).as_secs_f64();
+
+        self.value * 0.5_f64.powf(elapsed / half_life.as_secs_f64())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Prefetched {
+    seqno: i32,
+    etag: String,
+    state: GetAccountStateResponse,
+    /// the newest first
+    transactions: Vec<RawTransaction>,
+    /// the account has no transactions besides these
+    complete: bool,
+}
+
+/// States and latest transactions of hot addresses refreshed on each masterchain block, so that
+/// requests for them don't reach lite servers when a block comes. Addresses are ranked by their
+/// requests and stop being refreshed once they're rarely requested
+pub struct HotAddresses {
+    policy: PrefetchPolicy,
+    /// by raw address
+    scores: Mutex<HashMap<String, Score>>,
+    prefetched: Mutex<HashMap<String, Prefetched>>,
+    /// masterchain seqno of the last refresh, only what it fetched is served
+    seqno: AtomicI32,
+}
+
+fn raw_address(address: &str) -> Option<String> {
+    AccountAddressData::from_str(address)
+        .ok()
+        .map(|address| address.to_raw_string())
+}
+
+impl HotAddresses {
+    pub fn new(policy: PrefetchPolicy) -> anyhow::Result<Self> {
+        metrics::describe_counter!(
+            "ton_grpc_prefetch_hits_total",
+            "Number of requests served from prefetched states and transactions"
+        );
+        metrics::describe_counter!(
+            "ton_grpc_prefetch_late_total",
+            "Number of refreshes of hot addresses which missed their deadline"
+        );
+
+        let now = Instant::now();
+        let scores = policy
+            .addresses
+            .iter()
+            .map(|address| {
+                let address = raw_address(address)
+                    .ok_or_else(|| anyhow::anyhow!("invalid hot address: {}", address))?;
+
+                Ok((
+                    address,
+                    Score {
+                        value: 1.0,
+                        at: now,
+                    },
+                ))
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(Self {
+            policy,
+            scores: Mutex::new(scores),
+            prefetched: Default::default(),
+            seqno: AtomicI32::new(-1),
+        })
+    }
+
+    /// Counts a request for the latest data of `address`, other than hot addresses are ignored
+    pub fn touch(&self, address: &str) {
+        self.touch_at(address, Instant::now())
+    }
+
+    fn touch_at(&self, address: &str, now: Instant) {
+        let Some(address) = raw_address(address) else {
+            return;
+        };
+        let mut scores = self.scores.lock().unwrap();
+        if let Some(score) = scores.get_mut(&address) {
+            *score = Score {
+                value: score.decayed(now, self.policy.half_life) + 1.0,
+                at: now,
+            };
+        }
+    }
+
+    /// Addresses to refresh, the most requested first, as many as the requests per block allow
+    fn hot(&self, now: Instant) -> Vec<String> {
+        let scores = self.scores.lock().unwrap();
+        let mut hot: Vec<_> = scores
+            .iter()
+            .map(|(address, score)| (score.decayed(now, self.policy.half_life), address))
+            .filter(|(score, _)| *score >= self.policy.min_score)
+            .collect();
+        hot.sort_by(|(lhs, _), (rhs, _)| rhs.total_cmp(lhs));
+
+        hot.into_iter()
+            .take(self.policy.max_requests_per_block / self.policy.requests_per_address())
+            .map(|(_, address)| address.clone())
+            .collect()
+    }
+
+    fn fresh(&self, address: &str) -> Option<Prefetched> {
+        let address = raw_address(address)?;
+        let prefetched = self.prefetched.lock().unwrap();
+
+        prefetched
+            .get(&address)
+            .filter(|p| p.seqno == self.seqno.load(Ordering::Relaxed))
+            .cloned()
+    }
+
+    /// Etag and response of the latest state of `address` if it was refreshed on the last block
+    pub fn state(&self, address: &str) -> Option<(String, GetAccountStateResponse)> {
+        let mut prefetched = self.fresh(address)?;
+        prefetched.state.account_address = address.to_owned();
+        metrics::counter!("ton_grpc_prefetch_hits_total", "method" => "GetAccountState")
+            .increment(1);
+
+        Some((prefetched.etag, prefetched.state))
+    }
+
+    /// Latest transactions of `address` if they were refreshed on the last block and there are
+    /// more than `limit` of them, so that the next page is known, or they're all of the account
+    pub fn transactions(&self, address: &str, limit: usize) -> Option<Vec<RawTransaction>> {
+        let prefetched = self
+            .fresh(address)
+            .filter(|p| p.complete || p.transactions.len() > limit)?;
+        metrics::counter!("ton_grpc_prefetch_hits_total", "method" => "GetAccountTransactionsPage")
+            .increment(1);
+
+        Some(prefetched.transactions)
+    }
+
+    /// Serves what was refreshed on the block `seqno`, addresses which aren't hot are forgotten
+    fn finish(&self, seqno: i32, fetched: Vec<(String, Prefetched)>, hot: &[String]) {
+        let mut prefetched = self.prefetched.lock().unwrap();
+        prefetched.retain(|address, _| hot.contains(address));
+        prefetched.extend(fetched);
+        self.seqno.store(seqno, Ordering::Relaxed);
+    }
+
+    async fn fetch(
+        &self,
+        client: &TonClient,
+        address: &str,
+        block_id: &TonBlockIdExt,
+    ) -> anyhow::Result<Prefetched> {
+        let state = client
+            .raw_get_account_state_at_least_block(address, block_id)
+            .await?;
+        let last_tx = state.last_transaction_id.clone();
+        let (etag, state) = state_response(client, address.to_owned(), state).await?;
+
+        let transactions: Vec<_> = match last_tx {
+            Some(last_tx) if self.policy.transactions > 0 => {
+                client
+                    .get_account_tx_stream_from(address, Some(last_tx))
+                    .take(self.policy.transactions)
+                    .try_collect()
+                    .await?
+            }
+            _ => Vec::new(),
+        };
+
+        Ok(Prefetched {
+            seqno: block_id.seqno,
+            etag,
+            state,
+            complete: transactions.len() < self.policy.transactions,
+            transactions,
+        })
+    }
+
+    /// Refreshes hot addresses on the masterchain block `block_id`
+    async fn refresh(&self, client: &TonClient, block_id: &TonBlockIdExt) {
+        let hot = self.hot(Instant::now());
+        let fetched = Mutex::new(Vec::with_capacity(hot.len()));
+        let refresh = join_all(hot.iter().map(|address| async {
+            match self.fetch(client, address, block_id).await {
+                Ok(prefetched) => fetched.lock().unwrap().push((address.clone(), prefetched)),
+                Err(e) => tracing::warn!(address = %address, error = %e, "prefetch failed"),
+            }
+        }));
+
+        if tokio::time::timeout(self.policy.deadline, refresh)
+            .await
+            .is_err()
+        {
+            metrics::counter!("ton_grpc_prefetch_late_total").increment(1);
+            tracing::warn!(seqno = block_id.seqno, "prefetch missed its deadline");
+        }
+
+        self.finish(block_id.seqno, fetched.into_inner().unwrap(), &hot);
+    }
+}
+
+/// Refreshes `hot` addresses on every new masterchain block the shared watcher sees,
+/// not while `pause` holds
+pub async fn prefetch(
+    client: TonClient,
+    masterchain: MasterchainWatcher,
+    hot: Arc<HotAddresses>,
+    mut pause: Pause,
+) {
+    let mut receiver = masterchain.subscribe(&client);
+    loop {
+        if receiver.changed().await.is_err() {
+            return;
+        }
+        pause.resumed().await;
+        let Some(last) = receiver
+            .borrow_and_update()
+            .as_ref()
+            .map(|info| info.last.clone())
+        else {
+            continue;
+        };
+        if last.seqno <= hot.seqno.load(Ordering::Relaxed) {
+            continue;
+        }
+
+        hot.refresh(&client, &last).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const HOT: &str = "EQCjk1hh952vWaE9bRguFkAhDAL5jj3xj9p0uPWrFBq_GEMS";
+    const OTHER: &str = "EQCkgtq1pKJh4Zpif_z4RR2aYmespuImTw15amEacGX-k6Zj";
+
+    fn policy() -> PrefetchPolicy {
+        PrefetchPolicy {
+            addresses: vec![HOT.to_owned(), OTHER.to_owned()],
+            deadline: Duration::from_millis(500),
+            max_requests_per_block: 4,
+            transactions: 16,
+            half_life: Duration::from_secs(60),
+            min_score: 0.1,
+        }
+    }
+
+    fn tx(lt: i64) -> RawTransaction {
+        serde_json::from_value(json!({
+            "@type": "raw.transaction",
+            "address": {"@type": "accountAddress", "account_address": HOT},
+            "utime": "0",
+            "data": "",
+            "transaction_id": {"@type": "internal.transactionId", "lt": lt.to_string(), "hash": ""},
+            "fee": "0",
+            "storage_fee": "0",
+            "other_fee": "0",
+            "in_msg": null,
+            "out_msgs": [],
+        }))
+        .unwrap()
+    }
+
+    fn prefetched(seqno: i32, transactions: i64) -> Prefetched {
+        Prefetched {
+            seqno,
+            etag: "\"etag\"".to_owned(),
+            state: GetAccountStateResponse {
+                balance: 42,
+                ..Default::default()
+            },
+            transactions: (0..transactions).rev().map(tx).collect(),
+            complete: false,
+        }
+    }
+
+    #[test]
+    fn requests_decay() {
+        let now = Instant::now();
+        let score = Score {
+            value: 4.0,
+            at: now,
+        };
+
+        assert_eq!(
+            score.decayed(now + Duration::from_secs(120), Duration::from_secs(60)),
+            1.0
+        );
+    }
+
+    #[test]
+    fn most_requested_go_first_within_budget() {
+        let hot = HotAddresses::new(policy()).unwrap();
+        let now = Instant::now();
+        hot.touch_at(OTHER, now);
+        hot.touch_at("not an address", now);
+
+        // 2 requests per address, a state and a page of transactions
+        assert_eq!(
+            hot.hot(now),
+            vec![raw_address(OTHER).unwrap(), raw_address(HOT).unwrap()]
+        );
+
+        let hot = HotAddresses::new(PrefetchPolicy {
+            max_requests_per_block: 3,
+            ..policy()
+        })
+        .unwrap();
+        hot.touch_at(OTHER, now);
+        assert_eq!(hot.hot(now), vec![raw_address(OTHER).unwrap()]);
+    }
+
+    #[test]
+    fn unrequested_addresses_are_demoted() {
+        let hot = HotAddresses::new(policy()).unwrap();
+        let now = Instant::now();
+        let later = now + Duration::from_secs(60 * 5);
+        hot.touch_at(OTHER, later);
+
+        assert_eq!(hot.hot(later), vec![raw_address(OTHER).unwrap()]);
+    }
+
+    #[test]
+    fn only_the_last_refresh_is_served() {
+        let hot = HotAddresses::new(policy()).unwrap();
+        let address = raw_address(HOT).unwrap();
+        hot.finish(
+            10,
+            vec![(address.clone(), prefetched(10, 4))],
+            &[address.clone()],
+        );
+
+        let (etag, state) = hot.state(HOT).unwrap();
+        assert_eq!(etag, "\"etag\"");
+        assert_eq!(state.account_address, HOT);
+        assert_eq!(state.balance, 42);
+        assert_eq!(hot.transactions(HOT, 3).unwrap().len(), 4);
+        // the next page isn't known
+        assert!(hot.transactions(HOT, 4).is_none());
+        assert!(hot.state(OTHER).is_none());
+
+        // not refreshed on the next block
+        hot.finish(11, Vec::new(), &[address]);
+        assert!(hot.state(HOT).is_none());
+    }
+}
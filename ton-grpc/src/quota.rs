@@ -0,0 +1,532 @@
+use crate::methods::MethodOverrides;
+use anyhow::anyhow;
+use futures::future::{ready, BoxFuture};
+use futures::FutureExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tonic::body::BoxBody;
+use tonic::codegen::http::{Request, Response};
+use tonic::codegen::Service;
+use tonic::metadata::MetadataValue;
+use tonic::{Code, Status};
+use tower::{Layer, ServiceExt};
+
+/// Metadata key of the API key a request is accounted to
+pub const API_KEY_HEADER: &str = "x-api-key";
+/// Metadata of over-quota rejections, unix time when the exhausted quota resets
+pub const QUOTA_RESET_HEADER: &str = "x-quota-reset";
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// Quotas of a key, counted in method costs, a missing quota is unlimited
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ApiKey {
+    /// used in metrics and by the admin service instead of the key itself
+    pub name: String,
+    #[serde(default)]
+    pub daily: Option<u64>,
+    #[serde(default)]
+    pub monthly: Option<u64>,
+    /// tiers of keys, e.g. archival ones allowed to call GetFullTransactions
+    #[serde(default)]
+    pub methods: MethodOverrides,
+}
+
+/// Reads `{"<key>": {"name": "acme", "daily": 100000, "monthly": 2000000, "methods": {"allow": []}}}`
+pub fn load_api_keys(path: &Path) -> anyhow::Result<HashMap<String, ApiKey>> {
+    let keys: HashMap<String, ApiKey> = serde_json::from_slice(&std::fs::read(path)?)?;
+
+    let mut names = HashMap::new();
+    for key in keys.values() {
+        if names.insert(&key.name, ()).is_some() {
+            return Err(anyhow!("api key name {} is not unique", key.name));
+        }
+    }
+
+    Ok(keys)
+}
+
+/// Parses `Method=cost`
+pub fn parse_method_cost(s: &str) -> anyhow::Result<(String, u64)> {
+    let (method, cost) = s
+        .split_once('=')
+        .ok_or_else(|| anyhow!("expected Method=cost, got {}", s))?;
+
+    Ok((method.to_owned(), cost.parse()?))
+}
+
+/// Day and month, both counted from the unix epoch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Period {
+    day: i64,
+    month: i64,
+}
+
+impl Period {
+    fn at(time: SystemTime) -> Self {
+        let seconds = time
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs() as i64);
+        let day = seconds.div_euclid(SECONDS_PER_DAY);
+        let (year, month) = civil_from_days(day);
+
+        Self {
+            day,
+            month: (year - 1970) * 12 + month - 1,
+        }
+    }
+
+    fn daily_reset(&self) -> i64 {
+        (self.day + 1) * SECONDS_PER_DAY
+    }
+
+    fn monthly_reset(&self) -> i64 {
+        let next = self.month + 1;
+
+        days_from_civil(1970 + next.div_euclid(12), next.rem_euclid(12) + 1) * SECONDS_PER_DAY
+    }
+}
+
+/// Unix times when the current day and month are over
+pub fn reset_times(now: SystemTime) -> (i64, i64) {
+    let period = Period::at(now);
+
+    (period.daily_reset(), period.monthly_reset())
+}
+
+/// `(year, month)` of days since the unix epoch, month is in `1..=12`
+fn civil_from_days(days: i64) -> (i64, i64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (year, month)
+}
+
+/// Days since the unix epoch of the first day of the month
+fn days_from_civil(year: i64, month: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+
+    era * 146097 + doe - 719468
+}
+
+/// Costs spent by a key during its current day and month
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Usage {
+    pub day: i64,
+    pub daily: u64,
+    pub month: i64,
+    pub monthly: u64,
+}
+
+impl Usage {
+    /// Counters of periods which are over start from zero
+    fn at(mut self, period: Period) -> Self {
+        if self.day != period.day {
+            self.day = period.day;
+            self.daily = 0;
+        }
+        if self.month != period.month {
+            self.month = period.month;
+            self.monthly = 0;
+        }
+
+        self
+    }
+}
+
+/// Where usage of keys is kept, the default one is [`MemoryUsageStore`]
+pub trait UsageStore: Send + Sync {
+    fn get(&self, key: &str) -> Usage;
+
+    /// Applies `f` to the usage of `key` atomically
+    fn update(&self, key: &str, f: &mut dyn FnMut(&mut Usage));
+}
+
+/// Usage kept in memory, survives restarts through a JSON snapshot if a path is given
+#[derive(Debug, Default)]
+pub struct MemoryUsageStore {
+    usage: Mutex<HashMap<String, Usage>>,
+    snapshot_path: Option<PathBuf>,
+}
+
+impl MemoryUsageStore {
+    /// Starts from the snapshot at `path` if there is one
+    pub fn with_snapshot(path: PathBuf) -> anyhow::Result<Self> {
+        let usage = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(Self {
+            usage: Mutex::new(usage),
+            snapshot_path: Some(path),
+        })
+    }
+
+    /// Writes the snapshot next to the target first, so a crash never leaves it half written
+    pub fn snapshot(&self) -> anyhow::Result<()> {
+        let Some(path) = &self.snapshot_path else {
+            return Ok(());
+        };
+
+        let json = serde_json::to_vec(&*self.usage.lock().unwrap())?;
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, json)?;
+        std::fs::rename(tmp, path)?;
+
+        Ok(())
+    }
+}
+
+impl UsageStore for MemoryUsageStore {
+    fn get(&self, key: &str) -> Usage {
+        self.usage
+            .lock()
+            .unwrap()
+            .get(key)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    fn update(&self, key: &str, f: &mut dyn FnMut(&mut Usage)) {
+        f(self
+            .usage
+            .lock()
+            .unwrap()
+            .entry(key.to_owned())
+            .or_default())
+    }
+}
+
+/// Daily and monthly quotas of API keys, usage is keyed by key name
+pub struct Quotas {
+    keys: HashMap<String, ApiKey>,
+    costs: HashMap<String, u64>,
+    store: Arc<dyn UsageStore>,
+}
+
+impl Quotas {
+    pub fn new(keys: HashMap<String, ApiKey>, store: Arc<dyn UsageStore>) -> Self {
+        Self {
+            keys,
+            costs: HashMap::from([
+                ("GetTransactions".to_owned(), 10),
+                ("GetAccountTransactions".to_owned(), 10),
+                ("GetAccountTransactionsPage".to_owned(), 5),
+                ("GetTransactionIds".to_owned(), 5),
+                ("GetAccountAddresses".to_owned(), 5),
+                ("GetBlockData".to_owned(), 10),
+                ("GetConsistentSnapshot".to_owned(), 10),
+            ]),
+            store,
+        }
+    }
+
+    /// Overrides the cost of methods, any other method costs 1
+    pub fn set_method_costs(mut self, costs: impl IntoIterator<Item = (String, u64)>) -> Self {
+        self.costs.extend(costs);
+        self
+    }
+
+    pub fn cost(&self, method: &str) -> u64 {
+        self.costs.get(method).copied().unwrap_or(1)
+    }
+
+    pub fn key(&self, key: &str) -> Option<&ApiKey> {
+        self.keys.get(key)
+    }
+
+    /// Key of the request if it is known and has quota left
+    pub fn check(&self, key: Option<&str>, now: SystemTime) -> Result<&ApiKey, Status> {
+        let key =
+            key.ok_or_else(|| Status::unauthenticated(format!("{} is required", API_KEY_HEADER)))?;
+        let key = self
+            .keys
+            .get(key)
+            .ok_or_else(|| Status::unauthenticated("unknown api key"))?;
+
+        let period = Period::at(now);
+        let usage = self.store.get(&key.name).at(period);
+        let exhausted = if key.daily.is_some_and(|quota| usage.daily >= quota) {
+            Some(("daily", period.daily_reset()))
+        } else if key.monthly.is_some_and(|quota| usage.monthly >= quota) {
+            Some(("monthly", period.monthly_reset()))
+        } else {
+            None
+        };
+
+        match exhausted {
+            None => Ok(key),
+            Some((quota, reset)) => {
+                metrics::counter!("ton_grpc_key_rejected_total", "key" => key.name.clone())
+                    .increment(1);
+
+                let mut status = Status::new(
+                    Code::ResourceExhausted,
+                    format!("{} quota of {} is exhausted", quota, key.name),
+                );
+                status
+                    .metadata_mut()
+                    .insert(QUOTA_RESET_HEADER, MetadataValue::from(reset));
+
+                Err(status)
+            }
+        }
+    }
+
+    /// Accounts a successful request of `method`
+    pub fn record(&self, key: &ApiKey, method: &str, now: SystemTime) {
+        let cost = self.cost(method);
+        let period = Period::at(now);
+        self.store.update(&key.name, &mut |usage| {
+            *usage = usage.at(period);
+            usage.daily += cost;
+            usage.monthly += cost;
+        });
+
+        metrics::counter!("ton_grpc_key_requests_total", "key" => key.name.clone(), "method" => method.to_owned())
+            .increment(1);
+        metrics::counter!("ton_grpc_key_cost_total", "key" => key.name.clone()).increment(cost);
+    }
+
+    pub fn key_by_name(&self, name: &str) -> Option<&ApiKey> {
+        self.keys.values().find(|key| key.name == name)
+    }
+
+    pub fn usage(&self, key: &ApiKey, now: SystemTime) -> Usage {
+        self.store.get(&key.name).at(Period::at(now))
+    }
+
+    pub fn reset(&self, key: &ApiKey) {
+        self.store
+            .update(&key.name, &mut |usage| *usage = Usage::default());
+    }
+}
+
+/// Method of a request to the `ton` package, other packages like health and reflection are free
+pub fn quoted_method(path: &str) -> Option<&str> {
+    path.strip_prefix("/ton.")?
+        .split_once('/')
+        .map(|(_, method)| method)
+}
+
+#[derive(Clone)]
+pub struct QuotaLayer {
+    quotas: Arc<Quotas>,
+}
+
+impl QuotaLayer {
+    pub fn new(quotas: Arc<Quotas>) -> Self {
+        Self { quotas }
+    }
+}
+
+impl<S> Layer<S> for QuotaLayer {
+    type Service = QuotaService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        QuotaService {
+            inner,
+            quotas: self.quotas.clone(),
+        }
+    }
+}
+
+/// Rejects requests of unknown or over-quota keys and accounts the successful ones
+#[derive(Clone)]
+pub struct QuotaService<S> {
+    inner: S,
+    quotas: Arc<Quotas>,
+}
+
+impl<S, B> Service<Request<B>> for QuotaService<S>
+where
+    S: Service<Request<B>, Response = Response<BoxBody>> + Clone + Send + 'static,
+    S::Error: Send + 'static,
+    S::Future: Send,
+    B: Send + 'static,
+{
+    type Response = Response<BoxBody>;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, mut req: Request<B>) -> Self::Future {
+        let Some(method) = quoted_method(req.uri().path()).map(ToOwned::to_owned) else {
+            return self.inner.clone().oneshot(req).boxed();
+        };
+
+        let now = SystemTime::now();
+        let key = req
+            .headers()
+            .get(API_KEY_HEADER)
+            .and_then(|v| v.to_str().ok());
+        let key = match self.quotas.check(key, now) {
+            Ok(key) => key.clone(),
+            Err(status) => return ready(Ok(status.to_http())).boxed(),
+        };
+        // handlers find the key of the request in its extensions
+        req.extensions_mut().insert(key.clone());
+
+        let quotas = self.quotas.clone();
+        let inner = self.inner.clone();
+        async move {
+            let response = inner.oneshot(req).await?;
+
+            // errors found before the first message come as trailers only, i.e. in headers
+            let failed = response
+                .headers()
+                .get("grpc-status")
+                .is_some_and(|status| status != "0");
+            if !failed {
+                quotas.record(&key, &method, now);
+            }
+
+            Ok(response)
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn quotas() -> Quotas {
+        Quotas::new(
+            HashMap::from([(
+                "secret".to_owned(),
+                ApiKey {
+                    name: "acme".to_owned(),
+                    daily: Some(10),
+                    monthly: Some(25),
+                    methods: Default::default(),
+                },
+            )]),
+            Arc::new(MemoryUsageStore::default()),
+        )
+    }
+
+    // 2024-01-31T12:00:00Z
+    fn january() -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1706702400)
+    }
+
+    #[test]
+    fn civil_dates() {
+        assert_eq!(civil_from_days(0), (1970, 1));
+        assert_eq!(civil_from_days(19753), (2024, 1));
+        assert_eq!(civil_from_days(19783), (2024, 3));
+        assert_eq!(days_from_civil(2024, 2), 19754);
+        assert_eq!(days_from_civil(2025, 1), 20089);
+    }
+
+    #[test]
+    fn check_rejects_unknown_keys() {
+        let quotas = quotas();
+
+        assert_eq!(
+            quotas.check(None, january()).unwrap_err().code(),
+            Code::Unauthenticated
+        );
+        assert_eq!(
+            quotas.check(Some("guess"), january()).unwrap_err().code(),
+            Code::Unauthenticated
+        );
+        assert_eq!(
+            quotas.check(Some("secret"), january()).unwrap().name,
+            "acme"
+        );
+    }
+
+    #[test]
+    fn daily_quota_resets_next_day() {
+        let quotas = quotas();
+        let key = quotas.check(Some("secret"), january()).unwrap().clone();
+
+        quotas.record(&key, "GetTransactions", january());
+        let status = quotas.check(Some("secret"), january()).unwrap_err();
+
+        assert_eq!(status.code(), Code::ResourceExhausted);
+        // 2024-02-01T00:00:00Z
+        assert_eq!(
+            status.metadata().get(QUOTA_RESET_HEADER).unwrap(),
+            "1706745600"
+        );
+        assert!(quotas
+            .check(
+                Some("secret"),
+                january() + Duration::from_secs(12 * 60 * 60)
+            )
+            .is_ok());
+    }
+
+    #[test]
+    fn monthly_quota_resets_next_month() {
+        let quotas = quotas();
+        let key = quotas.check(Some("secret"), january()).unwrap().clone();
+        let day = Duration::from_secs(SECONDS_PER_DAY as u64);
+        let february = january() + day;
+
+        for at in [february, february + day, february + day * 2] {
+            quotas.record(&key, "GetTransactions", at);
+        }
+        let status = quotas
+            .check(Some("secret"), february + day * 3)
+            .unwrap_err();
+
+        assert_eq!(status.code(), Code::ResourceExhausted);
+        // 2024-03-01T00:00:00Z
+        assert_eq!(
+            status.metadata().get(QUOTA_RESET_HEADER).unwrap(),
+            "1709251200"
+        );
+
+        quotas.reset(&key);
+        assert_eq!(
+            quotas.usage(&key, february),
+            Usage::default().at(Period::at(february))
+        );
+    }
+
+    #[test]
+    fn snapshot_round_trip() {
+        let path = std::env::temp_dir().join(format!("usage-{}.json", std::process::id()));
+        let store = MemoryUsageStore::with_snapshot(path.clone()).unwrap();
+        store.update("acme", &mut |usage| usage.daily = 7);
+        store.snapshot().unwrap();
+
+        let restored = MemoryUsageStore::with_snapshot(path.clone()).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(restored.get("acme").daily, 7);
+    }
+
+    #[test]
+    fn method_of_path() {
+        assert_eq!(
+            quoted_method("/ton.AccountService/GetAccountState"),
+            Some("GetAccountState")
+        );
+        assert_eq!(quoted_method("/grpc.health.v1.Health/Check"), None);
+    }
+}
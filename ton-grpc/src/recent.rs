@@ -0,0 +1,273 @@
+use crate::memory::{EntrySize, Tracked};
+use sha2::{Digest, Sha256};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::SystemTime;
+use tokio::sync::broadcast;
+
+/// Updates of the feed a subscriber may fall behind by before it misses some
+const FEED_CAPACITY: usize = 1024;
+
+/// Transaction which processed a recent message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Confirmation {
+    pub lt: i64,
+    pub transaction_hash: String,
+    /// unix time of the transaction
+    pub utime: i64,
+}
+
+/// A message accepted by the lite server, confirmed once its transaction is seen
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecentMessage {
+    pub hash: String,
+    /// none if destinations are redacted
+    pub destination: Option<String>,
+    /// unix time
+    pub sent_at: i64,
+    /// bytes of the boc
+    pub size: usize,
+    /// anonymous id of the api key which sent it, see [`anonymous_id`]
+    pub sender: Option<String>,
+    pub confirmation: Option<Confirmation>,
+}
+
+impl RecentMessage {
+    fn heap_size(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.hash.len()
+            + self.destination.as_ref().map_or(0, String::len)
+            + self.sender.as_ref().map_or(0, String::len)
+            + self
+                .confirmation
+                .as_ref()
+                .map_or(0, |c| c.transaction_hash.len())
+    }
+}
+
+/// Settings of [`RecentMessages`], the feed is off unless it's configured
+#[derive(Debug, Clone)]
+pub struct RecentMessagesPolicy {
+    /// messages kept, the oldest ones are dropped first
+    pub capacity: usize,
+    pub redact_destination: bool,
+    /// api keys are identified by the hash of the salt and their name
+    pub salt: String,
+}
+
+/// Messages recently sent through the proxy, each one once, for a submission tracking view.
+/// Subscribers get new messages and confirmations of the kept ones
+pub struct RecentMessages {
+    policy: RecentMessagesPolicy,
+    messages: Mutex<VecDeque<RecentMessage>>,
+    feed: broadcast::Sender<RecentMessage>,
+    entry_size: EntrySize,
+}
+
+/// Stable id of an api key which doesn't reveal its name without the salt
+pub fn anonymous_id(salt: &str, api_key: &str) -> String {
+    let hash = Sha256::new()
+        .chain_update(salt)
+        .chain_update([0])
+        .chain_update(api_key)
+        .finalize();
+
+    hex::encode(&hash[..8])
+}
+
+fn unix_time(time: SystemTime) -> i64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+impl RecentMessages {
+    pub fn new(policy: RecentMessagesPolicy) -> Self {
+        Self {
+            policy,
+            messages: Default::default(),
+            feed: broadcast::channel(FEED_CAPACITY).0,
+            entry_size: Default::default(),
+        }
+    }
+
+    /// Keeps the message unless it's kept already
+    pub fn record(
+        &self,
+        hash: String,
+        destination: Option<String>,
+        size: usize,
+        api_key: Option<&str>,
+    ) {
+        self.record_at(hash, destination, size, api_key, SystemTime::now())
+    }
+
+    fn record_at(
+        &self,
+        hash: String,
+        destination: Option<String>,
+        size: usize,
+        api_key: Option<&str>,
+        now: SystemTime,
+    ) {
+        if self.policy.capacity == 0 {
+            return;
+        }
+
+        let message = RecentMessage {
+            hash,
+            destination: destination.filter(|_| !self.policy.redact_destination),
+            sent_at: unix_time(now),
+            size,
+            sender: api_key.map(|key| anonymous_id(&self.policy.salt, key)),
+            confirmation: None,
+        };
+
+        let mut messages = self.messages.lock().unwrap();
+        if messages.iter().any(|m| m.hash == message.hash) {
+            return;
+        }
+        self.entry_size.sample(|| message.heap_size());
+        while messages.len() >= self.policy.capacity {
+            messages.pop_front();
+        }
+        messages.push_back(message.clone());
+        drop(messages);
+
+        let _ = self.feed.send(message);
+    }
+
+    /// Confirms a kept message, false if it isn't kept or is confirmed already
+    pub fn confirm(&self, hash: &str, confirmation: Confirmation) -> bool {
+        let mut messages = self.messages.lock().unwrap();
+        let Some(message) = messages
+            .iter_mut()
+            .find(|m| m.hash == hash && m.confirmation.is_none())
+        else {
+            return false;
+        };
+        message.confirmation = Some(confirmation);
+        let message = message.clone();
+        drop(messages);
+
+        let _ = self.feed.send(message);
+
+        true
+    }
+
+    /// At most `limit` messages, the newest first
+    pub fn list(&self, limit: usize) -> Vec<RecentMessage> {
+        let messages = self.messages.lock().unwrap();
+
+        messages.iter().rev().take(limit).cloned().collect()
+    }
+
+    /// New messages and confirmations from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<RecentMessage> {
+        self.feed.subscribe()
+    }
+}
+
+impl Tracked for RecentMessages {
+    fn usage(&self) -> usize {
+        self.messages.lock().unwrap().len() * self.entry_size.get()
+    }
+
+    fn shrink(&self, bytes: usize) -> usize {
+        let entry_size = self.entry_size.get().max(1);
+        let mut messages = self.messages.lock().unwrap();
+        let mut freed = 0;
+        while freed < bytes && messages.pop_front().is_some() {
+            freed += entry_size;
+        }
+
+        freed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn recent(capacity: usize, redact_destination: bool) -> RecentMessages {
+        RecentMessages::new(RecentMessagesPolicy {
+            capacity,
+            redact_destination,
+            salt: "salt".to_owned(),
+        })
+    }
+
+    fn record(messages: &RecentMessages, hash: &str) {
+        messages.record_at(
+            hash.to_owned(),
+            Some("wallet".to_owned()),
+            100,
+            Some("key"),
+            SystemTime::UNIX_EPOCH + Duration::from_secs(10),
+        );
+    }
+
+    fn hashes(messages: &[RecentMessage]) -> Vec<&str> {
+        messages.iter().map(|m| m.hash.as_str()).collect()
+    }
+
+    fn confirmation() -> Confirmation {
+        Confirmation {
+            lt: 1,
+            transaction_hash: "tx".to_owned(),
+            utime: 12,
+        }
+    }
+
+    #[test]
+    fn oldest_are_dropped_and_duplicates_kept_once() {
+        let messages = recent(2, false);
+        for hash in ["a", "b", "b", "c"] {
+            record(&messages, hash);
+        }
+
+        assert_eq!(hashes(&messages.list(10)), vec!["c", "b"]);
+        assert_eq!(hashes(&messages.list(1)), vec!["c"]);
+    }
+
+    #[test]
+    fn sender_is_anonymous_and_destination_redacted() {
+        let messages = recent(2, true);
+        record(&messages, "a");
+
+        let message = messages.list(1).remove(0);
+        assert_eq!(message.destination, None);
+        assert_eq!(message.sender, Some(anonymous_id("salt", "key")));
+        assert_ne!(anonymous_id("salt", "key"), anonymous_id("other", "key"));
+        assert_eq!(message.sent_at, 10);
+    }
+
+    #[test]
+    fn confirmation_is_fed() {
+        let messages = recent(2, false);
+        let mut feed = messages.subscribe();
+        record(&messages, "a");
+
+        assert!(messages.confirm("a", confirmation()));
+        assert!(!messages.confirm("a", confirmation()));
+        assert!(!messages.confirm("b", confirmation()));
+
+        assert_eq!(feed.try_recv().unwrap().confirmation, None);
+        assert_eq!(feed.try_recv().unwrap().confirmation, Some(confirmation()));
+        assert!(feed.try_recv().is_err());
+        assert_eq!(messages.list(1)[0].confirmation, Some(confirmation()));
+    }
+
+    #[test]
+    fn shrink_drops_the_oldest() {
+        let messages = recent(10, false);
+        for hash in ["a", "b", "c", "d"] {
+            record(&messages, hash);
+        }
+        let entry_size = messages.usage() / 4;
+
+        assert_eq!(messages.shrink(entry_size + 1), 2 * entry_size);
+        assert_eq!(hashes(&messages.list(10)), vec!["d", "c"]);
+    }
+}
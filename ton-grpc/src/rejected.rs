@@ -0,0 +1,225 @@
+use crate::memory::Tracked;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+/// A message the lite server didn't accept
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rejected {
+    pub hash: String,
+    pub boc: String,
+    pub error: String,
+    /// unix time
+    pub rejected_at: i64,
+    /// name of the api key which sent it
+    pub api_key: Option<String>,
+}
+
+impl Rejected {
+    fn size(&self) -> usize {
+        self.hash.len()
+            + self.boc.len()
+            + self.error.len()
+            + self.api_key.as_ref().map_or(0, String::len)
+    }
+}
+
+/// Recently rejected messages for operators, bounded by the bytes of the messages kept rather
+/// than their count since a boc may be large. The oldest ones are dropped first.
+pub struct RejectedMessages {
+    max_bytes: usize,
+    retention: Duration,
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    messages: VecDeque<Rejected>,
+    bytes: usize,
+}
+
+impl Inner {
+    fn pop_oldest(&mut self) {
+        if let Some(oldest) = self.messages.pop_front() {
+            self.bytes -= oldest.size();
+        }
+    }
+
+    fn expire(&mut self, retention: Duration, now: SystemTime) {
+        let retention = i64::try_from(retention.as_secs()).unwrap_or(i64::MAX);
+        let oldest_kept = unix_time(now).saturating_sub(retention);
+        while self
+            .messages
+            .front()
+            .is_some_and(|m| m.rejected_at < oldest_kept)
+        {
+            self.pop_oldest();
+        }
+    }
+}
+
+fn unix_time(time: SystemTime) -> i64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+impl RejectedMessages {
+    pub fn new(max_bytes: usize, retention: Duration) -> Self {
+        Self {
+            max_bytes,
+            retention,
+            inner: Default::default(),
+        }
+    }
+
+    pub fn record(&self, hash: String, boc: String, error: String, api_key: Option<String>) {
+        self.record_at(hash, boc, error, api_key, SystemTime::now())
+    }
+
+    fn record_at(
+        &self,
+        hash: String,
+        boc: String,
+        error: String,
+        api_key: Option<String>,
+        now: SystemTime,
+    ) {
+        let rejected = Rejected {
+            hash,
+            boc,
+            error,
+            rejected_at: unix_time(now),
+            api_key,
+        };
+        let size = rejected.size();
+        if size > self.max_bytes {
+            tracing::debug!(
+                hash = rejected.hash,
+                size,
+                "rejected message is too large to keep"
+            );
+
+            return;
+        }
+
+        let mut inner = self.inner.lock().unwrap();
+        inner.expire(self.retention, now);
+        while inner.bytes + size > self.max_bytes {
+            inner.pop_oldest();
+        }
+        inner.bytes += size;
+        inner.messages.push_back(rejected);
+    }
+
+    /// The newest first
+    pub fn list(&self) -> Vec<Rejected> {
+        self.list_at(SystemTime::now())
+    }
+
+    fn list_at(&self, now: SystemTime) -> Vec<Rejected> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.expire(self.retention, now);
+
+        inner.messages.iter().rev().cloned().collect()
+    }
+
+    /// The latest rejection of the message
+    pub fn get(&self, hash: &str) -> Option<Rejected> {
+        self.list().into_iter().find(|m| m.hash == hash)
+    }
+}
+
+impl Tracked for RejectedMessages {
+    fn usage(&self) -> usize {
+        self.inner.lock().unwrap().bytes
+    }
+
+    fn shrink(&self, bytes: usize) -> usize {
+        let mut inner = self.inner.lock().unwrap();
+        let before = inner.bytes;
+        while before - inner.bytes < bytes && !inner.messages.is_empty() {
+            inner.pop_oldest();
+        }
+
+        before - inner.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    fn at(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    fn record(messages: &RejectedMessages, hash: &str, boc_len: usize, now: SystemTime) {
+        messages.record_at(
+            hash.to_owned(),
+            "b".repeat(boc_len),
+            "e".to_owned(),
+            None,
+            now,
+        );
+    }
+
+    fn hashes(messages: &[Rejected]) -> Vec<&str> {
+        messages.iter().map(|m| m.hash.as_str()).collect()
+    }
+
+    #[test]
+    fn bytes_are_capped() {
+        // each message takes 1 + 8 + 1 bytes
+        let messages = RejectedMessages::new(25, HOUR);
+        for hash in ["a", "b", "c"] {
+            record(&messages, hash, 8, at(0));
+        }
+
+        assert_eq!(hashes(&messages.list_at(at(0))), vec!["c", "b"]);
+    }
+
+    #[test]
+    fn oldest_are_shrunk() {
+        let messages = RejectedMessages::new(1024, HOUR);
+        for hash in ["a", "b", "c"] {
+            record(&messages, hash, 8, at(0));
+        }
+
+        assert_eq!(messages.usage(), 30);
+        assert_eq!(messages.shrink(15), 20);
+        assert_eq!(hashes(&messages.list_at(at(0))), vec!["c"]);
+    }
+
+    #[test]
+    fn too_large_message_is_dropped() {
+        let messages = RejectedMessages::new(25, HOUR);
+        record(&messages, "a", 8, at(0));
+        record(&messages, "b", 100, at(0));
+
+        assert_eq!(hashes(&messages.list_at(at(0))), vec!["a"]);
+    }
+
+    #[test]
+    fn old_messages_expire() {
+        let messages = RejectedMessages::new(1024, HOUR);
+        record(&messages, "a", 8, at(0));
+        record(&messages, "b", 8, at(1800));
+
+        assert_eq!(hashes(&messages.list_at(at(3600))), vec!["b", "a"]);
+        assert_eq!(hashes(&messages.list_at(at(3601))), vec!["b"]);
+        assert_eq!(messages.inner.lock().unwrap().bytes, 10);
+    }
+
+    #[test]
+    fn latest_rejection_of_hash() {
+        let messages = RejectedMessages::new(1024, Duration::MAX);
+        record(&messages, "a", 1, at(0));
+        record(&messages, "a", 2, at(1));
+
+        assert_eq!(messages.get("a").unwrap().boc, "bb");
+        assert_eq!(messages.get("b"), None);
+    }
+}
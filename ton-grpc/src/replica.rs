@@ -0,0 +1,222 @@
+use crate::network::NETWORK_HEADER;
+use crate::quota::API_KEY_HEADER;
+use crate::ton::block_service_client::BlockServiceClient;
+use crate::ton::get_transaction_ids_request::Order;
+use crate::ton::{BlockId, GetShardsRequest, GetTransactionIdsRequest, TransactionId};
+use anyhow::Context;
+use std::str::FromStr;
+use std::time::Duration;
+use tonic::transport::{Channel, Endpoint};
+use tonic::Request;
+use tonlibjson_client::address::{AccountAddressData, ShardContextAccountAddress};
+use tonlibjson_client::block::{
+    BlocksAccountTransactionId, BlocksHeader, BlocksShortTxId, BlocksTransactions, TonBlockIdExt,
+};
+use tonlibjson_client::replica::BlockSource;
+use url::Url;
+
+/// Times a listing of block transactions is resumed after its stream is cut
+const MAX_RESUMES: u32 = 3;
+
+/// Upstream instance of this proxy a replica reads block data from
+#[derive(Debug, Clone)]
+pub struct UpstreamPolicy {
+    pub url: Url,
+    /// x-api-key sent to the upstream
+    pub api_key: Option<String>,
+    pub timeout: Duration,
+}
+
+/// Block data of a network served by an upstream instance through BlockService,
+/// the connection is rebuilt on the next request once it's lost
+pub struct Upstream {
+    client: BlockServiceClient<Channel>,
+    network: String,
+    api_key: Option<String>,
+}
+
+impl Upstream {
+    pub fn new(network: String, policy: UpstreamPolicy) -> anyhow::Result<Self> {
+        let endpoint = Endpoint::from_shared(policy.url.to_string())?.timeout(policy.timeout);
+
+        Ok(Self {
+            client: BlockServiceClient::new(endpoint.connect_lazy()),
+            network,
+            api_key: policy.api_key,
+        })
+    }
+
+    fn request<T>(&self, message: T) -> anyhow::Result<Request<T>> {
+        let mut request = Request::new(message);
+        request
+            .metadata_mut()
+            .insert(NETWORK_HEADER, self.network.parse()?);
+        if let Some(api_key) = &self.api_key {
+            request
+                .metadata_mut()
+                .insert(API_KEY_HEADER, api_key.parse()?);
+        }
+
+        Ok(request)
+    }
+
+    /// Appends ids listed after `after` to `ids` until there are `limit` of them
+    async fn list(
+        &self,
+        block: &TonBlockIdExt,
+        after: Option<BlocksAccountTransactionId>,
+        reverse: bool,
+        limit: usize,
+        ids: &mut Vec<BlocksShortTxId>,
+    ) -> anyhow::Result<()> {
+        let order = if reverse { Order::Desc } else { Order::Asc };
+        let request = GetTransactionIdsRequest {
+            block_id: Some(block_id(block)),
+            order: order as i32,
+            check_complete: false,
+            after: after
+                .map(|after| cursor(block.workchain, &after))
+                .transpose()?,
+        };
+
+        let mut stream = self
+            .client
+            .clone()
+            .get_transaction_ids(self.request(request)?)
+            .await?
+            .into_inner();
+        while ids.len() < limit {
+            let Some(id) = stream.message().await? else {
+                break;
+            };
+            ids.push(short_tx_id(block.workchain, id)?);
+        }
+
+        Ok(())
+    }
+}
+
+fn block_id(block: &TonBlockIdExt) -> BlockId {
+    BlockId {
+        workchain: block.workchain,
+        shard: block.shard,
+        seqno: block.seqno,
+        root_hash: Some(block.root_hash.clone()),
+        file_hash: Some(block.file_hash.clone()),
+    }
+}
+
+fn cursor(workchain: i32, after: &BlocksAccountTransactionId) -> anyhow::Result<TransactionId> {
+    let account = ShardContextAccountAddress::from_str(&after.account)?;
+
+    Ok(TransactionId {
+        account_address: account.into_internal(workchain).to_string(),
+        hash: String::new(),
+        lt: after.lt,
+    })
+}
+
+fn short_tx_id(workchain: i32, id: TransactionId) -> anyhow::Result<BlocksShortTxId> {
+    let account = AccountAddressData::from_str(&id.account_address)?;
+
+    Ok(BlocksShortTxId {
+        mode: 0b111,
+        workchain,
+        account: account.into_shard_context(),
+        lt: id.lt,
+        hash: id.hash,
+    })
+}
+
+#[tonic::async_trait]
+impl BlockSource for Upstream {
+    async fn get_block_header(&self, id: &TonBlockIdExt) -> anyhow::Result<BlocksHeader> {
+        let header = self
+            .client
+            .clone()
+            .get_block_header(self.request(block_id(id))?)
+            .await?
+            .into_inner();
+
+        Ok(header.into())
+    }
+
+    async fn get_shards(&self, master: &TonBlockIdExt) -> anyhow::Result<Vec<TonBlockIdExt>> {
+        let request = GetShardsRequest {
+            workchain: master.workchain,
+            shard: master.shard,
+            seqno: master.seqno,
+            root_hash: Some(master.root_hash.clone()),
+            file_hash: Some(master.file_hash.clone()),
+            strict: true,
+            include_as_of: false,
+        };
+        let shards = self
+            .client
+            .clone()
+            .get_shards(self.request(request)?)
+            .await?
+            .into_inner();
+
+        Ok(shards.shards.into_iter().map(Into::into).collect())
+    }
+
+    /// Streams the ids from the upstream, a stream cut midway is resumed after the last id received
+    async fn blocks_get_transactions(
+        &self,
+        block: &TonBlockIdExt,
+        after: Option<BlocksAccountTransactionId>,
+        reverse: bool,
+        count: i32,
+    ) -> anyhow::Result<BlocksTransactions> {
+        let count = usize::try_from(count).context("count must not be negative")?;
+        // one more id than asked tells whether the listing goes on
+        let limit = count + 1;
+        let mut ids = Vec::with_capacity(limit);
+        let mut resumes = 0;
+        loop {
+            let cursor = ids.last().map(Into::into).or_else(|| after.clone());
+            match self.list(block, cursor, reverse, limit, &mut ids).await {
+                Ok(()) => break,
+                Err(e) if resumes < MAX_RESUMES => {
+                    resumes += 1;
+                    tracing::warn!(network = self.network, error = ?e, resumes, "upstream listing is resumed");
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        let incomplete = ids.len() > count;
+        ids.truncate(count);
+
+        Ok(BlocksTransactions {
+            id: block.clone(),
+            req_count: count as i32,
+            incomplete,
+            transactions: ids,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursor_of_listed_id_resumes_after_it() {
+        let id = TransactionId {
+            account_address: format!("0:{}", "ab".repeat(32)),
+            hash: "hash".to_owned(),
+            lt: 42,
+        };
+
+        let listed = short_tx_id(0, id.clone()).unwrap();
+        let after = BlocksAccountTransactionId::from(&listed);
+        let sent = cursor(0, &after).unwrap();
+
+        assert_eq!(sent.account_address, id.account_address);
+        assert_eq!(sent.lt, 42);
+        let resumed = BlocksAccountTransactionId::try_from(sent).unwrap();
+        assert_eq!((resumed.account, resumed.lt), (after.account, after.lt));
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::OwnedMutexGuard;
+use tonic::Status;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum SendQueueError {
+    #[error("queued behind {0} messages")]
+    Full(usize),
+    #[error("queued behind {0} messages")]
+    Timeout(usize),
+    #[error("too many wallets have messages queued")]
+    TooManyWallets,
+}
+
+impl From<SendQueueError> for Status {
+    fn from(e: SendQueueError) -> Self {
+        match e {
+            SendQueueError::Timeout(_) => Status::deadline_exceeded(e.to_string()),
+            SendQueueError::Full(_) | SendQueueError::TooManyWallets => {
+                Status::resource_exhausted(e.to_string())
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct Queue {
+    turn: Arc<tokio::sync::Mutex<()>>,
+    /// the message relayed and the ones waiting for their turn
+    pending: usize,
+}
+
+type Queues = Arc<Mutex<HashMap<String, Queue>>>;
+
+/// Messages to the same wallet relayed one at a time in the order they came, so concurrent
+/// messages of a wallet don't race for its seqno. A wallet is only kept while it has messages
+/// pending and at most `capacity` wallets are
+pub struct SendQueues {
+    /// messages which may wait behind the one relayed
+    max_depth: usize,
+    max_wait: Duration,
+    capacity: usize,
+    queues: Queues,
+}
+
+impl SendQueues {
+    pub fn new(max_depth: usize, max_wait: Duration, capacity: usize) -> Self {
+        Self {
+            max_depth,
+            max_wait,
+            capacity,
+            queues: Default::default(),
+        }
+    }
+
+    /// Waits for the turn of a message to `wallet`, which lasts until the [`Turn`] is dropped
+    pub async fn acquire(&self, wallet: &str) -> Result<Turn, SendQueueError> {
+        let (turn, ahead) = {
+            let mut queues = self.queues.lock().unwrap();
+            if !queues.contains_key(wallet) && queues.len() >= self.capacity {
+                return Err(SendQueueError::TooManyWallets);
+            }
+
+            let queue = queues.entry(wallet.to_owned()).or_default();
+            if queue.pending > self.max_depth {
+                return Err(SendQueueError::Full(queue.pending));
+            }
+            queue.pending += 1;
+
+            (queue.turn.clone(), queue.pending - 1)
+        };
+        let pending = Pending {
+            queues: self.queues.clone(),
+            wallet: wallet.to_owned(),
+        };
+
+        match tokio::time::timeout(self.max_wait, turn.lock_owned()).await {
+            Ok(guard) => Ok(Turn {
+                _guard: guard,
+                _pending: pending,
+            }),
+            Err(_) => Err(SendQueueError::Timeout(ahead)),
+        }
+    }
+
+    /// Wallets with messages pending
+    pub fn wallets(&self) -> usize {
+        self.queues.lock().unwrap().len()
+    }
+}
+
+/// A message counted in the queue of its wallet, the wallet is forgotten with its last one
+struct Pending {
+    queues: Queues,
+    wallet: String,
+}
+
+impl Drop for Pending {
+    fn drop(&mut self) {
+        let mut queues = self.queues.lock().unwrap();
+        if let Some(queue) = queues.get_mut(&self.wallet) {
+            queue.pending -= 1;
+            if queue.pending == 0 {
+                queues.remove(&self.wallet);
+            }
+        }
+    }
+}
+
+/// Turn of a message to be relayed
+pub struct Turn {
+    _guard: OwnedMutexGuard<()>,
+    _pending: Pending,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WAIT: Duration = Duration::from_secs(10);
+
+    #[tokio::test(start_paused = true)]
+    async fn messages_of_a_wallet_wait_for_their_turn() {
+        let queues = SendQueues::new(4, WAIT, 16);
+        let first = queues.acquire("wallet").await.unwrap();
+
+        let second = queues.acquire("wallet");
+        tokio::pin!(second);
+        assert!(futures::poll!(second.as_mut()).is_pending());
+
+        drop(first);
+        assert!(second.await.is_ok());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn other_wallets_are_not_queued() {
+        let queues = SendQueues::new(4, WAIT, 16);
+        let _first = queues.acquire("wallet").await.unwrap();
+
+        assert!(queues.acquire("other").await.is_ok());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn wait_times_out() {
+        let queues = SendQueues::new(4, WAIT, 16);
+        let _first = queues.acquire("wallet").await.unwrap();
+
+        let error = queues.acquire("wallet").await.err().unwrap();
+
+        assert_eq!(error, SendQueueError::Timeout(1));
+        assert_eq!(error.to_string(), "queued behind 1 messages");
+        assert_eq!(Status::from(error).code(), tonic::Code::DeadlineExceeded);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn queue_is_bounded() {
+        let queues = SendQueues::new(1, WAIT, 16);
+        let _first = queues.acquire("wallet").await.unwrap();
+        let second = queues.acquire("wallet");
+        tokio::pin!(second);
+        assert!(futures::poll!(second.as_mut()).is_pending());
+
+        assert_eq!(
+            queues.acquire("wallet").await.err(),
+            Some(SendQueueError::Full(2))
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn wallets_are_bounded_and_forgotten() {
+        let queues = SendQueues::new(4, WAIT, 1);
+        let first = queues.acquire("wallet").await.unwrap();
+
+        assert_eq!(
+            queues.acquire("other").await.err(),
+            Some(SendQueueError::TooManyWallets)
+        );
+
+        drop(first);
+        assert_eq!(queues.wallets(), 0);
+        assert!(queues.acquire("other").await.is_ok());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn timed_out_message_leaves_the_queue() {
+        let queues = SendQueues::new(4, WAIT, 16);
+        let first = queues.acquire("wallet").await.unwrap();
+        let _ = queues.acquire("wallet").await;
+
+        drop(first);
+        assert_eq!(queues.wallets(), 0);
+    }
+}
@@ -0,0 +1,383 @@
+use crate::account::AccountService;
+use crate::block::BlockService;
+use crate::cache::CachePolicy;
+use crate::call_options::{CallOptionsLayer, MethodCallOptions};
+use crate::cursor::Cursors;
+use crate::deadline::DeadlineLayer;
+use crate::json::JsonLimits;
+use crate::limits::{ParamLimits, ResponseSizeLimits};
+use crate::maintenance::{Maintenance, MaintenanceLayer};
+use crate::message::MessageService;
+use crate::methods::{MethodPolicy, MethodPolicyLayer, MethodService};
+use crate::network::NetworkRouter;
+use crate::panic::CatchPanicLayer;
+use crate::quota::{ApiKey, MemoryUsageStore, QuotaLayer, Quotas, UsageStore};
+use crate::session::{SessionLayer, Sessions};
+use crate::ton;
+use crate::ton::account_service_server::AccountServiceServer;
+use crate::ton::block_service_server::BlockServiceServer;
+use crate::ton::message_service_server::MessageServiceServer;
+use crate::ton::method_service_server::MethodServiceServer;
+use crate::ton::webhook_service_server::WebhookServiceServer;
+use crate::webhook::WebhookService;
+use anyhow::{anyhow, bail};
+use derive_new::new;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::Arc;
+use tonic::body::BoxBody;
+use tonic::codec::CompressionEncoding::Gzip;
+use tonic::codegen::http::{Request, Response};
+use tonic::codegen::Service;
+use tonic::server::NamedService;
+use tonic::transport::server::{Router, Routes};
+use tonic::transport::{Body, Server};
+use tonlibjson_client::ton::TonClient;
+use tonlibjson_client::zero_state::ZeroState;
+use tower::layer::util::{Identity, Stack};
+use tower::util::Either;
+
+/// Middlewares of [`ApiServer::router`], the outermost first: panics, maintenance,
+/// api keys and quotas, enabled methods, deadlines, sessions and call options of methods
+pub type Middleware = Stack<
+    CallOptionsLayer,
+    Stack<
+        Either<SessionLayer, Identity>,
+        Stack<
+            DeadlineLayer,
+            Stack<
+                MethodPolicyLayer,
+                Stack<
+                    Either<QuotaLayer, Identity>,
+                    Stack<Either<MaintenanceLayer, Identity>, Stack<CatchPanicLayer, Identity>>,
+                >,
+            >,
+        >,
+    >,
+>;
+
+/// Services of a network, shared settings of [`ServerBuilder`] are applied to them
+pub struct NetworkServices {
+    pub account: AccountService,
+    pub block: BlockService,
+    pub message: MessageService,
+    pub webhooks: Option<WebhookService>,
+    /// zero state the lite servers start from, the network is tagged by its name without one
+    pub zero_state: Option<ZeroState>,
+}
+
+impl NetworkServices {
+    pub fn new(client: TonClient) -> Self {
+        Self {
+            account: AccountService::new(client.clone()),
+            block: BlockService::new(client.clone()),
+            message: MessageService::new(client),
+            webhooks: None,
+            zero_state: None,
+        }
+    }
+}
+
+/// Composes the services of networks and the middlewares in front of them, each setting is
+/// optional. Metrics are reported to the global `metrics` recorder, an embedder installs
+/// its own one before serving.
+#[derive(new)]
+pub struct ServerBuilder {
+    default_network: String,
+    #[new(default)]
+    networks: HashMap<String, NetworkServices>,
+    #[new(default)]
+    routes: Routes,
+    #[new(default)]
+    api_keys: Option<HashMap<String, ApiKey>>,
+    #[new(default)]
+    method_costs: Vec<(String, u64)>,
+    #[new(default)]
+    usage_store: Option<Arc<dyn UsageStore>>,
+    #[new(default)]
+    method_policy: MethodPolicy,
+    #[new(default)]
+    response_size_limits: ResponseSizeLimits,
+    #[new(default)]
+    param_limits: ParamLimits,
+    #[new(default)]
+    json_limits: JsonLimits,
+    #[new(default)]
+    cache_policy: CachePolicy,
+    #[new(default)]
+    cursors: Cursors,
+    #[new(default)]
+    sessions: Option<Arc<Sessions>>,
+    #[new(default)]
+    maintenance: Option<Arc<Maintenance>>,
+    #[new(default)]
+    call_options: MethodCallOptions,
+}
+
+impl ServerBuilder {
+    pub fn add_network(mut self, network: impl Into<String>, services: NetworkServices) -> Self {
+        self.networks.insert(network.into(), services);
+
+        self
+    }
+
+    /// Serves `service` along with the API, e.g. health or an embedder's own one
+    pub fn add_service<S>(mut self, service: S) -> Self
+    where
+        S: Service<Request<Body>, Response = Response<BoxBody>, Error = Infallible>
+            + NamedService
+            + Clone
+            + Send
+            + 'static,
+        S::Future: Send + 'static,
+    {
+        self.routes = self.routes.add_service(service);
+
+        self
+    }
+
+    /// Requires one of `keys` in every request and counts their usage against quotas
+    pub fn set_api_keys(mut self, keys: HashMap<String, ApiKey>) -> Self {
+        self.api_keys = Some(keys);
+
+        self
+    }
+
+    /// Quota units of methods, requires [`Self::set_api_keys`]
+    pub fn set_method_costs(mut self, costs: impl IntoIterator<Item = (String, u64)>) -> Self {
+        self.method_costs = costs.into_iter().collect();
+
+        self
+    }
+
+    /// Store of key usage, requires [`Self::set_api_keys`], usage is kept in memory by default
+    pub fn set_usage_store(mut self, store: Arc<dyn UsageStore>) -> Self {
+        self.usage_store = Some(store);
+
+        self
+    }
+
+    pub fn set_method_policy(mut self, policy: MethodPolicy) -> Self {
+        self.method_policy = policy;
+
+        self
+    }
+
+    pub fn set_response_size_limits(mut self, limits: ResponseSizeLimits) -> Self {
+        self.response_size_limits = limits;
+
+        self
+    }
+
+    pub fn set_param_limits(mut self, limits: ParamLimits) -> Self {
+        self.param_limits = limits;
+
+        self
+    }
+
+    /// Bounds of JSON request parameters, e.g. the stack of RunGetMethod
+    pub fn set_json_limits(mut self, limits: JsonLimits) -> Self {
+        self.json_limits = limits;
+
+        self
+    }
+
+    pub fn set_cache_policy(mut self, policy: CachePolicy) -> Self {
+        self.cache_policy = policy;
+
+        self
+    }
+
+    pub fn set_cursors(mut self, cursors: Cursors) -> Self {
+        self.cursors = cursors;
+
+        self
+    }
+
+    /// Routes reads of a session to lite servers which have reached the blocks it has seen,
+    /// off by default, see [`crate::session::SESSION_HEADER`]
+    pub fn set_sessions(mut self, sessions: Arc<Sessions>) -> Self {
+        self.sessions = Some(sessions);
+
+        self
+    }
+
+    /// Rejects new requests while maintenance is on, see [`Maintenance`]
+    pub fn set_maintenance(mut self, maintenance: Arc<Maintenance>) -> Self {
+        self.maintenance = Some(maintenance);
+
+        self
+    }
+
+    /// Timeout and retries of the lite server calls of methods, see [`MethodCallOptions`]
+    pub fn set_call_options(mut self, options: MethodCallOptions) -> Self {
+        self.call_options = options;
+
+        self
+    }
+
+    /// Fails on settings which don't work together, e.g. method costs without api keys
+    pub fn build(self) -> anyhow::Result<ApiServer> {
+        if !self.networks.contains_key(&self.default_network) {
+            bail!("default network {} is not configured", self.default_network);
+        }
+        if self.api_keys.is_none() && !self.method_costs.is_empty() {
+            bail!("method costs require api keys");
+        }
+        if self.api_keys.is_none() && self.usage_store.is_some() {
+            bail!("a usage store requires api keys");
+        }
+
+        let quotas = self.api_keys.map(|keys| {
+            let store = self
+                .usage_store
+                .unwrap_or_else(|| Arc::new(MemoryUsageStore::default()));
+
+            Arc::new(Quotas::new(keys, store).set_method_costs(self.method_costs))
+        });
+        let method_policy = Arc::new(self.method_policy);
+
+        let max_size = self.response_size_limits.global();
+        let mut account_services = HashMap::new();
+        let mut block_services = HashMap::new();
+        let mut message_services = HashMap::new();
+        let mut webhook_services = HashMap::new();
+        let mut zero_states = HashMap::new();
+        for (network, services) in self.networks {
+            if let Some(zero_state) = services.zero_state {
+                zero_states.insert(network.clone(), zero_state);
+            }
+            let account = services
+                .account
+                .set_response_size_limits(self.response_size_limits.clone())
+                .set_param_limits(self.param_limits.clone())
+                .set_json_limits(self.json_limits)
+                .set_cache_policy(self.cache_policy)
+                .set_cursors(self.cursors.clone());
+            let block = services
+                .block
+                .set_response_size_limits(self.response_size_limits.clone())
+                .set_param_limits(self.param_limits.clone())
+                .set_cache_policy(self.cache_policy);
+
+            account_services.insert(
+                network.clone(),
+                AccountServiceServer::new(account)
+                    .accept_compressed(Gzip)
+                    .send_compressed(Gzip)
+                    .max_encoding_message_size(max_size),
+            );
+            block_services.insert(
+                network.clone(),
+                BlockServiceServer::new(block)
+                    .accept_compressed(Gzip)
+                    .send_compressed(Gzip)
+                    .max_encoding_message_size(max_size),
+            );
+            message_services.insert(
+                network.clone(),
+                MessageServiceServer::new(services.message)
+                    .accept_compressed(Gzip)
+                    .send_compressed(Gzip)
+                    .max_encoding_message_size(max_size),
+            );
+            if let Some(webhooks) = services.webhooks {
+                webhook_services.insert(network, WebhookServiceServer::new(webhooks));
+            }
+        }
+
+        let mut served = vec![
+            AccountServiceServer::<AccountService>::NAME,
+            BlockServiceServer::<BlockService>::NAME,
+            MessageServiceServer::<MessageService>::NAME,
+            MethodServiceServer::<MethodService>::NAME,
+        ];
+        if !webhook_services.is_empty() {
+            served.push(WebhookServiceServer::<WebhookService>::NAME);
+        }
+        let method_service = MethodServiceServer::new(MethodService::new(
+            method_policy.clone(),
+            quotas.clone(),
+            &served,
+        )?);
+        let reflection = tonic_reflection::server::Builder::configure()
+            .register_encoded_file_descriptor_set(tonic_health::pb::FILE_DESCRIPTOR_SET)
+            .register_encoded_file_descriptor_set(ton::FILE_DESCRIPTOR_SET)
+            .build()
+            .map_err(|e| anyhow!(e))?;
+
+        let default = self.default_network;
+        let webhooks = (!webhook_services.is_empty()).then(|| {
+            NetworkRouter::new(default.clone(), webhook_services).set_zero_states(&zero_states)
+        });
+        let mut routes = self
+            .routes
+            .add_service(reflection)
+            .add_service(
+                NetworkRouter::new(default.clone(), account_services).set_zero_states(&zero_states),
+            )
+            .add_service(
+                NetworkRouter::new(default.clone(), block_services).set_zero_states(&zero_states),
+            )
+            .add_service(
+                NetworkRouter::new(default, message_services).set_zero_states(&zero_states),
+            )
+            .add_service(method_service);
+        if let Some(webhooks) = webhooks {
+            routes = routes.add_service(webhooks);
+        }
+
+        Ok(ApiServer {
+            routes,
+            quotas,
+            method_policy,
+            sessions: self.sessions,
+            maintenance: self.maintenance,
+            call_options: Arc::new(self.call_options),
+        })
+    }
+
+    /// [`Self::build`] and [`ApiServer::router`]
+    pub fn build_router(self, server: Server) -> anyhow::Result<Router<Middleware>> {
+        Ok(self.build()?.router(server))
+    }
+}
+
+/// Routes and middlewares of the API, served on as many listeners as needed
+#[derive(Clone)]
+pub struct ApiServer {
+    routes: Routes,
+    quotas: Option<Arc<Quotas>>,
+    method_policy: Arc<MethodPolicy>,
+    sessions: Option<Arc<Sessions>>,
+    maintenance: Option<Arc<Maintenance>>,
+    call_options: Arc<MethodCallOptions>,
+}
+
+impl ApiServer {
+    /// Quotas of api keys, none if keys aren't required
+    pub fn quotas(&self) -> Option<Arc<Quotas>> {
+        self.quotas.clone()
+    }
+
+    pub fn router(&self, server: Server) -> Router<Middleware> {
+        server
+            .layer(CatchPanicLayer)
+            .layer(tower::util::option_layer(
+                self.maintenance.clone().map(MaintenanceLayer::new),
+            ))
+            .layer(tower::util::option_layer(
+                self.quotas.clone().map(QuotaLayer::new),
+            ))
+            .layer(MethodPolicyLayer::new(
+                self.method_policy.clone(),
+                self.quotas.clone(),
+            ))
+            .layer(DeadlineLayer)
+            .layer(tower::util::option_layer(
+                self.sessions.clone().map(SessionLayer::new),
+            ))
+            .layer(CallOptionsLayer::new(self.call_options.clone()))
+            .add_routes(self.routes.clone())
+    }
+}
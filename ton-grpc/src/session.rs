@@ -0,0 +1,252 @@
+use crate::memory::{EntrySize, Tracked};
+use crate::network::NETWORK_HEADER;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use quick_cache::sync::Cache;
+use std::convert::Infallible;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tonic::body::BoxBody;
+use tonic::codegen::http::{HeaderMap, Request, Response};
+use tonic::codegen::{Body, Bytes, Service};
+use tonic::Status;
+use tonlibjson_client::consistency::{self, Session};
+use tower::{Layer, ServiceExt};
+
+/// Token of a client session, e.g. of a wallet: reads of a session never go to lite servers
+/// behind the masterchain block its previous requests have seen, so a seqno read right after
+/// sending a message isn't an older one
+pub const SESSION_HEADER: &str = "x-ton-session";
+
+const MAX_TOKEN_LEN: usize = 128;
+
+/// Sessions by network and token, the least recently used ones are forgotten
+pub struct Sessions {
+    sessions: Cache<(String, String), Arc<Session>>,
+    entry_size: EntrySize,
+}
+
+impl Sessions {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            sessions: Cache::new(capacity),
+            entry_size: Default::default(),
+        }
+    }
+
+    fn get(&self, headers: &HeaderMap) -> Option<Arc<Session>> {
+        let header = |name| headers.get(name).and_then(|v| v.to_str().ok());
+
+        let token = header(SESSION_HEADER)
+            .filter(|token| !token.is_empty() && token.len() <= MAX_TOKEN_LEN)?;
+        let key = (
+            header(NETWORK_HEADER).unwrap_or_default().to_owned(),
+            token.to_owned(),
+        );
+
+        // concurrent first requests of a token share the session one of them inserts
+        let Ok(session) = self.sessions.get_or_insert_with(&key, || {
+            self.entry_size
+                .sample(|| std::mem::size_of::<Session>() + key.0.len() + key.1.len());
+
+            Ok::<_, Infallible>(Arc::new(Session::default()))
+        });
+
+        Some(session)
+    }
+}
+
+/// Sessions are forgotten as a whole, the cache has no order to drop the oldest ones by
+impl Tracked for Sessions {
+    fn usage(&self) -> usize {
+        self.sessions.len() * self.entry_size.get()
+    }
+
+    fn shrink(&self, _: usize) -> usize {
+        let freed = self.usage();
+        self.sessions.clear();
+
+        freed
+    }
+}
+
+#[derive(Clone)]
+pub struct SessionLayer {
+    sessions: Arc<Sessions>,
+}
+
+impl SessionLayer {
+    pub fn new(sessions: Arc<Sessions>) -> Self {
+        Self { sessions }
+    }
+}
+
+impl<S> Layer<S> for SessionLayer {
+    type Service = SessionService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        SessionService {
+            inner,
+            sessions: self.sessions.clone(),
+        }
+    }
+}
+
+/// Routes lite server requests of a handler and its response stream by the session of
+/// [`SESSION_HEADER`]
+#[derive(Clone)]
+pub struct SessionService<S> {
+    inner: S,
+    sessions: Arc<Sessions>,
+}
+
+impl<S, B> Service<Request<B>> for SessionService<S>
+where
+    S: Service<Request<B>, Response = Response<BoxBody>> + Clone + Send + 'static,
+    S::Error: Send + 'static,
+    S::Future: Send,
+    B: Send + 'static,
+{
+    type Response = Response<BoxBody>;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        let Some(session) = self.sessions.get(req.headers()) else {
+            return self.inner.clone().oneshot(req).boxed();
+        };
+
+        let response = consistency::scope(session.clone(), self.inner.clone().oneshot(req));
+        async move {
+            let response = response.await?;
+
+            Ok(response.map(|inner| SessionBody { inner, session }.boxed_unsync()))
+        }
+        .boxed()
+    }
+}
+
+struct SessionBody {
+    inner: BoxBody,
+    session: Arc<Session>,
+}
+
+impl Body for SessionBody {
+    type Data = Bytes;
+    type Error = Status;
+
+    fn poll_data(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let this = self.get_mut();
+        let inner = &mut this.inner;
+
+        consistency::sync_scope(this.session.clone(), || Pin::new(inner).poll_data(cx))
+    }
+
+    fn poll_trailers(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        Pin::new(&mut self.inner).poll_trailers(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tonic::codegen::http::HeaderValue;
+
+    /// Empty body which observes a seqno in the session it's polled in
+    struct ObserveBody(i32);
+
+    impl Body for ObserveBody {
+        type Data = Bytes;
+        type Error = Status;
+
+        fn poll_data(
+            self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+        ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+            if let Some(session) = consistency::current() {
+                session.observe(self.0);
+            }
+
+            Poll::Ready(None)
+        }
+
+        fn poll_trailers(
+            self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+        ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+            Poll::Ready(Ok(None))
+        }
+    }
+
+    fn headers(network: Option<&'static str>, token: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(SESSION_HEADER, HeaderValue::from_str(token).unwrap());
+        if let Some(network) = network {
+            headers.insert(NETWORK_HEADER, HeaderValue::from_static(network));
+        }
+
+        headers
+    }
+
+    #[test]
+    fn sessions_of_tokens_and_networks() {
+        let sessions = Sessions::new(16);
+        sessions.get(&headers(None, "wallet")).unwrap().observe(100);
+
+        assert_eq!(
+            sessions.get(&headers(None, "wallet")).unwrap().seqno(),
+            Some(100)
+        );
+        assert_eq!(
+            sessions
+                .get(&headers(Some("testnet"), "wallet"))
+                .unwrap()
+                .seqno(),
+            None
+        );
+        assert_eq!(sessions.get(&headers(None, "other")).unwrap().seqno(), None);
+        assert!(sessions.get(&HeaderMap::new()).is_none());
+        assert!(sessions.get(&headers(None, "")).is_none());
+        assert!(sessions.get(&headers(None, &"a".repeat(129))).is_none());
+    }
+
+    #[tokio::test]
+    async fn requests_and_their_streams_run_in_their_session() {
+        let sessions = Arc::new(Sessions::new(16));
+        let inner = tower::service_fn(|_: Request<()>| async {
+            if let Some(session) = consistency::current() {
+                session.observe(7);
+            }
+
+            Ok::<_, Infallible>(Response::new(ObserveBody(9).boxed_unsync()))
+        });
+        let service = SessionLayer::new(sessions.clone()).layer(inner);
+        let seqno = || sessions.get(&headers(None, "wallet")).unwrap().seqno();
+
+        let mut request = Request::new(());
+        *request.headers_mut() = headers(None, "wallet");
+        let mut body = service.clone().oneshot(request).await.unwrap().into_body();
+        assert_eq!(seqno(), Some(7));
+        assert!(body.data().await.is_none());
+        assert_eq!(seqno(), Some(9));
+
+        let mut body = service.oneshot(Request::new(())).await.unwrap().into_body();
+        assert!(body.data().await.is_none());
+        assert_eq!(sessions.sessions.len(), 1);
+    }
+}
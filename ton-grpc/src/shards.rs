@@ -0,0 +1,333 @@
+use crate::helpers::fetch_each;
+use crate::memory::{EntrySize, Tracked};
+use crate::ton::shard_change::Kind;
+use crate::ton::{GetShardHierarchyResponse, ShardChange, ShardError};
+use futures::{stream, Future, StreamExt, TryStreamExt};
+use quick_cache::sync::Cache;
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use tonlibjson_client::block::TonBlockIdExt;
+use tonlibjson_client::shard;
+use tonlibjson_client::ton::TonClient;
+
+/// Masterchain blocks whose shards are fetched at a time
+const SHARDS_CONCURRENCY: usize = 16;
+
+/// Shards of masterchain blocks, they never change once a block is committed
+pub struct ShardHistory {
+    shards: Cache<i32, Arc<Vec<TonBlockIdExt>>>,
+    entry_size: EntrySize,
+}
+
+impl Default for ShardHistory {
+    fn default() -> Self {
+        Self {
+            shards: Cache::new(4096),
+            entry_size: Default::default(),
+        }
+    }
+}
+
+impl ShardHistory {
+    async fn get(
+        &self,
+        client: &TonClient,
+        mc_seqno: i32,
+    ) -> anyhow::Result<Arc<Vec<TonBlockIdExt>>> {
+        self.shards
+            .get_or_insert_async(&mc_seqno, async {
+                let shards = client.get_shards(mc_seqno).await?.shards;
+                self.entry_size.sample(|| {
+                    shards
+                        .iter()
+                        .map(|shard| {
+                            std::mem::size_of::<TonBlockIdExt>()
+                                + shard.root_hash.len()
+                                + shard.file_hash.len()
+                        })
+                        .sum()
+                });
+
+                anyhow::Ok(Arc::new(shards))
+            })
+            .await
+    }
+
+    /// Shards at `from_seqno` and the splits and merges up to `to_seqno`
+    pub async fn hierarchy(
+        &self,
+        client: &TonClient,
+        from_seqno: i32,
+        to_seqno: i32,
+    ) -> anyhow::Result<GetShardHierarchyResponse> {
+        let blocks: Vec<_> = stream::iter(from_seqno..=to_seqno)
+            .map(|seqno| self.get(client, seqno))
+            .buffered(SHARDS_CONCURRENCY)
+            .try_collect()
+            .await?;
+
+        let mut changes = Vec::new();
+        for (seqno, pair) in (from_seqno + 1..).zip(blocks.windows(2)) {
+            changes.extend(topology_changes(seqno, &pair[0], &pair[1]));
+        }
+
+        Ok(GetShardHierarchyResponse {
+            shards: blocks[0].iter().cloned().map(Into::into).collect(),
+            changes,
+        })
+    }
+}
+
+/// Shards are dropped as a whole, the cache has no order to drop the oldest ones by
+impl Tracked for ShardHistory {
+    fn usage(&self) -> usize {
+        self.shards.len() * self.entry_size.get()
+    }
+
+    fn shrink(&self, _: usize) -> usize {
+        let freed = self.usage();
+        self.shards.clear();
+
+        freed
+    }
+}
+
+/// Shards fetched independently of each other, in the order they were given
+#[derive(Debug)]
+pub struct ShardResults<T> {
+    pub fetched: Vec<(TonBlockIdExt, T)>,
+    pub errors: Vec<ShardError>,
+}
+
+impl<T> ShardResults<T> {
+    pub fn is_complete(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+/// Fetches every shard with `fetch`, a failed shard is reported in `errors` unless `strict`,
+/// in which case the first failure fails all of them
+pub async fn fetch_shards<T, F, Fut>(
+    shards: Vec<TonBlockIdExt>,
+    strict: bool,
+    fetch: F,
+) -> anyhow::Result<ShardResults<T>>
+where
+    F: Fn(TonBlockIdExt) -> Fut,
+    Fut: Future<Output = anyhow::Result<T>>,
+{
+    let count = shards.len();
+    let results: Vec<_> = fetch_each(
+        stream::iter(shards).map(Ok),
+        count,
+        SHARDS_CONCURRENCY,
+        fetch,
+    )
+    .try_collect()
+    .await?;
+
+    let mut fetched = Vec::new();
+    let mut errors = Vec::new();
+    for (shard, result) in results {
+        match result {
+            Ok(value) => fetched.push((shard, value)),
+            Err(e) if strict => {
+                return Err(e.context(format!(
+                    "shard {}:{:016x}:{}",
+                    shard.workchain, shard.shard as u64, shard.seqno
+                )))
+            }
+            Err(e) => errors.push(ShardError {
+                block: Some(shard.into()),
+                error: format!("{e:#}"),
+            }),
+        }
+    }
+
+    Ok(ShardResults { fetched, errors })
+}
+
+type Topology = BTreeSet<(i32, i64)>;
+
+fn topology(shards: &[TonBlockIdExt]) -> Topology {
+    shards.iter().map(|s| (s.workchain, s.shard)).collect()
+}
+
+/// Splits and merges between the shards of consecutive masterchain blocks, a shard which
+/// is gone split into its descendants or merged into its ancestor
+pub fn topology_changes(
+    mc_seqno: i32,
+    before: &[TonBlockIdExt],
+    after: &[TonBlockIdExt],
+) -> Vec<ShardChange> {
+    let (before, after) = (topology(before), topology(after));
+    let removed: Vec<_> = before.difference(&after).collect();
+    let added: Vec<_> = after.difference(&before).collect();
+
+    // in the order of accounts they cover
+    let descendants = |of: &(i32, i64), among: &[&(i32, i64)]| -> Vec<i64> {
+        let mut shards: Vec<_> = among
+            .iter()
+            .filter(|(workchain, shard)| *workchain == of.0 && shard::is_ancestor(of.1, *shard))
+            .map(|(_, shard)| *shard)
+            .collect();
+        shards.sort_unstable_by_key(|shard| *shard as u64);
+
+        shards
+    };
+
+    let mut changes = Vec::new();
+    for gone in &removed {
+        let children = descendants(gone, &added);
+        if !children.is_empty() {
+            changes.push(ShardChange {
+                mc_seqno,
+                workchain: gone.0,
+                kind: Kind::Split.into(),
+                before: vec![gone.1],
+                after: children,
+            });
+        }
+    }
+    for new in &added {
+        let merged = descendants(new, &removed);
+        if !merged.is_empty() {
+            changes.push(ShardChange {
+                mc_seqno,
+                workchain: new.0,
+                kind: Kind::Merge.into(),
+                before: merged,
+                after: vec![new.1],
+            });
+        }
+    }
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROOT: i64 = shard::ROOT;
+    const LEFT: i64 = 0x4000000000000000;
+    const RIGHT: i64 = 0xc000000000000000_u64 as i64;
+    const RIGHT_LEFT: i64 = 0xa000000000000000_u64 as i64;
+    const RIGHT_RIGHT: i64 = 0xe000000000000000_u64 as i64;
+
+    fn shards(ids: &[i64]) -> Vec<TonBlockIdExt> {
+        ids.iter()
+            .map(|shard| TonBlockIdExt::new(0, *shard, 1, String::new(), String::new()))
+            .collect()
+    }
+
+    async fn fetch_header(shard: TonBlockIdExt) -> anyhow::Result<i32> {
+        if shard.shard == RIGHT {
+            anyhow::bail!("block is not applied");
+        }
+
+        Ok(shard.seqno)
+    }
+
+    #[tokio::test]
+    async fn failed_shard_is_reported() {
+        let results = fetch_shards(shards(&[LEFT, RIGHT]), false, fetch_header)
+            .await
+            .unwrap();
+
+        assert!(!results.is_complete());
+        assert_eq!(results.fetched, vec![(shards(&[LEFT])[0].clone(), 1)]);
+        assert_eq!(
+            results.errors,
+            vec![ShardError {
+                block: Some(shards(&[RIGHT])[0].clone().into()),
+                error: "block is not applied".to_owned(),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn strict_fails_on_failed_shard() {
+        let error = fetch_shards(shards(&[LEFT, RIGHT]), true, fetch_header)
+            .await
+            .unwrap_err();
+
+        assert!(format!("{error:#}").contains("block is not applied"));
+        assert!(format!("{error:#}").contains("c000000000000000"));
+    }
+
+    #[tokio::test]
+    async fn all_shards_fetched_is_complete() {
+        let results = fetch_shards(shards(&[LEFT]), true, fetch_header)
+            .await
+            .unwrap();
+
+        assert!(results.is_complete());
+        assert_eq!(results.fetched.len(), 1);
+    }
+
+    #[test]
+    fn unchanged_shards() {
+        assert!(topology_changes(2, &shards(&[LEFT, RIGHT]), &shards(&[LEFT, RIGHT])).is_empty());
+    }
+
+    #[test]
+    fn split() {
+        let changes = topology_changes(2, &shards(&[ROOT]), &shards(&[LEFT, RIGHT]));
+
+        assert_eq!(
+            changes,
+            vec![ShardChange {
+                mc_seqno: 2,
+                workchain: 0,
+                kind: Kind::Split.into(),
+                before: vec![ROOT],
+                after: vec![LEFT, RIGHT],
+            }]
+        );
+    }
+
+    #[test]
+    fn merge() {
+        let changes = topology_changes(
+            3,
+            &shards(&[LEFT, RIGHT_LEFT, RIGHT_RIGHT]),
+            &shards(&[LEFT, RIGHT]),
+        );
+
+        assert_eq!(
+            changes,
+            vec![ShardChange {
+                mc_seqno: 3,
+                workchain: 0,
+                kind: Kind::Merge.into(),
+                before: vec![RIGHT_LEFT, RIGHT_RIGHT],
+                after: vec![RIGHT],
+            }]
+        );
+    }
+
+    #[test]
+    fn split_and_merge_at_once() {
+        let changes = topology_changes(
+            4,
+            &shards(&[LEFT, RIGHT_LEFT, RIGHT_RIGHT]),
+            &shards(&[0x2000000000000000, 0x6000000000000000, RIGHT]),
+        );
+
+        let kinds: Vec<_> = changes.iter().map(|c| c.kind()).collect();
+        assert_eq!(kinds, vec![Kind::Split, Kind::Merge]);
+        assert_eq!(changes[0].before, vec![LEFT]);
+        assert_eq!(changes[1].after, vec![RIGHT]);
+    }
+
+    #[test]
+    fn workchains_are_apart() {
+        let mut before = shards(&[ROOT]);
+        before[0].workchain = 1;
+
+        let changes = topology_changes(2, &before, &shards(&[LEFT, RIGHT]));
+
+        assert!(changes.is_empty());
+    }
+}
@@ -0,0 +1,270 @@
+use crate::memory::{EntrySize, Tracked};
+use crate::ton::GetAccountStateResponse;
+use prost::Message;
+use quick_cache::sync::Cache;
+use quick_cache::{DefaultHashBuilder, Lifecycle, UnitWeighter};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::time::MissedTickBehavior;
+use tonic_health::server::HealthReporter;
+use tonic_health::ServingStatus;
+use tonlibjson_client::ton::TonClient;
+
+type Entry = (Instant, GetAccountStateResponse);
+
+/// Latest account states by address, served marked as stale while the circuit of reads is open
+pub struct StaleStates {
+    max_staleness: Duration,
+    cache: Cache<String, Entry, UnitWeighter, DefaultHashBuilder, DropEvicted>,
+    /// cached addresses by their last insertion, the oldest ones are evicted by [`Tracked::shrink`]
+    order: Arc<Mutex<Order>>,
+    entry_size: EntrySize,
+}
+
+/// One position per address, moved to the back as the address is inserted again
+#[derive(Default)]
+struct Order {
+    next: u64,
+    positions: HashMap<String, u64>,
+    addresses: BTreeMap<u64, String>,
+}
+
+impl Order {
+    fn push_back(&mut self, address: String) {
+        if let Some(position) = self.positions.insert(address.clone(), self.next) {
+            self.addresses.remove(&position);
+        }
+        self.addresses.insert(self.next, address);
+        self.next += 1;
+    }
+
+    fn remove(&mut self, address: &str) {
+        if let Some(position) = self.positions.remove(address) {
+            self.addresses.remove(&position);
+        }
+    }
+
+    fn pop_front(&mut self) -> Option<String> {
+        let (_, address) = self.addresses.pop_first()?;
+        self.positions.remove(&address);
+
+        Some(address)
+    }
+}
+
+/// Drops the positions of the addresses the cache evicts on its own, once the shard lock
+/// they're evicted under is released
+#[derive(Clone)]
+struct DropEvicted(Arc<Mutex<Order>>);
+
+impl Lifecycle<String, Entry> for DropEvicted {
+    type RequestState = Vec<String>;
+
+    fn begin_request(&self) -> Self::RequestState {
+        Vec::new()
+    }
+
+    fn on_evict(&self, evicted: &mut Self::RequestState, address: String, _: Entry) {
+        evicted.push(address);
+    }
+
+    fn end_request(&self, evicted: Self::RequestState) {
+        if evicted.is_empty() {
+            return;
+        }
+
+        let mut order = self.0.lock().unwrap();
+        for address in evicted {
+            order.remove(&address);
+        }
+    }
+}
+
+impl StaleStates {
+    pub fn new(max_staleness: Duration, capacity: usize) -> Self {
+        metrics::describe_counter!(
+            "ton_grpc_stale_responses_total",
+            "Number of responses served from cache while lite servers are unavailable"
+        );
+
+        let order = Arc::new(Mutex::default());
+
+        Self {
+            max_staleness,
+            cache: Cache::with(
+                capacity,
+                capacity as u64,
+                UnitWeighter,
+                Default::default(),
+                DropEvicted(order.clone()),
+            ),
+            order,
+            entry_size: Default::default(),
+        }
+    }
+
+    /// The cached state with `stale` and `as_of_seqno` set, none if it's older than the bound
+    pub fn get(&self, address: &str) -> Option<GetAccountStateResponse> {
+        let (fetched_at, mut response) = self.cache.get(address)?;
+        if fetched_at.elapsed() > self.max_staleness {
+            self.cache.remove(address);
+            self.order.lock().unwrap().remove(address);
+
+            return None;
+        }
+
+        metrics::counter!("ton_grpc_stale_responses_total", "method" => "GetAccountState")
+            .increment(1);
+        response.stale = true;
+        response.as_of_seqno = response.block_id.as_ref().map(|block| block.seqno);
+
+        Some(response)
+    }
+
+    pub fn insert(&self, address: String, response: GetAccountStateResponse) {
+        self.entry_size
+            .sample(|| address.len() + response.encoded_len());
+        // positioned first so that the cache dropping the very entry inserted drops it as well,
+        // not locked while inserting since the cache evicts under its shard lock
+        self.order.lock().unwrap().push_back(address.clone());
+        self.cache.insert(address, (Instant::now(), response));
+    }
+}
+
+impl Tracked for StaleStates {
+    fn usage(&self) -> usize {
+        self.cache.len() * self.entry_size.get()
+    }
+
+    fn shrink(&self, bytes: usize) -> usize {
+        let entry_size = self.entry_size.get().max(1);
+        let mut freed = 0;
+        while freed < bytes {
+            let Some(address) = self.order.lock().unwrap().pop_front() else {
+                break;
+            };
+            if self.cache.remove(&address).is_some() {
+                freed += entry_size;
+            }
+        }
+
+        freed
+    }
+}
+
+/// Keeps the `<network>.degraded` health service SERVING while the circuit of reads is open,
+/// the readiness of the network itself doesn't flap as stale states are served meanwhile
+pub async fn report_degraded(client: TonClient, network: String, mut reporter: HealthReporter) {
+    let service = format!("{}.degraded", network);
+    let mut degraded = false;
+    reporter
+        .set_service_status(&service, ServingStatus::NotServing)
+        .await;
+
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    loop {
+        interval.tick().await;
+        if client.is_reads_circuit_open() == degraded {
+            continue;
+        }
+
+        degraded = !degraded;
+        tracing::warn!(network, degraded, "degradation mode");
+        metrics::gauge!("ton_grpc_degraded", "network" => network.clone()).set(degraded as u8);
+        let status = if degraded {
+            ServingStatus::Serving
+        } else {
+            ServingStatus::NotServing
+        };
+        reporter.set_service_status(&service, status).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::{MemoryBudget, Tier};
+    use crate::ton::BlockIdExt;
+    use std::sync::Arc;
+
+    fn response() -> GetAccountStateResponse {
+        GetAccountStateResponse {
+            account_address: "address".to_owned(),
+            block_id: Some(BlockIdExt {
+                workchain: -1,
+                shard: i64::MIN,
+                seqno: 42,
+                root_hash: String::new(),
+                file_hash: String::new(),
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn stale_state_is_marked() {
+        let states = StaleStates::new(Duration::from_secs(60), 16);
+        states.insert("address".to_owned(), response());
+
+        let state = states.get("address").unwrap();
+
+        assert!(state.stale);
+        assert_eq!(state.as_of_seqno, Some(42));
+        assert!(states.get("other").is_none());
+    }
+
+    #[test]
+    fn state_beyond_staleness_bound_is_not_served() {
+        let states = StaleStates::new(Duration::ZERO, 16);
+        states.insert("address".to_owned(), response());
+        std::thread::sleep(Duration::from_millis(1));
+
+        assert!(states.get("address").is_none());
+    }
+
+    #[test]
+    fn oldest_states_are_evicted_over_budget() {
+        let states = Arc::new(StaleStates::new(Duration::from_secs(60), 1024));
+        for i in 0..100 {
+            states.insert(format!("address-{}", i), response());
+        }
+        let usage = states.usage();
+        let budget = MemoryBudget::new(usage);
+        budget.register("stale_states", Tier::Cache, states.clone());
+
+        assert!(budget.enforce() >= usage / 5);
+        assert!(states.usage() <= usage * 4 / 5);
+        assert!(states.get("address-0").is_none());
+        assert!(states.get("address-99").is_some());
+    }
+
+    #[test]
+    fn state_inserted_again_is_the_newest() {
+        let states = Arc::new(StaleStates::new(Duration::from_secs(60), 1024));
+        for i in 0..10 {
+            states.insert(format!("address-{}", i), response());
+            states.insert("hot".to_owned(), response());
+        }
+        assert_eq!(states.order.lock().unwrap().addresses.len(), 11);
+
+        states.shrink(states.usage() / 2);
+
+        assert!(states.get("hot").is_some());
+        assert!(states.get("address-0").is_none());
+        assert!(states.get("address-9").is_some());
+    }
+
+    #[test]
+    fn evicted_states_leave_the_order() {
+        let states = StaleStates::new(Duration::from_secs(60), 16);
+        for i in 0..1000 {
+            states.insert(format!("address-{}", i), response());
+        }
+
+        let order = states.order.lock().unwrap();
+        assert!(order.addresses.len() <= 32);
+        assert_eq!(order.addresses.len(), order.positions.len());
+    }
+}
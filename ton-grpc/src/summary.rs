@@ -0,0 +1,263 @@
+use crate::ton::{account_state_delta, AccountSummary, TransactionId};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use futures::try_join;
+use std::future::Future;
+use std::str::FromStr;
+use ton_contract::interfaces::InterfaceDetector;
+use toner::tlb::bits::de::unpack_bytes;
+use toner::ton::boc::BoC;
+use toner::ton::MsgAddress;
+use tonic::Status;
+use tonlibjson_client::address::AccountAddressData;
+use tonlibjson_client::block::{InternalTransactionId, RawTransactions};
+use tonlibjson_client::ton::{AccountStatus, TonClient};
+
+/// Pages of history walked back looking for the first transaction, deeper ones are approximate
+pub const SUMMARY_MAX_PAGES: usize = 8;
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct HistoryProbe {
+    pub transactions: u64,
+    pub first_utime: Option<i64>,
+    pub last_utime: Option<i64>,
+    /// the first transaction wasn't reached, counts are lower bounds and first_utime an upper one
+    pub approximate: bool,
+}
+
+/// Walks history back from `last` page by page, up to `max_pages`. Pages which fail after
+/// the first one make the probe approximate, old history may be pruned by lite servers.
+pub async fn probe_history<F, Fut>(
+    last: Option<InternalTransactionId>,
+    max_pages: usize,
+    mut page: F,
+) -> anyhow::Result<HistoryProbe>
+where
+    F: FnMut(InternalTransactionId) -> Fut,
+    Fut: Future<Output = anyhow::Result<RawTransactions>>,
+{
+    let mut probe = HistoryProbe::default();
+    let Some(mut next) = last else {
+        return Ok(probe);
+    };
+
+    for _ in 0..max_pages {
+        let txs = match page(next).await {
+            Ok(txs) => txs,
+            Err(e) if probe.transactions > 0 => {
+                tracing::debug!(error = ?e, "older transactions are unreachable");
+                probe.approximate = true;
+
+                return Ok(probe);
+            }
+            Err(e) => return Err(e),
+        };
+
+        if probe.last_utime.is_none() {
+            probe.last_utime = txs.transactions.first().map(|tx| tx.utime);
+        }
+        if let Some(oldest) = txs.transactions.last() {
+            probe.first_utime = Some(oldest.utime);
+        }
+        probe.transactions += txs.transactions.len() as u64;
+
+        match txs.previous_transaction_id {
+            Some(previous) if !txs.transactions.is_empty() => next = previous,
+            _ => return Ok(probe),
+        }
+    }
+    probe.approximate = true;
+
+    Ok(probe)
+}
+
+fn root_hash(boc: &str) -> anyhow::Result<Option<[u8; 32]>> {
+    if boc.is_empty() {
+        return Ok(None);
+    }
+
+    let boc: BoC = unpack_bytes(STANDARD.decode(boc)?)?;
+
+    Ok(boc.single_root().map(|root| root.hash()))
+}
+
+/// Balance, status, type, hashes and activity of an account, as explorers show it on a card
+pub async fn account_summary(client: &TonClient, address: &str) -> Result<AccountSummary, Status> {
+    let account = AccountAddressData::from_str(address)
+        .map_err(|e| Status::invalid_argument(e.to_string()))?;
+    let contract =
+        MsgAddress::from_str(address).map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+    let state = client
+        .raw_get_account_state(address)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+    let (detected, history) = try_join!(
+        async {
+            client
+                .contract_interfaces(&contract)
+                .await
+                .map_err(anyhow::Error::from)
+        },
+        probe_history(
+            state.last_transaction_id.clone(),
+            SUMMARY_MAX_PAGES,
+            |next| async move { client.raw_get_transactions(address, &next).await }
+        )
+    )
+    .map_err(|e| Status::internal(e.to_string()))?;
+
+    let code_hash = root_hash(&state.code).map_err(|e| Status::internal(e.to_string()))?;
+    let data_hash = root_hash(&state.data).map_err(|e| Status::internal(e.to_string()))?;
+    let status = account_state_delta::Status::from(AccountStatus::from(&state));
+
+    Ok(AccountSummary {
+        account_address: address.to_owned(),
+        balance: state.balance.unwrap_or_default(),
+        status: status.into(),
+        interfaces: detected
+            .interfaces
+            .iter()
+            .map(|interface| interface.name().to_owned())
+            .collect(),
+        code_hash: code_hash.map(|hash| STANDARD.encode(hash)),
+        data_hash: data_hash.map(|hash| STANDARD.encode(hash)),
+        last_transaction_id: state
+            .last_transaction_id
+            .map(|tx_id| TransactionId::from((&account, tx_id))),
+        last_activity: history.last_utime,
+        first_activity: history.first_utime,
+        transaction_count: history.transactions,
+        approximate: history.approximate,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::Mutex;
+
+    /// history of `count` transactions with lt and utime 1..=count, `page_size` per page
+    fn page(count: i64, page_size: i64, from: &InternalTransactionId) -> RawTransactions {
+        let lts: Vec<_> = (1..=from.lt).rev().take(page_size as usize).collect();
+        let oldest = *lts.last().unwrap();
+        assert!(from.lt <= count);
+
+        serde_json::from_value(json!({
+            "@type": "raw.transactions",
+            "transactions": lts.iter().map(|lt| json!({
+                "@type": "raw.transaction",
+                "address": {"@type": "accountAddress", "account_address": "0:0000000000000000000000000000000000000000000000000000000000000001"},
+                "utime": lt.to_string(),
+                "data": "",
+                "transaction_id": {"@type": "internal.transactionId", "lt": lt.to_string(), "hash": format!("hash-{}", lt)},
+                "fee": "0",
+                "storage_fee": "0",
+                "other_fee": "0",
+                "in_msg": null,
+                "out_msgs": [],
+            })).collect::<Vec<_>>(),
+            // tonlib returns an empty id at the start of history
+            "previous_transaction_id": if oldest == 1 {
+                serde_json::to_value(InternalTransactionId::default()).unwrap()
+            } else {
+                json!({"@type": "internal.transactionId", "lt": (oldest - 1).to_string(), "hash": format!("hash-{}", oldest - 1)})
+            },
+        }))
+        .unwrap()
+    }
+
+    fn last(lt: i64) -> Option<InternalTransactionId> {
+        Some(InternalTransactionId {
+            lt,
+            hash: format!("hash-{}", lt),
+        })
+    }
+
+    #[tokio::test]
+    async fn shallow_history_is_exact() {
+        let probe = probe_history(last(25), 8, |next| async move { Ok(page(25, 10, &next)) })
+            .await
+            .unwrap();
+
+        assert_eq!(
+            probe,
+            HistoryProbe {
+                transactions: 25,
+                first_utime: Some(1),
+                last_utime: Some(25),
+                approximate: false,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn deep_history_is_approximate() {
+        let pages = Mutex::new(0);
+        let probe = probe_history(last(1000), 3, |next| {
+            *pages.lock().unwrap() += 1;
+            async move { Ok(page(1000, 10, &next)) }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(*pages.lock().unwrap(), 3);
+        assert_eq!(
+            probe,
+            HistoryProbe {
+                transactions: 30,
+                first_utime: Some(971),
+                last_utime: Some(1000),
+                approximate: true,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn history_of_exactly_max_pages_is_exact() {
+        let probe = probe_history(last(30), 3, |next| async move { Ok(page(30, 10, &next)) })
+            .await
+            .unwrap();
+
+        assert_eq!(probe.transactions, 30);
+        assert!(!probe.approximate);
+    }
+
+    #[tokio::test]
+    async fn pruned_history_is_approximate() {
+        let probe = probe_history(last(25), 8, |next| async move {
+            if next.lt < 20 {
+                Err(anyhow::anyhow!("lt not in db"))
+            } else {
+                Ok(page(25, 10, &next))
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(probe.transactions, 10);
+        assert_eq!(probe.first_utime, Some(16));
+        assert!(probe.approximate);
+    }
+
+    #[tokio::test]
+    async fn account_without_transactions() {
+        let probe = probe_history(None, 8, |_| async { unreachable!() })
+            .await
+            .unwrap();
+
+        assert_eq!(probe, HistoryProbe::default());
+    }
+
+    #[tokio::test]
+    async fn failing_first_page_is_error() {
+        let result = probe_history(last(25), 8, |_| async {
+            Err::<RawTransactions, _>(anyhow::anyhow!("timeout"))
+        })
+        .await;
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,469 @@
+use anyhow::{anyhow, Context};
+use rustls::crypto::ring;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{RootCertStore, ServerConfig};
+use std::io::{self, BufReader};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, RwLock};
+use std::task::{Context as TaskContext, Poll};
+use std::time::{Duration, SystemTime};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::mpsc;
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::transport::server::Connected;
+use webpki::EndEntityCert;
+
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+const TLS_ACCEPT_BACKLOG: usize = 64;
+const OID_COMMON_NAME: &[u8] = &[0x55, 0x04, 0x03];
+
+/// PEM files of the server certificate, its key and the CA of accepted clients
+#[derive(Debug, Clone)]
+pub struct TlsFiles {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+    /// requires clients to present a certificate signed by this CA
+    pub client_ca: Option<PathBuf>,
+}
+
+impl TlsFiles {
+    fn paths(&self) -> impl Iterator<Item = &Path> {
+        [Some(&self.cert), Some(&self.key), self.client_ca.as_ref()]
+            .into_iter()
+            .flatten()
+            .map(PathBuf::as_path)
+    }
+
+    fn modified(&self) -> Vec<Option<SystemTime>> {
+        self.paths()
+            .map(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok())
+            .collect()
+    }
+
+    fn load(&self) -> anyhow::Result<ServerConfig> {
+        let provider = Arc::new(ring::default_provider());
+        let certs = read_certs(&self.cert)?;
+        let key = read_key(&self.key)?;
+
+        let builder = ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()?;
+        let builder = match &self.client_ca {
+            Some(path) => {
+                let mut roots = RootCertStore::empty();
+                for cert in read_certs(path)? {
+                    roots.add(cert)?;
+                }
+
+                builder.with_client_cert_verifier(
+                    WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+                        .build()?,
+                )
+            }
+            None => builder.with_no_client_auth(),
+        };
+
+        let mut config = builder.with_single_cert(certs, key)?;
+        config.alpn_protocols = vec![b"h2".to_vec()];
+
+        Ok(config)
+    }
+}
+
+fn read_certs(path: &Path) -> anyhow::Result<Vec<CertificateDer<'static>>> {
+    let file = std::fs::File::open(path).with_context(|| path.display().to_string())?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file)).collect::<Result<Vec<_>, _>>()?;
+    if certs.is_empty() {
+        return Err(anyhow!("no certificates in {}", path.display()));
+    }
+
+    Ok(certs)
+}
+
+fn read_key(path: &Path) -> anyhow::Result<PrivateKeyDer<'static>> {
+    let file = std::fs::File::open(path).with_context(|| path.display().to_string())?;
+
+    rustls_pemfile::private_key(&mut BufReader::new(file))?
+        .ok_or_else(|| anyhow!("no private key in {}", path.display()))
+}
+
+/// DER tag, contents and the rest of `input`
+fn der_tlv(input: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = input.split_first()?;
+    let (&len, rest) = rest.split_first()?;
+    let (len, rest) = if len < 0x80 {
+        (len as usize, rest)
+    } else {
+        let n = (len & 0x7f) as usize;
+        if n == 0 || n > 4 || rest.len() < n {
+            return None;
+        }
+        let (len, rest) = rest.split_at(n);
+
+        (len.iter().fold(0, |acc, b| acc << 8 | *b as usize), rest)
+    };
+    if rest.len() < len {
+        return None;
+    }
+    let (value, rest) = rest.split_at(len);
+
+    Some((tag, value, rest))
+}
+
+/// Subject CN of a certificate
+fn common_name(cert: &CertificateDer) -> Option<String> {
+    let cert = EndEntityCert::try_from(cert).ok()?;
+
+    // Name ::= SEQUENCE OF SET OF SEQUENCE { type OID, value ANY }
+    let mut names = cert.subject();
+    while let Some((_, mut attributes, rest)) = der_tlv(names) {
+        names = rest;
+        while let Some((_, attribute, rest)) = der_tlv(attributes) {
+            attributes = rest;
+            if let Some((0x06, OID_COMMON_NAME, value)) = der_tlv(attribute) {
+                let (_, value, _) = der_tlv(value)?;
+
+                return String::from_utf8(value.to_vec()).ok();
+            }
+        }
+    }
+
+    None
+}
+
+/// Server TLS config which follows changes of its files
+pub struct ReloadableTls {
+    files: TlsFiles,
+    state: RwLock<(Vec<Option<SystemTime>>, Arc<ServerConfig>)>,
+}
+
+impl ReloadableTls {
+    pub fn new(files: TlsFiles) -> anyhow::Result<Arc<Self>> {
+        let modified = files.modified();
+        let config = Arc::new(files.load()?);
+
+        Ok(Arc::new(Self {
+            files,
+            state: RwLock::new((modified, config)),
+        }))
+    }
+
+    fn acceptor(&self) -> TlsAcceptor {
+        TlsAcceptor::from(self.state.read().unwrap().1.clone())
+    }
+
+    /// Reloads the files if they changed since the last load or if forced, the current
+    /// config stays in use when they are broken. Open connections aren't affected.
+    pub fn reload(&self, force: bool) -> anyhow::Result<bool> {
+        let modified = self.files.modified();
+        if !force && self.state.read().unwrap().0 == modified {
+            return Ok(false);
+        }
+
+        let config = Arc::new(self.files.load()?);
+        *self.state.write().unwrap() = (modified, config);
+
+        Ok(true)
+    }
+
+    /// Checks the files every `interval` and reloads them on SIGHUP
+    pub fn spawn_reloader(self: &Arc<Self>, interval: Duration) -> anyhow::Result<()> {
+        let tls = self.clone();
+        let mut hangup = signal(SignalKind::hangup())?;
+        let mut interval = tokio::time::interval(interval);
+
+        tokio::spawn(async move {
+            loop {
+                let force = tokio::select! {
+                    _ = hangup.recv() => true,
+                    _ = interval.tick() => false,
+                };
+
+                match tls.reload(force) {
+                    Ok(true) => tracing::info!(cert = ?tls.files.cert, "tls certificate reloaded"),
+                    Ok(false) => {}
+                    Err(e) => tracing::error!(error = ?e, "failed to reload tls certificate"),
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Connections of `listener` after the handshake, failed handshakes are dropped
+    pub fn incoming(self: &Arc<Self>, listener: TcpListener) -> TlsIncoming {
+        let (tx, rx) = mpsc::channel(TLS_ACCEPT_BACKLOG);
+        let tls = self.clone();
+
+        tokio::spawn(async move {
+            loop {
+                let (stream, remote_addr) = tokio::select! {
+                    accepted = listener.accept() => match accepted {
+                        Ok(accepted) => accepted,
+                        Err(e) => {
+                            tracing::warn!(error = ?e, "failed to accept connection");
+                            continue;
+                        }
+                    },
+                    _ = tx.closed() => break,
+                };
+                let _ = stream.set_nodelay(true);
+
+                let acceptor = tls.acceptor();
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    match tokio::time::timeout(TLS_HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await
+                    {
+                        Ok(Ok(stream)) => {
+                            let _ = tx.send(Ok(TlsConnection::new(stream))).await;
+                        }
+                        Ok(Err(e)) => {
+                            tracing::debug!(?remote_addr, error = ?e, "tls handshake failed")
+                        }
+                        Err(_) => tracing::debug!(?remote_addr, "tls handshake timed out"),
+                    }
+                });
+            }
+        });
+
+        ReceiverStream::new(rx)
+    }
+}
+
+pub type TlsIncoming = ReceiverStream<io::Result<TlsConnection>>;
+
+/// Peer of a TLS connection, available in request extensions
+#[derive(Debug, Clone)]
+pub struct TlsConnectInfo {
+    pub remote_addr: Option<SocketAddr>,
+    /// subject CN of the client certificate
+    pub client_cn: Option<String>,
+}
+
+pub struct TlsConnection {
+    stream: TlsStream<TcpStream>,
+    info: TlsConnectInfo,
+}
+
+impl TlsConnection {
+    fn new(stream: TlsStream<TcpStream>) -> Self {
+        let (tcp, session) = stream.get_ref();
+        let info = TlsConnectInfo {
+            remote_addr: tcp.peer_addr().ok(),
+            client_cn: session
+                .peer_certificates()
+                .and_then(|certs| certs.first())
+                .and_then(common_name),
+        };
+
+        Self { stream, info }
+    }
+}
+
+impl Connected for TlsConnection {
+    type ConnectInfo = TlsConnectInfo;
+
+    fn connect_info(&self) -> Self::ConnectInfo {
+        self.info.clone()
+    }
+}
+
+impl AsyncRead for TlsConnection {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for TlsConnection {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+    use rustls::pki_types::ServerName;
+    use rustls::ClientConfig;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_rustls::TlsConnector;
+
+    fn fixture(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/tls")
+            .join(name)
+    }
+
+    fn files(mtls: bool) -> TlsFiles {
+        TlsFiles {
+            cert: fixture("server.pem"),
+            key: fixture("server.key"),
+            client_ca: mtls.then(|| fixture("ca.pem")),
+        }
+    }
+
+    fn connector(client: Option<&str>) -> TlsConnector {
+        let mut roots = RootCertStore::empty();
+        roots
+            .add(read_certs(&fixture("ca.pem")).unwrap().remove(0))
+            .unwrap();
+        let builder = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots);
+
+        let config = match client {
+            Some(name) => builder
+                .with_client_auth_cert(
+                    read_certs(&fixture(&format!("{}.pem", name))).unwrap(),
+                    read_key(&fixture(&format!("{}.key", name))).unwrap(),
+                )
+                .unwrap(),
+            None => builder.with_no_client_auth(),
+        };
+
+        TlsConnector::from(Arc::new(config))
+    }
+
+    async fn serve(tls: &Arc<ReloadableTls>) -> (SocketAddr, TlsIncoming) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        (addr, tls.incoming(listener))
+    }
+
+    /// Handshake with the server, then a ping so that rejected clients notice it
+    async fn connect(addr: SocketAddr, client: Option<&str>) -> io::Result<()> {
+        let tcp = TcpStream::connect(addr).await?;
+        let mut stream = connector(client)
+            .connect(ServerName::try_from("localhost").unwrap(), tcp)
+            .await?;
+        stream.write_all(b"ping").await?;
+        let mut buf = [0; 4];
+        stream.read_exact(&mut buf).await?;
+
+        Ok(())
+    }
+
+    async fn pong(incoming: &mut TlsIncoming) -> TlsConnectInfo {
+        let mut connection = incoming.next().await.unwrap().unwrap();
+        let mut buf = [0; 4];
+        connection.read_exact(&mut buf).await.unwrap();
+        connection.write_all(b"pong").await.unwrap();
+
+        connection.connect_info()
+    }
+
+    #[test]
+    fn common_name_of_subject() {
+        let cert = read_certs(&fixture("client.pem")).unwrap().remove(0);
+
+        assert_eq!(common_name(&cert).as_deref(), Some("auditor"));
+    }
+
+    #[tokio::test]
+    async fn plain_tls_accepts_anonymous_clients() {
+        let tls = ReloadableTls::new(files(false)).unwrap();
+        let (addr, mut incoming) = serve(&tls).await;
+
+        let (client, info) = tokio::join!(connect(addr, None), pong(&mut incoming));
+
+        client.unwrap();
+        assert_eq!(info.client_cn, None);
+        assert!(info.remote_addr.is_some());
+    }
+
+    #[tokio::test]
+    async fn mtls_attaches_client_cn() {
+        let tls = ReloadableTls::new(files(true)).unwrap();
+        let (addr, mut incoming) = serve(&tls).await;
+
+        let (client, info) = tokio::join!(connect(addr, Some("client")), pong(&mut incoming));
+
+        client.unwrap();
+        assert_eq!(info.client_cn.as_deref(), Some("auditor"));
+    }
+
+    #[tokio::test]
+    async fn mtls_rejects_unknown_clients() {
+        let tls = ReloadableTls::new(files(true)).unwrap();
+        let (addr, mut incoming) = serve(&tls).await;
+
+        assert!(connect(addr, None).await.is_err());
+        assert!(connect(addr, Some("other-client")).await.is_err());
+
+        // the listener survives failed handshakes
+        let (client, info) = tokio::join!(connect(addr, Some("client")), pong(&mut incoming));
+        client.unwrap();
+        assert_eq!(info.client_cn.as_deref(), Some("auditor"));
+    }
+
+    /// Replaces a file and moves its mtime forward, copies within a tick keep the old one
+    fn replace(from: &Path, to: &Path) {
+        std::fs::copy(from, to).unwrap();
+        let file = std::fs::File::options().write(true).open(to).unwrap();
+        let modified = file.metadata().unwrap().modified().unwrap();
+        file.set_modified(modified + Duration::from_secs(1))
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn reload_follows_files() {
+        let dir = std::env::temp_dir().join(format!("tls-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir(&dir).unwrap();
+        let files = TlsFiles {
+            cert: dir.join("server.pem"),
+            key: dir.join("server.key"),
+            client_ca: None,
+        };
+        std::fs::copy(fixture("server.pem"), &files.cert).unwrap();
+        std::fs::copy(fixture("server.key"), &files.key).unwrap();
+
+        let tls = ReloadableTls::new(files.clone()).unwrap();
+        let (addr, mut incoming) = serve(&tls).await;
+        assert!(!tls.reload(false).unwrap());
+
+        // a certificate clients don't trust is served after the reload
+        replace(&fixture("other-client.pem"), &files.cert);
+        replace(&fixture("other-client.key"), &files.key);
+        assert!(tls.reload(false).unwrap());
+        assert!(connect(addr, None).await.is_err());
+
+        replace(&fixture("server.pem"), &files.cert);
+        replace(&fixture("server.key"), &files.key);
+        assert!(tls.reload(false).unwrap());
+        let (client, _) = tokio::join!(connect(addr, None), pong(&mut incoming));
+        client.unwrap();
+
+        // broken files keep the current config
+        std::fs::write(&files.key, "broken").unwrap();
+        assert!(tls.reload(true).is_err());
+        let (client, _) = tokio::join!(connect(addr, None), pong(&mut incoming));
+        client.unwrap();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
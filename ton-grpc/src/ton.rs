@@ -1,12 +1,35 @@
+use crate::ton::dns_resolve_request::Category as DnsCategory;
+use crate::ton::dns_resolve_response::Record as DnsRecord;
 use crate::ton::get_account_state_response::AccountState;
+use crate::ton::get_out_msg_queue_sizes_response::OutMsgQueueSize;
+use crate::ton::masterchain_block_event::Event;
 use crate::ton::message::MsgData;
+use crate::{recent, webhook};
 use anyhow::anyhow;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+use ton_contract::config;
+use ton_contract::decode::{self, decode_jetton_message, decode_nft_message};
+use ton_contract::dns;
+use ton_contract::elector;
+use ton_contract::multisig;
+use toner::tlb::Cell;
+use toner::ton::MsgAddress;
+use tonlibjson_client::account_cell;
 use tonlibjson_client::address::AccountAddressData;
 use tonlibjson_client::block;
 use tonlibjson_client::block::{
     MsgBoxedData, MsgDataDecryptedText, MsgDataEncryptedText, MsgDataRaw, MsgDataText,
 };
+use tonlibjson_client::boc::{self, parse_base64_boc, to_base64_boc};
+use tonlibjson_client::read_context::ReadContext;
+use tonlibjson_client::reorg;
+use tonlibjson_client::ton::AccountStatus;
+use tonlibjson_client::transaction;
+use tonlibjson_client::transport;
 
 tonic::include_proto!("ton");
 
@@ -24,6 +47,15 @@ impl From<block::TonBlockIdExt> for BlockIdExt {
     }
 }
 
+impl From<ReadContext> for AsOf {
+    fn from(value: ReadContext) -> Self {
+        Self {
+            masterchain: Some(value.masterchain.into()),
+            shard: value.shard.map(Into::into),
+        }
+    }
+}
+
 impl From<BlockIdExt> for block::TonBlockIdExt {
     fn from(value: BlockIdExt) -> Self {
         Self {
@@ -36,18 +68,99 @@ impl From<BlockIdExt> for block::TonBlockIdExt {
     }
 }
 
-impl From<(i32, block::BlocksShortTxId)> for TransactionId {
-    fn from((chain_id, value): (i32, block::BlocksShortTxId)) -> Self {
-        let address = value.clone().into_internal_string(chain_id);
+impl From<&GetShardsRequest> for BlockId {
+    fn from(value: &GetShardsRequest) -> Self {
+        Self {
+            workchain: value.workchain,
+            shard: value.shard,
+            seqno: value.seqno,
+            root_hash: value.root_hash.clone(),
+            file_hash: value.file_hash.clone(),
+        }
+    }
+}
+
+impl From<block::BlocksBlockLinkBack> for BlockLinkBack {
+    fn from(value: block::BlocksBlockLinkBack) -> Self {
+        Self {
+            to_key_block: value.to_key_block,
+            from: Some(value.from.into()),
+            to: Some(value.to.into()),
+            dest_proof: value.dest_proof,
+            proof: value.proof,
+            state_proof: value.state_proof,
+        }
+    }
+}
+
+impl From<(block::TonBlockIdExt, block::BlocksShardBlockProof)> for BlockProof {
+    fn from((block, proof): (block::TonBlockIdExt, block::BlocksShardBlockProof)) -> Self {
+        Self {
+            block: Some(block.into()),
+            mc_links: proof.mc_proof.into_iter().map(Into::into).collect(),
+            mc_block: Some(proof.mc_id.into()),
+            shard_links: proof
+                .links
+                .into_iter()
+                .map(|link| ShardBlockLink {
+                    id: Some(link.id.into()),
+                    proof: link.proof,
+                })
+                .collect(),
+        }
+    }
+}
 
+impl From<block::BlocksShortTxId> for TransactionId {
+    fn from(value: block::BlocksShortTxId) -> Self {
         Self {
-            account_address: address,
+            account_address: value.address().to_string(),
             lt: value.lt,
             hash: value.hash,
         }
     }
 }
 
+/// Cursor of a listing of block transactions
+impl TryFrom<TransactionId> for block::BlocksAccountTransactionId {
+    type Error = anyhow::Error;
+
+    fn try_from(value: TransactionId) -> Result<Self, Self::Error> {
+        Ok(Self {
+            account: AccountAddressData::from_str(&value.account_address)?
+                .into_shard_context()
+                .to_string(),
+            lt: value.lt,
+        })
+    }
+}
+
+impl From<BlocksHeader> for block::BlocksHeader {
+    fn from(value: BlocksHeader) -> Self {
+        Self {
+            id: value.id.unwrap_or_default().into(),
+            global_id: value.global_id,
+            version: value.version,
+            flags: value.flags,
+            after_merge: value.after_merge,
+            after_split: value.after_split,
+            before_split: value.before_split,
+            want_merge: value.want_merge,
+            want_split: value.want_split,
+            validator_list_hash_short: value.validator_list_hash_short,
+            catchain_seqno: value.catchain_seqno,
+            min_ref_mc_seqno: value.min_ref_mc_seqno,
+            is_key_block: value.is_key_block,
+            prev_key_block_seqno: value.prev_key_block_seqno,
+            start_lt: value.start_lt,
+            end_lt: value.end_lt,
+            gen_utime: value.gen_utime,
+            vert_seqno: value.vert_seqno,
+            prev_blocks: value.prev_blocks.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
 impl From<block::BlocksHeader> for BlocksHeader {
     fn from(value: block::BlocksHeader) -> Self {
         Self {
@@ -74,6 +187,41 @@ impl From<block::BlocksHeader> for BlocksHeader {
     }
 }
 
+impl From<reorg::BlockEvent> for MasterchainBlockEvent {
+    fn from(value: reorg::BlockEvent) -> Self {
+        let event = match value {
+            reorg::BlockEvent::Applied(header) => Event::Applied(header.into()),
+            reorg::BlockEvent::Reverted(id) => Event::Reverted(id.into()),
+        };
+
+        Self { event: Some(event) }
+    }
+}
+
+impl
+    From<(
+        &AccountAddressData,
+        tonlibjson_client::ton::AccountStateDelta,
+    )> for AccountStateDelta
+{
+    fn from(
+        (account_address, delta): (
+            &AccountAddressData,
+            tonlibjson_client::ton::AccountStateDelta,
+        ),
+    ) -> Self {
+        Self {
+            account_address: account_address.to_raw_string(),
+            block_id: Some(delta.block_id.into()),
+            balance: delta.balance.unwrap_or_default(),
+            status: account_state_delta::Status::from(delta.status).into(),
+            last_transaction_id: delta
+                .last_transaction_id
+                .map(|tx| (account_address, tx).into()),
+        }
+    }
+}
+
 impl From<(&AccountAddressData, block::InternalTransactionId)> for TransactionId {
     fn from((account_address, tx_id): (&AccountAddressData, block::InternalTransactionId)) -> Self {
         Self {
@@ -102,12 +250,23 @@ impl From<PartialTransactionId> for block::InternalTransactionId {
     }
 }
 
+impl From<AccountStatus> for account_state_delta::Status {
+    fn from(value: AccountStatus) -> Self {
+        match value {
+            AccountStatus::Uninit => account_state_delta::Status::Uninit,
+            AccountStatus::Active => account_state_delta::Status::Active,
+            AccountStatus::Frozen => account_state_delta::Status::Frozen,
+        }
+    }
+}
+
 impl From<block::RawFullAccountState> for AccountState {
     fn from(value: block::RawFullAccountState) -> Self {
         if !value.code.is_empty() {
             AccountState::Active(ActiveAccountState {
                 code: value.code,
                 data: value.data,
+                special: None,
             })
         } else if !value.frozen_hash.is_empty() {
             AccountState::Frozen(FrozenAccountState {
@@ -119,6 +278,243 @@ impl From<block::RawFullAccountState> for AccountState {
     }
 }
 
+impl From<transport::AccountStateProofs> for AccountStateProofs {
+    fn from(value: transport::AccountStateProofs) -> Self {
+        Self {
+            block_id: Some(value.block_id.into()),
+            shard_block_id: Some(value.shard_block_id.into()),
+            shard_proof: value.shard_proof,
+            proof: value.proof,
+        }
+    }
+}
+
+impl From<block::BlocksOutMsgQueueSizes> for GetOutMsgQueueSizesResponse {
+    fn from(value: block::BlocksOutMsgQueueSizes) -> Self {
+        Self {
+            shards: value
+                .shards
+                .into_iter()
+                .map(|s| OutMsgQueueSize {
+                    block_id: Some(s.id.into()),
+                    size: s.size,
+                })
+                .collect(),
+            ext_msg_queue_size_limit: value.ext_msg_queue_size_limit,
+        }
+    }
+}
+
+impl From<DnsCategory> for dns::DnsCategory {
+    fn from(value: DnsCategory) -> Self {
+        match value {
+            DnsCategory::Wallet => dns::DnsCategory::Wallet,
+            DnsCategory::Site => dns::DnsCategory::Site,
+            DnsCategory::Storage => dns::DnsCategory::Storage,
+            DnsCategory::NextResolver => dns::DnsCategory::NextResolver,
+        }
+    }
+}
+
+impl From<dns::DnsRecord> for DnsRecord {
+    fn from(value: dns::DnsRecord) -> Self {
+        match value {
+            dns::DnsRecord::Wallet(address) => Self::Wallet(address.to_string()),
+            dns::DnsRecord::NextResolver(address) => Self::NextResolver(address.to_string()),
+            dns::DnsRecord::Site(adnl) => Self::Site(hex::encode(adnl)),
+            dns::DnsRecord::Storage(bag_id) => Self::Storage(hex::encode(bag_id)),
+        }
+    }
+}
+
+impl From<elector::ElectionData> for GetElectionDataResponse {
+    fn from(value: elector::ElectionData) -> Self {
+        Self {
+            election_id: value.election_id,
+            elect_close: value.elect_close,
+            min_stake: value.min_stake.to_string(),
+            total_stake: value.total_stake.to_string(),
+            participants: value
+                .participants
+                .into_iter()
+                .map(|p| get_election_data_response::Participant {
+                    pubkey: hex::encode(p.pubkey),
+                    stake: p.stake.to_string(),
+                    max_factor: p.max_factor,
+                    wallet_address: p.wallet.to_string(),
+                    adnl_addr: hex::encode(p.adnl_addr),
+                })
+                .collect(),
+            failed: value.failed,
+            finished: value.finished,
+        }
+    }
+}
+
+impl From<Vec<boc::CellNode>> for ParseBocResponse {
+    fn from(value: Vec<boc::CellNode>) -> Self {
+        Self {
+            cells: value
+                .into_iter()
+                .map(|cell| parse_boc_response::Cell {
+                    hash: STANDARD.encode(cell.hash),
+                    depth: cell.depth.into(),
+                    bits: cell.bits as u32,
+                    data: hex::encode(cell.data),
+                    refs: cell.refs.into_iter().map(|i| i as u32).collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+fn base64_boc(cell: Cell) -> String {
+    to_base64_boc(cell).unwrap_or_default()
+}
+
+impl From<Option<account_cell::AccountState>> for ParsedAccount {
+    fn from(value: Option<account_cell::AccountState>) -> Self {
+        let Some(state) = value else {
+            return Self::default();
+        };
+
+        let status = if state.active {
+            account_state_delta::Status::Active
+        } else if state.frozen_hash.is_some() {
+            account_state_delta::Status::Frozen
+        } else {
+            account_state_delta::Status::Uninit
+        };
+
+        Self {
+            exists: true,
+            account_address: state.address.to_string(),
+            status: status.into(),
+            storage: Some(parsed_account::StorageStat {
+                cells: state.storage.cells as i64,
+                bits: state.storage.bits as i64,
+                public_cells: state.storage.public_cells.map(|cells| cells as i64),
+                last_paid: state.storage.last_paid.into(),
+                due_payment: state.storage.due_payment.map(|due| due.to_string()),
+            }),
+            last_transaction_lt: state.last_trans_lt as i64,
+            balance: state.balance.grams.to_string(),
+            extra_currencies: state
+                .balance
+                .extra_currencies
+                .into_iter()
+                .map(|(id, amount)| (id as i32, amount.to_string()))
+                .collect(),
+            code: state.code.map(base64_boc).unwrap_or_default(),
+            data: state.data.map(base64_boc).unwrap_or_default(),
+            frozen_hash: state
+                .frozen_hash
+                .map(|hash| STANDARD.encode(hash))
+                .unwrap_or_default(),
+            special: state.special.map(Into::into),
+        }
+    }
+}
+
+fn addresses(addresses: Vec<MsgAddress>) -> Vec<String> {
+    addresses.iter().map(ToString::to_string).collect()
+}
+
+impl From<multisig::MultisigData> for MultisigInfo {
+    fn from(value: multisig::MultisigData) -> Self {
+        Self {
+            account_address: String::new(),
+            next_order_seqno: value.next_order_seqno.map(|seqno| seqno.to_string()),
+            threshold: value.threshold,
+            signers: addresses(value.signers),
+            proposers: addresses(value.proposers),
+            raw_stack: None,
+        }
+    }
+}
+
+impl From<multisig::MultisigAction> for multisig_order::Action {
+    fn from(value: multisig::MultisigAction) -> Self {
+        let action = match value {
+            multisig::MultisigAction::SendMessage {
+                mode,
+                destination,
+                value,
+                bounce,
+                body,
+            } => {
+                let (jetton, nft, decode_error) = decode_body(&body);
+
+                multisig_order::action::Action::SendMessage(multisig_order::SendMessage {
+                    mode: mode.into(),
+                    destination: destination.to_string(),
+                    value: value.to_string(),
+                    bounce,
+                    body: base64_boc(body),
+                    jetton,
+                    nft,
+                    decode_error,
+                })
+            }
+            multisig::MultisigAction::UpdateParams {
+                threshold,
+                signers,
+                proposers,
+            } => multisig_order::action::Action::UpdateParams(multisig_order::UpdateParams {
+                threshold,
+                signers: addresses(signers),
+                proposers: addresses(proposers),
+            }),
+            multisig::MultisigAction::Unknown(cell) => {
+                multisig_order::action::Action::Unknown(base64_boc(cell))
+            }
+        };
+
+        Self {
+            action: Some(action),
+        }
+    }
+}
+
+impl From<multisig::MultisigOrder> for MultisigOrder {
+    fn from(value: multisig::MultisigOrder) -> Self {
+        Self {
+            order_address: String::new(),
+            approved_by: value.approved_by(),
+            multisig_address: value.multisig.to_string(),
+            order_seqno: value.order_seqno.to_string(),
+            threshold: value.threshold,
+            executed: value.executed,
+            signers: addresses(value.signers),
+            approvals_num: value.approvals_num,
+            expiration_date: value.expiration_date,
+            actions: value.actions.into_iter().map(Into::into).collect(),
+            raw_stack: None,
+        }
+    }
+}
+
+impl From<config::ConfigProposal> for ConfigProposal {
+    fn from(value: config::ConfigProposal) -> Self {
+        let boc = |cell: Arc<Cell>| to_base64_boc(cell).ok();
+
+        Self {
+            hash: hex::encode(value.hash),
+            expires: value.expires,
+            critical: value.critical,
+            param_id: value.param_id,
+            param_value: value.param_value.and_then(boc),
+            param_hash: value.param_hash.map(hex::encode),
+            vset_id: hex::encode(value.vset_id),
+            voters: value.voters.into_iter().map(Into::into).collect(),
+            weight_remaining: value.weight_remaining,
+            rounds_remaining: value.rounds_remaining.into(),
+            losses: value.losses.into(),
+            wins: value.wins.into(),
+        }
+    }
+}
+
 impl From<block::TvmCell> for TvmCell {
     fn from(value: block::TvmCell) -> Self {
         Self { bytes: value.bytes }
@@ -142,9 +538,13 @@ impl From<MsgBoxedData> for MsgData {
     }
 }
 
-impl From<block::RawMessage> for Message {
-    fn from(value: block::RawMessage) -> Self {
-        Self {
+impl TryFrom<block::RawMessage> for Message {
+    type Error = anyhow::Error;
+
+    fn try_from(value: block::RawMessage) -> Result<Self, Self::Error> {
+        let extra_currencies = value.extra_currencies()?;
+
+        Ok(Self {
             source: value.source.account_address.map(|s| s.to_string()),
             destination: value.destination.account_address.map(|s| s.to_string()),
             value: value.value,
@@ -152,23 +552,288 @@ impl From<block::RawMessage> for Message {
             ihr_fee: value.ihr_fee,
             created_lt: value.created_lt,
             body_hash: value.body_hash.clone(),
+            extra_currencies,
             msg_data: Some(value.msg_data.into()),
+            jetton: None,
+            nft: None,
+            decode_error: None,
+        })
+    }
+}
+
+impl Message {
+    fn decode_operation(&mut self) {
+        let Some(MsgData::Raw(raw)) = &self.msg_data else {
+            return;
+        };
+        let Some(body) = body_cell(&raw.body) else {
+            return;
+        };
+
+        (self.jetton, self.nft, self.decode_error) = decode_body(&body);
+    }
+}
+
+/// Jetton and NFT operations of a message body, and the error of a malformed one
+fn decode_body(body: &Cell) -> (Option<JettonMessage>, Option<NftMessage>, Option<String>) {
+    let jetton = decode_jetton_message(body).map(Into::into);
+    match decode_nft_message(body) {
+        Some(Ok(nft)) => (jetton, Some(nft.into()), None),
+        Some(Err(e)) => (jetton, None, Some(e.to_string())),
+        None => (jetton, None, None),
+    }
+}
+
+impl Transaction {
+    /// Fills `jetton` and `nft` of messages whose raw body is a known operation,
+    /// and `description` from the transaction BoC
+    pub fn decode_messages(mut self) -> Self {
+        self.in_msg
+            .iter_mut()
+            .chain(self.out_msgs.iter_mut())
+            .for_each(Message::decode_operation);
+        self.description = parse_base64_boc(&self.data)
+            .and_then(|root| transaction::parse_description(&root))
+            .ok()
+            .flatten()
+            .map(Into::into);
+
+        self
+    }
+}
+
+impl From<transaction::TransactionDescription> for TransactionDescription {
+    fn from(value: transaction::TransactionDescription) -> Self {
+        Self {
+            compute: value.compute.map(Into::into),
+            action: value
+                .action
+                .map(|action| transaction_description::ActionPhase {
+                    success: action.success,
+                    result_code: action.result_code,
+                    total_actions: action.total_actions.into(),
+                }),
+            aborted: value.aborted,
+            bounce: value.bounce.map(|bounce| {
+                match bounce {
+                    transaction::BouncePhase::NegativeFunds => {
+                        transaction_description::BouncePhase::NegativeFunds
+                    }
+                    transaction::BouncePhase::NoFunds => {
+                        transaction_description::BouncePhase::NoFunds
+                    }
+                    transaction::BouncePhase::Ok => transaction_description::BouncePhase::Ok,
+                }
+                .into()
+            }),
+            destroyed: value.destroyed,
         }
     }
 }
 
-impl From<(&AccountAddressData, block::RawTransaction)> for Transaction {
-    fn from((address, value): (&AccountAddressData, block::RawTransaction)) -> Self {
+impl From<transaction::ComputePhase> for transaction_description::ComputePhase {
+    fn from(value: transaction::ComputePhase) -> Self {
+        use transaction_description::compute_phase::SkipReason;
+
+        match value {
+            transaction::ComputePhase::Skipped(reason) => Self {
+                skipped: Some(
+                    match reason {
+                        transaction::ComputeSkipReason::NoState => SkipReason::NoState,
+                        transaction::ComputeSkipReason::BadState => SkipReason::BadState,
+                        transaction::ComputeSkipReason::NoGas => SkipReason::NoGas,
+                        transaction::ComputeSkipReason::Suspended => SkipReason::Suspended,
+                    }
+                    .into(),
+                ),
+                ..Default::default()
+            },
+            transaction::ComputePhase::Vm {
+                success,
+                exit_code,
+                gas_used,
+                vm_steps,
+                ..
+            } => Self {
+                skipped: None,
+                success,
+                exit_code,
+                gas_used,
+                vm_steps,
+            },
+        }
+    }
+}
+
+fn body_cell(body: &str) -> Option<Arc<Cell>> {
+    parse_base64_boc(body).ok()
+}
+
+fn non_null(address: MsgAddress) -> Option<String> {
+    (address != MsgAddress::NULL).then(|| address.to_string())
+}
+
+impl From<decode::JettonMessage> for JettonMessage {
+    fn from(value: decode::JettonMessage) -> Self {
+        match value {
+            decode::JettonMessage::Transfer {
+                query_id,
+                amount,
+                destination,
+                response_destination,
+                forward_ton_amount,
+                comment,
+            } => Self {
+                operation: jetton_message::Operation::Transfer.into(),
+                query_id,
+                amount: amount.to_string(),
+                destination: non_null(destination),
+                source: None,
+                response_destination: non_null(response_destination),
+                forward_ton_amount: forward_ton_amount.to_string(),
+                comment,
+            },
+            decode::JettonMessage::InternalTransfer {
+                query_id,
+                amount,
+                from,
+                response_destination,
+                forward_ton_amount,
+                comment,
+            } => Self {
+                operation: jetton_message::Operation::InternalTransfer.into(),
+                query_id,
+                amount: amount.to_string(),
+                destination: None,
+                source: non_null(from),
+                response_destination: non_null(response_destination),
+                forward_ton_amount: forward_ton_amount.to_string(),
+                comment,
+            },
+            decode::JettonMessage::TransferNotification {
+                query_id,
+                amount,
+                sender,
+                comment,
+            } => Self {
+                operation: jetton_message::Operation::TransferNotification.into(),
+                query_id,
+                amount: amount.to_string(),
+                destination: None,
+                source: non_null(sender),
+                response_destination: None,
+                forward_ton_amount: "0".to_owned(),
+                comment,
+            },
+            decode::JettonMessage::Burn {
+                query_id,
+                amount,
+                response_destination,
+            } => Self {
+                operation: jetton_message::Operation::Burn.into(),
+                query_id,
+                amount: amount.to_string(),
+                destination: None,
+                source: None,
+                response_destination: non_null(response_destination),
+                forward_ton_amount: "0".to_owned(),
+                comment: None,
+            },
+        }
+    }
+}
+
+impl From<recent::RecentMessage> for RecentMessage {
+    fn from(value: recent::RecentMessage) -> Self {
         Self {
+            hash: value.hash,
+            destination: value.destination,
+            sent_at: value.sent_at,
+            size: value.size as u32,
+            sender: value.sender,
+            confirmation: value
+                .confirmation
+                .map(|confirmation| recent_message::Confirmation {
+                    lt: confirmation.lt,
+                    transaction_hash: confirmation.transaction_hash,
+                    utime: confirmation.utime,
+                }),
+        }
+    }
+}
+
+impl From<decode::NftMessage> for NftMessage {
+    fn from(value: decode::NftMessage) -> Self {
+        let boc = |payload: Cell| to_base64_boc(payload).ok();
+
+        match value {
+            decode::NftMessage::Transfer {
+                query_id,
+                new_owner,
+                response_destination,
+                forward_amount,
+                forward_payload,
+                comment,
+            } => Self {
+                operation: nft_message::Operation::Transfer.into(),
+                query_id,
+                new_owner: non_null(new_owner),
+                prev_owner: None,
+                response_destination: non_null(response_destination),
+                forward_amount: forward_amount.to_string(),
+                forward_payload: forward_payload.and_then(boc),
+                comment,
+            },
+            decode::NftMessage::OwnershipAssigned {
+                query_id,
+                prev_owner,
+                forward_payload,
+                comment,
+            } => Self {
+                operation: nft_message::Operation::OwnershipAssigned.into(),
+                query_id,
+                new_owner: None,
+                prev_owner: non_null(prev_owner),
+                response_destination: None,
+                forward_amount: "0".to_owned(),
+                forward_payload: forward_payload.and_then(boc),
+                comment,
+            },
+            decode::NftMessage::GetStaticData { query_id } => Self {
+                operation: nft_message::Operation::GetStaticData.into(),
+                query_id,
+                new_owner: None,
+                prev_owner: None,
+                response_destination: None,
+                forward_amount: "0".to_owned(),
+                forward_payload: None,
+                comment: None,
+            },
+        }
+    }
+}
+
+impl TryFrom<(&AccountAddressData, block::RawTransaction)> for Transaction {
+    type Error = anyhow::Error;
+
+    fn try_from(
+        (address, value): (&AccountAddressData, block::RawTransaction),
+    ) -> Result<Self, Self::Error> {
+        Ok(Self {
             id: Some((address, value.transaction_id).into()),
             utime: value.utime,
             data: value.data.clone(),
             fee: value.fee,
             storage_fee: value.storage_fee,
             other_fee: value.other_fee,
-            in_msg: value.in_msg.map(|m| m.into()),
-            out_msgs: value.out_msgs.into_iter().map(Into::into).collect(),
-        }
+            in_msg: value.in_msg.map(Message::try_from).transpose()?,
+            out_msgs: value
+                .out_msgs
+                .into_iter()
+                .map(Message::try_from)
+                .collect::<anyhow::Result<_>>()?,
+            description: None,
+        })
     }
 }
 
@@ -191,8 +856,181 @@ impl TryFrom<(i32, block::RawTransaction)> for Transaction {
             fee: value.fee,
             storage_fee: value.storage_fee,
             other_fee: value.other_fee,
-            in_msg: value.in_msg.map(|m| m.into()),
-            out_msgs: value.out_msgs.into_iter().map(Into::into).collect(),
+            in_msg: value.in_msg.map(Message::try_from).transpose()?,
+            out_msgs: value
+                .out_msgs
+                .into_iter()
+                .map(Message::try_from)
+                .collect::<anyhow::Result<_>>()?,
+            description: None,
         })
     }
 }
+
+impl From<webhook::Webhook> for Webhook {
+    fn from(value: webhook::Webhook) -> Self {
+        Self {
+            last_transaction_id: value.last_transaction_id.map(|tx_id| TransactionId {
+                account_address: value.account_address.clone(),
+                lt: tx_id.lt,
+                hash: tx_id.hash,
+            }),
+            id: value.id,
+            account_address: value.account_address,
+            url: value.url.to_string(),
+        }
+    }
+}
+
+impl From<webhook::DeadLetter> for DeadLetter {
+    fn from(value: webhook::DeadLetter) -> Self {
+        Self {
+            transaction_id: Some(TransactionId {
+                account_address: value.account_address.clone(),
+                lt: value.transaction_id.lt,
+                hash: value.transaction_id.hash,
+            }),
+            webhook_id: value.webhook_id,
+            account_address: value.account_address,
+            attempts: value.attempts,
+            error: value.error,
+            failed_at: value
+                .failed_at
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs() as i64),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_bigint::BigUint;
+    use toner::tlb::bits::r#as::VarInt;
+    use toner::tlb::bits::ser::BitWriterExt;
+
+    fn raw_message(body: Cell) -> Message {
+        Message {
+            msg_data: Some(MsgData::Raw(MessageDataRaw {
+                body: to_base64_boc(body).unwrap(),
+                init_state: String::new(),
+            })),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn parsed_boc_cells() {
+        let mut leaf = Cell::builder();
+        leaf.pack(0xabu8).unwrap().pack(true).unwrap();
+        let leaf = Arc::new(leaf.into_cell());
+        let root = Cell {
+            data: Default::default(),
+            references: vec![leaf.clone(), leaf],
+        };
+
+        let response: ParseBocResponse = boc::cell_tree(&root).into();
+
+        assert_eq!(response.cells.len(), 2);
+        assert_eq!(response.cells[0].refs, vec![1, 1]);
+        assert_eq!(response.cells[0].depth, 1);
+        assert_eq!(response.cells[1].bits, 9);
+        assert_eq!(response.cells[1].data, "ab80");
+        assert_eq!(response.cells[0].hash, STANDARD.encode(root.hash()),);
+    }
+
+    #[test]
+    fn decode_messages_fills_operations() {
+        let owner: MsgAddress = "EQCjk1hh952vWaE9bRguFkAhDAL5jj3xj9p0uPWrFBq_GEMS"
+            .parse()
+            .unwrap();
+        let mut burn = Cell::builder();
+        burn.pack(decode::JETTON_BURN_OP)
+            .unwrap()
+            .pack(9_u64)
+            .unwrap()
+            .pack_as::<_, VarInt<4>>(BigUint::from(10_u8))
+            .unwrap()
+            .pack(owner)
+            .unwrap()
+            .pack(false)
+            .unwrap();
+        let mut comment = Cell::builder();
+        comment.pack(0_u32).unwrap().pack("hello").unwrap();
+        let mut truncated_nft = Cell::builder();
+        truncated_nft
+            .pack(decode::NFT_TRANSFER_OP)
+            .unwrap()
+            .pack(1_u64)
+            .unwrap();
+
+        let tx = Transaction {
+            in_msg: Some(raw_message(burn.into_cell())),
+            out_msgs: vec![
+                raw_message(comment.into_cell()),
+                raw_message(truncated_nft.into_cell()),
+            ],
+            ..Default::default()
+        }
+        .decode_messages();
+
+        assert_eq!(
+            tx.in_msg.unwrap().jetton,
+            Some(JettonMessage {
+                operation: jetton_message::Operation::Burn.into(),
+                query_id: 9,
+                amount: "10".to_owned(),
+                destination: None,
+                source: None,
+                response_destination: Some(owner.to_string()),
+                forward_ton_amount: "0".to_owned(),
+                comment: None,
+            })
+        );
+        assert_eq!(tx.out_msgs[0].jetton, None);
+        assert_eq!(tx.out_msgs[0].decode_error, None);
+        assert_eq!(tx.out_msgs[1].nft, None);
+        assert_eq!(
+            tx.out_msgs[1].decode_error.as_deref(),
+            Some("malformed nft transfer body")
+        );
+    }
+
+    fn raw_message_json(extra_currencies: serde_json::Value) -> block::RawMessage {
+        serde_json::from_value(serde_json::json!({
+            "@type": "raw.message",
+            "source": {"@type": "accountAddress", "account_address": "kQCSES0TZYqcVkgoguhIb8iMEo4cvaEwmIrU5qbQgnN8fo2A"},
+            "destination": {"@type": "accountAddress", "account_address": "0QAdFQdB4UJH1XyIaOy_5bkMZCT7xdJTMQYeVeOFl3G2t8Lb"},
+            "value": "1000000000",
+            "extra_currencies": extra_currencies,
+            "fwd_fee": "266669",
+            "ihr_fee": "0",
+            "created_lt": "30021934000002",
+            "body_hash": "lqKW0iTyhcZ77pPDD4owkVfw2qNdxbh+QQt4YwoJz8c=",
+            "msg_data": {"@type": "msg.dataRaw", "body": "te6cckEBAQEAAgAAAEysuc0=", "init_state": ""}
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn message_extra_currencies() {
+        let message = Message::try_from(raw_message_json(serde_json::json!([
+            {"@type": "extraCurrency", "id": 100, "amount": "2500000"}
+        ])))
+        .unwrap();
+
+        assert_eq!(
+            message.extra_currencies,
+            std::collections::HashMap::from([(100, "2500000".to_owned())])
+        );
+    }
+
+    #[test]
+    fn message_malformed_extra_currencies() {
+        let message = Message::try_from(raw_message_json(serde_json::json!([
+            {"@type": "extraCurrency", "id": "usd", "amount": "2500000"}
+        ])));
+
+        assert!(message.is_err());
+    }
+}
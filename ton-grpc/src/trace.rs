@@ -0,0 +1,362 @@
+use crate::ton::message_trace::{node::State, Edge, Node};
+use crate::ton::{MessageTrace, Transaction};
+use futures::{stream, StreamExt};
+use std::str::FromStr;
+use tonic::Status;
+use tonlibjson_client::address::AccountAddressData;
+use tonlibjson_client::block::{InternalTransactionId, RawMessage, RawTransaction};
+use tonlibjson_client::ton::TonClient;
+
+/// Out messages followed per transaction, the rest mark the trace as truncated
+pub const TRACE_MAX_FAN_OUT: usize = 32;
+/// Nodes of a single trace, unresolved ones included
+pub const TRACE_MAX_NODES: usize = 256;
+/// Transactions of a destination account checked for the result of a message
+const TRACE_MAX_SCANNED: usize = 64;
+const TRACE_CONCURRENCY: usize = 8;
+
+#[tonic::async_trait]
+pub trait TraceSource: Send + Sync {
+    async fn transaction(
+        &self,
+        address: &str,
+        id: &InternalTransactionId,
+    ) -> anyhow::Result<Option<RawTransaction>>;
+
+    /// Transaction which processed the internal message, none if there is none yet
+    async fn result_transaction(&self, msg: &RawMessage) -> anyhow::Result<Option<RawTransaction>>;
+}
+
+#[tonic::async_trait]
+impl TraceSource for TonClient {
+    async fn transaction(
+        &self,
+        address: &str,
+        id: &InternalTransactionId,
+    ) -> anyhow::Result<Option<RawTransaction>> {
+        let txs = self.raw_get_transactions(address, id).await?;
+
+        Ok(txs
+            .transactions
+            .into_iter()
+            .find(|tx| &tx.transaction_id == id))
+    }
+
+    async fn result_transaction(&self, msg: &RawMessage) -> anyhow::Result<Option<RawTransaction>> {
+        self.locate_result_tx(msg, TRACE_MAX_SCANNED).await
+    }
+}
+
+/// Messages of `tx` which go to another account
+fn internal_out_msgs(tx: &RawTransaction) -> impl Iterator<Item = &RawMessage> {
+    tx.out_msgs
+        .iter()
+        .filter(|msg| msg.destination.account_address.is_some())
+}
+
+fn node(id: usize, depth: u32, address: &str, state: State) -> Node {
+    Node {
+        id: id as u32,
+        depth,
+        account_address: address.to_owned(),
+        state: state as i32,
+        transaction: None,
+        error: None,
+    }
+}
+
+fn transaction(address: &str, tx: RawTransaction) -> anyhow::Result<Transaction> {
+    let account = AccountAddressData::from_str(address)?;
+
+    Transaction::try_from((&account, tx))
+}
+
+/// Transactions caused by the root one and its descendants, breadth first and up to `max_depth`
+/// levels below the root. Children which can't be found become unresolved leaves.
+pub async fn message_trace(
+    source: &impl TraceSource,
+    address: &str,
+    root: InternalTransactionId,
+    max_depth: u32,
+) -> Result<MessageTrace, Status> {
+    let account = AccountAddressData::from_str(address)
+        .map_err(|e| Status::invalid_argument(e.to_string()))?;
+    let root_tx = source
+        .transaction(address, &root)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?
+        .ok_or_else(|| Status::not_found(format!("transaction {} not found", root.hash)))?;
+
+    let mut trace = MessageTrace::default();
+    let mut level = vec![(0, root_tx)];
+    let root_transaction = Transaction::try_from((&account, level[0].1.clone()))
+        .map_err(|e| Status::internal(e.to_string()))?;
+    trace.nodes.push(Node {
+        transaction: Some(root_transaction),
+        ..node(0, 0, address, State::Resolved)
+    });
+
+    for depth in 1..=max_depth + 1 {
+        let mut children = Vec::new();
+        for (parent, tx) in &level {
+            let msgs: Vec<_> = internal_out_msgs(tx).collect();
+            if msgs.len() > TRACE_MAX_FAN_OUT {
+                trace.truncated = true;
+            }
+
+            children.extend(
+                msgs.into_iter()
+                    .take(TRACE_MAX_FAN_OUT)
+                    .map(|msg| (*parent, msg.clone())),
+            );
+        }
+
+        let room = TRACE_MAX_NODES - trace.nodes.len();
+        if children.len() > room {
+            trace.truncated = true;
+            children.truncate(room);
+        }
+        if children.is_empty() {
+            break;
+        }
+
+        let results: Vec<_> = if depth > max_depth {
+            children.iter().map(|_| None).collect()
+        } else {
+            stream::iter(children.clone())
+                .map(|(_, msg)| async move { Some(source.result_transaction(&msg).await) })
+                .buffered(TRACE_CONCURRENCY)
+                .collect()
+                .await
+        };
+
+        level = Vec::new();
+        for ((parent, msg), result) in children.into_iter().zip(results) {
+            let id = trace.nodes.len();
+            let destination = msg.destination.account_address.clone().unwrap_or_default();
+            let child = match result {
+                Some(Ok(Some(tx))) => match transaction(&destination, tx.clone()) {
+                    Ok(transaction) => {
+                        level.push((id, tx));
+
+                        Node {
+                            transaction: Some(transaction),
+                            ..node(id, depth, &destination, State::Resolved)
+                        }
+                    }
+                    Err(e) => Node {
+                        error: Some(e.to_string()),
+                        ..node(id, depth, &destination, State::Failed)
+                    },
+                },
+                Some(Ok(None)) => node(id, depth, &destination, State::NotFound),
+                Some(Err(e)) => Node {
+                    error: Some(e.to_string()),
+                    ..node(id, depth, &destination, State::Failed)
+                },
+                None => node(id, depth, &destination, State::DepthLimit),
+            };
+
+            trace.nodes.push(child);
+            trace.edges.push(Edge {
+                from: parent as u32,
+                to: id as u32,
+                message: Some(msg.into()),
+            });
+        }
+    }
+
+    Ok(trace)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    fn address(n: u8) -> String {
+        format!("0:{:064x}", n)
+    }
+
+    fn message(source: u8, destination: u8, created_lt: i64) -> serde_json::Value {
+        json!({
+            "@type": "raw.message",
+            "source": {"@type": "accountAddress", "account_address": address(source)},
+            "destination": {"@type": "accountAddress", "account_address": address(destination)},
+            "value": "1000",
+            "fwd_fee": "0",
+            "ihr_fee": "0",
+            "created_lt": created_lt.to_string(),
+            "body_hash": format!("body-{}", created_lt),
+            "msg_data": {"@type": "msg.dataRaw", "body": "", "init_state": ""}
+        })
+    }
+
+    fn tx(
+        account: u8,
+        lt: i64,
+        in_msg: serde_json::Value,
+        out_msgs: Vec<(u8, i64)>,
+    ) -> RawTransaction {
+        serde_json::from_value(json!({
+            "@type": "raw.transaction",
+            "address": {"@type": "accountAddress", "account_address": address(account)},
+            "utime": "0",
+            "data": "",
+            "transaction_id": {"@type": "internal.transactionId", "lt": lt.to_string(), "hash": format!("hash-{}", lt)},
+            "fee": "0",
+            "storage_fee": "0",
+            "other_fee": "0",
+            "in_msg": in_msg,
+            "out_msgs": out_msgs
+                .into_iter()
+                .map(|(destination, created_lt)| message(account, destination, created_lt))
+                .collect::<Vec<_>>(),
+        }))
+        .unwrap()
+    }
+
+    /// Results of messages keyed by their created_lt, missing ones are not processed yet
+    #[derive(Default)]
+    struct Fake {
+        root: Option<RawTransaction>,
+        results: HashMap<i64, anyhow::Result<RawTransaction>>,
+    }
+
+    impl Fake {
+        fn result(&mut self, from: u8, to: u8, created_lt: i64, out_msgs: Vec<(u8, i64)>) {
+            let tx = tx(to, created_lt + 1, message(from, to, created_lt), out_msgs);
+            self.results.insert(created_lt, Ok(tx));
+        }
+    }
+
+    #[tonic::async_trait]
+    impl TraceSource for Fake {
+        async fn transaction(
+            &self,
+            _address: &str,
+            id: &InternalTransactionId,
+        ) -> anyhow::Result<Option<RawTransaction>> {
+            Ok(self.root.clone().filter(|tx| &tx.transaction_id == id))
+        }
+
+        async fn result_transaction(
+            &self,
+            msg: &RawMessage,
+        ) -> anyhow::Result<Option<RawTransaction>> {
+            match self.results.get(&msg.created_lt) {
+                Some(Ok(tx)) => Ok(Some(tx.clone())),
+                Some(Err(e)) => Err(anyhow::anyhow!("{}", e)),
+                None => Ok(None),
+            }
+        }
+    }
+
+    fn root_id() -> InternalTransactionId {
+        InternalTransactionId {
+            lt: 100,
+            hash: "hash-100".to_owned(),
+        }
+    }
+
+    fn states(trace: &MessageTrace) -> Vec<(u32, u32, State)> {
+        trace
+            .nodes
+            .iter()
+            .map(|node| (node.id, node.depth, node.state()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn missing_children_are_unresolved_leaves() {
+        // 1 -> 2 -> 4, 1 -> 3 which isn't processed yet, 2 -> 5 which fails
+        let mut fake = Fake {
+            root: Some(tx(1, 100, json!(null), vec![(2, 101), (3, 102)])),
+            ..Default::default()
+        };
+        fake.result(1, 2, 101, vec![(4, 201), (5, 202)]);
+        fake.result(2, 4, 201, vec![]);
+        fake.results.insert(202, Err(anyhow::anyhow!("timeout")));
+
+        let trace = message_trace(&fake, &address(1), root_id(), 4)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            states(&trace),
+            [
+                (0, 0, State::Resolved),
+                (1, 1, State::Resolved),
+                (2, 1, State::NotFound),
+                (3, 2, State::Resolved),
+                (4, 2, State::Failed),
+            ]
+        );
+        let edges: Vec<_> = trace.edges.iter().map(|e| (e.from, e.to)).collect();
+        assert_eq!(edges, [(0, 1), (0, 2), (1, 3), (1, 4)]);
+        assert_eq!(trace.nodes[2].account_address, address(3));
+        assert_eq!(trace.nodes[4].error.as_deref(), Some("timeout"));
+        assert!(!trace.truncated);
+    }
+
+    #[tokio::test]
+    async fn depth_is_limited() {
+        let mut fake = Fake {
+            root: Some(tx(1, 100, json!(null), vec![(2, 101)])),
+            ..Default::default()
+        };
+        fake.result(1, 2, 101, vec![(3, 201)]);
+        fake.result(2, 3, 201, vec![]);
+
+        let trace = message_trace(&fake, &address(1), root_id(), 1)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            states(&trace),
+            [
+                (0, 0, State::Resolved),
+                (1, 1, State::Resolved),
+                (2, 2, State::DepthLimit),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn fan_out_and_nodes_are_capped() {
+        let out_msgs: Vec<_> = (0..TRACE_MAX_FAN_OUT as i64 + 1)
+            .map(|i| (2, 101 + i))
+            .collect();
+        let mut fake = Fake {
+            root: Some(tx(1, 100, json!(null), out_msgs.clone())),
+            ..Default::default()
+        };
+        for (_, created_lt) in &out_msgs {
+            let grandchildren = (0..TRACE_MAX_FAN_OUT as i64)
+                .map(|i| (3, created_lt * 1000 + i))
+                .collect();
+            fake.result(1, 2, *created_lt, grandchildren);
+        }
+
+        let trace = message_trace(&fake, &address(1), root_id(), 4)
+            .await
+            .unwrap();
+
+        assert!(trace.truncated);
+        assert_eq!(trace.nodes.len(), TRACE_MAX_NODES);
+        assert_eq!(
+            trace.nodes.iter().filter(|node| node.depth == 1).count(),
+            TRACE_MAX_FAN_OUT
+        );
+    }
+
+    #[tokio::test]
+    async fn missing_root_is_not_found() {
+        let status = message_trace(&Fake::default(), &address(1), root_id(), 4)
+            .await
+            .unwrap_err();
+
+        assert_eq!(status.code(), tonic::Code::NotFound);
+    }
+}
@@ -0,0 +1,259 @@
+use crate::maintenance::Pause;
+use crate::memory::Tracked;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::watch;
+use tonlibjson_client::ton::{AccountStateDelta, TonClient};
+
+type Watchers = Arc<Mutex<HashMap<String, Arc<watch::Sender<Option<AccountStateDelta>>>>>>;
+
+/// Account watchers shared by all subscribers of an address,
+/// a watcher stops once its last subscriber is gone
+pub struct AccountWatchers {
+    client: TonClient,
+    poll_interval: Duration,
+    watchers: Watchers,
+    pause: Pause,
+}
+
+impl AccountWatchers {
+    pub fn new(client: TonClient, poll_interval: Duration) -> Self {
+        Self {
+            client,
+            poll_interval,
+            watchers: Default::default(),
+            pause: Pause::default(),
+        }
+    }
+
+    /// Stops polling while `pause` holds, subscribers keep the last state
+    pub fn set_pause(mut self, pause: Pause) -> Self {
+        self.pause = pause;
+        self
+    }
+
+    /// Latest state of the address, none until the first poll completes
+    pub fn subscribe(&self, address: &str) -> watch::Receiver<Option<AccountStateDelta>> {
+        let client = self.client.clone();
+        let poll_interval = self.poll_interval;
+        let watched = address.to_owned();
+
+        subscribe_with(
+            &self.watchers,
+            address,
+            poll_interval,
+            self.pause.clone(),
+            move || client.watch_account(&watched, poll_interval).boxed(),
+        )
+    }
+}
+
+/// Watchers are accounted only, they're never dropped while subscribed to
+impl Tracked for AccountWatchers {
+    fn usage(&self) -> usize {
+        usage(&self.watchers)
+    }
+
+    fn shrink(&self, _: usize) -> usize {
+        0
+    }
+}
+
+fn usage(watchers: &Watchers) -> usize {
+    watchers
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(address, sender)| {
+            let delta = sender.borrow().as_ref().map_or(0, |delta| {
+                delta.block_id.root_hash.len()
+                    + delta.block_id.file_hash.len()
+                    + delta
+                        .last_transaction_id
+                        .as_ref()
+                        .map_or(0, |id| id.hash.len())
+            });
+
+            address.len() + std::mem::size_of::<Option<AccountStateDelta>>() + delta
+        })
+        .sum()
+}
+
+fn subscribe_with<F>(
+    watchers: &Watchers,
+    address: &str,
+    retry_interval: Duration,
+    pause: Pause,
+    watch_account: F,
+) -> watch::Receiver<Option<AccountStateDelta>>
+where
+    F: Fn() -> BoxStream<'static, anyhow::Result<AccountStateDelta>> + Send + 'static,
+{
+    let mut guard = watchers.lock().unwrap();
+    if let Some(sender) = guard.get(address) {
+        return sender.subscribe();
+    }
+
+    let (sender, receiver) = watch::channel(None);
+    let sender = Arc::new(sender);
+    guard.insert(address.to_owned(), sender.clone());
+
+    tokio::spawn(run(
+        watchers.clone(),
+        address.to_owned(),
+        sender,
+        retry_interval,
+        pause,
+        watch_account,
+    ));
+
+    receiver
+}
+
+async fn run<F>(
+    watchers: Watchers,
+    address: String,
+    sender: Arc<watch::Sender<Option<AccountStateDelta>>>,
+    retry_interval: Duration,
+    mut pause: Pause,
+    watch_account: F,
+) where
+    F: Fn() -> BoxStream<'static, anyhow::Result<AccountStateDelta>>,
+{
+    loop {
+        let mut stream = watch_account();
+        loop {
+            tokio::select! {
+                _ = sender.closed() => break,
+                _ = pause.paused() => {
+                    drop(stream);
+                    tokio::select! {
+                        _ = sender.closed() => break,
+                        _ = pause.resumed() => {}
+                    }
+                    stream = watch_account();
+                }
+                delta = stream.next() => match delta {
+                    Some(Ok(delta)) => {
+                        sender.send_replace(Some(delta));
+                    }
+                    Some(Err(e)) => {
+                        tracing::warn!(address, error = ?e, "account watcher failed");
+                        tokio::select! {
+                            _ = sender.closed() => break,
+                            _ = tokio::time::sleep(retry_interval) => {}
+                        }
+                        stream = watch_account();
+                    }
+                    None => stream = watch_account(),
+                }
+            }
+        }
+
+        // a subscriber may have joined while the watcher was stopping
+        let mut watchers = watchers.lock().unwrap();
+        if sender.receiver_count() == 0 {
+            watchers.remove(&address);
+
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream;
+    use tonlibjson_client::block::TonBlockIdExt;
+    use tonlibjson_client::ton::AccountStatus;
+
+    fn delta(balance: i64) -> AccountStateDelta {
+        AccountStateDelta {
+            block_id: TonBlockIdExt::new(-1, i64::MIN, 1, String::new(), String::new()),
+            balance: Some(balance),
+            status: AccountStatus::Active,
+            last_transaction_id: None,
+        }
+    }
+
+    fn pending_after(balance: i64) -> BoxStream<'static, anyhow::Result<AccountStateDelta>> {
+        stream::iter([Ok(delta(balance))])
+            .chain(stream::pending())
+            .boxed()
+    }
+
+    #[tokio::test]
+    async fn subscribers_share_watcher() {
+        let watchers = Watchers::default();
+        let mut first = subscribe_with(&watchers, "a", Duration::ZERO, Pause::default(), || {
+            pending_after(1)
+        });
+        let mut second = subscribe_with(&watchers, "a", Duration::ZERO, Pause::default(), || {
+            pending_after(2)
+        });
+
+        first.changed().await.unwrap();
+        assert_eq!(*first.borrow(), Some(delta(1)));
+        assert_eq!(*second.borrow_and_update(), Some(delta(1)));
+        assert_eq!(watchers.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn watchers_are_accounted() {
+        let watchers = Watchers::default();
+        assert_eq!(usage(&watchers), 0);
+
+        let _receiver = subscribe_with(&watchers, "a", Duration::ZERO, Pause::default(), || {
+            pending_after(1)
+        });
+
+        assert!(usage(&watchers) > std::mem::size_of::<Option<AccountStateDelta>>());
+    }
+
+    #[tokio::test]
+    async fn last_subscriber_stops_watcher() {
+        let watchers = Watchers::default();
+        let first = subscribe_with(&watchers, "a", Duration::ZERO, Pause::default(), || {
+            pending_after(1)
+        });
+        let second = subscribe_with(&watchers, "b", Duration::ZERO, Pause::default(), || {
+            pending_after(1)
+        });
+
+        drop(first);
+        while watchers.lock().unwrap().contains_key("a") {
+            tokio::task::yield_now().await;
+        }
+
+        assert!(watchers.lock().unwrap().contains_key("b"));
+        drop(second);
+    }
+
+    #[tokio::test]
+    async fn watcher_restarts_after_error() {
+        let watchers = Watchers::default();
+        let calls = Arc::new(Mutex::new(0));
+        let counter = calls.clone();
+        let mut receiver = subscribe_with(
+            &watchers,
+            "a",
+            Duration::ZERO,
+            Pause::default(),
+            move || {
+                let mut calls = counter.lock().unwrap();
+                *calls += 1;
+                if *calls == 1 {
+                    stream::iter([Err(anyhow::anyhow!("lite server is down"))]).boxed()
+                } else {
+                    pending_after(*calls)
+                }
+            },
+        );
+
+        receiver.changed().await.unwrap();
+        assert_eq!(*receiver.borrow(), Some(delta(2)));
+    }
+}
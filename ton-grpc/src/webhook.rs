@@ -0,0 +1,662 @@
+use crate::canonical;
+use crate::memory::Tracked;
+use crate::ton::webhook_service_server::WebhookService as BaseWebhookService;
+use crate::ton::{
+    self as proto, DeleteWebhookRequest, ListDeadLettersRequest, ListDeadLettersResponse,
+    ListWebhooksRequest, ListWebhooksResponse, RegisterWebhookRequest,
+};
+use crate::watch::AccountWatchers;
+use anyhow::anyhow;
+use derive_new::new;
+use futures::TryStreamExt;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::Sha256;
+use std::collections::{HashMap, VecDeque};
+use std::ops::Bound;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::task::AbortHandle;
+use tonic::{async_trait, Request, Response, Status};
+use tonlibjson_client::address::AccountAddressData;
+use tonlibjson_client::block::{InternalTransactionId, RawTransaction};
+use tonlibjson_client::ton::TonClient;
+use url::Url;
+use uuid::Uuid;
+
+/// Failed deliveries kept for inspection, the oldest ones are dropped first
+const DEAD_LETTERS_CAPACITY: usize = 1000;
+/// Hex HMAC-SHA256 of the request body keyed by the shared secret, prefixed with `sha256=`
+pub const SIGNATURE_HEADER: &str = "x-ton-grpc-signature";
+pub const WEBHOOK_ID_HEADER: &str = "x-ton-grpc-webhook-id";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Webhook {
+    pub id: String,
+    pub account_address: String,
+    pub url: Url,
+    /// last transaction delivered or dead-lettered, deliveries resume after it
+    pub last_transaction_id: Option<InternalTransactionId>,
+}
+
+impl Webhook {
+    fn heap_size(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.id.len()
+            + self.account_address.len()
+            + self.url.as_str().len()
+            + self
+                .last_transaction_id
+                .as_ref()
+                .map_or(0, |id| id.hash.len())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DeliveryPolicy {
+    /// signs payloads if set
+    pub secret: Option<String>,
+    /// attempts of a single delivery before it's dead-lettered
+    pub max_attempts: u32,
+    pub first_delay: Duration,
+    pub max_delay: Duration,
+    pub timeout: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct DeadLetter {
+    pub webhook_id: String,
+    pub account_address: String,
+    pub transaction_id: InternalTransactionId,
+    pub attempts: u32,
+    pub error: String,
+    pub failed_at: SystemTime,
+}
+
+impl DeadLetter {
+    fn heap_size(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.webhook_id.len()
+            + self.account_address.len()
+            + self.transaction_id.hash.len()
+            + self.error.len()
+    }
+}
+
+/// Registered webhooks, survives restarts through a JSON file if a path is given
+#[derive(Debug)]
+struct Registry {
+    path: Option<PathBuf>,
+    capacity: usize,
+    webhooks: HashMap<String, Webhook>,
+}
+
+impl Registry {
+    fn load(path: Option<PathBuf>, capacity: usize) -> anyhow::Result<Self> {
+        let webhooks: Vec<Webhook> = match &path {
+            Some(path) => match std::fs::read(path) {
+                Ok(bytes) => serde_json::from_slice(&bytes)?,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => vec![],
+                Err(e) => return Err(e.into()),
+            },
+            None => vec![],
+        };
+
+        Ok(Self {
+            path,
+            capacity,
+            webhooks: webhooks
+                .into_iter()
+                .map(|webhook| (webhook.id.clone(), webhook))
+                .collect(),
+        })
+    }
+
+    /// Writes the file next to the target first, so a crash never leaves it half written
+    fn save(&self) -> anyhow::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        let mut webhooks: Vec<_> = self.webhooks.values().collect();
+        webhooks.sort_by(|lhs, rhs| lhs.id.cmp(&rhs.id));
+
+        let json = serde_json::to_vec(&webhooks)?;
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, json)?;
+        std::fs::rename(tmp, path)?;
+
+        Ok(())
+    }
+
+    fn insert(&mut self, webhook: Webhook) -> Result<(), Status> {
+        if self.webhooks.len() >= self.capacity {
+            return Err(Status::resource_exhausted(format!(
+                "no more than {} webhooks",
+                self.capacity
+            )));
+        }
+        self.webhooks.insert(webhook.id.clone(), webhook);
+
+        self.save().map_err(|e| Status::internal(e.to_string()))
+    }
+
+    fn remove(&mut self, id: &str) -> Result<Webhook, Status> {
+        let webhook = self
+            .webhooks
+            .remove(id)
+            .ok_or_else(|| Status::not_found(format!("webhook {} not found", id)))?;
+
+        self.save().map_err(|e| Status::internal(e.to_string()))?;
+
+        Ok(webhook)
+    }
+
+    /// Moves the position of the webhook, false if it's gone
+    fn advance(&mut self, id: &str, tx_id: InternalTransactionId) -> bool {
+        let Some(webhook) = self.webhooks.get_mut(id) else {
+            return false;
+        };
+        webhook.last_transaction_id = Some(tx_id);
+
+        if let Err(e) = self.save() {
+            tracing::error!(error = ?e, "failed to save webhooks");
+        }
+
+        true
+    }
+}
+
+/// Webhooks which get new transactions of their address POSTed as JSON,
+/// addresses are polled through the shared account watchers
+pub struct Webhooks {
+    client: TonClient,
+    watchers: Arc<AccountWatchers>,
+    http: reqwest::Client,
+    policy: DeliveryPolicy,
+    registry: Mutex<Registry>,
+    tasks: Mutex<HashMap<String, AbortHandle>>,
+    dead_letters: Mutex<VecDeque<DeadLetter>>,
+}
+
+impl Webhooks {
+    /// Resumes deliveries of webhooks registered in the file at `path`
+    pub fn new(
+        client: TonClient,
+        watchers: Arc<AccountWatchers>,
+        policy: DeliveryPolicy,
+        capacity: usize,
+        path: Option<PathBuf>,
+    ) -> anyhow::Result<Arc<Self>> {
+        let registry = Registry::load(path, capacity)?;
+        let webhooks: Vec<_> = registry.webhooks.values().cloned().collect();
+
+        let this = Arc::new(Self {
+            client,
+            watchers,
+            http: reqwest::Client::builder().timeout(policy.timeout).build()?,
+            policy,
+            registry: Mutex::new(registry),
+            tasks: Default::default(),
+            dead_letters: Default::default(),
+        });
+        for webhook in webhooks {
+            this.spawn(webhook);
+        }
+
+        Ok(this)
+    }
+
+    /// Delivers transactions of `address` made after the registration to `url`
+    pub async fn register(self: &Arc<Self>, address: &str, url: &str) -> Result<Webhook, Status> {
+        // the raw form shares the watcher with WatchAccountState
+        let address = AccountAddressData::from_str(address)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?
+            .to_raw_string();
+        let url = Url::parse(url).map_err(|e| Status::invalid_argument(e.to_string()))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(Status::invalid_argument("url must be http or https"));
+        }
+
+        let state = self
+            .client
+            .raw_get_account_state(&address)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        let webhook = Webhook {
+            id: Uuid::new_v4().simple().to_string(),
+            account_address: address,
+            url,
+            last_transaction_id: state.last_transaction_id,
+        };
+        self.registry.lock().unwrap().insert(webhook.clone())?;
+
+        tracing::info!(
+            webhook_id = webhook.id,
+            address = webhook.account_address,
+            "webhook registered"
+        );
+        self.spawn(webhook.clone());
+
+        Ok(webhook)
+    }
+
+    pub fn list(&self) -> Vec<Webhook> {
+        let mut webhooks: Vec<_> = self
+            .registry
+            .lock()
+            .unwrap()
+            .webhooks
+            .values()
+            .cloned()
+            .collect();
+        webhooks.sort_by(|lhs, rhs| lhs.id.cmp(&rhs.id));
+
+        webhooks
+    }
+
+    pub fn delete(&self, id: &str) -> Result<Webhook, Status> {
+        let webhook = self.registry.lock().unwrap().remove(id)?;
+        if let Some(task) = self.tasks.lock().unwrap().remove(id) {
+            task.abort();
+        }
+
+        tracing::info!(webhook_id = id, "webhook deleted");
+
+        Ok(webhook)
+    }
+
+    /// Failed deliveries, the newest first
+    pub fn dead_letters(&self) -> Vec<DeadLetter> {
+        self.dead_letters
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .cloned()
+            .collect()
+    }
+
+    fn spawn(self: &Arc<Self>, webhook: Webhook) {
+        let id = webhook.id.clone();
+        let task = tokio::spawn(self.clone().run(webhook));
+
+        self.tasks.lock().unwrap().insert(id, task.abort_handle());
+    }
+
+    async fn run(self: Arc<Self>, webhook: Webhook) {
+        let id = webhook.id;
+        let address = webhook.account_address;
+        let mut receiver = self.watchers.subscribe(&address);
+
+        while receiver.changed().await.is_ok() {
+            let last = receiver
+                .borrow_and_update()
+                .as_ref()
+                .and_then(|delta| delta.last_transaction_id.clone());
+            let Some(last) = last else {
+                continue;
+            };
+            let Some(from) = self.position(&id) else {
+                return;
+            };
+            if from.as_ref() == Some(&last) {
+                continue;
+            }
+
+            let transactions = self.new_transactions(&address, last, from).await;
+            // the oldest first
+            for tx in transactions.into_iter().rev() {
+                let tx_id = tx.transaction_id.clone();
+                let body = payload(&id, &address, &tx);
+
+                if let Err((attempts, e)) =
+                    deliver(&self.http, &self.policy, &webhook.url, &id, body).await
+                {
+                    tracing::warn!(webhook_id = id, attempts, error = ?e, "webhook delivery dead-lettered");
+                    metrics::counter!("ton_webhook_dead_letters_total").increment(1);
+
+                    let mut dead_letters = self.dead_letters.lock().unwrap();
+                    if dead_letters.len() == DEAD_LETTERS_CAPACITY {
+                        dead_letters.pop_front();
+                    }
+                    dead_letters.push_back(DeadLetter {
+                        webhook_id: id.clone(),
+                        account_address: address.clone(),
+                        transaction_id: tx_id.clone(),
+                        attempts,
+                        error: e.to_string(),
+                        failed_at: SystemTime::now(),
+                    });
+                } else {
+                    metrics::counter!("ton_webhook_delivered_total").increment(1);
+                }
+
+                if !self.registry.lock().unwrap().advance(&id, tx_id) {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Position of the webhook, none if it's gone
+    fn position(&self, id: &str) -> Option<Option<InternalTransactionId>> {
+        self.registry
+            .lock()
+            .unwrap()
+            .webhooks
+            .get(id)
+            .map(|webhook| webhook.last_transaction_id.clone())
+    }
+
+    /// Transactions after `from` up to `last`, the newest first, retried until they're fetched
+    async fn new_transactions(
+        &self,
+        address: &str,
+        last: InternalTransactionId,
+        from: Option<InternalTransactionId>,
+    ) -> Vec<RawTransaction> {
+        let mut failures = 0;
+        loop {
+            let range = (
+                Bound::Included(last.clone()),
+                from.clone().map_or(Bound::Unbounded, Bound::Excluded),
+            );
+            match self
+                .client
+                .get_account_tx_range(address, range)
+                .try_collect()
+                .await
+            {
+                Ok(transactions) => return transactions,
+                Err(e) => {
+                    failures += 1;
+                    tracing::warn!(address, error = ?e, failures, "webhook transactions failed");
+
+                    tokio::time::sleep(backoff(&self.policy, failures)).await;
+                }
+            }
+        }
+    }
+}
+
+/// Registered webhooks are accounted only, the oldest dead letters are dropped to shrink
+impl Tracked for Webhooks {
+    fn usage(&self) -> usize {
+        let webhooks: usize = self
+            .registry
+            .lock()
+            .unwrap()
+            .webhooks
+            .values()
+            .map(Webhook::heap_size)
+            .sum();
+        let dead_letters: usize = self
+            .dead_letters
+            .lock()
+            .unwrap()
+            .iter()
+            .map(DeadLetter::heap_size)
+            .sum();
+
+        webhooks + dead_letters
+    }
+
+    fn shrink(&self, bytes: usize) -> usize {
+        drop_oldest(&mut self.dead_letters.lock().unwrap(), bytes)
+    }
+}
+
+/// Drops the oldest dead letters worth at least `bytes`, returns the bytes dropped
+fn drop_oldest(dead_letters: &mut VecDeque<DeadLetter>, bytes: usize) -> usize {
+    let mut freed = 0;
+    while freed < bytes {
+        let Some(dead_letter) = dead_letters.pop_front() else {
+            break;
+        };
+        freed += dead_letter.heap_size();
+    }
+
+    freed
+}
+
+fn backoff(policy: &DeliveryPolicy, failures: u32) -> Duration {
+    policy
+        .first_delay
+        .saturating_mul(1 << (failures - 1).min(31))
+        .min(policy.max_delay)
+}
+
+/// Canonical JSON, so that receivers may hash and archive deliveries
+fn payload(webhook_id: &str, address: &str, tx: &RawTransaction) -> Vec<u8> {
+    canonical::to_vec(&json!({
+        "webhook_id": webhook_id,
+        "account_address": address,
+        "transaction": tx,
+    }))
+    .expect("JSON values serialize")
+}
+
+/// `sha256=` and the hex HMAC-SHA256 of `body`
+pub fn signature(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any size");
+    mac.update(body);
+
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// POSTs `body` until a 2xx response, returns the number of attempts and the last error
+/// if there were `max_attempts` failures
+async fn deliver(
+    http: &reqwest::Client,
+    policy: &DeliveryPolicy,
+    url: &Url,
+    webhook_id: &str,
+    body: Vec<u8>,
+) -> Result<(), (u32, anyhow::Error)> {
+    let signature = policy
+        .secret
+        .as_ref()
+        .map(|secret| signature(secret, &body));
+
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+
+        let mut request = http
+            .post(url.clone())
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(WEBHOOK_ID_HEADER, webhook_id)
+            .body(body.clone());
+        if let Some(signature) = &signature {
+            request = request.header(SIGNATURE_HEADER, signature);
+        }
+
+        let error = match request.send().await {
+            Ok(response) if response.status().is_success() => return Ok(()),
+            Ok(response) => anyhow!("webhook responded with {}", response.status()),
+            Err(e) => e.into(),
+        };
+        if attempts >= policy.max_attempts {
+            return Err((attempts, error));
+        }
+
+        tokio::time::sleep(backoff(policy, attempts)).await;
+    }
+}
+
+#[derive(new)]
+pub struct WebhookService {
+    webhooks: Arc<Webhooks>,
+}
+
+#[async_trait]
+impl BaseWebhookService for WebhookService {
+    #[tracing::instrument(skip_all, err)]
+    async fn register_webhook(
+        &self,
+        request: Request<RegisterWebhookRequest>,
+    ) -> Result<Response<proto::Webhook>, Status> {
+        let msg = request.into_inner();
+        let webhook = self
+            .webhooks
+            .register(&msg.account_address, &msg.url)
+            .await?;
+
+        Ok(Response::new(webhook.into()))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn list_webhooks(
+        &self,
+        _request: Request<ListWebhooksRequest>,
+    ) -> Result<Response<ListWebhooksResponse>, Status> {
+        Ok(Response::new(ListWebhooksResponse {
+            webhooks: self.webhooks.list().into_iter().map(Into::into).collect(),
+        }))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn delete_webhook(
+        &self,
+        request: Request<DeleteWebhookRequest>,
+    ) -> Result<Response<proto::Webhook>, Status> {
+        let webhook = self.webhooks.delete(&request.into_inner().id)?;
+
+        Ok(Response::new(webhook.into()))
+    }
+
+    #[tracing::instrument(skip_all, err)]
+    async fn list_dead_letters(
+        &self,
+        _request: Request<ListDeadLettersRequest>,
+    ) -> Result<Response<ListDeadLettersResponse>, Status> {
+        Ok(Response::new(ListDeadLettersResponse {
+            dead_letters: self
+                .webhooks
+                .dead_letters()
+                .into_iter()
+                .map(Into::into)
+                .collect(),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn webhook(id: &str) -> Webhook {
+        Webhook {
+            id: id.to_owned(),
+            account_address: "EQCD39VS5jcptHL8vMjEXrzGaRcCVYto7HUn4bpAOg8xqB2N".to_owned(),
+            url: Url::parse("https://example.com/hook").unwrap(),
+            last_transaction_id: None,
+        }
+    }
+
+    fn tx_id(lt: i64) -> InternalTransactionId {
+        InternalTransactionId {
+            lt,
+            hash: format!("hash-{}", lt),
+        }
+    }
+
+    #[test]
+    fn registry_round_trip() {
+        let path = std::env::temp_dir().join(format!("webhooks-{}.json", Uuid::new_v4()));
+        let mut registry = Registry::load(Some(path.clone()), 16).unwrap();
+        registry.insert(webhook("a")).unwrap();
+        registry.insert(webhook("b")).unwrap();
+        assert!(registry.advance("a", tx_id(7)));
+        registry.remove("b").unwrap();
+
+        let restored = Registry::load(Some(path.clone()), 16).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(restored.webhooks.len(), 1);
+        assert_eq!(restored.webhooks["a"].last_transaction_id, Some(tx_id(7)));
+    }
+
+    #[test]
+    fn registry_is_bounded() {
+        let mut registry = Registry::load(None, 1).unwrap();
+        registry.insert(webhook("a")).unwrap();
+
+        let status = registry.insert(webhook("b")).unwrap_err();
+
+        assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+        assert!(!registry.advance("b", tx_id(1)));
+        assert_eq!(
+            registry.remove("b").unwrap_err().code(),
+            tonic::Code::NotFound
+        );
+    }
+
+    #[test]
+    fn oldest_dead_letters_are_dropped() {
+        let mut dead_letters: VecDeque<_> = (1..=3)
+            .map(|lt| DeadLetter {
+                webhook_id: "a".to_owned(),
+                account_address: "address".to_owned(),
+                transaction_id: tx_id(lt),
+                attempts: 1,
+                error: "refused".to_owned(),
+                failed_at: SystemTime::UNIX_EPOCH,
+            })
+            .collect();
+        let size = dead_letters[0].heap_size();
+
+        assert_eq!(drop_oldest(&mut dead_letters, size + 1), 2 * size);
+        assert_eq!(dead_letters.len(), 1);
+        assert_eq!(dead_letters[0].transaction_id, tx_id(3));
+    }
+
+    #[test]
+    fn signature_of_body() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            signature("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        let policy = DeliveryPolicy {
+            secret: None,
+            max_attempts: 3,
+            first_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(10),
+            timeout: Duration::from_secs(1),
+        };
+
+        assert_eq!(backoff(&policy, 1), Duration::from_secs(1));
+        assert_eq!(backoff(&policy, 3), Duration::from_secs(4));
+        assert_eq!(backoff(&policy, 40), Duration::from_secs(10));
+    }
+
+    #[tokio::test]
+    async fn delivery_gives_up_after_max_attempts() {
+        let policy = DeliveryPolicy {
+            secret: Some("secret".to_owned()),
+            max_attempts: 3,
+            first_delay: Duration::ZERO,
+            max_delay: Duration::ZERO,
+            timeout: Duration::from_secs(1),
+        };
+        // nothing listens on the discard port
+        let url = Url::parse("http://127.0.0.1:9/hook").unwrap();
+
+        let (attempts, _) = deliver(&reqwest::Client::new(), &policy, &url, "a", vec![])
+            .await
+            .unwrap_err();
+
+        assert_eq!(attempts, 3);
+    }
+}
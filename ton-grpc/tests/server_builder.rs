@@ -0,0 +1,134 @@
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
+use ton_grpc::cache::CachePolicy;
+use ton_grpc::cursor::Cursors;
+use ton_grpc::json::JsonLimits;
+use ton_grpc::limits::{ParamLimits, ResponseSizeLimits};
+use ton_grpc::maintenance::Maintenance;
+use ton_grpc::methods::MethodPolicy;
+use ton_grpc::quota::{ApiKey, MemoryUsageStore, API_KEY_HEADER};
+use ton_grpc::server::{Middleware, NetworkServices, ServerBuilder};
+use ton_grpc::ton::method_service_client::MethodServiceClient;
+use ton_grpc::ton::ListMethodsRequest;
+use tonic::transport::server::Router;
+use tonic::transport::{Channel, Server};
+use tonic::{Code, Request};
+use tonlibjson_client::ton::{TonClient, TonClientBuilder};
+
+/// A client without lite servers, listing methods doesn't reach them
+fn client() -> TonClient {
+    TonClientBuilder::from_config(serde_json::from_value(json!({"liteservers": []})).unwrap())
+        .build()
+        .unwrap()
+}
+
+async fn serve(router: Router<Middleware>) -> MethodServiceClient<Channel> {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(router.serve_with_incoming(TcpListenerStream::new(listener)));
+
+    MethodServiceClient::connect(format!("http://{}", addr))
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn minimal_stack() {
+    let router = ServerBuilder::new("mainnet".to_owned())
+        .add_network("mainnet", NetworkServices::new(client()))
+        .build_router(Server::builder())
+        .unwrap();
+    let mut methods = serve(router).await;
+
+    let response = methods
+        .list_methods(ListMethodsRequest {})
+        .await
+        .unwrap()
+        .into_inner();
+
+    assert!(!response.methods.is_empty());
+    assert!(response.methods.iter().all(|method| method.enabled));
+}
+
+#[tokio::test]
+async fn fully_loaded_stack() {
+    let keys = HashMap::from([(
+        "secret".to_owned(),
+        ApiKey {
+            name: "acme".to_owned(),
+            daily: Some(1000),
+            monthly: None,
+            methods: Default::default(),
+        },
+    )]);
+    let (_, health) = tonic_health::server::health_reporter();
+    let router = ServerBuilder::new("mainnet".to_owned())
+        .add_network("mainnet", NetworkServices::new(client()))
+        .add_network("testnet", NetworkServices::new(client()))
+        .add_service(health)
+        .set_api_keys(keys)
+        .set_method_costs([("ListMethods".to_owned(), 1)])
+        .set_usage_store(Arc::new(MemoryUsageStore::default()))
+        .set_method_policy(MethodPolicy::new(&[], &["GetBlockData".to_owned()]))
+        .set_response_size_limits(ResponseSizeLimits::new(1024 * 1024, []))
+        .set_param_limits(ParamLimits::default())
+        .set_json_limits(JsonLimits::default())
+        .set_cache_policy(CachePolicy::default())
+        .set_cursors(Cursors::new(b"cursor secret"))
+        .set_maintenance(Arc::new(Maintenance::new(Duration::from_secs(30))))
+        .build_router(Server::builder())
+        .unwrap();
+    let mut methods = serve(router).await;
+
+    let status = methods
+        .list_methods(ListMethodsRequest {})
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+
+    let mut request = Request::new(ListMethodsRequest {});
+    request
+        .metadata_mut()
+        .insert(API_KEY_HEADER, "secret".parse().unwrap());
+    let response = methods.list_methods(request).await.unwrap().into_inner();
+
+    let block_data = response
+        .methods
+        .iter()
+        .find(|method| method.name == "GetBlockData")
+        .unwrap();
+    assert!(!block_data.enabled);
+}
+
+#[tokio::test]
+async fn incompatible_settings_are_rejected() {
+    let error = ServerBuilder::new("mainnet".to_owned())
+        .add_network("mainnet", NetworkServices::new(client()))
+        .set_method_costs([("GetAccountState".to_owned(), 5)])
+        .build()
+        .err()
+        .unwrap();
+    assert_eq!(error.to_string(), "method costs require api keys");
+
+    let error = ServerBuilder::new("mainnet".to_owned())
+        .add_network("mainnet", NetworkServices::new(client()))
+        .set_usage_store(Arc::new(MemoryUsageStore::default()))
+        .build()
+        .err()
+        .unwrap();
+    assert_eq!(error.to_string(), "a usage store requires api keys");
+
+    let error = ServerBuilder::new("mainnet".to_owned())
+        .add_network("testnet", NetworkServices::new(client()))
+        .build()
+        .err()
+        .unwrap();
+    assert_eq!(
+        error.to_string(),
+        "default network mainnet is not configured"
+    );
+}
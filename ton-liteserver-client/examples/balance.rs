@@ -3,7 +3,9 @@ use futures::{stream, StreamExt};
 use std::net::SocketAddrV4;
 use std::time::Duration;
 use tokio::time::Instant;
-use ton_client_util::discover::{read_ton_config_from_url_stream, LiteServerDiscover};
+use ton_client_util::discover::{
+    read_ton_config_from_url_stream, ConfigRefresh, LiteServerDiscover,
+};
 use ton_client_util::router::balance::Balance;
 use ton_client_util::service::shared::SharedLayer;
 use ton_client_util::service::timeout::TimeoutLayer;
@@ -21,10 +23,15 @@ use tower::{BoxError, ServiceBuilder, ServiceExt};
 async fn main() -> Result<(), tower::BoxError> {
     tracing_subscriber::fmt::init();
 
-    let discovery = LiteServerDiscover::new(read_ton_config_from_url_stream(
-        "https://ton.org/global-config.json".parse()?,
-        tokio::time::interval_at(Instant::now(), Duration::from_secs(30)),
-    ))
+    let refresh = ConfigRefresh::default();
+    let discovery = LiteServerDiscover::new(
+        read_ton_config_from_url_stream(
+            "https://ton.org/global-config.json".parse()?,
+            tokio::time::interval_at(Instant::now(), Duration::from_secs(30)),
+            &refresh,
+        ),
+        refresh.clone(),
+    )
     .then(|change| async {
         match change {
             Ok(Change::Insert(k, v)) => {
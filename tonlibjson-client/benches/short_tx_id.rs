@@ -0,0 +1,69 @@
+//! Allocations of the per-transaction conversions of a block listing, run with
+//! `cargo bench -p tonlibjson-client --bench short_tx_id`
+use std::str::FromStr;
+use tonlibjson_client::address::ShardContextAccountAddress;
+use tonlibjson_client::block::BlocksShortTxId;
+
+#[global_allocator]
+static ALLOC: dhat::Alloc = dhat::Alloc;
+
+const BATCH: usize = 10_000;
+
+fn batch() -> Vec<BlocksShortTxId> {
+    (0..BATCH)
+        .map(|i| {
+            let mut bytes = [0; 32];
+            bytes[..8].copy_from_slice(&(i as u64).to_be_bytes());
+
+            BlocksShortTxId {
+                mode: 7,
+                workchain: 0,
+                account: ShardContextAccountAddress { bytes },
+                lt: 33756943000007 + i as i64,
+                hash: "752Szayka+Eh54Zvco5l84d6WL+zJFmyh1wqRxD08Uo=".to_owned(),
+            }
+        })
+        .collect()
+}
+
+fn allocations(f: impl FnOnce()) -> u64 {
+    let before = dhat::HeapStats::get().total_blocks;
+    f();
+
+    dhat::HeapStats::get().total_blocks - before
+}
+
+fn main() {
+    let _profiler = dhat::Profiler::builder().testing().build();
+
+    let ids = batch();
+    let accounts: Vec<_> = ids.iter().map(|id| id.account.to_string()).collect();
+    let mut out = Vec::with_capacity(BATCH * 512);
+
+    let via_value = allocations(|| {
+        for id in &ids {
+            serde_json::to_writer(&mut out, &id.to_json(false)).unwrap();
+        }
+    });
+    out.clear();
+    let serialized = allocations(|| serde_json::to_writer(&mut out, &ids).unwrap());
+    let parsed = allocations(|| {
+        for account in &accounts {
+            ShardContextAccountAddress::from_str(account).unwrap();
+        }
+    });
+    let addresses = allocations(|| {
+        for id in &ids {
+            out.extend_from_slice(id.address().to_string().as_bytes());
+        }
+    });
+
+    println!("allocations of {BATCH} short tx ids");
+    println!("  serialized through serde_json::Value: {via_value}");
+    println!("  serialized: {serialized}");
+    println!("  accounts parsed from base64: {parsed}");
+    println!("  addresses displayed: {addresses}");
+
+    assert!(serialized < via_value / 10);
+    assert_eq!(parsed, 0);
+}
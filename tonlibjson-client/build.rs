@@ -84,12 +84,23 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             configure_type()
                 .derives(vec!["Clone", "Serialize", "Deserialize"])
                 .field("in_msg", configure_field().optional().build())
+                .capture_unknown()
+                .build(),
+        )
+        .configure_full(
+            "raw.message",
+            configure_type()
+                .derives(vec!["Clone", "Serialize", "Deserialize"])
+                .field("extra_currencies", configure_field().skip().build())
+                .capture_unknown()
                 .build(),
         )
         .configure_full(
             "raw.fullAccountState",
             configure_type()
                 .derives(vec!["Deserialize"])
+                .field("extra_currencies", configure_field().skip().build())
+                .capture_unknown()
                 .field(
                     "balance",
                     configure_field()
@@ -121,6 +132,16 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             vec!["Clone", "Serialize", "new"],
         )
         .configure("getAccountState", vec!["Clone", "Serialize", "new"])
+        .configure_full(
+            "fullAccountState",
+            configure_type()
+                .derives(vec!["Clone", "Serialize", "Deserialize"])
+                .field(
+                    "account_state",
+                    configure_field().type_name("ParsedAccountState").build(),
+                )
+                .build(),
+        )
         .configure(
             "blocks.getMasterchainInfo",
             vec!["Clone", "Default", "Serialize", "new"],
@@ -135,6 +156,17 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         .configure("raw.sendMessageReturnHash", vec!["Serialize", "new"])
         .configure("smc.load", vec!["Clone", "Serialize", "new"])
         .configure("smc.runGetMethod", vec!["Clone", "Serialize", "new"])
+        .configure("getConfigParam", vec!["Clone", "Serialize", "new"])
+        .configure(
+            "blocks.getMasterchainBlockSignatures",
+            vec!["Clone", "Serialize", "new"],
+        )
+        .configure(
+            "blocks.getOutMsgQueueSizes",
+            vec!["Clone", "Serialize", "new"],
+        )
+        // account is typed and the workchain of the block is added, see `block`
+        .configure_full("blocks.shortTxId", configure_type().hand_written().build())
         .configure_full(
             "raw.getTransactionsV2",
             configure_type()
@@ -165,11 +197,15 @@ fn configure_field() -> FieldConfigurationBuilder {
 struct TypeConfigurationBuilder {
     derives: Vec<String>,
     fields: HashMap<String, FieldConfiguration>,
+    capture_unknown: bool,
+    hand_written: bool,
 }
 
 struct TypeConfiguration {
     pub derives: Vec<String>,
     pub fields: HashMap<String, FieldConfiguration>,
+    pub capture_unknown: bool,
+    pub hand_written: bool,
 }
 
 impl Default for TypeConfiguration {
@@ -182,6 +218,8 @@ impl Default for TypeConfiguration {
                 "Deserialize".to_owned(),
             ],
             fields: HashMap::new(),
+            capture_unknown: false,
+            hand_written: false,
         }
     }
 }
@@ -192,6 +230,7 @@ struct FieldConfigurationBuilder {
     optional: bool,
     deserialize_with: Option<String>,
     serialize_with: Option<String>,
+    type_name: Option<String>,
 }
 
 #[derive(Default)]
@@ -200,6 +239,7 @@ struct FieldConfiguration {
     pub optional: bool,
     pub deserialize_with: Option<String>,
     pub serialize_with: Option<String>,
+    pub type_name: Option<String>,
 }
 
 impl FieldConfigurationBuilder {
@@ -226,12 +266,20 @@ impl FieldConfigurationBuilder {
         self
     }
 
+    // a hand-written type in place of the one of the scheme, it's in scope of `block`
+    fn type_name(mut self, type_name: &str) -> Self {
+        self.type_name = Some(type_name.to_owned());
+
+        self
+    }
+
     fn build(self) -> FieldConfiguration {
         FieldConfiguration {
             skip: self.skip,
             optional: self.optional,
             deserialize_with: self.deserialize_with,
             serialize_with: self.serialize_with,
+            type_name: self.type_name,
         }
     }
 }
@@ -250,10 +298,26 @@ impl TypeConfigurationBuilder {
         self
     }
 
+    // keeps fields missing in the scheme (or skipped) in `unknown`, so they aren't silently dropped
+    fn capture_unknown(mut self) -> Self {
+        self.capture_unknown = true;
+
+        self
+    }
+
+    // the type isn't generated, a hand-written one of the same name is in scope of `block`
+    fn hand_written(mut self) -> Self {
+        self.hand_written = true;
+
+        self
+    }
+
     fn build(self) -> TypeConfiguration {
         TypeConfiguration {
             derives: self.derives,
             fields: self.fields,
+            capture_unknown: self.capture_unknown,
+            hand_written: self.hand_written,
         }
     }
 }
@@ -375,6 +439,9 @@ impl Generator {
 
                 let default = TypeConfiguration::default();
                 let configuration = self.types.get(definition.id()).unwrap_or(&default);
+                if configuration.hand_written {
+                    continue;
+                }
 
                 eprintln!("definition = {:?}", definition);
 
@@ -384,7 +451,7 @@ impl Generator {
                 let derives = format!("derive({})", configuration.derives.join(","));
                 let t = syn::parse_str::<MetaList>(&derives)?;
 
-                let fields: Vec<_> = definition
+                let mut fields: Vec<_> = definition
                     .fields()
                     .iter()
                     .filter(|field| {
@@ -408,10 +475,11 @@ impl Generator {
                         eprintln!("field = {:?}", field);
                         let field_name = format_ident!("{}", &field_name);
                         let mut deserialize_number_from_string = false; // TODO[akostylev0]
-                        let field_type: Box<dyn ToTokens> = if field
-                            .field_type()
-                            .is_some_and(|typ| typ == "#")
+                        let field_type: Box<dyn ToTokens> = if let Some(type_name) =
+                            &field_configuration.type_name
                         {
+                            Box::new(format_ident!("{}", type_name))
+                        } else if field.field_type().is_some_and(|typ| typ == "#") {
                             deserialize_number_from_string = true;
                             if field_configuration.optional {
                                 Box::new(
@@ -485,6 +553,13 @@ impl Generator {
                     })
                     .collect();
 
+                if configuration.capture_unknown {
+                    fields.push(quote! {
+                        #[serde(flatten)]
+                        pub unknown: UnknownFields
+                    });
+                }
+
                 let traits = if definition.is_functional() {
                     let result_name =
                         format_ident!("{}", generate_type_name(definition.result_type()));
@@ -30,7 +30,7 @@ async fn main() -> anyhow::Result<()> {
 
                                     tracing::info!(tx = ?tx);
 
-                                    let address = tx.into_internal_string(block.workchain);
+                                    let address = tx.address().to_string();
                                     match ton.get_account_state(&address).await {
                                         Ok(account) => {
                                             tracing::info!("{}: {}", &address, account.balance)
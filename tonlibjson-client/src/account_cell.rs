@@ -0,0 +1,478 @@
+use crate::boc::parse_base64_boc;
+use num_bigint::BigUint;
+use std::collections::BTreeMap;
+use toner::tlb::bits::bitvec::field::BitField;
+use toner::tlb::bits::bitvec::order::Msb0;
+use toner::tlb::bits::bitvec::vec::BitVec;
+use toner::tlb::bits::de::BitReaderExt;
+use toner::tlb::bits::r#as::{NBits, VarInt};
+use toner::tlb::bits::ser::pack;
+use toner::tlb::de::args::CellDeserializeWithArgs;
+use toner::tlb::de::{CellDeserialize, CellParser, CellParserError};
+use toner::tlb::r#as::{Data, NoArgs, Ref};
+use toner::tlb::{Cell, Error as _, StringError};
+use toner::ton::currency::Grams;
+use toner::ton::hashmap::HashmapE;
+use toner::ton::state_init::{StateInit, TickTock};
+use toner::ton::MsgAddress;
+
+/// Decodes `Account`, trying the current `StorageInfo` layout first
+pub fn parse_account(root: &Cell) -> anyhow::Result<Option<AccountState>> {
+    match root.parse_fully_with::<Account>(true) {
+        Ok(Account(state)) => Ok(state),
+        Err(_) => Ok(root.parse_fully_with::<Account>(false)?.0),
+    }
+}
+
+/// Decodes the base64 BoC of `ShardAccount`, as returned by `getShardAccountCell`
+pub fn parse_shard_account(boc: &str) -> anyhow::Result<Option<AccountState>> {
+    let ShardAccount(account) = parse_base64_boc(boc)?.parse_fully()?;
+
+    parse_account(&account)
+}
+
+/// Decodes the base64 BoC of either `Account` or `ShardAccount` offline, e.g. a state of
+/// account state proofs or of a dump
+pub fn parse_account_state(boc: &str) -> anyhow::Result<Option<AccountState>> {
+    let root = parse_base64_boc(boc)?;
+
+    parse_account(&root).or_else(|e| {
+        let ShardAccount(account) = root.parse_fully().map_err(|_| e)?;
+
+        parse_account(&account)
+    })
+}
+
+/// ```tlb
+/// account_descr$_ account:^Account last_trans_hash:bits256
+///   last_trans_lt:uint64 = ShardAccount;
+/// ```
+struct ShardAccount(Cell);
+
+impl<'de> CellDeserialize<'de> for ShardAccount {
+    fn parse(parser: &mut CellParser<'de>) -> Result<Self, CellParserError<'de>> {
+        let account = parser.parse_as::<_, Ref>()?;
+        let _last_trans_hash: [u8; 32] = parser.unpack()?;
+        let _last_trans_lt: u64 = parser.unpack()?;
+
+        Ok(Self(account))
+    }
+}
+
+/// Parts of `Account` which end up in `raw.fullAccountState`, plus tick-tock flags
+/// ```tlb
+/// account_none$0 = Account;
+/// account$1 addr:MsgAddressInt storage_stat:StorageInfo storage:AccountStorage = Account;
+///
+/// storage_info$_ used:StorageUsed storage_extra:StorageExtraInfo last_paid:uint32
+///   due_payment:(Maybe Grams) = StorageInfo;
+/// storage_used$_ cells:(VarUInteger 7) bits:(VarUInteger 7) = StorageUsed;
+/// storage_extra_none$000 = StorageExtraInfo;
+/// storage_extra_info$001 dict_hash:uint256 = StorageExtraInfo;
+///
+/// account_storage$_ last_trans_lt:uint64 balance:CurrencyCollection state:AccountState
+///   = AccountStorage;
+///
+/// account_uninit$00 = AccountState;
+/// account_active$1 _:StateInit = AccountState;
+/// account_frozen$01 state_hash:bits256 = AccountState;
+/// ```
+/// NOTE: `storage_extra` is missing and `StorageUsed` has `public_cells:(VarUInteger 7)`
+/// in accounts serialized before global version 10, `Args` tells which layout to expect
+struct Account(Option<AccountState>);
+
+#[derive(Debug, Clone)]
+pub struct AccountState {
+    pub address: MsgAddress,
+    pub storage: StorageStat,
+    pub last_trans_lt: u64,
+    pub balance: Balance,
+    /// `account_active`, the state init may have neither code nor data
+    pub active: bool,
+    pub code: Option<Cell>,
+    pub data: Option<Cell>,
+    pub frozen_hash: Option<[u8; 32]>,
+    /// set for special accounts of the masterchain, e.g. the config or the elector
+    pub special: Option<Special>,
+}
+
+/// `StorageInfo` of an account, storage fees are charged for `cells` and `bits`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageStat {
+    pub cells: u64,
+    pub bits: u64,
+    /// only in accounts serialized before global version 10
+    pub public_cells: Option<u64>,
+    /// unix time storage fees were last paid at
+    pub last_paid: u32,
+    /// storage fees the balance didn't cover
+    pub due_payment: Option<BigUint>,
+}
+
+/// `CurrencyCollection` of an account, extra currencies by their id
+/// ```tlb
+/// currencies$_ grams:Grams other:ExtraCurrencyCollection = CurrencyCollection;
+/// extra_currencies$_ dict:(HashmapE 32 (VarUInteger 32)) = ExtraCurrencyCollection;
+/// ```
+/// NOTE: toner reads `VarUInteger 32` with a 32 bits long length rather than 5 bits,
+/// which fails on any account holding extra currencies
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Balance {
+    pub grams: BigUint,
+    pub extra_currencies: BTreeMap<u32, BigUint>,
+}
+
+impl<'de> CellDeserialize<'de> for Balance {
+    fn parse(parser: &mut CellParser<'de>) -> Result<Self, CellParserError<'de>> {
+        let grams = parser.unpack_as::<_, Grams>()?;
+        let extra_currencies = parser
+            .parse_as_with::<Vec<(BitVec<u8, Msb0>, BigUint)>, HashmapE<NoArgs<_, Data<VarInt<5>>>>>((32, ()))?
+            .into_iter()
+            .map(|(id, amount)| (id.load_be::<u32>(), amount))
+            .collect();
+
+        Ok(Self {
+            grams,
+            extra_currencies,
+        })
+    }
+}
+
+fn var_u64(value: BigUint) -> Result<u64, StringError> {
+    u64::try_from(value).map_err(StringError::custom)
+}
+
+/// `TickTock` of a special account, its transactions run at the start and the end of each block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Special {
+    pub tick: bool,
+    pub tock: bool,
+}
+
+impl TryFrom<TickTock> for Special {
+    type Error = StringError;
+
+    /// `TickTock` keeps its fields private, they are read back from its bits
+    fn try_from(value: TickTock) -> Result<Self, Self::Error> {
+        let bits = pack(value)?;
+
+        Ok(Self {
+            tick: bits[0],
+            tock: bits[1],
+        })
+    }
+}
+
+impl<'de> CellDeserializeWithArgs<'de> for Account {
+    type Args = bool;
+
+    fn parse_with(
+        parser: &mut CellParser<'de>,
+        storage_extra: Self::Args,
+    ) -> Result<Self, CellParserError<'de>> {
+        if !parser.unpack::<bool>()? {
+            return Ok(Self(None));
+        }
+
+        let address: MsgAddress = parser.unpack()?;
+        let cells = var_u64(parser.unpack_as::<_, VarInt<3>>()?)?;
+        let bits = var_u64(parser.unpack_as::<_, VarInt<3>>()?)?;
+        let public_cells = if storage_extra {
+            match parser.unpack_as::<u8, NBits<3>>()? {
+                0b000 => {}
+                // `public_cells` of the old layout up to 255 reads as this tag, and toner
+                // panics on reading past the end of the cell
+                0b001 if parser.bits_left() < 256 => {
+                    return Err(StringError::custom("storage extra info is cut"))
+                }
+                0b001 => {
+                    let _dict_hash: [u8; 32] = parser.unpack()?;
+                }
+                tag => {
+                    return Err(StringError::custom(format!(
+                        "unsupported storage extra tag: {tag:#b}"
+                    )))
+                }
+            }
+
+            None
+        } else {
+            Some(var_u64(parser.unpack_as::<_, VarInt<3>>()?)?)
+        };
+        let last_paid = parser.unpack()?;
+        let due_payment = parser.unpack_as::<_, Option<Grams>>()?;
+
+        let last_trans_lt = parser.unpack()?;
+        let balance = parser.parse()?;
+
+        let active = parser.unpack::<bool>()?;
+        let (code, data, frozen_hash, special) = if active {
+            let state_init: StateInit = parser.parse()?;
+            let special = state_init
+                .special
+                .map(Special::try_from)
+                .transpose()
+                .map_err(StringError::custom)?;
+
+            (state_init.code, state_init.data, None, special)
+        } else if parser.unpack::<bool>()? {
+            (None, None, Some(parser.unpack()?), None)
+        } else {
+            (None, None, None, None)
+        };
+
+        Ok(Self(Some(AccountState {
+            address,
+            storage: StorageStat {
+                cells,
+                bits,
+                public_cells,
+                last_paid,
+                due_payment,
+            },
+            last_trans_lt,
+            balance,
+            active,
+            code,
+            data,
+            frozen_hash,
+            special,
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::boc::to_base64_boc;
+    use toner::tlb::bits::ser::BitWriterExt;
+    use toner::ton::currency::CurrencyCollection;
+
+    /// `account$1` up to its `AccountState`, in the current `StorageInfo` layout
+    fn account(lt: u64, grams: u32) -> toner::tlb::ser::CellBuilder {
+        let address: MsgAddress = "EQBGXZ9ddZeWypx8EkJieHJX75ct0bpkmu0Y4YoYr3NM0Z9e"
+            .parse()
+            .unwrap();
+        let mut builder = Cell::builder();
+        builder
+            .pack(true)
+            .unwrap()
+            .pack(address)
+            .unwrap()
+            // cells, bits
+            .pack_as::<_, VarInt<3>>(BigUint::from(1_u8))
+            .unwrap()
+            .pack_as::<_, VarInt<3>>(BigUint::from(8_u8))
+            .unwrap()
+            // storage_extra_none
+            .pack_as::<_, NBits<3>>(0_u8)
+            .unwrap()
+            // last_paid, due_payment
+            .pack(1_u32)
+            .unwrap()
+            .pack(false)
+            .unwrap()
+            // last_trans_lt
+            .pack(lt)
+            .unwrap()
+            .store(CurrencyCollection {
+                grams: BigUint::from(grams),
+                ..Default::default()
+            })
+            .unwrap();
+
+        builder
+    }
+
+    /// `account$1` of a wallet in the `StorageInfo` layout before global version 10,
+    /// holding 5 of the extra currency 239
+    fn old_wallet() -> Cell {
+        let address: MsgAddress = "EQBGXZ9ddZeWypx8EkJieHJX75ct0bpkmu0Y4YoYr3NM0Z9e"
+            .parse()
+            .unwrap();
+        let mut extra_currencies = Cell::builder();
+        extra_currencies
+            // hml_long$10, the whole key
+            .pack_as::<_, NBits<2>>(0b10_u8)
+            .unwrap()
+            .pack_as::<_, NBits<6>>(32_u8)
+            .unwrap()
+            .pack(239_u32)
+            .unwrap()
+            .pack_as::<_, VarInt<5>>(BigUint::from(5_u8))
+            .unwrap();
+        let mut code = Cell::builder();
+        code.pack(0xff00_u16).unwrap();
+
+        let mut builder = Cell::builder();
+        builder
+            .pack(true)
+            .unwrap()
+            .pack(address)
+            .unwrap()
+            // cells, bits, public_cells
+            .pack_as::<_, VarInt<3>>(BigUint::from(3_u8))
+            .unwrap()
+            .pack_as::<_, VarInt<3>>(BigUint::from(1000_u16))
+            .unwrap()
+            .pack_as::<_, VarInt<3>>(BigUint::from(2_u8))
+            .unwrap()
+            // last_paid, due_payment
+            .pack(1_700_000_000_u32)
+            .unwrap()
+            .pack_as::<_, Option<Grams>>(Some(BigUint::from(7_u8)))
+            .unwrap()
+            // last_trans_lt
+            .pack(42_u64)
+            .unwrap()
+            .pack_as::<_, Grams>(BigUint::from(1000_u32))
+            .unwrap()
+            .pack(true)
+            .unwrap()
+            .store_as::<_, Ref>(extra_currencies.into_cell())
+            .unwrap()
+            // account_active without split_depth and special
+            .pack([true, false, false])
+            .unwrap()
+            .store_as::<_, Option<Ref>>(Some(code.into_cell()))
+            .unwrap()
+            .store_as::<_, Option<Ref>>(None::<Cell>)
+            .unwrap()
+            // library
+            .pack(false)
+            .unwrap();
+
+        builder.into_cell()
+    }
+
+    #[test]
+    fn parse_account_state_offline() {
+        let boc = to_base64_boc(old_wallet()).unwrap();
+
+        let state = parse_account_state(&boc).unwrap().unwrap();
+
+        assert_eq!(
+            state.storage,
+            StorageStat {
+                cells: 3,
+                bits: 1000,
+                public_cells: Some(2),
+                last_paid: 1_700_000_000,
+                due_payment: Some(BigUint::from(7_u8)),
+            }
+        );
+        assert_eq!(
+            state.balance,
+            Balance {
+                grams: BigUint::from(1000_u32),
+                extra_currencies: BTreeMap::from([(239, BigUint::from(5_u8))]),
+            }
+        );
+        assert!(state.active);
+        assert!(state.code.is_some());
+        assert!(state.data.is_none());
+    }
+
+    #[test]
+    fn parse_account_state_of_shard_account() {
+        let mut shard_account = Cell::builder();
+        shard_account
+            .store_as::<_, Ref>(old_wallet())
+            .unwrap()
+            .pack([0_u8; 32])
+            .unwrap()
+            .pack(42_u64)
+            .unwrap();
+        let boc = to_base64_boc(shard_account.into_cell()).unwrap();
+
+        let state = parse_account_state(&boc).unwrap().unwrap();
+
+        assert_eq!(state.last_trans_lt, 42);
+        assert_eq!(state.storage.public_cells, Some(2));
+        assert!(parse_account_state("te6cckEBAQEAAgAAAEysuc0=").is_err());
+    }
+
+    #[test]
+    fn parse_uninit_account() {
+        let mut builder = account(42, 1000);
+        // account_uninit
+        builder.pack_as::<_, NBits<2>>(0_u8).unwrap();
+
+        let state = parse_account(&builder.into_cell()).unwrap().unwrap();
+
+        assert_eq!(state.last_trans_lt, 42);
+        assert_eq!(state.balance.grams, BigUint::from(1000_u32));
+        assert!(!state.active);
+        assert!(state.code.is_none());
+        assert!(state.frozen_hash.is_none());
+        assert!(state.special.is_none());
+    }
+
+    #[test]
+    fn parse_account_none() {
+        let mut builder = Cell::builder();
+        builder.pack(false).unwrap();
+
+        assert!(parse_account(&builder.into_cell()).unwrap().is_none());
+    }
+
+    #[test]
+    fn parse_frozen_account() {
+        let mut builder = account(42, 1000);
+        // account_frozen
+        builder
+            .pack_as::<_, NBits<2>>(0b01_u8)
+            .unwrap()
+            .pack([7_u8; 32])
+            .unwrap();
+
+        let state = parse_account(&builder.into_cell()).unwrap().unwrap();
+
+        assert_eq!(state.frozen_hash, Some([7; 32]));
+        assert!(state.special.is_none());
+    }
+
+    #[test]
+    fn parse_special_shard_account() {
+        let mut code = Cell::builder();
+        code.pack(0xff00_u16).unwrap();
+        let mut builder = account(42, 1000);
+        // account_active
+        builder.pack(true).unwrap();
+        builder
+            // split_depth, special: tick only, as the elector
+            .pack_as::<_, Option<NBits<5>>>(None::<u8>)
+            .unwrap()
+            .pack([true, true, false])
+            .unwrap()
+            .store_as::<_, Option<Ref>>(Some(code.into_cell()))
+            .unwrap()
+            .store_as::<_, Option<Ref>>(None::<Cell>)
+            .unwrap()
+            // library
+            .pack(false)
+            .unwrap();
+
+        let mut shard_account = Cell::builder();
+        shard_account
+            .store_as::<_, Ref>(builder.into_cell())
+            .unwrap()
+            .pack([0_u8; 32])
+            .unwrap()
+            .pack(42_u64)
+            .unwrap();
+        let boc = to_base64_boc(shard_account.into_cell()).unwrap();
+
+        let state = parse_shard_account(&boc).unwrap().unwrap();
+
+        assert_eq!(
+            state.special,
+            Some(Special {
+                tick: true,
+                tock: false
+            })
+        );
+        assert!(state.code.is_some());
+        assert!(state.data.is_none());
+    }
+}
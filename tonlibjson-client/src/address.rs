@@ -3,7 +3,7 @@ use base64::display::Base64Display;
 use base64::Engine;
 use bytes::BufMut;
 use crc::Crc;
-use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use serde::{de, ser, Deserialize, Deserializer, Serialize, Serializer};
 use std::fmt;
 use std::fmt::{Debug, Display, Formatter};
 use std::str::FromStr;
@@ -149,7 +149,8 @@ impl AccountAddressData {
     }
 }
 
-#[derive(Clone, Ord, PartialOrd, Eq, PartialEq)]
+/// Account id without its workchain, in base64 as tonlib gives it
+#[derive(Clone, Default, Ord, PartialOrd, Eq, PartialEq, Hash)]
 pub struct ShardContextAccountAddress {
     pub bytes: [u8; 32],
 }
@@ -157,22 +158,26 @@ pub struct ShardContextAccountAddress {
 impl FromStr for ShardContextAccountAddress {
     type Err = anyhow::Error;
 
+    /// Base64 or 64 hex digits, decoded without allocating
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let bytes = base64::engine::general_purpose::STANDARD
-            .decode(s)
-            .with_context(|| format!("input string is {}", s))?;
+        let mut bytes = [0; 32];
+        if s.len() == 64 && s.bytes().all(|b| b.is_ascii_hexdigit()) {
+            hex::decode_to_slice(s, &mut bytes)?;
 
-        if bytes.len() != 32 {
-            return Err(anyhow!(
-                "invalid length, expected 32 got {} bytes",
-                bytes.len()
-            ));
+            return Ok(Self { bytes });
         }
 
-        let mut buf = [0; 32];
-        buf.copy_from_slice(&bytes);
+        // base64 decoding asks for room of its estimate, a byte more than 32
+        let mut buf = [0; 48];
+        let len = base64::engine::general_purpose::STANDARD
+            .decode_slice(s, &mut buf)
+            .with_context(|| format!("input string is {}", s))?;
+        if len != 32 {
+            return Err(anyhow!("invalid length, expected 32 got {} bytes", len));
+        }
+        bytes.copy_from_slice(&buf[..32]);
 
-        Ok(Self { bytes: buf })
+        Ok(Self { bytes })
     }
 }
 
@@ -187,7 +192,12 @@ impl Serialize for ShardContextAccountAddress {
     where
         S: Serializer,
     {
-        serializer.serialize_str(self.to_string().as_str())
+        let mut buf = [0; 44];
+        let len = base64::engine::general_purpose::STANDARD
+            .encode_slice(self.bytes, &mut buf)
+            .map_err(ser::Error::custom)?;
+
+        serializer.serialize_str(ascii(&buf[..len]))
     }
 }
 
@@ -211,6 +221,16 @@ impl Debug for ShardContextAccountAddress {
 }
 
 impl ShardContextAccountAddress {
+    pub fn to_hex(&self) -> String {
+        self.hex().to_string()
+    }
+
+    /// Hex of the account, displayed or serialized without allocating
+    #[inline]
+    pub fn hex(&self) -> Hex<'_> {
+        Hex(&self.bytes)
+    }
+
     pub fn into_internal(self, chain_id: i32) -> InternalAccountAddress {
         InternalAccountAddress {
             chain_id,
@@ -235,10 +255,46 @@ impl Debug for InternalAccountAddress {
 
 impl Display for InternalAccountAddress {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "{}:{}", self.chain_id, hex::encode(self.bytes))
+        write!(f, "{}:{}", self.chain_id, Hex(&self.bytes))
     }
 }
 
+/// Lowercase hex of 32 bytes, encoded on the stack
+#[derive(Clone, Copy)]
+pub struct Hex<'a>(pub &'a [u8; 32]);
+
+impl Hex<'_> {
+    #[inline]
+    fn encode(&self) -> [u8; 64] {
+        let mut buf = [0; 64];
+        // the buffer is exactly twice as long as the bytes
+        let _ = hex::encode_to_slice(self.0, &mut buf);
+
+        buf
+    }
+}
+
+impl Display for Hex<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(ascii(&self.encode()))
+    }
+}
+
+impl Serialize for Hex<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(ascii(&self.encode()))
+    }
+}
+
+/// Output of hex and base64 encoders is ASCII
+#[inline]
+fn ascii(buf: &[u8]) -> &str {
+    std::str::from_utf8(buf).unwrap_or_default()
+}
+
 #[cfg(test)]
 mod tests {
     use crate::address::{AccountAddressData, InternalAccountAddress, ShardContextAccountAddress};
@@ -253,6 +309,41 @@ mod tests {
         assert_eq!("AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=", actual)
     }
 
+    #[test]
+    fn shard_context_account_data_from_base64_and_hex() {
+        let base64 =
+            ShardContextAccountAddress::from_str("o5NYYfedr1mhPW0YLhZAIQwC+Y498Y/adLj1qxQavxg=")
+                .unwrap();
+        let hex = ShardContextAccountAddress::from_str(
+            "a3935861f79daf59a13d6d182e1640210c02f98e3df18fda74b8f5ab141abf18",
+        )
+        .unwrap();
+
+        assert_eq!(base64, hex);
+        assert_eq!(
+            hex.to_hex(),
+            "a3935861f79daf59a13d6d182e1640210c02f98e3df18fda74b8f5ab141abf18"
+        );
+        assert!(ShardContextAccountAddress::from_str("AAAA").is_err());
+        assert!(ShardContextAccountAddress::from_str(&"A".repeat(88)).is_err());
+    }
+
+    #[test]
+    fn shard_context_account_data_serialize() {
+        let address =
+            ShardContextAccountAddress::from_str("o5NYYfedr1mhPW0YLhZAIQwC+Y498Y/adLj1qxQavxg=")
+                .unwrap();
+
+        assert_eq!(
+            serde_json::to_string(&address).unwrap(),
+            "\"o5NYYfedr1mhPW0YLhZAIQwC+Y498Y/adLj1qxQavxg=\""
+        );
+        assert_eq!(
+            serde_json::to_string(&address.hex()).unwrap(),
+            "\"a3935861f79daf59a13d6d182e1640210c02f98e3df18fda74b8f5ab141abf18\""
+        );
+    }
+
     #[test]
     fn internal_account_address_to_string() {
         let input = InternalAccountAddress {
@@ -1,20 +1,33 @@
-use crate::address::{AccountAddressData, InternalAccountAddress, ShardContextAccountAddress};
+use crate::address::{
+    AccountAddressData, Hex, InternalAccountAddress, ShardContextAccountAddress,
+};
+use crate::breaker::ToBreaker;
+pub use crate::deserialize::UnknownFields;
 use crate::deserialize::{
     deserialize_default_as_none, deserialize_empty_as_none, deserialize_number_from_string,
-    deserialize_ton_account_balance, serialize_none_as_empty,
+    deserialize_ton_account_balance, serialize_none_as_empty, serialize_number_as_string,
 };
+pub use crate::parsed_account::ParsedAccountState;
 use crate::request::Requestable;
 use anyhow::anyhow;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
 use derive_new::new;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::borrow::Cow;
 use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::error::Error as StdError;
 use std::fmt::{Display, Formatter};
 use std::str::FromStr;
 use std::time::Duration;
 use ton_client_util::router::route::{BlockCriteria, Route, ToRoute};
 use ton_client_util::service::timeout::ToTimeout;
+use toner::tlb::bits::bitvec::field::BitField;
+use toner::tlb::bits::de::unpack_bytes;
+use toner::ton::boc::BoC;
 
 pub trait Functional {
     type Result;
@@ -48,6 +61,8 @@ impl ToRoute for BlocksGetBlockHeader {
 
 impl ToTimeout for BlocksGetBlockHeader {}
 
+impl ToBreaker for BlocksGetBlockHeader {}
+
 impl From<TonBlockIdExt> for TonBlockId {
     fn from(block: TonBlockIdExt) -> Self {
         TonBlockId {
@@ -68,19 +83,167 @@ impl From<BlocksHeader> for TonBlockId {
     }
 }
 
+/// Transaction of a block listing, `mode` tells which of its fields the lite server has filled
+#[derive(Debug, Clone)]
+pub struct BlocksShortTxId {
+    pub mode: Int31,
+    /// of the block listing the transaction, it isn't a field of the tonlib response
+    pub workchain: Int32,
+    pub account: ShardContextAccountAddress,
+    pub lt: Int64,
+    pub hash: Bytes,
+}
+
 impl BlocksShortTxId {
-    pub fn account(&self) -> &str {
+    const HAS_ACCOUNT: Int31 = 1;
+    const HAS_LT: Int31 = 2;
+    const HAS_HASH: Int31 = 4;
+
+    pub fn account(&self) -> &ShardContextAccountAddress {
         &self.account
     }
 
-    pub fn into_internal(self, chain_id: i32) -> InternalAccountAddress {
-        ShardContextAccountAddress::from_str(&self.account)
-            .unwrap()
-            .into_internal(chain_id)
+    pub fn address(&self) -> InternalAccountAddress {
+        self.account.clone().into_internal(self.workchain)
+    }
+
+    pub fn has_account(&self) -> bool {
+        self.mode & Self::HAS_ACCOUNT != 0
+    }
+
+    pub fn has_lt(&self) -> bool {
+        self.mode & Self::HAS_LT != 0
+    }
+
+    pub fn has_hash(&self) -> bool {
+        self.mode & Self::HAS_HASH != 0
+    }
+
+    /// `legacy` JSON has the fields of tonlib, `mode` and a base64 account, for consumers
+    /// of the JSON written before the workchain and the flags of `mode` were added,
+    /// [`Serialize`] writes the other shape
+    pub fn to_json(&self, legacy: bool) -> Value {
+        let json = if legacy {
+            serde_json::to_value(self.legacy_json())
+        } else {
+            serde_json::to_value(self.json())
+        };
+
+        json.unwrap_or_default()
+    }
+
+    #[inline]
+    fn json(&self) -> ShortTxIdJson<'_> {
+        ShortTxIdJson {
+            r#type: "blocks.shortTxId",
+            workchain: self.workchain,
+            account: self.account.hex(),
+            lt: self.lt,
+            hash: &self.hash,
+            has_account: self.has_account(),
+            has_lt: self.has_lt(),
+            has_hash: self.has_hash(),
+        }
+    }
+
+    #[inline]
+    fn legacy_json(&self) -> LegacyShortTxIdJson<'_> {
+        LegacyShortTxIdJson {
+            r#type: "blocks.shortTxId",
+            mode: self.mode,
+            account: &self.account,
+            lt: self.lt,
+            hash: &self.hash,
+        }
+    }
+}
+
+/// Fields of [`BlocksShortTxId`] borrowed to be written straight into the serializer
+#[derive(Serialize)]
+struct ShortTxIdJson<'a> {
+    #[serde(rename = "@type")]
+    r#type: &'static str,
+    workchain: Int32,
+    account: Hex<'a>,
+    #[serde(serialize_with = "serialize_number_as_string")]
+    lt: Int64,
+    hash: &'a str,
+    has_account: bool,
+    has_lt: bool,
+    has_hash: bool,
+}
+
+#[derive(Serialize)]
+struct LegacyShortTxIdJson<'a> {
+    #[serde(rename = "@type")]
+    r#type: &'static str,
+    mode: Int31,
+    account: &'a ShardContextAccountAddress,
+    lt: Int64,
+    hash: &'a str,
+}
+
+impl Serialize for BlocksShortTxId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.json().serialize(serializer)
+    }
+}
+
+/// Either shape: tonlib's one with `mode`, or the one with the flags of `mode`
+impl<'de> Deserialize<'de> for BlocksShortTxId {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct Fields<'a> {
+            mode: Option<Int31>,
+            #[serde(default)]
+            workchain: Int32,
+            #[serde(default, borrow)]
+            account: Cow<'a, str>,
+            #[serde(default, deserialize_with = "deserialize_number_from_string")]
+            lt: Int64,
+            #[serde(default)]
+            hash: Bytes,
+            has_account: Option<bool>,
+            has_lt: Option<bool>,
+            has_hash: Option<bool>,
+        }
+
+        let fields = Fields::deserialize(deserializer)?;
+        let account = if fields.account.is_empty() {
+            ShardContextAccountAddress::default()
+        } else {
+            ShardContextAccountAddress::from_str(&fields.account)
+                .map_err(serde::de::Error::custom)?
+        };
+        let mode = fields.mode.unwrap_or_else(|| {
+            [
+                (fields.has_account, Self::HAS_ACCOUNT),
+                (fields.has_lt, Self::HAS_LT),
+                (fields.has_hash, Self::HAS_HASH),
+            ]
+            .into_iter()
+            .filter(|(has, _)| has.unwrap_or_default())
+            .fold(0, |mode, (_, flag)| mode | flag)
+        });
+
+        Ok(Self {
+            mode,
+            workchain: fields.workchain,
+            account,
+            lt: fields.lt,
+            hash: fields.hash,
+        })
     }
+}
+
+impl BlocksTransactions {
+    /// Transactions of the block get its workchain, tonlib lists them by account alone
+    pub(crate) fn with_workchain(mut self) -> Self {
+        for tx in self.transactions.iter_mut() {
+            tx.workchain = self.id.workchain;
+        }
 
-    pub fn into_internal_string(self, chain_id: i32) -> String {
-        self.into_internal(chain_id).to_string()
+        self
     }
 }
 
@@ -145,6 +308,8 @@ impl ToRoute for GetShardAccountCell {
 
 impl ToTimeout for GetShardAccountCell {}
 
+impl ToBreaker for GetShardAccountCell {}
+
 impl ToRoute for GetShardAccountCellByTransaction {
     fn to_route(&self) -> Route {
         let data = self
@@ -164,6 +329,8 @@ impl ToRoute for GetShardAccountCellByTransaction {
 
 impl ToTimeout for GetShardAccountCellByTransaction {}
 
+impl ToBreaker for GetShardAccountCellByTransaction {}
+
 impl ToRoute for RawGetAccountState {
     fn to_route(&self) -> Route {
         Route::Latest
@@ -172,6 +339,8 @@ impl ToRoute for RawGetAccountState {
 
 impl ToTimeout for RawGetAccountState {}
 
+impl ToBreaker for RawGetAccountState {}
+
 impl ToRoute for RawGetAccountStateByTransaction {
     fn to_route(&self) -> Route {
         let data = self
@@ -191,6 +360,8 @@ impl ToRoute for RawGetAccountStateByTransaction {
 
 impl ToTimeout for RawGetAccountStateByTransaction {}
 
+impl ToBreaker for RawGetAccountStateByTransaction {}
+
 impl ToRoute for GetAccountState {
     fn to_route(&self) -> Route {
         Route::Latest
@@ -199,6 +370,8 @@ impl ToRoute for GetAccountState {
 
 impl ToTimeout for GetAccountState {}
 
+impl ToBreaker for GetAccountState {}
+
 impl ToRoute for BlocksGetMasterchainInfo {
     fn to_route(&self) -> Route {
         Route::Latest
@@ -207,6 +380,8 @@ impl ToRoute for BlocksGetMasterchainInfo {
 
 impl ToTimeout for BlocksGetMasterchainInfo {}
 
+impl ToBreaker for BlocksGetMasterchainInfo {}
+
 impl ToRoute for BlocksLookupBlock {
     fn to_route(&self) -> Route {
         let criteria = match self.mode {
@@ -234,6 +409,8 @@ impl ToRoute for BlocksLookupBlock {
 
 impl ToTimeout for BlocksLookupBlock {}
 
+impl ToBreaker for BlocksLookupBlock {}
+
 impl BlocksLookupBlock {
     pub fn seqno(id: TonBlockId) -> Self {
         Self {
@@ -268,6 +445,8 @@ impl ToRoute for BlocksGetShards {
 
 impl ToTimeout for BlocksGetShards {}
 
+impl ToBreaker for BlocksGetShards {}
+
 impl BlocksGetTransactionsExt {
     pub fn unverified(
         block_id: TonBlockIdExt,
@@ -319,6 +498,8 @@ impl ToRoute for BlocksGetTransactionsExt {
 
 impl ToTimeout for BlocksGetTransactionsExt {}
 
+impl ToBreaker for BlocksGetTransactionsExt {}
+
 impl BlocksGetTransactions {
     pub fn unverified(
         block_id: TonBlockIdExt,
@@ -370,6 +551,8 @@ impl ToRoute for BlocksGetTransactions {
 
 impl ToTimeout for BlocksGetTransactions {}
 
+impl ToBreaker for BlocksGetTransactions {}
+
 impl Default for BlocksAccountTransactionId {
     fn default() -> Self {
         Self {
@@ -406,6 +589,60 @@ impl TryFrom<&RawTransaction> for BlocksAccountTransactionId {
     }
 }
 
+impl RawMessage {
+    /// Extra currencies carried by the message: currency id -> amount
+    pub fn extra_currencies(&self) -> anyhow::Result<HashMap<i32, String>> {
+        extra_currencies(&self.unknown)
+    }
+
+    /// Hash of the message, only reported by tonlib versions which know it
+    pub fn hash(&self) -> Option<&str> {
+        self.unknown.get("hash").and_then(Value::as_str)
+    }
+
+    /// Bounced messages carry 0xffffffff followed by the original body
+    pub fn is_bounce(&self) -> bool {
+        let MsgBoxedData::MsgDataRaw(MsgDataRaw { body, .. }) = &self.msg_data else {
+            return false;
+        };
+        let Some(root) = STANDARD
+            .decode(body)
+            .ok()
+            .and_then(|body| unpack_bytes::<BoC>(body).ok())
+            .and_then(|boc| boc.single_root().cloned())
+        else {
+            return false;
+        };
+
+        root.data.len() >= 32 && root.data[..32].load_be::<u32>() == 0xffffffff
+    }
+}
+
+impl RawFullAccountState {
+    /// Extra currencies held by the account: currency id -> amount
+    pub fn extra_currencies(&self) -> anyhow::Result<HashMap<i32, String>> {
+        extra_currencies(&self.unknown)
+    }
+}
+
+fn extra_currencies(fields: &UnknownFields) -> anyhow::Result<HashMap<i32, String>> {
+    #[derive(Deserialize)]
+    struct Entry {
+        #[serde(deserialize_with = "deserialize_number_from_string")]
+        id: i32,
+        amount: String,
+    }
+
+    let Some(value) = fields.get("extra_currencies") else {
+        return Ok(HashMap::default());
+    };
+
+    Ok(Vec::<Entry>::deserialize(value)?
+        .into_iter()
+        .map(|e| (e.id, e.amount))
+        .collect())
+}
+
 impl ToRoute for RawSendMessage {
     fn to_route(&self) -> Route {
         Route::Latest
@@ -414,6 +651,12 @@ impl ToRoute for RawSendMessage {
 
 impl ToTimeout for RawSendMessage {}
 
+impl ToBreaker for RawSendMessage {
+    fn is_send(&self) -> bool {
+        true
+    }
+}
+
 impl ToRoute for RawSendMessageReturnHash {
     fn to_route(&self) -> Route {
         Route::Latest
@@ -422,6 +665,12 @@ impl ToRoute for RawSendMessageReturnHash {
 
 impl ToTimeout for RawSendMessageReturnHash {}
 
+impl ToBreaker for RawSendMessageReturnHash {
+    fn is_send(&self) -> bool {
+        true
+    }
+}
+
 impl ToRoute for SmcLoad {
     fn to_route(&self) -> Route {
         Route::Latest
@@ -430,6 +679,71 @@ impl ToRoute for SmcLoad {
 
 impl ToTimeout for SmcLoad {}
 
+impl ToBreaker for SmcLoad {}
+
+impl ToRoute for GetConfigParam {
+    fn to_route(&self) -> Route {
+        Route::Latest
+    }
+}
+
+impl ToTimeout for GetConfigParam {}
+
+impl ToBreaker for GetConfigParam {}
+
+impl ToRoute for GetConfigAll {
+    fn to_route(&self) -> Route {
+        Route::Latest
+    }
+}
+
+impl ToTimeout for GetConfigAll {}
+
+impl ToBreaker for GetConfigAll {}
+
+impl ToRoute for BlocksGetMasterchainBlockSignatures {
+    fn to_route(&self) -> Route {
+        Route::Block {
+            chain: -1,
+            criteria: BlockCriteria::Seqno {
+                shard: i64::MIN,
+                seqno: self.seqno,
+            },
+        }
+    }
+}
+
+impl ToTimeout for BlocksGetMasterchainBlockSignatures {}
+
+impl ToBreaker for BlocksGetMasterchainBlockSignatures {}
+
+/// Proofs reach as deep as the proven block, lite servers which don't have it can't build them
+impl ToRoute for BlocksGetShardBlockProof {
+    fn to_route(&self) -> Route {
+        Route::Block {
+            chain: self.id.workchain,
+            criteria: BlockCriteria::Seqno {
+                shard: self.id.shard,
+                seqno: self.id.seqno,
+            },
+        }
+    }
+}
+
+impl ToTimeout for BlocksGetShardBlockProof {}
+
+impl ToBreaker for BlocksGetShardBlockProof {}
+
+impl ToRoute for BlocksGetOutMsgQueueSizes {
+    fn to_route(&self) -> Route {
+        Route::Latest
+    }
+}
+
+impl ToTimeout for BlocksGetOutMsgQueueSizes {}
+
+impl ToBreaker for BlocksGetOutMsgQueueSizes {}
+
 impl SmcBoxedMethodId {
     pub fn by_name(name: &str) -> Self {
         Self::SmcMethodIdName(SmcMethodIdName {
@@ -440,6 +754,8 @@ impl SmcBoxedMethodId {
 
 impl ToTimeout for SmcBoxedMethodId {}
 
+impl ToBreaker for SmcBoxedMethodId {}
+
 impl<T> Requestable for T
 where
     T: Functional + Serialize,
@@ -467,18 +783,35 @@ impl ToRoute for RawGetTransactionsV2 {
 
 impl ToTimeout for RawGetTransactionsV2 {}
 
+impl ToBreaker for RawGetTransactionsV2 {}
+
 impl ToTimeout for Sync {
     fn to_timeout(&self) -> Option<Duration> {
         Some(Duration::from_secs(5 * 60))
     }
 }
 
+impl ToBreaker for Sync {}
+
 #[derive(Debug, Deserialize)]
 pub struct TonError {
     code: i32,
     message: String,
 }
 
+impl TonError {
+    pub fn new(code: i32, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn code(&self) -> i32 {
+        self.code
+    }
+}
+
 impl Display for TonError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -518,6 +851,15 @@ where
     }
 }
 
+impl<T> ToBreaker for WithBlock<T>
+where
+    T: ToBreaker,
+{
+    fn is_send(&self) -> bool {
+        self.function.is_send()
+    }
+}
+
 impl<T: Functional> ToRoute for WithBlock<T> {
     fn to_route(&self) -> Route {
         Route::Block {
@@ -545,6 +887,78 @@ mod tests {
         assert!(address.account_address.is_none())
     }
 
+    fn short_tx_id() -> BlocksShortTxId {
+        let mut id: BlocksShortTxId = serde_json::from_value(json!({
+            "@type": "blocks.shortTxId",
+            "mode": 7,
+            "account": "o5NYYfedr1mhPW0YLhZAIQwC+Y498Y/adLj1qxQavxg=",
+            "lt": "33756943000007",
+            "hash": "752Szayka+Eh54Zvco5l84d6WL+zJFmyh1wqRxD08Uo=",
+        }))
+        .unwrap();
+        id.workchain = -1;
+
+        id
+    }
+
+    #[test]
+    fn short_tx_id_json() {
+        let id = short_tx_id();
+
+        assert_eq!(
+            id.to_json(false),
+            json!({
+                "@type": "blocks.shortTxId",
+                "workchain": -1,
+                "account": "a3935861f79daf59a13d6d182e1640210c02f98e3df18fda74b8f5ab141abf18",
+                "lt": "33756943000007",
+                "hash": "752Szayka+Eh54Zvco5l84d6WL+zJFmyh1wqRxD08Uo=",
+                "has_account": true,
+                "has_lt": true,
+                "has_hash": true,
+            })
+        );
+        assert_eq!(
+            id.address().to_string(),
+            "-1:a3935861f79daf59a13d6d182e1640210c02f98e3df18fda74b8f5ab141abf18"
+        );
+
+        let parsed: BlocksShortTxId = serde_json::from_value(id.to_json(false)).unwrap();
+        assert_eq!(parsed, id);
+        assert_eq!(parsed.mode, 7);
+        assert_eq!(parsed.workchain, -1);
+    }
+
+    #[test]
+    fn short_tx_id_legacy_json() {
+        let id = short_tx_id();
+
+        assert_eq!(
+            id.to_json(true),
+            json!({
+                "@type": "blocks.shortTxId",
+                "mode": 7,
+                "account": "o5NYYfedr1mhPW0YLhZAIQwC+Y498Y/adLj1qxQavxg=",
+                "lt": 33756943000007_i64,
+                "hash": "752Szayka+Eh54Zvco5l84d6WL+zJFmyh1wqRxD08Uo=",
+            })
+        );
+
+        let parsed: BlocksShortTxId = serde_json::from_value(id.to_json(true)).unwrap();
+        assert_eq!(parsed, id);
+        assert_eq!(parsed.mode, 7);
+    }
+
+    #[test]
+    fn short_tx_id_mode_flags() {
+        let mut id = short_tx_id();
+        id.mode = 2;
+
+        assert!(!id.has_account());
+        assert!(id.has_lt());
+        assert!(!id.has_hash());
+    }
+
     #[test]
     fn serialize_account_address_empty() {
         let address = AccountAddress {
@@ -655,6 +1069,55 @@ mod tests {
         assert_eq!(serde_json::to_string(&list).unwrap(), "{\"@type\":\"tvm.stackEntryList\",\"list\":{\"@type\":\"tvm.list\",\"elements\":[{\"@type\":\"tvm.stackEntrySlice\",\"slice\":{\"@type\":\"tvm.slice\",\"bytes\":\"test\"}},{\"@type\":\"tvm.stackEntryTuple\",\"tuple\":{\"@type\":\"tvm.tuple\",\"elements\":[{\"@type\":\"tvm.stackEntrySlice\",\"slice\":{\"@type\":\"tvm.slice\",\"bytes\":\"test\"}},{\"@type\":\"tvm.stackEntryCell\",\"cell\":{\"@type\":\"tvm.cell\",\"bytes\":\"test\"}}]}}]}}");
     }
 
+    #[test]
+    fn raw_message_extra_currencies() {
+        let json = json!({
+            "@type": "raw.message",
+            "hash": "cAHTfRmR5Ibe3Q0H0dhnFTNmAyQ6wg/nBdD1D8fzdcg=",
+            "source": {"@type": "accountAddress", "account_address": "kQCSES0TZYqcVkgoguhIb8iMEo4cvaEwmIrU5qbQgnN8fo2A"},
+            "destination": {"@type": "accountAddress", "account_address": "0QAdFQdB4UJH1XyIaOy_5bkMZCT7xdJTMQYeVeOFl3G2t8Lb"},
+            "value": "1000000000",
+            "extra_currencies": [{"@type": "extraCurrency", "id": 100, "amount": "2500000"}],
+            "fwd_fee": "266669",
+            "ihr_fee": "0",
+            "created_lt": "30021934000002",
+            "body_hash": "lqKW0iTyhcZ77pPDD4owkVfw2qNdxbh+QQt4YwoJz8c=",
+            "msg_data": {"@type": "msg.dataRaw", "body": "te6cckEBAQEAAgAAAEysuc0=", "init_state": ""}
+        });
+
+        let message = serde_json::from_value::<RawMessage>(json).unwrap();
+
+        assert_eq!(
+            message.extra_currencies().unwrap(),
+            HashMap::from([(100, "2500000".to_owned())])
+        );
+        assert!(message.unknown.get("hash").is_some());
+        assert!(message.unknown.get("@type").is_none());
+        assert_eq!(
+            serde_json::to_value(&message).unwrap()["extra_currencies"][0]["amount"],
+            "2500000"
+        );
+    }
+
+    #[test]
+    fn raw_full_account_state_without_extra_currencies() {
+        let json = json!({
+            "@type": "raw.fullAccountState",
+            "balance": "-1",
+            "code": "",
+            "data": "",
+            "last_transaction_id": {"@type": "internal.transactionId", "lt": "0", "hash": "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA="},
+            "block_id": {"@type": "ton.blockIdExt", "workchain": -1, "shard": "-9223372036854775808", "seqno": 1, "root_hash": "", "file_hash": ""},
+            "frozen_hash": "",
+            "sync_utime": "1700000000"
+        });
+
+        let state = serde_json::from_value::<RawFullAccountState>(json).unwrap();
+
+        assert!(state.extra_currencies().unwrap().is_empty());
+        assert!(state.unknown.is_empty());
+    }
+
     #[test]
     fn smc_method_id() {
         let number = SmcBoxedMethodId::SmcMethodIdNumber(SmcMethodIdNumber { number: 123 });
@@ -677,4 +1140,51 @@ mod tests {
             })
         );
     }
+
+    #[test]
+    fn raw_message_bounce() {
+        let body = |bits: &[u8]| {
+            let cell = toner::tlb::Cell {
+                data: toner::tlb::bits::bitvec::vec::BitVec::from_slice(bits),
+                references: vec![],
+            };
+            let boc = toner::tlb::bits::ser::pack_with(
+                BoC::from_root(cell),
+                toner::ton::boc::BagOfCellsArgs {
+                    has_idx: false,
+                    has_crc32c: true,
+                },
+            )
+            .unwrap();
+
+            STANDARD.encode(boc.as_raw_slice())
+        };
+        let message = |body: String| {
+            serde_json::from_value::<RawMessage>(json!({
+                "@type": "raw.message",
+                "hash": "cAHTfRmR5Ibe3Q0H0dhnFTNmAyQ6wg/nBdD1D8fzdcg=",
+                "source": {"@type": "accountAddress", "account_address": "kQCSES0TZYqcVkgoguhIb8iMEo4cvaEwmIrU5qbQgnN8fo2A"},
+                "destination": {"@type": "accountAddress", "account_address": "0QAdFQdB4UJH1XyIaOy_5bkMZCT7xdJTMQYeVeOFl3G2t8Lb"},
+                "value": "1000000000",
+                "fwd_fee": "266669",
+                "ihr_fee": "0",
+                "created_lt": "30021934000002",
+                "body_hash": "",
+                "msg_data": {"@type": "msg.dataRaw", "body": body, "init_state": ""}
+            }))
+            .unwrap()
+        };
+
+        let bounce = message(body(&[0xff, 0xff, 0xff, 0xff, 0, 0, 0, 1]));
+        let transfer = message(body(&[0, 0, 0, 0]));
+        let empty = message("te6cckEBAQEAAgAAAEysuc0=".to_owned());
+
+        assert!(bounce.is_bounce());
+        assert!(!transfer.is_bounce());
+        assert!(!empty.is_bounce());
+        assert_eq!(
+            bounce.hash(),
+            Some("cAHTfRmR5Ibe3Q0H0dhnFTNmAyQ6wg/nBdD1D8fzdcg=")
+        );
+    }
 }
@@ -0,0 +1,249 @@
+//! Cells and BoCs without a lite server. Slices are read with [`Cell::parser`],
+//! e.g. `unpack::<u32>()`, `unpack_as::<_, NBits<N>>()`, `parse_as::<_, Ref>()` or `parse::<MsgAddress>()`.
+use anyhow::anyhow;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Arc;
+use toner::tlb::bits::bitvec::order::Msb0;
+use toner::tlb::bits::bitvec::vec::BitVec;
+use toner::tlb::bits::de::unpack_bytes;
+use toner::tlb::bits::ser::pack_with;
+use toner::tlb::de::CellDeserialize;
+use toner::tlb::r#as::NoArgs;
+use toner::tlb::Cell;
+use toner::ton::boc::{BagOfCellsArgs, BoC};
+use toner::ton::hashmap::Hashmap;
+
+/// The single root of a serialized BoC
+pub fn parse_boc(bytes: &[u8]) -> anyhow::Result<Arc<Cell>> {
+    let boc: BoC = unpack_bytes(bytes)?;
+
+    boc.single_root()
+        .cloned()
+        .ok_or_else(|| anyhow!("boc must have a single root"))
+}
+
+/// [`parse_boc`] of a base64 BoC, as tonlib and lite servers send them
+pub fn parse_base64_boc(boc: &str) -> anyhow::Result<Arc<Cell>> {
+    parse_boc(&STANDARD.decode(boc.trim())?)
+}
+
+/// Base64 BoC of `root` with a crc32c and without an index, as tonlib serializes them
+pub fn to_base64_boc(root: impl Into<Arc<Cell>>) -> anyhow::Result<String> {
+    let boc = pack_with(
+        BoC::from_root(root),
+        BagOfCellsArgs {
+            has_idx: false,
+            has_crc32c: true,
+        },
+    )?;
+
+    Ok(STANDARD.encode(boc.as_raw_slice()))
+}
+
+/// Entries of a dictionary with `key_bits` long keys, `root` is the `Hashmap` itself
+/// rather than a `HashmapE` pointing to it. Values are parsed from the rest of the leaves
+pub fn dict_entries<'de, T>(
+    root: &'de Cell,
+    key_bits: u32,
+) -> anyhow::Result<Vec<(BitVec<u8, Msb0>, T)>>
+where
+    T: CellDeserialize<'de>,
+{
+    Ok(root
+        .parser()
+        .parse_as_with::<_, Hashmap<NoArgs<_>, ()>>((key_bits, ()))?)
+}
+
+pub trait ReprHash {
+    /// [`Cell::hash`] computing each cell once, `Cell::hash` walks a cell again
+    /// for every path to it
+    fn repr_hash(&self) -> [u8; 32];
+}
+
+impl ReprHash for Cell {
+    fn repr_hash(&self) -> [u8; 32] {
+        Hashes::default().of(self).0
+    }
+}
+
+/// Hashes and depths by cell, cells are borrowed for as long as it lives
+#[derive(Default)]
+struct Hashes(HashMap<*const Cell, ([u8; 32], u16)>);
+
+impl Hashes {
+    fn of(&mut self, cell: &Cell) -> ([u8; 32], u16) {
+        if let Some(known) = self.0.get(&(cell as *const Cell)) {
+            return *known;
+        }
+
+        let refs: Vec<_> = cell.references.iter().map(|r| self.of(r)).collect();
+
+        // ordinary cells only, toner has no exotic ones
+        let bits = cell.data.len();
+        let mut repr = vec![refs.len() as u8, ((bits / 8) + bits.div_ceil(8)) as u8];
+        repr.extend_from_slice(cell.data.as_raw_slice());
+        if bits % 8 != 0 {
+            // the rest of the last byte is a stop bit followed by zeros
+            let rest = bits % 8;
+            let last = repr.last_mut().expect("cell has data");
+            *last = (*last & (!0u8 << (8 - rest))) | (1 << (7 - rest));
+        }
+        for (_, depth) in &refs {
+            repr.extend_from_slice(&depth.to_be_bytes());
+        }
+        for (hash, _) in &refs {
+            repr.extend_from_slice(hash);
+        }
+
+        let hash = Sha256::digest(repr).into();
+        let depth = refs.iter().map(|(_, depth)| depth + 1).max().unwrap_or(0);
+        self.0.insert(cell, (hash, depth));
+
+        (hash, depth)
+    }
+}
+
+/// A cell of [`cell_tree`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CellNode {
+    pub hash: [u8; 32],
+    pub depth: u16,
+    pub bits: usize,
+    /// Bits padded with zeros to whole bytes
+    pub data: Vec<u8>,
+    /// Indices of the references in the tree
+    pub refs: Vec<usize>,
+}
+
+/// Distinct cells of `root` in breadth-first order, the root comes first.
+/// A cell referenced more than once is listed once
+pub fn cell_tree(root: &Cell) -> Vec<CellNode> {
+    let mut hashes = Hashes::default();
+    let mut indices: HashMap<[u8; 32], usize> = HashMap::new();
+    let mut cells = vec![root];
+    let mut nodes = Vec::new();
+
+    indices.insert(hashes.of(root).0, 0);
+    while let Some(&cell) = cells.get(nodes.len()) {
+        let refs = cell
+            .references
+            .iter()
+            .map(|r| {
+                let hash = hashes.of(r).0;
+                *indices.entry(hash).or_insert_with(|| {
+                    cells.push(r);
+
+                    cells.len() - 1
+                })
+            })
+            .collect();
+        let (hash, depth) = hashes.of(cell);
+
+        nodes.push(CellNode {
+            hash,
+            depth,
+            bits: cell.data.len(),
+            data: cell.data.as_raw_slice().to_vec(),
+            refs,
+        });
+    }
+
+    nodes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Code of wallet v4r2
+    const WALLET_V4R2: &str = include_str!("../tests/fixtures/wallet_v4r2.code");
+
+    #[test]
+    fn known_hashes() {
+        let empty = Cell::new();
+        let wallet = parse_base64_boc(WALLET_V4R2).unwrap();
+
+        assert_eq!(
+            hex::encode(empty.repr_hash()),
+            "96a296d224f285c67bee93c30f8a309157f0daa35dc5b87e410b78630a09cfc7"
+        );
+        assert_eq!(
+            hex::encode(wallet.repr_hash()),
+            "feb5ff6820e2ff0d9483e7e0d62c817d846789fb4ae580c878866d959dabd5c0"
+        );
+    }
+
+    #[test]
+    fn repr_hash_is_cell_hash() {
+        // cells of every data length up to a few bytes, with and without references
+        for bits in 0..=40usize {
+            let leaf = Cell {
+                data: (0..bits).map(|i| i % 3 == 0).collect(),
+                references: Vec::new(),
+            };
+            let node = Cell {
+                data: (0..bits).map(|i| i % 2 == 0).collect(),
+                references: vec![Arc::new(leaf.clone()), Arc::new(Cell::new())],
+            };
+
+            assert_eq!(leaf.repr_hash(), leaf.hash(), "{} bits", bits);
+            assert_eq!(node.repr_hash(), node.hash(), "{} bits", bits);
+        }
+    }
+
+    #[test]
+    fn boc_round_trip() {
+        let wallet = parse_base64_boc(WALLET_V4R2).unwrap();
+        let boc = to_base64_boc(wallet.clone()).unwrap();
+
+        assert_eq!(parse_base64_boc(&boc).unwrap(), wallet);
+        assert!(parse_base64_boc("not a boc").is_err());
+    }
+
+    #[test]
+    fn wallet_v4r2_tree() {
+        let wallet = parse_base64_boc(WALLET_V4R2).unwrap();
+        let tree = cell_tree(&wallet);
+
+        assert_eq!(tree[0].hash, wallet.hash());
+        assert_eq!(tree[0].bits, wallet.data.len());
+        assert_eq!(tree[0].refs.len(), wallet.references.len());
+        assert!(tree
+            .iter()
+            .flat_map(|node| &node.refs)
+            .all(|i| *i > 0 && *i < tree.len()));
+    }
+
+    #[test]
+    fn shared_cells_are_listed_once() {
+        let leaf = Arc::new(Cell {
+            data: BitVec::repeat(true, 3),
+            references: Vec::new(),
+        });
+        let root = Cell {
+            data: BitVec::new(),
+            references: vec![leaf.clone(), leaf],
+        };
+
+        let tree = cell_tree(&root);
+
+        assert_eq!(tree.len(), 2);
+        assert_eq!(tree[0].refs, vec![1, 1]);
+    }
+
+    #[test]
+    fn dict_of_methods() {
+        let wallet = parse_base64_boc(WALLET_V4R2).unwrap();
+        // the method dictionary of the code, see `ton_contract::code::method_ids`
+        let methods = &wallet.references[0];
+
+        let entries: Vec<(_, ())> = dict_entries(methods, 19).unwrap();
+
+        assert_eq!(entries.len(), 7);
+        assert!(entries.iter().all(|(key, _)| key.len() == 19));
+    }
+}
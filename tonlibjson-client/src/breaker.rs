@@ -0,0 +1,377 @@
+use crate::error::Error;
+use futures::ready;
+use pin_project::pin_project;
+use std::borrow::Cow;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use ton_client_util::router::route::Error as RouteError;
+use tower::timeout::error::Elapsed;
+use tower::{BoxError, Service};
+
+/// Returned without calling the upstream while the circuit is open
+#[derive(Debug, thiserror::Error)]
+#[error("upstream unavailable: {breaker} circuit is open")]
+pub struct CircuitOpen {
+    pub breaker: &'static str,
+}
+
+/// Whether `error` came from an open circuit rather than from the upstream itself
+pub fn is_circuit_open(error: &anyhow::Error) -> bool {
+    match error.downcast_ref::<Error>() {
+        Some(Error::Tower(e)) => e.is::<CircuitOpen>(),
+        _ => error.is::<CircuitOpen>(),
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BreakerPolicy {
+    /// consecutive upstream failures which open the circuit
+    pub failure_threshold: u32,
+    /// how long the circuit stays open until a single probe is let through
+    pub open_for: Duration,
+}
+
+impl BreakerPolicy {
+    /// Policy of reads
+    pub fn reads() -> Self {
+        Self {
+            failure_threshold: 20,
+            open_for: Duration::from_secs(5),
+        }
+    }
+
+    /// Policy of sent messages, which open sooner and stay open longer than reads
+    pub fn sends() -> Self {
+        Self {
+            failure_threshold: 5,
+            open_for: Duration::from_secs(10),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Closed {
+        failures: u32,
+    },
+    /// half-open once `probe_at` has passed: the next request is a probe, the others still fail
+    Open {
+        probe_at: Instant,
+    },
+}
+
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    name: &'static str,
+    network: Cow<'static, str>,
+    policy: BreakerPolicy,
+    state: Mutex<State>,
+}
+
+impl CircuitBreaker {
+    pub fn new(name: &'static str, network: Cow<'static, str>, policy: BreakerPolicy) -> Self {
+        metrics::describe_gauge!(
+            "ton_circuit_breaker_open",
+            "Whether the circuit breaker is open"
+        );
+        metrics::describe_counter!(
+            "ton_circuit_breaker_transitions_total",
+            "Number of circuit breaker state transitions"
+        );
+        metrics::describe_counter!(
+            "ton_circuit_breaker_rejected_total",
+            "Number of requests failed by an open circuit breaker"
+        );
+        metrics::gauge!("ton_circuit_breaker_open", "network" => network.clone(), "breaker" => name)
+            .set(0);
+
+        Self {
+            name,
+            network,
+            policy,
+            state: Mutex::new(State::Closed { failures: 0 }),
+        }
+    }
+
+    /// Open or half-open, i.e. requests fail without reaching the upstream
+    pub fn is_open(&self) -> bool {
+        matches!(*self.state.lock().unwrap(), State::Open { .. })
+    }
+
+    /// Err if the request must fail right away
+    fn acquire(&self, now: Instant) -> Result<(), CircuitOpen> {
+        let mut state = self.state.lock().unwrap();
+        match *state {
+            State::Closed { .. } => Ok(()),
+            State::Open { probe_at } if now >= probe_at => {
+                *state = State::Open {
+                    probe_at: now + self.policy.open_for,
+                };
+                self.transition("half_open");
+
+                Ok(())
+            }
+            State::Open { .. } => {
+                metrics::counter!("ton_circuit_breaker_rejected_total", "network" => self.network.clone(), "breaker" => self.name)
+                    .increment(1);
+
+                Err(CircuitOpen { breaker: self.name })
+            }
+        }
+    }
+
+    fn on_success(&self) {
+        let mut state = self.state.lock().unwrap();
+        if matches!(*state, State::Open { .. }) {
+            self.transition("closed");
+            metrics::gauge!("ton_circuit_breaker_open", "network" => self.network.clone(), "breaker" => self.name)
+                .set(0);
+        }
+
+        *state = State::Closed { failures: 0 };
+    }
+
+    fn on_failure(&self, now: Instant) {
+        let mut state = self.state.lock().unwrap();
+        let open = match *state {
+            State::Closed { failures } if failures + 1 < self.policy.failure_threshold => {
+                *state = State::Closed {
+                    failures: failures + 1,
+                };
+
+                false
+            }
+            State::Closed { .. } => true,
+            // failed probe
+            State::Open { .. } => true,
+        };
+
+        if open {
+            *state = State::Open {
+                probe_at: now + self.policy.open_for,
+            };
+            self.transition("open");
+            metrics::gauge!("ton_circuit_breaker_open", "network" => self.network.clone(), "breaker" => self.name)
+                .set(1);
+        }
+    }
+
+    fn transition(&self, to: &'static str) {
+        tracing::warn!(network = %self.network, breaker = self.name, to, "circuit breaker transition");
+        metrics::counter!("ton_circuit_breaker_transitions_total", "network" => self.network.clone(), "breaker" => self.name, "to" => to)
+            .increment(1);
+    }
+}
+
+/// Timeouts and missing lite servers, any other error means a lite server has answered
+fn is_upstream_failure(error: &BoxError) -> bool {
+    if error.is::<Elapsed>() {
+        return true;
+    }
+
+    let route_error = match error.downcast_ref::<Error>() {
+        Some(Error::Route(e)) => Some(e),
+        _ => error.downcast_ref::<RouteError>(),
+    };
+
+    matches!(route_error, Some(RouteError::RouteNotAvailable))
+}
+
+/// Picks the breaker of a request, sent messages have their own
+pub(crate) trait ToBreaker {
+    fn is_send(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct BreakerService<S> {
+    inner: S,
+    reads: Option<Arc<CircuitBreaker>>,
+    sends: Option<Arc<CircuitBreaker>>,
+}
+
+impl<S> BreakerService<S> {
+    pub(crate) fn new(
+        inner: S,
+        network: Cow<'static, str>,
+        reads: Option<BreakerPolicy>,
+        sends: Option<BreakerPolicy>,
+    ) -> Self {
+        Self {
+            inner,
+            reads: reads
+                .map(|policy| Arc::new(CircuitBreaker::new("reads", network.clone(), policy))),
+            sends: sends.map(|policy| Arc::new(CircuitBreaker::new("sends", network, policy))),
+        }
+    }
+
+    pub(crate) fn reads(&self) -> Option<Arc<CircuitBreaker>> {
+        self.reads.clone()
+    }
+}
+
+impl<S, Req> Service<Req> for BreakerService<S>
+where
+    S: Service<Req, Error = BoxError>,
+    Req: ToBreaker,
+{
+    type Response = S::Response;
+    type Error = BoxError;
+    type Future = ResponseFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        let breaker = if req.is_send() {
+            self.sends.clone()
+        } else {
+            self.reads.clone()
+        };
+
+        if let Some(Err(e)) = breaker.as_ref().map(|b| b.acquire(Instant::now())) {
+            return ResponseFuture::Rejected { error: Some(e) };
+        }
+
+        ResponseFuture::Called {
+            future: self.inner.call(req),
+            breaker,
+        }
+    }
+}
+
+#[pin_project(project = ResponseFutureProj)]
+pub(crate) enum ResponseFuture<F> {
+    Rejected {
+        error: Option<CircuitOpen>,
+    },
+    Called {
+        #[pin]
+        future: F,
+        breaker: Option<Arc<CircuitBreaker>>,
+    },
+}
+
+impl<F, T> Future for ResponseFuture<F>
+where
+    F: Future<Output = Result<T, BoxError>>,
+{
+    type Output = Result<T, BoxError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.project() {
+            ResponseFutureProj::Rejected { error } => {
+                Poll::Ready(Err(error.take().expect("polled after error").into()))
+            }
+            ResponseFutureProj::Called { future, breaker } => {
+                let result = ready!(future.poll(cx));
+                if let Some(breaker) = breaker {
+                    match &result {
+                        Err(e) if is_upstream_failure(e) => breaker.on_failure(Instant::now()),
+                        _ => breaker.on_success(),
+                    }
+                }
+
+                Poll::Ready(result)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::{
+        BlocksGetMasterchainInfo, RawSendMessage, RawSendMessageReturnHash, TonBlockIdExt,
+        WithBlock,
+    };
+    use ton_client_util::service::deadline::DeadlineExceeded;
+
+    fn breaker() -> CircuitBreaker {
+        CircuitBreaker::new(
+            "test",
+            Cow::Borrowed("test"),
+            BreakerPolicy {
+                failure_threshold: 3,
+                open_for: Duration::from_secs(5),
+            },
+        )
+    }
+
+    #[test]
+    fn opens_after_consecutive_failures() {
+        let breaker = breaker();
+        let now = Instant::now();
+
+        breaker.on_failure(now);
+        breaker.on_failure(now);
+        breaker.on_success();
+        breaker.on_failure(now);
+        breaker.on_failure(now);
+        assert!(breaker.acquire(now).is_ok());
+
+        assert!(!breaker.is_open());
+        breaker.on_failure(now);
+        assert!(breaker.is_open());
+        assert_eq!(breaker.acquire(now).unwrap_err().breaker, "test");
+    }
+
+    #[test]
+    fn half_open_lets_a_single_probe_through() {
+        let breaker = breaker();
+        let now = Instant::now();
+        for _ in 0..3 {
+            breaker.on_failure(now);
+        }
+
+        let later = now + Duration::from_secs(5);
+        assert!(breaker.acquire(later).is_ok());
+        assert!(breaker.acquire(later).is_err());
+
+        breaker.on_failure(later);
+        assert!(breaker.acquire(later + Duration::from_secs(1)).is_err());
+
+        let probe = later + Duration::from_secs(5);
+        assert!(breaker.acquire(probe).is_ok());
+        breaker.on_success();
+        assert!(breaker.acquire(probe).is_ok());
+        assert!(breaker.acquire(probe).is_ok());
+    }
+
+    #[test]
+    fn timeouts_and_missing_routes_are_failures() {
+        assert!(is_upstream_failure(&Elapsed::new().into()));
+        assert!(is_upstream_failure(&RouteError::RouteNotAvailable.into()));
+        assert!(is_upstream_failure(
+            &Error::Route(RouteError::RouteNotAvailable).into()
+        ));
+        assert!(!is_upstream_failure(&RouteError::RouteUnknown.into()));
+        assert!(!is_upstream_failure(&DeadlineExceeded.into()));
+        assert!(!is_upstream_failure(
+            &"Ton error occurred with code 400".into()
+        ));
+    }
+
+    #[test]
+    fn circuit_open_is_recognized() {
+        let error: anyhow::Error = Error::Tower(CircuitOpen { breaker: "sends" }.into()).into();
+
+        assert!(is_circuit_open(&error));
+        assert!(!is_circuit_open(&anyhow::anyhow!("timeout")));
+    }
+
+    #[test]
+    fn sends_have_their_own_breaker() {
+        assert!(RawSendMessage::new("boc".to_owned()).is_send());
+        assert!(RawSendMessageReturnHash::new("boc".to_owned()).is_send());
+        assert!(!BlocksGetMasterchainInfo::default().is_send());
+
+        let block = TonBlockIdExt::new(-1, i64::MIN, 1, String::new(), String::new());
+        assert!(WithBlock::new(block, RawSendMessage::new("boc".to_owned())).is_send());
+    }
+}
@@ -0,0 +1,270 @@
+use pin_project::pin_project;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::task::futures::TaskLocalFuture;
+use tokio::time::{sleep, Sleep};
+use ton_client_util::router::hint;
+use ton_client_util::router::route::Route;
+use tower::timeout::error::Elapsed;
+use tower::{BoxError, Service};
+
+tokio::task_local! {
+    static OPTIONS: CallOptions;
+}
+
+/// Overrides of the timeout and the retries of [`crate::ton::TonClientBuilder`] for some calls,
+/// the client settings apply to whatever is unset
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CallOptions {
+    timeout: Option<Duration>,
+    max_retries: Option<u32>,
+    route_hint: Option<Route>,
+}
+
+impl CallOptions {
+    /// Time a call may take, its retries included
+    pub fn set_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Retries of a failed call, none at all with 0. The retry budget still applies
+    pub fn set_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = Some(max_retries);
+        self
+    }
+
+    /// Route of calls to the latest block, e.g. to lite servers which have a block the caller
+    /// has seen. Calls of a given block keep their own route
+    pub fn set_route_hint(mut self, route: Route) -> Self {
+        self.route_hint = Some(route);
+        self
+    }
+
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    pub fn max_retries(&self) -> Option<u32> {
+        self.max_retries
+    }
+
+    pub fn route_hint(&self) -> Option<Route> {
+        self.route_hint
+    }
+
+    /// These options, the unset ones taken from `outer`
+    pub fn or(self, outer: Self) -> Self {
+        Self {
+            timeout: self.timeout.or(outer.timeout),
+            max_retries: self.max_retries.or(outer.max_retries),
+            route_hint: self.route_hint.or(outer.route_hint),
+        }
+    }
+}
+
+/// Calls made by `f` take `options`, the innermost options win if nested
+pub async fn scope<F: Future>(options: CallOptions, f: F) -> F::Output {
+    OPTIONS.scope(options.or(current()), f).await
+}
+
+/// [`scope`] for a single poll, e.g. of a stream made on behalf of a request
+pub fn sync_scope<R>(options: CallOptions, f: impl FnOnce() -> R) -> R {
+    OPTIONS.sync_scope(options.or(current()), f)
+}
+
+/// Options of the calls the current task makes
+pub fn current() -> CallOptions {
+    OPTIONS.try_with(|options| *options).unwrap_or_default()
+}
+
+/// Runs calls with the options of a [`crate::ton::TonClient::with_options`] handle
+#[derive(Clone)]
+pub(crate) struct CallScope<S> {
+    inner: S,
+    options: CallOptions,
+}
+
+impl<S> CallScope<S> {
+    pub(crate) fn new(inner: S) -> Self {
+        Self {
+            inner,
+            options: CallOptions::default(),
+        }
+    }
+
+    pub(crate) fn options(&self) -> CallOptions {
+        self.options
+    }
+
+    pub(crate) fn set_options(&mut self, options: CallOptions) {
+        self.options = options;
+    }
+}
+
+impl<S, Req> Service<Req> for CallScope<S>
+where
+    S: Service<Req>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = TaskLocalFuture<CallOptions, TaskLocalFuture<Option<Route>, S::Future>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        let options = self.options.or(current());
+        let future = OPTIONS.sync_scope(options, || {
+            hint::sync_scope(options.route_hint, || self.inner.call(req))
+        });
+
+        OPTIONS.scope(options, hint::scope(options.route_hint, future))
+    }
+}
+
+/// Timeout of calls, of [`CallOptions`] if set and `default` otherwise
+#[derive(Clone)]
+pub(crate) struct CallTimeout<S> {
+    inner: S,
+    default: Duration,
+}
+
+impl<S> CallTimeout<S> {
+    pub(crate) fn new(inner: S, default: Duration) -> Self {
+        Self { inner, default }
+    }
+}
+
+impl<S, Req> Service<Req> for CallTimeout<S>
+where
+    S: Service<Req>,
+    S::Error: Into<BoxError>,
+{
+    type Response = S::Response;
+    type Error = BoxError;
+    type Future = TimeoutFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        let timeout = current().timeout.unwrap_or(self.default);
+
+        TimeoutFuture {
+            inner: self.inner.call(req),
+            sleep: sleep(timeout),
+        }
+    }
+}
+
+#[pin_project]
+pub(crate) struct TimeoutFuture<F> {
+    #[pin]
+    inner: F,
+    #[pin]
+    sleep: Sleep,
+}
+
+impl<F, T, E> Future for TimeoutFuture<F>
+where
+    F: Future<Output = Result<T, E>>,
+    E: Into<BoxError>,
+{
+    type Output = Result<T, BoxError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        if let Poll::Ready(result) = this.inner.poll(cx) {
+            return Poll::Ready(result.map_err(Into::into));
+        }
+
+        this.sleep.poll(cx).map(|_| Err(Elapsed::new().into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ton_client_util::router::route::BlockCriteria;
+    use tower::ServiceExt;
+
+    fn slow() -> CallTimeout<impl Service<u32, Response = u32, Error = BoxError> + Clone> {
+        CallTimeout::new(
+            tower::service_fn(|req: u32| async move {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+
+                Ok::<_, BoxError>(req)
+            }),
+            Duration::from_secs(10),
+        )
+    }
+
+    #[tokio::test]
+    async fn innermost_options_win() {
+        let outer = CallOptions::default()
+            .set_timeout(Duration::from_secs(1))
+            .set_max_retries(3);
+
+        scope(outer, async {
+            scope(CallOptions::default().set_max_retries(0), async {
+                assert_eq!(
+                    current(),
+                    CallOptions::default()
+                        .set_timeout(Duration::from_secs(1))
+                        .set_max_retries(0)
+                );
+            })
+            .await;
+        })
+        .await;
+        assert_eq!(current(), CallOptions::default());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn call_timeout_overrides_default() {
+        assert_eq!(slow().oneshot(1).await.unwrap(), 1);
+
+        let error = scope(
+            CallOptions::default().set_timeout(Duration::from_secs(1)),
+            slow().oneshot(1),
+        )
+        .await
+        .unwrap_err();
+        assert!(error.is::<Elapsed>());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn handle_options_override_scope() {
+        let mut handle = CallScope::new(slow());
+        handle.set_options(CallOptions::default().set_timeout(Duration::from_secs(1)));
+
+        let result = scope(
+            CallOptions::default().set_timeout(Duration::from_secs(10)),
+            handle.oneshot(1),
+        )
+        .await;
+        assert!(result.unwrap_err().is::<Elapsed>());
+    }
+
+    #[tokio::test]
+    async fn route_hint_is_seen_by_router() {
+        let route = Route::Block {
+            chain: -1,
+            criteria: BlockCriteria::Seqno {
+                shard: i64::MIN,
+                seqno: 42,
+            },
+        };
+        let mut handle = CallScope::new(tower::service_fn(|_: u32| async {
+            Ok::<_, BoxError>(hint::apply(Route::Latest))
+        }));
+        handle.set_options(CallOptions::default().set_route_hint(route));
+
+        assert_eq!(handle.oneshot(1).await.unwrap(), route);
+    }
+}
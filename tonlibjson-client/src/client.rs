@@ -1,5 +1,7 @@
 use crate::block::TonError;
+use crate::fixture::Recorder;
 use crate::request::Requestable;
+use crate::tonlib::Tonlib;
 use anyhow::anyhow;
 use dashmap::DashMap;
 use futures::ready;
@@ -22,9 +24,10 @@ type RequestStorage = DashMap<RequestId, oneshot::Sender<Response>>;
 
 #[derive(Debug, Clone)]
 pub(crate) struct Client {
-    client: Arc<tonlibjson_sys::Client>,
+    client: Arc<dyn Tonlib>,
     responses: Arc<RequestStorage>,
     drop_guard: Arc<DropGuard>,
+    recorder: Option<Arc<Recorder>>,
 }
 
 impl Client {
@@ -33,7 +36,10 @@ impl Client {
     }
 
     pub(crate) fn new() -> Self {
-        let client = Arc::new(tonlibjson_sys::Client::new());
+        Self::with_tonlib(Arc::new(tonlibjson_sys::Client::new()))
+    }
+
+    pub(crate) fn with_tonlib(client: Arc<dyn Tonlib>) -> Self {
         let client_recv = client.clone();
 
         let responses: Arc<RequestStorage> = Default::default();
@@ -46,6 +52,7 @@ impl Client {
             let timeout = Duration::from_secs(1);
             while !child_token.is_cancelled() {
                 if let Ok(packet) = client_recv.receive(timeout) {
+                    let packet: &str = &packet;
                     if let Ok(response) = serde_json::from_str::<Response>(packet) {
                         if let Some((_, sender)) = responses_rcv.remove(&response.id) {
                             let _ = sender.send(response);
@@ -68,8 +75,13 @@ impl Client {
             client,
             responses,
             drop_guard: Arc::new(cancel_token.drop_guard()),
+            recorder: None,
         }
     }
+
+    pub(crate) fn set_recorder(&mut self, recorder: Arc<Recorder>) {
+        self.recorder = Some(recorder);
+    }
 }
 
 impl Default for Client {
@@ -101,12 +113,19 @@ where
                 let (tx, rx) = oneshot::channel::<Response>();
                 self.responses.insert(req.id, tx);
 
+                let recording = self.recorder.as_ref().and_then(|recorder| {
+                    serde_json::to_value(&req.body)
+                        .ok()
+                        .map(|request| (Arc::clone(recorder), request))
+                });
+
                 match self.client.send(&json) {
                     Ok(_) => ResponseFuture::new(
                         rx,
                         Arc::clone(&self.drop_guard),
                         req.id,
                         Arc::clone(&self.responses),
+                        recording,
                     ),
                     Err(e) => ResponseFuture::failed(e),
                 }
@@ -127,6 +146,7 @@ enum ResponseState {
         drop_guard: Arc<DropGuard>,
         request_id: RequestId,
         request_storage: Arc<RequestStorage>,
+        recording: Option<(Arc<Recorder>, Value)>,
     },
 }
 
@@ -157,6 +177,7 @@ impl<R> ResponseFuture<R> {
         drop_guard: Arc<DropGuard>,
         request_id: RequestId,
         request_storage: Arc<RequestStorage>,
+        recording: Option<(Arc<Recorder>, Value)>,
     ) -> Self {
         Self {
             state: ResponseState::Rx {
@@ -164,6 +185,7 @@ impl<R> ResponseFuture<R> {
                 drop_guard,
                 request_id,
                 request_storage,
+                recording,
             },
             _phantom: PhantomData,
         }
@@ -190,9 +212,13 @@ where
             ResponseStateProj::Failed { error } => {
                 Poll::Ready(Err(error.take().expect("polled after error")))
             }
-            ResponseStateProj::Rx { rx, .. } => {
+            ResponseStateProj::Rx { rx, recording, .. } => {
                 return match ready!(rx.poll(cx)) {
                     Ok(response) => {
+                        if let Some((recorder, request)) = recording.take() {
+                            recorder.record(request, response.data.clone());
+                        }
+
                         // TODO[akostylev0] refac!!
                         if response.data["@type"] == "error" {
                             tracing::trace!("Error occurred: {:?}", &response.data);
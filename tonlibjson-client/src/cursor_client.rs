@@ -1,11 +1,16 @@
-use crate::block::{BlocksGetBlockHeader, BlocksLookupBlock};
+use crate::block::{
+    AccountAddress, BlocksGetBlockHeader, BlocksLookupBlock, RawFullAccountState,
+    RawGetAccountState,
+};
 use crate::block::{
     BlocksGetMasterchainInfo, BlocksGetShards, BlocksHeader, BlocksMasterchainInfo, Sync,
     TonBlockId, TonBlockIdExt,
 };
 use crate::client::Client;
 use crate::error::ErrorService;
+use crate::keepalive::{Keepalive, Liveness};
 use crate::metric::ConcurrencyMetric;
+use crate::pool::{Health, LiteServerStatus};
 use crate::request::Specialized;
 use anyhow::Result;
 use dashmap::{DashMap, DashSet};
@@ -17,14 +22,16 @@ use std::collections::{HashMap, HashSet};
 use std::convert::Infallible;
 use std::future::Future;
 use std::pin::Pin;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll};
 use std::time::Duration;
 use tokio::sync::mpsc::UnboundedSender;
 use tokio::sync::watch::{Receiver, Sender};
-use tokio::time::{interval, MissedTickBehavior};
+use tokio::time::{interval, Instant, MissedTickBehavior};
 use tokio_retry::strategy::{jitter, FibonacciBackoff};
 use tokio_retry::Retry;
+use tokio_util::sync::{CancellationToken, DropGuard};
+use ton_client_util::router::latency::LatencyStats;
 use ton_client_util::router::route::BlockCriteria;
 use ton_client_util::router::shard_prefix::ShardPrefix;
 use ton_client_util::router::Routed;
@@ -37,8 +44,13 @@ use tower::load::PeakEwma;
 use tower::{Service, ServiceExt};
 use tracing::instrument;
 
+#[cfg(not(feature = "failpoints"))]
+pub(crate) type LiteServerClient = PeakEwma<Client>;
+#[cfg(feature = "failpoints")]
+pub(crate) type LiteServerClient = crate::failpoint::FailpointService<PeakEwma<Client>>;
+
 pub(crate) type InnerClient =
-    ConcurrencyMetric<ConcurrencyLimit<SharedService<ErrorService<Timeout<PeakEwma<Client>>>>>>;
+    ConcurrencyMetric<ConcurrencyLimit<SharedService<ErrorService<Timeout<LiteServerClient>>>>>;
 
 type ChainId = i32;
 type ShardId = (i32, i64);
@@ -139,6 +151,12 @@ impl Registry {
             .and_then(|s| s.right_next())
     }
 
+    fn get_first_seqno(&self, shard_id: &ShardId) -> Option<Seqno> {
+        self.shard_bounds_registry
+            .get(shard_id)
+            .and_then(|s| s.left.as_ref().map(|h| h.id.seqno))
+    }
+
     fn get_last_seqno(&self, shard_id: &ShardId) -> Option<Seqno> {
         self.shard_bounds_registry
             .get(shard_id)
@@ -256,16 +274,21 @@ impl Registry {
 
 #[derive(Clone)]
 pub(crate) struct CursorClient {
+    network: Cow<'static, str>,
     id: Cow<'static, str>,
     client: InnerClient,
 
     masterchain_info_rx: Receiver<Option<BlocksMasterchainInfo>>,
     registry: Arc<Registry>,
+    last_ping: Arc<Mutex<Option<Duration>>>,
+    liveness: Arc<Liveness>,
+    /// stops the discovery and keepalive of the lite server once it leaves the pool
+    _drop_guard: Arc<DropGuard>,
 }
 
 impl Routed for CursorClient {
     fn contains(&self, chain: &ChainId, criteria: &BlockCriteria) -> bool {
-        self.registry.contains(chain, criteria, false)
+        !self.liveness.is_dead() && self.registry.contains(chain, criteria, false)
     }
 
     fn contains_not_available(&self, chain: &ChainId, criteria: &BlockCriteria) -> bool {
@@ -273,6 +296,10 @@ impl Routed for CursorClient {
     }
 
     fn last_seqno(&self) -> Option<Seqno> {
+        if self.liveness.is_dead() {
+            return None;
+        }
+
         let master_shard_id = self
             .masterchain_info_rx
             .borrow()
@@ -281,12 +308,27 @@ impl Routed for CursorClient {
 
         self.registry.get_last_seqno(&master_shard_id)
     }
+
+    fn latency(&self) -> Option<LatencyStats> {
+        self.client.latency().stats()
+    }
+
+    fn archival(&self) -> bool {
+        self.master_shard_id()
+            .and_then(|id| self.registry.get_first_seqno(&id))
+            .is_some_and(|seqno| seqno <= 1)
+    }
 }
 
 impl CursorClient {
+    /// Shards of workchain blocks aren't followed in `masterchain_only` mode, idle connections
+    /// are probed with `keepalive`
     pub(crate) fn new(
+        network: Cow<'static, str>,
         id: String,
-        client: ConcurrencyLimit<SharedService<ErrorService<Timeout<PeakEwma<Client>>>>>,
+        client: ConcurrencyLimit<SharedService<ErrorService<Timeout<LiteServerClient>>>>,
+        masterchain_only: bool,
+        keepalive: Option<Keepalive>,
     ) -> Self {
         metrics::describe_counter!(
             "ton_liteserver_last_seqno",
@@ -302,27 +344,51 @@ impl CursorClient {
         );
         metrics::describe_gauge!("ton_liteserver_requests_total", "Total count of requests");
         metrics::describe_gauge!("ton_liteserver_requests", "Number of concurrent requests");
+        metrics::describe_gauge!(
+            "ton_liteserver_latency_seconds",
+            "Latency quantiles of successful requests over the last minute"
+        );
+        metrics::describe_gauge!(
+            "ton_liteserver_error_rate",
+            "Share of failed requests over the last minute"
+        );
 
         let id = Cow::from(id);
-        let client = ConcurrencyMetric::new(client, id.clone());
+        let client = ConcurrencyMetric::new(client, network.clone(), id.clone());
         let (mtx, mrx) = tokio::sync::watch::channel(None);
         let mut mc_watcher = mtx.subscribe();
+        let token = CancellationToken::new();
 
         let _self = Self {
+            network,
             id,
             client,
 
             masterchain_info_rx: mrx,
             registry: Default::default(),
+            last_ping: Default::default(),
+            liveness: Default::default(),
+            _drop_guard: Arc::new(token.clone().drop_guard()),
         };
 
-        tokio::spawn(_self.last_block_loop(mtx));
+        spawn_until_cancelled(&token, _self.last_block_loop(mtx, masterchain_only));
         let inner = _self.first_block_loop();
-        tokio::spawn(async move {
-            mc_watcher.changed().await.unwrap();
-
-            inner.await;
+        spawn_until_cancelled(&token, async move {
+            if mc_watcher.changed().await.is_ok() {
+                inner.await;
+            }
         });
+        if let Some(keepalive) = keepalive {
+            spawn_until_cancelled(
+                &token,
+                keepalive.run(
+                    _self.id.clone(),
+                    _self.client.get_ref().clone(),
+                    _self.client.last_response().clone(),
+                    _self.liveness.clone(),
+                ),
+            );
+        }
 
         _self
     }
@@ -330,39 +396,124 @@ impl CursorClient {
     fn last_block_loop(
         &self,
         mtx: Sender<Option<BlocksMasterchainInfo>>,
+        masterchain_only: bool,
     ) -> impl Future<Output = Infallible> {
+        let network = self.network.clone();
         let id = self.id.clone();
         let client = self.client.clone();
         let registry = self.registry.clone();
+        let last_ping = self.last_ping.clone();
 
-        let discover = LastBlockDiscover::new(id, client, registry, mtx);
+        let discover = LastBlockDiscover::new(
+            network,
+            id,
+            client,
+            registry,
+            last_ping,
+            mtx,
+            masterchain_only,
+        );
 
         discover.discover()
     }
 
     fn first_block_loop(&self) -> impl Future<Output = Infallible> {
+        let network = self.network.clone();
         let id = self.id.clone();
         let client = self.client.clone();
         let registry = self.registry.clone();
 
-        let discover =
-            FirstBlockDiscover::new(id, client, registry, self.masterchain_info_rx.clone());
+        let discover = FirstBlockDiscover::new(
+            network,
+            id,
+            client,
+            registry,
+            self.masterchain_info_rx.clone(),
+        );
 
         discover.discover()
     }
 
     fn edges_defined(&self) -> bool {
-        let Some(master_shard_id) = self
-            .masterchain_info_rx
+        let Some(master_shard_id) = self.master_shard_id() else {
+            return false;
+        };
+
+        self.registry.edges_defined(&master_shard_id)
+    }
+
+    fn master_shard_id(&self) -> Option<ShardId> {
+        self.masterchain_info_rx
             .borrow()
             .as_ref()
             .map(|info| (info.last.workchain, info.last.shard))
-        else {
-            return false;
+    }
+
+    pub(crate) fn status(&self) -> LiteServerStatus {
+        let master_shard_id = self.master_shard_id();
+        let first_seqno = master_shard_id.and_then(|id| self.registry.get_first_seqno(&id));
+        let latency = self.client.latency().stats();
+
+        let health = if self.liveness.is_dead() {
+            Health::Dead
+        } else {
+            Health::new(self.edges_defined(), latency.as_ref())
         };
 
-        self.registry.edges_defined(&master_shard_id)
+        LiteServerStatus {
+            id: self.id.to_string(),
+            health,
+            archival: self.archival(),
+            first_seqno,
+            last_seqno: self.last_seqno(),
+            last_ping: *self.last_ping.lock().unwrap(),
+            consecutive_failures: self.client.consecutive_failures(),
+            inflight: self.client.load(),
+            latency,
+            idle: self.client.last_response().lock().unwrap().elapsed(),
+            last_probe: self.liveness.last_probe(),
+        }
+    }
+
+    /// Latest state of the account on this lite server, bypassing the router
+    pub(crate) async fn raw_get_account_state(
+        &self,
+        address: AccountAddress,
+    ) -> Result<RawFullAccountState> {
+        self.client
+            .clone()
+            .oneshot(RawGetAccountState::new(address))
+            .await
     }
+
+    /// Round trip of a masterchain info request, bypassing the cursor
+    pub(crate) async fn ping(&self) -> Result<Duration> {
+        ping(self.client.clone(), &self.last_ping).await
+    }
+}
+
+fn spawn_until_cancelled<F>(token: &CancellationToken, future: F)
+where
+    F: Future + Send + 'static,
+{
+    let token = token.clone();
+
+    tokio::spawn(async move {
+        tokio::select! {
+            _ = token.cancelled() => {},
+            _ = future => {},
+        }
+    });
+}
+
+async fn ping(client: InnerClient, last_ping: &Mutex<Option<Duration>>) -> Result<Duration> {
+    let started_at = Instant::now();
+    client.oneshot(BlocksGetMasterchainInfo::new()).await?;
+    let rtt = started_at.elapsed();
+
+    last_ping.lock().unwrap().replace(rtt);
+
+    Ok(rtt)
 }
 
 impl Service<Specialized<BlocksGetMasterchainInfo>> for CursorClient {
@@ -514,6 +665,7 @@ async fn wait_for_block_header(
 }
 
 struct FirstBlockDiscover {
+    network: Cow<'static, str>,
     id: Cow<'static, str>,
     client: InnerClient,
     registry: Arc<Registry>,
@@ -523,12 +675,14 @@ struct FirstBlockDiscover {
 
 impl FirstBlockDiscover {
     fn new(
+        network: Cow<'static, str>,
         id: Cow<'static, str>,
         client: InnerClient,
         registry: Arc<Registry>,
         rx: Receiver<Option<BlocksMasterchainInfo>>,
     ) -> Self {
         Self {
+            network,
             id,
             client,
             registry,
@@ -572,7 +726,7 @@ impl FirstBlockDiscover {
         let cur = self.current.as_ref().map(|n| n.id.seqno + 32);
         let (mfb, wfb) = find_first_blocks(&mut self.client, &start, lhs, cur).await?;
 
-        metrics::counter!("ton_liteserver_first_seqno", "liteserver_id" => self.id.clone())
+        metrics::counter!("ton_liteserver_first_seqno", "network" => self.network.clone(), "liteserver_id" => self.id.clone())
             .absolute(mfb.id.seqno as u64);
 
         self.registry.upsert_left(&mfb);
@@ -584,104 +738,112 @@ impl FirstBlockDiscover {
     }
 }
 
+/// Follows shards of every masterchain block sent to it, so workchain blocks are routed
+fn track_shards(client: InnerClient, registry: Arc<Registry>) -> UnboundedSender<TonBlockIdExt> {
+    let (last_block_tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<TonBlockIdExt>();
+
+    // TODO[akostylev0] find last available block
+    tokio::spawn(async move {
+        let mut channels: HashMap<ShardId, UnboundedSender<TonBlockIdExt>> = Default::default();
+        while let Some(block_id) = rx.recv().await {
+            let retry_strategy = FibonacciBackoff::from_millis(32).map(jitter).take(8);
+            match Retry::spawn(retry_strategy, || {
+                client
+                    .clone()
+                    .oneshot(BlocksGetShards::new(block_id.clone()))
+            })
+            .await
+            {
+                Ok(shards) => {
+                    let actual_shards: HashSet<ShardId> =
+                        HashSet::from_iter(shards.shards.iter().map(|s| s.into()));
+
+                    for shard in shards.shards {
+                        registry.upsert_right_end(&shard);
+
+                        let shard_id: ShardId = (&shard).into();
+                        let tx = if let Some(tx) = channels.get_mut(&shard_id) {
+                            tx
+                        } else {
+                            let (tx, mut rx) =
+                                tokio::sync::mpsc::unbounded_channel::<TonBlockIdExt>();
+
+                            tracing::info!(shard_id = ?shard_id, "spawn new channel for shard");
+                            tokio::spawn({
+                                let client = client.clone();
+                                let registry = registry.clone();
+
+                                async move {
+                                    let registry = registry.clone();
+                                    while let Some(block_id) = rx.recv().await {
+                                        let retry_strategy =
+                                            FibonacciBackoff::from_millis(32).map(jitter).take(16);
+                                        match Retry::spawn(retry_strategy, || {
+                                            client.clone().oneshot(BlocksGetBlockHeader::new(
+                                                block_id.clone(),
+                                            ))
+                                        })
+                                        .await
+                                        {
+                                            Ok(header) => registry.upsert_right(&header),
+                                            Err(e) => {
+                                                tracing::warn!(error = ?e, "failed to get shard header");
+                                            }
+                                        }
+                                    }
+                                }
+                            });
+
+                            channels.insert(shard_id, tx);
+                            channels.get_mut(&shard_id).unwrap()
+                        };
+
+                        let _ = tx.send(shard);
+                    }
+
+                    channels.retain(|s, _| actual_shards.contains(s));
+                }
+                Err(error) => {
+                    tracing::warn!(error =?error, "get shards failed");
+                }
+            }
+        }
+    });
+
+    last_block_tx
+}
+
 struct LastBlockDiscover {
+    network: Cow<'static, str>,
     id: Cow<'static, str>,
     client: InnerClient,
     registry: Arc<Registry>,
+    last_ping: Arc<Mutex<Option<Duration>>>,
     current: Option<BlocksMasterchainInfo>,
     mtx: Sender<Option<BlocksMasterchainInfo>>,
-    last_block_tx: UnboundedSender<TonBlockIdExt>,
+    /// none in masterchain only mode
+    last_block_tx: Option<UnboundedSender<TonBlockIdExt>>,
 }
 
 impl LastBlockDiscover {
     fn new(
+        network: Cow<'static, str>,
         id: Cow<'static, str>,
         client: InnerClient,
         registry: Arc<Registry>,
+        last_ping: Arc<Mutex<Option<Duration>>>,
         mtx: Sender<Option<BlocksMasterchainInfo>>,
+        masterchain_only: bool,
     ) -> Self {
-        let (last_block_tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<TonBlockIdExt>();
-
-        // TODO[akostylev0] find last available block
-        tokio::spawn({
-            let client = client.clone();
-            let registry = registry.clone();
-
-            async move {
-                let mut channels: HashMap<ShardId, UnboundedSender<TonBlockIdExt>> =
-                    Default::default();
-                while let Some(block_id) = rx.recv().await {
-                    let retry_strategy = FibonacciBackoff::from_millis(32).map(jitter).take(8);
-                    match Retry::spawn(retry_strategy, || {
-                        client
-                            .clone()
-                            .oneshot(BlocksGetShards::new(block_id.clone()))
-                    })
-                    .await
-                    {
-                        Ok(shards) => {
-                            let actual_shards: HashSet<ShardId> =
-                                HashSet::from_iter(shards.shards.iter().map(|s| s.into()));
-
-                            for shard in shards.shards {
-                                registry.upsert_right_end(&shard);
-
-                                let shard_id: ShardId = (&shard).into();
-                                let tx = if let Some(tx) = channels.get_mut(&shard_id) {
-                                    tx
-                                } else {
-                                    let (tx, mut rx) =
-                                        tokio::sync::mpsc::unbounded_channel::<TonBlockIdExt>();
-
-                                    tracing::info!(shard_id = ?shard_id, "spawn new channel for shard");
-                                    tokio::spawn({
-                                        let client = client.clone();
-                                        let registry = registry.clone();
-
-                                        async move {
-                                            let registry = registry.clone();
-                                            while let Some(block_id) = rx.recv().await {
-                                                let retry_strategy =
-                                                    FibonacciBackoff::from_millis(32)
-                                                        .map(jitter)
-                                                        .take(16);
-                                                match Retry::spawn(retry_strategy, || {
-                                                    client.clone().oneshot(
-                                                        BlocksGetBlockHeader::new(block_id.clone()),
-                                                    )
-                                                })
-                                                .await
-                                                {
-                                                    Ok(header) => registry.upsert_right(&header),
-                                                    Err(e) => {
-                                                        tracing::warn!(error = ?e, "failed to get shard header");
-                                                    }
-                                                }
-                                            }
-                                        }
-                                    });
-
-                                    channels.insert(shard_id, tx);
-                                    channels.get_mut(&shard_id).unwrap()
-                                };
-
-                                let _ = tx.send(shard);
-                            }
-
-                            channels.retain(|s, _| actual_shards.contains(s));
-                        }
-                        Err(error) => {
-                            tracing::warn!(error =?error, "get shards failed");
-                        }
-                    }
-                }
-            }
-        });
+        let last_block_tx =
+            (!masterchain_only).then(|| track_shards(client.clone(), registry.clone()));
 
         Self {
+            network,
             id,
             client,
             registry,
+            last_ping,
             current: None,
             mtx,
             last_block_tx,
@@ -695,8 +857,9 @@ impl LastBlockDiscover {
         loop {
             timer.tick().await;
 
-            metrics::gauge!("ton_liteserver_requests", "liteserver_id" => self.id.clone())
+            metrics::gauge!("ton_liteserver_requests", "network" => self.network.clone(), "liteserver_id" => self.id.clone())
                 .set(self.client.load() as f64);
+            self.report_latency();
 
             match self.next().await {
                 Ok(Some(info)) => {
@@ -708,18 +871,33 @@ impl LastBlockDiscover {
         }
     }
 
+    fn report_latency(&self) {
+        let Some(stats) = self.client.latency().stats() else {
+            return;
+        };
+
+        for (quantile, latency) in [("0.5", stats.p50), ("0.95", stats.p95), ("0.99", stats.p99)] {
+            metrics::gauge!("ton_liteserver_latency_seconds", "network" => self.network.clone(), "liteserver_id" => self.id.clone(), "quantile" => quantile)
+                .set(latency.as_secs_f64());
+        }
+        metrics::gauge!("ton_liteserver_error_rate", "network" => self.network.clone(), "liteserver_id" => self.id.clone())
+            .set(stats.error_rate());
+    }
+
     async fn next(&mut self) -> Result<Option<BlocksMasterchainInfo>> {
+        let started_at = Instant::now();
         let mut info = (&mut self.client)
             .oneshot(BlocksGetMasterchainInfo::new())
             .await?;
-        metrics::counter!("ton_liteserver_last_seqno", "liteserver_id" => self.id.clone())
+        self.last_ping.lock().unwrap().replace(started_at.elapsed());
+        metrics::counter!("ton_liteserver_last_seqno", "network" => self.network.clone(), "liteserver_id" => self.id.clone())
             .absolute(info.last.seqno as u64);
         if self.current.as_ref().is_some_and(|c| c == &info) {
             return Ok(None);
         }
 
         let last_block = (&mut self.client).oneshot(Sync::default()).await?;
-        metrics::counter!("ton_liteserver_synced_seqno", "liteserver_id" => self.id.clone())
+        metrics::counter!("ton_liteserver_synced_seqno", "network" => self.network.clone(), "liteserver_id" => self.id.clone())
             .absolute(last_block.seqno as u64);
         self.registry.upsert_right_end(&last_block);
 
@@ -734,7 +912,9 @@ impl LastBlockDiscover {
 
             let header = wait_for_block_header(block_id, self.client.clone()).await?;
 
-            self.last_block_tx.send(last_block.clone())?;
+            if let Some(last_block_tx) = &self.last_block_tx {
+                last_block_tx.send(last_block.clone())?;
+            }
             self.registry.upsert_right(&header);
 
             info.last = header.id;
@@ -1,4 +1,6 @@
 use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use std::collections::HashMap;
 use std::fmt::Display;
 use std::str::FromStr;
 
@@ -55,6 +57,15 @@ where
     Ok(if v == -1 { None } else { Some(v) })
 }
 
+/// Number as a string, written into the serializer without allocating one
+pub fn serialize_number_as_string<S, T>(v: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    T: Display,
+{
+    serializer.collect_str(v)
+}
+
 pub fn serialize_none_as_empty<S, T>(v: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
@@ -65,3 +76,29 @@ where
         Some(v) => v.serialize(serializer),
     }
 }
+
+/// Fields which aren't described by the scheme, `@type` tag excluded
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UnknownFields(HashMap<String, Value>);
+
+impl UnknownFields {
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.0.get(key)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl<'de> Deserialize<'de> for UnknownFields {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let mut fields = HashMap::<String, Value>::deserialize(deserializer)?;
+        fields.remove("@type");
+
+        Ok(Self(fields))
+    }
+}
@@ -0,0 +1,441 @@
+//! Failpoints inject faults into requests to lite servers, for testing how the client copes with
+//! slow, failing or lost lite servers. Compiled with the `failpoints` feature only.
+//!
+//! Failpoints are read from `TONLIBJSON_FAILPOINTS` on the first request and may be replaced at
+//! runtime by [`Failpoints::set`]. The variable holds failpoints separated by `;`, each is a fault
+//! followed by its optional scope, e.g.
+//! `latency=200,method=raw.getAccountState;error=500,server=<lite server key>,p=0.1;drop,p=0.5`:
+//!
+//! - `latency=<ms>` delays the response, `error=<code>` fails the request with the tonlib error
+//!   code, `drop` never answers and `disconnect` fails every request to the lite server from then on
+//! - `method=<@type>` scopes to the tonlib method, `server=<id>` to the lite server, either by its
+//!   id or by its key, `p=<probability>` fires for a share of requests only
+
+use crate::block::TonError;
+use anyhow::{anyhow, bail};
+use futures::future::{pending, ready, BoxFuture};
+use futures::FutureExt;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::sync::{Arc, OnceLock, RwLock};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tower::load::Load;
+use tower::Service;
+
+pub const FAILPOINTS_ENV: &str = "TONLIBJSON_FAILPOINTS";
+
+/// tonlib error code of requests to disconnected lite servers
+const DISCONNECTED_CODE: i32 = 502;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Fault {
+    Latency(Duration),
+    Error(i32),
+    Drop,
+    Disconnect,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Failpoint {
+    pub fault: Fault,
+    /// tonlib method, any if none
+    pub method: Option<String>,
+    /// lite server id or key, any if none
+    pub lite_server: Option<String>,
+    /// share of matching requests the fault fires for, from 0 to 1
+    pub probability: f64,
+}
+
+impl Failpoint {
+    pub fn new(fault: Fault) -> Self {
+        Self {
+            fault,
+            method: None,
+            lite_server: None,
+            probability: 1.0,
+        }
+    }
+
+    fn matches(&self, lite_server: &str, method: Option<&str>) -> bool {
+        let method = self
+            .method
+            .as_deref()
+            .map_or(true, |expected| Some(expected) == method);
+        let lite_server = self.lite_server.as_deref().map_or(true, |expected| {
+            expected == lite_server
+                || lite_server
+                    .split_once(':')
+                    .is_some_and(|(_, key)| key == expected)
+        });
+
+        method && lite_server
+    }
+}
+
+impl FromStr for Failpoint {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut items = s.split(',').map(str::trim);
+
+        let fault = items.next().unwrap_or_default();
+        let fault = match fault.split_once('=') {
+            Some(("latency", ms)) => Fault::Latency(Duration::from_millis(ms.parse()?)),
+            Some(("error", code)) => Fault::Error(code.parse()?),
+            None if fault == "drop" => Fault::Drop,
+            None if fault == "disconnect" => Fault::Disconnect,
+            _ => bail!("unknown fault of failpoint {}", s),
+        };
+
+        let mut failpoint = Failpoint::new(fault);
+        for item in items {
+            match item.split_once('=') {
+                Some(("method", method)) => failpoint.method = Some(method.to_owned()),
+                Some(("server", id)) => failpoint.lite_server = Some(id.to_owned()),
+                Some(("p", p)) => failpoint.probability = p.parse()?,
+                _ => bail!("unknown scope {} of failpoint {}", item, s),
+            }
+        }
+        if !(0.0..=1.0).contains(&failpoint.probability) {
+            bail!("probability of failpoint {} is out of [0, 1]", s)
+        }
+
+        Ok(failpoint)
+    }
+}
+
+pub fn parse(spec: &str) -> anyhow::Result<Vec<Failpoint>> {
+    spec.split(';')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            s.parse()
+                .map_err(|e| anyhow!("{}: {:#}", FAILPOINTS_ENV, e))
+        })
+        .collect()
+}
+
+#[derive(Debug, Default)]
+pub struct Failpoints {
+    failpoints: RwLock<Vec<Failpoint>>,
+    disconnected: RwLock<HashSet<String>>,
+}
+
+impl Failpoints {
+    /// Failpoints of every lite server client, initially from `TONLIBJSON_FAILPOINTS`
+    pub fn global() -> &'static Failpoints {
+        static FAILPOINTS: OnceLock<Failpoints> = OnceLock::new();
+
+        FAILPOINTS.get_or_init(|| {
+            let failpoints = Failpoints::default();
+            if let Ok(spec) = std::env::var(FAILPOINTS_ENV) {
+                failpoints.set(parse(&spec).unwrap());
+            }
+
+            failpoints
+        })
+    }
+
+    /// Replaces the failpoints, disconnected lite servers are connected again
+    pub fn set(&self, failpoints: Vec<Failpoint>) {
+        tracing::warn!(?failpoints, "failpoints set");
+
+        *self.failpoints.write().unwrap() = failpoints;
+        self.disconnected.write().unwrap().clear();
+    }
+
+    pub fn clear(&self) {
+        self.set(Vec::new());
+    }
+
+    /// Fault of a request, the first failpoint which matches and fires
+    fn fault(&self, lite_server: &str, method: Option<&str>) -> Option<Fault> {
+        if self.disconnected.read().unwrap().contains(lite_server) {
+            return Some(Fault::Disconnect);
+        }
+
+        let fault = self
+            .failpoints
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|failpoint| failpoint.matches(lite_server, method))
+            .find(|failpoint| rand::random::<f64>() < failpoint.probability)
+            .map(|failpoint| failpoint.fault.clone());
+
+        if fault == Some(Fault::Disconnect) {
+            tracing::warn!(lite_server, "failpoint disconnected lite server");
+            self.disconnected
+                .write()
+                .unwrap()
+                .insert(lite_server.to_owned());
+        }
+
+        fault
+    }
+}
+
+/// Injects faults of [`Failpoints`] into requests to a single lite server
+#[derive(Clone)]
+pub struct FailpointService<S> {
+    inner: S,
+    lite_server: Arc<str>,
+    failpoints: &'static Failpoints,
+}
+
+impl<S> FailpointService<S> {
+    pub fn new(
+        inner: S,
+        lite_server: impl Into<Arc<str>>,
+        failpoints: &'static Failpoints,
+    ) -> Self {
+        Self {
+            inner,
+            lite_server: lite_server.into(),
+            failpoints,
+        }
+    }
+}
+
+impl<S, Req> Service<Req> for FailpointService<S>
+where
+    S: Service<Req, Error = anyhow::Error>,
+    S::Response: Send + 'static,
+    S::Future: Send + 'static,
+    Req: Serialize,
+{
+    type Response = S::Response;
+    type Error = anyhow::Error;
+    type Future = BoxFuture<'static, Result<S::Response, anyhow::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        let request = serde_json::to_value(&req).unwrap_or_default();
+        let method = request.get("@type").and_then(|method| method.as_str());
+
+        match self.failpoints.fault(&self.lite_server, method) {
+            None => self.inner.call(req).boxed(),
+            Some(Fault::Latency(latency)) => {
+                let response = self.inner.call(req);
+
+                async move {
+                    tokio::time::sleep(latency).await;
+
+                    response.await
+                }
+                .boxed()
+            }
+            Some(Fault::Error(code)) => {
+                ready(Err(TonError::new(code, "failpoint error").into())).boxed()
+            }
+            Some(Fault::Drop) => pending().boxed(),
+            Some(Fault::Disconnect) => ready(Err(TonError::new(
+                DISCONNECTED_CODE,
+                "LITE_SERVER_NETWORK: failpoint disconnected",
+            )
+            .into()))
+            .boxed(),
+        }
+    }
+}
+
+impl<S: Load> Load for FailpointService<S> {
+    type Metric = S::Metric;
+
+    fn load(&self) -> Self::Metric {
+        self.inner.load()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::BlocksGetMasterchainInfo;
+    use crate::breaker::{BreakerPolicy, BreakerService};
+    use serde_json::{json, Value};
+    use std::borrow::Cow;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use ton_client_util::service::timeout::Timeout;
+    use tower::timeout::error::Elapsed;
+    use tower::util::BoxCloneService;
+    use tower::{service_fn, ServiceExt};
+
+    const LITE_SERVER: &str = "pub:abc";
+
+    fn failpoints(spec: &str) -> &'static Failpoints {
+        let failpoints = Box::leak(Box::default());
+        Failpoints::set(failpoints, parse(spec).unwrap());
+
+        failpoints
+    }
+
+    fn lite_server<Req: Serialize + 'static>(
+        failpoints: &'static Failpoints,
+        calls: Arc<AtomicUsize>,
+    ) -> FailpointService<BoxCloneService<Req, Value, anyhow::Error>> {
+        let inner = BoxCloneService::new(service_fn(move |req: Req| {
+            calls.fetch_add(1, Ordering::SeqCst);
+
+            ready(Ok(serde_json::to_value(req).unwrap()))
+        }));
+
+        FailpointService::new(inner, LITE_SERVER, failpoints)
+    }
+
+    fn ton_error(error: &anyhow::Error) -> i32 {
+        error.downcast_ref::<TonError>().unwrap().code()
+    }
+
+    #[test]
+    fn failpoints_are_parsed() {
+        let failpoints = parse(
+            "latency=200,method=raw.getAccountState; error=500,server=abc,p=0.25 ;drop;disconnect",
+        )
+        .unwrap();
+
+        assert_eq!(
+            failpoints,
+            vec![
+                Failpoint {
+                    method: Some("raw.getAccountState".to_owned()),
+                    ..Failpoint::new(Fault::Latency(Duration::from_millis(200)))
+                },
+                Failpoint {
+                    lite_server: Some("abc".to_owned()),
+                    probability: 0.25,
+                    ..Failpoint::new(Fault::Error(500))
+                },
+                Failpoint::new(Fault::Drop),
+                Failpoint::new(Fault::Disconnect),
+            ]
+        );
+        assert!(parse("").unwrap().is_empty());
+        assert!(parse("crash").is_err());
+        assert!(parse("drop,shard=0").is_err());
+        assert!(parse("drop,p=2").is_err());
+    }
+
+    #[test]
+    fn failpoints_are_scoped() {
+        let failpoint = Failpoint {
+            method: Some("raw.sendMessage".to_owned()),
+            lite_server: Some("abc".to_owned()),
+            ..Failpoint::new(Fault::Drop)
+        };
+
+        assert!(failpoint.matches("pub:abc", Some("raw.sendMessage")));
+        assert!(!failpoint.matches("pub:abd", Some("raw.sendMessage")));
+        assert!(!failpoint.matches("pub:abc", Some("raw.getAccountState")));
+        assert!(!failpoint.matches("pub:abc", None));
+        assert!(Failpoint::new(Fault::Drop).matches("pub:abd", None));
+
+        let failpoints = failpoints("error=500,p=0");
+        assert_eq!(failpoints.fault(LITE_SERVER, None), None);
+    }
+
+    #[tokio::test]
+    async fn error_fails_request_without_lite_server() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let failpoints = failpoints("error=429,method=raw.getAccountState");
+        let mut client = lite_server(failpoints, calls.clone());
+
+        let error = (&mut client)
+            .oneshot(json!({"@type": "raw.getAccountState"}))
+            .await
+            .unwrap_err();
+        assert_eq!(ton_error(&error), 429);
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+
+        let response = (&mut client)
+            .oneshot(json!({"@type": "blocks.getMasterchainInfo"}))
+            .await
+            .unwrap();
+        assert_eq!(response["@type"], "blocks.getMasterchainInfo");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn latency_delays_response() {
+        let failpoints = failpoints("latency=1500");
+        let client = lite_server::<Value>(failpoints, Default::default());
+
+        let started = tokio::time::Instant::now();
+        client.oneshot(json!({"@type": "sync"})).await.unwrap();
+
+        assert_eq!(started.elapsed(), Duration::from_millis(1500));
+    }
+
+    #[tokio::test]
+    async fn disconnect_lasts_until_failpoints_are_set() {
+        let failpoints = failpoints("disconnect,method=sync");
+        let mut client = lite_server::<Value>(failpoints, Default::default());
+
+        let error = (&mut client)
+            .oneshot(json!({"@type": "sync"}))
+            .await
+            .unwrap_err();
+        assert_eq!(ton_error(&error), DISCONNECTED_CODE);
+        let error = (&mut client)
+            .oneshot(json!({"@type": "blocks.getMasterchainInfo"}))
+            .await
+            .unwrap_err();
+        assert_eq!(ton_error(&error), DISCONNECTED_CODE);
+
+        failpoints.clear();
+        assert!((&mut client)
+            .oneshot(json!({"@type": "blocks.getMasterchainInfo"}))
+            .await
+            .is_ok());
+    }
+
+    fn breaker<S>(client: S, failure_threshold: u32) -> BreakerService<Timeout<S>> {
+        BreakerService::new(
+            Timeout::new(client, Duration::from_secs(5)),
+            Cow::Borrowed("test"),
+            Some(BreakerPolicy {
+                failure_threshold,
+                open_for: Duration::from_secs(10),
+            }),
+            None,
+        )
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn dropped_responses_time_out_and_open_breaker() {
+        let failpoints = failpoints("drop");
+        let mut client = breaker(lite_server(failpoints, Default::default()), 2);
+        let breaker = client.reads().unwrap();
+
+        for _ in 0..2 {
+            let error = (&mut client)
+                .oneshot(BlocksGetMasterchainInfo::default())
+                .await
+                .unwrap_err();
+            assert!(error.is::<Elapsed>());
+        }
+
+        assert!(breaker.is_open());
+    }
+
+    #[tokio::test]
+    async fn tonlib_errors_do_not_open_breaker() {
+        let failpoints = failpoints("error=500");
+        let mut client = breaker(lite_server(failpoints, Default::default()), 1);
+        let breaker = client.reads().unwrap();
+
+        for _ in 0..3 {
+            let error = (&mut client)
+                .oneshot(BlocksGetMasterchainInfo::default())
+                .await
+                .unwrap_err();
+            assert!(error.to_string().contains("code 500"));
+        }
+
+        assert!(!breaker.is_open());
+    }
+}
@@ -0,0 +1,806 @@
+use crate::address::{AccountAddressData, ShardContextAccountAddress};
+use crate::block::{InternalTransactionId, TonBlockIdExt};
+use crate::boc::to_base64_boc;
+use crate::ton::{TonClient, TonClientBuilder, TonConfig};
+use crate::tonlib::Tonlib;
+use crate::verify::{signed_message, ValidatorDescr};
+use anyhow::{anyhow, bail};
+use base64::Engine;
+use ed25519_dalek::{Signer, SigningKey};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::collections::{HashMap, VecDeque};
+use std::str::FromStr;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+use toner::tlb::bits::r#as::NBits;
+use toner::tlb::bits::ser::BitWriterExt;
+use toner::tlb::r#as::Ref;
+use toner::tlb::Cell;
+
+/// Single shard of both chains of a [`Scenario`], they never split
+pub const SHARD: i64 = i64::MIN;
+/// Blocks start at this logical time times their seqno
+const BLOCK_LT: i64 = 1_000_000;
+const GENESIS_UTIME: i64 = 1_700_000_000;
+/// Seconds between blocks
+const BLOCK_INTERVAL: i64 = 5;
+
+/// Tonlib serving a [`Scenario`] to the same JSON requests tonlibjson gets, so the client is
+/// exercised without lite servers. Responses are deterministic and come in the order of requests
+#[derive(Debug)]
+pub struct FakeTonlib {
+    scenario: Arc<Scenario>,
+    packets: Mutex<VecDeque<String>>,
+    received: Condvar,
+}
+
+impl FakeTonlib {
+    pub fn new(scenario: Arc<Scenario>) -> Self {
+        Self {
+            scenario,
+            packets: Default::default(),
+            received: Condvar::new(),
+        }
+    }
+
+    fn push(&self, packet: Value) {
+        self.packets.lock().unwrap().push_back(packet.to_string());
+        self.received.notify_one();
+    }
+}
+
+impl Tonlib for FakeTonlib {
+    fn send(&self, request: &str) -> anyhow::Result<()> {
+        let request: Value = serde_json::from_str(request)?;
+
+        // tonlib reports its progress to the last block before it responds
+        if request["@type"] == "sync" {
+            let last_seqno = self.scenario.last_seqno();
+            self.push(json!({
+                "@type": "updateSyncState",
+                "sync_state": {
+                    "@type": "syncStateInProgress",
+                    "from_seqno": last_seqno - 1,
+                    "to_seqno": last_seqno,
+                    "current_seqno": last_seqno - 1,
+                },
+            }));
+            self.push(json!({
+                "@type": "updateSyncState",
+                "sync_state": {"@type": "syncStateDone"},
+            }));
+        }
+
+        let mut response = self.scenario.respond(&request);
+        response["@extra"] = request["@extra"].clone();
+        self.push(response);
+
+        Ok(())
+    }
+
+    fn receive(&self, timeout: Duration) -> anyhow::Result<Cow<'_, str>> {
+        let packets = self.packets.lock().unwrap();
+        let (mut packets, _) = self
+            .received
+            .wait_timeout_while(packets, timeout, |packets| packets.is_empty())
+            .unwrap();
+
+        packets
+            .pop_front()
+            .map(Cow::Owned)
+            .ok_or_else(|| anyhow!("null received"))
+    }
+}
+
+/// Chain of a [`FakeTonlib`]: a masterchain and a basechain whose blocks share seqnos, histories
+/// of accounts, and errors of the next calls of a method. It is shared by the fakes of a pool
+#[derive(Debug)]
+pub struct Scenario {
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+struct State {
+    first_seqno: i32,
+    last_seqno: i32,
+    /// by raw address
+    accounts: HashMap<String, Account>,
+    /// by method
+    failures: HashMap<String, VecDeque<(i32, String)>>,
+    sent: Vec<String>,
+    /// masterchain blocks are signed by a key which isn't the one of the validator
+    forged_signatures: bool,
+}
+
+#[derive(Debug, Default)]
+struct Account {
+    balance: i64,
+    /// the oldest first
+    transactions: Vec<InternalTransactionId>,
+    /// hash of the in_msg by lt, transactions without one have no in_msg
+    in_msgs: HashMap<i64, String>,
+}
+
+/// Builder of a pool of `lite_servers` fakes sharing the scenario
+pub fn client_builder(scenario: &Arc<Scenario>, lite_servers: usize) -> TonClientBuilder {
+    let config = serde_json::from_value::<TonConfig>(json!({
+        "@type": "config.global",
+        "liteservers": (0..lite_servers).map(|i| json!({
+            "id": {"@type": "pub.ed25519", "key": format!("fake-{}", i)},
+            "ip": i,
+            "port": 1,
+        })).collect::<Vec<_>>(),
+    }))
+    .expect("config of fakes is valid");
+
+    let scenario = scenario.clone();
+    TonClientBuilder::from_config(config)
+        .set_tonlib(move |_| Arc::new(FakeTonlib::new(scenario.clone())))
+}
+
+/// Waits until `client` has followed the basechain up to the last block of `scenario`,
+/// requests of basechain accounts aren't routed before
+pub async fn wait_followed(client: &TonClient, scenario: &Scenario) -> anyhow::Result<()> {
+    let last_seqno = scenario.last_seqno();
+    tokio::time::timeout(Duration::from_secs(10), async {
+        while client
+            .look_up_block_by_seqno(0, SHARD, last_seqno)
+            .await
+            .is_err()
+        {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    })
+    .await
+    .map_err(|_| anyhow!("basechain isn't followed up to {}", last_seqno))
+}
+
+/// Id of the block `seqno` of the masterchain, -1, or of the basechain, 0
+pub fn block_id(workchain: i32, seqno: i32) -> TonBlockIdExt {
+    TonBlockIdExt {
+        workchain,
+        shard: SHARD,
+        seqno,
+        root_hash: hash(&format!("root:{}:{}", workchain, seqno)),
+        file_hash: hash(&format!("file:{}:{}", workchain, seqno)),
+    }
+}
+
+impl Scenario {
+    /// Blocks from `first_seqno` to `last_seqno` are available, the cursor looks for the first
+    /// one 200000 blocks behind the last one
+    pub fn new(first_seqno: i32, last_seqno: i32) -> Self {
+        Self {
+            state: Mutex::new(State {
+                first_seqno,
+                last_seqno,
+                accounts: Default::default(),
+                failures: Default::default(),
+                sent: Default::default(),
+                forged_signatures: false,
+            }),
+        }
+    }
+
+    pub fn last_seqno(&self) -> i32 {
+        self.state.lock().unwrap().last_seqno
+    }
+
+    /// Produces `count` blocks
+    pub fn advance(&self, count: i32) {
+        self.state.lock().unwrap().last_seqno += count;
+    }
+
+    /// Adds a transaction of `address` to the block `seqno` of its workchain,
+    /// the block can't be older than the one of the last transaction of the account
+    pub fn add_transaction(
+        &self,
+        address: &str,
+        seqno: i32,
+    ) -> anyhow::Result<InternalTransactionId> {
+        self.push_transaction(address, seqno, None)
+    }
+
+    /// Same as [`Scenario::add_transaction`] with an in_msg of hash `message_hash`
+    pub fn add_transaction_with_message(
+        &self,
+        address: &str,
+        seqno: i32,
+        message_hash: &str,
+    ) -> anyhow::Result<InternalTransactionId> {
+        self.push_transaction(address, seqno, Some(message_hash.to_owned()))
+    }
+
+    fn push_transaction(
+        &self,
+        address: &str,
+        seqno: i32,
+        message_hash: Option<String>,
+    ) -> anyhow::Result<InternalTransactionId> {
+        let address = AccountAddressData::from_str(address)?;
+        let start_lt = seqno as i64 * BLOCK_LT;
+
+        let mut state = self.state.lock().unwrap();
+        let account = state.accounts.entry(address.to_raw_string()).or_default();
+        let lt = account
+            .transactions
+            .last()
+            .map_or(start_lt, |tx| tx.lt.max(start_lt))
+            + 1;
+        if lt >= start_lt + BLOCK_LT {
+            bail!("block {} is older than the last transaction", seqno);
+        }
+
+        let tx = InternalTransactionId {
+            hash: hash(&format!("tx:{}:{}", address.to_raw_string(), lt)),
+            lt,
+        };
+        account.transactions.push(tx.clone());
+        if let Some(message_hash) = message_hash {
+            account.in_msgs.insert(lt, message_hash);
+        }
+
+        Ok(tx)
+    }
+
+    pub fn set_balance(&self, address: &str, balance: i64) -> anyhow::Result<()> {
+        let address = AccountAddressData::from_str(address)?;
+
+        let mut state = self.state.lock().unwrap();
+        state
+            .accounts
+            .entry(address.to_raw_string())
+            .or_default()
+            .balance = balance;
+
+        Ok(())
+    }
+
+    /// The next `times` calls of `method`, e.g. `raw.getAccountState`, fail with a tonlib error
+    pub fn fail(&self, method: &str, times: usize, code: i32, message: &str) {
+        let mut state = self.state.lock().unwrap();
+        state
+            .failures
+            .entry(method.to_owned())
+            .or_default()
+            .extend(std::iter::repeat_n((code, message.to_owned()), times));
+    }
+
+    /// Signatures of masterchain blocks are made by a key other than the one of the validator
+    /// from now on, so that verification of blocks fails
+    pub fn forge_signatures(&self) {
+        self.state.lock().unwrap().forged_signatures = true;
+    }
+
+    /// Bodies of the sent messages, the oldest first
+    pub fn sent(&self) -> Vec<String> {
+        self.state.lock().unwrap().sent.clone()
+    }
+
+    /// Response of tonlib to `request`, without its `@extra`
+    pub fn respond(&self, request: &Value) -> Value {
+        let method = request["@type"].as_str().unwrap_or_default();
+        if method == "withBlock" {
+            return self.respond(&request["function"]);
+        }
+
+        let mut state = self.state.lock().unwrap();
+        if let Some((code, message)) = state.failures.get_mut(method).and_then(VecDeque::pop_front)
+        {
+            return error(code, &message);
+        }
+
+        let response = match method {
+            "init" => Ok(json!({
+                "@type": "options.info",
+                "config_info": {
+                    "@type": "options.configInfo",
+                    "default_wallet_id": "698983191",
+                    "default_rwallet_init_public_key": "",
+                },
+            })),
+            "sync" => Ok(block_json(&block_id(-1, state.last_seqno))),
+            "blocks.getMasterchainInfo" => Ok(state.masterchain_info()),
+            "blocks.lookupBlock" => state.lookup_block(request),
+            "blocks.getBlockHeader" => state.block_header(request),
+            "blocks.getShards" => state.shards(request),
+            "blocks.getTransactions" => state.block_transactions(request),
+            "blocks.getMasterchainBlockSignatures" => state.block_signatures(request),
+            "getConfigParam" => state.config_param(request),
+            "blocks.getOutMsgQueueSizes" => Ok(state.out_msg_queue_sizes()),
+            "raw.getAccountState" => state.account_state(request),
+            "raw.getTransactionsV2" => state.account_transactions(request),
+            "raw.sendMessage" => state.send(request).map(|_| json!({"@type": "ok"})),
+            "raw.sendMessageReturnHash" => state
+                .send(request)
+                .map(|body| json!({"@type": "raw.extMessageInfo", "hash": hash(&body)})),
+            _ => return error(400, &format!("unknown method {}", method)),
+        };
+
+        response.unwrap_or_else(|e| error(500, &e.to_string()))
+    }
+}
+
+impl State {
+    fn masterchain_info(&self) -> Value {
+        json!({
+            "@type": "blocks.masterchainInfo",
+            "last": block_json(&block_id(-1, self.last_seqno)),
+            "state_root_hash": hash(&format!("state:{}", self.last_seqno)),
+            "init": block_json(&block_id(-1, 0)),
+        })
+    }
+
+    /// Sizes at the last blocks, the queues are empty
+    fn out_msg_queue_sizes(&self) -> Value {
+        json!({
+            "@type": "blocks.outMsgQueueSizes",
+            "shards": [-1, 0].map(|workchain| json!({
+                "@type": "blocks.outMsgQueueSize",
+                "id": block_json(&block_id(workchain, self.last_seqno)),
+                "size": 0,
+            })),
+            "ext_msg_queue_size_limit": 8000,
+        })
+    }
+
+    fn available(&self, workchain: i32, shard: i64, seqno: i32) -> anyhow::Result<()> {
+        if !matches!(workchain, -1 | 0)
+            || shard != SHARD
+            || seqno < self.first_seqno
+            || seqno > self.last_seqno
+        {
+            bail!("LITE_SERVER_NOTREADY: cannot load block");
+        }
+
+        Ok(())
+    }
+
+    /// Block of the request `id`, it has to be the one of the scenario
+    fn block(&self, id: &Value) -> anyhow::Result<TonBlockIdExt> {
+        let block: TonBlockIdExt = serde_json::from_value(id.clone())?;
+        self.available(block.workchain, block.shard, block.seqno)?;
+        if block != block_id(block.workchain, block.seqno) {
+            bail!("LITE_SERVER_UNKNOWN: block hash mismatch");
+        }
+
+        Ok(block)
+    }
+
+    fn lookup_block(&self, request: &Value) -> anyhow::Result<Value> {
+        let id = &request["id"];
+        let workchain = int(&id["workchain"])? as i32;
+        let shard = int(&id["shard"])?;
+        let seqno = match int(&request["mode"])? {
+            1 => int(&id["seqno"])? as i32,
+            2 => (int(&request["lt"])? / BLOCK_LT) as i32,
+            4 => ((int(&request["utime"])? - GENESIS_UTIME) / BLOCK_INTERVAL) as i32,
+            mode => bail!("unknown lookup mode {}", mode),
+        };
+        self.available(workchain, shard, seqno)?;
+
+        Ok(block_json(&block_id(workchain, seqno)))
+    }
+
+    fn block_header(&self, request: &Value) -> anyhow::Result<Value> {
+        let block = self.block(&request["id"])?;
+        let start_lt = block.seqno as i64 * BLOCK_LT;
+        let prev_blocks: Vec<_> = (block.seqno > self.first_seqno)
+            .then(|| block_json(&block_id(block.workchain, block.seqno - 1)))
+            .into_iter()
+            .collect();
+
+        Ok(json!({
+            "@type": "blocks.header",
+            "id": block_json(&block),
+            "global_id": -239,
+            "version": 0,
+            "flags": 1,
+            "after_merge": false,
+            "after_split": false,
+            "before_split": false,
+            "want_merge": false,
+            "want_split": false,
+            "validator_list_hash_short": 0,
+            "catchain_seqno": 0,
+            "min_ref_mc_seqno": block.seqno,
+            "is_key_block": false,
+            "prev_key_block_seqno": 0,
+            "start_lt": start_lt.to_string(),
+            "end_lt": (start_lt + BLOCK_LT - 1).to_string(),
+            "gen_utime": utime(block.seqno).to_string(),
+            "vert_seqno": 1,
+            "prev_blocks": prev_blocks,
+        }))
+    }
+
+    fn block_signatures(&self, request: &Value) -> anyhow::Result<Value> {
+        let seqno = int(&request["seqno"])? as i32;
+        self.available(-1, SHARD, seqno)?;
+
+        let block = block_id(-1, seqno);
+        let key = match self.forged_signatures {
+            true => SigningKey::from_bytes(&[0; 32]),
+            false => validator(),
+        };
+        let signature = key.sign(&signed_message(&block)?);
+
+        Ok(json!({
+            "@type": "blocks.blockSignatures",
+            "id": block_json(&block),
+            "signatures": [{
+                "@type": "blocks.signature",
+                "node_id_short": base64::engine::general_purpose::STANDARD.encode(validator_descr().node_id_short()),
+                "signature": base64::engine::general_purpose::STANDARD.encode(signature.to_bytes()),
+            }],
+        }))
+    }
+
+    /// Only the validator set, param 34, is there. Its single validator holds every block
+    fn config_param(&self, request: &Value) -> anyhow::Result<Value> {
+        let param = int(&request["param"])?;
+        if param != 34 {
+            bail!("config param {} is not set", param);
+        }
+
+        let validator = validator_descr();
+        let mut validators = Cell::builder();
+        // `Hashmap 16` of the single key 0, hml_same$11 v:0 n:16
+        validators
+            .pack_as::<_, NBits<2>>(0b11_u8)?
+            .pack(false)?
+            .pack_as::<_, NBits<5>>(16_u8)?
+            // validator#53 ed25519_pubkey#8e81278a
+            .pack(0x53_u8)?
+            .pack(0x8e81278a_u32)?
+            .pack(validator.public_key)?
+            .pack(validator.weight)?;
+        let mut validator_set = Cell::builder();
+        // validators_ext#12 with a HashmapE
+        validator_set
+            .pack(0x12_u8)?
+            .pack(GENESIS_UTIME as u32)?
+            .pack(u32::MAX)?
+            .pack(1_u16)?
+            .pack(1_u16)?
+            .pack(validator.weight)?
+            .pack(true)?
+            .store_as::<_, Ref>(validators.into_cell())?;
+
+        Ok(json!({
+            "@type": "configInfo",
+            "config": {
+                "@type": "tvm.cell",
+                "bytes": to_base64_boc(validator_set.into_cell())?,
+            },
+        }))
+    }
+
+    fn shards(&self, request: &Value) -> anyhow::Result<Value> {
+        let block = self.block(&request["id"])?;
+        if block.workchain != -1 {
+            bail!("shards of a workchain block");
+        }
+
+        Ok(json!({
+            "@type": "blocks.shards",
+            "shards": [block_json(&block_id(0, block.seqno))],
+        }))
+    }
+
+    /// Transactions of the block ordered by account and lt, as lite servers list them
+    fn block_transactions(&self, request: &Value) -> anyhow::Result<Value> {
+        const REVERSE: i64 = 64;
+        const AFTER: i64 = 128;
+
+        let block = self.block(&request["id"])?;
+        let mode = int(&request["mode"])?;
+        let count = int(&request["count"])? as usize;
+        let start_lt = block.seqno as i64 * BLOCK_LT;
+
+        let mut transactions: Vec<_> = self
+            .accounts
+            .iter()
+            .filter_map(|(address, account)| {
+                let address = AccountAddressData::from_str(address).ok()?;
+
+                (address.chain_id == block.workchain).then_some((address.bytes, account))
+            })
+            .flat_map(|(bytes, account)| {
+                account
+                    .transactions
+                    .iter()
+                    .filter(move |tx| tx.lt >= start_lt && tx.lt < start_lt + BLOCK_LT)
+                    .map(move |tx| (bytes, tx))
+            })
+            .collect();
+        transactions.sort_by_key(|(bytes, tx)| (*bytes, tx.lt));
+        if mode & REVERSE != 0 {
+            transactions.reverse();
+        }
+        if mode & AFTER != 0 {
+            let after = &request["after"];
+            let account = ShardContextAccountAddress::from_str(
+                after["account"].as_str().unwrap_or_default(),
+            )?;
+            let after = (account.bytes, int(&after["lt"])?);
+            if let Some(position) = transactions
+                .iter()
+                .position(|(bytes, tx)| (*bytes, tx.lt) == after)
+            {
+                transactions.drain(..=position);
+            }
+        }
+
+        let incomplete = transactions.len() > count;
+        let transactions: Vec<_> = transactions
+            .into_iter()
+            .take(count)
+            .map(|(bytes, tx)| {
+                json!({
+                    "@type": "blocks.shortTxId",
+                    "mode": 7,
+                    "account": base64::engine::general_purpose::STANDARD.encode(bytes),
+                    "lt": tx.lt.to_string(),
+                    "hash": tx.hash,
+                })
+            })
+            .collect();
+
+        Ok(json!({
+            "@type": "blocks.transactions",
+            "id": block_json(&block),
+            "req_count": count,
+            "incomplete": incomplete,
+            "transactions": transactions,
+        }))
+    }
+
+    fn account_state(&self, request: &Value) -> anyhow::Result<Value> {
+        let address = address(&request["account_address"])?;
+        let account = self.accounts.get(&address.to_raw_string());
+
+        Ok(json!({
+            "@type": "raw.fullAccountState",
+            "balance": account.map_or(-1, |account| account.balance).to_string(),
+            "code": "",
+            "data": "",
+            "last_transaction_id": transaction_id_json(
+                account.and_then(|account| account.transactions.last())
+            ),
+            // states are read at a masterchain block whatever the workchain of the account
+            "block_id": block_json(&block_id(-1, self.last_seqno)),
+            "frozen_hash": "",
+            "sync_utime": utime(self.last_seqno).to_string(),
+        }))
+    }
+
+    /// Transactions from the requested one to older ones, the newest first
+    fn account_transactions(&self, request: &Value) -> anyhow::Result<Value> {
+        let account_address = &request["account_address"];
+        let address = address(account_address)?;
+        let from = &request["from_transaction_id"];
+        let (lt, hash) = (int(&from["lt"])?, from["hash"].as_str().unwrap_or_default());
+        let count = int(&request["count"])? as usize;
+
+        let account = self.accounts.get(&address.to_raw_string());
+        let transactions = account
+            .map(|account| account.transactions.as_slice())
+            .unwrap_or_default();
+        let in_msg = |lt: i64| {
+            account
+                .and_then(|account| account.in_msgs.get(&lt))
+                .map(|hash| {
+                    json!({
+                        "@type": "raw.message",
+                        "hash": hash,
+                        "source": {"@type": "accountAddress", "account_address": ""},
+                        "destination": account_address,
+                        "value": "0",
+                        "fwd_fee": "0",
+                        "ihr_fee": "0",
+                        "created_lt": "0",
+                        "body_hash": "",
+                        "msg_data": {"@type": "msg.dataRaw", "body": "te6cckEBAQEAAgAAAEysuc0=", "init_state": ""}
+                    })
+                })
+        };
+        let Some(end) = transactions
+            .iter()
+            .position(|tx| tx.lt == lt && tx.hash == hash)
+        else {
+            bail!("LITE_SERVER_UNKNOWN: cannot find transaction {}", lt);
+        };
+        let start = (end + 1).saturating_sub(count);
+
+        Ok(json!({
+            "@type": "raw.transactions",
+            "transactions": transactions[start..=end].iter().rev().map(|tx| json!({
+                "@type": "raw.transaction",
+                "address": account_address,
+                "utime": utime((tx.lt / BLOCK_LT) as i32).to_string(),
+                "data": "",
+                "transaction_id": transaction_id_json(Some(tx)),
+                "fee": "0",
+                "storage_fee": "0",
+                "other_fee": "0",
+                "in_msg": in_msg(tx.lt),
+                "out_msgs": [],
+            })).collect::<Vec<_>>(),
+            "previous_transaction_id": transaction_id_json(start.checked_sub(1).map(|i| &transactions[i])),
+        }))
+    }
+
+    fn send(&mut self, request: &Value) -> anyhow::Result<String> {
+        let body = request["body"]
+            .as_str()
+            .filter(|body| !body.is_empty())
+            .ok_or_else(|| anyhow!("empty message"))?;
+        self.sent.push(body.to_owned());
+
+        Ok(body.to_owned())
+    }
+}
+
+/// Key of the single validator of a [`Scenario`]
+fn validator() -> SigningKey {
+    SigningKey::from_bytes(&[7; 32])
+}
+
+fn validator_descr() -> ValidatorDescr {
+    ValidatorDescr {
+        public_key: validator().verifying_key().to_bytes(),
+        weight: 1,
+    }
+}
+
+fn error(code: i32, message: &str) -> Value {
+    json!({"@type": "error", "code": code, "message": message})
+}
+
+fn hash(data: &str) -> String {
+    base64::engine::general_purpose::STANDARD.encode(Sha256::digest(data))
+}
+
+fn utime(seqno: i32) -> i64 {
+    GENESIS_UTIME + seqno as i64 * BLOCK_INTERVAL
+}
+
+/// Number of a request, tonlib takes both numbers and strings of them
+fn int(value: &Value) -> anyhow::Result<i64> {
+    match value {
+        Value::Number(n) => n.as_i64(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+    .ok_or_else(|| anyhow!("expected an integer, got {}", value))
+}
+
+fn address(account_address: &Value) -> anyhow::Result<AccountAddressData> {
+    account_address["account_address"]
+        .as_str()
+        .ok_or_else(|| anyhow!("empty account address"))
+        .and_then(AccountAddressData::from_str)
+}
+
+fn block_json(block: &TonBlockIdExt) -> Value {
+    json!({
+        "@type": "ton.blockIdExt",
+        "workchain": block.workchain,
+        "shard": block.shard.to_string(),
+        "seqno": block.seqno,
+        "root_hash": block.root_hash,
+        "file_hash": block.file_hash,
+    })
+}
+
+/// Zero id for none, as tonlib has it
+fn transaction_id_json(tx: Option<&InternalTransactionId>) -> Value {
+    json!({
+        "@type": "internal.transactionId",
+        "lt": tx.map_or(0, |tx| tx.lt).to_string(),
+        "hash": tx.map_or("", |tx| tx.hash.as_str()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scenario() -> Scenario {
+        let scenario = Scenario::new(1, 10);
+        for account in 1..=3 {
+            for _ in 0..2 {
+                scenario
+                    .add_transaction(&format!("0:{:064x}", account), 5)
+                    .unwrap();
+            }
+        }
+
+        scenario
+    }
+
+    fn lts(response: &Value) -> Vec<i64> {
+        response["transactions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|tx| int(&tx["lt"]).or_else(|_| int(&tx["transaction_id"]["lt"])))
+            .collect::<anyhow::Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn block_transactions_are_paged_from_both_ends() {
+        let scenario = scenario();
+        let request = |mode: i64, after: Value| {
+            json!({
+                "@type": "blocks.getTransactions",
+                "id": block_json(&block_id(0, 5)),
+                "mode": mode,
+                "count": 4,
+                "after": after,
+            })
+        };
+
+        let first = scenario.respond(&request(7, json!({})));
+        assert_eq!(
+            lts(&first),
+            vec![5_000_001, 5_000_002, 5_000_001, 5_000_002]
+        );
+        assert_eq!(first["incomplete"], true);
+
+        let last = &first["transactions"][3];
+        let rest = scenario.respond(&request(
+            7 + 128,
+            json!({"account": last["account"], "lt": last["lt"]}),
+        ));
+        assert_eq!(lts(&rest), vec![5_000_001, 5_000_002]);
+        assert_eq!(rest["incomplete"], false);
+
+        let reverse = scenario.respond(&request(7 + 64, json!({})));
+        assert_eq!(reverse["transactions"][0], rest["transactions"][1]);
+    }
+
+    #[test]
+    fn account_transactions_end_with_zero_id() {
+        let scenario = scenario();
+        let address = json!({"account_address": format!("0:{:064x}", 1)});
+        let state =
+            scenario.respond(&json!({"@type": "raw.getAccountState", "account_address": address}));
+
+        let transactions = scenario.respond(&json!({
+            "@type": "raw.getTransactionsV2",
+            "account_address": address,
+            "from_transaction_id": state["last_transaction_id"],
+            "count": 16,
+        }));
+
+        assert_eq!(lts(&transactions), vec![5_000_002, 5_000_001]);
+        assert_eq!(
+            transactions["previous_transaction_id"],
+            transaction_id_json(None)
+        );
+    }
+
+    #[test]
+    fn failures_come_first_and_run_out() {
+        let scenario = scenario();
+        scenario.fail("sync", 1, 500, "LITE_SERVER_NOTREADY");
+
+        assert_eq!(
+            scenario.respond(&json!({"@type": "sync"})),
+            error(500, "LITE_SERVER_NOTREADY")
+        );
+        assert_eq!(
+            scenario.respond(&json!({"@type": "sync"})),
+            block_json(&block_id(-1, 10))
+        );
+        assert_eq!(
+            scenario.respond(&json!({"@type": "blocks.lookupBlock", "mode": 1, "id": {"workchain": -1, "shard": SHARD, "seqno": 11}, "lt": 0, "utime": 0}))["code"],
+            500
+        );
+    }
+}
@@ -0,0 +1,290 @@
+use crate::tonlib::Tonlib;
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::borrow::Cow;
+use std::collections::{HashMap, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Single tonlib request and its response, fixture files hold one entry per line
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FixtureEntry {
+    /// unix time in milliseconds
+    pub timestamp: u64,
+    /// request without `@extra`, its `@type` is the method
+    pub request: Value,
+    /// response without `@extra`, tonlib errors included
+    pub response: Value,
+}
+
+/// Appends every tonlib request and response to a fixture file, the file is written by
+/// a thread of its own so that responses never wait for the disk
+#[derive(Debug)]
+pub struct Recorder {
+    entries: Option<Sender<FixtureEntry>>,
+    writer: Option<JoinHandle<()>>,
+}
+
+impl Recorder {
+    pub fn create(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        let (entries, rx) = mpsc::channel::<FixtureEntry>();
+        let writer = std::thread::spawn(move || {
+            for entry in rx {
+                // a single write per line, so that lines of other recorders aren't interleaved
+                let result = serde_json::to_string(&entry)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|line| Ok(file.write_all(format!("{}\n", line).as_bytes())?));
+                if let Err(e) = result {
+                    tracing::warn!(error = ?e, "failed to record fixture");
+                }
+            }
+        });
+
+        Ok(Self {
+            entries: Some(entries),
+            writer: Some(writer),
+        })
+    }
+
+    pub(crate) fn record(&self, request: Value, response: Value) {
+        let entry = FixtureEntry {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or_default(),
+            request: strip_extra(request),
+            response: strip_extra(response),
+        };
+
+        if let Some(entries) = &self.entries {
+            let _ = entries.send(entry);
+        }
+    }
+}
+
+impl Drop for Recorder {
+    /// Waits for the recorded entries to be written
+    fn drop(&mut self) {
+        self.entries.take();
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+/// Responses of a fixture file keyed by the canonical request,
+/// repeated requests get the recorded responses in order and the last one after that
+#[derive(Debug, Default)]
+pub struct Fixture {
+    responses: Mutex<HashMap<String, VecDeque<Value>>>,
+}
+
+impl Fixture {
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        Self::from_reader(BufReader::new(File::open(path)?))
+    }
+
+    pub fn from_reader(reader: impl BufRead) -> anyhow::Result<Self> {
+        let mut responses: HashMap<String, VecDeque<Value>> = HashMap::new();
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let entry: FixtureEntry = serde_json::from_str(&line)?;
+            responses
+                .entry(canonical(&strip_extra(entry.request)))
+                .or_default()
+                .push_back(entry.response);
+        }
+
+        Ok(Self {
+            responses: Mutex::new(responses),
+        })
+    }
+
+    pub fn response(&self, request: &Value) -> Option<Value> {
+        let mut responses = self.responses.lock().unwrap();
+        let queue = responses.get_mut(&canonical(request))?;
+
+        if queue.len() > 1 {
+            queue.pop_front()
+        } else {
+            queue.front().cloned()
+        }
+    }
+}
+
+/// Tonlib answering with the responses of a [`Fixture`], so that [`crate::ton::TonClient`] replays
+/// a recorded session, requests missing from the fixture fail with a tonlib error
+#[derive(Debug)]
+pub struct ReplayTonlib {
+    fixture: Arc<Fixture>,
+    packets: Mutex<VecDeque<String>>,
+    received: Condvar,
+}
+
+impl ReplayTonlib {
+    pub fn new(fixture: Arc<Fixture>) -> Self {
+        Self {
+            fixture,
+            packets: Default::default(),
+            received: Condvar::new(),
+        }
+    }
+}
+
+impl Tonlib for ReplayTonlib {
+    fn send(&self, request: &str) -> anyhow::Result<()> {
+        let mut request: Value = serde_json::from_str(request)?;
+        let extra = request["@extra"].take();
+        let request = strip_extra(request);
+
+        let mut response = self.fixture.response(&request).unwrap_or_else(|| {
+            json!({"@type": "error", "code": 500, "message": format!("no fixture for {}", request)})
+        });
+        response["@extra"] = extra;
+
+        self.packets.lock().unwrap().push_back(response.to_string());
+        self.received.notify_one();
+
+        Ok(())
+    }
+
+    fn receive(&self, timeout: Duration) -> anyhow::Result<Cow<'_, str>> {
+        let packets = self.packets.lock().unwrap();
+        let (mut packets, _) = self
+            .received
+            .wait_timeout_while(packets, timeout, |packets| packets.is_empty())
+            .unwrap();
+
+        packets
+            .pop_front()
+            .map(Cow::Owned)
+            .ok_or_else(|| anyhow!("null received"))
+    }
+}
+
+fn strip_extra(mut value: Value) -> Value {
+    if let Some(object) = value.as_object_mut() {
+        object.remove("@extra");
+    }
+
+    value
+}
+
+/// JSON with object keys sorted, so field order doesn't matter
+fn canonical(value: &Value) -> String {
+    fn sort(value: &Value) -> Value {
+        match value {
+            Value::Object(object) => {
+                let mut keys: Vec<_> = object.keys().collect();
+                keys.sort();
+
+                Value::Object(
+                    keys.into_iter()
+                        .map(|k| (k.clone(), sort(&object[k])))
+                        .collect(),
+                )
+            }
+            Value::Array(array) => Value::Array(array.iter().map(sort).collect()),
+            v => v.clone(),
+        }
+    }
+
+    sort(value).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn canonical_ignores_key_order() {
+        assert_eq!(
+            canonical(&json!({"b": 1, "a": {"d": 2, "c": [{"f": 3, "e": 4}]}})),
+            canonical(&json!({"a": {"c": [{"e": 4, "f": 3}], "d": 2}, "b": 1}))
+        );
+    }
+
+    #[test]
+    fn fixture_replays_in_order() {
+        let lines = [
+            json!({"timestamp": 1, "request": {"@type": "a", "@extra": "x"}, "response": {"n": 1}}),
+            json!({"timestamp": 2, "request": {"@type": "a"}, "response": {"n": 2}}),
+            json!({"timestamp": 3, "request": {"@type": "b"}, "response": {"n": 3}}),
+        ]
+        .map(|l| l.to_string())
+        .join("\n");
+
+        let fixture = Fixture::from_reader(lines.as_bytes()).unwrap();
+
+        assert_eq!(
+            fixture.response(&json!({"@type": "a"})),
+            Some(json!({"n": 1}))
+        );
+        assert_eq!(
+            fixture.response(&json!({"@type": "a"})),
+            Some(json!({"n": 2}))
+        );
+        assert_eq!(
+            fixture.response(&json!({"@type": "a"})),
+            Some(json!({"n": 2}))
+        );
+        assert_eq!(
+            fixture.response(&json!({"@type": "b"})),
+            Some(json!({"n": 3}))
+        );
+        assert_eq!(fixture.response(&json!({"@type": "c"})), None);
+    }
+
+    #[test]
+    fn recorder_strips_extra() {
+        let path = std::env::temp_dir().join(format!("fixture-{}.jsonl", uuid::Uuid::new_v4()));
+        let recorder = Recorder::create(&path).unwrap();
+
+        recorder.record(
+            json!({"@type": "a", "@extra": "x"}),
+            json!({"@type": "ok", "@extra": "x"}),
+        );
+        drop(recorder);
+
+        let fixture = Fixture::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            fixture.response(&json!({"@type": "a"})),
+            Some(json!({"@type": "ok"}))
+        );
+    }
+
+    #[test]
+    fn replay_tonlib_answers_with_recorded_responses() {
+        let lines =
+            [json!({"timestamp": 1, "request": {"@type": "a"}, "response": {"@type": "ok"}})]
+                .map(|l| l.to_string())
+                .join("\n");
+        let tonlib = ReplayTonlib::new(Arc::new(Fixture::from_reader(lines.as_bytes()).unwrap()));
+
+        tonlib.send(r#"{"@type": "a", "@extra": "1"}"#).unwrap();
+        tonlib.send(r#"{"@type": "b", "@extra": "2"}"#).unwrap();
+
+        let receive = || {
+            serde_json::from_str::<Value>(&tonlib.receive(Duration::from_secs(1)).unwrap()).unwrap()
+        };
+        assert_eq!(receive(), json!({"@type": "ok", "@extra": "1"}));
+        let error = receive();
+        assert_eq!(error["@type"], "error");
+        assert_eq!(error["@extra"], "2");
+        assert!(tonlib.receive(Duration::from_millis(10)).is_err());
+    }
+}
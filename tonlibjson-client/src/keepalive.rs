@@ -0,0 +1,194 @@
+use crate::block::BlocksGetMasterchainInfo;
+use futures::never::Never;
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::time::{interval, timeout, Instant, MissedTickBehavior};
+use tower::{Service, ServiceExt};
+
+/// Probing of idle lite server connections, see [`crate::ton::TonClientBuilder::set_keepalive`]
+#[derive(Debug, Clone, Copy)]
+pub struct KeepalivePolicy {
+    /// a connection without a response for this long is probed
+    pub idle: Duration,
+    /// a probe without a response in time marks the connection dead, it's rebuilt
+    pub timeout: Duration,
+}
+
+impl Default for KeepalivePolicy {
+    fn default() -> Self {
+        Self {
+            idle: Duration::from_secs(30),
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Time of the last successful response of a lite server
+pub(crate) type LastResponse = Arc<Mutex<Instant>>;
+
+/// Outcome of the keepalive probes of a lite server
+#[derive(Debug, Default)]
+pub(crate) struct Liveness {
+    dead: AtomicBool,
+    last_probe: Mutex<Option<Duration>>,
+}
+
+impl Liveness {
+    /// Missed the last probe, the lite server isn't routed to until it's rebuilt or answers one
+    pub(crate) fn is_dead(&self) -> bool {
+        self.dead.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn last_probe(&self) -> Option<Duration> {
+        *self.last_probe.lock().unwrap()
+    }
+}
+
+/// Probes lite servers of a pool, their ids are sent to `rebuilds` as they miss a probe
+#[derive(Debug, Clone)]
+pub(crate) struct Keepalive {
+    policy: KeepalivePolicy,
+    network: Cow<'static, str>,
+    rebuilds: UnboundedSender<String>,
+}
+
+impl Keepalive {
+    pub(crate) fn new(
+        policy: KeepalivePolicy,
+        network: Cow<'static, str>,
+        rebuilds: UnboundedSender<String>,
+    ) -> Self {
+        Self {
+            policy,
+            network,
+            rebuilds,
+        }
+    }
+
+    /// Probes `client` once it has been idle for the policy's time, probes are sent to `client`
+    /// directly so they are neither counted as requests nor recorded as latencies of routing
+    pub(crate) async fn run<S>(
+        self,
+        id: Cow<'static, str>,
+        mut client: S,
+        last_response: LastResponse,
+        liveness: Arc<Liveness>,
+    ) -> Never
+    where
+        S: Service<BlocksGetMasterchainInfo>,
+    {
+        metrics::describe_gauge!(
+            "ton_liteserver_probe_seconds",
+            "Round trip of the last keepalive probe of an idle connection"
+        );
+        metrics::describe_counter!(
+            "ton_liteserver_probes_total",
+            "Total count of keepalive probes by result"
+        );
+
+        let mut timer = interval(self.policy.idle / 2);
+        timer.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+        loop {
+            timer.tick().await;
+
+            let idle = last_response.lock().unwrap().elapsed();
+            if idle < self.policy.idle && !liveness.is_dead() {
+                continue;
+            }
+
+            let started_at = Instant::now();
+            let probe = timeout(
+                self.policy.timeout,
+                (&mut client).oneshot(BlocksGetMasterchainInfo::new()),
+            )
+            .await;
+            if let Ok(Ok(_)) = probe {
+                let rtt = started_at.elapsed();
+                metrics::gauge!("ton_liteserver_probe_seconds", "network" => self.network.clone(), "liteserver_id" => id.clone())
+                    .set(rtt.as_secs_f64());
+                metrics::counter!("ton_liteserver_probes_total", "network" => self.network.clone(), "liteserver_id" => id.clone(), "result" => "ok")
+                    .increment(1);
+
+                *last_response.lock().unwrap() = Instant::now();
+                liveness.last_probe.lock().unwrap().replace(rtt);
+                if liveness.dead.swap(false, Ordering::Relaxed) {
+                    tracing::info!(liteserver_id = %id, "lite server answers probes again");
+                }
+
+                continue;
+            }
+
+            metrics::counter!("ton_liteserver_probes_total", "network" => self.network.clone(), "liteserver_id" => id.clone(), "result" => "missed")
+                .increment(1);
+            liveness.dead.store(true, Ordering::Relaxed);
+            tracing::warn!(liteserver_id = %id, ?idle, "lite server missed a keepalive probe, rebuilding its connection");
+            let _ = self.rebuilds.send(id.to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicU32;
+
+    fn policy() -> KeepalivePolicy {
+        KeepalivePolicy {
+            idle: Duration::from_secs(10),
+            timeout: Duration::from_secs(1),
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn idle_connections_are_probed_and_rebuilt() {
+        let (tx, mut rebuilds) = tokio::sync::mpsc::unbounded_channel();
+        let answering = Arc::new(AtomicBool::new(true));
+        let probes = Arc::new(AtomicU32::new(0));
+        let client = tower::service_fn({
+            let answering = answering.clone();
+            let probes = probes.clone();
+
+            move |_: BlocksGetMasterchainInfo| {
+                probes.fetch_add(1, Ordering::Relaxed);
+                let answering = answering.load(Ordering::Relaxed);
+
+                async move {
+                    if !answering {
+                        std::future::pending::<()>().await;
+                    }
+
+                    Ok::<_, anyhow::Error>(())
+                }
+            }
+        });
+        let last_response = LastResponse::new(Mutex::new(Instant::now()));
+        let liveness = Arc::new(Liveness::default());
+        tokio::spawn(Keepalive::new(policy(), Cow::Borrowed("test"), tx).run(
+            Cow::from("ls"),
+            client,
+            last_response.clone(),
+            liveness.clone(),
+        ));
+
+        tokio::time::sleep(Duration::from_secs(6)).await;
+        assert_eq!(probes.load(Ordering::Relaxed), 0);
+
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        assert_eq!(probes.load(Ordering::Relaxed), 1);
+        assert!(!liveness.is_dead());
+        assert!(liveness.last_probe().is_some());
+
+        answering.store(false, Ordering::Relaxed);
+        tokio::time::sleep(Duration::from_secs(12)).await;
+        assert!(liveness.is_dead());
+        assert_eq!(rebuilds.recv().await.unwrap(), "ls");
+
+        answering.store(true, Ordering::Relaxed);
+        tokio::time::sleep(Duration::from_secs(6)).await;
+        assert!(!liveness.is_dead());
+    }
+}
@@ -1,12 +1,43 @@
+pub mod account_cell;
 pub mod address;
 pub mod block;
+pub mod boc;
+pub mod breaker;
+pub mod call_options;
 mod client;
 mod cursor_client;
 mod deserialize;
 mod error;
+#[cfg(feature = "failpoints")]
+pub mod failpoint;
+pub mod fake;
+pub mod fixture;
+pub mod keepalive;
+pub mod listing;
 mod make;
 mod metric;
+mod parsed_account;
+pub mod pool;
+pub mod proof;
+pub mod read_context;
+pub mod reorg;
+pub mod replica;
 mod request;
 mod retry;
+pub mod send;
 mod session;
+pub mod shadow;
+pub mod shard;
+mod span;
 pub mod ton;
+pub mod tonlib;
+pub mod traffic;
+pub mod transaction;
+pub mod transport;
+pub mod utime;
+pub mod verify;
+pub mod workchain;
+pub mod zero_state;
+
+pub use ton_client_util::router::consistency;
+pub use ton_client_util::service::deadline;
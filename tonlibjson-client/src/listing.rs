@@ -0,0 +1,85 @@
+use futures::{Stream, StreamExt};
+use tokio_stream::StreamMap;
+
+/// Items of a listing read from both of its ends at once
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BothEnds<T> {
+    pub items: Vec<T>,
+    /// the ends met, a lite server which cuts a listing short without telling leaves a gap
+    /// between them otherwise
+    pub met: bool,
+}
+
+/// Reads `forward` and `reverse`, the same listing in opposite orders, until they meet
+pub async fn from_both_ends<T, S>(forward: S, reverse: S) -> anyhow::Result<BothEnds<T>>
+where
+    T: PartialEq + Clone,
+    S: Stream<Item = anyhow::Result<T>> + Unpin,
+{
+    let mut ends = StreamMap::from_iter([(false, forward), (true, reverse)]);
+    let mut last: [Option<T>; 2] = [None, None];
+    let mut items = Vec::new();
+
+    while let Some((reverse, item)) = ends.next().await {
+        let item = item?;
+        if last[!reverse as usize].as_ref() == Some(&item) {
+            return Ok(BothEnds { items, met: true });
+        }
+        last[reverse as usize] = Some(item.clone());
+        items.push(item);
+    }
+
+    Ok(BothEnds {
+        met: items.is_empty(),
+        items,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream::{self, BoxStream};
+
+    /// Listing of a lite server which reports the first `count` items of each end only
+    fn cut_short(
+        items: &[u32],
+        count: usize,
+    ) -> (
+        BoxStream<'static, anyhow::Result<u32>>,
+        BoxStream<'static, anyhow::Result<u32>>,
+    ) {
+        let forward: Vec<_> = items.iter().copied().take(count).map(Ok).collect();
+        let reverse: Vec<_> = items.iter().rev().copied().take(count).map(Ok).collect();
+
+        (stream::iter(forward).boxed(), stream::iter(reverse).boxed())
+    }
+
+    #[tokio::test]
+    async fn ends_meet() {
+        let (forward, reverse) = cut_short(&[1, 2, 3, 4, 5], 5);
+
+        let listing = from_both_ends(forward, reverse).await.unwrap();
+
+        assert!(listing.met);
+        let mut items = listing.items;
+        items.sort();
+        assert_eq!(items, vec![1, 2, 3, 4, 5]);
+    }
+
+    #[tokio::test]
+    async fn listing_cut_short_leaves_a_gap() {
+        let (forward, reverse) = cut_short(&[1, 2, 3, 4, 5], 2);
+
+        let listing = from_both_ends(forward, reverse).await.unwrap();
+
+        assert!(!listing.met);
+        assert_eq!(listing.items.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn empty_listing_is_complete() {
+        let (forward, reverse) = cut_short(&[], 0);
+
+        assert!(from_both_ends(forward, reverse).await.unwrap().met);
+    }
+}
@@ -1,10 +1,15 @@
 use crate::block::BlocksGetMasterchainInfo;
 use crate::client::Client;
-use crate::cursor_client::CursorClient;
+use crate::cursor_client::{CursorClient, LiteServerClient};
 use crate::error::ErrorLayer;
+use crate::fixture::Recorder;
+use crate::keepalive::Keepalive;
+use crate::tonlib::{MakeTonlib, Tonlib};
 use serde_json::{json, Value};
+use std::borrow::Cow;
 use std::future::Future;
 use std::pin::Pin;
+use std::sync::Arc;
 use std::task::{Context, Poll};
 use std::time::Duration;
 use ton_client_util::discover::config::{LiteServerId, TonConfig};
@@ -14,8 +19,18 @@ use tower::limit::ConcurrencyLimitLayer;
 use tower::load::PeakEwma;
 use tower::{Service, ServiceBuilder, ServiceExt};
 
-#[derive(Default, Debug)]
-pub(crate) struct ClientFactory;
+#[derive(Default, Clone)]
+pub(crate) struct ClientFactory {
+    recorder: Option<Arc<Recorder>>,
+    /// tonlibjson if none
+    tonlib: Option<MakeTonlib>,
+}
+
+impl ClientFactory {
+    pub(crate) fn new(recorder: Option<Arc<Recorder>>, tonlib: Option<MakeTonlib>) -> Self {
+        Self { recorder, tonlib }
+    }
+}
 
 impl Service<TonConfig> for ClientFactory {
     type Response = Client;
@@ -27,11 +42,17 @@ impl Service<TonConfig> for ClientFactory {
     }
 
     fn call(&mut self, req: TonConfig) -> Self::Future {
+        let recorder = self.recorder.clone();
+        let tonlib = self.tonlib.as_ref().map(|make| make(&req));
+
         Box::pin(async move {
             let mut client = ClientBuilder::from_config(&req.to_string())
                 .disable_logging()
-                .build()
+                .build(tonlib)
                 .await?;
+            if let Some(recorder) = recorder {
+                client.set_recorder(recorder);
+            }
 
             let _ = (&mut client)
                 .oneshot(BlocksGetMasterchainInfo::default())
@@ -46,14 +67,40 @@ impl Service<TonConfig> for ClientFactory {
 pub(crate) struct CursorClientFactory;
 
 impl CursorClientFactory {
-    pub(crate) fn create(id: LiteServerId, client: PeakEwma<Client>) -> CursorClient {
+    pub(crate) fn create(
+        network: Cow<'static, str>,
+        id: LiteServerId,
+        client: PeakEwma<Client>,
+        masterchain_only: bool,
+        keepalive: Option<Keepalive>,
+    ) -> CursorClient {
         ServiceBuilder::new()
-            .layer_fn(|s| CursorClient::new(id.to_string(), s))
+            .layer_fn(|s| {
+                CursorClient::new(
+                    network.clone(),
+                    id.to_string(),
+                    s,
+                    masterchain_only,
+                    keepalive.clone(),
+                )
+            })
             .layer(ConcurrencyLimitLayer::new(256))
             .layer(SharedLayer)
             .layer(ErrorLayer)
             .layer(TimeoutLayer::new(Duration::from_secs(5)))
-            .service(client)
+            .service(Self::failpoints(&id, client))
+    }
+
+    #[cfg(not(feature = "failpoints"))]
+    fn failpoints(_: &LiteServerId, client: PeakEwma<Client>) -> LiteServerClient {
+        client
+    }
+
+    #[cfg(feature = "failpoints")]
+    fn failpoints(id: &LiteServerId, client: PeakEwma<Client>) -> LiteServerClient {
+        use crate::failpoint::{FailpointService, Failpoints};
+
+        FailpointService::new(client, id.to_string(), Failpoints::global())
     }
 }
 
@@ -93,12 +140,15 @@ impl ClientBuilder {
         self
     }
 
-    async fn build(self) -> anyhow::Result<Client> {
+    async fn build(self, tonlib: Option<Arc<dyn Tonlib>>) -> anyhow::Result<Client> {
         if let Some(level) = self.logging {
             Client::set_logging(level);
         }
 
-        let mut client = Client::new();
+        let mut client = match tonlib {
+            Some(tonlib) => Client::with_tonlib(tonlib),
+            None => Client::new(),
+        };
         let _ = (&mut client).oneshot(self.config).await?;
 
         Ok(client)
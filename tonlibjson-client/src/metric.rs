@@ -1,26 +1,49 @@
+use crate::keepalive::LastResponse;
 use pin_project::{pin_project, pinned_drop};
 use std::borrow::Cow;
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::Arc;
-use std::task::{Context, Poll};
+use std::task::{ready, Context, Poll};
+use tokio::time::Instant;
+use ton_client_util::router::latency::LatencyTracker;
 use tower::load::Load;
 use tower::Service;
+use tracing::instrument::Instrumented;
+use tracing::Instrument;
 
 type Counter = Arc<std::sync::atomic::AtomicI32>;
+type Failures = Arc<std::sync::atomic::AtomicU32>;
 
 #[pin_project(PinnedDrop)]
 pub struct ResponseFuture<T> {
     #[pin]
     inner: T,
     inflight: Counter,
+    latency: LatencyTracker,
+    failures: Failures,
+    last_response: LastResponse,
+    started_at: Instant,
 }
 
 impl<T> ResponseFuture<T> {
-    pub fn new(inner: T, inflight: Counter) -> ResponseFuture<T> {
+    pub fn new(
+        inner: T,
+        inflight: Counter,
+        latency: LatencyTracker,
+        failures: Failures,
+        last_response: LastResponse,
+    ) -> ResponseFuture<T> {
         inflight.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
 
-        Self { inner, inflight }
+        Self {
+            inner,
+            inflight,
+            latency,
+            failures,
+            last_response,
+            started_at: Instant::now(),
+        }
     }
 }
 
@@ -39,29 +62,67 @@ where
     type Output = Result<T, E>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        self.project().inner.poll(cx)
+        let this = self.project();
+        let response = ready!(this.inner.poll(cx));
+        this.latency
+            .record(this.started_at.elapsed(), response.is_ok());
+        if response.is_ok() {
+            this.failures.store(0, std::sync::atomic::Ordering::Relaxed);
+            *this.last_response.lock().unwrap() = Instant::now();
+        } else {
+            this.failures
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        }
+
+        Poll::Ready(response)
     }
 }
 
 #[derive(Clone, Debug)]
 pub struct ConcurrencyMetric<S> {
     inner: S,
+    network: Cow<'static, str>,
     liteserver_id: Cow<'static, str>,
     inflight: Counter,
+    latency: LatencyTracker,
+    failures: Failures,
+    last_response: LastResponse,
 }
 
 impl<S> ConcurrencyMetric<S> {
-    pub(crate) fn new(inner: S, liteserver_id: Cow<'static, str>) -> Self {
+    pub(crate) fn new(
+        inner: S,
+        network: Cow<'static, str>,
+        liteserver_id: Cow<'static, str>,
+    ) -> Self {
         Self {
             inner,
+            network,
             liteserver_id,
             inflight: Counter::default(),
+            latency: LatencyTracker::default(),
+            failures: Failures::default(),
+            last_response: LastResponse::new(Instant::now().into()),
         }
     }
 
     pub(crate) fn get_ref(&self) -> &S {
         &self.inner
     }
+
+    pub(crate) fn latency(&self) -> &LatencyTracker {
+        &self.latency
+    }
+
+    /// Time of the last successful response, a connection without one is idle
+    pub(crate) fn last_response(&self) -> &LastResponse {
+        &self.last_response
+    }
+
+    /// Requests failed in a row since the last successful one
+    pub(crate) fn consecutive_failures(&self) -> u32 {
+        self.failures.load(std::sync::atomic::Ordering::Relaxed)
+    }
 }
 
 impl<S, Request> Service<Request> for ConcurrencyMetric<S>
@@ -70,7 +131,7 @@ where
 {
     type Response = S::Response;
     type Error = S::Error;
-    type Future = ResponseFuture<S::Future>;
+    type Future = ResponseFuture<Instrumented<S::Future>>;
 
     fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
         self.inner.poll_ready(cx)
@@ -79,11 +140,22 @@ where
     fn call(&mut self, req: Request) -> Self::Future {
         let req_type = std::any::type_name::<Request>();
 
-        metrics::counter!("ton_liteserver_requests_total", "liteserver_id" => self.liteserver_id.clone(), r"request_type" => req_type).increment(1);
-
-        let future = self.inner.call(req);
-
-        ResponseFuture::new(future, Arc::clone(&self.inflight))
+        metrics::counter!("ton_liteserver_requests_total", "network" => self.network.clone(), "liteserver_id" => self.liteserver_id.clone(), r"request_type" => req_type).increment(1);
+
+        let span = tracing::debug_span!(
+            "tonlib_call",
+            liteserver_id = %self.liteserver_id,
+            request_type = req_type
+        );
+        let future = span.in_scope(|| self.inner.call(req)).instrument(span);
+
+        ResponseFuture::new(
+            future,
+            Arc::clone(&self.inflight),
+            self.latency.clone(),
+            Arc::clone(&self.failures),
+            Arc::clone(&self.last_response),
+        )
     }
 }
 
@@ -0,0 +1,196 @@
+use crate::block::{
+    DnsAccountState, PchanAccountState, RawAccountState, RwalletAccountState, UninitedAccountState,
+    WalletHighloadV1AccountState, WalletHighloadV2AccountState, WalletV3AccountState,
+    WalletV4AccountState,
+};
+use serde::{de, Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+
+/// `account_state` of `getAccountState` picked by its `@type`. The untagged union of
+/// the scheme doesn't look at the tag, so any wallet would pass for `wallet.v3.accountState`.
+///
+/// It's serialized back in the shape of tonlib, numbers are numbers rather than strings.
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum ParsedAccountState {
+    /// `wallet.v3.accountState`
+    WalletV3(WalletV3AccountState),
+    /// `wallet.v4.accountState`, tonlib doesn't list plugins of the wallet
+    WalletV4(WalletV4AccountState),
+    /// `wallet.highload.v1.accountState`
+    WalletHighloadV1(WalletHighloadV1AccountState),
+    /// `wallet.highload.v2.accountState`, it has no seqno
+    WalletHighloadV2(WalletHighloadV2AccountState),
+    /// `rwallet.accountState`
+    RestrictedWallet(RwalletAccountState),
+    /// `dns.accountState`, without code and data
+    Dns(DnsAccountState),
+    /// `pchan.accountState`, without code and data
+    PaymentChannel(PchanAccountState),
+    /// `raw.accountState` of contracts tonlib doesn't recognize, frozen ones included
+    RawAccount(RawAccountState),
+    /// `uninited.accountState`
+    Uninitialized(UninitedAccountState),
+    /// `@type` unknown to the scheme, as tonlib returns it
+    Raw(Value),
+}
+
+impl<'de> Deserialize<'de> for ParsedAccountState {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = Value::deserialize(deserializer)?;
+        let r#type = value
+            .get("@type")
+            .and_then(Value::as_str)
+            .ok_or_else(|| de::Error::missing_field("@type"))?;
+
+        let state = match r#type {
+            "wallet.v3.accountState" => serde_json::from_value(value).map(Self::WalletV3),
+            "wallet.v4.accountState" => serde_json::from_value(value).map(Self::WalletV4),
+            "wallet.highload.v1.accountState" => {
+                serde_json::from_value(value).map(Self::WalletHighloadV1)
+            }
+            "wallet.highload.v2.accountState" => {
+                serde_json::from_value(value).map(Self::WalletHighloadV2)
+            }
+            "rwallet.accountState" => serde_json::from_value(value).map(Self::RestrictedWallet),
+            "dns.accountState" => serde_json::from_value(value).map(Self::Dns),
+            "pchan.accountState" => serde_json::from_value(value).map(Self::PaymentChannel),
+            "raw.accountState" => serde_json::from_value(value).map(Self::RawAccount),
+            "uninited.accountState" => serde_json::from_value(value).map(Self::Uninitialized),
+            _ => Ok(Self::Raw(value)),
+        };
+
+        state.map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn round_trip(value: Value) -> (ParsedAccountState, Value) {
+        let state: ParsedAccountState = serde_json::from_value(value).unwrap();
+        let serialized = serde_json::to_value(&state).unwrap();
+
+        (state, serialized)
+    }
+
+    #[test]
+    fn wallet_v3() {
+        let (state, serialized) = round_trip(json!({
+            "@type": "wallet.v3.accountState",
+            "wallet_id": "698983191",
+            "seqno": 12
+        }));
+
+        let ParsedAccountState::WalletV3(wallet) = state else {
+            panic!("{:?}", state)
+        };
+        assert_eq!((wallet.wallet_id, wallet.seqno), (698983191, 12));
+        assert_eq!(
+            serialized,
+            json!({"@type": "wallet.v3.accountState", "wallet_id": 698983191, "seqno": 12})
+        );
+    }
+
+    #[test]
+    fn wallet_v4() {
+        let (state, serialized) = round_trip(json!({
+            "@type": "wallet.v4.accountState",
+            "wallet_id": "698983191",
+            "seqno": 57
+        }));
+
+        let ParsedAccountState::WalletV4(wallet) = state else {
+            panic!("{:?}", state)
+        };
+        assert_eq!((wallet.wallet_id, wallet.seqno), (698983191, 57));
+        assert_eq!(
+            serialized,
+            json!({"@type": "wallet.v4.accountState", "wallet_id": 698983191, "seqno": 57})
+        );
+    }
+
+    #[test]
+    fn highload_wallets() {
+        let (v1, v1_serialized) = round_trip(json!({
+            "@type": "wallet.highload.v1.accountState",
+            "wallet_id": "0",
+            "seqno": "3"
+        }));
+        let (v2, v2_serialized) = round_trip(json!({
+            "@type": "wallet.highload.v2.accountState",
+            "wallet_id": "698983191"
+        }));
+
+        assert!(matches!(v1, ParsedAccountState::WalletHighloadV1(_)));
+        assert_eq!(
+            v1_serialized,
+            json!({"@type": "wallet.highload.v1.accountState", "wallet_id": 0, "seqno": 3})
+        );
+        assert!(matches!(v2, ParsedAccountState::WalletHighloadV2(_)));
+        assert_eq!(
+            v2_serialized,
+            json!({"@type": "wallet.highload.v2.accountState", "wallet_id": 698983191})
+        );
+    }
+
+    #[test]
+    fn raw_account_and_uninitialized() {
+        let raw = json!({
+            "@type": "raw.accountState",
+            "code": "te6cckEBAQEAAgAAAEysuc0=",
+            "data": "te6cckEBAQEAAgAAAEysuc0=",
+            "frozen_hash": ""
+        });
+        let uninited = json!({"@type": "uninited.accountState", "frozen_hash": ""});
+
+        let (state, serialized) = round_trip(raw.clone());
+        assert!(matches!(state, ParsedAccountState::RawAccount(_)));
+        assert_eq!(serialized, raw);
+
+        let (state, serialized) = round_trip(uninited.clone());
+        assert!(matches!(state, ParsedAccountState::Uninitialized(_)));
+        assert_eq!(serialized, uninited);
+    }
+
+    #[test]
+    fn unknown_type_is_kept_as_is() {
+        let value = json!({
+            "@type": "wallet.v5.accountState",
+            "wallet_id": "2147483409",
+            "seqno": 1,
+            "extensions": []
+        });
+
+        let (state, serialized) = round_trip(value.clone());
+
+        assert!(matches!(state, ParsedAccountState::Raw(_)));
+        assert_eq!(serialized, value);
+    }
+
+    #[test]
+    fn wallet_is_not_mistaken_for_another() {
+        let (state, _) = round_trip(json!({
+            "@type": "wallet.highload.v2.accountState",
+            "wallet_id": "1",
+            "seqno": 1
+        }));
+
+        assert!(matches!(state, ParsedAccountState::WalletHighloadV2(_)));
+    }
+
+    #[test]
+    fn malformed_known_type_fails() {
+        let result = serde_json::from_value::<ParsedAccountState>(json!({
+            "@type": "raw.accountState",
+            "code": 1
+        }));
+
+        assert!(result.is_err());
+    }
+}
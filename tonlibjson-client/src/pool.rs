@@ -0,0 +1,258 @@
+use crate::block::{AccountAddress, RawFullAccountState};
+use crate::cursor_client::CursorClient;
+use anyhow::anyhow;
+use dashmap::DashMap;
+use futures::future::join_all;
+use std::sync::Arc;
+use std::time::Duration;
+pub use ton_client_util::router::latency::LatencyStats;
+use ton_client_util::router::MAX_ERROR_RATE;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Health {
+    /// the seqno window of the lite server is not discovered yet, it isn't routed to
+    Syncing,
+    Healthy,
+    /// fails too often, latency aware routing skips it
+    Unhealthy,
+    /// missed a keepalive probe, it isn't routed to while its connection is rebuilt
+    Dead,
+}
+
+impl Health {
+    pub(crate) fn new(edges_defined: bool, latency: Option<&LatencyStats>) -> Self {
+        if !edges_defined {
+            return Health::Syncing;
+        }
+
+        match latency {
+            Some(stats) if stats.error_rate() > MAX_ERROR_RATE => Health::Unhealthy,
+            _ => Health::Healthy,
+        }
+    }
+}
+
+/// A lite server of the pool as the router sees it
+#[derive(Debug, Clone)]
+pub struct LiteServerStatus {
+    pub id: String,
+    pub health: Health,
+    /// the first masterchain block is available
+    pub archival: bool,
+    /// masterchain seqnos available to request
+    pub first_seqno: Option<i32>,
+    pub last_seqno: Option<i32>,
+    /// round trip of the last masterchain info request
+    pub last_ping: Option<Duration>,
+    pub consecutive_failures: u32,
+    /// requests in flight, routing prefers less loaded servers
+    pub inflight: i32,
+    /// latencies over the last minute, latency aware routing prefers the lowest p95
+    pub latency: Option<LatencyStats>,
+    /// time since the last successful response
+    pub idle: Duration,
+    /// round trip of the last keepalive probe, idle connections only are probed
+    pub last_probe: Option<Duration>,
+}
+
+/// Latest state of an account as a single lite server answers it
+#[derive(Debug)]
+pub struct LiteServerAccountState {
+    pub id: String,
+    pub state: Result<RawFullAccountState, String>,
+    /// differs from the most recent state, either at the same masterchain block
+    /// or lagging behind it more than expected
+    pub diverged: bool,
+}
+
+#[derive(Debug)]
+pub struct AccountStateComparison {
+    /// sorted by id
+    pub lite_servers: Vec<LiteServerAccountState>,
+    /// masterchain seqno of the most recent state
+    pub max_seqno: Option<i32>,
+}
+
+impl AccountStateComparison {
+    /// The most recent state is the one of the latest block with the latest transaction,
+    /// states of lite servers up to `max_lag` masterchain blocks behind it may differ
+    fn new(states: Vec<(String, Result<RawFullAccountState, String>)>, max_lag: i32) -> Self {
+        let latest = states
+            .iter()
+            .filter_map(|(_, state)| state.as_ref().ok())
+            .max_by_key(|state| {
+                let lt = state.last_transaction_id.as_ref().map(|tx| tx.lt);
+
+                (state.block_id.seqno, lt)
+            });
+        let max_seqno = latest.map(|state| state.block_id.seqno);
+        let diverged: Vec<_> = states
+            .iter()
+            .map(|(_, state)| match (state, latest) {
+                (Ok(state), Some(latest)) => {
+                    let lag = latest.block_id.seqno - state.block_id.seqno;
+                    let differs = state.balance != latest.balance
+                        || state.last_transaction_id != latest.last_transaction_id;
+
+                    differs && (lag == 0 || lag > max_lag)
+                }
+                _ => false,
+            })
+            .collect();
+
+        let mut lite_servers: Vec<_> = states
+            .into_iter()
+            .zip(diverged)
+            .map(|((id, state), diverged)| LiteServerAccountState {
+                id,
+                state,
+                diverged,
+            })
+            .collect();
+        lite_servers.sort_unstable_by(|a, b| a.id.cmp(&b.id));
+
+        Self {
+            lite_servers,
+            max_seqno,
+        }
+    }
+
+    pub fn diverged(&self) -> bool {
+        self.lite_servers.iter().any(|s| s.diverged)
+    }
+}
+
+/// Lite servers discovered by [`crate::ton::TonClient`], keyed by id
+#[derive(Clone, Default)]
+pub(crate) struct Pool {
+    clients: Arc<DashMap<String, CursorClient>>,
+}
+
+impl Pool {
+    pub(crate) fn insert(&self, id: String, client: CursorClient) {
+        self.clients.insert(id, client);
+    }
+
+    pub(crate) fn remove(&self, id: &str) {
+        self.clients.remove(id);
+    }
+
+    /// Sorted by id
+    pub(crate) fn status(&self) -> Vec<LiteServerStatus> {
+        let mut status: Vec<_> = self.clients.iter().map(|c| c.status()).collect();
+        status.sort_unstable_by(|a, b| a.id.cmp(&b.id));
+
+        status
+    }
+
+    /// Asks every healthy lite server concurrently
+    pub(crate) async fn compare_account_state(
+        &self,
+        address: AccountAddress,
+        max_lag: i32,
+    ) -> AccountStateComparison {
+        let clients: Vec<_> = self
+            .clients
+            .iter()
+            .filter(|c| c.status().health == Health::Healthy)
+            .map(|c| (c.key().clone(), c.value().clone()))
+            .collect();
+
+        let states = join_all(clients.into_iter().map(|(id, client)| {
+            let address = address.clone();
+
+            async move {
+                let state = client
+                    .raw_get_account_state(address)
+                    .await
+                    .map_err(|e| e.to_string());
+
+                (id, state)
+            }
+        }))
+        .await;
+
+        AccountStateComparison::new(states, max_lag)
+    }
+
+    pub(crate) async fn ping(&self, id: &str) -> anyhow::Result<Duration> {
+        let client = self
+            .clients
+            .get(id)
+            .map(|c| c.clone())
+            .ok_or_else(|| anyhow!("lite server {} not found", id))?;
+
+        client.ping().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(requests: u64, errors: u64) -> LatencyStats {
+        LatencyStats {
+            p50: Duration::from_millis(10),
+            p95: Duration::from_millis(20),
+            p99: Duration::from_millis(30),
+            requests,
+            errors,
+        }
+    }
+
+    #[test]
+    fn health_of_lite_server() {
+        assert_eq!(Health::new(false, Some(&stats(10, 0))), Health::Syncing);
+        assert_eq!(Health::new(true, None), Health::Healthy);
+        assert_eq!(Health::new(true, Some(&stats(10, 5))), Health::Healthy);
+        assert_eq!(Health::new(true, Some(&stats(10, 6))), Health::Unhealthy);
+    }
+
+    fn state(seqno: i32, balance: i64, lt: i64) -> Result<RawFullAccountState, String> {
+        Ok(serde_json::from_value(serde_json::json!({
+            "@type": "raw.fullAccountState",
+            "balance": balance.to_string(),
+            "code": "",
+            "data": "",
+            "last_transaction_id": {"@type": "internal.transactionId", "lt": lt.to_string(), "hash": "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA="},
+            "block_id": {"@type": "ton.blockIdExt", "workchain": -1, "shard": "-9223372036854775808", "seqno": seqno, "root_hash": "", "file_hash": ""},
+            "frozen_hash": "",
+            "sync_utime": "0",
+        }))
+        .unwrap())
+    }
+
+    #[test]
+    fn divergence_beyond_expected_lag() {
+        let comparison = AccountStateComparison::new(
+            vec![
+                ("latest".to_owned(), state(100, 10, 2)),
+                ("same_block".to_owned(), state(100, 7, 1)),
+                ("behind".to_owned(), state(98, 7, 1)),
+                ("lagging".to_owned(), state(90, 7, 1)),
+                ("consistent".to_owned(), state(90, 10, 2)),
+                ("failed".to_owned(), Err("timeout".to_owned())),
+            ],
+            4,
+        );
+
+        let diverged: Vec<_> = comparison
+            .lite_servers
+            .iter()
+            .filter(|s| s.diverged)
+            .map(|s| s.id.as_str())
+            .collect();
+
+        assert_eq!(comparison.max_seqno, Some(100));
+        assert_eq!(diverged, vec!["lagging", "same_block"]);
+        assert!(comparison.diverged());
+    }
+
+    #[tokio::test]
+    async fn ping_of_unknown_lite_server() {
+        let pool = Pool::default();
+
+        assert!(pool.status().is_empty());
+        assert!(pool.ping("unknown").await.is_err());
+    }
+}
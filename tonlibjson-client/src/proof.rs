@@ -0,0 +1,158 @@
+use crate::block::{BlocksShardBlockProof, TonBlockIdExt};
+use crate::boc::parse_base64_boc;
+use crate::verify::VerificationError;
+
+/// Proofs fail either because a lite server couldn't build them, e.g. the history is pruned,
+/// or because the links don't lead from the trusted block to the proven one
+#[derive(Debug, thiserror::Error)]
+pub enum ProofError {
+    #[error("proof is unavailable: {0:#}")]
+    Unavailable(anyhow::Error),
+    #[error(transparent)]
+    Invalid(#[from] VerificationError),
+}
+
+fn decodes(boc: &str, what: &str) -> Result<(), VerificationError> {
+    if boc.is_empty() {
+        return Ok(());
+    }
+
+    parse_base64_boc(boc)
+        .map(|_| ())
+        .map_err(|e| VerificationError::new(format!("malformed {}: {}", what, e)))
+}
+
+/// Checks that the backward links of the masterchain lead from `trusted` to the masterchain
+/// block of the proof and the shard links from it down to `block`, and that every proof is a
+/// BoC. Merkle proofs themselves are left to the caller, who checks them against the hashes
+/// of the linked blocks.
+pub fn verify_block_proof(
+    trusted: &TonBlockIdExt,
+    block: &TonBlockIdExt,
+    proof: &BlocksShardBlockProof,
+) -> Result<(), VerificationError> {
+    if &proof.from != trusted {
+        return Err(VerificationError::new("proof starts at another block"));
+    }
+
+    let mut at = trusted;
+    for link in &proof.mc_proof {
+        if &link.from != at {
+            return Err(VerificationError::new(format!(
+                "masterchain link from {} doesn't follow {}",
+                link.from.seqno, at.seqno
+            )));
+        }
+        if link.to.seqno > link.from.seqno {
+            return Err(VerificationError::new("masterchain link goes forward"));
+        }
+        decodes(&link.dest_proof, "destination proof")?;
+        decodes(&link.proof, "link proof")?;
+        decodes(&link.state_proof, "state proof")?;
+
+        at = &link.to;
+    }
+    if at != &proof.mc_id {
+        return Err(VerificationError::new(
+            "masterchain links don't lead to the masterchain block of the proof",
+        ));
+    }
+
+    let mut at = &proof.mc_id;
+    for link in &proof.links {
+        decodes(&link.proof, "shard link proof")?;
+
+        at = &link.id;
+    }
+    if at != block {
+        return Err(VerificationError::new("links don't lead to the block"));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::{BlocksBlockLinkBack, BlocksShardBlockLink};
+
+    // empty cell
+    const BOC: &str = "te6cckEBAQEAAgAAAEysuc0=";
+
+    fn block(workchain: i32, seqno: i32) -> TonBlockIdExt {
+        TonBlockIdExt::new(workchain, i64::MIN, seqno, String::new(), String::new())
+    }
+
+    fn link_back(from: i32, to: i32) -> BlocksBlockLinkBack {
+        BlocksBlockLinkBack {
+            to_key_block: false,
+            from: block(-1, from),
+            to: block(-1, to),
+            dest_proof: BOC.to_owned(),
+            proof: BOC.to_owned(),
+            state_proof: BOC.to_owned(),
+        }
+    }
+
+    fn proof(
+        links: Vec<BlocksShardBlockLink>,
+        mc_proof: Vec<BlocksBlockLinkBack>,
+    ) -> BlocksShardBlockProof {
+        BlocksShardBlockProof {
+            from: block(-1, 100),
+            mc_id: block(-1, 10),
+            links,
+            mc_proof,
+        }
+    }
+
+    #[test]
+    fn chain_from_trusted_block() {
+        let shard_link = BlocksShardBlockLink {
+            id: block(0, 20),
+            proof: BOC.to_owned(),
+        };
+        let proof = proof(
+            vec![shard_link],
+            vec![link_back(100, 50), link_back(50, 10)],
+        );
+
+        assert!(verify_block_proof(&block(-1, 100), &block(0, 20), &proof).is_ok());
+    }
+
+    #[test]
+    fn masterchain_block_has_no_shard_links() {
+        let proof = proof(vec![], vec![link_back(100, 10)]);
+
+        assert!(verify_block_proof(&block(-1, 100), &block(-1, 10), &proof).is_ok());
+        assert!(verify_block_proof(&block(-1, 100), &block(0, 10), &proof).is_err());
+    }
+
+    #[test]
+    fn broken_chain() {
+        let proof = proof(vec![], vec![link_back(100, 50), link_back(40, 10)]);
+
+        let error = verify_block_proof(&block(-1, 100), &block(-1, 10), &proof).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "block verification failed: masterchain link from 40 doesn't follow 50"
+        );
+    }
+
+    #[test]
+    fn another_trusted_block() {
+        let proof = proof(vec![], vec![link_back(100, 10)]);
+
+        assert!(verify_block_proof(&block(-1, 99), &block(-1, 10), &proof).is_err());
+    }
+
+    #[test]
+    fn malformed_proof() {
+        let mut link = link_back(100, 10);
+        link.proof = "not a boc".to_owned();
+
+        let error = verify_block_proof(&block(-1, 100), &block(-1, 10), &proof(vec![], vec![link]))
+            .unwrap_err();
+        assert!(error.to_string().contains("malformed link proof"));
+    }
+}
@@ -0,0 +1,63 @@
+use crate::address::AccountAddressData;
+use crate::block::TonBlockIdExt;
+use crate::shard;
+
+/// Blocks of the chain state a read reflects
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReadContext {
+    pub masterchain: TonBlockIdExt,
+    /// block of the shard holding the account read, none for the masterchain
+    pub shard: Option<TonBlockIdExt>,
+}
+
+impl ReadContext {
+    pub fn masterchain(block: TonBlockIdExt) -> Self {
+        Self {
+            masterchain: block,
+            shard: None,
+        }
+    }
+
+    /// Read of `account` at `masterchain`, its shard block is the one of `shards` holding it
+    pub fn of_account(
+        masterchain: TonBlockIdExt,
+        account: &AccountAddressData,
+        shards: &[TonBlockIdExt],
+    ) -> Self {
+        let shard = shards
+            .iter()
+            .find(|block| {
+                block.workchain == account.chain_id
+                    && shard::is_ancestor(block.shard, shard::of_account(&account.bytes))
+            })
+            .cloned();
+
+        Self { masterchain, shard }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(workchain: i32, shard: i64) -> TonBlockIdExt {
+        TonBlockIdExt::new(workchain, shard, 1, String::new(), String::new())
+    }
+
+    #[test]
+    fn shard_block_holds_the_account() {
+        let masterchain = block(-1, shard::ROOT);
+        let (left, right) = shard::children(shard::ROOT).unwrap();
+        let shards = [block(0, left), block(0, right)];
+        let account = AccountAddressData {
+            chain_id: 0,
+            bytes: [0xff; 32],
+            flags: None,
+        };
+
+        let context = ReadContext::of_account(masterchain.clone(), &account, &shards);
+
+        assert_eq!(context.masterchain, masterchain);
+        assert_eq!(context.shard, Some(block(0, right)));
+    }
+}
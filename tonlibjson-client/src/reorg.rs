@@ -0,0 +1,334 @@
+use crate::block::{BlocksHeader, TonBlockIdExt};
+use crate::transport::LiteServerTransport;
+use anyhow::anyhow;
+use async_stream::try_stream;
+use futures::Stream;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::MissedTickBehavior;
+
+/// Blocks remembered by default, a reorg deeper than this fails the stream
+pub const DEFAULT_REORG_WINDOW: usize = 64;
+
+#[derive(Debug, Clone)]
+pub enum BlockEvent {
+    /// block of the chain, its ancestors come first
+    Applied(BlocksHeader),
+    /// previously applied block which is no longer an ancestor of the tip, its descendants come first
+    Reverted(TonBlockIdExt),
+}
+
+/// Recently applied blocks of a single chain, each one the parent of the next
+#[derive(Debug)]
+struct BlockWindow {
+    capacity: usize,
+    blocks: VecDeque<TonBlockIdExt>,
+}
+
+impl BlockWindow {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            blocks: VecDeque::new(),
+        }
+    }
+
+    fn position(&self, block: &TonBlockIdExt) -> Option<usize> {
+        self.blocks.iter().position(|b| b == block)
+    }
+
+    /// Reverts the blocks after `fork`, all of them if none, and applies `branch` in the order given
+    fn apply(&mut self, fork: Option<usize>, branch: Vec<BlocksHeader>) -> Vec<BlockEvent> {
+        let kept = fork.map_or(0, |fork| fork + 1);
+        let mut events: Vec<_> = self
+            .blocks
+            .drain(kept..)
+            .rev()
+            .map(BlockEvent::Reverted)
+            .collect();
+
+        for header in branch {
+            self.blocks.push_back(header.id.clone());
+            events.push(BlockEvent::Applied(header));
+        }
+        while self.blocks.len() > self.capacity {
+            self.blocks.pop_front();
+        }
+
+        events
+    }
+}
+
+/// Turns a sequence of chain tips into applied and reverted blocks
+pub struct BlockFollower {
+    transport: Arc<dyn LiteServerTransport>,
+    window: BlockWindow,
+}
+
+impl BlockFollower {
+    pub fn new(transport: Arc<dyn LiteServerTransport>, window: usize) -> Self {
+        Self {
+            transport,
+            window: BlockWindow::new(window),
+        }
+    }
+
+    /// Events leading from the blocks applied so far to `tip`, the first tip is applied alone.
+    /// Parents of `tip` are fetched until one of them is in the window, blocks of the window
+    /// after that parent have been superseded.
+    pub async fn advance(&mut self, tip: &TonBlockIdExt) -> anyhow::Result<Vec<BlockEvent>> {
+        if self.window.blocks.back() == Some(tip) {
+            return Ok(vec![]);
+        }
+
+        let mut branch = vec![self.header(tip).await?];
+        let fork = loop {
+            let Some(oldest) = self.window.blocks.front() else {
+                break None;
+            };
+
+            let header = branch.last().unwrap();
+            if let Some(fork) = header
+                .prev_blocks
+                .iter()
+                .find_map(|prev| self.window.position(prev))
+            {
+                break Some(fork);
+            }
+            if header.id.seqno <= oldest.seqno || branch.len() >= self.window.capacity {
+                return Err(anyhow!(
+                    "block {} doesn't descend from the last {} blocks",
+                    tip.seqno,
+                    self.window.blocks.len()
+                ));
+            }
+
+            let prev = header
+                .prev_blocks
+                .first()
+                .ok_or_else(|| anyhow!("block {} has no parent", header.id.seqno))?
+                .clone();
+            branch.push(self.header(&prev).await?);
+        };
+        branch.reverse();
+
+        let events = self.window.apply(fork, branch);
+        let reverted = events
+            .iter()
+            .filter(|e| matches!(e, BlockEvent::Reverted(_)))
+            .count();
+        if reverted > 0 {
+            tracing::warn!(tip = tip.seqno, reverted, "chain reorganization");
+            metrics::counter!("ton_block_reverted_total").increment(reverted as u64);
+        }
+
+        Ok(events)
+    }
+
+    async fn header(&self, block: &TonBlockIdExt) -> anyhow::Result<BlocksHeader> {
+        self.transport
+            .get_block_header(
+                block.workchain,
+                block.shard,
+                block.seqno,
+                Some((block.root_hash.clone(), block.file_hash.clone())),
+            )
+            .await
+    }
+}
+
+/// Polls the masterchain tip and emits its blocks in order, with reverts of blocks
+/// which left the chain. The stream ends after the first error.
+pub fn follow_masterchain(
+    transport: Arc<dyn LiteServerTransport>,
+    poll_interval: Duration,
+    window: usize,
+) -> impl Stream<Item = anyhow::Result<BlockEvent>> + 'static {
+    let tips = {
+        let transport = transport.clone();
+
+        try_stream! {
+            let mut interval = tokio::time::interval(poll_interval);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+            loop {
+                interval.tick().await;
+
+                yield transport.get_masterchain_info().await?.last;
+            }
+        }
+    };
+
+    follow_tips(transport, tips, window)
+}
+
+/// Emits the blocks of a chain in order as its `tips` come, with reverts of blocks
+/// which left the chain. The stream ends after the first error.
+pub fn follow_tips<S>(
+    transport: Arc<dyn LiteServerTransport>,
+    tips: S,
+    window: usize,
+) -> impl Stream<Item = anyhow::Result<BlockEvent>> + 'static
+where
+    S: Stream<Item = anyhow::Result<TonBlockIdExt>> + Send + 'static,
+{
+    try_stream! {
+        let mut follower = BlockFollower::new(transport, window);
+
+        for await tip in tips {
+            for event in follower.advance(&tip?).await? {
+                yield event;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::{BlocksGetBlockHeader, BlocksGetMasterchainInfo};
+    use crate::fixture::Fixture;
+    use crate::transport::replay::Replay;
+    use futures::{StreamExt, TryStreamExt};
+    use serde_json::{json, Value};
+
+    fn block(seqno: i32, branch: &str) -> TonBlockIdExt {
+        TonBlockIdExt::new(
+            -1,
+            i64::MIN,
+            seqno,
+            format!("{}{}", branch, seqno),
+            format!("file-{}{}", branch, seqno),
+        )
+    }
+
+    fn header_entry(id: &TonBlockIdExt, prev: Option<&TonBlockIdExt>) -> Value {
+        json!({
+            "timestamp": 0,
+            "request": serde_json::to_value(BlocksGetBlockHeader::new(id.clone())).unwrap(),
+            "response": {
+                "@type": "blocks.header",
+                "id": id,
+                "global_id": -239,
+                "version": 0,
+                "flags": 1,
+                "after_merge": false,
+                "after_split": false,
+                "before_split": false,
+                "want_merge": false,
+                "want_split": false,
+                "validator_list_hash_short": 0,
+                "catchain_seqno": 0,
+                "min_ref_mc_seqno": 0,
+                "is_key_block": false,
+                "prev_key_block_seqno": 0,
+                "start_lt": "0",
+                "end_lt": "0",
+                "gen_utime": "0",
+                "vert_seqno": 1,
+                "prev_blocks": prev.into_iter().collect::<Vec<_>>(),
+            }
+        })
+    }
+
+    fn tip_entry(id: &TonBlockIdExt) -> Value {
+        json!({
+            "timestamp": 0,
+            "request": serde_json::to_value(BlocksGetMasterchainInfo::default()).unwrap(),
+            "response": {
+                "@type": "blocks.masterchainInfo",
+                "last": id,
+                "state_root_hash": "",
+                "init": id,
+            }
+        })
+    }
+
+    /// a1 <- a2 <- a3 is superseded by a1 <- b2 <- b3 <- b4
+    fn reorg(tips: &[TonBlockIdExt]) -> Arc<dyn LiteServerTransport> {
+        let (a1, a2, a3) = (block(1, "a"), block(2, "a"), block(3, "a"));
+        let (b2, b3, b4) = (block(2, "b"), block(3, "b"), block(4, "b"));
+
+        let mut entries = vec![
+            header_entry(&a1, None),
+            header_entry(&a2, Some(&a1)),
+            header_entry(&a3, Some(&a2)),
+            header_entry(&b2, Some(&a1)),
+            header_entry(&b3, Some(&b2)),
+            header_entry(&b4, Some(&b3)),
+        ];
+        entries.extend(tips.iter().map(tip_entry));
+
+        let lines: Vec<_> = entries.iter().map(Value::to_string).collect();
+        let fixture = Fixture::from_reader(lines.join("\n").as_bytes()).unwrap();
+
+        Arc::new(Replay::new(fixture))
+    }
+
+    fn describe(events: &[BlockEvent]) -> Vec<String> {
+        events
+            .iter()
+            .map(|event| match event {
+                BlockEvent::Applied(header) => format!("+{}", header.id.root_hash),
+                BlockEvent::Reverted(id) => format!("-{}", id.root_hash),
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn superseded_blocks_are_reverted() {
+        let mut follower = BlockFollower::new(reorg(&[]), 8);
+
+        for tip in [block(1, "a"), block(2, "a"), block(3, "a")] {
+            follower.advance(&tip).await.unwrap();
+        }
+        assert!(follower.advance(&block(3, "a")).await.unwrap().is_empty());
+
+        let events = follower.advance(&block(4, "b")).await.unwrap();
+
+        assert_eq!(describe(&events), ["-a3", "-a2", "+b2", "+b3", "+b4"]);
+    }
+
+    #[tokio::test]
+    async fn reorg_deeper_than_window_fails() {
+        let mut follower = BlockFollower::new(reorg(&[]), 2);
+
+        for tip in [block(1, "a"), block(2, "a"), block(3, "a")] {
+            follower.advance(&tip).await.unwrap();
+        }
+
+        assert!(follower.advance(&block(4, "b")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn masterchain_stream_reverts() {
+        let transport = reorg(&[block(1, "a"), block(3, "a"), block(4, "b")]);
+
+        let events: Vec<_> = follow_masterchain(transport, Duration::from_millis(1), 8)
+            .take(7)
+            .try_collect()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            describe(&events),
+            ["+a1", "+a2", "+a3", "-a3", "-a2", "+b2", "+b3"]
+        );
+    }
+
+    #[tokio::test]
+    async fn tips_stream_reverts() {
+        let tips = futures::stream::iter([block(1, "a"), block(3, "a"), block(4, "b")].map(Ok));
+
+        let events: Vec<_> = follow_tips(reorg(&[]), tips, 8)
+            .try_collect()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            describe(&events),
+            ["+a1", "+a2", "+a3", "-a3", "-a2", "+b2", "+b3", "+b4"]
+        );
+    }
+}
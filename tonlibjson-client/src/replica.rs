@@ -0,0 +1,152 @@
+//! Read replicas, which fetch block data from an upstream instance instead of lite servers.
+//! Each kind of block data is served by either the upstream or the lite servers, while account
+//! states, lookups of blocks and sends always go to the lite servers.
+
+use crate::block::{BlocksAccountTransactionId, BlocksHeader, BlocksTransactions, TonBlockIdExt};
+use crate::ton::TonClient;
+use async_trait::async_trait;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Kinds of block data a [`BlockSource`] may serve
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BlockData {
+    Headers,
+    Shards,
+    /// ids of transactions of a block, full transactions are still read from lite servers
+    Transactions,
+}
+
+impl BlockData {
+    pub const ALL: [BlockData; 3] = [
+        BlockData::Headers,
+        BlockData::Shards,
+        BlockData::Transactions,
+    ];
+}
+
+impl FromStr for BlockData {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "headers" => Ok(Self::Headers),
+            "shards" => Ok(Self::Shards),
+            "transactions" => Ok(Self::Transactions),
+            _ => anyhow::bail!("unknown block data {}", s),
+        }
+    }
+}
+
+/// Provider of block data, responses keep the tonlibjson shape
+#[async_trait]
+pub trait BlockSource: Send + Sync {
+    async fn get_block_header(&self, id: &TonBlockIdExt) -> anyhow::Result<BlocksHeader>;
+
+    /// Shard blocks of the masterchain block
+    async fn get_shards(&self, master: &TonBlockIdExt) -> anyhow::Result<Vec<TonBlockIdExt>>;
+
+    /// Up to `count` ids of transactions of the block after `after`, see `blocks.getTransactions`
+    async fn blocks_get_transactions(
+        &self,
+        block: &TonBlockIdExt,
+        after: Option<BlocksAccountTransactionId>,
+        reverse: bool,
+        count: i32,
+    ) -> anyhow::Result<BlocksTransactions>;
+}
+
+#[async_trait]
+impl BlockSource for TonClient {
+    async fn get_block_header(&self, id: &TonBlockIdExt) -> anyhow::Result<BlocksHeader> {
+        self.get_block_header_by_id(id).await
+    }
+
+    async fn get_shards(&self, master: &TonBlockIdExt) -> anyhow::Result<Vec<TonBlockIdExt>> {
+        self.get_shards_by_block_id(master.clone()).await
+    }
+
+    async fn blocks_get_transactions(
+        &self,
+        block: &TonBlockIdExt,
+        after: Option<BlocksAccountTransactionId>,
+        reverse: bool,
+        count: i32,
+    ) -> anyhow::Result<BlocksTransactions> {
+        TonClient::blocks_get_transactions(self, block, after, reverse, count).await
+    }
+}
+
+/// Upstream block data is read from, see [`crate::ton::TonClientBuilder::set_replica`]
+#[derive(Clone)]
+pub struct Replica {
+    source: Arc<dyn BlockSource>,
+    data: Vec<BlockData>,
+}
+
+impl Replica {
+    /// Reads the given kinds of block data from `source`, every kind if empty
+    pub fn new(source: Arc<dyn BlockSource>, data: Vec<BlockData>) -> Self {
+        let data = if data.is_empty() {
+            BlockData::ALL.to_vec()
+        } else {
+            data
+        };
+
+        Self { source, data }
+    }
+
+    /// The source of `data` unless it's read from lite servers
+    pub(crate) fn source(&self, data: BlockData) -> Option<&dyn BlockSource> {
+        self.data.contains(&data).then_some(self.source.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Nothing;
+
+    #[async_trait]
+    impl BlockSource for Nothing {
+        async fn get_block_header(&self, _: &TonBlockIdExt) -> anyhow::Result<BlocksHeader> {
+            anyhow::bail!("no header")
+        }
+
+        async fn get_shards(&self, _: &TonBlockIdExt) -> anyhow::Result<Vec<TonBlockIdExt>> {
+            Ok(vec![])
+        }
+
+        async fn blocks_get_transactions(
+            &self,
+            _: &TonBlockIdExt,
+            _: Option<BlocksAccountTransactionId>,
+            _: bool,
+            _: i32,
+        ) -> anyhow::Result<BlocksTransactions> {
+            anyhow::bail!("no transactions")
+        }
+    }
+
+    #[test]
+    fn data_is_mixed() {
+        let replica = Replica::new(Arc::new(Nothing), vec![BlockData::Shards]);
+        assert!(replica.source(BlockData::Shards).is_some());
+        assert!(replica.source(BlockData::Headers).is_none());
+
+        let replica = Replica::new(Arc::new(Nothing), vec![]);
+        assert!(BlockData::ALL
+            .iter()
+            .all(|data| replica.source(*data).is_some()));
+    }
+
+    #[test]
+    fn data_is_parsed() {
+        assert_eq!(
+            "transactions".parse::<BlockData>().unwrap(),
+            BlockData::Transactions
+        );
+        assert!("accounts".parse::<BlockData>().is_err());
+    }
+}
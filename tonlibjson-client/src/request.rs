@@ -1,3 +1,4 @@
+use crate::breaker::ToBreaker;
 use derive_new::new;
 use serde::de::DeserializeOwned;
 use serde::{Serialize, Serializer};
@@ -63,12 +64,33 @@ where
     }
 }
 
+impl<T> ToBreaker for Forward<T>
+where
+    T: ToBreaker,
+{
+    fn is_send(&self) -> bool {
+        self.inner.is_send()
+    }
+}
+
 // TODO[akostylev0] reinvent that layer
 #[derive(new, Clone)]
 pub(crate) struct Specialized<T> {
     inner: T,
 }
 
+impl<T> Serialize for Specialized<T>
+where
+    T: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.inner.serialize(serializer)
+    }
+}
+
 impl<T> ToRoute for Specialized<T>
 where
     T: ToRoute,
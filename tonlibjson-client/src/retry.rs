@@ -1,7 +1,9 @@
 use crate::block::{RawSendMessage, RawSendMessageReturnHash};
+use crate::call_options;
 use crate::error::Error;
 use futures::future::BoxFuture;
 use futures::FutureExt;
+use std::borrow::Cow;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio_retry::strategy::{jitter, FibonacciBackoff};
@@ -11,12 +13,19 @@ use tower::retry::Policy;
 
 #[derive(Clone)]
 pub struct RetryPolicy {
+    network: Cow<'static, str>,
     budget: Arc<Budget>,
     backoff: FibonacciBackoff,
+    retries: u32,
 }
 
 impl RetryPolicy {
-    pub fn new(budget: Budget, first_delay_millis: u64, max_delay: Duration) -> Self {
+    pub fn new(
+        network: Cow<'static, str>,
+        budget: Budget,
+        first_delay_millis: u64,
+        max_delay: Duration,
+    ) -> Self {
         metrics::describe_counter!(
             "ton_retry_budget_withdraw_success",
             "Number of withdraws that were successful"
@@ -29,8 +38,10 @@ impl RetryPolicy {
         let retry_strategy = FibonacciBackoff::from_millis(first_delay_millis).max_delay(max_delay);
 
         Self {
+            network,
             budget: Arc::new(budget),
             backoff: retry_strategy,
+            retries: 0,
         }
     }
 }
@@ -77,14 +88,24 @@ impl<T: Clone, Res> Policy<T, Res, tower::BoxError> for RetryPolicy {
                     }
                 }
 
+                if call_options::current()
+                    .max_retries()
+                    .is_some_and(|max_retries| self.retries >= max_retries)
+                {
+                    return None;
+                }
+
                 let request_type: &str = std::any::type_name::<T>();
 
                 match self.budget.withdraw() {
                     Ok(_) => {
-                        metrics::counter!("ton_retry_budget_withdraw_success", "request_type" => request_type).increment(1);
+                        metrics::counter!("ton_retry_budget_withdraw_success", "network" => self.network.clone(), "request_type" => request_type).increment(1);
 
                         Some({
                             let mut pol = self.clone();
+                            pol.retries += 1;
+                            // the `dispatch` span of the request
+                            tracing::Span::current().record("retries", pol.retries);
 
                             async move {
                                 let millis = pol.backoff.by_ref().map(jitter).next().unwrap();
@@ -97,7 +118,7 @@ impl<T: Clone, Res> Policy<T, Res, tower::BoxError> for RetryPolicy {
                         })
                     }
                     Err(_) => {
-                        metrics::counter!("ton_retry_budget_withdraw_fail", "request_type" => request_type).increment(1);
+                        metrics::counter!("ton_retry_budget_withdraw_fail", "network" => self.network.clone(), "request_type" => request_type).increment(1);
 
                         None
                     }
@@ -110,3 +131,41 @@ impl<T: Clone, Res> Policy<T, Res, tower::BoxError> for RetryPolicy {
         Some(req.clone())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::call_options::{scope, CallOptions};
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use tower::retry::Retry;
+    use tower::ServiceExt;
+
+    async fn attempts(options: CallOptions) -> u32 {
+        let calls = Arc::new(AtomicU32::new(0));
+        let policy = RetryPolicy::new(
+            Cow::Borrowed("test"),
+            Budget::new(Duration::from_secs(10), 10, 0.0),
+            1,
+            Duration::from_millis(1),
+        );
+        let service = tower::service_fn({
+            let calls = calls.clone();
+            move |_: u32| {
+                calls.fetch_add(1, Ordering::SeqCst);
+
+                async { Err::<u32, tower::BoxError>("failed".into()) }
+            }
+        });
+
+        let _ = scope(options, Retry::new(policy, service).oneshot(1)).await;
+
+        calls.load(Ordering::SeqCst)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn call_options_cap_retries() {
+        assert_eq!(attempts(CallOptions::default().set_max_retries(0)).await, 1);
+        assert_eq!(attempts(CallOptions::default().set_max_retries(2)).await, 3);
+        assert!(attempts(CallOptions::default()).await > 3);
+    }
+}
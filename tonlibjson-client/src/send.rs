@@ -0,0 +1,293 @@
+//! Retries of sent messages. A message is the same whichever lite server relays it, so sending
+//! it again is harmless, what matters is telling a message which didn't go out from one which
+//! was rejected:
+//!
+//! | class        | errors                                                    | action                         |
+//! |--------------|-----------------------------------------------------------|--------------------------------|
+//! | connectivity | timeouts, no lite server available, closed connections    | retry, up to `attempts`        |
+//! | duplicate    | the lite server has already seen the message              | success, the first went out    |
+//! | circuit open | the send breaker is open                                  | fail, no lite server was asked |
+//! | rejected     | any other error, e.g. the external message isn't accepted | fail, a retry fails alike      |
+//!
+//! Retries go through the balancer again, which picks among the synced lite servers, so they
+//! land on another connection unless there is a single one.
+
+use crate::boc::parse_base64_boc;
+use crate::breaker::is_circuit_open;
+use crate::error::Error;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use std::future::Future;
+use std::time::Duration;
+use tokio_retry::strategy::{jitter, FibonacciBackoff};
+use ton_client_util::router::route::Error as RouteError;
+use tower::timeout::error::Elapsed;
+
+/// Messages of tonlib errors of lite servers which weren't reached
+const CONNECTIVITY_MESSAGES: [&str; 5] = [
+    "timeout",
+    "connection",
+    "lite_server_network",
+    "adnl",
+    "oneshot closed",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SendErrorClass {
+    Connectivity,
+    Duplicate,
+    CircuitOpen,
+    Rejected,
+}
+
+pub fn classify(error: &anyhow::Error) -> SendErrorClass {
+    if is_circuit_open(error) {
+        return SendErrorClass::CircuitOpen;
+    }
+
+    let (elapsed, route_error) = match error.downcast_ref::<Error>() {
+        Some(Error::Tower(e)) => (e.is::<Elapsed>(), e.downcast_ref::<RouteError>()),
+        Some(Error::Route(e)) => (false, Some(e)),
+        _ => (error.is::<Elapsed>(), error.downcast_ref::<RouteError>()),
+    };
+    if elapsed || matches!(route_error, Some(RouteError::RouteNotAvailable)) {
+        return SendErrorClass::Connectivity;
+    }
+
+    let message = error.to_string().to_lowercase();
+    if message.contains("duplicate") {
+        SendErrorClass::Duplicate
+    } else if CONNECTIVITY_MESSAGES.iter().any(|m| message.contains(m)) {
+        SendErrorClass::Connectivity
+    } else {
+        SendErrorClass::Rejected
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SendRetryPolicy {
+    /// sends of a message including the first one
+    pub attempts: u32,
+    pub first_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for SendRetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 3,
+            first_delay: Duration::from_millis(128),
+            max_delay: Duration::from_secs(1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sent {
+    pub hash: String,
+    /// a lite server had already seen the message, an earlier attempt or send went out
+    pub duplicate: bool,
+    pub attempts: u32,
+}
+
+/// Sends `message` with `send` as of the table of this module
+pub async fn send_with_retry<F, Fut>(
+    policy: &SendRetryPolicy,
+    message: &str,
+    mut send: F,
+) -> anyhow::Result<Sent>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = anyhow::Result<String>>,
+{
+    let mut backoff = FibonacciBackoff::from_millis(policy.first_delay.as_millis() as u64)
+        .max_delay(policy.max_delay)
+        .map(jitter);
+    let mut attempts = 0;
+
+    loop {
+        attempts += 1;
+        let error = match send().await {
+            Ok(hash) => {
+                return Ok(Sent {
+                    hash,
+                    duplicate: false,
+                    attempts,
+                })
+            }
+            Err(e) => e,
+        };
+
+        let class = classify(&error);
+        metrics::counter!("ton_send_errors_total", "class" => format!("{:?}", class)).increment(1);
+        match class {
+            SendErrorClass::Duplicate => {
+                return Ok(Sent {
+                    hash: STANDARD.encode(parse_base64_boc(message)?.hash()),
+                    duplicate: true,
+                    attempts,
+                })
+            }
+            SendErrorClass::Connectivity if attempts < policy.attempts => {
+                tracing::warn!(attempts, error = ?error, "send failed, retrying");
+                if let Some(delay) = backoff.next() {
+                    tokio::time::sleep(delay).await;
+                }
+            }
+            _ => return Err(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::TonError;
+    use crate::breaker::CircuitOpen;
+    use std::collections::VecDeque;
+    use std::sync::{Arc, Mutex};
+
+    // empty cell
+    const MESSAGE: &str = "te6cckEBAQEAAgAAAEysuc0=";
+    const HASH: &str = "lqKW0iTyhcZ77pPDD4owkVfw2qNdxbh+QQt4YwoJz8c=";
+
+    fn ton_error(code: i32, message: &str) -> anyhow::Error {
+        let error: TonError =
+            serde_json::from_value(serde_json::json!({"code": code, "message": message})).unwrap();
+
+        Error::Custom(error.into()).into()
+    }
+
+    fn timeout() -> anyhow::Error {
+        Error::Tower(Elapsed::new().into()).into()
+    }
+
+    fn no_route() -> anyhow::Error {
+        Error::Route(RouteError::RouteNotAvailable).into()
+    }
+
+    fn circuit_open() -> anyhow::Error {
+        Error::Tower(CircuitOpen { breaker: "sends" }.into()).into()
+    }
+
+    fn rejected() -> anyhow::Error {
+        ton_error(
+            500,
+            "cannot apply external message to current state : External message was not accepted",
+        )
+    }
+
+    fn duplicate() -> anyhow::Error {
+        ton_error(500, "duplicate message")
+    }
+
+    /// Lite servers answering sends in turn
+    fn transport(
+        responses: Vec<anyhow::Result<String>>,
+    ) -> (
+        Arc<Mutex<u32>>,
+        impl FnMut() -> futures::future::Ready<anyhow::Result<String>>,
+    ) {
+        let calls = Arc::new(Mutex::new(0));
+        let counter = calls.clone();
+        let mut responses = VecDeque::from(responses);
+
+        (calls, move || {
+            *counter.lock().unwrap() += 1;
+
+            futures::future::ready(responses.pop_front().expect("no more responses"))
+        })
+    }
+
+    #[test]
+    fn classes_of_errors() {
+        assert_eq!(classify(&timeout()), SendErrorClass::Connectivity);
+        assert_eq!(classify(&no_route()), SendErrorClass::Connectivity);
+        assert_eq!(
+            classify(&ton_error(500, "LITE_SERVER_NETWORK: connection reset")),
+            SendErrorClass::Connectivity
+        );
+        assert_eq!(classify(&duplicate()), SendErrorClass::Duplicate);
+        assert_eq!(classify(&circuit_open()), SendErrorClass::CircuitOpen);
+        assert_eq!(classify(&rejected()), SendErrorClass::Rejected);
+        assert_eq!(
+            classify(&anyhow::anyhow!("failed to parse boc")),
+            SendErrorClass::Rejected
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn connectivity_errors_are_retried() {
+        let (calls, send) = transport(vec![Err(timeout()), Err(no_route()), Ok(HASH.to_owned())]);
+
+        let sent = send_with_retry(&SendRetryPolicy::default(), MESSAGE, send)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            sent,
+            Sent {
+                hash: HASH.to_owned(),
+                duplicate: false,
+                attempts: 3
+            }
+        );
+        assert_eq!(*calls.lock().unwrap(), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retries_are_bounded() {
+        let (calls, send) = transport(vec![Err(timeout()), Err(timeout()), Err(timeout())]);
+
+        let error = send_with_retry(&SendRetryPolicy::default(), MESSAGE, send)
+            .await
+            .unwrap_err();
+
+        assert_eq!(classify(&error), SendErrorClass::Connectivity);
+        assert_eq!(*calls.lock().unwrap(), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn duplicate_after_lost_response_is_success() {
+        // the first send went out though its response was lost
+        let (calls, send) = transport(vec![Err(timeout()), Err(duplicate())]);
+
+        let sent = send_with_retry(&SendRetryPolicy::default(), MESSAGE, send)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            sent,
+            Sent {
+                hash: HASH.to_owned(),
+                duplicate: true,
+                attempts: 2
+            }
+        );
+        assert_eq!(*calls.lock().unwrap(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rejections_are_not_retried() {
+        let (calls, send) = transport(vec![Err(rejected())]);
+
+        let error = send_with_retry(&SendRetryPolicy::default(), MESSAGE, send)
+            .await
+            .unwrap_err();
+
+        assert_eq!(classify(&error), SendErrorClass::Rejected);
+        assert_eq!(*calls.lock().unwrap(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn open_circuit_is_not_retried() {
+        let (calls, send) = transport(vec![Err(circuit_open())]);
+
+        let error = send_with_retry(&SendRetryPolicy::default(), MESSAGE, send)
+            .await
+            .unwrap_err();
+
+        assert!(is_circuit_open(&error));
+        assert_eq!(*calls.lock().unwrap(), 1);
+    }
+}
@@ -1,22 +1,36 @@
 use crate::block::{
-    AccountAddress, SmcBoxedMethodId, SmcLoad, SmcRunGetMethod, TvmBoxedStackEntry,
+    AccountAddress, SmcBoxedMethodId, SmcLoad, SmcRunGetMethod, TonBlockIdExt, TvmBoxedStackEntry,
+    WithBlock,
 };
+use crate::breaker::ToBreaker;
 use crate::client::Client;
 use crate::request::Requestable;
 use derive_new::new;
 use futures::future::BoxFuture;
 use futures::FutureExt;
 use futures::TryFutureExt;
+use serde::Serialize;
 use std::task::{Context, Poll};
-use ton_client_util::router::route::{Route, ToRoute};
+use ton_client_util::router::route::{BlockCriteria, Route, ToRoute};
 use ton_client_util::service::timeout::ToTimeout;
 use tower::{Service, ServiceExt};
 
-#[derive(new, Clone)]
+/// Serialized only to look at the addressed workchain, it is not a request of tonlib
+#[derive(new, Clone, Serialize)]
 pub struct RunGetMethod {
     address: AccountAddress,
     method: SmcBoxedMethodId,
     stack: Vec<TvmBoxedStackEntry>,
+    #[new(default)]
+    block: Option<TonBlockIdExt>,
+}
+
+impl RunGetMethod {
+    /// Runs the method on the account state at `block` instead of the latest one
+    pub fn with_block(mut self, block: TonBlockIdExt) -> Self {
+        self.block = Some(block);
+        self
+    }
 }
 
 impl Service<RunGetMethod> for Client {
@@ -31,18 +45,58 @@ impl Service<RunGetMethod> for Client {
     fn call(&mut self, req: RunGetMethod) -> Self::Future {
         let clone = self.clone();
 
-        self.call(SmcLoad::new(req.address))
-            .and_then(move |info| {
-                clone.oneshot(SmcRunGetMethod::new(info.id, req.method, req.stack))
-            })
-            .boxed()
+        let load = match req.block {
+            Some(block) => self.call(WithBlock::new(block, SmcLoad::new(req.address))),
+            None => self.call(SmcLoad::new(req.address)),
+        };
+
+        load.and_then(move |info| {
+            clone.oneshot(SmcRunGetMethod::new(info.id, req.method, req.stack))
+        })
+        .boxed()
     }
 }
 
 impl ToRoute for RunGetMethod {
     fn to_route(&self) -> Route {
-        Route::Latest
+        match &self.block {
+            Some(block) => Route::Block {
+                chain: block.workchain,
+                criteria: BlockCriteria::Seqno {
+                    shard: block.shard,
+                    seqno: block.seqno,
+                },
+            },
+            None => Route::Latest,
+        }
     }
 }
 
 impl ToTimeout for RunGetMethod {}
+
+impl ToBreaker for RunGetMethod {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_get_method_routes_to_pinned_block() {
+        let address =
+            AccountAddress::new("EQCjk1hh952vWaE9bRguFkAhDAL5jj3xj9p0uPWrFBq_GEMS").unwrap();
+        let request = RunGetMethod::new(address, SmcBoxedMethodId::by_name("seqno"), vec![]);
+        let block = TonBlockIdExt::new(-1, i64::MIN, 34000000, String::new(), String::new());
+
+        assert!(matches!(request.clone().to_route(), Route::Latest));
+        assert!(matches!(
+            request.with_block(block).to_route(),
+            Route::Block {
+                chain: -1,
+                criteria: BlockCriteria::Seqno {
+                    shard: i64::MIN,
+                    seqno: 34000000,
+                },
+            }
+        ));
+    }
+}
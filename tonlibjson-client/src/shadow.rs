@@ -0,0 +1,295 @@
+//! Shadowing of reads, for validating a backend against the one serving, e.g. the native
+//! lite server backend against tonlibjson or another set of lite servers. A sample of reads
+//! is run again on the shadow backend in the background, caller waits for the primary one
+//! only. Outcomes are compared as JSON whatever the order of keys and mismatches are logged
+//! and counted in `ton_shadow_requests_total`.
+//!
+//! Only reads of a given block or transaction are shadowed, the latest masterchain block or
+//! account state may change between the two requests. Sends are never shadowed.
+
+use crate::transport::LiteServerTransport;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::future::Future;
+use std::sync::Arc;
+
+/// tonlib methods which may be shadowed
+pub const SHADOWED_METHODS: [&str; 4] = [
+    "blocks.lookupBlock",
+    "blocks.getBlockHeader",
+    "blocks.getTransactions",
+    "raw.getTransactions",
+];
+
+/// Differences listed by a mismatch log, the others are counted only
+const LOGGED_DIFFERENCES: usize = 8;
+/// Chars of a value shown by a difference, BoCs are long
+const SHOWN_VALUE_CHARS: usize = 64;
+
+#[derive(Debug, Clone)]
+pub struct ShadowPolicy {
+    /// share of reads shadowed, from 0 to 1
+    pub sample_rate: f64,
+    /// tonlib methods shadowed, all of [`SHADOWED_METHODS`] if empty
+    pub methods: Vec<String>,
+}
+
+impl Default for ShadowPolicy {
+    fn default() -> Self {
+        Self {
+            sample_rate: 0.01,
+            methods: Vec::new(),
+        }
+    }
+}
+
+/// Backend reads are shadowed on, see [`crate::ton::TonClientBuilder::set_shadow`]
+pub struct Shadow {
+    transport: Arc<dyn LiteServerTransport>,
+    policy: ShadowPolicy,
+}
+
+/// Response as JSON, or the error. Errors of both backends are alike whatever their message,
+/// backends word them differently.
+type Outcome = Result<Value, String>;
+
+pub fn to_json<T: Serialize>(value: &T) -> Value {
+    serde_json::to_value(value).unwrap_or(Value::Null)
+}
+
+impl Shadow {
+    pub fn new(transport: Arc<dyn LiteServerTransport>, policy: ShadowPolicy) -> Self {
+        Self { transport, policy }
+    }
+
+    fn sampled(&self, method: &str) -> bool {
+        let enabled =
+            self.policy.methods.is_empty() || self.policy.methods.iter().any(|m| m == method);
+
+        enabled && rand::random::<f64>() < self.policy.sample_rate
+    }
+
+    /// Runs `call` on the shadow backend in the background if the read is sampled, both
+    /// responses are compared by their `project`ion
+    pub(crate) fn compare<T, F, Fut>(
+        &self,
+        method: &'static str,
+        params: Value,
+        primary: &anyhow::Result<T>,
+        project: fn(&T) -> Value,
+        call: F,
+    ) where
+        T: 'static,
+        F: FnOnce(Arc<dyn LiteServerTransport>) -> Fut,
+        Fut: Future<Output = anyhow::Result<T>> + Send + 'static,
+    {
+        if !self.sampled(method) {
+            return;
+        }
+
+        let primary = outcome(primary, project);
+        let shadow = call(self.transport.clone());
+        tokio::spawn(async move {
+            let shadow = outcome(&shadow.await, project);
+            let differences = differences(&primary, &shadow);
+
+            let result = if differences.is_empty() {
+                "match"
+            } else {
+                "mismatch"
+            };
+            metrics::counter!("ton_shadow_requests_total", "method" => method, "result" => result)
+                .increment(1);
+
+            if !differences.is_empty() {
+                tracing::warn!(
+                    method,
+                    params = %params,
+                    differences = differences.len(),
+                    diff = summary(&differences),
+                    "shadow backend disagrees"
+                );
+            }
+        });
+    }
+}
+
+fn outcome<T>(result: &anyhow::Result<T>, project: fn(&T) -> Value) -> Outcome {
+    result.as_ref().map(project).map_err(|e| format!("{:#}", e))
+}
+
+fn differences(primary: &Outcome, shadow: &Outcome) -> Vec<String> {
+    match (primary, shadow) {
+        (Ok(primary), Ok(shadow)) => diff(primary, shadow),
+        (Err(_), Err(_)) => Vec::new(),
+        (Ok(_), Err(e)) => vec![format!("shadow failed: {}", e)],
+        (Err(e), Ok(_)) => vec![format!("primary failed: {}", e)],
+    }
+}
+
+/// Paths where the values differ, keys of objects in their sorted order
+pub fn diff(primary: &Value, shadow: &Value) -> Vec<String> {
+    let mut differences = Vec::new();
+    walk("", primary, shadow, &mut differences);
+
+    differences
+}
+
+fn walk(path: &str, primary: &Value, shadow: &Value, differences: &mut Vec<String>) {
+    match (primary, shadow) {
+        (Value::Object(primary), Value::Object(shadow)) => {
+            let keys: BTreeSet<_> = primary
+                .keys()
+                .chain(shadow.keys())
+                .filter(|key| *key != "@extra")
+                .collect();
+            for key in keys {
+                let path = format!("{}.{}", path, key);
+                match (primary.get(key), shadow.get(key)) {
+                    (Some(primary), Some(shadow)) => walk(&path, primary, shadow, differences),
+                    (Some(_), None) => differences.push(format!("{}: missing on shadow", path)),
+                    _ => differences.push(format!("{}: missing on primary", path)),
+                }
+            }
+        }
+        (Value::Array(primary), Value::Array(shadow)) if primary.len() == shadow.len() => {
+            for (i, (primary, shadow)) in primary.iter().zip(shadow).enumerate() {
+                walk(&format!("{}[{}]", path, i), primary, shadow, differences);
+            }
+        }
+        (Value::Array(primary), Value::Array(shadow)) => differences.push(format!(
+            "{}: {} items != {} items",
+            root(path),
+            primary.len(),
+            shadow.len()
+        )),
+        _ if primary == shadow => {}
+        _ => differences.push(format!(
+            "{}: {} != {}",
+            root(path),
+            brief(primary),
+            brief(shadow)
+        )),
+    }
+}
+
+fn root(path: &str) -> &str {
+    if path.is_empty() {
+        "."
+    } else {
+        path
+    }
+}
+
+fn brief(value: &Value) -> String {
+    let value = value.to_string();
+    match value.char_indices().nth(SHOWN_VALUE_CHARS) {
+        Some((end, _)) => format!("{}...", &value[..end]),
+        None => value,
+    }
+}
+
+fn summary(differences: &[String]) -> String {
+    let mut summary = differences
+        .iter()
+        .take(LOGGED_DIFFERENCES)
+        .cloned()
+        .collect::<Vec<_>>()
+        .join("; ");
+    if differences.len() > LOGGED_DIFFERENCES {
+        summary += &format!("; and {} more", differences.len() - LOGGED_DIFFERENCES);
+    }
+
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::Fixture;
+    use crate::transport::replay::Replay;
+    use serde_json::json;
+
+    #[test]
+    fn order_of_keys_does_not_matter() {
+        let primary: Value = serde_json::from_str(r#"{"seqno": 1, "shard": 2}"#).unwrap();
+        let shadow: Value = serde_json::from_str(r#"{"shard": 2, "seqno": 1}"#).unwrap();
+
+        assert!(diff(&primary, &shadow).is_empty());
+    }
+
+    #[test]
+    fn paths_of_differences() {
+        let primary =
+            json!({"id": {"seqno": 1}, "txs": [{"lt": 1}, {"lt": 2}], "fee": 5, "@extra": "a"});
+        let shadow =
+            json!({"id": {"seqno": 2}, "txs": [{"lt": 1}, {"lt": 3}], "utime": 9, "@extra": "b"});
+
+        assert_eq!(
+            diff(&primary, &shadow),
+            vec![
+                ".fee: missing on shadow",
+                ".id.seqno: 1 != 2",
+                ".txs[1].lt: 2 != 3",
+                ".utime: missing on primary"
+            ]
+        );
+        assert_eq!(
+            diff(&json!([1, 2]), &json!([1])),
+            vec![".: 2 items != 1 items"]
+        );
+    }
+
+    #[test]
+    fn long_values_are_cut() {
+        let differences = diff(&json!({"boc": "a".repeat(100)}), &json!({"boc": "b"}));
+
+        assert_eq!(
+            differences,
+            vec![format!(".boc: \"{}... != \"b\"", "a".repeat(63))]
+        );
+    }
+
+    #[test]
+    fn errors_of_both_backends_match() {
+        let error = Err("not found".to_owned());
+        let other = Err("block is not applied".to_owned());
+
+        assert!(differences(&error, &other).is_empty());
+        assert_eq!(
+            differences(&Ok(json!(1)), &error),
+            vec!["shadow failed: not found"]
+        );
+        assert_eq!(
+            differences(&other, &Ok(json!(1))),
+            vec!["primary failed: block is not applied"]
+        );
+    }
+
+    #[test]
+    fn summary_is_bounded() {
+        let differences: Vec<_> = (0..10).map(|i| i.to_string()).collect();
+
+        assert_eq!(summary(&differences), "0; 1; 2; 3; 4; 5; 6; 7; and 2 more");
+    }
+
+    #[test]
+    fn methods_are_filtered() {
+        let transport = Arc::new(Replay::new(Fixture::from_reader(&b""[..]).unwrap()));
+        let shadow = |sample_rate, methods: &[&str]| {
+            Shadow::new(
+                transport.clone(),
+                ShadowPolicy {
+                    sample_rate,
+                    methods: methods.iter().map(|m| m.to_string()).collect(),
+                },
+            )
+        };
+
+        assert!(shadow(1.0, &[]).sampled("raw.getTransactions"));
+        assert!(shadow(1.0, &["blocks.lookupBlock"]).sampled("blocks.lookupBlock"));
+        assert!(!shadow(1.0, &["blocks.lookupBlock"]).sampled("raw.getTransactions"));
+        assert!(!shadow(0.0, &[]).sampled("blocks.lookupBlock"));
+    }
+}
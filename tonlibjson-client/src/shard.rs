@@ -0,0 +1,165 @@
+//! Prefix algebra of shard ids: the prefix of a shard is the bits above its lowest set bit,
+//! a split appends a bit to the prefix and a merge removes the last one
+
+/// Shard id of the whole workchain, its prefix is empty
+pub const ROOT: i64 = i64::MIN;
+
+fn lower_bit(shard: i64) -> u64 {
+    let shard = shard as u64;
+
+    shard & shard.wrapping_neg()
+}
+
+/// Length of the prefix
+pub fn depth(shard: i64) -> u32 {
+    63 - (shard as u64).trailing_zeros()
+}
+
+/// Shard which split into `shard`, none for the root
+pub fn parent(shard: i64) -> Option<i64> {
+    if shard == ROOT {
+        return None;
+    }
+    let lower_bit = lower_bit(shard);
+
+    Some((((shard as u64) - lower_bit) | (lower_bit << 1)) as i64)
+}
+
+/// Shards `shard` splits into, none for a prefix of 63 bits
+pub fn children(shard: i64) -> Option<(i64, i64)> {
+    let lower_bit = lower_bit(shard);
+    if lower_bit == 1 {
+        return None;
+    }
+
+    Some((
+        ((shard as u64) - (lower_bit >> 1)) as i64,
+        ((shard as u64) + (lower_bit >> 1)) as i64,
+    ))
+}
+
+/// Prefix of `shard` starts with the prefix of `ancestor`, a shard is its own ancestor
+pub fn is_ancestor(ancestor: i64, shard: i64) -> bool {
+    let lower_bit = lower_bit(ancestor);
+    if lower_bit == 0 || lower_bit < self::lower_bit(shard) {
+        return false;
+    }
+    // bits of the prefix of `ancestor`
+    let mask = !(lower_bit - 1) ^ lower_bit;
+
+    (shard as u64) & mask == (ancestor as u64) & mask
+}
+
+pub fn is_child(child: i64, parent: i64) -> bool {
+    self::parent(child) == Some(parent)
+}
+
+/// Shards cover overlapping ranges of accounts, e.g. the same account before and after a split
+pub fn intersects(a: i64, b: i64) -> bool {
+    is_ancestor(a, b) || is_ancestor(b, a)
+}
+
+/// Ancestor of `shard` with a prefix of at most `max_depth` bits, the shard itself if it's shallower
+pub fn truncate(shard: i64, max_depth: u32) -> i64 {
+    if depth(shard) <= max_depth || max_depth >= 63 {
+        return shard;
+    }
+    let lower_bit = 1_u64 << (63 - max_depth);
+
+    (((shard as u64) & !(lower_bit - 1) & !lower_bit) | lower_bit) as i64
+}
+
+/// The deepest shard the account belongs to, its prefix is the first 63 bits of the account
+pub fn of_account(account: &[u8; 32]) -> i64 {
+    let mut prefix = [0; 8];
+    prefix.copy_from_slice(&account[..8]);
+
+    (u64::from_be_bytes(prefix) | 1) as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEFT: i64 = 0x4000000000000000;
+    const RIGHT: i64 = 0xc000000000000000_u64 as i64;
+
+    #[test]
+    fn root_splits_into_halves() {
+        assert_eq!(depth(ROOT), 0);
+        assert_eq!(parent(ROOT), None);
+        assert_eq!(children(ROOT), Some((LEFT, RIGHT)));
+        assert_eq!(parent(LEFT), Some(ROOT));
+        assert_eq!(parent(RIGHT), Some(ROOT));
+        assert_eq!(depth(RIGHT), 1);
+    }
+
+    #[test]
+    fn parent_and_children() {
+        let shard = 0xa000000000000000_u64 as i64;
+
+        assert_eq!(depth(shard), 2);
+        assert_eq!(parent(shard), Some(RIGHT));
+        assert_eq!(
+            children(RIGHT),
+            Some((shard, 0xe000000000000000_u64 as i64))
+        );
+        assert!(is_child(shard, RIGHT));
+        assert!(!is_child(shard, LEFT));
+        assert!(!is_child(shard, ROOT));
+    }
+
+    #[test]
+    fn deepest_shard_has_no_children() {
+        assert_eq!(depth(1), 63);
+        assert_eq!(children(1), None);
+        assert_eq!(parent(1), Some(2));
+    }
+
+    #[test]
+    fn ancestors() {
+        let shard = 0xa000000000000000_u64 as i64;
+
+        assert!(is_ancestor(ROOT, shard));
+        assert!(is_ancestor(RIGHT, shard));
+        assert!(is_ancestor(shard, shard));
+        assert!(!is_ancestor(LEFT, shard));
+        assert!(!is_ancestor(shard, RIGHT));
+        assert!(!is_ancestor(0, shard));
+    }
+
+    #[test]
+    fn truncate_to_depth() {
+        let shard = 0xa000000000000000_u64 as i64;
+
+        assert_eq!(truncate(shard, 1), RIGHT);
+        assert_eq!(truncate(shard, 0), ROOT);
+        assert_eq!(truncate(shard, 2), shard);
+        assert_eq!(truncate(shard, 8), shard);
+        assert_eq!(truncate(1, 8), 0x0080000000000000);
+        assert_eq!(depth(truncate(-1, 8)), 8);
+        assert!(is_ancestor(truncate(-1, 8), -1));
+    }
+
+    #[test]
+    fn shard_of_account() {
+        let mut account = [0xff; 32];
+        account[0] = 0xa5;
+        let shard = of_account(&account);
+
+        assert_eq!(depth(shard), 63);
+        assert!(is_ancestor(RIGHT, shard));
+        assert!(!is_ancestor(LEFT, shard));
+        assert_eq!(truncate(shard, 8), 0xa580000000000000_u64 as i64);
+    }
+
+    #[test]
+    fn intersections() {
+        let shard = 0xa000000000000000_u64 as i64;
+
+        assert!(intersects(RIGHT, shard));
+        assert!(intersects(shard, RIGHT));
+        assert!(!intersects(LEFT, shard));
+        assert!(!intersects(LEFT, RIGHT));
+    }
+}
@@ -0,0 +1,62 @@
+use std::task::{Context, Poll};
+use tower::Service;
+use tracing::instrument::Instrumented;
+use tracing::Instrument;
+
+/// Runs each request of [`crate::ton::TonClient`] in a `dispatch` span, calls of lite servers
+/// and retries are recorded within it
+#[derive(Clone)]
+pub(crate) struct DispatchSpan<S> {
+    inner: S,
+}
+
+impl<S> DispatchSpan<S> {
+    pub(crate) fn new(inner: S) -> Self {
+        Self { inner }
+    }
+}
+
+impl<S, Req> Service<Req> for DispatchSpan<S>
+where
+    S: Service<Req>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Instrumented<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        let span = tracing::debug_span!(
+            "dispatch",
+            request_type = std::any::type_name::<Req>(),
+            retries = 0
+        );
+
+        span.in_scope(|| self.inner.call(req)).instrument(span)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tower::ServiceExt;
+    use tracing_test::traced_test;
+
+    #[tokio::test]
+    #[traced_test]
+    async fn calls_run_within_dispatch_span() {
+        let service = DispatchSpan::new(tower::service_fn(|req: u32| async move {
+            tracing::Span::current().record("retries", 2);
+            tracing::debug!("called");
+
+            Ok::<_, anyhow::Error>(req)
+        }));
+
+        assert_eq!(service.oneshot(7).await.unwrap(), 7);
+        assert!(logs_contain("dispatch{request_type=\"u32\""));
+        assert!(logs_contain("retries=2}: "));
+    }
+}
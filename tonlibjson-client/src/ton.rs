@@ -1,47 +1,85 @@
-use crate::address::InternalAccountAddress;
+use crate::address::{AccountAddressData, InternalAccountAddress};
 use crate::block::{
-    AccountAddress, BlocksAccountTransactionId, BlocksGetBlockHeader, BlocksGetMasterchainInfo,
-    BlocksGetShards, BlocksGetTransactions, BlocksGetTransactionsExt, BlocksHeader,
-    BlocksLookupBlock, BlocksMasterchainInfo, BlocksShards, BlocksShortTxId, BlocksTransactions,
-    BlocksTransactionsExt, FullAccountState, GetAccountState, GetShardAccountCell,
-    GetShardAccountCellByTransaction, InternalTransactionId, RawFullAccountState,
-    RawGetAccountState, RawGetAccountStateByTransaction, RawGetTransactionsV2, RawSendMessage,
-    RawSendMessageReturnHash, RawTransaction, RawTransactions, SmcBoxedMethodId, SmcRunResult,
-    TonBlockId, TonBlockIdExt, TvmBoxedStackEntry, TvmCell, WithBlock,
+    AccountAddress, BlocksAccountTransactionId, BlocksGetBlockHeader,
+    BlocksGetMasterchainBlockSignatures, BlocksGetMasterchainInfo, BlocksGetOutMsgQueueSizes,
+    BlocksGetShardBlockProof, BlocksGetShards, BlocksGetTransactions, BlocksGetTransactionsExt,
+    BlocksHeader, BlocksLookupBlock, BlocksMasterchainInfo, BlocksOutMsgQueueSizes,
+    BlocksShardBlockProof, BlocksShards, BlocksShortTxId, BlocksTransactions,
+    BlocksTransactionsExt, ConfigInfo, FullAccountState, GetAccountState, GetConfigAll,
+    GetConfigParam, GetShardAccountCell, GetShardAccountCellByTransaction, InternalTransactionId,
+    RawFullAccountState, RawGetAccountState, RawGetAccountStateByTransaction, RawGetTransactionsV2,
+    RawMessage, RawSendMessage, RawSendMessageReturnHash, RawTransaction, RawTransactions,
+    SmcBoxedMethodId, SmcRunResult, TonBlockId, TonBlockIdExt, TvmBoxedStackEntry, TvmCell,
+    WithBlock,
 };
+use crate::breaker::{BreakerPolicy, BreakerService, CircuitBreaker};
+use crate::call_options::{self, CallOptions, CallScope, CallTimeout};
 use crate::cursor_client::CursorClient;
 use crate::error::ErrorService;
+use crate::fixture::{Fixture, Recorder, ReplayTonlib};
+use crate::keepalive::{Keepalive, KeepalivePolicy};
+use crate::listing::from_both_ends;
 use crate::make::{ClientFactory, CursorClientFactory};
+use crate::pool::{AccountStateComparison, LiteServerStatus, Pool};
+use crate::proof::{verify_block_proof, ProofError};
+use crate::read_context::ReadContext;
+use crate::replica::{BlockData, BlockSource, Replica};
 use crate::request::{Forward, Specialized};
 use crate::retry::RetryPolicy;
+use crate::send::{send_with_retry, SendRetryPolicy, Sent};
 use crate::session::RunGetMethod;
+use crate::shadow::{to_json, Shadow};
+use crate::span::DispatchSpan;
+use crate::tonlib::{MakeTonlib, Tonlib};
+use crate::traffic::TrafficStats;
+#[cfg(feature = "liteserver")]
+use crate::transport::lite_server::LiteServerBackend;
+use crate::transport::replay::Replay;
+use crate::transport::{Backend, LiteServerTransport};
+use crate::verify::{ValidatorSet, VerificationError};
+use crate::workchain::{MasterchainOnly, MasterchainOnlyTransport};
 use anyhow::anyhow;
 use async_stream::try_stream;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use dashmap::DashMap;
 use futures::{stream, try_join, Stream, StreamExt, TryFutureExt, TryStream, TryStreamExt};
 use itertools::Itertools;
-use serde_json::Value;
-use std::cmp::min;
-use std::collections::{Bound, HashMap};
+use quick_cache::sync::Cache;
+use serde_json::{json, Value};
+use std::borrow::Cow;
+use std::cmp::{max_by_key, min};
+use std::collections::{BTreeMap, Bound, HashMap};
+use std::convert::Infallible;
+use std::future::Future;
 use std::ops::RangeBounds;
 use std::path::PathBuf;
 use std::pin::Pin;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
 use tokio::time::MissedTickBehavior;
+use tokio_stream::wrappers::UnboundedReceiverStream;
 use tokio_stream::StreamMap;
 use tokio_util::either;
 use ton_client_util::discover::config::LiteServerId;
+pub use ton_client_util::discover::config::{load_ton_config, TonConfig};
+pub use ton_client_util::discover::Refreshed;
 use ton_client_util::discover::{
-    read_ton_config_from_file_stream, read_ton_config_from_url_stream, LiteServerDiscover,
+    read_ton_config_from_file_stream, read_ton_config_from_url_stream, ConfigRefresh,
+    LiteServerDiscover,
 };
 use ton_client_util::router::balance::Balance;
 use ton_client_util::router::route::{BlockCriteria, Route};
 use ton_client_util::service::shared::SharedService;
+use toner::tlb::bits::de::unpack_bytes;
+use toner::ton::boc::BoC;
 use tower::discover::Change;
 use tower::load::PeakEwmaDiscover;
 use tower::retry::budget::Budget;
 use tower::retry::Retry;
-use tower::timeout::Timeout;
 use tower::util::Either;
 use tower::{Layer, ServiceExt};
 use tracing::{instrument, trace};
@@ -60,31 +98,252 @@ pub fn default_ton_config_url() -> Url {
 type BoxCursorClientDiscover =
     Pin<Box<dyn Stream<Item = Result<Change<LiteServerId, CursorClient>, anyhow::Error>> + Send>>;
 type SharedBalance = SharedService<Balance<CursorClient, BoxCursorClientDiscover>>;
+type SharedRetry = Either<Retry<RetryPolicy, SharedBalance>, SharedBalance>;
 
 #[derive(Clone)]
 pub struct TonClient {
-    client: ErrorService<Timeout<Either<Retry<RetryPolicy, SharedBalance>, SharedBalance>>>,
+    client: CallScope<
+        DispatchSpan<ErrorService<MasterchainOnly<BreakerService<CallTimeout<SharedRetry>>>>>,
+    >,
+    // keyed by seqno of the masterchain block the sizes were taken at, lite servers take them
+    // at their own last block, which isn't the one of the masterchain info
+    out_msg_queue_sizes: Arc<Mutex<Option<(i32, BlocksOutMsgQueueSizes)>>>,
+    verify_blocks: bool,
+    // validator sets of the epochs seen by utime_since
+    validator_sets: Arc<Mutex<BTreeMap<u32, Arc<ValidatorSet>>>>,
+    // headers by full block id, they never change once the block exists
+    headers: Arc<Cache<TonBlockIdExt, BlocksHeader>>,
+    // approximate bytes of a cached header along with its id, measured on the last one fetched
+    header_size: Arc<AtomicUsize>,
+    pool: Pool,
+    reads_breaker: Option<Arc<CircuitBreaker>>,
+    config_refresh: Option<ConfigRefresh>,
+    send_retry: SendRetryPolicy,
+    shadow: Option<Arc<Shadow>>,
+    replica: Option<Replica>,
+    traffic: Arc<TrafficStats>,
 }
 
 const MAIN_CHAIN: i32 = -1;
 const MAIN_SHARD: i64 = -9223372036854775808;
 
+/// Number of block headers kept by [`TonClient::get_block_header_by_id`]
+pub const BLOCK_HEADER_CACHE_CAPACITY: usize = 16384;
+
+/// Validator sets kept to verify masterchain blocks, the oldest epoch is dropped first
+const VALIDATOR_SETS_CAPACITY: usize = 4;
+
+/// Max number of calls accepted by [`TonClient::run_get_methods`]
+pub const RUN_GET_METHODS_LIMIT: usize = 100;
+const RUN_GET_METHODS_CONCURRENCY: usize = 16;
+const WAIT_FOR_TRANSACTION_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Message awaited by [`TonClient::wait_for_transaction`]
+#[derive(Debug, Clone)]
+pub enum MessageRef {
+    /// hash of the message, requires tonlib to report message hashes
+    Hash(String),
+    /// source address, none for external messages, and hash of the body
+    Body {
+        source: Option<String>,
+        body_hash: String,
+    },
+}
+
+impl MessageRef {
+    fn matches(&self, msg: &RawMessage) -> bool {
+        match self {
+            MessageRef::Hash(hash) => msg.hash() == Some(hash.as_str()),
+            MessageRef::Body { source, body_hash } => {
+                let source = source
+                    .as_deref()
+                    .map(AccountAddressData::from_str)
+                    .transpose();
+                let msg_source = msg
+                    .source
+                    .account_address
+                    .as_deref()
+                    .map(AccountAddressData::from_str)
+                    .transpose();
+
+                match (source, msg_source) {
+                    (Ok(source), Ok(msg_source)) => {
+                        source.map(|a| (a.chain_id, a.bytes))
+                            == msg_source.map(|a| (a.chain_id, a.bytes))
+                            && &msg.body_hash == body_hash
+                    }
+                    _ => false,
+                }
+            }
+        }
+    }
+}
+
+/// Transaction ids of a block, see [`TonClient::get_block_tx_ids_checked`]
+#[derive(Debug, Clone)]
+pub struct CheckedTxIds {
+    pub transactions: Vec<BlocksShortTxId>,
+    /// why transactions may be missing
+    pub warning: Option<String>,
+}
+
+/// Outcome of [`TonClient::wait_for_transaction`]
+#[derive(Debug)]
+pub enum WaitForTransaction {
+    /// transaction which processed the message, `bounced` is set if it sent a bounce back
+    Found {
+        transaction: Box<RawTransaction>,
+        bounced: bool,
+    },
+    /// nothing found, transactions up to `last_checked_lt` were seen,
+    /// 0 if the account wasn't read in time
+    Timeout { last_checked_lt: i64 },
+}
+
+/// Status of an account as told by its raw state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccountStatus {
+    Uninit,
+    Active,
+    Frozen,
+}
+
+impl From<&RawFullAccountState> for AccountStatus {
+    fn from(state: &RawFullAccountState) -> Self {
+        if !state.code.is_empty() {
+            AccountStatus::Active
+        } else if !state.frozen_hash.is_empty() {
+            AccountStatus::Frozen
+        } else {
+            AccountStatus::Uninit
+        }
+    }
+}
+
+/// Account state emitted by [`TonClient::watch_account`] once it differs from the previous one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountStateDelta {
+    /// masterchain block the state was read at
+    pub block_id: TonBlockIdExt,
+    pub balance: Option<i64>,
+    pub status: AccountStatus,
+    pub last_transaction_id: Option<InternalTransactionId>,
+}
+
+impl AccountStateDelta {
+    fn changed_since(&self, previous: Option<&AccountStateDelta>) -> bool {
+        previous.map_or(true, |previous| {
+            self.balance != previous.balance
+                || self.status != previous.status
+                || self.last_transaction_id != previous.last_transaction_id
+        })
+    }
+}
+
+/// Single get method call of [`TonClient::run_get_methods`]
+#[derive(Debug, Clone)]
+pub struct GetMethodCall {
+    pub address: String,
+    pub method: String,
+    pub stack: Vec<TvmBoxedStackEntry>,
+}
+
+/// Client whose account reads and get method calls all see the state of one block,
+/// see [`TonClient::with_block`]
+#[derive(Clone)]
+pub struct PinnedTonClient {
+    client: TonClient,
+    block: TonBlockIdExt,
+}
+
+impl PinnedTonClient {
+    pub fn block_id(&self) -> &TonBlockIdExt {
+        &self.block
+    }
+
+    pub async fn raw_get_account_state(
+        &self,
+        address: &str,
+    ) -> anyhow::Result<RawFullAccountState> {
+        self.client
+            .raw_get_account_state_on_block(address, self.block.clone())
+            .await
+    }
+
+    pub async fn get_shard_account_cell(&self, address: &str) -> anyhow::Result<TvmCell> {
+        self.client
+            .get_shard_account_cell_on_block(address, self.block.clone())
+            .await
+    }
+
+    pub async fn run_get_method(
+        &self,
+        address: String,
+        method: String,
+        stack: Vec<TvmBoxedStackEntry>,
+    ) -> anyhow::Result<SmcRunResult> {
+        self.client
+            .run_get_method_on_block(address, method, stack, self.block.clone())
+            .await
+    }
+
+    pub async fn run_get_methods(
+        &self,
+        calls: Vec<GetMethodCall>,
+    ) -> anyhow::Result<Vec<anyhow::Result<SmcRunResult>>> {
+        self.client
+            .run_get_methods_at(calls, Some(self.block.clone()))
+            .await
+    }
+
+    /// Same as [`PinnedTonClient::run_get_method`], along with the blocks the method ran at
+    pub async fn run_get_method_with_context(
+        &self,
+        address: String,
+        method: String,
+        stack: Vec<TvmBoxedStackEntry>,
+    ) -> anyhow::Result<(SmcRunResult, ReadContext)> {
+        let account = AccountAddressData::from_str(&address)?;
+        let (result, context) = try_join!(
+            self.run_get_method(address, method, stack),
+            self.client.read_context(self.block.clone(), &account)
+        )?;
+
+        Ok((result, context))
+    }
+}
+
 enum ConfigSource {
     FromFile { path: PathBuf },
     FromUrl { url: Url, interval: Duration },
+    Static { config: TonConfig },
 }
 
 pub struct TonClientBuilder {
     config_source: ConfigSource,
+    network: Cow<'static, str>,
     timeout: Duration,
     ewma_default_rtt: Duration,
     ewma_decay: Duration,
+    latency_aware_routing: bool,
+    max_seqno_lag: Option<i32>,
     retry_enabled: bool,
     retry_budget_ttl: Duration,
     retry_min_per_sec: u32,
     retry_percent: f32,
     retry_first_delay: Duration,
     retry_max_delay: Duration,
+    read_breaker: Option<BreakerPolicy>,
+    send_breaker: Option<BreakerPolicy>,
+    send_retry: SendRetryPolicy,
+    verify_blocks: bool,
+    backend: Backend,
+    record_fixture: Option<PathBuf>,
+    tonlib: Option<MakeTonlib>,
+    masterchain_only: bool,
+    shadow: Option<Arc<Shadow>>,
+    replica: Option<Replica>,
+    keepalive: Option<KeepalivePolicy>,
 }
 
 impl Default for TonClientBuilder {
@@ -94,15 +353,29 @@ impl Default for TonClientBuilder {
                 url: default_ton_config_url(),
                 interval: Duration::from_secs(60),
             },
+            network: Cow::Borrowed(""),
             timeout: Duration::from_secs(10),
             ewma_default_rtt: Duration::from_millis(70),
             ewma_decay: Duration::from_millis(1),
+            latency_aware_routing: false,
+            max_seqno_lag: None,
             retry_enabled: true,
             retry_budget_ttl: Duration::from_secs(10),
             retry_min_per_sec: 10,
             retry_percent: 0.1,
             retry_first_delay: Duration::from_millis(128),
             retry_max_delay: Duration::from_millis(4096),
+            read_breaker: Some(BreakerPolicy::reads()),
+            send_breaker: Some(BreakerPolicy::sends()),
+            send_retry: SendRetryPolicy::default(),
+            verify_blocks: false,
+            backend: Backend::default(),
+            record_fixture: None,
+            tonlib: None,
+            masterchain_only: false,
+            shadow: None,
+            replica: None,
+            keepalive: None,
         }
     }
 }
@@ -122,6 +395,22 @@ impl TonClientBuilder {
         }
     }
 
+    /// Lite servers of `config`, it is never reloaded
+    pub fn from_config(config: TonConfig) -> Self {
+        Self {
+            config_source: ConfigSource::Static { config },
+            ..Default::default()
+        }
+    }
+
+    /// Label of the metrics of the client, its lite servers, retries and breakers,
+    /// empty unless set
+    pub fn set_network(mut self, network: impl Into<Cow<'static, str>>) -> Self {
+        self.network = network.into();
+
+        self
+    }
+
     pub fn set_ewma_default_rtt(mut self, default_rtt: Duration) -> Self {
         self.ewma_default_rtt = default_rtt;
 
@@ -134,6 +423,22 @@ impl TonClientBuilder {
         self
     }
 
+    /// Send each request to the healthy lite server with the lowest p95 latency
+    /// instead of balancing requests by their load
+    pub fn set_latency_aware_routing(mut self, enabled: bool) -> Self {
+        self.latency_aware_routing = enabled;
+
+        self
+    }
+
+    /// Never send requests of a block to lite servers this many masterchain blocks behind
+    /// the most recent one of the pool
+    pub fn set_max_seqno_lag(mut self, max_lag: Option<i32>) -> Self {
+        self.max_seqno_lag = max_lag;
+
+        self
+    }
+
     pub fn disable_retry(mut self) -> Self {
         self.retry_enabled = false;
 
@@ -170,35 +475,165 @@ impl TonClientBuilder {
         self
     }
 
+    /// Circuit breaker of every request but sent messages
+    pub fn set_read_breaker(mut self, policy: BreakerPolicy) -> Self {
+        self.read_breaker = Some(policy);
+
+        self
+    }
+
+    /// Circuit breaker of sent messages
+    pub fn set_send_breaker(mut self, policy: BreakerPolicy) -> Self {
+        self.send_breaker = Some(policy);
+
+        self
+    }
+
+    /// Retries of [`TonClient::send_message_with_retry`]
+    pub fn set_send_retry(mut self, policy: SendRetryPolicy) -> Self {
+        self.send_retry = policy;
+
+        self
+    }
+
+    pub fn disable_breaker(mut self) -> Self {
+        self.read_breaker = None;
+        self.send_breaker = None;
+
+        self
+    }
+
     pub fn set_timeout(mut self, timeout: Duration) -> Self {
         self.timeout = timeout;
 
         self
     }
 
-    pub fn build(self) -> anyhow::Result<TonClient> {
-        let stream = match self.config_source {
-            ConfigSource::FromFile { path } => {
-                let mut interval = tokio::time::interval(Duration::from_secs(1));
-                interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    /// Verify masterchain blocks returned by lookups against validator signatures
+    pub fn set_verify_blocks(mut self, verify_blocks: bool) -> Self {
+        self.verify_blocks = verify_blocks;
+
+        self
+    }
+
+    /// Backend used by [`TonClientBuilder::build_transport`]
+    pub fn set_backend(mut self, backend: Backend) -> Self {
+        self.backend = backend;
+
+        self
+    }
+
+    /// Appends every tonlib request and response to `path`, see [`Fixture`]
+    pub fn set_record_fixture(mut self, path: PathBuf) -> Self {
+        self.record_fixture = Some(path);
+
+        self
+    }
+
+    /// Talks to lite servers through `make` instead of tonlibjson, e.g. a [`crate::fake::FakeTonlib`]
+    pub fn set_tonlib(
+        mut self,
+        make: impl Fn(&TonConfig) -> Arc<dyn Tonlib> + Send + Sync + 'static,
+    ) -> Self {
+        self.tonlib = Some(Arc::new(make));
 
-                either::Either::Left(read_ton_config_from_file_stream(path, interval))
+        self
+    }
+
+    /// Rejects requests to workchains with [`crate::workchain::WorkchainDisabled`], shards of workchain blocks
+    /// aren't followed and aren't routed by
+    pub fn set_masterchain_only(mut self, masterchain_only: bool) -> Self {
+        self.masterchain_only = masterchain_only;
+
+        self
+    }
+
+    /// Runs a sample of reads on `shadow` too and reports where it disagrees, see [`Shadow`]
+    pub fn set_shadow(mut self, shadow: Shadow) -> Self {
+        self.shadow = Some(Arc::new(shadow));
+
+        self
+    }
+
+    /// Reads block data from the upstream of `replica` instead of lite servers, see [`Replica`]
+    pub fn set_replica(mut self, replica: Replica) -> Self {
+        self.replica = Some(replica);
+
+        self
+    }
+
+    /// Probes connections to lite servers which have been idle for a while, a lite server
+    /// missing a probe isn't routed to and its connection is rebuilt
+    pub fn set_keepalive(mut self, policy: KeepalivePolicy) -> Self {
+        self.keepalive = Some(policy);
+
+        self
+    }
+
+    pub fn build_transport(self) -> anyhow::Result<Arc<dyn LiteServerTransport>> {
+        let masterchain_only = self.masterchain_only;
+        let transport: Arc<dyn LiteServerTransport> = match self.backend {
+            Backend::Tonlibjson => return Ok(Arc::new(self.build()?)),
+            Backend::Replay(ref path) => Arc::new(Replay::new(Fixture::load(path)?)),
+            #[cfg(feature = "liteserver")]
+            Backend::LiteServer => Arc::new(LiteServerBackend::new(
+                lite_server_discover(self.config_source).0,
+                self.timeout,
+            )),
+        };
+
+        Ok(if masterchain_only {
+            Arc::new(MasterchainOnlyTransport::new(transport))
+        } else {
+            transport
+        })
+    }
+
+    pub fn build(self) -> anyhow::Result<TonClient> {
+        let recorder = self
+            .record_fixture
+            .as_ref()
+            .map(Recorder::create)
+            .transpose()?
+            .map(Arc::new);
+        // every lite server of the config answers from the same fixture
+        let tonlib = match (&self.backend, self.tonlib) {
+            (Backend::Replay(path), None) => {
+                let fixture = Arc::new(Fixture::load(path)?);
+                let make: MakeTonlib = Arc::new(move |_: &TonConfig| {
+                    Arc::new(ReplayTonlib::new(fixture.clone())) as Arc<dyn Tonlib>
+                });
+
+                Some(make)
             }
-            ConfigSource::FromUrl { url, interval } => {
-                let mut interval = tokio::time::interval(interval);
-                interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+            (_, tonlib) => tonlib,
+        };
+        let client_factory = ClientFactory::new(recorder, tonlib);
+
+        let (lite_server_discover, config_refresh) = lite_server_discover(self.config_source);
+        let (lite_server_discover, keepalive) = match self.keepalive {
+            Some(policy) => {
+                let (tx, rx) = unbounded_channel();
 
-                either::Either::Right(read_ton_config_from_url_stream(url.clone(), interval))
+                (
+                    with_rebuilds(lite_server_discover, rx).left_stream(),
+                    Some(Keepalive::new(policy, self.network.clone(), tx)),
+                )
             }
+            None => (lite_server_discover.right_stream(), None),
         };
-        let lite_server_discover = LiteServerDiscover::new(stream);
-        let client_discover = lite_server_discover.then(|s| async {
-            match s {
-                Ok(Change::Insert(k, v)) => {
-                    ClientFactory.oneshot(v).await.map(|v| Change::Insert(k, v))
+        let client_discover = lite_server_discover.then(move |s| {
+            let client_factory = client_factory.clone();
+
+            async move {
+                match s {
+                    Ok(Change::Insert(k, v)) => client_factory
+                        .oneshot(v)
+                        .await
+                        .map(|v| Change::Insert(k, v)),
+                    Ok(Change::Remove(k)) => Ok(Change::Remove(k)),
+                    Err(_) => unreachable!(),
                 }
-                Ok(Change::Remove(k)) => Ok(Change::Remove(k)),
-                Err(_) => unreachable!(),
             }
         });
 
@@ -209,21 +644,53 @@ impl TonClientBuilder {
             tower::load::CompleteOnResponse::default(),
         );
 
-        let cursor_client_discover = ewma_discover.then(|s| async {
-            match s {
-                Ok(Change::Insert(k, v)) => {
-                    Ok(Change::Insert(k.clone(), CursorClientFactory::create(k, v)))
+        let pool = Pool::default();
+        let masterchain_only = self.masterchain_only;
+        let cursor_client_discover = ewma_discover.then({
+            let pool = pool.clone();
+            let network = self.network.clone();
+
+            move |s| {
+                let pool = pool.clone();
+                let keepalive = keepalive.clone();
+                let network = network.clone();
+
+                async move {
+                    match s {
+                        Ok(Change::Insert(k, v)) => {
+                            let client = CursorClientFactory::create(
+                                network,
+                                k.clone(),
+                                v,
+                                masterchain_only,
+                                keepalive,
+                            );
+                            pool.insert(k.to_string(), client.clone());
+
+                            Ok(Change::Insert(k, client))
+                        }
+                        Ok(Change::Remove(k)) => {
+                            pool.remove(&k.to_string());
+
+                            Ok(Change::Remove(k))
+                        }
+                        Err(e) => Err(e),
+                    }
                 }
-                Ok(Change::Remove(k)) => Ok(Change::Remove(k)),
-                Err(e) => Err(e),
             }
         });
 
-        let client = Balance::new(cursor_client_discover.boxed());
+        let traffic = Arc::new(TrafficStats::default());
+        let client = Balance::new(cursor_client_discover.boxed())
+            .set_latency_aware_routing(self.latency_aware_routing)
+            .set_max_lag(self.max_seqno_lag)
+            .set_route_observer(Some(traffic.clone()))
+            .set_network(self.network.clone());
 
         let client = SharedService::new(client);
         let client = tower::util::option_layer(if self.retry_enabled {
             Some(tower::retry::RetryLayer::new(RetryPolicy::new(
+                self.network.clone(),
                 Budget::new(
                     self.retry_budget_ttl,
                     self.retry_min_per_sec,
@@ -237,13 +704,101 @@ impl TonClientBuilder {
         })
         .layer(client);
 
-        let client = Timeout::new(client, self.timeout);
-        let client = ErrorService::new(client);
-
-        Ok(TonClient { client })
+        let client = CallTimeout::new(client, self.timeout);
+        let client = BreakerService::new(
+            client,
+            self.network.clone(),
+            self.read_breaker,
+            self.send_breaker,
+        );
+        let reads_breaker = client.reads();
+        let client = MasterchainOnly::new(client, self.masterchain_only);
+        let client = CallScope::new(DispatchSpan::new(ErrorService::new(client)));
+
+        Ok(TonClient {
+            client,
+            out_msg_queue_sizes: Default::default(),
+            verify_blocks: self.verify_blocks,
+            validator_sets: Default::default(),
+            headers: Arc::new(Cache::new(BLOCK_HEADER_CACHE_CAPACITY)),
+            header_size: Default::default(),
+            pool,
+            reads_breaker,
+            config_refresh,
+            send_retry: self.send_retry,
+            shadow: self.shadow,
+            replica: self.replica,
+            traffic,
+        })
     }
 }
 
+/// Discovered lite servers and the refresh of their config, a static config is never refreshed
+fn lite_server_discover(
+    config_source: ConfigSource,
+) -> (LiteServerDiscover, Option<ConfigRefresh>) {
+    let refresh = ConfigRefresh::default();
+    let refreshed = !matches!(config_source, ConfigSource::Static { .. });
+    let stream = match config_source {
+        ConfigSource::FromFile { path } => {
+            let mut interval = tokio::time::interval(Duration::from_secs(1));
+            interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+            either::Either::Left(read_ton_config_from_file_stream(path, interval, &refresh))
+        }
+        ConfigSource::FromUrl { url, interval } => {
+            let mut interval = tokio::time::interval(interval);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+            either::Either::Right(either::Either::Left(read_ton_config_from_url_stream(
+                url, interval, &refresh,
+            )))
+        }
+        // kept open as the reloading sources are
+        ConfigSource::Static { config } => either::Either::Right(either::Either::Right(
+            stream::once(async { Ok(config) }).chain(stream::pending()),
+        )),
+    };
+
+    (
+        LiteServerDiscover::new(stream, refresh.clone()),
+        refreshed.then_some(refresh),
+    )
+}
+
+/// Lite servers of `discover` inserted again as their ids are sent to `rebuilds`, the new client
+/// replaces the one with the dead connection
+fn with_rebuilds(
+    discover: LiteServerDiscover,
+    rebuilds: UnboundedReceiver<String>,
+) -> impl Stream<Item = Result<Change<LiteServerId, TonConfig>, Infallible>> {
+    let configs: Arc<DashMap<String, (LiteServerId, TonConfig)>> = Default::default();
+    let discover = discover.inspect({
+        let configs = configs.clone();
+
+        move |change| match change {
+            Ok(Change::Insert(id, config)) => {
+                configs.insert(id.to_string(), (id.clone(), config.clone()));
+            }
+            Ok(Change::Remove(id)) => {
+                configs.remove(&id.to_string());
+            }
+            Err(_) => {}
+        }
+    });
+    let rebuilds = UnboundedReceiverStream::new(rebuilds).filter_map(move |id| {
+        let change = configs.get(&id).map(|entry| {
+            let (id, config) = entry.value().clone();
+
+            Ok(Change::Insert(id, config))
+        });
+
+        futures::future::ready(change)
+    });
+
+    stream::select(discover, rebuilds)
+}
+
 impl TonClient {
     pub async fn ready(&mut self) -> anyhow::Result<()> {
         self.get_masterchain_info().await?;
@@ -252,6 +807,58 @@ impl TonClient {
         Ok(())
     }
 
+    /// Handle whose calls take `options` over the client settings, a task's
+    /// [`call_options::scope`] applies to whatever they leave unset
+    pub fn with_options(&self, options: CallOptions) -> Self {
+        let mut client = self.clone();
+        client.client.set_options(options.or(self.client.options()));
+
+        client
+    }
+
+    /// Reads fail fast as lite servers keep failing, see [`TonClientBuilder::set_read_breaker`]
+    pub fn is_reads_circuit_open(&self) -> bool {
+        self.reads_breaker
+            .as_ref()
+            .is_some_and(|breaker| breaker.is_open())
+    }
+
+    /// Lite servers of the pool with their health, seqno window and routing load
+    pub fn lite_servers(&self) -> Vec<LiteServerStatus> {
+        self.pool.status()
+    }
+
+    /// Requests routed by workchain, shard and archival routing
+    pub fn traffic_stats(&self) -> &TrafficStats {
+        &self.traffic
+    }
+
+    /// Latest state of the account from each healthy lite server, see [`AccountStateComparison`]
+    pub async fn compare_account_state(
+        &self,
+        address: &str,
+        max_lag: i32,
+    ) -> anyhow::Result<AccountStateComparison> {
+        let address = AccountAddress::new(address)?;
+
+        Ok(self.pool.compare_account_state(address, max_lag).await)
+    }
+
+    /// Reads the config now instead of waiting for the interval, lite servers missing
+    /// from it are removed and new ones are added once they answer
+    pub async fn refresh_config(&self) -> anyhow::Result<Refreshed> {
+        self.config_refresh
+            .as_ref()
+            .ok_or_else(|| anyhow!("config is static"))?
+            .refresh()
+            .await
+    }
+
+    /// Measures the round trip of a masterchain info request to the lite server `id`
+    pub async fn ping_lite_server(&self, id: &str) -> anyhow::Result<Duration> {
+        self.pool.ping(id).await
+    }
+
     pub async fn get_masterchain_info(&self) -> anyhow::Result<BlocksMasterchainInfo> {
         self.client
             .clone()
@@ -270,12 +877,27 @@ impl TonClient {
             return Err(anyhow!("seqno must be greater than 0"));
         }
 
-        self.client
+        let block = self
+            .client
             .clone()
             .oneshot(BlocksLookupBlock::seqno(TonBlockId::new(
                 chain, shard, seqno,
             )))
-            .await
+            .await;
+        if let Some(shadow) = &self.shadow {
+            shadow.compare(
+                "blocks.lookupBlock",
+                json!({"workchain": chain, "shard": shard, "seqno": seqno}),
+                &block,
+                to_json,
+                move |shadow| async move { shadow.look_up_block_by_seqno(chain, shard, seqno).await },
+            );
+        }
+        let block = block?;
+
+        self.verify_block_id(&block).await?;
+
+        Ok(block)
     }
 
     pub async fn look_up_block_by_lt(
@@ -288,19 +910,44 @@ impl TonClient {
             return Err(anyhow!("lt must be greater than 0"));
         }
 
-        self.client
+        let block = self
+            .client
             .clone()
             .oneshot(BlocksLookupBlock::logical_time(
                 TonBlockId::new(chain, shard, 0),
                 lt,
             ))
-            .await
+            .await;
+        if let Some(shadow) = &self.shadow {
+            shadow.compare(
+                "blocks.lookupBlock",
+                json!({"workchain": chain, "shard": shard, "lt": lt}),
+                &block,
+                to_json,
+                move |shadow| async move { shadow.look_up_block_by_lt(chain, shard, lt).await },
+            );
+        }
+        let block = block?;
+
+        self.verify_block_id(&block).await?;
+
+        Ok(block)
+    }
+
+    /// Upstream `data` is read from, none if it's read from lite servers
+    fn replica(&self, data: BlockData) -> Option<&dyn BlockSource> {
+        self.replica.as_ref()?.source(data)
     }
 
     pub async fn get_shards(&self, master_seqno: i32) -> anyhow::Result<BlocksShards> {
         let block = self
             .look_up_block_by_seqno(MAIN_CHAIN, MAIN_SHARD, master_seqno)
             .await?;
+        if let Some(replica) = self.replica(BlockData::Shards) {
+            let shards = replica.get_shards(&block).await?;
+
+            return Ok(BlocksShards { shards });
+        }
 
         self.client
             .clone()
@@ -315,6 +962,9 @@ impl TonClient {
         if block_id.workchain != -1 {
             return Err(anyhow!("workchain must be -1"));
         }
+        if let Some(replica) = self.replica(BlockData::Shards) {
+            return replica.get_shards(&block_id).await;
+        }
 
         self.client
             .clone()
@@ -330,6 +980,8 @@ impl TonClient {
         seqno: i32,
         hashes: Option<(String, String)>,
     ) -> anyhow::Result<BlocksHeader> {
+        // the lookup verifies the block itself
+        let verify = hashes.is_some();
         let (root_hash, file_hash) = match hashes {
             Some((root_hash, file_hash)) => (root_hash, file_hash),
             _ => {
@@ -338,16 +990,217 @@ impl TonClient {
             }
         };
 
-        self.client
+        let id = TonBlockIdExt {
+            workchain,
+            shard,
+            seqno,
+            root_hash,
+            file_hash,
+        };
+        let header = match self.replica(BlockData::Headers) {
+            Some(replica) => replica.get_block_header(&id).await,
+            None => {
+                self.client
+                    .clone()
+                    .oneshot(BlocksGetBlockHeader::new(id.clone()))
+                    .await
+            }
+        };
+        if let Some(shadow) = &self.shadow {
+            shadow.compare(
+                "blocks.getBlockHeader",
+                json!({"id": id}),
+                &header,
+                to_json,
+                move |shadow| async move {
+                    let hashes = Some((id.root_hash, id.file_hash));
+
+                    shadow
+                        .get_block_header(workchain, shard, seqno, hashes)
+                        .await
+                },
+            );
+        }
+        let header = header?;
+
+        if verify {
+            self.verify_block_header(&header).await?;
+        }
+
+        Ok(header)
+    }
+
+    /// Header of the block with the given id, cached
+    pub async fn get_block_header_by_id(&self, id: &TonBlockIdExt) -> anyhow::Result<BlocksHeader> {
+        let mut hit = true;
+        let header = self
+            .headers
+            .get_or_insert_async(id, async {
+                hit = false;
+                let header = match self.replica(BlockData::Headers) {
+                    Some(replica) => replica.get_block_header(id).await?,
+                    None => {
+                        self.client
+                            .clone()
+                            .oneshot(BlocksGetBlockHeader::new(id.clone()))
+                            .await?
+                    }
+                };
+                self.verify_block_header(&header).await?;
+                self.header_size.store(
+                    std::mem::size_of::<TonBlockIdExt>() + json_size(id) + json_size(&header),
+                    Ordering::Relaxed,
+                );
+
+                Ok(header)
+            })
+            .await;
+        tracing::debug!(cache = "block_header", hit);
+
+        header
+    }
+
+    /// Latest masterchain block with `gen_utime <= utime`, none if `utime` precedes the first block
+    pub async fn find_masterchain_block_by_utime(
+        &self,
+        utime: i64,
+    ) -> anyhow::Result<Option<BlocksHeader>> {
+        crate::utime::find_masterchain_block_by_utime(self, utime).await
+    }
+
+    /// Headers of `from` and its ancestors, `count` in total, newest first.
+    ///
+    /// The walk follows the first of `prev_blocks`: a masterchain block always has a single
+    /// predecessor, while a shardchain block right after a merge has two and the walk goes on
+    /// along the left one, the right one is still listed in the yielded header.
+    pub fn block_ancestors(
+        &self,
+        from: TonBlockIdExt,
+        count: usize,
+    ) -> impl Stream<Item = anyhow::Result<BlocksHeader>> + 'static {
+        let client = self.clone();
+
+        walk_ancestors(from, count, move |id| {
+            let client = client.clone();
+
+            async move { client.get_block_header_by_id(&id).await }
+        })
+    }
+
+    /// Links from the `trusted` masterchain block back to `block`, `trusted` must not precede
+    /// the masterchain block of `block`
+    pub async fn get_block_proof(
+        &self,
+        block: &TonBlockIdExt,
+        trusted: &TonBlockIdExt,
+    ) -> Result<BlocksShardBlockProof, ProofError> {
+        let proof = self
+            .client
             .clone()
-            .oneshot(BlocksGetBlockHeader::new(TonBlockIdExt {
-                workchain,
-                shard,
-                seqno,
-                root_hash,
-                file_hash,
-            }))
+            .oneshot(BlocksGetShardBlockProof {
+                id: block.clone(),
+                mode: 1,
+                from: trusted.clone(),
+            })
             .await
+            .map_err(ProofError::Unavailable)?;
+
+        verify_block_proof(trusted, block, &proof)?;
+
+        Ok(proof)
+    }
+
+    async fn verify_block_id(&self, block_id: &TonBlockIdExt) -> anyhow::Result<()> {
+        if !self.verify_blocks || block_id.workchain != MAIN_CHAIN {
+            return Ok(());
+        }
+
+        let header = self
+            .client
+            .clone()
+            .oneshot(BlocksGetBlockHeader::new(block_id.clone()))
+            .await?;
+
+        self.verify_block_header(&header).await
+    }
+
+    async fn verify_block_header(&self, header: &BlocksHeader) -> anyhow::Result<()> {
+        if !self.verify_blocks || header.id.workchain != MAIN_CHAIN {
+            return Ok(());
+        }
+
+        let signatures = self
+            .client
+            .clone()
+            .oneshot(BlocksGetMasterchainBlockSignatures::new(header.id.seqno))
+            .await?;
+        if signatures.id != header.id {
+            return Err(VerificationError::new("signatures belong to another block").into());
+        }
+
+        if let Some(validator_set) = self.cached_validator_set(header.gen_utime) {
+            if validator_set
+                .verify_masterchain_block(&header.id, &signatures.signatures)
+                .is_ok()
+            {
+                return Ok(());
+            }
+        }
+
+        // validators switch at a key block rather than right at utime_since, so a block close to
+        // the switch may be signed by another set than the one of its utime
+        let validator_set = self.fetch_validator_set(header).await?;
+
+        validator_set
+            .verify_masterchain_block(&header.id, &signatures.signatures)
+            .map_err(Into::into)
+    }
+
+    /// Validator set of the latest epoch started by `utime`, if it's kept and not over by then
+    fn cached_validator_set(&self, utime: i64) -> Option<Arc<ValidatorSet>> {
+        let utime = utime.clamp(0, u32::MAX as i64) as u32;
+
+        self.validator_sets
+            .lock()
+            .unwrap()
+            .range(..=utime)
+            .next_back()
+            .map(|(_, validator_set)| validator_set.clone())
+            .filter(|validator_set| validator_set.covers(utime as i64))
+    }
+
+    async fn fetch_validator_set(
+        &self,
+        header: &BlocksHeader,
+    ) -> anyhow::Result<Arc<ValidatorSet>> {
+        // the block is signed by the validator set of the previous block state
+        let prev_block = header
+            .prev_blocks
+            .first()
+            .ok_or_else(|| VerificationError::new("block has no previous block"))?;
+        let config = self
+            .client
+            .clone()
+            .oneshot(WithBlock::new(
+                prev_block.clone(),
+                GetConfigParam::new(0, 34),
+            ))
+            .await?;
+
+        let boc: BoC = unpack_bytes(STANDARD.decode(config.config.bytes)?)?;
+        let validator_set: ValidatorSet = boc
+            .single_root()
+            .ok_or_else(|| anyhow!("config param 34 is empty"))?
+            .parse_fully()?;
+        let validator_set = Arc::new(validator_set);
+
+        let mut validator_sets = self.validator_sets.lock().unwrap();
+        validator_sets.insert(validator_set.utime_since, validator_set.clone());
+        while validator_sets.len() > VALIDATOR_SETS_CAPACITY {
+            validator_sets.pop_first();
+        }
+
+        Ok(validator_set)
     }
 
     #[instrument(skip_all, err)]
@@ -436,17 +1289,35 @@ impl TonClient {
         address: &str,
         from_tx: &InternalTransactionId,
     ) -> anyhow::Result<RawTransactions> {
-        let address = AccountAddress::new(address)?;
+        let account_address = AccountAddress::new(address)?;
 
-        self.client
+        let transactions = self
+            .client
             .clone()
             .oneshot(RawGetTransactionsV2::new(
-                address,
+                account_address,
                 from_tx.clone(),
                 16,
                 false,
             ))
-            .await
+            .await;
+        if let Some(shadow) = &self.shadow {
+            let (address, from_tx) = (address.to_owned(), from_tx.clone());
+            shadow.compare(
+                "raw.getTransactions",
+                json!({"account_address": address, "from_transaction_id": from_tx}),
+                &transactions,
+                |transactions| {
+                    json!({
+                        "transactions": transactions.transactions,
+                        "previous_transaction_id": transactions.previous_transaction_id,
+                    })
+                },
+                move |shadow| async move { shadow.raw_get_transactions(&address, &from_tx).await },
+            );
+        }
+
+        transactions
     }
 
     pub async fn blocks_get_transactions_ext(
@@ -474,15 +1345,41 @@ impl TonClient {
         reverse: bool,
         count: i32,
     ) -> anyhow::Result<BlocksTransactions> {
-        self.client
-            .clone()
-            .oneshot(BlocksGetTransactions::unverified(
-                block.to_owned(),
-                tx,
-                reverse,
-                count,
-            ))
-            .await
+        let transactions = match self.replica(BlockData::Transactions) {
+            Some(replica) => {
+                replica
+                    .blocks_get_transactions(block, tx.clone(), reverse, count)
+                    .await
+            }
+            None => {
+                self.client
+                    .clone()
+                    .oneshot(BlocksGetTransactions::unverified(
+                        block.to_owned(),
+                        tx.clone(),
+                        reverse,
+                        count,
+                    ))
+                    .await
+            }
+        }
+        .map(BlocksTransactions::with_workchain);
+        if let Some(shadow) = &self.shadow {
+            let block = block.to_owned();
+            shadow.compare(
+                "blocks.getTransactions",
+                json!({"id": block, "after": tx, "reverse": reverse, "count": count}),
+                &transactions,
+                to_json,
+                move |shadow| async move {
+                    shadow
+                        .blocks_get_transactions(&block, tx, reverse, count)
+                        .await
+                },
+            );
+        }
+
+        transactions
     }
 
     pub async fn blocks_get_transactions_verified(
@@ -501,6 +1398,7 @@ impl TonClient {
                 count,
             ))
             .await
+            .map(BlocksTransactions::with_workchain)
     }
 
     pub async fn send_message(&self, message: &str) -> anyhow::Result<()> {
@@ -520,6 +1418,25 @@ impl TonClient {
             .await
     }
 
+    /// Retries connectivity errors and takes a duplicate for a success,
+    /// see [`crate::send`]
+    pub async fn send_message_with_retry(&self, message: &str) -> anyhow::Result<Sent> {
+        let mut policy = self.send_retry;
+        if let Some(max_retries) = self
+            .client
+            .options()
+            .or(call_options::current())
+            .max_retries()
+        {
+            policy.attempts = max_retries + 1;
+        }
+
+        send_with_retry(&policy, message, || {
+            self.send_message_returning_hash(message)
+        })
+        .await
+    }
+
     pub fn get_block_tx_stream_unordered(
         &self,
         block: &TonBlockIdExt,
@@ -544,6 +1461,51 @@ impl TonClient {
         }
     }
 
+    /// Transaction ids of a block listed from both ends. The block is listed once more,
+    /// by whichever lite servers the balancer picks then, if the ends don't meet, and the
+    /// longer listing comes with a warning if they don't meet again
+    pub async fn get_block_tx_ids_checked(
+        &self,
+        block: &TonBlockIdExt,
+    ) -> anyhow::Result<CheckedTxIds> {
+        let list = || {
+            from_both_ends(
+                self.get_block_tx_id_stream(block, false).boxed(),
+                self.get_block_tx_id_stream(block, true).boxed(),
+            )
+        };
+
+        let first = list().await?;
+        if first.met {
+            return Ok(CheckedTxIds {
+                transactions: first.items,
+                warning: None,
+            });
+        }
+        tracing::warn!(
+            seqno = block.seqno,
+            collected = first.items.len(),
+            "listings of the block from both ends didn't meet"
+        );
+
+        let second = list().await?;
+        if second.met {
+            return Ok(CheckedTxIds {
+                transactions: second.items,
+                warning: None,
+            });
+        }
+
+        let listing = max_by_key(first, second, |listing| listing.items.len());
+        Ok(CheckedTxIds {
+            warning: Some(format!(
+                "listings of the block from both ends didn't meet twice, {} transactions were collected and more may be missing",
+                listing.items.len()
+            )),
+            transactions: listing.items,
+        })
+    }
+
     pub fn get_block_tx_stream(
         &self,
         block: &TonBlockIdExt,
@@ -603,6 +1565,16 @@ impl TonClient {
         &self,
         block: &TonBlockIdExt,
         reverse: bool,
+    ) -> impl Stream<Item = anyhow::Result<BlocksShortTxId>> + 'static {
+        self.get_block_tx_id_stream_after(block, reverse, None)
+    }
+
+    /// Same as [`TonClient::get_block_tx_id_stream`], resumes the listing after `after`
+    pub fn get_block_tx_id_stream_after(
+        &self,
+        block: &TonBlockIdExt,
+        reverse: bool,
+        after: Option<BlocksAccountTransactionId>,
     ) -> impl Stream<Item = anyhow::Result<BlocksShortTxId>> + 'static {
         struct State {
             last_tx: Option<BlocksAccountTransactionId>,
@@ -614,7 +1586,7 @@ impl TonClient {
 
         stream::try_unfold(
             State {
-                last_tx: None,
+                last_tx: after,
                 incomplete: true,
                 block: block.clone(),
                 this: self.clone(),
@@ -868,16 +1840,355 @@ impl TonClient {
         address: String,
         method: String,
         stack: Vec<TvmBoxedStackEntry>,
+    ) -> anyhow::Result<SmcRunResult> {
+        self.run_get_method_at(address, method, stack, None).await
+    }
+
+    pub async fn run_get_method_on_block(
+        &self,
+        address: String,
+        method: String,
+        stack: Vec<TvmBoxedStackEntry>,
+        block: TonBlockIdExt,
+    ) -> anyhow::Result<SmcRunResult> {
+        self.run_get_method_at(address, method, stack, Some(block))
+            .await
+    }
+
+    async fn run_get_method_at(
+        &self,
+        address: String,
+        method: String,
+        stack: Vec<TvmBoxedStackEntry>,
+        block: Option<TonBlockIdExt>,
     ) -> anyhow::Result<SmcRunResult> {
         let address = AccountAddress::new(&address)?;
         let method = SmcBoxedMethodId::by_name(&method);
 
+        let request = RunGetMethod::new(address, method, stack);
+        let request = match block {
+            Some(block) => request.with_block(block),
+            None => request,
+        };
+
+        self.client.clone().oneshot(request).await
+    }
+
+    /// Blocks a read of `account` at the masterchain block reflects,
+    /// the shard block of a workchain account is looked up in the shards of the masterchain block
+    pub async fn read_context(
+        &self,
+        masterchain: TonBlockIdExt,
+        account: &AccountAddressData,
+    ) -> anyhow::Result<ReadContext> {
+        if masterchain.workchain != MAIN_CHAIN {
+            return Err(anyhow!("workchain must be -1"));
+        }
+        if account.chain_id == MAIN_CHAIN {
+            return Ok(ReadContext::masterchain(masterchain));
+        }
+
+        let shards = self.get_shards_by_block_id(masterchain.clone()).await?;
+
+        Ok(ReadContext::of_account(masterchain, account, &shards))
+    }
+
+    /// Pins account reads and get method calls to `block`,
+    /// a masterchain block covers accounts of every shard
+    pub fn with_block(&self, block: TonBlockIdExt) -> PinnedTonClient {
+        PinnedTonClient {
+            client: self.clone(),
+            block,
+        }
+    }
+
+    /// Pins account reads and get method calls to the last masterchain block
+    pub async fn with_last_block(&self) -> anyhow::Result<PinnedTonClient> {
+        let block = self.get_masterchain_info().await?.last;
+
+        Ok(self.with_block(block))
+    }
+
+    /// Waits for a transaction of `address` with the message as in_msg,
+    /// only transactions after `after_lt` are checked, the current last one if missing
+    pub async fn wait_for_transaction(
+        &self,
+        address: &str,
+        message: &MessageRef,
+        after_lt: Option<i64>,
+        timeout: Duration,
+    ) -> anyhow::Result<WaitForTransaction> {
+        let mut last_checked_lt = after_lt;
+
+        // lite server requests count against the timeout too, the first read of the account as well
+        let polled = tokio::time::timeout(
+            timeout,
+            self.poll_for_transaction(address, message, &mut last_checked_lt),
+        )
+        .await;
+
+        match polled {
+            Ok(found) => found,
+            Err(_) => Ok(WaitForTransaction::Timeout {
+                last_checked_lt: last_checked_lt.unwrap_or_default(),
+            }),
+        }
+    }
+
+    /// Checks new transactions of `address` every interval until one has the message as in_msg,
+    /// `last_checked_lt` follows the transactions checked, it starts at the last one if missing
+    async fn poll_for_transaction(
+        &self,
+        address: &str,
+        message: &MessageRef,
+        last_checked_lt: &mut Option<i64>,
+    ) -> anyhow::Result<WaitForTransaction> {
+        if last_checked_lt.is_none() {
+            let last_tx = self
+                .raw_get_account_state(address)
+                .await?
+                .last_transaction_id;
+            *last_checked_lt = Some(last_tx.map_or(0, |tx| tx.lt));
+        }
+
+        let mut interval = tokio::time::interval(WAIT_FOR_TRANSACTION_INTERVAL);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            interval.tick().await;
+
+            let Some(last_tx) = self
+                .raw_get_account_state(address)
+                .await?
+                .last_transaction_id
+            else {
+                continue;
+            };
+            let checked_lt = last_checked_lt.unwrap_or_default();
+            if last_tx.lt <= checked_lt {
+                continue;
+            }
+
+            let mut txs = self
+                .get_account_tx_stream_from(address, Some(last_tx.clone()))
+                .try_take_while(move |tx| {
+                    futures::future::ready(Ok(tx.transaction_id.lt > checked_lt))
+                })
+                .boxed();
+            while let Some(tx) = txs.try_next().await? {
+                if tx.in_msg.as_ref().is_some_and(|msg| message.matches(msg)) {
+                    let bounced = tx.out_msgs.iter().any(RawMessage::is_bounce);
+
+                    return Ok(WaitForTransaction::Found {
+                        transaction: Box::new(tx),
+                        bounced,
+                    });
+                }
+            }
+
+            *last_checked_lt = Some(last_tx.lt);
+        }
+    }
+
+    /// Transaction of the destination account which processed the internal message `msg`,
+    /// none if it isn't among the first `max_scanned` transactions after the message was created
+    pub async fn locate_result_tx(
+        &self,
+        msg: &RawMessage,
+        max_scanned: usize,
+    ) -> anyhow::Result<Option<RawTransaction>> {
+        let destination = msg
+            .destination
+            .account_address
+            .as_deref()
+            .ok_or_else(|| anyhow!("message has no destination"))?;
+        let message = MessageRef::Body {
+            source: msg.source.account_address.clone(),
+            body_hash: msg.body_hash.clone(),
+        };
+
+        let created_lt = msg.created_lt;
+        let mut txs = self
+            .get_account_tx_stream(destination)
+            .try_take_while(move |tx| futures::future::ready(Ok(tx.transaction_id.lt > created_lt)))
+            .take(max_scanned)
+            .boxed();
+        while let Some(tx) = txs.try_next().await? {
+            let found = tx
+                .in_msg
+                .as_ref()
+                .is_some_and(|in_msg| in_msg.created_lt == created_lt && message.matches(in_msg));
+            if found {
+                return Ok(Some(tx));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Polls the state of `address` at each new masterchain block and emits it when the balance,
+    /// the status or the last transaction changes, the first poll always emits.
+    /// The stream ends after the first error.
+    pub fn watch_account(
+        &self,
+        address: &str,
+        poll_interval: Duration,
+    ) -> impl Stream<Item = anyhow::Result<AccountStateDelta>> + 'static {
+        let client = self.clone();
+        let address = address.to_owned();
+
+        try_stream! {
+            let mut interval = tokio::time::interval(poll_interval);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            let mut last_block: Option<TonBlockIdExt> = None;
+            let mut last: Option<AccountStateDelta> = None;
+
+            loop {
+                interval.tick().await;
+
+                let block = client.get_masterchain_info().await?.last;
+                if last_block.as_ref() == Some(&block) {
+                    continue;
+                }
+
+                let state = client
+                    .raw_get_account_state_on_block(&address, block.clone())
+                    .await?;
+                let delta = AccountStateDelta {
+                    block_id: block.clone(),
+                    balance: state.balance,
+                    status: (&state).into(),
+                    last_transaction_id: state.last_transaction_id,
+                };
+                last_block = Some(block);
+
+                if delta.changed_since(last.as_ref()) {
+                    last = Some(delta.clone());
+
+                    yield delta;
+                }
+            }
+        }
+    }
+
+    /// Runs get methods concurrently, results are in the order of calls and fail independently
+    pub async fn run_get_methods(
+        &self,
+        calls: Vec<GetMethodCall>,
+    ) -> anyhow::Result<Vec<anyhow::Result<SmcRunResult>>> {
+        self.run_get_methods_at(calls, None).await
+    }
+
+    async fn run_get_methods_at(
+        &self,
+        calls: Vec<GetMethodCall>,
+        block: Option<TonBlockIdExt>,
+    ) -> anyhow::Result<Vec<anyhow::Result<SmcRunResult>>> {
+        if calls.len() > RUN_GET_METHODS_LIMIT {
+            return Err(anyhow!(
+                "too many get method calls: {}, max is {}",
+                calls.len(),
+                RUN_GET_METHODS_LIMIT
+            ));
+        }
+
+        Ok(stream::iter(calls)
+            .map(|call| {
+                self.run_get_method_at(call.address, call.method, call.stack, block.clone())
+            })
+            .buffered(RUN_GET_METHODS_CONCURRENCY)
+            .collect()
+            .await)
+    }
+
+    /// Sizes of the out msg queues of the last blocks, they are fetched again once the
+    /// masterchain moves past the block the cached ones were taken at
+    pub async fn get_out_msg_queue_sizes(&self) -> anyhow::Result<BlocksOutMsgQueueSizes> {
+        let seqno = self.get_masterchain_info().await?.last.seqno;
+        if let Some((cached_seqno, sizes)) = self.out_msg_queue_sizes.lock().unwrap().as_ref() {
+            if *cached_seqno >= seqno {
+                return Ok(sizes.clone());
+            }
+        }
+
+        let sizes = self
+            .client
+            .clone()
+            .oneshot(BlocksGetOutMsgQueueSizes::new(0, 0, 0))
+            .await?;
+
+        let taken_at = sizes
+            .shards
+            .iter()
+            .find(|shard| shard.id.workchain == -1)
+            .map(|shard| shard.id.seqno);
+        if let Some(taken_at) = taken_at {
+            self.out_msg_queue_sizes
+                .lock()
+                .unwrap()
+                .replace((taken_at, sizes.clone()));
+        }
+
+        Ok(sizes)
+    }
+
+    /// Approximate bytes kept by the block header, validator set and out msg queue caches
+    pub fn cache_usage(&self) -> usize {
+        let headers = self.headers.len() * self.header_size.load(Ordering::Relaxed);
+        let validator_sets: usize = self
+            .validator_sets
+            .lock()
+            .unwrap()
+            .values()
+            .map(|validator_set| validator_set.heap_size())
+            .sum();
+        let out_msg_queue_sizes = self
+            .out_msg_queue_sizes
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map_or(0, |(_, sizes)| json_size(sizes));
+
+        headers + validator_sets + out_msg_queue_sizes
+    }
+
+    /// Drops the caches until at least `bytes` are freed, the headers go first and as a whole
+    /// since the cache has no order to drop the oldest ones by, returns the bytes dropped
+    pub fn shrink_caches(&self, bytes: usize) -> usize {
+        let mut freed = self.headers.len() * self.header_size.load(Ordering::Relaxed);
+        self.headers.clear();
+        if freed >= bytes {
+            return freed;
+        }
+
+        if let Some((_, sizes)) = self.out_msg_queue_sizes.lock().unwrap().take() {
+            freed += json_size(&sizes);
+        }
+        if freed >= bytes {
+            return freed;
+        }
+
+        let validator_sets = std::mem::take(&mut *self.validator_sets.lock().unwrap());
+        freed += validator_sets
+            .values()
+            .map(|validator_set| validator_set.heap_size())
+            .sum::<usize>();
+
+        freed
+    }
+
+    pub async fn get_config_param(&self, param: i32) -> anyhow::Result<ConfigInfo> {
         self.client
             .clone()
-            .oneshot(RunGetMethod::new(address, method, stack))
+            .oneshot(GetConfigParam::new(0, param))
             .await
     }
 
+    /// Whole blockchain config as of the latest block
+    pub async fn get_config_all(&self) -> anyhow::Result<ConfigInfo> {
+        self.client.clone().oneshot(GetConfigAll { mode: 0 }).await
+    }
+
     pub async fn get_shard_account_cell(&self, address: &str) -> anyhow::Result<TvmCell> {
         let address = AccountAddress::new(address)?;
 
@@ -938,7 +2249,6 @@ impl TonClient {
         &self,
         block: &TonBlockIdExt,
     ) -> impl TryStream<Ok = InternalAccountAddress, Error = anyhow::Error> + 'static {
-        let chain = block.workchain;
         let stream_map = StreamMap::from_iter(
             [false, true].map(|r| (r, self.get_block_tx_id_stream(block, r).boxed())),
         );
@@ -957,9 +2267,9 @@ impl TonClient {
                     if addr == tx.account() { continue }
                 }
 
-                last.insert(key, tx.account().to_owned());
+                last.insert(key, tx.account().clone());
 
-                yield tx.into_internal(chain);
+                yield tx.address();
             }
         };
 
@@ -1023,3 +2333,170 @@ impl TonClient {
         state.last_transaction_id.ok_or(anyhow!("tx not found"))
     }
 }
+
+/// Approximate bytes of `value` by its JSON form
+fn json_size<T: serde::Serialize>(value: &T) -> usize {
+    serde_json::to_vec(value).map_or(0, |json| json.len())
+}
+
+fn walk_ancestors<F, Fut>(
+    from: TonBlockIdExt,
+    count: usize,
+    fetch: F,
+) -> impl Stream<Item = anyhow::Result<BlocksHeader>>
+where
+    F: Fn(TonBlockIdExt) -> Fut,
+    Fut: Future<Output = anyhow::Result<BlocksHeader>>,
+{
+    stream::try_unfold((Some(from), count), move |(next, left)| {
+        let header = match next {
+            Some(id) if left > 0 => Some(fetch(id)),
+            _ => None,
+        };
+
+        async move {
+            let Some(header) = header else {
+                return Ok(None);
+            };
+            let header = header.await?;
+            let prev = header.prev_blocks.first().cloned();
+
+            anyhow::Ok(Some((header, (prev, left - 1))))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn block_id(shard: i64, seqno: i32) -> TonBlockIdExt {
+        TonBlockIdExt::new(0, shard, seqno, seqno.to_string(), seqno.to_string())
+    }
+
+    fn header(id: TonBlockIdExt, prev_blocks: Vec<TonBlockIdExt>) -> BlocksHeader {
+        serde_json::from_value(json!({
+            "@type": "blocks.header",
+            "id": id,
+            "after_merge": prev_blocks.len() == 2,
+            "after_split": false,
+            "before_split": false,
+            "want_merge": false,
+            "want_split": false,
+            "is_key_block": false,
+            "prev_blocks": prev_blocks,
+        }))
+        .unwrap()
+    }
+
+    async fn walk(from: TonBlockIdExt, count: usize) -> anyhow::Result<Vec<i32>> {
+        // 0x4000.. and 0xc000.. merge into 0x8000.. at seqno 10
+        let left = 0x4000000000000000;
+        let right = -0x4000000000000000;
+        let root = i64::MIN;
+        let headers = HashMap::from([
+            (
+                block_id(root, 11),
+                header(block_id(root, 11), vec![block_id(root, 10)]),
+            ),
+            (
+                block_id(root, 10),
+                header(
+                    block_id(root, 10),
+                    vec![block_id(left, 9), block_id(right, 5)],
+                ),
+            ),
+            (
+                block_id(left, 9),
+                header(block_id(left, 9), vec![block_id(left, 8)]),
+            ),
+            (
+                block_id(right, 5),
+                header(block_id(right, 5), vec![block_id(right, 4)]),
+            ),
+        ]);
+
+        walk_ancestors(from, count, |id| {
+            let header = headers
+                .get(&id)
+                .cloned()
+                .ok_or_else(|| anyhow!("unknown block {}", id.seqno));
+
+            async move { header }
+        })
+        .map_ok(|header| header.id.seqno)
+        .try_collect()
+        .await
+    }
+
+    fn delta(seqno: i32, balance: i64, lt: i64) -> AccountStateDelta {
+        AccountStateDelta {
+            block_id: block_id(i64::MIN, seqno),
+            balance: Some(balance),
+            status: AccountStatus::Active,
+            last_transaction_id: Some(InternalTransactionId {
+                hash: lt.to_string(),
+                lt,
+            }),
+        }
+    }
+
+    #[test]
+    fn account_state_delta_ignores_block() {
+        assert!(delta(1, 100, 10).changed_since(None));
+        assert!(!delta(2, 100, 10).changed_since(Some(&delta(1, 100, 10))));
+        assert!(delta(2, 90, 10).changed_since(Some(&delta(1, 100, 10))));
+        assert!(delta(2, 100, 11).changed_since(Some(&delta(1, 100, 10))));
+
+        let mut frozen = delta(2, 100, 10);
+        frozen.status = AccountStatus::Frozen;
+        assert!(frozen.changed_since(Some(&delta(1, 100, 10))));
+    }
+
+    #[test]
+    fn account_status_of_raw_state() {
+        let state = |code: &str, frozen_hash: &str| -> RawFullAccountState {
+            serde_json::from_value(json!({
+                "@type": "raw.fullAccountState",
+                "balance": "100",
+                "code": code,
+                "data": "",
+                "last_transaction_id": {"@type": "internal.transactionId", "lt": "0", "hash": ""},
+                "block_id": block_id(i64::MIN, 1),
+                "frozen_hash": frozen_hash,
+                "sync_utime": 0
+            }))
+            .unwrap()
+        };
+
+        assert_eq!(AccountStatus::from(&state("", "")), AccountStatus::Uninit);
+        assert_eq!(
+            AccountStatus::from(&state("te6cc", "")),
+            AccountStatus::Active
+        );
+        assert_eq!(
+            AccountStatus::from(&state("", "aGFzaA==")),
+            AccountStatus::Frozen
+        );
+    }
+
+    #[tokio::test]
+    async fn block_ancestors_follow_left_branch_after_merge() {
+        assert_eq!(walk(block_id(i64::MIN, 11), 3).await.unwrap(), [11, 10, 9]);
+    }
+
+    #[tokio::test]
+    async fn block_ancestors_count() {
+        assert_eq!(
+            walk(block_id(i64::MIN, 11), 0).await.unwrap(),
+            Vec::<i32>::new()
+        );
+        assert_eq!(walk(block_id(i64::MIN, 11), 1).await.unwrap(), [11]);
+    }
+
+    #[tokio::test]
+    async fn block_ancestors_stop_on_error() {
+        assert!(walk(block_id(i64::MIN, 11), 4).await.is_err());
+    }
+}
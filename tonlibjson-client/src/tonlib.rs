@@ -0,0 +1,27 @@
+use std::borrow::Cow;
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::time::Duration;
+use ton_client_util::discover::config::TonConfig;
+
+/// JSON interface of tonlib, every response carries the `@extra` of its request
+/// while updates such as `updateSyncState` carry none
+pub trait Tonlib: Debug + Send + Sync {
+    fn send(&self, request: &str) -> anyhow::Result<()>;
+
+    /// Next response or update, an error if none comes within `timeout`
+    fn receive(&self, timeout: Duration) -> anyhow::Result<Cow<'_, str>>;
+}
+
+/// Tonlib of the single lite server of a config, see [`crate::ton::TonClientBuilder::set_tonlib`]
+pub type MakeTonlib = Arc<dyn Fn(&TonConfig) -> Arc<dyn Tonlib> + Send + Sync>;
+
+impl Tonlib for tonlibjson_sys::Client {
+    fn send(&self, request: &str) -> anyhow::Result<()> {
+        tonlibjson_sys::Client::send(self, request)
+    }
+
+    fn receive(&self, timeout: Duration) -> anyhow::Result<Cow<'_, str>> {
+        tonlibjson_sys::Client::receive(self, timeout).map(Cow::Borrowed)
+    }
+}
@@ -0,0 +1,167 @@
+use crate::shard;
+use dashmap::DashMap;
+use std::sync::Mutex;
+use std::time::SystemTime;
+use ton_client_util::router::route::{BlockCriteria, Route};
+use ton_client_util::router::RouteObserver;
+
+/// Shards are counted by their prefix of at most this many bits, so that labels stay bounded
+pub const SHARD_BUCKET_DEPTH: u32 = 8;
+
+/// Where requests went, none of the workchain and shard for requests of the latest block
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TrafficKey {
+    pub workchain: Option<i32>,
+    /// shard truncated to [`SHARD_BUCKET_DEPTH`]
+    pub shard: Option<i64>,
+    /// only archival lite servers had the block
+    pub archival: bool,
+}
+
+impl TrafficKey {
+    pub fn new(route: &Route, archival: bool) -> Self {
+        let (workchain, shard) = match route {
+            Route::Block { chain, criteria } => {
+                let shard = match criteria {
+                    BlockCriteria::Seqno { shard, .. } => *shard,
+                    BlockCriteria::LogicalTime { address, .. } => shard::of_account(address),
+                };
+
+                (Some(*chain), Some(shard::truncate(shard, SHARD_BUCKET_DEPTH)))
+            }
+            Route::Latest => (None, None),
+        };
+
+        Self {
+            workchain,
+            shard,
+            archival,
+        }
+    }
+}
+
+/// Requests routed by [`TrafficKey`] since the start or the last reset
+pub struct TrafficStats {
+    requests: DashMap<TrafficKey, u64>,
+    since: Mutex<SystemTime>,
+}
+
+impl Default for TrafficStats {
+    fn default() -> Self {
+        metrics::describe_counter!(
+            "ton_router_traffic_count",
+            "Count of requests routed by workchain, shard prefix and archival routing"
+        );
+
+        Self {
+            requests: Default::default(),
+            since: Mutex::new(SystemTime::now()),
+        }
+    }
+}
+
+impl TrafficStats {
+    /// Counts sorted by key and the time they're counted since
+    pub fn snapshot(&self) -> (SystemTime, Vec<(TrafficKey, u64)>) {
+        let since = *self.since.lock().unwrap();
+        let mut requests: Vec<_> = self
+            .requests
+            .iter()
+            .map(|entry| (*entry.key(), *entry.value()))
+            .collect();
+        requests.sort_unstable();
+
+        (since, requests)
+    }
+
+    /// Counts up to now, they start over from zero
+    pub fn reset(&self) -> (SystemTime, Vec<(TrafficKey, u64)>) {
+        let snapshot = self.snapshot();
+        self.requests.clear();
+        *self.since.lock().unwrap() = SystemTime::now();
+
+        snapshot
+    }
+}
+
+impl RouteObserver for TrafficStats {
+    fn observe(&self, route: &Route, archival: bool) {
+        let key = TrafficKey::new(route, archival);
+        *self.requests.entry(key).or_default() += 1;
+
+        let workchain = key
+            .workchain
+            .map_or_else(|| "latest".to_owned(), |workchain| workchain.to_string());
+        let shard = key
+            .shard
+            .map_or_else(|| "latest".to_owned(), |shard| format!("{:016x}", shard as u64));
+        metrics::counter!(
+            "ton_router_traffic_count",
+            "workchain" => workchain,
+            "shard" => shard,
+            "archival" => archival.to_string()
+        )
+        .increment(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn block_routes_are_bucketed() {
+        let route = Route::Block {
+            chain: 0,
+            criteria: BlockCriteria::Seqno {
+                shard: 0xa5c0000000000000_u64 as i64,
+                seqno: 1,
+            },
+        };
+
+        assert_eq!(
+            TrafficKey::new(&route, true),
+            TrafficKey {
+                workchain: Some(0),
+                shard: Some(0xa580000000000000_u64 as i64),
+                archival: true,
+            }
+        );
+    }
+
+    #[test]
+    fn account_routes_are_bucketed_by_account() {
+        let mut address = [0; 32];
+        address[0] = 0x40;
+        let route = Route::Block {
+            chain: -1,
+            criteria: BlockCriteria::LogicalTime { address, lt: 1 },
+        };
+
+        assert_eq!(
+            TrafficKey::new(&route, false).shard,
+            Some(0x4080000000000000)
+        );
+    }
+
+    #[test]
+    fn counts_until_reset() {
+        let stats = TrafficStats::default();
+        stats.observe(&Route::Latest, false);
+        stats.observe(&Route::Latest, false);
+
+        let (_, requests) = stats.reset();
+        assert_eq!(
+            requests,
+            vec![(
+                TrafficKey {
+                    workchain: None,
+                    shard: None,
+                    archival: false,
+                },
+                2
+            )]
+        );
+        assert!(stats.snapshot().1.is_empty());
+    }
+}
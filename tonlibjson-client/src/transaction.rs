@@ -0,0 +1,490 @@
+//! Phases of a transaction from its BoC, which `raw.transaction` of tonlib leaves out
+use anyhow::anyhow;
+use num_bigint::BigUint;
+use toner::tlb::bits::de::{BitReader, BitReaderExt};
+use toner::tlb::bits::r#as::{NBits, VarInt};
+use toner::tlb::de::CellParser;
+use toner::tlb::r#as::Ref;
+use toner::tlb::Cell;
+use toner::ton::currency::{CurrencyCollection, Grams};
+
+/// Why the compute phase didn't run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComputeSkipReason {
+    NoState,
+    BadState,
+    NoGas,
+    Suspended,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComputePhase {
+    Skipped(ComputeSkipReason),
+    Vm {
+        success: bool,
+        exit_code: i32,
+        gas_fees: u64,
+        gas_used: u64,
+        vm_steps: u32,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActionPhase {
+    pub success: bool,
+    pub result_code: i32,
+    pub total_actions: u16,
+}
+
+/// Outcome of bouncing the inbound message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BouncePhase {
+    NegativeFunds,
+    NoFunds,
+    Ok,
+}
+
+/// `TransactionDescr` of ordinary, storage and tick-tock transactions.
+/// Only ordinary transactions bounce, storage ones have no compute phase
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransactionDescription {
+    /// none without a storage phase
+    pub storage_fees: Option<BigUint>,
+    pub compute: Option<ComputePhase>,
+    pub action: Option<ActionPhase>,
+    pub aborted: bool,
+    pub bounce: Option<BouncePhase>,
+    pub destroyed: bool,
+}
+
+/// Description of a `Transaction` cell, none for split and merge transactions
+/// ```tlb
+/// transaction$0111 ... description:^TransactionDescr = Transaction;
+/// ```
+pub fn parse_description(transaction: &Cell) -> anyhow::Result<Option<TransactionDescription>> {
+    let tag: u8 = transaction.parser().unpack_as::<_, NBits<4>>()?;
+    if tag != 0b0111 {
+        return Err(anyhow!("unsupported transaction tag: {tag:#b}"));
+    }
+    // the description is the last field and so the last reference
+    let description = transaction
+        .references
+        .last()
+        .ok_or_else(|| anyhow!("transaction has no description"))?;
+
+    parse_description_cell(description)
+}
+
+/// `TransactionDescr` cell of a transaction
+pub(crate) fn parse_description_cell(
+    description: &Cell,
+) -> anyhow::Result<Option<TransactionDescription>> {
+    description_of(&mut description.parser())
+}
+
+/// ```tlb
+/// trans_ord$0000 credit_first:Bool
+///   storage_ph:(Maybe TrStoragePhase) credit_ph:(Maybe TrCreditPhase)
+///   compute_ph:TrComputePhase action:(Maybe ^TrActionPhase)
+///   aborted:Bool bounce:(Maybe TrBouncePhase) destroyed:Bool = TransactionDescr;
+/// trans_storage$0001 storage_ph:TrStoragePhase = TransactionDescr;
+/// trans_tick_tock$001 is_tock:Bool storage_ph:TrStoragePhase
+///   compute_ph:TrComputePhase action:(Maybe ^TrActionPhase)
+///   aborted:Bool destroyed:Bool = TransactionDescr;
+/// ```
+fn description_of(parser: &mut CellParser<'_>) -> anyhow::Result<Option<TransactionDescription>> {
+    let tag: u8 = parser.unpack_as::<_, NBits<3>>()?;
+    let description = match tag {
+        // trans_storage$0001
+        0b000 if parser.unpack::<bool>()? => {
+            let storage_fees = storage_phase(parser)?;
+
+            TransactionDescription {
+                storage_fees: Some(storage_fees),
+                compute: None,
+                action: None,
+                aborted: false,
+                bounce: None,
+                destroyed: false,
+            }
+        }
+        0b000 => {
+            let _credit_first: bool = parser.unpack()?;
+            let storage_fees = if parser.unpack::<bool>()? {
+                Some(storage_phase(parser)?)
+            } else {
+                None
+            };
+            if parser.unpack::<bool>()? {
+                let _due_fees_collected: Option<BigUint> =
+                    parser.unpack_as::<_, Option<Grams>>()?;
+                let _credit: CurrencyCollection = parser.parse()?;
+            }
+            let compute = compute_phase(parser)?;
+            let action = action_phase(parser)?;
+            let aborted = parser.unpack()?;
+            let bounce = if parser.unpack::<bool>()? {
+                Some(bounce_phase(parser)?)
+            } else {
+                None
+            };
+
+            TransactionDescription {
+                storage_fees,
+                compute: Some(compute),
+                action,
+                aborted,
+                bounce,
+                destroyed: parser.unpack()?,
+            }
+        }
+        0b001 => {
+            let _is_tock: bool = parser.unpack()?;
+            let storage_fees = storage_phase(parser)?;
+            let compute = compute_phase(parser)?;
+            let action = action_phase(parser)?;
+
+            TransactionDescription {
+                storage_fees: Some(storage_fees),
+                compute: Some(compute),
+                action,
+                aborted: parser.unpack()?,
+                bounce: None,
+                destroyed: parser.unpack()?,
+            }
+        }
+        _ => return Ok(None),
+    };
+
+    Ok(Some(description))
+}
+
+/// ```tlb
+/// tr_phase_storage$_ storage_fees_collected:Grams storage_fees_due:(Maybe Grams)
+///   status_change:AccStatusChange = TrStoragePhase;
+/// ```
+fn storage_phase(parser: &mut CellParser<'_>) -> anyhow::Result<BigUint> {
+    let collected = parser.unpack_as::<_, Grams>()?;
+    let _due: Option<BigUint> = parser.unpack_as::<_, Option<Grams>>()?;
+    skip_status_change(parser)?;
+
+    Ok(collected)
+}
+
+/// acst_unchanged$0, acst_frozen$10 or acst_deleted$11
+fn skip_status_change(parser: &mut CellParser<'_>) -> anyhow::Result<()> {
+    if parser.unpack::<bool>()? {
+        parser.skip(1)?;
+    }
+
+    Ok(())
+}
+
+/// ```tlb
+/// tr_phase_compute_skipped$0 reason:ComputeSkipReason = TrComputePhase;
+/// tr_phase_compute_vm$1 success:Bool msg_state_used:Bool account_activated:Bool gas_fees:Grams
+///   ^[ gas_used:(VarUInteger 7) gas_limit:(VarUInteger 7) gas_credit:(Maybe (VarUInteger 3))
+///   mode:int8 exit_code:int32 exit_arg:(Maybe int32) vm_steps:uint32 ... ] = TrComputePhase;
+///
+/// cskip_no_state$00 cskip_bad_state$01 cskip_no_gas$10 cskip_suspended$110 = ComputeSkipReason;
+/// ```
+fn compute_phase(parser: &mut CellParser<'_>) -> anyhow::Result<ComputePhase> {
+    if !parser.unpack::<bool>()? {
+        let reason = match parser.unpack_as::<u8, NBits<2>>()? {
+            0b00 => ComputeSkipReason::NoState,
+            0b01 => ComputeSkipReason::BadState,
+            0b10 => ComputeSkipReason::NoGas,
+            _ => {
+                parser.skip(1)?;
+                ComputeSkipReason::Suspended
+            }
+        };
+
+        return Ok(ComputePhase::Skipped(reason));
+    }
+
+    let success = parser.unpack()?;
+    let _msg_state_used: bool = parser.unpack()?;
+    let _account_activated: bool = parser.unpack()?;
+    let gas_fees: BigUint = parser.unpack_as::<_, Grams>()?;
+
+    let vm: Cell = parser.parse_as::<_, Ref>()?;
+    let mut vm = vm.parser();
+    let gas_used: BigUint = vm.unpack_as::<_, VarInt<3>>()?;
+    let _gas_limit: BigUint = vm.unpack_as::<_, VarInt<3>>()?;
+    let _gas_credit: Option<BigUint> = vm.unpack_as::<_, Option<VarInt<2>>>()?;
+    let _mode: i8 = vm.unpack()?;
+    let exit_code = vm.unpack()?;
+    // exit_arg
+    if vm.unpack::<bool>()? {
+        vm.skip(32)?;
+    }
+
+    Ok(ComputePhase::Vm {
+        success,
+        exit_code,
+        gas_fees: gas_fees.try_into()?,
+        gas_used: gas_used.try_into()?,
+        vm_steps: vm.unpack()?,
+    })
+}
+
+/// ```tlb
+/// tr_phase_action$_ success:Bool valid:Bool no_funds:Bool status_change:AccStatusChange
+///   total_fwd_fees:(Maybe Grams) total_action_fees:(Maybe Grams) result_code:int32
+///   result_arg:(Maybe int32) tot_actions:uint16 ... = TrActionPhase;
+/// ```
+fn action_phase(parser: &mut CellParser<'_>) -> anyhow::Result<Option<ActionPhase>> {
+    let Some(action) = parser.parse_as::<Option<Cell>, Option<Ref>>()? else {
+        return Ok(None);
+    };
+
+    let mut action = action.parser();
+    let success = action.unpack()?;
+    let _valid: bool = action.unpack()?;
+    let _no_funds: bool = action.unpack()?;
+    skip_status_change(&mut action)?;
+    let _total_fwd_fees: Option<BigUint> = action.unpack_as::<_, Option<Grams>>()?;
+    let _total_action_fees: Option<BigUint> = action.unpack_as::<_, Option<Grams>>()?;
+    let result_code = action.unpack()?;
+    // result_arg
+    if action.unpack::<bool>()? {
+        action.skip(32)?;
+    }
+
+    Ok(Some(ActionPhase {
+        success,
+        result_code,
+        total_actions: action.unpack()?,
+    }))
+}
+
+/// ```tlb
+/// tr_phase_bounce_negfunds$00 msg_size:StorageUsedShort req_fwd_fees:Grams = TrBouncePhase;
+/// tr_phase_bounce_nofunds$01 msg_size:StorageUsedShort req_fwd_fees:Grams = TrBouncePhase;
+/// tr_phase_bounce_ok$1 msg_size:StorageUsedShort msg_fees:Grams fwd_fees:Grams = TrBouncePhase;
+///
+/// storage_used_short$_ cells:(VarUInteger 7) bits:(VarUInteger 7) = StorageUsedShort;
+/// ```
+fn bounce_phase(parser: &mut CellParser<'_>) -> anyhow::Result<BouncePhase> {
+    let bounce = if parser.unpack::<bool>()? {
+        BouncePhase::Ok
+    } else if parser.unpack::<bool>()? {
+        BouncePhase::NoFunds
+    } else {
+        BouncePhase::NegativeFunds
+    };
+
+    let _cells: BigUint = parser.unpack_as::<_, VarInt<3>>()?;
+    let _bits: BigUint = parser.unpack_as::<_, VarInt<3>>()?;
+    let _fees: BigUint = parser.unpack_as::<_, Grams>()?;
+    if bounce == BouncePhase::Ok {
+        let _fwd_fees: BigUint = parser.unpack_as::<_, Grams>()?;
+    }
+
+    Ok(bounce)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use toner::tlb::ser::CellBuilder;
+
+    /// Exit code of a compute phase out of gas
+    const OUT_OF_GAS: i32 = -14;
+
+    fn grams(builder: &mut CellBuilder, value: u32) {
+        builder.pack_as::<_, Grams>(BigUint::from(value)).unwrap();
+    }
+
+    /// Transaction with `description`, the fields before it are left empty
+    fn transaction(description: Cell) -> Cell {
+        let mut transaction = Cell::builder();
+        transaction.pack_as::<_, NBits<4>>(0b0111u8).unwrap();
+        for reference in [Cell::new(), Cell::new(), description] {
+            transaction.store_as::<_, Ref>(reference).unwrap();
+        }
+
+        transaction.into_cell()
+    }
+
+    /// Ordinary transaction of an internal message with storage, credit and vm compute phases
+    fn ordinary(exit_code: i32, result_code: Option<i32>, bounce: Option<BouncePhase>) -> Cell {
+        let mut vm = Cell::builder();
+        vm.pack_as::<_, VarInt<3>>(BigUint::from(1234u32)).unwrap();
+        vm.pack_as::<_, VarInt<3>>(BigUint::from(10000u32)).unwrap();
+        vm.pack_as::<_, Option<VarInt<2>>>(None::<BigUint>).unwrap();
+        vm.pack(0i8).unwrap().pack(exit_code).unwrap();
+        // no exit_arg, vm_steps, the state hashes aren't read
+        vm.pack(false).unwrap().pack(58u32).unwrap();
+
+        let action = result_code.map(|result_code| {
+            let mut action = Cell::builder();
+            action.pack([result_code == 0, true, false, false]).unwrap();
+            action.pack_as::<_, Option<Grams>>(None::<BigUint>).unwrap();
+            action.pack_as::<_, Option<Grams>>(None::<BigUint>).unwrap();
+            action.pack(result_code).unwrap().pack(false).unwrap();
+            // tot_actions
+            action.pack(1u16).unwrap();
+
+            action.into_cell()
+        });
+
+        let mut description = Cell::builder();
+        description.pack_as::<_, NBits<4>>(0u8).unwrap();
+        // credit_first, storage phase
+        description.pack([false, true]).unwrap();
+        grams(&mut description, 10);
+        // no fees due, status unchanged, credit phase without fees collected
+        description.pack([false, false, true, false]).unwrap();
+        grams(&mut description, 1_000_000);
+        // no extra currencies
+        description.pack(false).unwrap();
+        // vm compute phase, success, msg_state_used, account_activated
+        description
+            .pack([true, exit_code == 0, false, false])
+            .unwrap();
+        grams(&mut description, 5);
+        description.store_as::<_, Ref>(vm.into_cell()).unwrap();
+        description.store_as::<_, Option<Ref>>(action).unwrap();
+        // aborted
+        description.pack(exit_code != 0).unwrap();
+        match bounce {
+            None => {
+                description.pack(false).unwrap();
+            }
+            Some(bounce) => {
+                let tag: &[bool] = match bounce {
+                    BouncePhase::NegativeFunds => &[true, false, false],
+                    BouncePhase::NoFunds => &[true, false, true],
+                    BouncePhase::Ok => &[true, true],
+                };
+                for bit in tag {
+                    description.pack(*bit).unwrap();
+                }
+                // msg_size
+                description
+                    .pack_as::<_, VarInt<3>>(BigUint::from(1u32))
+                    .unwrap();
+                description
+                    .pack_as::<_, VarInt<3>>(BigUint::from(267u32))
+                    .unwrap();
+                grams(&mut description, 400);
+                if bounce == BouncePhase::Ok {
+                    grams(&mut description, 200);
+                }
+            }
+        }
+        // destroyed
+        description.pack(false).unwrap();
+
+        transaction(description.into_cell())
+    }
+
+    #[test]
+    fn successful_transfer() {
+        let description = parse_description(&ordinary(0, Some(0), None)).unwrap();
+
+        assert_eq!(
+            description,
+            Some(TransactionDescription {
+                storage_fees: Some(BigUint::from(10u32)),
+                compute: Some(ComputePhase::Vm {
+                    success: true,
+                    exit_code: 0,
+                    gas_fees: 5,
+                    gas_used: 1234,
+                    vm_steps: 58,
+                }),
+                action: Some(ActionPhase {
+                    success: true,
+                    result_code: 0,
+                    total_actions: 1,
+                }),
+                aborted: false,
+                bounce: None,
+                destroyed: false,
+            })
+        );
+    }
+
+    #[test]
+    fn bounced_message() {
+        let description = parse_description(&ordinary(0xffff, None, Some(BouncePhase::Ok)))
+            .unwrap()
+            .unwrap();
+
+        assert!(description.aborted);
+        assert_eq!(description.action, None);
+        assert_eq!(description.bounce, Some(BouncePhase::Ok));
+        assert!(!description.destroyed);
+    }
+
+    #[test]
+    fn out_of_gas() {
+        let transaction = ordinary(OUT_OF_GAS, None, Some(BouncePhase::NoFunds));
+
+        let description = parse_description(&transaction).unwrap().unwrap();
+
+        assert!(matches!(
+            description.compute,
+            Some(ComputePhase::Vm {
+                success: false,
+                exit_code: OUT_OF_GAS,
+                ..
+            })
+        ));
+        assert!(description.aborted);
+        assert_eq!(description.bounce, Some(BouncePhase::NoFunds));
+    }
+
+    #[test]
+    fn skipped_compute_phase() {
+        let mut description = Cell::builder();
+        description.pack_as::<_, NBits<4>>(0u8).unwrap();
+        // no storage and credit phases, compute phase skipped with no_gas$10
+        description
+            .pack([false, false, false, false, true, false])
+            .unwrap();
+        // no action phase, aborted, no bounce phase, not destroyed
+        description.pack([false, true, false, false]).unwrap();
+
+        let description = parse_description(&transaction(description.into_cell()))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            description.compute,
+            Some(ComputePhase::Skipped(ComputeSkipReason::NoGas))
+        );
+        assert!(description.aborted);
+    }
+
+    #[test]
+    fn storage_transaction() {
+        let mut description = Cell::builder();
+        description.pack_as::<_, NBits<4>>(0b0001u8).unwrap();
+        grams(&mut description, 10);
+        // no fees due, status unchanged
+        description.pack([false, false]).unwrap();
+
+        let description = parse_description(&transaction(description.into_cell()))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(description.storage_fees, Some(BigUint::from(10u32)));
+        assert_eq!(description.compute, None);
+    }
+
+    #[test]
+    fn split_and_merge_have_no_description() {
+        let mut split_prepare = Cell::builder();
+        split_prepare.pack_as::<_, NBits<4>>(0b0100u8).unwrap();
+
+        assert_eq!(
+            parse_description(&transaction(split_prepare.into_cell())).unwrap(),
+            None
+        );
+        assert!(parse_description(&Cell::new()).is_err());
+    }
+}
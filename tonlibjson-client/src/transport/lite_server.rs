@@ -0,0 +1,909 @@
+use crate::account_cell::parse_account;
+use crate::address::{AccountAddressData, ShardContextAccountAddress};
+use crate::block::{
+    AccountAddress, BlocksAccountTransactionId, BlocksHeader, BlocksMasterchainInfo,
+    BlocksShortTxId, BlocksTransactions, InternalTransactionId, MsgBoxedData, MsgDataEncryptedText,
+    MsgDataRaw, MsgDataText, RawFullAccountState, RawMessage, RawTransaction, RawTransactions,
+    TonBlockIdExt,
+};
+use crate::shard;
+use crate::transaction::parse_description_cell;
+use crate::transport::{AccountStateProofs, LiteServerTransport};
+use anyhow::anyhow;
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use futures::future::BoxFuture;
+use futures::{try_join, FutureExt, Stream, StreamExt, TryFutureExt};
+use num_bigint::BigUint;
+use sha2::{Digest, Sha256};
+use std::net::SocketAddrV4;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::time::Duration;
+use ton_client_util::discover::config::LiteServerId;
+use ton_client_util::discover::LiteServerDiscover;
+use ton_client_util::router::balance::Balance;
+use ton_client_util::service::shared::SharedService;
+use ton_client_util::service::timeout::Timeout;
+use ton_liteserver_client::client::{Error, LiteServerClient};
+use ton_liteserver_client::make::MakeClient;
+use ton_liteserver_client::request::Requestable;
+use ton_liteserver_client::tl::{
+    BoxedBool, LiteServerAccountId, LiteServerGetAccountState, LiteServerGetBlock,
+    LiteServerGetBlockHeader, LiteServerGetMasterchainInfo, LiteServerGetOneTransaction,
+    LiteServerGetTransactions, LiteServerListBlockTransactions, LiteServerLookupBlock,
+    LiteServerSendMessage, LiteServerTransactionId3, TonNodeBlockId, TonNodeBlockIdExt, True,
+};
+use ton_liteserver_client::tlb::blk_prev_info::BlkPrevInfo;
+use ton_liteserver_client::tlb::block_header::BlockHeader;
+use ton_liteserver_client::tlb::merkle_proof::MerkleProof;
+use ton_liteserver_client::tracked_client::TrackedClient;
+use toner::tlb::bits::bitvec::order::Msb0;
+use toner::tlb::bits::bitvec::vec::BitVec;
+use toner::tlb::bits::de::{unpack_bytes, BitReaderExt};
+use toner::tlb::bits::r#as::NBits;
+use toner::tlb::bits::ser::pack_with;
+use toner::tlb::de::{CellDeserialize, CellParser, CellParserError};
+use toner::tlb::r#as::{EitherInlineOrRef, NoArgs, Ref};
+use toner::tlb::ser::CellSerializeExt;
+use toner::tlb::{Cell, Error as _, StringError};
+use toner::ton::boc::{BagOfCellsArgs, BoC};
+use toner::ton::currency::{CurrencyCollection, Grams};
+use toner::ton::hashmap::HashmapE;
+use toner::ton::state_init::StateInit;
+use toner::ton::MsgAddress;
+use tower::discover::Change;
+use tower::reconnect::Reconnect;
+use tower::timeout::error::Elapsed;
+use tower::util::MapErr;
+use tower::{BoxError, Service, ServiceExt};
+
+/// `raw.getTransactionsV2` of tonlibjson returns up to 16 transactions per page
+const TRANSACTIONS_PAGE_SIZE: usize = 16;
+
+type IntoLiteServerError = fn(Box<dyn std::error::Error + Send + Sync + 'static>) -> Error;
+type LiteServerService =
+    TrackedClient<MapErr<Timeout<SharedService<Reconnect<MakeClient, ()>>>, IntoLiteServerError>>;
+type BoxLiteServerDiscover = Pin<
+    Box<dyn Stream<Item = Result<Change<LiteServerId, LiteServerService>, anyhow::Error>> + Send>,
+>;
+type SharedBalance = SharedService<Balance<LiteServerService, BoxLiteServerDiscover>>;
+
+/// Backend which speaks lite API over ADNL directly, without tonlibjson
+#[derive(Clone)]
+pub struct LiteServerBackend {
+    client: SharedBalance,
+}
+
+impl LiteServerBackend {
+    pub fn new(discover: LiteServerDiscover, timeout: Duration) -> Self {
+        let discover = discover.then(move |change| async move {
+            match change {
+                Ok(Change::Insert(id, config)) => {
+                    let liteserver = config
+                        .liteservers
+                        .first()
+                        .cloned()
+                        .ok_or_else(|| anyhow!("config has no liteservers"))?;
+                    let key: [u8; 32] = STANDARD
+                        .decode(&liteserver.id.key)?
+                        .try_into()
+                        .map_err(|_| anyhow!("invalid liteserver key"))?;
+                    if liteserver.ip.is_none() {
+                        return Err(anyhow!("liteserver without ip is not supported"));
+                    }
+                    let addr: SocketAddrV4 = liteserver.into();
+
+                    let client = Timeout::new(
+                        SharedService::new(Reconnect::new::<LiteServerClient, ()>(
+                            MakeClient::new(addr, key),
+                            (),
+                        )),
+                        timeout,
+                    );
+                    let client = TrackedClient::new(MapErr::new(
+                        client,
+                        into_lite_server_error as IntoLiteServerError,
+                    ));
+
+                    Ok(Change::Insert(id, client))
+                }
+                Ok(Change::Remove(id)) => Ok(Change::Remove(id)),
+                Err(_) => unreachable!(),
+            }
+        });
+
+        Self {
+            client: SharedService::new(Balance::new(discover.boxed())),
+        }
+    }
+
+    fn call<R>(&self, request: R) -> BoxFuture<'static, anyhow::Result<R::Response>>
+    where
+        R: Requestable + 'static,
+        SharedBalance: Service<R, Response = R::Response, Error = BoxError>,
+        <SharedBalance as Service<R>>::Future: Send,
+    {
+        self.client
+            .clone()
+            .oneshot(request)
+            .map_err(|e| anyhow!(e))
+            .boxed()
+    }
+
+    async fn last_block(&self) -> anyhow::Result<TonNodeBlockIdExt> {
+        Ok(self
+            .call(LiteServerGetMasterchainInfo::default())
+            .await?
+            .last)
+    }
+
+    async fn header(&self, id: TonNodeBlockIdExt) -> anyhow::Result<BlocksHeader> {
+        let response = self.call(LiteServerGetBlockHeader::new(id)).await?;
+        let boc: BoC = unpack_bytes(&response.header_proof)?;
+        let proof: MerkleProof = boc
+            .single_root()
+            .ok_or_else(|| anyhow!("header proof must have a single root"))?
+            .parse_fully()?;
+
+        Ok(to_blocks_header(response.id, proof.virtual_root))
+    }
+
+    async fn transaction_hash(
+        &self,
+        block: TonNodeBlockIdExt,
+        account: LiteServerAccountId,
+        lt: i64,
+    ) -> anyhow::Result<[u8; 32]> {
+        let response = self
+            .call(LiteServerGetOneTransaction {
+                id: block,
+                account,
+                lt,
+            })
+            .await?;
+        let boc: BoC = unpack_bytes(&response.transaction)?;
+
+        Ok(boc
+            .single_root()
+            .ok_or_else(|| anyhow!("transaction must have a single root"))?
+            .hash())
+    }
+}
+
+#[async_trait]
+impl LiteServerTransport for LiteServerBackend {
+    async fn get_masterchain_info(&self) -> anyhow::Result<BlocksMasterchainInfo> {
+        let info = self.call(LiteServerGetMasterchainInfo::default()).await?;
+
+        Ok(BlocksMasterchainInfo {
+            last: to_block_id(info.last),
+            state_root_hash: STANDARD.encode(info.state_root_hash),
+            init: TonBlockIdExt {
+                workchain: info.init.workchain,
+                shard: 0,
+                seqno: 0,
+                root_hash: STANDARD.encode(info.init.root_hash),
+                file_hash: STANDARD.encode(info.init.file_hash),
+            },
+        })
+    }
+
+    async fn look_up_block_by_seqno(
+        &self,
+        chain: i32,
+        shard: i64,
+        seqno: i32,
+    ) -> anyhow::Result<TonBlockIdExt> {
+        if seqno <= 0 {
+            return Err(anyhow!("seqno must be greater than 0"));
+        }
+
+        let header = self
+            .call(LiteServerLookupBlock::seqno(TonNodeBlockId::new(
+                chain, shard, seqno,
+            )))
+            .await?;
+
+        Ok(to_block_id(header.id))
+    }
+
+    async fn look_up_block_by_lt(
+        &self,
+        chain: i32,
+        shard: i64,
+        lt: i64,
+    ) -> anyhow::Result<TonBlockIdExt> {
+        if lt <= 0 {
+            return Err(anyhow!("lt must be greater than 0"));
+        }
+
+        let header = self
+            .call(LiteServerLookupBlock {
+                mode: 2,
+                id: TonNodeBlockId::new(chain, shard, 0),
+                lt: Some(lt),
+                utime: None,
+            })
+            .await?;
+
+        Ok(to_block_id(header.id))
+    }
+
+    async fn get_block_header(
+        &self,
+        workchain: i32,
+        shard: i64,
+        seqno: i32,
+        hashes: Option<(String, String)>,
+    ) -> anyhow::Result<BlocksHeader> {
+        let id = match hashes {
+            Some((root_hash, file_hash)) => TonBlockIdExt {
+                workchain,
+                shard,
+                seqno,
+                root_hash,
+                file_hash,
+            },
+            None => self.look_up_block_by_seqno(workchain, shard, seqno).await?,
+        };
+
+        self.header(from_block_id(&id)?).await
+    }
+
+    async fn blocks_get_transactions(
+        &self,
+        block: &TonBlockIdExt,
+        tx: Option<BlocksAccountTransactionId>,
+        reverse: bool,
+        count: i32,
+    ) -> anyhow::Result<BlocksTransactions> {
+        let after = tx
+            .map(|tx| {
+                anyhow::Ok(LiteServerTransactionId3 {
+                    account: decode_hash(&tx.account)?,
+                    lt: tx.lt,
+                })
+            })
+            .transpose()?;
+        // account, lt and hash of every transaction
+        let mut mode = 0b111;
+        if after.is_some() {
+            mode |= 1 << 7;
+        }
+        if reverse {
+            mode |= 1 << 6;
+        }
+
+        let response = self
+            .call(LiteServerListBlockTransactions {
+                id: from_block_id(block)?,
+                mode,
+                count,
+                after,
+                reverse_order: reverse.then_some(True {}),
+                want_proof: None,
+            })
+            .await?;
+
+        Ok(BlocksTransactions {
+            id: to_block_id(response.id),
+            req_count: response.req_count,
+            incomplete: matches!(response.incomplete, BoxedBool::BoolTrue(_)),
+            transactions: response
+                .ids
+                .into_iter()
+                .map(|id| BlocksShortTxId {
+                    mode: id.mode,
+                    workchain: block.workchain,
+                    account: ShardContextAccountAddress {
+                        bytes: id.account.unwrap_or_default(),
+                    },
+                    lt: id.lt.unwrap_or_default(),
+                    hash: STANDARD.encode(id.hash.unwrap_or_default()),
+                })
+                .collect(),
+        })
+    }
+
+    async fn raw_get_account_state(&self, address: &str) -> anyhow::Result<RawFullAccountState> {
+        let address = AccountAddressData::from_str(address)?;
+        let account = LiteServerAccountId {
+            workchain: address.chain_id,
+            id: address.bytes,
+        };
+
+        let last = self.last_block().await?;
+        let response = self
+            .call(LiteServerGetAccountState {
+                id: last,
+                account: account.clone(),
+            })
+            .await?;
+
+        let state = if response.state.is_empty() {
+            None
+        } else {
+            let boc: BoC = unpack_bytes(&response.state)?;
+            let root = boc
+                .single_root()
+                .ok_or_else(|| anyhow!("account state must have a single root"))?;
+
+            parse_account(root)?
+        };
+
+        let header = self.header(response.shardblk.clone());
+        let last_transaction_id = async {
+            match state.as_ref().map(|state| state.last_trans_lt) {
+                Some(lt) if lt > 0 => {
+                    let hash = self
+                        .transaction_hash(response.shardblk.clone(), account.clone(), lt as i64)
+                        .await?;
+
+                    anyhow::Ok(Some(InternalTransactionId {
+                        lt: lt as i64,
+                        hash: STANDARD.encode(hash),
+                    }))
+                }
+                _ => Ok(None),
+            }
+        };
+        let (header, last_transaction_id) = try_join!(header, last_transaction_id)?;
+
+        // extra currencies are not decoded yet
+        let Some(state) = state else {
+            return Ok(RawFullAccountState {
+                balance: None,
+                code: String::new(),
+                data: String::new(),
+                last_transaction_id,
+                block_id: to_block_id(response.shardblk),
+                frozen_hash: String::new(),
+                sync_utime: header.gen_utime,
+                unknown: Default::default(),
+            });
+        };
+
+        Ok(RawFullAccountState {
+            balance: Some(
+                i64::try_from(&state.balance.grams).map_err(|_| anyhow!("balance overflow"))?,
+            ),
+            code: state.code.map(encode_boc).transpose()?.unwrap_or_default(),
+            data: state.data.map(encode_boc).transpose()?.unwrap_or_default(),
+            last_transaction_id,
+            block_id: to_block_id(response.shardblk),
+            frozen_hash: state
+                .frozen_hash
+                .map(|hash| STANDARD.encode(hash))
+                .unwrap_or_default(),
+            sync_utime: header.gen_utime,
+            unknown: Default::default(),
+        })
+    }
+
+    /// Transactions are fetched one by one, multi-root BoC isn't supported by `toner` yet
+    async fn raw_get_transactions(
+        &self,
+        address: &str,
+        from_tx: &InternalTransactionId,
+    ) -> anyhow::Result<RawTransactions> {
+        let address = AccountAddressData::from_str(address)?;
+        let account = LiteServerAccountId {
+            workchain: address.chain_id,
+            id: address.bytes,
+        };
+
+        let mut transactions = Vec::with_capacity(TRANSACTIONS_PAGE_SIZE);
+        let mut next = Some((from_tx.lt, decode_hash(&from_tx.hash)?));
+        while let Some((lt, hash)) = next {
+            if transactions.len() == TRANSACTIONS_PAGE_SIZE {
+                break;
+            }
+
+            let response = self
+                .call(LiteServerGetTransactions {
+                    count: 1,
+                    account: account.clone(),
+                    lt,
+                    hash,
+                })
+                .await?;
+            let boc: BoC = unpack_bytes(&response.transactions)?;
+            let root = boc
+                .single_root()
+                .ok_or_else(|| anyhow!("transaction must have a single root"))?;
+            let transaction: Transaction = root.parse_fully()?;
+
+            next = (transaction.prev_trans_lt > 0).then_some((
+                transaction.prev_trans_lt as i64,
+                transaction.prev_trans_hash,
+            ));
+            transactions.push(to_raw_transaction(address.chain_id, root, transaction)?);
+        }
+
+        Ok(RawTransactions {
+            transactions,
+            previous_transaction_id: next.map(|(lt, hash)| InternalTransactionId {
+                lt,
+                hash: STANDARD.encode(hash),
+            }),
+        })
+    }
+
+    async fn send_message(&self, message: &str) -> anyhow::Result<()> {
+        self.call(LiteServerSendMessage {
+            body: STANDARD.decode(message)?,
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_account_state_proofs(
+        &self,
+        address: &str,
+        block: &TonBlockIdExt,
+    ) -> anyhow::Result<AccountStateProofs> {
+        let address = AccountAddressData::from_str(address)?;
+        let response = self
+            .call(LiteServerGetAccountState {
+                id: from_block_id(block)?,
+                account: LiteServerAccountId {
+                    workchain: address.chain_id,
+                    id: address.bytes,
+                },
+            })
+            .await?;
+
+        Ok(AccountStateProofs {
+            block_id: to_block_id(response.id),
+            shard_block_id: to_block_id(response.shardblk),
+            shard_proof: STANDARD.encode(response.shard_proof),
+            proof: STANDARD.encode(response.proof),
+        })
+    }
+
+    async fn get_block_data(&self, block: &TonBlockIdExt) -> anyhow::Result<Vec<u8>> {
+        let id = from_block_id(block)?;
+        let response = self.call(LiteServerGetBlock::new(id.clone())).await?;
+        if response.id != id {
+            return Err(anyhow!("lite server returned another block"));
+        }
+        if Sha256::digest(&response.data).as_slice() != id.file_hash {
+            return Err(anyhow!("block data doesn't match file hash"));
+        }
+
+        Ok(response.data)
+    }
+}
+
+fn into_lite_server_error(error: BoxError) -> Error {
+    match error.downcast::<Error>() {
+        Ok(error) => *error,
+        Err(error) if error.is::<Elapsed>() => Error::Elapsed,
+        Err(error) => Error::Connection(error.to_string()),
+    }
+}
+
+fn to_block_id(id: TonNodeBlockIdExt) -> TonBlockIdExt {
+    TonBlockIdExt {
+        workchain: id.workchain,
+        shard: id.shard,
+        seqno: id.seqno,
+        root_hash: STANDARD.encode(id.root_hash),
+        file_hash: STANDARD.encode(id.file_hash),
+    }
+}
+
+fn from_block_id(id: &TonBlockIdExt) -> anyhow::Result<TonNodeBlockIdExt> {
+    Ok(TonNodeBlockIdExt {
+        workchain: id.workchain,
+        shard: id.shard,
+        seqno: id.seqno,
+        root_hash: decode_hash(&id.root_hash)?,
+        file_hash: decode_hash(&id.file_hash)?,
+    })
+}
+
+fn decode_hash(hash: &str) -> anyhow::Result<[u8; 32]> {
+    STANDARD
+        .decode(hash)?
+        .try_into()
+        .map_err(|_| anyhow!("invalid hash length: {}", hash))
+}
+
+fn encode_boc(cell: Cell) -> anyhow::Result<String> {
+    let bytes = pack_with(
+        BoC::from_root(cell),
+        BagOfCellsArgs {
+            has_idx: false,
+            has_crc32c: true,
+        },
+    )?;
+
+    Ok(STANDARD.encode(bytes.as_raw_slice()))
+}
+
+fn to_blocks_header(id: TonNodeBlockIdExt, header: BlockHeader) -> BlocksHeader {
+    let info = header.info;
+    let flag = |bit: u16| info.flags & (1 << bit) != 0;
+    let after_merge = flag(14);
+    let after_split = flag(12);
+
+    let shard = (info.shard.shard_prefix | (1 << (63 - info.shard.shard_pfx_bits))) as i64;
+    let prev_block =
+        |shard, prev: &ton_liteserver_client::tlb::ext_blk_ref::ExtBlkRef| TonBlockIdExt {
+            workchain: info.shard.workchain_id,
+            shard,
+            seqno: prev.seq_no as i32,
+            root_hash: STANDARD.encode(prev.root_hash),
+            file_hash: STANDARD.encode(prev.file_hash),
+        };
+    let prev_blocks = match &info.prev_ref {
+        // a block after a split isn't of the root, one after a merge isn't of a 63 bits prefix
+        BlkPrevInfo::Ref(prev) if after_split => {
+            vec![prev_block(shard::parent(shard).unwrap_or(shard), prev)]
+        }
+        BlkPrevInfo::Ref(prev) => vec![prev_block(shard, prev)],
+        BlkPrevInfo::RefPair(left, right) => {
+            let (left_shard, right_shard) = shard::children(shard).unwrap_or((shard, shard));
+
+            vec![prev_block(left_shard, left), prev_block(right_shard, right)]
+        }
+    };
+
+    BlocksHeader {
+        id: to_block_id(id),
+        global_id: header.global_id,
+        version: info.version as i32,
+        flags: (info.flags & 0xff) as i32,
+        after_merge,
+        after_split,
+        before_split: flag(13),
+        want_merge: flag(10),
+        want_split: flag(11),
+        validator_list_hash_short: info.gen_validator_list_hash_short as i32,
+        catchain_seqno: info.gen_catchain_seqno as i32,
+        min_ref_mc_seqno: info.min_ref_mc_seqno as i32,
+        is_key_block: flag(9),
+        prev_key_block_seqno: info.prev_key_block_seqno as i32,
+        start_lt: info.start_lt as i64,
+        end_lt: info.end_lt as i64,
+        gen_utime: info.gen_utime as i64,
+        vert_seqno: info.vert_seq_no as i32,
+        prev_blocks,
+    }
+}
+
+/// ```tlb
+/// transaction$0111 account_addr:bits256 lt:uint64
+///   prev_trans_hash:bits256 prev_trans_lt:uint64 now:uint32
+///   outmsg_cnt:uint15
+///   orig_status:AccountStatus end_status:AccountStatus
+///   ^[ in_msg:(Maybe ^(Message Any)) out_msgs:(HashmapE 15 ^(Message Any)) ]
+///   total_fees:CurrencyCollection state_update:^(HASH_UPDATE Account)
+///   description:^TransactionDescr = Transaction;
+/// ```
+struct Transaction {
+    account_addr: [u8; 32],
+    lt: u64,
+    prev_trans_hash: [u8; 32],
+    prev_trans_lt: u64,
+    now: u32,
+    in_msg: Option<Message>,
+    out_msgs: Vec<Message>,
+    total_fees: CurrencyCollection,
+    storage_fees: BigUint,
+}
+
+impl<'de> CellDeserialize<'de> for Transaction {
+    fn parse(parser: &mut CellParser<'de>) -> Result<Self, CellParserError<'de>> {
+        let tag: u8 = parser.unpack_as::<_, NBits<4>>()?;
+        if tag != 0b0111 {
+            return Err(StringError::custom(format!(
+                "unsupported transaction tag: {tag:#b}"
+            )));
+        }
+
+        let account_addr = parser.unpack()?;
+        let lt = parser.unpack()?;
+        let prev_trans_hash = parser.unpack()?;
+        let prev_trans_lt = parser.unpack()?;
+        let now = parser.unpack()?;
+        let _outmsg_cnt: u16 = parser.unpack_as::<_, NBits<15>>()?;
+        let _statuses: u8 = parser.unpack_as::<_, NBits<4>>()?;
+
+        let messages: Cell = parser.parse_as::<_, Ref>()?;
+        let mut messages = messages.parser();
+        let in_msg = messages.parse_as::<_, Option<Ref>>()?;
+        let mut out_msgs = messages
+            .parse_as_with::<Vec<(BitVec<u8, Msb0>, Message)>, HashmapE<Ref<NoArgs<_>>, ()>>((
+                15,
+                (),
+            ))?;
+        out_msgs.sort_by(|(lhs, _), (rhs, _)| lhs.cmp(rhs));
+
+        let total_fees = parser.parse()?;
+        let _state_update: Cell = parser.parse_as::<_, Ref>()?;
+        let description: Cell = parser.parse_as::<_, Ref>()?;
+        // storage fees are collected by ordinary, storage and tick-tock transactions only
+        let storage_fees = parse_description_cell(&description)
+            .map_err(|e| StringError::custom(e.to_string()))?
+            .and_then(|description| description.storage_fees)
+            .unwrap_or_default();
+
+        Ok(Self {
+            account_addr,
+            lt,
+            prev_trans_hash,
+            prev_trans_lt,
+            now,
+            in_msg,
+            out_msgs: out_msgs.into_iter().map(|(_, msg)| msg).collect(),
+            total_fees,
+            storage_fees,
+        })
+    }
+}
+
+/// `Message Any` with external addresses kept as `None`
+/// ```tlb
+/// int_msg_info$0 ihr_disabled:Bool bounce:Bool bounced:Bool
+///   src:MsgAddressInt dest:MsgAddressInt
+///   value:CurrencyCollection ihr_fee:Grams fwd_fee:Grams
+///   created_lt:uint64 created_at:uint32 = CommonMsgInfo;
+/// ext_in_msg_info$10 src:MsgAddressExt dest:MsgAddressInt
+///   import_fee:Grams = CommonMsgInfo;
+/// ext_out_msg_info$11 src:MsgAddressInt dest:MsgAddressExt
+///   created_lt:uint64 created_at:uint32 = CommonMsgInfo;
+///
+/// message$_ {X:Type} info:CommonMsgInfo
+///   init:(Maybe (Either StateInit ^StateInit))
+///   body:(Either X ^X) = Message X;
+/// ```
+struct Message {
+    src: Option<MsgAddress>,
+    dst: Option<MsgAddress>,
+    value: BigUint,
+    ihr_fee: BigUint,
+    fwd_fee: BigUint,
+    created_lt: u64,
+    init: Option<StateInit>,
+    body: Cell,
+}
+
+impl<'de> CellDeserialize<'de> for Message {
+    fn parse(parser: &mut CellParser<'de>) -> Result<Self, CellParserError<'de>> {
+        let mut message = if !parser.unpack::<bool>()? {
+            let _ihr_disabled: bool = parser.unpack()?;
+            let _bounce: bool = parser.unpack()?;
+            let _bounced: bool = parser.unpack()?;
+            let src = parser.unpack()?;
+            let dst = parser.unpack()?;
+            let value: CurrencyCollection = parser.parse()?;
+            let ihr_fee = parser.unpack_as::<_, Grams>()?;
+            let fwd_fee = parser.unpack_as::<_, Grams>()?;
+            let created_lt = parser.unpack()?;
+            let _created_at: u32 = parser.unpack()?;
+
+            Self {
+                src: Some(src),
+                dst: Some(dst),
+                value: value.grams,
+                ihr_fee,
+                fwd_fee,
+                created_lt,
+                init: None,
+                body: Cell::default(),
+            }
+        } else if !parser.unpack::<bool>()? {
+            skip_address_ext(parser)?;
+            let dst = parser.unpack()?;
+            let _import_fee: BigUint = parser.unpack_as::<_, Grams>()?;
+
+            Self {
+                src: None,
+                dst: Some(dst),
+                value: BigUint::ZERO,
+                ihr_fee: BigUint::ZERO,
+                fwd_fee: BigUint::ZERO,
+                created_lt: 0,
+                init: None,
+                body: Cell::default(),
+            }
+        } else {
+            let src = parser.unpack()?;
+            skip_address_ext(parser)?;
+            let created_lt = parser.unpack()?;
+            let _created_at: u32 = parser.unpack()?;
+
+            Self {
+                src: Some(src),
+                dst: None,
+                value: BigUint::ZERO,
+                ihr_fee: BigUint::ZERO,
+                fwd_fee: BigUint::ZERO,
+                created_lt,
+                init: None,
+                body: Cell::default(),
+            }
+        };
+
+        message.init = parser.parse_as::<_, Option<EitherInlineOrRef>>()?;
+        message.body = parser.parse_as::<_, EitherInlineOrRef>()?;
+
+        Ok(message)
+    }
+}
+
+/// ```tlb
+/// addr_none$00 = MsgAddressExt;
+/// addr_extern$01 len:(## 9) external_address:(bits len) = MsgAddressExt;
+/// ```
+fn skip_address_ext<'de>(parser: &mut CellParser<'de>) -> Result<(), CellParserError<'de>> {
+    match parser.unpack_as::<u8, NBits<2>>()? {
+        0b00 => Ok(()),
+        0b01 => {
+            let len: u16 = parser.unpack_as::<_, NBits<9>>()?;
+            for _ in 0..len {
+                let _: bool = parser.unpack()?;
+            }
+
+            Ok(())
+        }
+        tag => Err(StringError::custom(format!(
+            "unsupported external address tag: {tag:#b}"
+        ))),
+    }
+}
+
+fn to_account_address(address: Option<MsgAddress>) -> AccountAddress {
+    AccountAddress {
+        account_address: address.filter(|a| !a.is_null()).map(|a| {
+            AccountAddressData {
+                chain_id: a.workchain_id,
+                bytes: a.address,
+                flags: None,
+            }
+            .bounceable()
+            .to_string()
+        }),
+    }
+}
+
+fn to_raw_transaction(
+    chain_id: i32,
+    root: &Cell,
+    transaction: Transaction,
+) -> anyhow::Result<RawTransaction> {
+    let fee = i64::try_from(&transaction.total_fees.grams).map_err(|_| anyhow!("fee overflow"))?;
+    let storage_fee =
+        i64::try_from(&transaction.storage_fees).map_err(|_| anyhow!("storage fee overflow"))?;
+
+    Ok(RawTransaction {
+        address: to_account_address(Some(MsgAddress {
+            workchain_id: chain_id,
+            address: transaction.account_addr,
+        })),
+        utime: transaction.now as i64,
+        data: encode_boc(root.clone())?,
+        transaction_id: InternalTransactionId {
+            lt: transaction.lt as i64,
+            hash: STANDARD.encode(root.hash()),
+        },
+        fee,
+        storage_fee,
+        other_fee: fee - storage_fee,
+        in_msg: transaction.in_msg.map(to_raw_message).transpose()?,
+        out_msgs: transaction
+            .out_msgs
+            .into_iter()
+            .map(to_raw_message)
+            .collect::<anyhow::Result<_>>()?,
+        unknown: Default::default(),
+    })
+}
+
+const TEXT_COMMENT_OP: u32 = 0;
+const ENCRYPTED_COMMENT_OP: u32 = 0x2167da4b;
+
+fn to_raw_message(message: Message) -> anyhow::Result<RawMessage> {
+    let to_i64 = |value: BigUint| i64::try_from(value).map_err(|_| anyhow!("value overflow"));
+
+    let body_hash = STANDARD.encode(message.body.hash());
+    let msg_data = match read_comment(&message.body) {
+        Some((TEXT_COMMENT_OP, text)) => MsgBoxedData::MsgDataText(MsgDataText {
+            text: STANDARD.encode(text),
+        }),
+        Some((ENCRYPTED_COMMENT_OP, text)) => {
+            MsgBoxedData::MsgDataEncryptedText(MsgDataEncryptedText {
+                text: STANDARD.encode(text),
+            })
+        }
+        _ => MsgBoxedData::MsgDataRaw(MsgDataRaw {
+            body: encode_boc(message.body)?,
+            init_state: message
+                .init
+                .map(|init| encode_boc(init.to_cell()?))
+                .transpose()?
+                .unwrap_or_default(),
+        }),
+    };
+
+    Ok(RawMessage {
+        source: to_account_address(message.src),
+        destination: to_account_address(message.dst),
+        value: to_i64(message.value)?,
+        fwd_fee: to_i64(message.fwd_fee)?,
+        ihr_fee: to_i64(message.ihr_fee)?,
+        created_lt: message.created_lt as i64,
+        body_hash,
+        msg_data,
+        unknown: Default::default(),
+    })
+}
+
+/// Reads a snake encoded comment prefixed with `op`, same as tonlib does
+fn read_comment(body: &Cell) -> Option<(u32, Vec<u8>)> {
+    let mut parser = body.parser();
+    let op: u32 = parser.unpack().ok()?;
+    if op != TEXT_COMMENT_OP && op != ENCRYPTED_COMMENT_OP {
+        return None;
+    }
+
+    let mut text = Vec::new();
+    let mut cell = body;
+    let mut skip = 32;
+    loop {
+        if (cell.data.len() - skip) % 8 != 0 || cell.references.len() > 1 {
+            return None;
+        }
+        text.extend(cell.data[skip..].chunks(8).map(|byte| {
+            byte.iter()
+                .fold(0_u8, |acc, bit| (acc << 1) | u8::from(*bit))
+        }));
+
+        match cell.references.first() {
+            Some(next) => {
+                cell = next;
+                skip = 0;
+            }
+            None => break,
+        }
+    }
+
+    Some((op, text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use toner::tlb::bits::ser::BitWriterExt;
+
+    #[test]
+    fn read_text_comment() {
+        let mut tail = Cell::builder();
+        tail.pack(*b"world").unwrap();
+        let mut body = Cell::builder();
+        body.pack(TEXT_COMMENT_OP)
+            .unwrap()
+            .pack(*b"hello ")
+            .unwrap()
+            .store_as::<_, Ref>(tail.into_cell())
+            .unwrap();
+
+        let (op, text) = read_comment(&body.into_cell()).unwrap();
+
+        assert_eq!(op, TEXT_COMMENT_OP);
+        assert_eq!(text, b"hello world");
+    }
+
+    #[test]
+    fn read_comment_of_other_op() {
+        let mut body = Cell::builder();
+        body.pack(0x0f8a7ea5_u32).unwrap();
+
+        assert!(read_comment(&body.into_cell()).is_none());
+    }
+}
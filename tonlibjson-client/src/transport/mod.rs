@@ -0,0 +1,182 @@
+#[cfg(feature = "liteserver")]
+pub mod lite_server;
+pub mod replay;
+
+use crate::block::{
+    BlocksAccountTransactionId, BlocksHeader, BlocksMasterchainInfo, BlocksTransactions,
+    InternalTransactionId, RawFullAccountState, RawTransactions, TonBlockIdExt,
+};
+use crate::ton::TonClient;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Selects the implementation behind [`LiteServerTransport`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Backend {
+    /// tonlibjson C++ library
+    #[default]
+    Tonlibjson,
+    /// pure Rust ADNL client speaking lite API directly
+    #[cfg(feature = "liteserver")]
+    LiteServer,
+    /// responses of a fixture file recorded with [`crate::ton::TonClientBuilder::set_record_fixture`],
+    /// served to the transport and to the tonlib of every lite server of a [`crate::ton::TonClient`]
+    Replay(PathBuf),
+}
+
+impl FromStr for Backend {
+    type Err = anyhow::Error;
+
+    /// `tonlibjson` or `liteserver`, fixtures are replayed by path only
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tonlibjson" => Ok(Self::Tonlibjson),
+            #[cfg(feature = "liteserver")]
+            "liteserver" => Ok(Self::LiteServer),
+            _ => anyhow::bail!("unknown backend {}", s),
+        }
+    }
+}
+
+/// Proofs of an account state, all BoCs are base64 encoded
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AccountStateProofs {
+    /// block the proofs are anchored to
+    pub block_id: TonBlockIdExt,
+    /// shard block containing the account
+    pub shard_block_id: TonBlockIdExt,
+    /// proof of `shard_block_id` in `block_id`, empty if they are the same block
+    pub shard_proof: String,
+    /// proof of the account state in `shard_block_id`
+    pub proof: String,
+}
+
+/// Core queries every backend has to serve, responses keep the tonlibjson shape
+#[async_trait]
+pub trait LiteServerTransport: Send + Sync {
+    async fn get_masterchain_info(&self) -> anyhow::Result<BlocksMasterchainInfo>;
+
+    async fn look_up_block_by_seqno(
+        &self,
+        chain: i32,
+        shard: i64,
+        seqno: i32,
+    ) -> anyhow::Result<TonBlockIdExt>;
+
+    async fn look_up_block_by_lt(
+        &self,
+        chain: i32,
+        shard: i64,
+        lt: i64,
+    ) -> anyhow::Result<TonBlockIdExt>;
+
+    async fn get_block_header(
+        &self,
+        workchain: i32,
+        shard: i64,
+        seqno: i32,
+        hashes: Option<(String, String)>,
+    ) -> anyhow::Result<BlocksHeader>;
+
+    async fn blocks_get_transactions(
+        &self,
+        block: &TonBlockIdExt,
+        tx: Option<BlocksAccountTransactionId>,
+        reverse: bool,
+        count: i32,
+    ) -> anyhow::Result<BlocksTransactions>;
+
+    async fn raw_get_account_state(&self, address: &str) -> anyhow::Result<RawFullAccountState>;
+
+    async fn raw_get_transactions(
+        &self,
+        address: &str,
+        from_tx: &InternalTransactionId,
+    ) -> anyhow::Result<RawTransactions>;
+
+    async fn send_message(&self, message: &str) -> anyhow::Result<()>;
+
+    async fn get_account_state_proofs(
+        &self,
+        address: &str,
+        block: &TonBlockIdExt,
+    ) -> anyhow::Result<AccountStateProofs>;
+
+    /// Serialized block as a bag of cells
+    async fn get_block_data(&self, block: &TonBlockIdExt) -> anyhow::Result<Vec<u8>>;
+}
+
+#[async_trait]
+impl LiteServerTransport for TonClient {
+    async fn get_masterchain_info(&self) -> anyhow::Result<BlocksMasterchainInfo> {
+        TonClient::get_masterchain_info(self).await
+    }
+
+    async fn look_up_block_by_seqno(
+        &self,
+        chain: i32,
+        shard: i64,
+        seqno: i32,
+    ) -> anyhow::Result<TonBlockIdExt> {
+        TonClient::look_up_block_by_seqno(self, chain, shard, seqno).await
+    }
+
+    async fn look_up_block_by_lt(
+        &self,
+        chain: i32,
+        shard: i64,
+        lt: i64,
+    ) -> anyhow::Result<TonBlockIdExt> {
+        TonClient::look_up_block_by_lt(self, chain, shard, lt).await
+    }
+
+    async fn get_block_header(
+        &self,
+        workchain: i32,
+        shard: i64,
+        seqno: i32,
+        hashes: Option<(String, String)>,
+    ) -> anyhow::Result<BlocksHeader> {
+        TonClient::get_block_header(self, workchain, shard, seqno, hashes).await
+    }
+
+    async fn blocks_get_transactions(
+        &self,
+        block: &TonBlockIdExt,
+        tx: Option<BlocksAccountTransactionId>,
+        reverse: bool,
+        count: i32,
+    ) -> anyhow::Result<BlocksTransactions> {
+        TonClient::blocks_get_transactions(self, block, tx, reverse, count).await
+    }
+
+    async fn raw_get_account_state(&self, address: &str) -> anyhow::Result<RawFullAccountState> {
+        TonClient::raw_get_account_state(self, address).await
+    }
+
+    async fn raw_get_transactions(
+        &self,
+        address: &str,
+        from_tx: &InternalTransactionId,
+    ) -> anyhow::Result<RawTransactions> {
+        TonClient::raw_get_transactions(self, address, from_tx).await
+    }
+
+    async fn send_message(&self, message: &str) -> anyhow::Result<()> {
+        TonClient::send_message(self, message).await
+    }
+
+    async fn get_account_state_proofs(
+        &self,
+        _address: &str,
+        _block: &TonBlockIdExt,
+    ) -> anyhow::Result<AccountStateProofs> {
+        anyhow::bail!("account state proofs are not exposed by tonlibjson")
+    }
+
+    async fn get_block_data(&self, _block: &TonBlockIdExt) -> anyhow::Result<Vec<u8>> {
+        anyhow::bail!("block data is not exposed by tonlibjson")
+    }
+}
@@ -0,0 +1,149 @@
+use crate::block::{
+    AccountAddress, BlocksAccountTransactionId, BlocksGetBlockHeader, BlocksGetMasterchainInfo,
+    BlocksGetTransactions, BlocksHeader, BlocksLookupBlock, BlocksMasterchainInfo,
+    BlocksTransactions, InternalTransactionId, RawFullAccountState, RawGetAccountState,
+    RawGetTransactionsV2, RawSendMessage, RawTransactions, TonBlockId, TonBlockIdExt, TonError,
+};
+use crate::fixture::Fixture;
+use crate::request::Requestable;
+use crate::transport::{AccountStateProofs, LiteServerTransport};
+use anyhow::anyhow;
+use async_trait::async_trait;
+
+/// Serves responses of a [`Fixture`] to the same requests [`crate::ton::TonClient`] makes
+pub struct Replay {
+    fixture: Fixture,
+}
+
+impl Replay {
+    pub fn new(fixture: Fixture) -> Self {
+        Self { fixture }
+    }
+
+    fn call<R: Requestable>(&self, request: R) -> anyhow::Result<R::Response> {
+        let request = serde_json::to_value(&request)?;
+        let response = self
+            .fixture
+            .response(&request)
+            .ok_or_else(|| anyhow!("no fixture for {}", request))?;
+
+        if response["@type"] == "error" {
+            return Err(serde_json::from_value::<TonError>(response)?.into());
+        }
+
+        Ok(serde_json::from_value(response)?)
+    }
+}
+
+#[async_trait]
+impl LiteServerTransport for Replay {
+    async fn get_masterchain_info(&self) -> anyhow::Result<BlocksMasterchainInfo> {
+        self.call(BlocksGetMasterchainInfo::default())
+    }
+
+    async fn look_up_block_by_seqno(
+        &self,
+        chain: i32,
+        shard: i64,
+        seqno: i32,
+    ) -> anyhow::Result<TonBlockIdExt> {
+        if seqno <= 0 {
+            return Err(anyhow!("seqno must be greater than 0"));
+        }
+
+        self.call(BlocksLookupBlock::seqno(TonBlockId::new(
+            chain, shard, seqno,
+        )))
+    }
+
+    async fn look_up_block_by_lt(
+        &self,
+        chain: i32,
+        shard: i64,
+        lt: i64,
+    ) -> anyhow::Result<TonBlockIdExt> {
+        if lt <= 0 {
+            return Err(anyhow!("lt must be greater than 0"));
+        }
+
+        self.call(BlocksLookupBlock::logical_time(
+            TonBlockId::new(chain, shard, 0),
+            lt,
+        ))
+    }
+
+    async fn get_block_header(
+        &self,
+        workchain: i32,
+        shard: i64,
+        seqno: i32,
+        hashes: Option<(String, String)>,
+    ) -> anyhow::Result<BlocksHeader> {
+        let (root_hash, file_hash) = match hashes {
+            Some(hashes) => hashes,
+            None => {
+                let block = self.look_up_block_by_seqno(workchain, shard, seqno).await?;
+                (block.root_hash, block.file_hash)
+            }
+        };
+
+        self.call(BlocksGetBlockHeader::new(TonBlockIdExt {
+            workchain,
+            shard,
+            seqno,
+            root_hash,
+            file_hash,
+        }))
+    }
+
+    async fn blocks_get_transactions(
+        &self,
+        block: &TonBlockIdExt,
+        tx: Option<BlocksAccountTransactionId>,
+        reverse: bool,
+        count: i32,
+    ) -> anyhow::Result<BlocksTransactions> {
+        self.call(BlocksGetTransactions::unverified(
+            block.to_owned(),
+            tx,
+            reverse,
+            count,
+        ))
+        .map(BlocksTransactions::with_workchain)
+    }
+
+    async fn raw_get_account_state(&self, address: &str) -> anyhow::Result<RawFullAccountState> {
+        self.call(RawGetAccountState::new(AccountAddress::new(address)?))
+    }
+
+    async fn raw_get_transactions(
+        &self,
+        address: &str,
+        from_tx: &InternalTransactionId,
+    ) -> anyhow::Result<RawTransactions> {
+        self.call(RawGetTransactionsV2::new(
+            AccountAddress::new(address)?,
+            from_tx.clone(),
+            16,
+            false,
+        ))
+    }
+
+    async fn send_message(&self, message: &str) -> anyhow::Result<()> {
+        self.call(RawSendMessage::new(message.to_string()))?;
+
+        Ok(())
+    }
+
+    async fn get_account_state_proofs(
+        &self,
+        _address: &str,
+        _block: &TonBlockIdExt,
+    ) -> anyhow::Result<AccountStateProofs> {
+        anyhow::bail!("account state proofs are not recorded in fixtures")
+    }
+
+    async fn get_block_data(&self, _block: &TonBlockIdExt) -> anyhow::Result<Vec<u8>> {
+        anyhow::bail!("block data is not recorded in fixtures")
+    }
+}
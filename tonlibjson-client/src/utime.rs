@@ -0,0 +1,172 @@
+use crate::block::BlocksHeader;
+use crate::transport::LiteServerTransport;
+
+/// Latest masterchain block with `gen_utime <= utime`, none if `utime` precedes the first block.
+/// Steps back from the last block with doubling distance until a block isn't newer than
+/// `utime`, then binary searches between the last two probes, so recent times stay cheap
+/// and old blocks are only touched when needed.
+pub async fn find_masterchain_block_by_utime(
+    transport: &dyn LiteServerTransport,
+    utime: i64,
+) -> anyhow::Result<Option<BlocksHeader>> {
+    let last = transport.get_masterchain_info().await?.last;
+    let header = |seqno: i32| transport.get_block_header(last.workchain, last.shard, seqno, None);
+
+    let last_header = transport
+        .get_block_header(
+            last.workchain,
+            last.shard,
+            last.seqno,
+            Some((last.root_hash.clone(), last.file_hash.clone())),
+        )
+        .await?;
+    if last_header.gen_utime <= utime {
+        return Ok(Some(last_header));
+    }
+
+    // newer than utime
+    let mut hi = last.seqno;
+    let mut step = 1;
+    let mut lo = loop {
+        let seqno = (last.seqno - step).max(1);
+        let probe = header(seqno).await?;
+        if probe.gen_utime <= utime {
+            break probe;
+        }
+        if seqno == 1 {
+            return Ok(None);
+        }
+
+        hi = seqno;
+        step = step.saturating_mul(2);
+    };
+
+    while hi - lo.id.seqno > 1 {
+        let mid = lo.id.seqno + (hi - lo.id.seqno) / 2;
+        let probe = header(mid).await?;
+        if probe.gen_utime <= utime {
+            lo = probe;
+        } else {
+            hi = mid;
+        }
+    }
+
+    Ok(Some(lo))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::{
+        BlocksGetBlockHeader, BlocksGetMasterchainInfo, BlocksLookupBlock, TonBlockId,
+        TonBlockIdExt,
+    };
+    use crate::fixture::Fixture;
+    use crate::transport::replay::Replay;
+    use serde_json::{json, Value};
+
+    const LAST: i32 = 40;
+
+    fn block(seqno: i32) -> TonBlockIdExt {
+        TonBlockIdExt::new(
+            -1,
+            i64::MIN,
+            seqno,
+            format!("root-{}", seqno),
+            format!("file-{}", seqno),
+        )
+    }
+
+    /// blocks 1..=40 generated every 5 seconds from 1000, blocks 20 and 21 share a second
+    fn gen_utime(seqno: i32) -> i64 {
+        let seqno = if seqno > 20 { seqno - 1 } else { seqno };
+
+        1000 + 5 * (seqno as i64 - 1)
+    }
+
+    fn transport() -> Replay {
+        let mut entries = vec![json!({
+            "timestamp": 0,
+            "request": serde_json::to_value(BlocksGetMasterchainInfo::default()).unwrap(),
+            "response": {
+                "@type": "blocks.masterchainInfo",
+                "last": block(LAST),
+                "state_root_hash": "",
+                "init": block(LAST),
+            }
+        })];
+        for seqno in 1..=LAST {
+            let id = block(seqno);
+            entries.push(json!({
+                "timestamp": 0,
+                "request": serde_json::to_value(BlocksLookupBlock::seqno(TonBlockId::new(-1, i64::MIN, seqno))).unwrap(),
+                "response": id,
+            }));
+            entries.push(json!({
+                "timestamp": 0,
+                "request": serde_json::to_value(BlocksGetBlockHeader::new(id.clone())).unwrap(),
+                "response": {
+                    "@type": "blocks.header",
+                    "id": id,
+                    "global_id": -239,
+                    "version": 0,
+                    "flags": 1,
+                    "after_merge": false,
+                    "after_split": false,
+                    "before_split": false,
+                    "want_merge": false,
+                    "want_split": false,
+                    "validator_list_hash_short": 0,
+                    "catchain_seqno": 0,
+                    "min_ref_mc_seqno": 0,
+                    "is_key_block": false,
+                    "prev_key_block_seqno": 0,
+                    "start_lt": "0",
+                    "end_lt": "0",
+                    "gen_utime": gen_utime(seqno).to_string(),
+                    "vert_seqno": 1,
+                    "prev_blocks": [],
+                }
+            }));
+        }
+
+        let lines: Vec<_> = entries.iter().map(Value::to_string).collect();
+
+        Replay::new(Fixture::from_reader(lines.join("\n").as_bytes()).unwrap())
+    }
+
+    async fn find(utime: i64) -> Option<i32> {
+        find_masterchain_block_by_utime(&transport(), utime)
+            .await
+            .unwrap()
+            .map(|header| header.id.seqno)
+    }
+
+    #[tokio::test]
+    async fn block_generated_at_utime() {
+        for seqno in [1, 2, 7, 19, 22, 39, LAST] {
+            assert_eq!(find(gen_utime(seqno)).await, Some(seqno), "{}", seqno);
+        }
+    }
+
+    #[tokio::test]
+    async fn latest_block_of_a_second() {
+        assert_eq!(find(gen_utime(20)).await, Some(21));
+    }
+
+    #[tokio::test]
+    async fn block_between_utimes() {
+        assert_eq!(find(gen_utime(9) + 4).await, Some(9));
+        assert_eq!(find(gen_utime(9) - 1).await, Some(8));
+    }
+
+    #[tokio::test]
+    async fn utime_before_genesis() {
+        assert_eq!(find(gen_utime(1) - 1).await, None);
+    }
+
+    #[tokio::test]
+    async fn utime_in_the_future() {
+        assert_eq!(find(i64::MAX).await, Some(LAST));
+    }
+}
@@ -0,0 +1,291 @@
+use crate::block::{BlocksSignature, TonBlockIdExt};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use toner::tlb::bits::bitvec::field::BitField;
+use toner::tlb::bits::bitvec::order::Msb0;
+use toner::tlb::bits::bitvec::vec::BitVec;
+use toner::tlb::bits::de::BitReaderExt;
+use toner::tlb::de::{CellDeserialize, CellParser, CellParserError};
+use toner::tlb::r#as::NoArgs;
+use toner::tlb::{Error as _, StringError};
+use toner::ton::hashmap::HashmapE;
+
+/// TL id of `pub.ed25519 key:int256 = PublicKey`
+const PUB_ED25519_TL_ID: u32 = 0x4813b4c6;
+/// TL id of `ton.blockId root_cell_hash:int256 file_hash:int256 = ton.BlockId`
+const TON_BLOCK_ID_TL_ID: u32 = 0xc50b6e70;
+
+#[derive(Debug, thiserror::Error)]
+#[error("block verification failed: {0}")]
+pub struct VerificationError(String);
+
+impl VerificationError {
+    pub(crate) fn new(reason: impl Into<String>) -> Self {
+        Self(reason.into())
+    }
+}
+
+/// ```tlb
+/// ed25519_pubkey#8e81278a pubkey:bits256 = SigPubKey;
+/// validator#53 public_key:SigPubKey weight:uint64 = ValidatorDescr;
+/// validator_addr#73 public_key:SigPubKey weight:uint64 adnl_addr:bits256 = ValidatorDescr;
+/// ```
+#[derive(Debug, Clone)]
+pub struct ValidatorDescr {
+    pub public_key: [u8; 32],
+    pub weight: u64,
+}
+
+impl ValidatorDescr {
+    /// `sha256` of TL serialized `pub.ed25519`
+    pub fn node_id_short(&self) -> [u8; 32] {
+        Sha256::new()
+            .chain_update(PUB_ED25519_TL_ID.to_le_bytes())
+            .chain_update(self.public_key)
+            .finalize()
+            .into()
+    }
+}
+
+impl<'de> CellDeserialize<'de> for ValidatorDescr {
+    fn parse(parser: &mut CellParser<'de>) -> Result<Self, CellParserError<'de>> {
+        let tag: u8 = parser.unpack()?;
+        let key_tag: u32 = parser.unpack()?;
+        if key_tag != 0x8e81278a {
+            return Err(StringError::custom(format!(
+                "unsupported public key tag: {key_tag:x}"
+            )));
+        }
+        let public_key = parser.unpack()?;
+        let weight = parser.unpack()?;
+        match tag {
+            0x53 => {}
+            0x73 => {
+                let _adnl_addr: [u8; 32] = parser.unpack()?;
+            }
+            _ => {
+                return Err(StringError::custom(format!(
+                    "unsupported validator descr tag: {tag:x}"
+                )))
+            }
+        }
+
+        Ok(Self { public_key, weight })
+    }
+}
+
+/// ```tlb
+/// validators#11 utime_since:uint32 utime_until:uint32
+///   total:(## 16) main:(## 16) { main <= total } { main >= 1 }
+///   list:(Hashmap 16 ValidatorDescr) = ValidatorSet;
+/// validators_ext#12 utime_since:uint32 utime_until:uint32
+///   total:(## 16) main:(## 16) { main <= total } { main >= 1 }
+///   total_weight:uint64 list:(HashmapE 16 ValidatorDescr) = ValidatorSet;
+/// ```
+/// NOTE: only `validators_ext` is produced by the current network
+#[derive(Debug, Clone)]
+pub struct ValidatorSet {
+    pub utime_since: u32,
+    pub utime_until: u32,
+    pub main: u16,
+    /// ordered by validator index
+    pub list: Vec<ValidatorDescr>,
+}
+
+impl ValidatorSet {
+    pub fn covers(&self, utime: i64) -> bool {
+        (self.utime_since as i64..self.utime_until as i64).contains(&utime)
+    }
+
+    /// Approximate bytes kept, the validators included
+    pub fn heap_size(&self) -> usize {
+        std::mem::size_of::<Self>() + self.list.capacity() * std::mem::size_of::<ValidatorDescr>()
+    }
+
+    /// Checks that validators of the masterchain subset holding more than 2/3 of the weight signed the block
+    pub fn verify_masterchain_block(
+        &self,
+        block_id: &TonBlockIdExt,
+        signatures: &[BlocksSignature],
+    ) -> Result<(), VerificationError> {
+        let validators: HashMap<[u8; 32], &ValidatorDescr> = self
+            .list
+            .iter()
+            .take(self.main as usize)
+            .map(|v| (v.node_id_short(), v))
+            .collect();
+        let total_weight: u128 = validators.values().map(|v| v.weight as u128).sum();
+
+        let message = signed_message(block_id)?;
+
+        let mut signed: HashMap<[u8; 32], u64> = HashMap::new();
+        for signature in signatures {
+            let node_id = decode_hash(&signature.node_id_short)?;
+            let Some(validator) = validators.get(&node_id) else {
+                return Err(VerificationError::new("signature of unknown validator"));
+            };
+
+            let key = VerifyingKey::from_bytes(&validator.public_key)
+                .map_err(|e| VerificationError::new(e.to_string()))?;
+            let signature = STANDARD
+                .decode(&signature.signature)
+                .ok()
+                .and_then(|s| Signature::from_slice(&s).ok())
+                .ok_or_else(|| VerificationError::new("malformed signature"))?;
+            key.verify(&message, &signature)
+                .map_err(|_| VerificationError::new("invalid signature"))?;
+
+            signed.insert(node_id, validator.weight);
+        }
+
+        let signed_weight: u128 = signed.values().map(|w| *w as u128).sum();
+        if signed_weight * 3 <= total_weight * 2 {
+            return Err(VerificationError::new(format!(
+                "not enough signatures, weight {} of {}",
+                signed_weight, total_weight
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+impl<'de> CellDeserialize<'de> for ValidatorSet {
+    fn parse(parser: &mut CellParser<'de>) -> Result<Self, CellParserError<'de>> {
+        let tag: u8 = parser.unpack()?;
+        if tag != 0x12 {
+            return Err(StringError::custom(format!(
+                "unsupported validator set tag: {tag:x}"
+            )));
+        }
+        let utime_since = parser.unpack()?;
+        let utime_until = parser.unpack()?;
+        let _total: u16 = parser.unpack()?;
+        let main = parser.unpack()?;
+        let _total_weight: u64 = parser.unpack()?;
+        let mut list = parser
+            .parse_as_with::<Vec<(BitVec<u8, Msb0>, ValidatorDescr)>, HashmapE<NoArgs<_>, ()>>((
+                16,
+                (),
+            ))?
+            .into_iter()
+            .map(|(k, v)| (k.load_be::<u16>(), v))
+            .collect::<Vec<_>>();
+        list.sort_by_key(|(k, _)| *k);
+
+        Ok(Self {
+            utime_since,
+            utime_until,
+            main,
+            list: list.into_iter().map(|(_, v)| v).collect(),
+        })
+    }
+}
+
+/// TL serialized `ton.blockId` of `block_id`, what validators sign
+pub(crate) fn signed_message(block_id: &TonBlockIdExt) -> Result<Vec<u8>, VerificationError> {
+    let mut message = TON_BLOCK_ID_TL_ID.to_le_bytes().to_vec();
+    message.extend(decode_hash(&block_id.root_hash)?);
+    message.extend(decode_hash(&block_id.file_hash)?);
+
+    Ok(message)
+}
+
+fn decode_hash(hash: &str) -> Result<[u8; 32], VerificationError> {
+    STANDARD
+        .decode(hash)
+        .ok()
+        .and_then(|h| h.try_into().ok())
+        .ok_or_else(|| VerificationError::new(format!("malformed hash: {hash}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    fn block_id() -> TonBlockIdExt {
+        TonBlockIdExt {
+            workchain: -1,
+            shard: -9223372036854775808,
+            seqno: 42,
+            root_hash: STANDARD.encode([1; 32]),
+            file_hash: STANDARD.encode([2; 32]),
+        }
+    }
+
+    fn sign(key: &SigningKey, block_id: &TonBlockIdExt) -> BlocksSignature {
+        let validator = ValidatorDescr {
+            public_key: key.verifying_key().to_bytes(),
+            weight: 0,
+        };
+        let message = signed_message(block_id).unwrap();
+
+        BlocksSignature {
+            node_id_short: STANDARD.encode(validator.node_id_short()),
+            signature: STANDARD.encode(key.sign(&message).to_bytes()),
+        }
+    }
+
+    fn validator_set(keys: &[SigningKey]) -> ValidatorSet {
+        ValidatorSet {
+            utime_since: 100,
+            utime_until: 200,
+            main: keys.len() as u16,
+            list: keys
+                .iter()
+                .map(|k| ValidatorDescr {
+                    public_key: k.verifying_key().to_bytes(),
+                    weight: 10,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn verify_enough_signatures() {
+        let keys: Vec<_> = (1..=3).map(|i| SigningKey::from_bytes(&[i; 32])).collect();
+        let set = validator_set(&keys);
+        let block_id = block_id();
+        let signatures: Vec<_> = keys.iter().map(|k| sign(k, &block_id)).collect();
+
+        assert!(set.verify_masterchain_block(&block_id, &signatures).is_ok());
+    }
+
+    #[test]
+    fn verify_not_enough_signatures() {
+        let keys: Vec<_> = (1..=3).map(|i| SigningKey::from_bytes(&[i; 32])).collect();
+        let set = validator_set(&keys);
+        let block_id = block_id();
+        let signatures: Vec<_> = keys[..2].iter().map(|k| sign(k, &block_id)).collect();
+
+        assert!(set
+            .verify_masterchain_block(&block_id, &signatures)
+            .is_err());
+    }
+
+    #[test]
+    fn verify_signature_of_other_block() {
+        let keys: Vec<_> = (1..=3).map(|i| SigningKey::from_bytes(&[i; 32])).collect();
+        let set = validator_set(&keys);
+        let mut other = block_id();
+        other.root_hash = STANDARD.encode([3; 32]);
+        let signatures: Vec<_> = keys.iter().map(|k| sign(k, &other)).collect();
+
+        assert!(set
+            .verify_masterchain_block(&block_id(), &signatures)
+            .is_err());
+    }
+
+    #[test]
+    fn validator_set_covers() {
+        let set = validator_set(&[]);
+
+        assert!(set.covers(100));
+        assert!(set.covers(199));
+        assert!(!set.covers(200));
+    }
+}
@@ -0,0 +1,256 @@
+use crate::address::AccountAddressData;
+use crate::block::{
+    BlocksAccountTransactionId, BlocksHeader, BlocksMasterchainInfo, BlocksTransactions,
+    InternalTransactionId, RawFullAccountState, RawTransactions, TonBlockIdExt,
+};
+use crate::error::Error;
+use crate::transport::{AccountStateProofs, LiteServerTransport};
+use async_trait::async_trait;
+use futures::future::{ready, Either, Ready};
+use serde::Serialize;
+use serde_json::Value;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tower::{BoxError, Service};
+
+pub const MASTERCHAIN: i32 = -1;
+
+/// Requests to a workchain are rejected in masterchain only mode,
+/// see [`crate::ton::TonClientBuilder::set_masterchain_only`]
+#[derive(Debug, thiserror::Error)]
+#[error("workchain {0} disabled on this endpoint")]
+pub struct WorkchainDisabled(pub i32);
+
+pub fn is_workchain_disabled(error: &anyhow::Error) -> bool {
+    match error.downcast_ref::<Error>() {
+        Some(Error::Tower(e)) => e.is::<WorkchainDisabled>(),
+        _ => error.is::<WorkchainDisabled>(),
+    }
+}
+
+fn check(workchain: i32) -> anyhow::Result<()> {
+    if workchain != MASTERCHAIN {
+        return Err(WorkchainDisabled(workchain).into());
+    }
+
+    Ok(())
+}
+
+fn check_address(address: &str) -> anyhow::Result<()> {
+    check(AccountAddressData::from_str(address)?.chain_id)
+}
+
+/// The first workchain other than the masterchain among block ids and account addresses of a request
+fn workchain(value: &Value) -> Option<i32> {
+    match value {
+        Value::Object(fields) => fields.iter().find_map(|(key, value)| {
+            let workchain = match (key.as_str(), value) {
+                ("workchain", Value::Number(n)) => n.as_i64().map(|n| n as i32),
+                ("account_address", Value::String(address)) => {
+                    AccountAddressData::from_str(address)
+                        .ok()
+                        .map(|address| address.chain_id)
+                }
+                _ => None,
+            };
+
+            workchain
+                .filter(|workchain| *workchain != MASTERCHAIN)
+                .or_else(|| self::workchain(value))
+        }),
+        Value::Array(values) => values.iter().find_map(workchain),
+        _ => None,
+    }
+}
+
+/// Rejects requests addressing workchains if enabled, before they are routed to a lite server
+#[derive(Clone)]
+pub(crate) struct MasterchainOnly<S> {
+    inner: S,
+    enabled: bool,
+}
+
+impl<S> MasterchainOnly<S> {
+    pub(crate) fn new(inner: S, enabled: bool) -> Self {
+        Self { inner, enabled }
+    }
+}
+
+impl<S, Req> Service<Req> for MasterchainOnly<S>
+where
+    S: Service<Req, Error = BoxError>,
+    Req: Serialize,
+{
+    type Response = S::Response;
+    type Error = BoxError;
+    type Future = Either<Ready<Result<S::Response, BoxError>>, S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        if self.enabled {
+            let workchain = serde_json::to_value(&req).ok().as_ref().and_then(workchain);
+            if let Some(workchain) = workchain {
+                return Either::Left(ready(Err(WorkchainDisabled(workchain).into())));
+            }
+        }
+
+        Either::Right(self.inner.call(req))
+    }
+}
+
+/// [`MasterchainOnly`] of backends which don't go through [`crate::ton::TonClient`]
+pub struct MasterchainOnlyTransport {
+    inner: Arc<dyn LiteServerTransport>,
+}
+
+impl MasterchainOnlyTransport {
+    pub fn new(inner: Arc<dyn LiteServerTransport>) -> Self {
+        Self { inner }
+    }
+}
+
+#[async_trait]
+impl LiteServerTransport for MasterchainOnlyTransport {
+    async fn get_masterchain_info(&self) -> anyhow::Result<BlocksMasterchainInfo> {
+        self.inner.get_masterchain_info().await
+    }
+
+    async fn look_up_block_by_seqno(
+        &self,
+        chain: i32,
+        shard: i64,
+        seqno: i32,
+    ) -> anyhow::Result<TonBlockIdExt> {
+        check(chain)?;
+        self.inner.look_up_block_by_seqno(chain, shard, seqno).await
+    }
+
+    async fn look_up_block_by_lt(
+        &self,
+        chain: i32,
+        shard: i64,
+        lt: i64,
+    ) -> anyhow::Result<TonBlockIdExt> {
+        check(chain)?;
+        self.inner.look_up_block_by_lt(chain, shard, lt).await
+    }
+
+    async fn get_block_header(
+        &self,
+        workchain: i32,
+        shard: i64,
+        seqno: i32,
+        hashes: Option<(String, String)>,
+    ) -> anyhow::Result<BlocksHeader> {
+        check(workchain)?;
+        self.inner
+            .get_block_header(workchain, shard, seqno, hashes)
+            .await
+    }
+
+    async fn blocks_get_transactions(
+        &self,
+        block: &TonBlockIdExt,
+        tx: Option<BlocksAccountTransactionId>,
+        reverse: bool,
+        count: i32,
+    ) -> anyhow::Result<BlocksTransactions> {
+        check(block.workchain)?;
+        self.inner
+            .blocks_get_transactions(block, tx, reverse, count)
+            .await
+    }
+
+    async fn raw_get_account_state(&self, address: &str) -> anyhow::Result<RawFullAccountState> {
+        check_address(address)?;
+        self.inner.raw_get_account_state(address).await
+    }
+
+    async fn raw_get_transactions(
+        &self,
+        address: &str,
+        from_tx: &InternalTransactionId,
+    ) -> anyhow::Result<RawTransactions> {
+        check_address(address)?;
+        self.inner.raw_get_transactions(address, from_tx).await
+    }
+
+    async fn send_message(&self, message: &str) -> anyhow::Result<()> {
+        self.inner.send_message(message).await
+    }
+
+    async fn get_account_state_proofs(
+        &self,
+        address: &str,
+        block: &TonBlockIdExt,
+    ) -> anyhow::Result<AccountStateProofs> {
+        check_address(address)?;
+        check(block.workchain)?;
+        self.inner.get_account_state_proofs(address, block).await
+    }
+
+    async fn get_block_data(&self, block: &TonBlockIdExt) -> anyhow::Result<Vec<u8>> {
+        check(block.workchain)?;
+        self.inner.get_block_data(block).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::{
+        AccountAddress, BlocksGetBlockHeader, BlocksGetMasterchainInfo, BlocksLookupBlock,
+        RawGetAccountState, TonBlockId,
+    };
+
+    const ELECTOR: &str = "-1:3333333333333333333333333333333333333333333333333333333333333333";
+    const WALLET: &str = "EQBGXZ9ddZeWypx8EkJieHJX75ct0bpkmu0Y4YoYr3NM0Z9e";
+
+    fn workchain_of(request: impl Serialize) -> Option<i32> {
+        workchain(&serde_json::to_value(request).unwrap())
+    }
+
+    #[test]
+    fn workchain_of_address() {
+        let masterchain = RawGetAccountState::new(AccountAddress::new(ELECTOR).unwrap());
+        let basechain = RawGetAccountState::new(AccountAddress::new(WALLET).unwrap());
+
+        assert_eq!(workchain_of(masterchain), None);
+        assert_eq!(workchain_of(basechain), Some(0));
+    }
+
+    #[test]
+    fn workchain_of_block() {
+        let masterchain = BlocksLookupBlock::seqno(TonBlockId::new(-1, i64::MIN, 1));
+        let basechain = BlocksLookupBlock::seqno(TonBlockId::new(0, i64::MIN, 1));
+        let header = BlocksGetBlockHeader::new(TonBlockIdExt::new(
+            0,
+            i64::MIN,
+            1,
+            String::new(),
+            String::new(),
+        ));
+
+        assert_eq!(workchain_of(masterchain), None);
+        assert_eq!(workchain_of(basechain), Some(0));
+        assert_eq!(workchain_of(header), Some(0));
+        assert_eq!(workchain_of(BlocksGetMasterchainInfo::default()), None);
+    }
+
+    #[test]
+    fn disabled_workchain_is_recognized() {
+        let error = anyhow::Error::from(Error::Tower(WorkchainDisabled(0).into()));
+
+        assert!(is_workchain_disabled(&error));
+        assert!(is_workchain_disabled(&WorkchainDisabled(0).into()));
+        assert!(!is_workchain_disabled(&anyhow::anyhow!("other")));
+        assert_eq!(
+            WorkchainDisabled(0).to_string(),
+            "workchain 0 disabled on this endpoint"
+        );
+    }
+}
@@ -0,0 +1,191 @@
+use crate::block::BlocksMasterchainInfo;
+use anyhow::{anyhow, bail};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// Masterchain block a network starts from, tells networks apart
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZeroState {
+    pub root_hash: String,
+    pub file_hash: String,
+}
+
+impl ZeroState {
+    pub fn mainnet() -> Self {
+        Self {
+            root_hash: "F6OpKZKqvqeFp6CQmFomXNMfMj2EnaUSOXN+Mh+wVWk=".to_owned(),
+            file_hash: "XplPz01CXAps5qeSWUtxcyBfdAo5zVb1N979KLSKD24=".to_owned(),
+        }
+    }
+
+    pub fn testnet() -> Self {
+        Self {
+            root_hash: "gj+B8wb/AmlPk1z1AhVI484rhrUpgSr2oSFIh56VoSg=".to_owned(),
+            file_hash: "Z+IKwYS54DmmJmesw/nAD5DzWadnOCMzee+kdgSYDOg=".to_owned(),
+        }
+    }
+
+    /// Zero state of a well-known network by its name
+    pub fn known(network: &str) -> Option<Self> {
+        match network {
+            "mainnet" => Some(Self::mainnet()),
+            "testnet" => Some(Self::testnet()),
+            _ => None,
+        }
+    }
+
+    /// Zero state of the network of [`crate::ton::default_ton_config_url`]
+    #[cfg(not(feature = "testnet"))]
+    pub fn default_network() -> Self {
+        Self::mainnet()
+    }
+
+    /// Zero state of the network of [`crate::ton::default_ton_config_url`]
+    #[cfg(feature = "testnet")]
+    pub fn default_network() -> Self {
+        Self::testnet()
+    }
+
+    /// Zero state `info` starts from
+    pub fn of(info: &BlocksMasterchainInfo) -> Self {
+        Self {
+            root_hash: info.init.root_hash.clone(),
+            file_hash: info.init.file_hash.clone(),
+        }
+    }
+
+    /// Name of a well-known network, the root hash otherwise
+    pub fn tag(&self) -> String {
+        if self == &Self::mainnet() {
+            "mainnet".to_owned()
+        } else if self == &Self::testnet() {
+            "testnet".to_owned()
+        } else {
+            self.root_hash.clone()
+        }
+    }
+
+    /// Fails naming both zero states unless `info` starts from this one
+    pub fn check(&self, info: &BlocksMasterchainInfo) -> anyhow::Result<()> {
+        let init = &info.init;
+        let reported = Self::of(info);
+
+        if init.workchain != -1 || init.seqno != 0 || &reported != self {
+            bail!(
+                "lite servers start from zero state {} of workchain {} seqno {}, expected {}",
+                reported,
+                init.workchain,
+                init.seqno,
+                self
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// `root_hash:file_hash`
+impl Display for ZeroState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.root_hash, self.file_hash)
+    }
+}
+
+impl FromStr for ZeroState {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (root_hash, file_hash) = s
+            .split_once(':')
+            .ok_or_else(|| anyhow!("expected root_hash:file_hash, got {}", s))?;
+
+        Ok(Self {
+            root_hash: root_hash.to_owned(),
+            file_hash: file_hash.to_owned(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn info(root_hash: &str, file_hash: &str) -> BlocksMasterchainInfo {
+        let init = json!({
+            "@type": "ton.blockIdExt",
+            "workchain": -1,
+            "shard": "0",
+            "seqno": 0,
+            "root_hash": root_hash,
+            "file_hash": file_hash,
+        });
+
+        serde_json::from_value(json!({
+            "@type": "blocks.masterchainInfo",
+            "last": init,
+            "state_root_hash": "",
+            "init": init,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn mainnet_matches() {
+        let mainnet = ZeroState::mainnet();
+
+        mainnet
+            .check(&info(&mainnet.root_hash, &mainnet.file_hash))
+            .unwrap();
+    }
+
+    #[test]
+    fn mismatch_names_both_zero_states() {
+        let testnet = ZeroState::testnet();
+
+        let error = ZeroState::mainnet()
+            .check(&info(&testnet.root_hash, &testnet.file_hash))
+            .unwrap_err()
+            .to_string();
+
+        assert!(error.contains(&testnet.to_string()), "{}", error);
+        assert!(
+            error.contains(&ZeroState::mainnet().to_string()),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn file_hash_is_checked() {
+        let mainnet = ZeroState::mainnet();
+
+        assert!(mainnet
+            .check(&info(&mainnet.root_hash, &ZeroState::testnet().file_hash))
+            .is_err());
+    }
+
+    #[test]
+    fn parse() {
+        let mainnet = ZeroState::mainnet();
+
+        assert_eq!(mainnet.to_string().parse::<ZeroState>().unwrap(), mainnet);
+        assert!("F6OpKZKqvqeFp6CQmFomXNMfMj2EnaUSOXN+Mh+wVWk="
+            .parse::<ZeroState>()
+            .is_err());
+        assert_eq!(ZeroState::known("testnet"), Some(ZeroState::testnet()));
+        assert_eq!(ZeroState::known("private"), None);
+    }
+
+    #[test]
+    fn tag() {
+        let private = ZeroState {
+            root_hash: "root".to_owned(),
+            file_hash: "file".to_owned(),
+        };
+
+        assert_eq!(ZeroState::testnet().tag(), "testnet");
+        assert_eq!(private.tag(), "root");
+        assert_eq!(ZeroState::of(&info("root", "file")), private);
+    }
+}
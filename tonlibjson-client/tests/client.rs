@@ -1,11 +1,38 @@
-use futures::StreamExt;
+use futures::{StreamExt, TryStreamExt};
+use serde_json::json;
+use std::path::PathBuf;
+use std::time::Duration;
 use tonlibjson_client::block::{InternalTransactionId, RawTransaction};
-use tonlibjson_client::ton::{TonClient, TonClientBuilder};
+use tonlibjson_client::ton::{
+    AccountStatus, GetMethodCall, TonClient, TonClientBuilder, TonConfig,
+};
+use tonlibjson_client::transport::Backend;
 use tracing::debug;
 use tracing_test::traced_test;
 
+/// Mainnet session of these tests, recorded by running them with `TON_RECORD_FIXTURE` set:
+/// `TON_RECORD_FIXTURE=1 cargo test -p tonlibjson-client --test client -- --ignored --test-threads=1`
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/client.jsonl");
+
+/// Records a mainnet session with `TON_RECORD_FIXTURE` set, replays the recorded one otherwise
 async fn client() -> TonClient {
-    let mut client = TonClientBuilder::default().build().unwrap();
+    let builder = if std::env::var_os("TON_RECORD_FIXTURE").is_some() {
+        TonClientBuilder::default().set_record_fixture(PathBuf::from(FIXTURE))
+    } else {
+        // requests don't depend on the config, the recorded answers are served to any lite server
+        let config = serde_json::from_value::<TonConfig>(json!({
+            "@type": "config.global",
+            "liteservers": [{
+                "id": {"@type": "pub.ed25519", "key": "replay"},
+                "ip": 0,
+                "port": 1,
+            }],
+        }))
+        .unwrap();
+
+        TonClientBuilder::from_config(config).set_backend(Backend::Replay(PathBuf::from(FIXTURE)))
+    };
+    let mut client = builder.build().unwrap();
     client.ready().await.unwrap();
 
     client
@@ -140,3 +167,67 @@ async fn get_block_header_with_hashes() -> anyhow::Result<()> {
     assert_eq!(mc_header.want_split, false);
     Ok(())
 }
+
+#[tokio::test]
+#[traced_test]
+#[ignore]
+async fn run_get_methods_isolates_failures() -> anyhow::Result<()> {
+    let client = client().await;
+    let call = |address: &str| GetMethodCall {
+        address: address.to_owned(),
+        method: "seqno".to_owned(),
+        stack: vec![],
+    };
+
+    let results = client
+        .run_get_methods(vec![
+            call("EQCjk1hh952vWaE9bRguFkAhDAL5jj3xj9p0uPWrFBq_GEMS"),
+            call("EQAAFhjXzKuQ5N0c96nsdZQWATcJm909LYSaCAvWFxVJP80D"),
+        ])
+        .await?;
+
+    // the second address has no contract, its call fails alone
+    assert_eq!(results.len(), 2);
+    assert!(results[0].is_ok());
+    assert!(results[1].is_err());
+    Ok(())
+}
+
+#[tokio::test]
+#[traced_test]
+#[ignore]
+async fn with_block_pins_account_reads() -> anyhow::Result<()> {
+    let client = client().await;
+    let elector = "Ef8zMzMzMzMzMzMzMzMzMzMzMzMzMzMzMzMzMzMzMzMzM0vF";
+
+    let pinned = client.with_last_block().await?;
+    let state = pinned.raw_get_account_state(elector).await?;
+    let result = pinned
+        .run_get_method(elector.to_owned(), "active_election_id".to_owned(), vec![])
+        .await?;
+
+    assert_eq!(&state.block_id, pinned.block_id());
+    assert_eq!(result.exit_code, 0);
+    Ok(())
+}
+
+#[tokio::test]
+#[traced_test]
+#[ignore]
+async fn watch_account_emits_current_state() -> anyhow::Result<()> {
+    let client = client().await;
+
+    let delta = client
+        .watch_account(
+            "EQCjk1hh952vWaE9bRguFkAhDAL5jj3xj9p0uPWrFBq_GEMS",
+            Duration::from_secs(1),
+        )
+        .boxed()
+        .try_next()
+        .await?
+        .unwrap();
+
+    assert_eq!(delta.status, AccountStatus::Active);
+    assert!(delta.last_transaction_id.is_some());
+    Ok(())
+}
@@ -0,0 +1,101 @@
+#![cfg(feature = "failpoints")]
+
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tonlibjson_client::breaker::{is_circuit_open, BreakerPolicy};
+use tonlibjson_client::failpoint::{parse, Failpoints};
+use tonlibjson_client::ton::{TonClient, TonClientBuilder};
+use tracing_test::traced_test;
+
+const ADDRESS: &str = "EQCjk1hh952vWaE9bRguFkAhDAL5jj3xj9p0uPWrFBq_GEMS";
+
+/// failpoints are shared by every client, tests take turns
+static FAILPOINTS: Mutex<()> = Mutex::const_new(());
+
+async fn client(builder: TonClientBuilder) -> TonClient {
+    let mut client = builder.build().unwrap();
+    client.ready().await.unwrap();
+
+    client
+}
+
+fn set_failpoints(spec: &str) {
+    Failpoints::global().set(parse(spec).unwrap());
+}
+
+#[tokio::test]
+#[traced_test]
+#[ignore]
+async fn retries_hide_failing_lite_servers() -> anyhow::Result<()> {
+    let _guard = FAILPOINTS.lock().await;
+    let client = client(TonClientBuilder::default()).await;
+
+    set_failpoints("error=500,method=raw.getAccountState,p=0.2");
+    for _ in 0..10 {
+        client.raw_get_account_state(ADDRESS).await?;
+    }
+    Failpoints::global().clear();
+
+    Ok(())
+}
+
+#[tokio::test]
+#[traced_test]
+#[ignore]
+async fn slow_lite_servers_time_out() -> anyhow::Result<()> {
+    let _guard = FAILPOINTS.lock().await;
+    let client = client(TonClientBuilder::default().set_timeout(Duration::from_secs(1))).await;
+
+    set_failpoints("latency=3000,method=raw.getAccountState");
+    assert!(client.raw_get_account_state(ADDRESS).await.is_err());
+
+    Failpoints::global().clear();
+    client.raw_get_account_state(ADDRESS).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+#[traced_test]
+#[ignore]
+async fn dropped_responses_open_read_breaker() -> anyhow::Result<()> {
+    let _guard = FAILPOINTS.lock().await;
+    let client = client(
+        TonClientBuilder::default()
+            .set_timeout(Duration::from_secs(1))
+            .set_read_breaker(BreakerPolicy {
+                failure_threshold: 2,
+                open_for: Duration::from_secs(60),
+            }),
+    )
+    .await;
+
+    set_failpoints("drop,method=raw.getAccountState");
+    for _ in 0..2 {
+        let error = client.raw_get_account_state(ADDRESS).await.unwrap_err();
+        assert!(!is_circuit_open(&error));
+    }
+
+    let error = client.raw_get_account_state(ADDRESS).await.unwrap_err();
+    assert!(is_circuit_open(&error));
+    Failpoints::global().clear();
+
+    Ok(())
+}
+
+#[tokio::test]
+#[traced_test]
+#[ignore]
+async fn disconnected_lite_servers_fail_until_cleared() -> anyhow::Result<()> {
+    let _guard = FAILPOINTS.lock().await;
+    let client = client(TonClientBuilder::default()).await;
+
+    set_failpoints("disconnect,method=raw.getAccountState");
+    assert!(client.raw_get_account_state(ADDRESS).await.is_err());
+    assert!(client.raw_get_account_state(ADDRESS).await.is_err());
+
+    Failpoints::global().clear();
+    client.raw_get_account_state(ADDRESS).await?;
+
+    Ok(())
+}
@@ -0,0 +1,311 @@
+use futures::TryStreamExt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tonlibjson_client::address::AccountAddressData;
+use tonlibjson_client::fake::{block_id, client_builder, wait_followed, Scenario};
+use tonlibjson_client::replica::{BlockData, Replica};
+use tonlibjson_client::ton::{MessageRef, TonClient, WaitForTransaction};
+use tracing_test::traced_test;
+
+const ADDRESS: &str = "EQCjk1hh952vWaE9bRguFkAhDAL5jj3xj9p0uPWrFBq_GEMS";
+const FIRST_SEQNO: i32 = 900_000;
+const LAST_SEQNO: i32 = 1_000_000;
+
+fn scenario() -> Arc<Scenario> {
+    Arc::new(Scenario::new(FIRST_SEQNO, LAST_SEQNO))
+}
+
+async fn client(scenario: &Arc<Scenario>, lite_servers: usize) -> TonClient {
+    client_with_replica(scenario, lite_servers, None).await
+}
+
+/// Pool of `lite_servers` fakes sharing the scenario, ready once the basechain is followed too
+async fn client_with_replica(
+    scenario: &Arc<Scenario>,
+    lite_servers: usize,
+    replica: Option<Replica>,
+) -> TonClient {
+    let builder = client_builder(scenario, lite_servers);
+    let builder = match replica {
+        Some(replica) => builder.set_replica(replica),
+        None => builder,
+    };
+    let mut client = builder.build().unwrap();
+    client.ready().await.unwrap();
+    wait_followed(&client, scenario).await.unwrap();
+
+    client
+}
+
+#[tokio::test]
+#[traced_test]
+async fn syncs_to_the_last_block() -> anyhow::Result<()> {
+    let client = client(&scenario(), 2).await;
+
+    let info = client.get_masterchain_info().await?;
+
+    assert_eq!(info.last, block_id(-1, LAST_SEQNO));
+    Ok(())
+}
+
+#[tokio::test]
+#[traced_test]
+async fn follows_new_blocks() -> anyhow::Result<()> {
+    let scenario = scenario();
+    let client = client(&scenario, 2).await;
+
+    scenario.advance(3);
+    let last = tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            let info = client.get_masterchain_info().await.unwrap();
+            if info.last.seqno == LAST_SEQNO + 3 {
+                return info.last;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    })
+    .await?;
+
+    assert_eq!(last, block_id(-1, LAST_SEQNO + 3));
+    let header = client
+        .get_block_header(-1, last.shard, last.seqno, None)
+        .await?;
+    assert_eq!(header.prev_blocks, vec![block_id(-1, LAST_SEQNO + 2)]);
+    Ok(())
+}
+
+#[tokio::test]
+#[traced_test]
+async fn out_msg_queue_sizes_are_cached_by_their_block() -> anyhow::Result<()> {
+    let scenario = scenario();
+    let client = client(&scenario, 2).await;
+
+    // lite servers answer at their last block, which the client may not have followed yet
+    scenario.advance(1);
+    let sizes = client.get_out_msg_queue_sizes().await?;
+    assert_eq!(sizes.shards[0].id, block_id(-1, LAST_SEQNO + 1));
+
+    tokio::time::timeout(Duration::from_secs(10), async {
+        while client.get_masterchain_info().await.unwrap().last.seqno != LAST_SEQNO + 1 {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    })
+    .await?;
+
+    // the block is the one the cached sizes were taken at, so lite servers aren't asked again
+    scenario.fail(
+        "blocks.getOutMsgQueueSizes",
+        10,
+        500,
+        "LITE_SERVER_NETWORK: timeout",
+    );
+    let cached = client.get_out_msg_queue_sizes().await?;
+    assert_eq!(cached.shards[0].id, block_id(-1, LAST_SEQNO + 1));
+    Ok(())
+}
+
+#[tokio::test]
+#[traced_test]
+async fn pages_account_transactions() -> anyhow::Result<()> {
+    let scenario = scenario();
+    let txs = (0..40)
+        .map(|i| scenario.add_transaction(ADDRESS, LAST_SEQNO - 40 + i / 2))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let client = client(&scenario, 2).await;
+
+    let state = client.raw_get_account_state(ADDRESS).await?;
+    assert_eq!(state.last_transaction_id.as_ref(), txs.last());
+
+    let listed: Vec<_> = client
+        .get_account_tx_stream(ADDRESS)
+        .map_ok(|tx| tx.transaction_id)
+        .try_collect()
+        .await?;
+    assert_eq!(listed, txs.into_iter().rev().collect::<Vec<_>>());
+    Ok(())
+}
+
+#[tokio::test]
+#[traced_test]
+async fn lists_block_transactions_from_both_ends() -> anyhow::Result<()> {
+    let scenario = scenario();
+    let seqno = LAST_SEQNO - 1;
+    for account in 1..=5 {
+        for _ in 0..10 {
+            scenario.add_transaction(&format!("0:{:064x}", account), seqno)?;
+        }
+    }
+    let client = client(&scenario, 2).await;
+
+    let listing = client.get_block_tx_ids_checked(&block_id(0, seqno)).await?;
+
+    assert_eq!(listing.warning, None);
+    assert_eq!(listing.transactions.len(), 50);
+    assert!(listing.transactions.iter().all(|tx| tx.workchain == 0));
+    Ok(())
+}
+
+#[tokio::test]
+#[traced_test]
+async fn sends_boc() -> anyhow::Result<()> {
+    let scenario = scenario();
+    let client = client(&scenario, 2).await;
+
+    let hash = client
+        .send_message_returning_hash("te6cckEBAQEAAgAAAEysuc0=")
+        .await?;
+
+    assert!(!hash.is_empty());
+    assert_eq!(scenario.sent(), vec!["te6cckEBAQEAAgAAAEysuc0=".to_owned()]);
+    Ok(())
+}
+
+#[tokio::test]
+#[traced_test]
+async fn injected_errors() -> anyhow::Result<()> {
+    let scenario = scenario();
+    scenario.set_balance(ADDRESS, 42)?;
+    let client = client(&scenario, 2).await;
+
+    // reads are retried
+    scenario.fail(
+        "raw.getAccountState",
+        2,
+        500,
+        "LITE_SERVER_NETWORK: timeout",
+    );
+    let state = client.raw_get_account_state(ADDRESS).await?;
+    assert_eq!(state.balance, Some(42));
+
+    // sends aren't
+    scenario.fail("raw.sendMessage", 1, 500, "LITE_SERVER_NETWORK: timeout");
+    assert!(client
+        .send_message("te6cckEBAQEAAgAAAEysuc0=")
+        .await
+        .is_err());
+    assert!(scenario.sent().is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+#[traced_test]
+async fn replica_reads_block_data_from_upstream() -> anyhow::Result<()> {
+    let seqno = LAST_SEQNO - 1;
+    let upstream = scenario();
+    for account in 1..=3 {
+        upstream.add_transaction(&format!("0:{:064x}", account), seqno)?;
+    }
+    let local = scenario();
+    local.set_balance(ADDRESS, 42)?;
+    let upstream = client(&upstream, 1).await;
+    let replica = Replica::new(Arc::new(upstream), vec![BlockData::Transactions]);
+    let client = client_with_replica(&local, 1, Some(replica)).await;
+
+    let listing = client.get_block_tx_ids_checked(&block_id(0, seqno)).await?;
+    assert_eq!(listing.transactions.len(), 3);
+
+    let state = client.raw_get_account_state(ADDRESS).await?;
+    assert_eq!(state.balance, Some(42));
+    Ok(())
+}
+
+#[tokio::test]
+#[traced_test]
+async fn reads_report_their_blocks() -> anyhow::Result<()> {
+    let scenario = scenario();
+    scenario.set_balance(ADDRESS, 42)?;
+    let client = client(&scenario, 1).await;
+
+    let state = client.raw_get_account_state(ADDRESS).await?;
+    let account = AccountAddressData::from_str(ADDRESS)?;
+    let context = client
+        .read_context(state.block_id.clone(), &account)
+        .await?;
+
+    assert_eq!(context.masterchain, state.block_id);
+    assert_eq!(context.shard, Some(block_id(0, state.block_id.seqno)));
+    Ok(())
+}
+
+#[tokio::test]
+#[traced_test]
+async fn waits_for_transaction_of_message() -> anyhow::Result<()> {
+    let scenario = scenario();
+    let first = scenario.add_transaction(ADDRESS, LAST_SEQNO)?;
+    let client = client(&scenario, 1).await;
+    scenario.add_transaction_with_message(ADDRESS, LAST_SEQNO, "other")?;
+    let expected = scenario.add_transaction_with_message(ADDRESS, LAST_SEQNO, "message")?;
+    scenario.add_transaction(ADDRESS, LAST_SEQNO)?;
+
+    let message = MessageRef::Hash("message".to_owned());
+    let found = client
+        .wait_for_transaction(ADDRESS, &message, Some(first.lt), Duration::from_secs(10))
+        .await?;
+
+    let WaitForTransaction::Found {
+        transaction,
+        bounced,
+    } = found
+    else {
+        panic!("expected a transaction, got {:?}", found);
+    };
+    assert_eq!(transaction.transaction_id.lt, expected.lt);
+    assert!(!bounced);
+    Ok(())
+}
+
+#[tokio::test]
+#[traced_test]
+async fn wait_for_transaction_times_out() -> anyhow::Result<()> {
+    let scenario = scenario();
+    let first = scenario.add_transaction_with_message(ADDRESS, LAST_SEQNO, "other")?;
+    let client = client(&scenario, 1).await;
+
+    let message = MessageRef::Hash("message".to_owned());
+    let waited = client
+        .wait_for_transaction(ADDRESS, &message, None, Duration::from_millis(100))
+        .await?;
+
+    assert!(matches!(
+        waited,
+        WaitForTransaction::Timeout { last_checked_lt } if last_checked_lt == first.lt
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+#[traced_test]
+async fn wait_for_transaction_times_out_reading_account() -> anyhow::Result<()> {
+    let scenario = scenario();
+    scenario.add_transaction_with_message(ADDRESS, LAST_SEQNO, "other")?;
+    let client = client(&scenario, 1).await;
+
+    let message = MessageRef::Hash("message".to_owned());
+    let waited = client
+        .wait_for_transaction(ADDRESS, &message, None, Duration::ZERO)
+        .await?;
+
+    assert!(matches!(
+        waited,
+        WaitForTransaction::Timeout { last_checked_lt: 0 }
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+#[traced_test]
+async fn shrinks_caches() -> anyhow::Result<()> {
+    let client = client(&scenario(), 1).await;
+    assert_eq!(client.cache_usage(), 0);
+
+    client
+        .get_block_header_by_id(&block_id(-1, LAST_SEQNO))
+        .await?;
+    let usage = client.cache_usage();
+    assert!(usage > 0);
+
+    assert_eq!(client.shrink_caches(1), usage);
+    assert_eq!(client.cache_usage(), 0);
+    Ok(())
+}
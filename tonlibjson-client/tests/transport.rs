@@ -0,0 +1,159 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use tonlibjson_client::block::{InternalTransactionId, TonBlockIdExt};
+use tonlibjson_client::ton::TonClientBuilder;
+use tonlibjson_client::transport::{Backend, LiteServerTransport};
+use tracing_test::traced_test;
+
+fn transport(backend: Backend) -> Arc<dyn LiteServerTransport> {
+    TonClientBuilder::default()
+        .set_backend(backend)
+        .build_transport()
+        .unwrap()
+}
+
+async fn masterchain_block_matches(transport: Arc<dyn LiteServerTransport>) -> anyhow::Result<()> {
+    let info = transport.get_masterchain_info().await?;
+    let block = transport
+        .look_up_block_by_seqno(info.last.workchain, info.last.shard, info.last.seqno)
+        .await?;
+    assert_eq!(block, info.last);
+
+    let header = transport
+        .get_block_header(block.workchain, block.shard, block.seqno, None)
+        .await?;
+    assert_eq!(header.id, block);
+    assert!(!header.prev_blocks.is_empty());
+    assert!(header.start_lt < header.end_lt);
+    assert!(header.gen_utime > 0);
+
+    let txs = transport
+        .blocks_get_transactions(&block, None, false, 16)
+        .await?;
+    assert_eq!(txs.id, block);
+    Ok(())
+}
+
+async fn account_transactions_match(transport: Arc<dyn LiteServerTransport>) -> anyhow::Result<()> {
+    let address = "EQCjk1hh952vWaE9bRguFkAhDAL5jj3xj9p0uPWrFBq_GEMS";
+    let state = transport.raw_get_account_state(address).await?;
+    let tx = InternalTransactionId {
+        hash: "752Szayka+Eh54Zvco5l84d6WL+zJFmyh1wqRxD08Uo=".to_owned(),
+        lt: 33756943000007,
+    };
+
+    let txs = transport.raw_get_transactions(address, &tx).await?;
+    assert_eq!(txs.transactions[0].transaction_id, tx);
+    assert!(state.last_transaction_id.is_some());
+    Ok(())
+}
+
+#[tokio::test]
+#[traced_test]
+#[ignore]
+async fn tonlibjson_masterchain_block() -> anyhow::Result<()> {
+    masterchain_block_matches(transport(Backend::Tonlibjson)).await
+}
+
+#[tokio::test]
+#[traced_test]
+#[ignore]
+async fn tonlibjson_account_transactions() -> anyhow::Result<()> {
+    account_transactions_match(transport(Backend::Tonlibjson)).await
+}
+
+#[cfg(feature = "liteserver")]
+#[tokio::test]
+#[traced_test]
+#[ignore]
+async fn liteserver_masterchain_block() -> anyhow::Result<()> {
+    masterchain_block_matches(transport(Backend::LiteServer)).await
+}
+
+#[cfg(feature = "liteserver")]
+#[tokio::test]
+#[traced_test]
+#[ignore]
+async fn liteserver_account_transactions() -> anyhow::Result<()> {
+    account_transactions_match(transport(Backend::LiteServer)).await
+}
+
+#[cfg(feature = "liteserver")]
+#[tokio::test]
+#[traced_test]
+#[ignore]
+async fn liteserver_block_data() -> anyhow::Result<()> {
+    let transport = transport(Backend::LiteServer);
+    let block = transport.get_masterchain_info().await?.last;
+
+    let data = transport.get_block_data(&block).await?;
+    assert!(!data.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn tonlibjson_block_data_unsupported() {
+    let error = TonClientBuilder::default()
+        .build()
+        .unwrap()
+        .get_block_data(&TonBlockIdExt::new(
+            -1,
+            i64::MIN,
+            1,
+            String::new(),
+            String::new(),
+        ))
+        .await
+        .unwrap_err();
+
+    assert_eq!(error.to_string(), "block data is not exposed by tonlibjson");
+}
+
+fn replay() -> Arc<dyn LiteServerTransport> {
+    transport(Backend::Replay(PathBuf::from(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/replay.jsonl"
+    ))))
+}
+
+#[tokio::test]
+async fn replay_masterchain_block() -> anyhow::Result<()> {
+    masterchain_block_matches(replay()).await
+}
+
+#[tokio::test]
+async fn replay_account_state() -> anyhow::Result<()> {
+    let state = replay()
+        .raw_get_account_state("EQCjk1hh952vWaE9bRguFkAhDAL5jj3xj9p0uPWrFBq_GEMS")
+        .await?;
+
+    assert_eq!(state.balance, Some(1234567890));
+    assert_eq!(
+        state.last_transaction_id,
+        Some(InternalTransactionId {
+            hash: "752Szayka+Eh54Zvco5l84d6WL+zJFmyh1wqRxD08Uo=".to_owned(),
+            lt: 33756943000007,
+        })
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn replay_tonlib_error() {
+    let error = replay()
+        .look_up_block_by_seqno(-1, -9223372036854775808, 99999999)
+        .await
+        .unwrap_err();
+
+    assert!(error.to_string().contains("LITE_SERVER_NOTREADY"));
+}
+
+#[tokio::test]
+async fn replay_unknown_request() {
+    let error = replay()
+        .raw_get_account_state("EQBO_mAVkaHxt6Ibz7wqIJ_UIDmxZBFcgkk7fvIzkh7l42wO")
+        .await
+        .unwrap_err();
+
+    assert!(error.to_string().starts_with("no fixture for"));
+}